@@ -12,19 +12,35 @@
  *    limitations under the License.
  */
 
-use crate::config::{EngineConfig, LogFormat};
+use crate::config::{EngineConfig, LogFormat, LoggingConfig};
+use crate::dead_letter::read_dead_letters;
 use crate::engine::Engine;
-use clap::{Arg, Command};
+use clap::{Arg, ArgMatches, Command};
+use std::path::Path;
 use std::process;
+use std::sync::OnceLock;
 use tracing::{info, Level};
+use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::fmt;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 
 pub struct Cli {
     pub config: Option<EngineConfig>,
+    pub config_path: Option<String>,
+    /// Set once logging is initialized. Flushes the log file when dropped, must live until
+    /// the process exits
+    log_guard: OnceLock<Option<WorkerGuard>>,
+    /// Dead letter file to replay and the index of the stream to replay it into
+    replay: Option<(String, usize)>,
 }
 impl Default for Cli {
     fn default() -> Self {
-        Self { config: None }
+        Self {
+            config: None,
+            config_path: None,
+            log_guard: OnceLock::new(),
+            replay: None,
+        }
     }
 }
 
@@ -49,19 +65,55 @@ impl Cli {
                     .help("Only the profile is verified, not the engine is started.")
                     .action(clap::ArgAction::SetTrue),
             )
+            .arg(
+                Arg::new("log-level")
+                    .long("log-level")
+                    .value_name("LEVEL")
+                    .help("Log level, overrides logging.level of the profile.")
+                    .value_parser(["trace", "debug", "info", "warn", "error"]),
+            )
+            .arg(
+                Arg::new("log-format")
+                    .long("log-format")
+                    .value_name("FORMAT")
+                    .help("Log format, overrides logging.format of the profile.")
+                    .value_parser(["text", "json"]),
+            )
+            .arg(
+                Arg::new("log-file")
+                    .long("log-file")
+                    .value_name("FILE")
+                    .help("Log file, rotated hourly. Overrides logging.file_path of the profile."),
+            )
+            .arg(
+                Arg::new("replay")
+                    .long("replay")
+                    .value_name("FILE")
+                    .help("Replay the dead letter messages of the file through a stream's pipeline, then exit."),
+            )
+            .arg(
+                Arg::new("stream")
+                    .long("stream")
+                    .value_name("INDEX")
+                    .help("Index of the stream to replay dead letter messages into.")
+                    .value_parser(clap::value_parser!(usize))
+                    .default_value("0")
+                    .requires("replay"),
+            )
             .get_matches();
 
         // Get the profile path
         let config_path = matches.get_one::<String>("config").unwrap();
 
         // Get the profile path
-        let config = match EngineConfig::from_file(config_path) {
+        let mut config = match EngineConfig::from_file(config_path) {
             Ok(config) => config,
             Err(e) => {
                 println!("Failed to load configuration file: {}", e);
                 process::exit(1);
             }
         };
+        apply_log_args(&mut config.logging, &matches);
 
         // If you just verify the configuration, exit it
         if matches.get_flag("validate") {
@@ -69,19 +121,70 @@ impl Cli {
             return Ok(());
         }
         self.config = Some(config);
+        self.config_path = Some(config_path.clone());
+        if let Some(file) = matches.get_one::<String>("replay") {
+            let index = *matches.get_one::<usize>("stream").unwrap();
+            self.replay = Some((file.clone(), index));
+        }
+        Ok(())
+    }
+
+    /// Initialize the logging system, if it was not initialized yet. Call it before the
+    /// engine is built so that its startup is logged; [`Cli::run`] calls it otherwise.
+    pub fn init_logging(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.log_guard.get().is_some() {
+            return Ok(());
+        }
+        if let Some(config) = &self.config {
+            let _ = self.log_guard.set(init_logging(&config.logging)?);
+        }
         Ok(())
     }
+
     pub async fn run(&self) -> Result<(), Box<dyn std::error::Error>> {
-        // Initialize the logging system
+        self.init_logging()?;
         let config = self.config.clone().unwrap();
-        init_logging(&config);
-        let engine = Engine::new(config);
+        if let Some((file, index)) = &self.replay {
+            let stream_config = config
+                .streams
+                .get(*index)
+                .ok_or_else(|| format!("Stream #{} does not exist", index))?;
+            let envelopes = read_dead_letters(Path::new(file))?;
+            info!(
+                "Replaying {} dead letter messages from {}",
+                envelopes.len(),
+                file
+            );
+            stream_config.build()?.replay(envelopes).await?;
+            return Ok(());
+        }
+        let mut engine = Engine::new(config);
+        if let Some(config_path) = &self.config_path {
+            engine = engine.with_config_path(config_path);
+        }
         engine.run().await?;
         Ok(())
     }
 }
-fn init_logging(config: &EngineConfig) -> () {
-    let log_level = match config.logging.level.as_str() {
+
+/// Override the logging configuration with the command line flags
+fn apply_log_args(logging: &mut LoggingConfig, matches: &ArgMatches) {
+    if let Some(level) = matches.get_one::<String>("log-level") {
+        logging.level = level.clone();
+    }
+    if let Some(format) = matches.get_one::<String>("log-format") {
+        logging.format = match format.as_str() {
+            "json" => LogFormat::JSON,
+            _ => LogFormat::PLAIN,
+        };
+    }
+    if let Some(file) = matches.get_one::<String>("log-file") {
+        logging.file_path = Some(file.clone());
+    }
+}
+
+fn init_logging(config: &LoggingConfig) -> Result<Option<WorkerGuard>, Box<dyn std::error::Error>> {
+    let log_level = match config.level.as_str() {
         "trace" => Level::TRACE,
         "debug" => Level::DEBUG,
         "info" => Level::INFO,
@@ -90,62 +193,44 @@ fn init_logging(config: &EngineConfig) -> () {
         _ => Level::INFO,
     };
 
-    let subscriber_builder = fmt::Subscriber::builder().with_max_level(log_level);
-
     // Check if we need to output logs to a file
-    if let Some(file_path) = &config.logging.file_path {
-        // Create the file and parent directories if they don't exist
-        if let Some(parent) = std::path::Path::new(file_path).parent() {
-            std::fs::create_dir_all(parent).ok();
-        }
-
-        // Open the file for writing
-        match std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(file_path)
-        {
-            Ok(file) => {
-                match config.logging.format {
-                    LogFormat::JSON => {
-                        let subscriber = subscriber_builder
-                            .with_writer(std::sync::Mutex::new(file))
-                            .pretty()
-                            .json()
-                            .finish();
-                        tracing::subscriber::set_global_default(subscriber)
-                            .expect("You can't set a global default log subscriber");
-                    }
-                    LogFormat::PLAIN => {
-                        let subscriber = subscriber_builder
-                            .with_writer(std::sync::Mutex::new(file))
-                            .pretty()
-                            .finish();
-                        tracing::subscriber::set_global_default(subscriber)
-                            .expect("You can't set a global default log subscriber");
-                    }
-                }
+    let (writer, guard) = match &config.file_path {
+        Some(file_path) => {
+            let path = Path::new(file_path);
+            let directory = path
+                .parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            let file_name = path
+                .file_name()
+                .ok_or_else(|| format!("Invalid log file path: {}", file_path))?;
+            std::fs::create_dir_all(directory)?;
 
-                info!("Logging to file: {}", file_path);
-                return;
-            }
-            Err(e) => {
-                eprintln!("Failed to open log file {}: {}", file_path, e);
-                // Fall back to console logging
-            }
+            // The appender writes to `<file_path>.<yyyy-MM-dd-HH>` and starts a new file every hour.
+            // Writes happen on a background thread that is flushed when the guard is dropped.
+            let appender = tracing_appender::rolling::hourly(directory, file_name);
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            (BoxMakeWriter::new(writer), Some(guard))
         }
-    }
+        None => (BoxMakeWriter::new(std::io::stdout), None),
+    };
 
-    match config.logging.format {
+    let subscriber_builder = fmt::Subscriber::builder()
+        .with_max_level(log_level)
+        .with_writer(writer);
+    match config.format {
         LogFormat::JSON => {
-            let subscriber = subscriber_builder.pretty().json().finish();
-            tracing::subscriber::set_global_default(subscriber)
-                .expect("You can't set a global default log subscriber");
+            let subscriber = subscriber_builder.json().finish();
+            tracing::subscriber::set_global_default(subscriber)?;
         }
         LogFormat::PLAIN => {
             let subscriber = subscriber_builder.pretty().finish();
-            tracing::subscriber::set_global_default(subscriber)
-                .expect("You can't set a global default log subscriber");
+            tracing::subscriber::set_global_default(subscriber)?;
         }
     }
+
+    if let Some(file_path) = &config.file_path {
+        info!("Logging to file: {}", file_path);
+    }
+    Ok(guard)
 }
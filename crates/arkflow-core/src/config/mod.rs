@@ -0,0 +1,629 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Configuration module
+//!
+//! Provide configuration management for the stream processing engine.
+
+use serde::de::{self, DeserializeOwned};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use toml;
+
+use crate::engine::schema_reflection::SchemaReflectionConfig;
+use crate::{stream::StreamConfig, Error};
+
+pub mod schema_registry;
+
+/// Configuration file format
+#[derive(Debug, Clone, Copy)]
+pub enum ConfigFormat {
+    YAML,
+    JSON,
+    TOML,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    JSON,
+    #[serde(alias = "text")]
+    PLAIN,
+}
+
+/// Log configuration
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    /// Log level
+    pub level: String,
+    /// Output to file?
+    /// Log file path, rotated hourly
+    #[serde(alias = "file")]
+    pub file_path: Option<String>,
+    /// Log format (text or json)
+    #[serde(default = "default_log_format")]
+    pub format: LogFormat,
+}
+
+/// Health check configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthCheckConfig {
+    /// Whether health check is enabled
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Listening address for health check server
+    #[serde(default = "default_address")]
+    pub address: String,
+    /// Path for health check endpoint
+    #[serde(default = "default_health_path")]
+    pub health_path: String,
+    /// Path for readiness check endpoint
+    #[serde(default = "default_readiness_path")]
+    pub readiness_path: String,
+    /// Path for liveness check endpoint
+    #[serde(default = "default_liveness_path")]
+    pub liveness_path: String,
+}
+
+/// Engine configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EngineConfig {
+    /// Streams configuration
+    pub streams: Vec<StreamConfig>,
+    /// Logging configuration (optional)
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    /// Health check configuration (optional)
+    #[serde(default)]
+    pub health_check: HealthCheckConfig,
+    /// Schema reflection server configuration (optional)
+    #[serde(default)]
+    pub schema_reflection: Option<SchemaReflectionConfig>,
+}
+
+impl EngineConfig {
+    /// Load configuration from file
+    pub fn from_file(path: &str) -> Result<Self, Error> {
+        let config: Self = read_config(Path::new(path))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check the constraints spanning several streams
+    pub fn validate(&self) -> Result<(), Error> {
+        let limited = self
+            .streams
+            .iter()
+            .filter(|stream| stream.resource_limits.is_some())
+            .count();
+        if limited > 1 {
+            return Err(Error::Config(format!(
+                "resource_limits apply to the whole process and can only be set on one stream, \
+                 found {}",
+                limited
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl ConfigFormat {
+    /// Get configuration format from the file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "yaml" | "yml" => Some(ConfigFormat::YAML),
+            "json" => Some(ConfigFormat::JSON),
+            "toml" => Some(ConfigFormat::TOML),
+            _ => None,
+        }
+    }
+
+    /// Deserialize `content` in this format
+    pub fn parse<T: DeserializeOwned>(self, content: &str) -> Result<T, Error> {
+        match self {
+            ConfigFormat::YAML => serde_yaml::from_str(content)
+                .map_err(|e| Error::Config(format!("YAML parsing error: {}", e))),
+            ConfigFormat::JSON => serde_json::from_str(content)
+                .map_err(|e| Error::Config(format!("JSON parsing error: {}", e))),
+            ConfigFormat::TOML => toml::from_str(content)
+                .map_err(|e| Error::Config(format!("TOML parsing error: {}", e))),
+        }
+    }
+}
+
+/// Load the configuration of a single stream from a YAML, JSON or TOML file
+pub fn load_config(path: &Path) -> Result<StreamConfig, Error> {
+    read_config(path)
+}
+
+/// Read a configuration file, substituting `${ENV_VAR}` references in its string values
+fn read_config<T: DeserializeOwned>(path: &Path) -> Result<T, Error> {
+    let format = ConfigFormat::from_path(path).ok_or_else(|| {
+        Error::Config("The configuration file format cannot be determined. Please use YAML, JSON, or TOML format.".to_string())
+    })?;
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| Error::Config(format!("Unable to read configuration file: {}", e)))?;
+
+    let value = substitute_env_vars(format.parse(&content)?)?;
+    // Deserialize through YAML, which accepts `null` for empty lists, e.g. `processors:`
+    serde_yaml::to_value(value)
+        .and_then(serde_yaml::from_value)
+        .map_err(|e| Error::Config(format!("Invalid configuration: {}", e)))
+}
+
+/// Substitute environment variable references in all string values of `value`.
+///
+/// Substituted values stay strings, so that e.g. a password `007` is not read as the number 7;
+/// numeric fields that take references use [`deserialize_number`].
+fn substitute_env_vars(value: serde_json::Value) -> Result<serde_json::Value, Error> {
+    use serde_json::Value;
+
+    Ok(match value {
+        Value::String(s) => Value::String(expand_env_vars(&s)?),
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(substitute_env_vars)
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| Ok((key, substitute_env_vars(value)?)))
+                .collect::<Result<_, Error>>()?,
+        ),
+        value => value,
+    })
+}
+
+/// Replace `${ENV_VAR}` references in `content` with the value of the environment variable.
+///
+/// `${ENV_VAR:-default}` falls back to `default` when the variable is unset or empty.
+fn expand_env_vars(content: &str) -> Result<String, Error> {
+    let mut result = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let reference = after
+            .find('}')
+            .map(|end| &after[..end])
+            .map(|reference| match reference.split_once(":-") {
+                Some((name, default)) => (reference, name, Some(default)),
+                None => (reference, reference, None),
+            })
+            .filter(|(_, name, _)| is_env_var_name(name));
+        match reference {
+            Some((reference, name, default)) => {
+                let value = match (std::env::var(name), default) {
+                    (Ok(value), Some(default)) if value.is_empty() => default.to_string(),
+                    (Ok(value), _) => value,
+                    (Err(_), Some(default)) => default.to_string(),
+                    (Err(_), None) => {
+                        return Err(Error::Config(format!(
+                            "Environment variable {} not found",
+                            name
+                        )))
+                    }
+                };
+                result.push_str(&value);
+                rest = &after[reference.len() + 1..];
+            }
+            None => {
+                result.push_str("${");
+                rest = after;
+            }
+        }
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+fn is_env_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Deserialize a number that may also be given as a string, such as a substituted
+/// `${ENV_VAR}` reference, e.g. `thread_num: "${THREADS}"`.
+pub fn deserialize_number<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: fmt::Display,
+{
+    NumberOrString::deserialize(deserializer)?.parse()
+}
+
+/// [`deserialize_number`] for optional fields, which also need `#[serde(default)]`
+pub fn deserialize_optional_number<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: fmt::Display,
+{
+    Option::<NumberOrString<T>>::deserialize(deserializer)?
+        .map(NumberOrString::parse)
+        .transpose()
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrString<T> {
+    Number(T),
+    String(String),
+}
+
+impl<T> NumberOrString<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    fn parse<E: de::Error>(self) -> Result<T, E> {
+        match self {
+            NumberOrString::Number(number) => Ok(number),
+            NumberOrString::String(s) => s
+                .trim()
+                .parse()
+                .map_err(|e| E::custom(format!("invalid number {:?}: {}", s, e))),
+        }
+    }
+}
+
+/// Serialize a typed component configuration into the untyped form passed to the builders.
+///
+/// Unit values mean an empty configuration, as for a component with only a `type` in a
+/// configuration file; anything else must serialize to a map.
+pub(crate) fn component_config<T: Serialize>(
+    config: &T,
+) -> Result<Option<serde_json::Value>, Error> {
+    match serde_json::to_value(config)? {
+        serde_json::Value::Null => Ok(Some(serde_json::Value::Object(Default::default()))),
+        value @ serde_json::Value::Object(_) => Ok(Some(value)),
+        value => Err(Error::Config(format!(
+            "Component configuration must be a map, got {}",
+            value
+        ))),
+    }
+}
+
+/// Default address for health check server
+fn default_address() -> String {
+    "0.0.0.0:8080".to_string()
+}
+
+/// Default value for health check path
+fn default_health_path() -> String {
+    "/health".to_string()
+}
+
+/// Default value for readiness path
+fn default_readiness_path() -> String {
+    "/readiness".to_string()
+}
+
+/// Default value for liveness path
+fn default_liveness_path() -> String {
+    "/liveness".to_string()
+}
+/// Default value for health check enabled
+fn default_enabled() -> bool {
+    true
+}
+
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            address: default_address(),
+            health_path: default_health_path(),
+            readiness_path: default_readiness_path(),
+            liveness_path: default_liveness_path(),
+        }
+    }
+}
+
+/// Default value for log format
+fn default_log_format() -> LogFormat {
+    LogFormat::PLAIN
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            file_path: None,
+            format: default_log_format(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn examples() -> Vec<PathBuf> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../examples");
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| matches!(ConfigFormat::from_path(path), Some(ConfigFormat::YAML)))
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn test_expand_env_vars() {
+        std::env::set_var("ARKFLOW_TEST_BROKER", "localhost:9092");
+        assert_eq!(
+            expand_env_vars("brokers: [\"${ARKFLOW_TEST_BROKER}\"]").unwrap(),
+            "brokers: [\"localhost:9092\"]"
+        );
+        assert_eq!(
+            expand_env_vars("$ARKFLOW_TEST_BROKER ${not a var} ${").unwrap(),
+            "$ARKFLOW_TEST_BROKER ${not a var} ${"
+        );
+        let err = expand_env_vars("${ARKFLOW_TEST_MISSING}").unwrap_err();
+        assert!(err.to_string().contains("ARKFLOW_TEST_MISSING"), "{}", err);
+    }
+
+    #[test]
+    fn test_expand_env_vars_default() {
+        std::env::set_var("ARKFLOW_TEST_TOPIC", "orders");
+        std::env::set_var("ARKFLOW_TEST_EMPTY", "");
+        assert_eq!(
+            expand_env_vars("${ARKFLOW_TEST_TOPIC:-events}").unwrap(),
+            "orders"
+        );
+        assert_eq!(
+            expand_env_vars("${ARKFLOW_TEST_UNSET:-events}").unwrap(),
+            "events"
+        );
+        assert_eq!(
+            expand_env_vars("${ARKFLOW_TEST_EMPTY:-events}").unwrap(),
+            "events"
+        );
+        assert_eq!(
+            expand_env_vars("http://${ARKFLOW_TEST_UNSET:-localhost:8080}/${ARKFLOW_TEST_UNSET:-}")
+                .unwrap(),
+            "http://localhost:8080/"
+        );
+    }
+
+    #[test]
+    fn test_substitute_env_vars() {
+        std::env::set_var("ARKFLOW_TEST_PASSWORD", "secret: \"quoted\"");
+        let value = serde_json::json!({
+            "name": "${ARKFLOW_TEST_UNSET:-default}",
+            "count": 3,
+            "enabled": true,
+            "auth": { "password": "${ARKFLOW_TEST_PASSWORD}" },
+            "brokers": ["${ARKFLOW_TEST_UNSET:-a:9092}", "b:9092"],
+        });
+        assert_eq!(
+            substitute_env_vars(value).unwrap(),
+            serde_json::json!({
+                "name": "default",
+                "count": 3,
+                "enabled": true,
+                "auth": { "password": "secret: \"quoted\"" },
+                "brokers": ["a:9092", "b:9092"],
+            })
+        );
+
+        let err = substitute_env_vars(serde_json::json!({ "list": ["${ARKFLOW_TEST_MISSING}"] }))
+            .unwrap_err();
+        assert!(err.to_string().contains("ARKFLOW_TEST_MISSING"), "{}", err);
+    }
+
+    #[test]
+    fn test_substitute_env_vars_keeps_strings() {
+        std::env::set_var("ARKFLOW_TEST_PIN", "007");
+        std::env::set_var("ARKFLOW_TEST_TOKEN", "123456");
+        std::env::set_var("ARKFLOW_TEST_PORT", "9092");
+        let value = serde_json::json!({
+            "pin": "${ARKFLOW_TEST_PIN}",
+            "token": "${ARKFLOW_TEST_TOKEN}",
+            "enabled": "${ARKFLOW_TEST_UNSET:-true}",
+            "address": "localhost:${ARKFLOW_TEST_PORT}",
+        });
+        assert_eq!(
+            substitute_env_vars(value).unwrap(),
+            serde_json::json!({
+                "pin": "007",
+                "token": "123456",
+                "enabled": "true",
+                "address": "localhost:9092",
+            })
+        );
+    }
+
+    #[test]
+    fn test_deserialize_number() {
+        #[derive(Debug, Deserialize)]
+        struct Numbers {
+            #[serde(deserialize_with = "deserialize_number")]
+            threads: u32,
+            #[serde(default, deserialize_with = "deserialize_optional_number")]
+            batch_size: Option<usize>,
+            #[serde(default, deserialize_with = "deserialize_optional_number")]
+            count: Option<usize>,
+        }
+
+        let numbers: Numbers =
+            serde_json::from_value(serde_json::json!({ "threads": 3, "batch_size": " 100 " }))
+                .unwrap();
+        assert_eq!(numbers.threads, 3);
+        assert_eq!(numbers.batch_size, Some(100));
+        assert_eq!(numbers.count, None);
+
+        let err = serde_json::from_value::<Numbers>(serde_json::json!({ "threads": "three" }))
+            .unwrap_err();
+        assert!(err.to_string().contains("three"), "{}", err);
+
+        std::env::set_var("ARKFLOW_TEST_THREADS", "3");
+        let dir =
+            std::env::temp_dir().join(format!("arkflow-config-numbers-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("stream.yaml");
+        std::fs::write(
+            &path,
+            r#"
+input:
+  type: generate
+  context: '{ "value": 10 }'
+  interval: 1s
+pipeline:
+  thread_num: "${ARKFLOW_TEST_THREADS}"
+  processors: []
+output:
+  type: stdout
+"#,
+        )
+        .unwrap();
+        let config = load_config(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(config.pipeline.thread_num, 3);
+    }
+
+    #[test]
+    fn test_examples_round_trip() {
+        let paths = examples();
+        assert!(!paths.is_empty());
+        for path in paths {
+            let config = EngineConfig::from_file(path.to_str().unwrap())
+                .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+            for stream in &config.streams {
+                let expected = serde_json::to_value(stream).unwrap();
+
+                let yaml = serde_yaml::to_string(stream).unwrap();
+                let from_yaml: StreamConfig = ConfigFormat::YAML.parse(&yaml).unwrap();
+                assert_eq!(
+                    serde_json::to_value(from_yaml).unwrap(),
+                    expected,
+                    "{}",
+                    path.display()
+                );
+
+                let toml =
+                    toml::to_string(stream).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+                let from_toml: StreamConfig = ConfigFormat::TOML.parse(&toml).unwrap();
+                assert_eq!(
+                    serde_json::to_value(from_toml).unwrap(),
+                    expected,
+                    "{}",
+                    path.display()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_load_config() {
+        std::env::set_var("ARKFLOW_TEST_INTERVAL", "1s");
+        let dir = std::env::temp_dir().join(format!("arkflow-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let yaml_path = dir.join("stream.yaml");
+        std::fs::write(
+            &yaml_path,
+            r#"
+input:
+  type: "generate"
+  context: '{ "value": 10 }'
+  interval: "${ARKFLOW_TEST_INTERVAL}"
+  batch_size: 10
+pipeline:
+  thread_num: 2
+  processors:
+    - type: "json_to_arrow"
+output:
+  type: "stdout"
+"#,
+        )
+        .unwrap();
+        let toml_path = dir.join("stream.toml");
+        std::fs::write(
+            &toml_path,
+            r#"
+[input]
+type = "generate"
+context = '{ "value": 10 }'
+interval = "${ARKFLOW_TEST_INTERVAL}"
+batch_size = 10
+
+[pipeline]
+thread_num = 2
+
+[[pipeline.processors]]
+type = "json_to_arrow"
+
+[output]
+type = "stdout"
+"#,
+        )
+        .unwrap();
+
+        let from_yaml = load_config(&yaml_path).unwrap();
+        let from_toml = load_config(&toml_path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            from_yaml.input.config.as_ref().unwrap()["interval"],
+            serde_json::json!("1s")
+        );
+        assert_eq!(
+            serde_json::to_value(from_yaml).unwrap(),
+            serde_json::to_value(from_toml).unwrap()
+        );
+        assert!(load_config(Path::new("stream.ini")).is_err());
+    }
+
+    #[test]
+    fn test_resource_limits_on_one_stream_only() {
+        let stream = r#"
+  - input:
+      type: generate
+      context: '{ "value": 10 }'
+      interval: 1s
+      batch_size: 10
+    pipeline:
+      thread_num: 1
+      processors: []
+    output:
+      type: stdout
+    resource_limits:
+      max_memory_bytes: 1073741824
+"#;
+        let one: EngineConfig = ConfigFormat::YAML
+            .parse(&format!("streams:{}", stream))
+            .unwrap();
+        assert!(one.validate().is_ok());
+
+        let two: EngineConfig = ConfigFormat::YAML
+            .parse(&format!("streams:{}{}", stream, stream))
+            .unwrap();
+        let err = two.validate().unwrap_err();
+        assert!(err.to_string().contains("resource_limits"), "{}", err);
+    }
+}
@@ -0,0 +1,280 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Schema registry
+//!
+//! Persist the Arrow schema of each table so that incompatible upstream schema changes
+//! are detected instead of silently breaking downstream processors.
+
+use crate::Error;
+use datafusion::arrow::datatypes::{DataType, Schema};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Registry storing one schema per table as a JSON file in a directory
+pub struct SchemaRegistry {
+    directory: PathBuf,
+    schemas: Mutex<HashMap<String, Schema>>,
+}
+
+impl SchemaRegistry {
+    /// Create a registry backed by `directory`, creating it if it does not exist
+    pub fn new(directory: impl Into<PathBuf>) -> Result<Self, Error> {
+        let directory = directory.into();
+        std::fs::create_dir_all(&directory)?;
+        Ok(Self {
+            directory,
+            schemas: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Check `schema` against the stored schema of `table`.
+    ///
+    /// The first schema seen for a table is stored. Later schemas may widen field types,
+    /// relax nullability or add nullable fields, in which case the stored schema is updated.
+    /// Removed fields and narrowed or changed types are rejected.
+    pub fn check(&self, table: &str, schema: &Schema) -> Result<(), Error> {
+        let mut schemas = self
+            .schemas
+            .lock()
+            .map_err(|_| Error::Unknown("Schema registry lock poisoned".to_string()))?;
+
+        let stored = match schemas.get(table) {
+            Some(stored) => Some(stored.clone()),
+            None => self.load(table)?,
+        };
+        let Some(stored) = stored else {
+            self.store(table, schema)?;
+            schemas.insert(table.to_string(), schema.clone());
+            return Ok(());
+        };
+
+        match evolve(&stored, schema) {
+            Ok(Some(updated)) => {
+                self.store(table, &updated)?;
+                schemas.insert(table.to_string(), updated);
+                Ok(())
+            }
+            Ok(None) => {
+                schemas.insert(table.to_string(), stored);
+                Ok(())
+            }
+            Err(e) => Err(Error::Config(format!(
+                "schema mismatch: table {}: {}",
+                table, e
+            ))),
+        }
+    }
+
+    /// File of `table`. Characters other than ASCII letters, digits, `-` and `_` are
+    /// percent-encoded, so that a table name cannot point outside the directory.
+    fn path(&self, table: &str) -> PathBuf {
+        let mut file_name = String::with_capacity(table.len() + 5);
+        for byte in table.bytes() {
+            if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+                file_name.push(byte as char);
+            } else {
+                file_name.push_str(&format!("%{:02X}", byte));
+            }
+        }
+        file_name.push_str(".json");
+        self.directory.join(file_name)
+    }
+
+    fn load(&self, table: &str) -> Result<Option<Schema>, Error> {
+        let path = self.path(table);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path)?;
+        Ok(Some(serde_json::from_str(&content)?))
+    }
+
+    fn store(&self, table: &str, schema: &Schema) -> Result<(), Error> {
+        // Write to a temporary file first so that a crash never leaves a truncated schema behind
+        let path = self.path(table);
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, serde_json::to_string_pretty(schema)?)?;
+        std::fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+}
+
+/// Compare `incoming` with `stored`, returning the updated schema if `incoming` extends it
+fn evolve(stored: &Schema, incoming: &Schema) -> Result<Option<Schema>, String> {
+    let mut changed = false;
+    let mut fields = Vec::with_capacity(incoming.fields().len());
+
+    for stored_field in stored.fields() {
+        let Ok(field) = incoming.field_with_name(stored_field.name()) else {
+            return Err(format!("field {} was removed", stored_field.name()));
+        };
+
+        let data_type = if field.data_type() == stored_field.data_type() {
+            stored_field.data_type().clone()
+        } else if is_widening(stored_field.data_type(), field.data_type()) {
+            changed = true;
+            field.data_type().clone()
+        } else {
+            return Err(format!(
+                "field {} changed from {} to {}",
+                stored_field.name(),
+                stored_field.data_type(),
+                field.data_type()
+            ));
+        };
+
+        let nullable = stored_field.is_nullable() || field.is_nullable();
+        changed |= nullable != stored_field.is_nullable();
+        fields.push(
+            stored_field
+                .as_ref()
+                .clone()
+                .with_data_type(data_type)
+                .with_nullable(nullable),
+        );
+    }
+
+    for field in incoming.fields() {
+        if stored.field_with_name(field.name()).is_ok() {
+            continue;
+        }
+        if !field.is_nullable() {
+            return Err(format!("new field {} is not nullable", field.name()));
+        }
+        changed = true;
+        fields.push(field.as_ref().clone());
+    }
+
+    Ok(changed.then(|| Schema::new_with_metadata(fields, stored.metadata().clone())))
+}
+
+/// Whether values of type `from` can be represented as `to` without loss
+fn is_widening(from: &DataType, to: &DataType) -> bool {
+    use DataType::*;
+    matches!(
+        (from, to),
+        (Int8, Int16 | Int32 | Int64 | Float32 | Float64)
+            | (Int16, Int32 | Int64 | Float32 | Float64)
+            | (Int32, Int64 | Float64)
+            | (
+                UInt8,
+                UInt16 | UInt32 | UInt64 | Int16 | Int32 | Int64 | Float32 | Float64
+            )
+            | (UInt16, UInt32 | UInt64 | Int32 | Int64 | Float32 | Float64)
+            | (UInt32, UInt64 | Int64 | Float64)
+            | (Float16, Float32 | Float64)
+            | (Float32, Float64)
+            | (Utf8, LargeUtf8 | Utf8View)
+            | (Binary, LargeBinary | BinaryView)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::datatypes::Field;
+
+    fn registry(name: &str) -> (SchemaRegistry, PathBuf) {
+        let directory = std::env::temp_dir().join(format!(
+            "arkflow-schema-registry-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&directory);
+        (SchemaRegistry::new(&directory).unwrap(), directory)
+    }
+
+    fn schema(fields: Vec<(&str, DataType, bool)>) -> Schema {
+        Schema::new(
+            fields
+                .into_iter()
+                .map(|(name, data_type, nullable)| Field::new(name, data_type, nullable))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn test_is_widening() {
+        assert!(is_widening(&DataType::Int32, &DataType::Int64));
+        assert!(is_widening(&DataType::Float32, &DataType::Float64));
+        assert!(is_widening(&DataType::Utf8, &DataType::LargeUtf8));
+        assert!(!is_widening(&DataType::Int64, &DataType::Int32));
+        assert!(!is_widening(&DataType::Int64, &DataType::Float64));
+        assert!(!is_widening(&DataType::Utf8, &DataType::Int64));
+    }
+
+    #[test]
+    fn test_evolve() {
+        let stored = schema(vec![("id", DataType::Int32, false)]);
+
+        assert_eq!(evolve(&stored, &stored), Ok(None));
+
+        let widened = schema(vec![
+            ("id", DataType::Int64, true),
+            ("name", DataType::Utf8, true),
+        ]);
+        assert_eq!(evolve(&stored, &widened), Ok(Some(widened.clone())));
+
+        let narrowed = schema(vec![("id", DataType::Int16, false)]);
+        assert!(evolve(&stored, &narrowed).is_err());
+
+        let removed = schema(vec![("name", DataType::Utf8, true)]);
+        assert!(evolve(&stored, &removed).is_err());
+
+        let required = schema(vec![
+            ("id", DataType::Int32, false),
+            ("name", DataType::Utf8, false),
+        ]);
+        assert!(evolve(&stored, &required).is_err());
+    }
+
+    #[test]
+    fn test_check_persists_schema() {
+        let (registry, directory) = registry("persist");
+        let stored = schema(vec![("id", DataType::Int32, false)]);
+        let widened = schema(vec![("id", DataType::Int64, false)]);
+        registry.check("orders", &stored).unwrap();
+        registry.check("orders", &widened).unwrap();
+
+        // A new registry on the same directory loads the widened schema
+        let registry = SchemaRegistry::new(&directory).unwrap();
+        assert!(matches!(
+            registry.check("orders", &stored),
+            Err(Error::Config(_))
+        ));
+        registry.check("orders", &widened).unwrap();
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_table_stays_in_directory() {
+        let (registry, directory) = registry("escape");
+        let path = registry.path("../outside/../../x");
+        assert_eq!(path.parent(), Some(directory.as_path()));
+        assert_eq!(
+            path.file_name().and_then(|name| name.to_str()),
+            Some("%2E%2E%2Foutside%2F%2E%2E%2F%2E%2E%2Fx.json")
+        );
+
+        registry
+            .check("../x", &schema(vec![("id", DataType::Int32, false)]))
+            .unwrap();
+        assert!(directory.join("%2E%2E%2Fx.json").exists());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,238 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Dead letter messages
+//!
+//! Messages that fail processing can be written to the error output wrapped in an envelope
+//! describing the failure, so that they can be analyzed and replayed later.
+
+use crate::{Error, MessageBatch, DEFAULT_BINARY_VALUE_FIELD};
+use base64::Engine;
+use datafusion::arrow::array::{Array, BinaryArray};
+use datafusion::arrow::ipc::reader::StreamReader;
+use datafusion::arrow::ipc::writer::StreamWriter;
+use datafusion::arrow::record_batch::RecordBatch;
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Format of the messages written to the error output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorOutputFormat {
+    /// The failed message as it was received by the pipeline
+    #[default]
+    Raw,
+    /// One [`DeadLetterEnvelope`] per row, serialized as JSON
+    DeadLetter,
+}
+
+/// A message that failed processing, together with the context of the failure
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeadLetterEnvelope {
+    pub original_content: DeadLetterContent,
+    pub error_message: String,
+    pub error_kind: String,
+    pub pipeline_stage: String,
+    pub timestamp_ms: u64,
+    pub attempt_count: u32,
+}
+
+/// Content of a single failed message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum DeadLetterContent {
+    /// Binary payload, base64 encoded
+    Binary(String),
+    /// Row of an Arrow batch, base64 encoded Arrow IPC stream keeping the original schema
+    Arrow(String),
+}
+
+impl DeadLetterEnvelope {
+    /// Rebuild the original message
+    pub fn to_message_batch(&self) -> Result<MessageBatch, Error> {
+        let invalid = |e: &dyn std::fmt::Display| {
+            Error::Process(format!("Invalid dead letter content: {}", e))
+        };
+        match &self.original_content {
+            DeadLetterContent::Binary(value) => {
+                let value = base64::engine::general_purpose::STANDARD
+                    .decode(value)
+                    .map_err(|e| invalid(&e))?;
+                MessageBatch::new_binary(vec![value])
+            }
+            DeadLetterContent::Arrow(row) => {
+                let ipc = base64::engine::general_purpose::STANDARD
+                    .decode(row)
+                    .map_err(|e| invalid(&e))?;
+                let mut reader =
+                    StreamReader::try_new(Cursor::new(ipc), None).map_err(|e| invalid(&e))?;
+                let batch = reader
+                    .next()
+                    .ok_or_else(|| Error::Process("Empty dead letter content".to_string()))?
+                    .map_err(|e| invalid(&e))?;
+                Ok(MessageBatch::new_arrow(batch))
+            }
+        }
+    }
+}
+
+impl MessageBatch {
+    /// Wrap each row of the batch in a [`DeadLetterEnvelope`].
+    ///
+    /// The result is a binary batch whose elements are the JSON-serialized envelopes.
+    pub fn wrap_as_dead_letter(
+        &self,
+        error: &Error,
+        stage: &str,
+        attempt: u32,
+    ) -> Result<MessageBatch, Error> {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+
+        let envelopes = self
+            .original_contents()?
+            .into_iter()
+            .map(|original_content| {
+                serde_json::to_vec(&DeadLetterEnvelope {
+                    original_content,
+                    error_message: error.to_string(),
+                    error_kind: error.kind().to_string(),
+                    pipeline_stage: stage.to_string(),
+                    timestamp_ms,
+                    attempt_count: attempt,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut batch = MessageBatch::new_binary(envelopes)?;
+        batch.set_input_name(self.get_input_name());
+        Ok(batch)
+    }
+
+    fn original_contents(&self) -> Result<Vec<DeadLetterContent>, Error> {
+        if let Some(values) = self
+            .column_by_name(DEFAULT_BINARY_VALUE_FIELD)
+            .and_then(|column| column.as_any().downcast_ref::<BinaryArray>())
+        {
+            return Ok(values
+                .iter()
+                .map(|value| {
+                    DeadLetterContent::Binary(
+                        base64::engine::general_purpose::STANDARD.encode(value.unwrap_or_default()),
+                    )
+                })
+                .collect());
+        }
+
+        (0..self.num_rows())
+            .map(|i| {
+                let ipc = encode_ipc(&self.slice(i, 1))
+                    .map_err(|e| Error::Process(format!("Arrow IPC serialization error: {}", e)))?;
+                Ok(DeadLetterContent::Arrow(
+                    base64::engine::general_purpose::STANDARD.encode(ipc),
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Encode `batch` as an Arrow IPC stream
+fn encode_ipc(batch: &RecordBatch) -> Result<Vec<u8>, datafusion::arrow::error::ArrowError> {
+    let mut buf = Vec::new();
+    let mut writer = StreamWriter::try_new(&mut buf, &batch.schema())?;
+    writer.write(batch)?;
+    writer.finish()?;
+    drop(writer);
+    Ok(buf)
+}
+
+/// Read dead letter envelopes from a file containing one JSON envelope per line
+pub fn read_dead_letters(path: &Path) -> Result<Vec<DeadLetterEnvelope>, Error> {
+    let content = std::fs::read_to_string(path)?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line).map_err(|e| {
+                Error::Config(format!(
+                    "Invalid dead letter at {}:{}: {}",
+                    path.display(),
+                    i + 1,
+                    e
+                ))
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{ArrayRef, Int8Array, StringArray, TimestampMillisecondArray};
+    use std::sync::Arc;
+
+    /// Envelopes of `msg`, after a JSON round trip
+    fn envelopes(msg: &MessageBatch) -> Vec<DeadLetterEnvelope> {
+        let error = Error::Process("boom".to_string());
+        msg.wrap_as_dead_letter(&error, "pipeline", 2)
+            .unwrap()
+            .to_binary(DEFAULT_BINARY_VALUE_FIELD)
+            .unwrap()
+            .into_iter()
+            .map(|envelope| serde_json::from_slice(envelope).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let msg = MessageBatch::new_binary(vec![b"a".to_vec(), vec![0, 255]]).unwrap();
+        let envelopes = envelopes(&msg);
+        assert_eq!(envelopes.len(), 2);
+        assert_eq!(envelopes[0].error_kind, "process");
+        assert_eq!(envelopes[0].attempt_count, 2);
+
+        let replayed = envelopes[1].to_message_batch().unwrap();
+        assert_eq!(
+            replayed.to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap(),
+            vec![&[0u8, 255][..]]
+        );
+    }
+
+    #[test]
+    fn test_arrow_round_trip() {
+        let batch = RecordBatch::try_from_iter([
+            (
+                "ts",
+                Arc::new(TimestampMillisecondArray::from(vec![1, 2])) as ArrayRef,
+            ),
+            ("level", Arc::new(Int8Array::from(vec![Some(-1), None]))),
+            ("name", Arc::new(StringArray::from(vec!["a", "b"]))),
+        ])
+        .unwrap();
+        let envelopes = envelopes(&MessageBatch::new_arrow(batch.clone()));
+        assert_eq!(envelopes.len(), 2);
+
+        // The schema is kept, rather than inferred from JSON values
+        for (i, envelope) in envelopes.iter().enumerate() {
+            let replayed = envelope.to_message_batch().unwrap();
+            assert_eq!(replayed.schema(), batch.schema());
+            assert_eq!(*replayed, batch.slice(i, 1));
+        }
+    }
+}
@@ -13,6 +13,8 @@
  */
 
 use crate::config::EngineConfig;
+use schema_reflection::SchemaReflectionServer;
+use std::path::PathBuf;
 use std::process;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -29,6 +31,8 @@ use axum::{routing::get, Router};
 use serde::Serialize;
 use tokio::net::TcpListener;
 
+pub mod schema_reflection;
+
 /// Health check status
 struct HealthState {
     /// Whether the engine has been initialized
@@ -69,6 +73,8 @@ pub struct Engine {
     config: EngineConfig,
     /// Health check status shared between the engine and health check endpoints
     health_state: Arc<HealthState>,
+    /// Path of the configuration file, used to hot-reload pipelines on SIGHUP
+    config_path: Option<PathBuf>,
 }
 impl Engine {
     /// Create a new engine with the provided configuration
@@ -85,9 +91,16 @@ impl Engine {
                 is_ready: AtomicBool::new(false),
                 is_running: AtomicBool::new(false),
             }),
+            config_path: None,
         }
     }
 
+    /// Enable pipeline hot-reload from the given configuration file on SIGHUP
+    pub fn with_config_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config_path = Some(path.into());
+        self
+    }
+
     /// Start the health check server if enabled in configuration
     ///
     /// Sets up HTTP endpoints for health, readiness, and liveness checks.
@@ -223,6 +236,11 @@ impl Engine {
         // Start the health check server
         self.start_health_check_server(token.clone()).await?;
 
+        // Start the schema reflection server
+        if let Some(schema_reflection) = &self.config.schema_reflection {
+            SchemaReflectionServer::new(schema_reflection)?.start(token.clone())?;
+        }
+
         // Create and run all flows
         let mut streams = Vec::new();
         let mut handles = Vec::new();
@@ -231,7 +249,10 @@ impl Engine {
             info!("Initializing flow #{}", i + 1);
 
             match stream_config.build() {
-                Ok(stream) => {
+                Ok(mut stream) => {
+                    if let Some(path) = &self.config_path {
+                        stream.set_reload_source(path.clone(), i);
+                    }
                     streams.push(stream);
                 }
                 Err(e) => {
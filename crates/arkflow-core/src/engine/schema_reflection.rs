@@ -0,0 +1,564 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Schema reflection
+//!
+//! The schemas of the messages written to outputs are registered by table name, the input
+//! name of the messages or `default`, so that downstream tools can discover them as the
+//! schemas evolve. They are exposed as JSON by the stream admin server and as protobuf
+//! descriptors by a gRPC server implementing the
+//! [Server Reflection Protocol](https://github.com/grpc/grpc/blob/master/doc/server-reflection.md).
+//!
+//! Each table is described by a `arkflow/<table>.proto` file holding a single message in
+//! the `arkflow` package, with one field per column.
+
+use crate::pipeline::DEFAULT_SCHEMA_TABLE;
+use crate::{Error, MessageBatch};
+use datafusion::arrow::datatypes::{DataType, Field, Fields, SchemaRef};
+use futures::TryStreamExt;
+use prost::Message;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, FieldDescriptorProto, FileDescriptorProto};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::RwLock;
+use tokio_util::sync::CancellationToken;
+use tonic::codegen::{
+    empty_body, http, Body, BoxFuture, BoxStream, Context, Poll, Service, StdError,
+};
+use tonic::server::{Grpc, NamedService, StreamingService};
+use tonic::transport::server::TcpIncoming;
+use tonic::{Status, Streaming};
+use tracing::{error, info};
+
+/// Package of the generated protobuf messages
+const PROTO_PACKAGE: &str = "arkflow";
+const REFLECTION_SERVICE: &str = "grpc.reflection.v1alpha.ServerReflection";
+const REFLECTION_INFO_PATH: &str = "/grpc.reflection.v1alpha.ServerReflection/ServerReflectionInfo";
+
+lazy_static::lazy_static! {
+    static ref SCHEMAS: RwLock<BTreeMap<String, SchemaRef>> = RwLock::new(BTreeMap::new());
+}
+
+/// Register `schema` as the current schema of `table`
+pub fn register_schema(table: &str, schema: &SchemaRef) {
+    if SCHEMAS.read().unwrap().get(table) == Some(schema) {
+        return;
+    }
+    SCHEMAS
+        .write()
+        .unwrap()
+        .insert(table.to_string(), schema.clone());
+}
+
+/// Register the schema of a message written to an output, under its input name
+pub(crate) fn register_message(msg: &MessageBatch) {
+    let schema = msg.schema();
+    if schema.fields().is_empty() {
+        return;
+    }
+    let input_name = msg.get_input_name();
+    register_schema(
+        input_name.as_deref().unwrap_or(DEFAULT_SCHEMA_TABLE),
+        &schema,
+    );
+}
+
+/// Current schema of every table, by table name
+pub fn get_registered_schemas() -> BTreeMap<String, SchemaRef> {
+    SCHEMAS.read().unwrap().clone()
+}
+
+/// Name of the protobuf message describing `table`, in CamelCase
+pub fn proto_message_name(table: &str) -> String {
+    let name: String = table
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            std::iter::once(first).chain(chars).collect::<String>()
+        })
+        .collect();
+    match name.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => name,
+        _ => format!("T{}", name),
+    }
+}
+
+/// Describe the schema of `table` as a protobuf file
+pub fn schema_to_descriptor(table: &str, schema: &SchemaRef) -> FileDescriptorProto {
+    let message_name = proto_message_name(table);
+    let scope = format!(".{}.{}", PROTO_PACKAGE, message_name);
+    FileDescriptorProto {
+        name: Some(proto_file_name(table)),
+        package: Some(PROTO_PACKAGE.to_string()),
+        message_type: vec![message_descriptor(&message_name, &scope, schema.fields())],
+        syntax: Some("proto3".to_string()),
+        ..Default::default()
+    }
+}
+
+fn proto_file_name(table: &str) -> String {
+    format!("{}/{}.proto", PROTO_PACKAGE, table)
+}
+
+/// Describe `fields` as a message; `scope` is the fully qualified name of the message
+fn message_descriptor(name: &str, scope: &str, fields: &Fields) -> DescriptorProto {
+    let mut message = DescriptorProto {
+        name: Some(name.to_string()),
+        ..Default::default()
+    };
+    for (i, field) in fields.iter().enumerate() {
+        let field_name = proto_field_name(field.name());
+        let (label, data_type) = match field.data_type() {
+            DataType::List(item) | DataType::LargeList(item) | DataType::FixedSizeList(item, _) => {
+                (Label::Repeated, item.data_type())
+            }
+            data_type => (Label::Optional, data_type),
+        };
+        let (proto_type, type_name) = match data_type {
+            DataType::Struct(children) => {
+                // Nested messages are named after the field
+                let nested_name = proto_message_name(field.name());
+                let nested_scope = format!("{}.{}", scope, nested_name);
+                message
+                    .nested_type
+                    .push(message_descriptor(&nested_name, &nested_scope, children));
+                (Type::Message, Some(nested_scope))
+            }
+            data_type => (proto_type(data_type), None),
+        };
+        message.field.push(FieldDescriptorProto {
+            name: Some(field_name.clone()),
+            number: Some(i as i32 + 1),
+            label: Some(label as i32),
+            r#type: Some(proto_type as i32),
+            type_name,
+            json_name: Some(field.name().clone()),
+            ..Default::default()
+        });
+    }
+    message
+}
+
+/// Protobuf type of an Arrow scalar type. Timestamps and dates are their integer
+/// representation, and types without a protobuf equivalent are strings.
+fn proto_type(data_type: &DataType) -> Type {
+    match data_type {
+        DataType::Boolean => Type::Bool,
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Date32 => Type::Int32,
+        DataType::Int64
+        | DataType::Date64
+        | DataType::Timestamp(_, _)
+        | DataType::Time64(_)
+        | DataType::Duration(_) => Type::Int64,
+        DataType::Time32(_) => Type::Int32,
+        DataType::UInt8 | DataType::UInt16 | DataType::UInt32 => Type::Uint32,
+        DataType::UInt64 => Type::Uint64,
+        DataType::Float16 | DataType::Float32 => Type::Float,
+        DataType::Float64 => Type::Double,
+        DataType::Binary
+        | DataType::LargeBinary
+        | DataType::BinaryView
+        | DataType::FixedSizeBinary(_) => Type::Bytes,
+        _ => Type::String,
+    }
+}
+
+/// Protobuf field name of a column, non-identifier characters replaced by `_`
+fn proto_field_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    match name.chars().next() {
+        Some(c) if !c.is_ascii_digit() => name,
+        _ => format!("_{}", name),
+    }
+}
+
+/// Describe a column as reported by the admin server
+pub(crate) fn describe_field(field: &Field) -> serde_json::Value {
+    let (repeated, data_type) = match field.data_type() {
+        DataType::List(item) | DataType::LargeList(item) | DataType::FixedSizeList(item, _) => {
+            (true, item.data_type())
+        }
+        data_type => (false, data_type),
+    };
+    let proto_type = match data_type {
+        DataType::Struct(_) => proto_message_name(field.name()),
+        data_type => proto_type(data_type)
+            .as_str_name()
+            .trim_start_matches("TYPE_")
+            .to_lowercase(),
+    };
+    serde_json::json!({
+        "name": field.name(),
+        "data_type": field.data_type().to_string(),
+        "nullable": field.is_nullable(),
+        "proto_type": if repeated {
+            format!("repeated {}", proto_type)
+        } else {
+            proto_type
+        },
+    })
+}
+
+/// Schema reflection server configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaReflectionConfig {
+    /// Listening address of the gRPC server, e.g. `0.0.0.0:50051`
+    pub address: String,
+}
+
+/// gRPC server answering Server Reflection requests with the registered schemas
+pub struct SchemaReflectionServer {
+    address: SocketAddr,
+}
+
+impl SchemaReflectionServer {
+    pub fn new(config: &SchemaReflectionConfig) -> Result<Self, Error> {
+        let address = config.address.parse().map_err(|e| {
+            Error::Config(format!(
+                "Invalid schema reflection address {}: {}",
+                config.address, e
+            ))
+        })?;
+        Ok(Self { address })
+    }
+
+    /// Start the server on a separate task. It stops when `cancellation_token` is cancelled.
+    pub fn start(&self, cancellation_token: CancellationToken) -> Result<(), Error> {
+        let incoming = TcpIncoming::new(self.address, true, None).map_err(|e| {
+            Error::Config(format!(
+                "Unable to bind schema reflection server to {}: {}",
+                self.address, e
+            ))
+        })?;
+        info!("Starting schema reflection server on {}", self.address);
+
+        tokio::spawn(async move {
+            let server = tonic::transport::Server::builder()
+                .add_service(ServerReflectionServer)
+                .serve_with_incoming_shutdown(incoming, cancellation_token.cancelled_owned());
+            if let Err(e) = server.await {
+                error!("Schema reflection server error: {}", e);
+            } else {
+                info!("Schema reflection server stopped");
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Answer a Server Reflection request
+fn reflect(request: ServerReflectionRequest) -> ServerReflectionResponse {
+    let message_response = match &request.message_request {
+        Some(MessageRequest::FileByFilename(file_name)) => get_registered_schemas()
+            .iter()
+            .find(|(table, _)| proto_file_name(table) == *file_name)
+            .map(|(table, schema)| file_descriptor_response(table, schema))
+            .unwrap_or_else(|| not_found(format!("File not found: {}", file_name))),
+        Some(MessageRequest::FileContainingSymbol(symbol)) => get_registered_schemas()
+            .iter()
+            .find(|(table, _)| {
+                let message = format!("{}.{}", PROTO_PACKAGE, proto_message_name(table));
+                symbol == &message || symbol.starts_with(&format!("{}.", message))
+            })
+            .map(|(table, schema)| file_descriptor_response(table, schema))
+            .unwrap_or_else(|| not_found(format!("Symbol not found: {}", symbol))),
+        Some(MessageRequest::ListServices(_)) => {
+            MessageResponse::ListServicesResponse(ListServiceResponse {
+                service: vec![ServiceResponse {
+                    name: REFLECTION_SERVICE.to_string(),
+                }],
+            })
+        }
+        // The generated messages have no extensions
+        Some(MessageRequest::FileContainingExtension(extension)) => not_found(format!(
+            "Extension not found: {}",
+            extension.containing_type
+        )),
+        Some(MessageRequest::AllExtensionNumbersOfType(type_name)) => {
+            MessageResponse::AllExtensionNumbersResponse(ExtensionNumberResponse {
+                base_type_name: type_name.clone(),
+                extension_number: vec![],
+            })
+        }
+        None => MessageResponse::ErrorResponse(ErrorResponse {
+            error_code: tonic::Code::InvalidArgument as i32,
+            error_message: "Empty request".to_string(),
+        }),
+    };
+
+    ServerReflectionResponse {
+        valid_host: request.host.clone(),
+        original_request: Some(request),
+        message_response: Some(message_response),
+    }
+}
+
+fn file_descriptor_response(table: &str, schema: &SchemaRef) -> MessageResponse {
+    MessageResponse::FileDescriptorResponse(FileDescriptorResponse {
+        file_descriptor_proto: vec![schema_to_descriptor(table, schema).encode_to_vec()],
+    })
+}
+
+fn not_found(message: String) -> MessageResponse {
+    MessageResponse::ErrorResponse(ErrorResponse {
+        error_code: tonic::Code::NotFound as i32,
+        error_message: message,
+    })
+}
+
+/// The `ServerReflection` service
+#[derive(Clone)]
+struct ServerReflectionServer;
+
+impl NamedService for ServerReflectionServer {
+    const NAME: &'static str = REFLECTION_SERVICE;
+}
+
+impl<B> Service<http::Request<B>> for ServerReflectionServer
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<tonic::body::BoxBody>;
+    type Error = std::convert::Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        if request.uri().path() != REFLECTION_INFO_PATH {
+            return Box::pin(async move {
+                let mut response = http::Response::new(empty_body());
+                let headers = response.headers_mut();
+                headers.insert(
+                    Status::GRPC_STATUS,
+                    (tonic::Code::Unimplemented as i32).into(),
+                );
+                headers.insert(
+                    http::header::CONTENT_TYPE,
+                    tonic::metadata::GRPC_CONTENT_TYPE,
+                );
+                Ok(response)
+            });
+        }
+        Box::pin(async move {
+            let mut grpc = Grpc::new(tonic::codec::ProstCodec::default());
+            Ok(grpc.streaming(ServerReflectionInfo, request).await)
+        })
+    }
+}
+
+/// The bidirectional `ServerReflectionInfo` method, answering each request in order
+struct ServerReflectionInfo;
+
+impl StreamingService<ServerReflectionRequest> for ServerReflectionInfo {
+    type Response = ServerReflectionResponse;
+    type ResponseStream = BoxStream<ServerReflectionResponse>;
+    type Future = BoxFuture<tonic::Response<Self::ResponseStream>, Status>;
+
+    fn call(
+        &mut self,
+        request: tonic::Request<Streaming<ServerReflectionRequest>>,
+    ) -> Self::Future {
+        let requests = request.into_inner();
+        Box::pin(async move {
+            let responses = requests.map_ok(reflect);
+            Ok(tonic::Response::new(
+                Box::pin(responses) as Self::ResponseStream
+            ))
+        })
+    }
+}
+
+// Messages of `grpc/reflection/v1alpha/reflection.proto`
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct ServerReflectionRequest {
+    #[prost(string, tag = "1")]
+    pub host: String,
+    #[prost(oneof = "MessageRequest", tags = "3, 4, 5, 6, 7")]
+    pub message_request: Option<MessageRequest>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub(crate) enum MessageRequest {
+    #[prost(string, tag = "3")]
+    FileByFilename(String),
+    #[prost(string, tag = "4")]
+    FileContainingSymbol(String),
+    #[prost(message, tag = "5")]
+    FileContainingExtension(ExtensionRequest),
+    #[prost(string, tag = "6")]
+    AllExtensionNumbersOfType(String),
+    #[prost(string, tag = "7")]
+    ListServices(String),
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct ExtensionRequest {
+    #[prost(string, tag = "1")]
+    pub containing_type: String,
+    #[prost(int32, tag = "2")]
+    pub extension_number: i32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct ServerReflectionResponse {
+    #[prost(string, tag = "1")]
+    pub valid_host: String,
+    #[prost(message, optional, tag = "2")]
+    pub original_request: Option<ServerReflectionRequest>,
+    #[prost(oneof = "MessageResponse", tags = "4, 5, 6, 7")]
+    pub message_response: Option<MessageResponse>,
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, PartialEq, prost::Oneof)]
+pub(crate) enum MessageResponse {
+    #[prost(message, tag = "4")]
+    FileDescriptorResponse(FileDescriptorResponse),
+    #[prost(message, tag = "5")]
+    AllExtensionNumbersResponse(ExtensionNumberResponse),
+    #[prost(message, tag = "6")]
+    ListServicesResponse(ListServiceResponse),
+    #[prost(message, tag = "7")]
+    ErrorResponse(ErrorResponse),
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct FileDescriptorResponse {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub file_descriptor_proto: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct ExtensionNumberResponse {
+    #[prost(string, tag = "1")]
+    pub base_type_name: String,
+    #[prost(int32, repeated, tag = "2")]
+    pub extension_number: Vec<i32>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct ListServiceResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub service: Vec<ServiceResponse>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct ServiceResponse {
+    #[prost(string, tag = "1")]
+    pub name: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub(crate) struct ErrorResponse {
+    #[prost(int32, tag = "1")]
+    pub error_code: i32,
+    #[prost(string, tag = "2")]
+    pub error_message: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::datatypes::{Schema, TimeUnit};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_proto_message_name() {
+        assert_eq!(proto_message_name("default"), "Default");
+        assert_eq!(proto_message_name("http_events"), "HttpEvents");
+        assert_eq!(proto_message_name("kafka-orders.v2"), "KafkaOrdersV2");
+        assert_eq!(proto_message_name("1st"), "T1st");
+    }
+
+    #[test]
+    fn test_schema_to_descriptor() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("score", DataType::Float64, true),
+            Field::new("ts", DataType::Timestamp(TimeUnit::Millisecond, None), true),
+            Field::new(
+                "tags",
+                DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+                true,
+            ),
+            Field::new(
+                "user",
+                DataType::Struct(Fields::from(vec![Field::new(
+                    "user-id",
+                    DataType::UInt32,
+                    true,
+                )])),
+                true,
+            ),
+        ]));
+
+        let file = schema_to_descriptor("orders", &schema);
+        assert_eq!(file.name(), "arkflow/orders.proto");
+        assert_eq!(file.package(), "arkflow");
+        assert_eq!(file.syntax(), "proto3");
+
+        let message = &file.message_type[0];
+        assert_eq!(message.name(), "Orders");
+        let fields: Vec<_> = message
+            .field
+            .iter()
+            .map(|f| (f.name(), f.number(), f.label(), f.r#type()))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("id", 1, Label::Optional, Type::Int64),
+                ("name", 2, Label::Optional, Type::String),
+                ("score", 3, Label::Optional, Type::Double),
+                ("ts", 4, Label::Optional, Type::Int64),
+                ("tags", 5, Label::Repeated, Type::String),
+                ("user", 6, Label::Optional, Type::Message),
+            ]
+        );
+        assert_eq!(message.field[5].type_name(), ".arkflow.Orders.User");
+
+        let nested = &message.nested_type[0];
+        assert_eq!(nested.name(), "User");
+        assert_eq!(nested.field[0].name(), "user_id");
+        assert_eq!(nested.field[0].json_name(), "user-id");
+        assert_eq!(nested.field[0].r#type(), Type::Uint32);
+    }
+
+    #[test]
+    fn test_register_schema_replaces_previous_version() {
+        let v1 = Arc::new(Schema::new(vec![Field::new("a", DataType::Int64, true)]));
+        let v2 = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int64, true),
+            Field::new("b", DataType::Utf8, true),
+        ]));
+
+        register_schema("evolving", &v1);
+        assert_eq!(get_registered_schemas()["evolving"], v1);
+        register_schema("evolving", &v2);
+        assert_eq!(get_registered_schemas()["evolving"], v2);
+    }
+}
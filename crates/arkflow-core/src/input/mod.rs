@@ -102,6 +102,15 @@ pub struct InputConfig {
 }
 
 impl InputConfig {
+    /// Create the configuration of an input of type `type_name` from a typed configuration
+    pub fn from_typed<T: Serialize>(config: &T, type_name: &str) -> Result<Self, Error> {
+        Ok(Self {
+            input_type: type_name.to_string(),
+            name: None,
+            config: crate::config::component_config(config)?,
+        })
+    }
+
     /// Building input components
     pub fn build(&self, resource: &Resource) -> Result<Arc<dyn Input>, Error> {
         let builders = INPUT_BUILDERS.read().unwrap();
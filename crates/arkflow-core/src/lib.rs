@@ -14,14 +14,23 @@
 
 //! Rust stream processing engine
 
+use crate::input::Ack;
+use crate::output::OutputFormat;
 use crate::temporary::Temporary;
-use datafusion::arrow::array::{Array, ArrayRef, BinaryArray};
+use crate::trace::SpanContext;
+use datafusion::arrow::array::{new_null_array, Array, ArrayRef, BinaryArray};
+use datafusion::arrow::compute::{cast, concat_batches};
 use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
-use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::ipc::reader::StreamReader;
+use datafusion::arrow::ipc::writer::StreamWriter;
+use datafusion::arrow::json::LineDelimitedWriter;
+use datafusion::arrow::record_batch::{RecordBatch, RecordBatchOptions};
 use datafusion::parquet::data_type::AsBytes;
 use serde::Serialize;
+use std::any::Any;
 use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
 use thiserror::Error;
@@ -30,6 +39,7 @@ pub mod buffer;
 pub mod cli;
 pub mod codec;
 pub mod config;
+pub mod dead_letter;
 pub mod engine;
 pub mod input;
 pub mod output;
@@ -37,6 +47,8 @@ pub mod pipeline;
 pub mod processor;
 pub mod stream;
 pub mod temporary;
+pub mod trace;
+pub mod util;
 
 pub const DEFAULT_BINARY_VALUE_FIELD: &str = "__value__";
 pub const DEFAULT_RECORD_BATCH: usize = 8192;
@@ -76,7 +88,25 @@ pub enum Error {
     EOF,
 }
 
-#[derive(Clone)]
+impl Error {
+    /// Name of the error variant, e.g. `process`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Io(_) => "io",
+            Error::Serialization(_) => "serialization",
+            Error::Config(_) => "config",
+            Error::Read(_) => "read",
+            Error::Process(_) => "process",
+            Error::Connection(_) => "connection",
+            Error::Disconnection => "disconnection",
+            Error::Timeout => "timeout",
+            Error::Unknown(_) => "unknown",
+            Error::EOF => "eof",
+        }
+    }
+}
+
+#[derive(Clone, Default)]
 pub struct Resource {
     pub temporary: HashMap<String, Arc<dyn Temporary>>,
     pub input_names: RefCell<Vec<String>>,
@@ -84,11 +114,37 @@ pub struct Resource {
 
 pub type Bytes = Vec<u8>;
 
+/// Structured Rust values carried by a [`MessageBatch`], see [`MessageBatch::new_struct`]
+pub type StructItems = Vec<Box<dyn Any + Send + Sync>>;
+
 /// Represents a message in a stream processing engine.
 #[derive(Clone, Debug)]
 pub struct MessageBatch {
     record_batch: RecordBatch,
     input_name: Option<String>,
+    struct_items: Option<StructContent>,
+    span_context: Option<SpanContext>,
+    acks: AckHooks,
+}
+
+/// Shared [`StructItems`], so that cloning a batch does not require the items to be `Clone`
+#[derive(Clone)]
+struct StructContent(Arc<StructItems>);
+
+impl fmt::Debug for StructContent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "StructContent({} items)", self.0.len())
+    }
+}
+
+/// Acks to call once the batch was written, see [`MessageBatch::on_ack`]
+#[derive(Clone, Default)]
+struct AckHooks(Vec<Arc<dyn Ack>>);
+
+impl fmt::Debug for AckHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AckHooks({} acks)", self.0.len())
+    }
 }
 
 impl MessageBatch {
@@ -118,6 +174,9 @@ impl MessageBatch {
         Ok(Self {
             record_batch: batch,
             input_name: None,
+            struct_items: None,
+            span_context: None,
+            acks: AckHooks::default(),
         })
     }
 
@@ -129,6 +188,30 @@ impl MessageBatch {
         self.input_name.clone()
     }
 
+    /// Trace context of the span the batch was produced in, if it was traced
+    pub fn extract_span_context(&self) -> Option<SpanContext> {
+        self.span_context.clone()
+    }
+
+    /// Attach the trace context of the span the batch was produced in
+    pub fn inject_span_context(&mut self, context: &SpanContext) {
+        self.span_context = Some(context.clone());
+    }
+
+    /// Call `ack` once the results of processing the batch were written to the output.
+    ///
+    /// Lets a processor defer side effects, e.g. remembering what it has seen, until the
+    /// message can no longer be delivered again. The ack is dropped without being called if
+    /// processing or writing fails.
+    pub fn on_ack(&mut self, ack: Arc<dyn Ack>) {
+        self.acks.0.push(ack);
+    }
+
+    /// Remove the acks added with [`MessageBatch::on_ack`]
+    pub fn take_acks(&mut self) -> Vec<Arc<dyn Ack>> {
+        std::mem::take(&mut self.acks.0)
+    }
+
     pub fn new_binary_with_origin(&self, content: Vec<Bytes>) -> Result<Self, Error> {
         let schema = self.schema();
         let mut fields: Vec<Arc<Field>> = schema.fields().iter().cloned().collect();
@@ -184,13 +267,94 @@ impl MessageBatch {
         Ok(Self::new_binary(vec![content])?)
     }
 
+    /// Serialize the batch to an Arrow IPC stream.
+    ///
+    /// The binary message column is written as a `LargeBinary` column, which
+    /// [`MessageBatch::from_arrow_ipc`] reads back as binary messages.
+    pub fn to_arrow_ipc(&self) -> Result<Bytes, Error> {
+        if self.struct_items.is_some() {
+            return Err(Error::Process(
+                "Cannot serialize struct content to Arrow IPC".to_string(),
+            ));
+        }
+        let ipc_error = |e| Error::Process(format!("Arrow IPC serialization failed: {}", e));
+
+        let batch = if self.is_binary() {
+            cast_value_column(&self.record_batch, &DataType::LargeBinary).map_err(ipc_error)?
+        } else {
+            self.record_batch.clone()
+        };
+
+        let mut writer = StreamWriter::try_new(Vec::new(), &batch.schema()).map_err(ipc_error)?;
+        writer.write(&batch).map_err(ipc_error)?;
+        writer.finish().map_err(ipc_error)?;
+        writer.into_inner().map_err(ipc_error)
+    }
+
+    /// Read a batch from an Arrow IPC stream, concatenating the record batches of the stream.
+    ///
+    /// A `LargeBinary` [`DEFAULT_BINARY_VALUE_FIELD`] column, as written by
+    /// [`MessageBatch::to_arrow_ipc`], is read as binary messages.
+    pub fn from_arrow_ipc(bytes: &[u8]) -> Result<Self, Error> {
+        let ipc_error = |e| Error::Read(format!("Invalid Arrow IPC stream: {}", e));
+
+        let reader = StreamReader::try_new(bytes, None).map_err(ipc_error)?;
+        let schema = reader.schema();
+        let batches = reader.collect::<Result<Vec<_>, _>>().map_err(ipc_error)?;
+        let batch = concat_batches(&schema, &batches).map_err(ipc_error)?;
+
+        let is_large_binary = schema
+            .field_with_name(DEFAULT_BINARY_VALUE_FIELD)
+            .is_ok_and(|field| *field.data_type() == DataType::LargeBinary);
+        if !is_large_binary {
+            return Ok(Self::new_arrow(batch));
+        }
+        let batch = cast_value_column(&batch, &DataType::Binary).map_err(ipc_error)?;
+        Ok(Self::new_arrow(batch))
+    }
+
     pub fn new_arrow(content: RecordBatch) -> Self {
         Self {
             record_batch: content,
             input_name: None,
+            struct_items: None,
+            span_context: None,
+            acks: AckHooks::default(),
         }
     }
 
+    /// Create a message carrying structured Rust values, one per row, without serializing them.
+    ///
+    /// This lets tightly coupled processors of the same pipeline exchange arbitrary types. The
+    /// batch has no columns: `Struct` content cannot cross process boundaries, and must be
+    /// converted to binary or Arrow content by a processor before it reaches an output, which
+    /// does not get struct messages. Operations creating new batches, such as [`MessageBatch::rows`], drop the items.
+    pub fn new_struct(items: StructItems) -> Result<Self, Error> {
+        let options = RecordBatchOptions::new().with_row_count(Some(items.len()));
+        let record_batch =
+            RecordBatch::try_new_with_options(Arc::new(Schema::empty()), vec![], &options)
+                .map_err(|e| {
+                    Error::Process(format!("Creating an Arrow record batch failed: {}", e))
+                })?;
+        Ok(Self {
+            record_batch,
+            input_name: None,
+            struct_items: Some(StructContent(Arc::new(items))),
+            span_context: None,
+            acks: AckHooks::default(),
+        })
+    }
+
+    /// Get the structured values of a batch created with [`MessageBatch::new_struct`].
+    ///
+    /// Downcast each item with [`Any::downcast_ref`] to access it.
+    pub fn try_as_struct(&self) -> Result<&StructItems, Error> {
+        self.struct_items
+            .as_ref()
+            .map(|items| items.0.as_ref())
+            .ok_or_else(|| Error::Process("The message does not have struct content".to_string()))
+    }
+
     /// Create a message from a string.
     pub fn from_string(content: &str) -> Result<Self, Error> {
         Self::new_binary(vec![content.as_bytes().to_vec()])
@@ -200,8 +364,12 @@ impl MessageBatch {
         self.len() == 0
     }
 
+    /// Number of rows, or of structured values for struct content
     pub fn len(&self) -> usize {
-        self.record_batch.num_rows()
+        match &self.struct_items {
+            Some(items) => items.0.len(),
+            None => self.record_batch.num_rows(),
+        }
     }
 
     pub fn to_binary(&self, name: &str) -> Result<Vec<&[u8]>, Error> {
@@ -226,6 +394,323 @@ impl MessageBatch {
         }
         Ok(vec_bytes)
     }
+
+    /// Serialize the batch to one payload per message in `format`.
+    ///
+    /// Binary messages are parsed as JSON and re-encoded, the rows of Arrow batches are
+    /// encoded as objects.
+    pub fn serialize_to(&self, format: OutputFormat) -> Result<Vec<Bytes>, Error> {
+        self.serialize_field_to(DEFAULT_BINARY_VALUE_FIELD, format)
+    }
+
+    /// Serialize the batch like [`MessageBatch::serialize_to`], reading binary messages from
+    /// the `field` column when the batch has one.
+    pub fn serialize_field_to(
+        &self,
+        field: &str,
+        format: OutputFormat,
+    ) -> Result<Vec<Bytes>, Error> {
+        let has_binary_field = self
+            .record_batch
+            .schema()
+            .field_with_name(field)
+            .is_ok_and(|f| *f.data_type() == DataType::Binary);
+        let json_messages;
+        let payloads = if has_binary_field {
+            self.to_binary(field)?
+        } else {
+            json_messages = self.to_json_messages()?;
+            json_messages.to_binary(DEFAULT_BINARY_VALUE_FIELD)?
+        };
+        payloads
+            .into_iter()
+            .map(|payload| format.encode_json(payload))
+            .collect()
+    }
+
+    /// Whether the batch holds raw binary messages, i.e. has a binary
+    /// [`DEFAULT_BINARY_VALUE_FIELD`] column. The batch may have other columns, e.g. metadata
+    /// added by the input, which are carried along with the messages.
+    pub fn is_binary(&self) -> bool {
+        self.record_batch
+            .schema()
+            .field_with_name(DEFAULT_BINARY_VALUE_FIELD)
+            .is_ok_and(|field| *field.data_type() == DataType::Binary)
+    }
+
+    /// Replace the binary messages of the batch with `content`, one message per row, keeping
+    /// the other columns, the input name and the trace context.
+    pub fn with_binary_values(&self, content: Vec<Bytes>) -> Result<Self, Error> {
+        let error = |e| Error::Process(format!("Creating an Arrow record batch failed: {}", e));
+        let schema = self.record_batch.schema();
+        let index = schema.index_of(DEFAULT_BINARY_VALUE_FIELD).map_err(error)?;
+        let bytes: Vec<&[u8]> = content.iter().map(|x| x.as_bytes()).collect();
+        let mut columns = self.record_batch.columns().to_vec();
+        columns[index] = Arc::new(BinaryArray::from_vec(bytes));
+        let record_batch = RecordBatch::try_new(schema, columns).map_err(error)?;
+        Ok(Self {
+            record_batch,
+            input_name: self.input_name.clone(),
+            struct_items: None,
+            span_context: self.span_context.clone(),
+            acks: AckHooks::default(),
+        })
+    }
+
+    /// Whether the batch can be concatenated with `other` as is, i.e. both have the same
+    /// column names and types in the same order.
+    pub fn is_compatible_with(&self, other: &MessageBatch) -> bool {
+        let (schema, other_schema) = (self.schema(), other.schema());
+        schema.fields().len() == other_schema.fields().len()
+            && schema
+                .fields()
+                .iter()
+                .zip(other_schema.fields())
+                .all(|(a, b)| a.name() == b.name() && a.data_type() == b.data_type())
+    }
+
+    /// Concatenate batches into a single batch.
+    ///
+    /// Compatible batches are concatenated as is. When binary and Arrow batches are mixed,
+    /// every row of the Arrow batches is first converted to a binary message holding the row
+    /// as a JSON object. The other columns of binary batches are kept, filled with nulls for
+    /// the batches without them. Arrow batches with different schemas cannot be concatenated.
+    ///
+    /// The input name and the trace context are kept when all batches share them.
+    pub fn concat(batches: &[MessageBatch]) -> Result<MessageBatch, Error> {
+        let Some(first) = batches.first() else {
+            return Err(Error::Process("No batches to concatenate".to_string()));
+        };
+        if batches.iter().any(|batch| batch.struct_items.is_some()) {
+            return Err(Error::Process(
+                "Cannot concatenate batches with struct content".to_string(),
+            ));
+        }
+
+        let converted: Vec<MessageBatch>;
+        let batches = if batches.iter().all(|batch| batch.is_compatible_with(first)) {
+            batches
+        } else if batches.iter().any(MessageBatch::is_binary) {
+            let binary = batches
+                .iter()
+                .map(|batch| {
+                    if batch.is_binary() {
+                        Ok(batch.clone())
+                    } else {
+                        batch.to_json_messages()
+                    }
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            converted = align_columns(&binary)?;
+            &converted
+        } else {
+            return Err(Error::Process(
+                "Cannot concatenate Arrow batches with different schemas".to_string(),
+            ));
+        };
+
+        // A column is nullable in the result if it is nullable in any of the batches
+        let fields: Vec<Field> = batches[0]
+            .schema()
+            .fields()
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let nullable = batches.iter().any(|b| b.schema().field(i).is_nullable());
+                field.as_ref().clone().with_nullable(nullable)
+            })
+            .collect();
+        let schema = Arc::new(Schema::new(fields));
+        let record_batch = concat_batches(&schema, batches.iter().map(|b| &b.record_batch))
+            .map_err(|e| Error::Process(format!("Merge batches failed: {}", e)))?;
+
+        let input_name = first.get_input_name();
+        let same_input = batches.iter().all(|b| b.input_name == input_name);
+        let same_span = batches.iter().all(|b| b.span_context == first.span_context);
+        Ok(MessageBatch {
+            record_batch,
+            input_name: if same_input { input_name } else { None },
+            struct_items: None,
+            span_context: if same_span {
+                first.span_context.clone()
+            } else {
+                None
+            },
+            acks: AckHooks(batches.iter().flat_map(|b| b.acks.0.clone()).collect()),
+        })
+    }
+
+    /// Convert the batch to binary messages, one JSON object per row
+    fn to_json_messages(&self) -> Result<MessageBatch, Error> {
+        let mut buf = Vec::new();
+        let mut writer = LineDelimitedWriter::new(&mut buf);
+        writer
+            .write(&self.record_batch)
+            .map_err(|e| Error::Process(format!("Arrow JSON Serialization error: {}", e)))?;
+        writer.finish().map_err(|e| {
+            Error::Process(format!("Arrow JSON Serialization Complete Error: {}", e))
+        })?;
+
+        let lines = buf
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_vec())
+            .collect();
+        let mut batch = MessageBatch::new_binary(lines)?;
+        batch.set_input_name(self.get_input_name());
+        batch.span_context = self.span_context.clone();
+        Ok(batch)
+    }
+
+    /// Estimate the heap memory held by the batch in bytes.
+    ///
+    /// Binary messages count their payload lengths plus one `Vec` header each, other
+    /// columns the memory of their Arrow arrays.
+    pub fn estimate_heap_bytes(&self) -> usize {
+        let schema = self.record_batch.schema();
+        let values = schema
+            .index_of(DEFAULT_BINARY_VALUE_FIELD)
+            .ok()
+            .and_then(|index| {
+                let values = self.column(index).as_any().downcast_ref::<BinaryArray>()?;
+                Some((index, values))
+            });
+        let Some((index, values)) = values else {
+            return self.record_batch.get_array_memory_size();
+        };
+
+        let payload_bytes: usize = values
+            .iter()
+            .map(|value| value.map_or(0, <[u8]>::len) + std::mem::size_of::<Bytes>())
+            .sum();
+        let other_bytes: usize = self
+            .columns()
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != index)
+            .map(|(_, column)| column.get_array_memory_size())
+            .sum();
+        payload_bytes + other_bytes
+    }
+
+    /// Iterate over the rows of the batch, each as a single-row batch.
+    pub fn rows(&self) -> impl Iterator<Item = MessageBatch> + '_ {
+        (0..self.len()).map(move |i| self.row(i))
+    }
+
+    fn row(&self, i: usize) -> MessageBatch {
+        Self {
+            record_batch: self.record_batch.slice(i, 1),
+            input_name: self.input_name.clone(),
+            struct_items: None,
+            span_context: self.span_context.clone(),
+            acks: AckHooks::default(),
+        }
+    }
+}
+
+/// Cast the [`DEFAULT_BINARY_VALUE_FIELD`] column of `batch` to `data_type`, keeping the other
+/// columns
+fn cast_value_column(
+    batch: &RecordBatch,
+    data_type: &DataType,
+) -> Result<RecordBatch, datafusion::arrow::error::ArrowError> {
+    let schema = batch.schema();
+    let index = schema.index_of(DEFAULT_BINARY_VALUE_FIELD)?;
+    let mut fields: Vec<Field> = schema.fields().iter().map(|f| f.as_ref().clone()).collect();
+    fields[index] = fields[index].clone().with_data_type(data_type.clone());
+    let mut columns = batch.columns().to_vec();
+    columns[index] = cast(&columns[index], data_type)?;
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+}
+
+/// Give binary batches the same columns: the union of their columns in order of first
+/// appearance, with nulls for the rows of the batches missing a column.
+fn align_columns(batches: &[MessageBatch]) -> Result<Vec<MessageBatch>, Error> {
+    let mut fields: Vec<Field> = Vec::new();
+    for batch in batches {
+        for field in batch.schema().fields() {
+            match fields.iter().find(|f| f.name() == field.name()) {
+                Some(existing) if existing.data_type() != field.data_type() => {
+                    return Err(Error::Process(format!(
+                        "Cannot concatenate batches with different types for column {}",
+                        field.name()
+                    )));
+                }
+                Some(_) => {}
+                None => fields.push(field.as_ref().clone()),
+            }
+        }
+    }
+
+    batches
+        .iter()
+        .map(|batch| {
+            let columns = fields
+                .iter()
+                .map(|field| match batch.column_by_name(field.name()) {
+                    Some(column) => column.clone(),
+                    None => new_null_array(field.data_type(), batch.num_rows()),
+                })
+                .collect();
+            let schema = Schema::new(
+                fields
+                    .iter()
+                    .map(|field| {
+                        let missing = batch.column_by_name(field.name()).is_none();
+                        field.clone().with_nullable(field.is_nullable() || missing)
+                    })
+                    .collect::<Vec<_>>(),
+            );
+            let record_batch = RecordBatch::try_new(Arc::new(schema), columns)
+                .map_err(|e| Error::Process(format!("Merge batches failed: {}", e)))?;
+            Ok(MessageBatch {
+                record_batch,
+                input_name: batch.input_name.clone(),
+                struct_items: None,
+                span_context: batch.span_context.clone(),
+                acks: batch.acks.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Iterator over the rows of a [`MessageBatch`], see [`MessageBatch::rows`].
+pub struct MessageBatchIntoIter {
+    batch: MessageBatch,
+    index: usize,
+}
+
+impl Iterator for MessageBatchIntoIter {
+    type Item = MessageBatch;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.batch.len() {
+            return None;
+        }
+        let row = self.batch.row(self.index);
+        self.index += 1;
+        Some(row)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.batch.len() - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for MessageBatchIntoIter {}
+
+impl IntoIterator for MessageBatch {
+    type Item = MessageBatch;
+    type IntoIter = MessageBatchIntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        MessageBatchIntoIter {
+            batch: self,
+            index: 0,
+        }
+    }
 }
 
 impl Deref for MessageBatch {
@@ -241,10 +726,14 @@ impl From<RecordBatch> for MessageBatch {
         Self {
             record_batch: batch,
             input_name: None,
+            struct_items: None,
+            span_context: None,
+            acks: AckHooks::default(),
         }
     }
 }
 
+/// The struct items of a batch created with [`MessageBatch::new_struct`] are dropped.
 impl From<MessageBatch> for RecordBatch {
     fn from(batch: MessageBatch) -> Self {
         batch.record_batch
@@ -308,3 +797,269 @@ pub fn split_batch(batch_to_split: RecordBatch, size: usize) -> Vec<RecordBatch>
 
     chunks
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Int64Array, StringArray};
+
+    fn arrow_batch(ids: Vec<Option<i64>>, names: Vec<&str>) -> MessageBatch {
+        let batch = RecordBatch::try_from_iter_with_nullable([
+            (
+                "id",
+                Arc::new(Int64Array::from(ids.clone())) as ArrayRef,
+                ids.contains(&None),
+            ),
+            (
+                "name",
+                Arc::new(StringArray::from(names)) as ArrayRef,
+                false,
+            ),
+        ])
+        .unwrap();
+        MessageBatch::new_arrow(batch)
+    }
+
+    #[test]
+    fn test_concat_compatible_arrow() {
+        let mut first = arrow_batch(vec![Some(1)], vec!["a"]);
+        first.set_input_name(Some("orders".to_string()));
+        let mut second = arrow_batch(vec![None, Some(3)], vec!["b", "c"]);
+        second.set_input_name(Some("orders".to_string()));
+        assert!(first.is_compatible_with(&second));
+
+        let merged = MessageBatch::concat(&[first, second]).unwrap();
+        assert_eq!(merged.len(), 3);
+        assert!(merged.schema().field(0).is_nullable());
+        assert_eq!(merged.get_input_name(), Some("orders".to_string()));
+    }
+
+    #[test]
+    fn test_concat_binary() {
+        let mut first = MessageBatch::new_binary(vec![b"a".to_vec()]).unwrap();
+        first.set_input_name(Some("left".to_string()));
+        let mut second = MessageBatch::new_binary(vec![b"b".to_vec(), b"c".to_vec()]).unwrap();
+        second.set_input_name(Some("right".to_string()));
+
+        let merged = MessageBatch::concat(&[first, second]).unwrap();
+        assert!(merged.is_binary());
+        assert_eq!(
+            merged.to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap(),
+            vec![b"a".as_slice(), b"b".as_slice(), b"c".as_slice()]
+        );
+        assert_eq!(merged.get_input_name(), None);
+    }
+
+    #[test]
+    fn test_concat_mixed_content() {
+        let binary = MessageBatch::new_binary(vec![b"raw".to_vec()]).unwrap();
+        let arrow = arrow_batch(vec![Some(1), None], vec!["a", "b"]);
+        assert!(!binary.is_compatible_with(&arrow));
+
+        let merged = MessageBatch::concat(&[binary, arrow]).unwrap();
+        assert_eq!(
+            merged.to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap(),
+            vec![
+                b"raw".as_slice(),
+                br#"{"id":1,"name":"a"}"#.as_slice(),
+                br#"{"name":"b"}"#.as_slice(),
+            ]
+        );
+    }
+
+    /// A binary message with the topic column added by the MQTT input
+    fn mqtt_message(payload: &[u8], topic: &str) -> MessageBatch {
+        MessageBatch::new_arrow(
+            RecordBatch::try_from_iter([
+                (
+                    DEFAULT_BINARY_VALUE_FIELD,
+                    Arc::new(BinaryArray::from_vec(vec![payload])) as ArrayRef,
+                ),
+                (
+                    "mqtt_topic",
+                    Arc::new(StringArray::from(vec![topic])) as ArrayRef,
+                ),
+            ])
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_binary_with_metadata_columns() {
+        let message = mqtt_message(b"a", "sensors/a");
+        assert!(message.is_binary());
+        assert!(message.estimate_heap_bytes() > 1);
+
+        // The topic is kept when the payload is replaced
+        let replaced = message.with_binary_values(vec![b"b".to_vec()]).unwrap();
+        assert_eq!(
+            replaced.to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap(),
+            vec![b"b".as_slice()]
+        );
+        assert_eq!(
+            replaced.column_by_name("mqtt_topic"),
+            message.column_by_name("mqtt_topic")
+        );
+
+        let decoded = MessageBatch::from_arrow_ipc(&message.to_arrow_ipc().unwrap()).unwrap();
+        assert!(decoded.is_binary());
+        assert_eq!(
+            RecordBatch::from(decoded),
+            RecordBatch::from(message.clone())
+        );
+
+        // Plain binary messages and Arrow rows get a null topic
+        let plain = MessageBatch::new_binary(vec![b"c".to_vec()]).unwrap();
+        let arrow = arrow_batch(vec![Some(1)], vec!["d"]);
+        let merged = MessageBatch::concat(&[message, plain, arrow]).unwrap();
+        assert!(merged.is_binary());
+        assert_eq!(
+            merged.to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap(),
+            vec![
+                b"a".as_slice(),
+                b"c".as_slice(),
+                br#"{"id":1,"name":"d"}"#.as_slice()
+            ]
+        );
+        let topics = merged.column_by_name("mqtt_topic").unwrap();
+        let topics = topics.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(
+            topics.iter().collect::<Vec<_>>(),
+            vec![Some("sensors/a"), None, None]
+        );
+    }
+
+    #[test]
+    fn test_concat_incompatible_arrow() {
+        let arrow = arrow_batch(vec![Some(1)], vec!["a"]);
+        let other = MessageBatch::new_arrow(
+            RecordBatch::try_from_iter([(
+                "id",
+                Arc::new(StringArray::from(vec!["1"])) as ArrayRef,
+            )])
+            .unwrap(),
+        );
+        assert!(MessageBatch::concat(&[arrow, other]).is_err());
+        assert!(MessageBatch::concat(&[]).is_err());
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Reading {
+        sensor: String,
+        value: f64,
+    }
+
+    #[test]
+    fn test_struct_content() {
+        let batch = MessageBatch::new_struct(vec![
+            Box::new(Reading {
+                sensor: "a".to_string(),
+                value: 1.5,
+            }),
+            Box::new(Reading {
+                sensor: "b".to_string(),
+                value: 2.5,
+            }),
+        ])
+        .unwrap();
+        assert_eq!(batch.len(), 2);
+        assert_eq!(batch.num_columns(), 0);
+        assert!(batch.to_binary(DEFAULT_BINARY_VALUE_FIELD).is_err());
+
+        let cloned = batch.clone();
+        let readings: Vec<&Reading> = cloned
+            .try_as_struct()
+            .unwrap()
+            .iter()
+            .map(|item| item.downcast_ref::<Reading>().unwrap())
+            .collect();
+        assert_eq!(readings[1].sensor, "b");
+        assert_eq!(readings[1].value, 2.5);
+
+        assert!(MessageBatch::concat(&[batch.clone(), batch]).is_err());
+        assert!(arrow_batch(vec![Some(1)], vec!["a"])
+            .try_as_struct()
+            .is_err());
+    }
+
+    #[test]
+    fn test_rows_empty() {
+        let batch = MessageBatch::new_binary(vec![]).unwrap();
+        assert_eq!(batch.len(), 0);
+        assert_eq!(batch.rows().count(), 0);
+        assert_eq!(batch.into_iter().len(), 0);
+    }
+
+    #[test]
+    fn test_rows_binary() {
+        let mut batch = MessageBatch::new_binary(vec![b"a".to_vec(), b"bc".to_vec()]).unwrap();
+        batch.set_input_name(Some("events".to_string()));
+
+        let rows: Vec<_> = batch.rows().collect();
+        assert_eq!(rows.len(), batch.len());
+        assert_eq!(
+            rows[1].to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap(),
+            vec![b"bc".as_slice()]
+        );
+        assert_eq!(rows[1].get_input_name(), Some("events".to_string()));
+
+        let iter = batch.clone().into_iter();
+        assert_eq!(iter.len(), batch.len());
+        let values: Vec<Vec<u8>> = iter
+            .map(|row| row.to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap()[0].to_vec())
+            .collect();
+        assert_eq!(values, vec![b"a".to_vec(), b"bc".to_vec()]);
+    }
+
+    #[test]
+    fn test_rows_arrow() {
+        let batch = arrow_batch(vec![Some(1), None, Some(3)], vec!["a", "b", "c"]);
+        assert_eq!(batch.rows().count(), batch.len());
+
+        let mut iter = batch.clone().into_iter();
+        assert_eq!(iter.size_hint(), (3, Some(3)));
+        iter.next();
+        assert_eq!(iter.len(), 2);
+        let rows: Vec<_> = iter.collect();
+        assert!(rows.iter().all(|row| row.len() == 1));
+        assert_eq!(RecordBatch::from(rows[1].clone()), batch.slice(2, 1));
+    }
+
+    #[test]
+    fn test_arrow_ipc_round_trip_arrow() {
+        let batch = arrow_batch(vec![Some(1), None], vec!["a", "b"]);
+        let bytes = batch.to_arrow_ipc().unwrap();
+
+        let decoded = MessageBatch::from_arrow_ipc(&bytes).unwrap();
+        assert_eq!(RecordBatch::from(decoded), RecordBatch::from(batch));
+    }
+
+    #[test]
+    fn test_arrow_ipc_round_trip_binary() {
+        let batch =
+            MessageBatch::new_binary(vec![b"a".to_vec(), vec![], b"\x00\xff".to_vec()]).unwrap();
+        let bytes = batch.to_arrow_ipc().unwrap();
+
+        // Binary messages are written as a LargeBinary column
+        let reader = StreamReader::try_new(bytes.as_slice(), None).unwrap();
+        assert_eq!(reader.schema().field(0).data_type(), &DataType::LargeBinary);
+
+        let decoded = MessageBatch::from_arrow_ipc(&bytes).unwrap();
+        assert!(decoded.is_binary());
+        assert_eq!(
+            decoded.to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap(),
+            vec![b"a".as_slice(), b"".as_slice(), b"\x00\xff".as_slice()]
+        );
+    }
+
+    #[test]
+    fn test_arrow_ipc_invalid() {
+        assert!(MessageBatch::from_arrow_ipc(b"not arrow").is_err());
+        let batch = MessageBatch::new_struct(vec![Box::new(Reading {
+            sensor: "a".to_string(),
+            value: 1.0,
+        })])
+        .unwrap();
+        assert!(batch.to_arrow_ipc().is_err());
+    }
+}
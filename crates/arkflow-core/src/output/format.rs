@@ -0,0 +1,153 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Serialization formats of the payloads written by outputs
+//!
+//! Payloads are JSON documents, one per row, re-encoded in the configured format.
+
+use crate::{Bytes, Error};
+use bson::{Bson, Document};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Serialization format of the payloads written by an output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    #[default]
+    Json,
+    #[serde(alias = "msgpack")]
+    MessagePack,
+    Cbor,
+    /// Each payload must be a JSON object, BSON documents cannot hold other values
+    Bson,
+}
+
+impl OutputFormat {
+    /// MIME type of the payloads
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            OutputFormat::Json => "application/json",
+            OutputFormat::MessagePack => "application/msgpack",
+            OutputFormat::Cbor => "application/cbor",
+            OutputFormat::Bson => "application/bson",
+        }
+    }
+
+    /// Re-encode a JSON payload in this format
+    pub fn encode_json(&self, payload: &[u8]) -> Result<Bytes, Error> {
+        if *self == OutputFormat::Json {
+            return Ok(payload.to_vec());
+        }
+        let value: Value = serde_json::from_slice(payload)
+            .map_err(|e| Error::Process(format!("Payload is not valid JSON: {}", e)))?;
+        self.encode(&value)
+    }
+
+    /// Encode a value in this format
+    pub fn encode(&self, value: &Value) -> Result<Bytes, Error> {
+        let mut buf = Vec::new();
+        match self {
+            OutputFormat::Json => serde_json::to_writer(&mut buf, value)?,
+            OutputFormat::MessagePack => rmp_serde::encode::write(&mut buf, value)
+                .map_err(|e| Error::Process(format!("MessagePack serialization error: {}", e)))?,
+            OutputFormat::Cbor => ciborium::into_writer(value, &mut buf)
+                .map_err(|e| Error::Process(format!("CBOR serialization error: {}", e)))?,
+            OutputFormat::Bson => {
+                let Value::Object(document) = value else {
+                    return Err(Error::Process(
+                        "BSON payloads must be JSON objects".to_string(),
+                    ));
+                };
+                bson_document(document)
+                    .to_writer(&mut buf)
+                    .map_err(|e| Error::Process(format!("BSON serialization error: {}", e)))?;
+            }
+        }
+        Ok(buf)
+    }
+}
+
+/// BSON document of a JSON object. Integers are stored as 32-bit integers if they fit, as
+/// 64-bit integers otherwise, and as doubles above `i64::MAX`.
+fn bson_document(object: &Map<String, Value>) -> Document {
+    object
+        .iter()
+        .map(|(key, value)| (key.clone(), bson_value(value)))
+        .collect()
+}
+
+fn bson_value(value: &Value) -> Bson {
+    match value {
+        Value::Null => Bson::Null,
+        Value::Bool(b) => Bson::Boolean(*b),
+        Value::Number(n) => match n.as_i64() {
+            Some(v) => i32::try_from(v).map_or(Bson::Int64(v), Bson::Int32),
+            None => Bson::Double(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        Value::String(s) => Bson::String(s.clone()),
+        Value::Array(values) => Bson::Array(values.iter().map(bson_value).collect()),
+        Value::Object(object) => Bson::Document(bson_document(object)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_pack_round_trip() {
+        let value = serde_json::json!({
+            "null": null,
+            "bool": true,
+            "ints": [0, 127, 128, -1, -33, 70000, -70000, u64::MAX, i64::MIN],
+            "float": 1.5,
+            "text": "x".repeat(300),
+            "nested": {"list": [], "map": {}},
+        });
+        let encoded = OutputFormat::MessagePack.encode(&value).unwrap();
+        let decoded: Value = rmp_serde::from_slice(&encoded).unwrap();
+        assert_eq!(decoded, value);
+    }
+
+    #[test]
+    fn test_bson_round_trip() {
+        let value = serde_json::json!({
+            "null": null,
+            "bool": false,
+            "small": 1,
+            "large": 1_i64 << 40,
+            "huge": u64::MAX,
+            "float": 1.5,
+            "text": "x",
+            "list": [1, "a", {"b": null}],
+            "nested": {"c": -2},
+        });
+        let encoded = OutputFormat::Bson.encode(&value).unwrap();
+        let document = Document::from_reader(encoded.as_slice()).unwrap();
+        assert_eq!(document.get("small"), Some(&Bson::Int32(1)));
+        assert_eq!(document.get("large"), Some(&Bson::Int64(1 << 40)));
+        assert_eq!(document.get("huge"), Some(&Bson::Double(u64::MAX as f64)));
+
+        let decoded = Bson::Document(document).into_relaxed_extjson();
+        let mut expected = value;
+        expected["huge"] = serde_json::json!(u64::MAX as f64);
+        assert_eq!(decoded, expected);
+
+        // Keys are C strings in BSON
+        assert!(OutputFormat::Bson
+            .encode(&serde_json::json!({"a\0b": 1}))
+            .is_err());
+    }
+}
@@ -23,6 +23,9 @@ use std::sync::{Arc, RwLock};
 
 use crate::{Error, MessageBatch, Resource};
 
+mod format;
+pub use format::OutputFormat;
+
 lazy_static::lazy_static! {
     static ref OUTPUT_BUILDERS: RwLock<HashMap<String, Arc<dyn OutputBuilder>>> = RwLock::new(HashMap::new());
 }
@@ -37,6 +40,32 @@ pub trait Output: Send + Sync {
 
     /// Close the output destination connection
     async fn close(&self) -> Result<(), Error>;
+
+    /// Transactions of the output, `None` if its writes cannot be rolled back
+    fn transaction_coordinator(&self) -> Option<&dyn TransactionCoordinator> {
+        None
+    }
+}
+
+/// Identifier of a transaction opened by a [`TransactionCoordinator`]
+pub type TransactionId = u64;
+
+/// Transactions of an output, so that a batch can be written to several outputs before any of
+/// them commits it.
+///
+/// Writes made between [`TransactionCoordinator::begin`] and the commit or rollback of the
+/// transaction are part of it. Outputs have at most one open transaction.
+#[async_trait]
+pub trait TransactionCoordinator: Send + Sync {
+    /// Open a transaction
+    async fn begin(&self) -> Result<TransactionId, Error>;
+
+    /// Commit the writes of the open transaction `id`
+    async fn commit(&self, id: TransactionId) -> Result<(), Error>;
+
+    /// Discard the writes of transaction `id`. Fails if they cannot be discarded, e.g. because
+    /// the transaction is already committed.
+    async fn rollback(&self, id: TransactionId) -> Result<(), Error>;
 }
 
 /// Output configuration
@@ -50,6 +79,15 @@ pub struct OutputConfig {
 }
 
 impl OutputConfig {
+    /// Create the configuration of an output of type `type_name` from a typed configuration
+    pub fn from_typed<T: Serialize>(config: &T, type_name: &str) -> Result<Self, Error> {
+        Ok(Self {
+            output_type: type_name.to_string(),
+            name: None,
+            config: crate::config::component_config(config)?,
+        })
+    }
+
     /// Build the output component according to the configuration
     pub fn build(&self, resource: &Resource) -> Result<Arc<dyn Output>, Error> {
         let builders = OUTPUT_BUILDERS.read().unwrap();
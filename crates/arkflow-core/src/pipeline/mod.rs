@@ -18,28 +18,182 @@
 
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use std::time::Duration;
+use tracing::Instrument;
 
+use crate::config::schema_registry::SchemaRegistry;
+use crate::input::Ack;
+use crate::trace::SpanContext;
 use crate::{processor::Processor, Error, MessageBatch, Resource};
+use schema_coercion::{CastFailurePolicy, FieldSpec, SchemaCoercionLayer};
+use watermark::{watermark_batch, watermark_of, WatermarkConfig, WatermarkGenerator};
+
+pub mod schema_coercion;
+pub mod watermark;
+
+/// Table name of messages whose input has no name
+pub(crate) const DEFAULT_SCHEMA_TABLE: &str = "default";
 
 pub struct Pipeline {
     processors: Vec<Arc<dyn Processor>>,
+    schema_registry: Option<Arc<SchemaRegistry>>,
+    schema_coercion: Option<SchemaCoercionLayer>,
+    retry: Option<ProcessorRetryConfig>,
+    watermark: Option<WatermarkGenerator>,
 }
 
 impl Pipeline {
     /// Create a new pipeline
     pub fn new(processors: Vec<Arc<dyn Processor>>) -> Self {
-        Self { processors }
+        Self {
+            processors,
+            schema_registry: None,
+            schema_coercion: None,
+            retry: None,
+            watermark: None,
+        }
+    }
+
+    /// Retry messages whose processing failed according to `retry`
+    pub fn with_retry(mut self, retry: ProcessorRetryConfig) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Retry policy of messages whose processing failed
+    pub fn retry(&self) -> Option<&ProcessorRetryConfig> {
+        self.retry.as_ref()
+    }
+
+    /// Track the watermark of incoming messages and pass it to the processors accepting
+    /// watermarks after every message
+    pub fn with_watermark(mut self, config: WatermarkConfig) -> Self {
+        self.watermark = Some(WatermarkGenerator::new(config));
+        self
+    }
+
+    /// Current watermark, `None` if watermarks are not tracked or no timestamp was seen yet
+    pub fn watermark(&self) -> Option<i64> {
+        self.watermark.as_ref().and_then(|w| w.watermark())
+    }
+
+    /// Check the schema of incoming messages against `registry` before processing them
+    pub fn with_schema_registry(mut self, registry: Arc<SchemaRegistry>) -> Self {
+        self.schema_registry = Some(registry);
+        self
+    }
+
+    /// Cast the columns of incoming messages to the schema of `layer` before processing them
+    pub fn with_schema_coercion(mut self, layer: SchemaCoercionLayer) -> Self {
+        self.schema_coercion = Some(layer);
+        self
     }
 
     /// Process messages
+    ///
+    /// Messages carrying a trace context are processed in a child span, whose context is
+    /// attached to the resulting messages. Results created by a processor without a trace
+    /// context inherit the context of the message they were created from.
+    ///
+    /// The acks processors added with [`MessageBatch::on_ack`] are attached to the first
+    /// resulting message, even if a later processor replaced or dropped the message they were
+    /// added to. They are called right away if there are no resulting messages.
     pub async fn process(&self, msg: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
-        let mut msgs = vec![msg];
+        let Some(parent) = msg.extract_span_context() else {
+            return self.process_messages(msg).await;
+        };
+
+        let context = parent.child();
+        let span = if parent.sampled {
+            tracing::info_span!(
+                "pipeline",
+                trace_id = %format!("{:032x}", context.trace_id),
+                span_id = %format!("{:016x}", context.span_id),
+                parent_span_id = %format!("{:016x}", parent.span_id),
+            )
+        } else {
+            tracing::Span::none()
+        };
+        let mut msgs = self.process_messages(msg).instrument(span).await?;
+        for msg in &mut msgs {
+            // Keep the sampling decisions of the processors, e.g. a sampler
+            if let Some(current) = msg
+                .extract_span_context()
+                .filter(|current| current.trace_id == parent.trace_id)
+            {
+                msg.inject_span_context(&SpanContext {
+                    sampled: current.sampled,
+                    ..context.clone()
+                });
+            }
+        }
+        Ok(msgs)
+    }
+
+    async fn process_messages(&self, mut msg: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+        let mut acks = msg.take_acks();
+        let msg = match &self.schema_coercion {
+            Some(layer) => layer.coerce(msg)?,
+            None => msg,
+        };
+        if let Some(registry) = &self.schema_registry {
+            let input_name = msg.get_input_name();
+            let table = input_name.as_deref().unwrap_or(DEFAULT_SCHEMA_TABLE);
+            registry.check(table, &msg.schema())?;
+        }
+
+        let watermark = match &self.watermark {
+            Some(generator) => generator.observe(&msg)?,
+            None => None,
+        };
+        let mut msgs = self.run_processors(vec![msg], &mut acks).await?;
+        if let Some(watermark) = watermark {
+            msgs.extend(
+                self.run_processors(vec![watermark_batch(watermark)?], &mut acks)
+                    .await?,
+            );
+            msgs.retain(|msg| watermark_of(msg).is_none());
+        }
+
+        match msgs.first_mut() {
+            Some(first) => acks.into_iter().for_each(|ack| first.on_ack(ack)),
+            None => {
+                for ack in acks {
+                    ack.ack().await;
+                }
+            }
+        }
+        Ok(msgs)
+    }
+
+    /// Run the messages through every processor. Watermark batches are only processed by the
+    /// processors accepting them, and passed on to the next processor. The acks added by the
+    /// processors are moved to `acks`.
+    async fn run_processors(
+        &self,
+        mut msgs: Vec<MessageBatch>,
+        acks: &mut Vec<Arc<dyn Ack>>,
+    ) -> Result<Vec<MessageBatch>, Error> {
         for processor in &self.processors {
             let mut new_msgs = Vec::with_capacity(msgs.len());
             for msg in msgs {
-                match processor.process(msg).await {
-                    Ok(processed) => new_msgs.extend(processed),
-                    Err(e) => return Err(e),
+                if watermark_of(&msg).is_some() {
+                    if processor.accepts_watermarks() {
+                        for mut processed in processor.process(msg.clone()).await? {
+                            acks.extend(processed.take_acks());
+                            new_msgs.push(processed);
+                        }
+                    }
+                    new_msgs.push(msg);
+                    continue;
+                }
+                let context = msg.extract_span_context();
+                for mut processed in processor.process(msg).await? {
+                    acks.extend(processed.take_acks());
+                    if let (Some(context), None) = (&context, processed.extract_span_context()) {
+                        processed.inject_span_context(context);
+                    }
+                    new_msgs.push(processed);
                 }
             }
             msgs = new_msgs;
@@ -59,22 +213,366 @@ impl Pipeline {
 /// Pipeline configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PipelineConfig {
-    #[serde(default = "default_thread_num")]
+    #[serde(
+        default = "default_thread_num",
+        deserialize_with = "crate::config::deserialize_number"
+    )]
     pub thread_num: u32,
     pub processors: Vec<crate::processor::ProcessorConfig>,
+    /// Directory of the schema registry. If set, the schema of incoming messages is
+    /// checked for incompatible changes, keyed by the input name.
+    pub schema_registry_path: Option<String>,
+    /// Retry messages whose processing failed before sending them to the error output
+    pub retry: Option<ProcessorRetryConfig>,
+    /// Track the event-time watermark of incoming messages
+    pub watermark: Option<WatermarkConfig>,
+    /// Columns of incoming messages to cast to the declared type before processing them
+    pub input_schema: Option<Vec<FieldSpec>>,
+    /// What happens to values of the input schema columns that cannot be cast
+    #[serde(default)]
+    pub on_cast_failure: CastFailurePolicy,
+}
+
+/// Retry policy of messages whose processing failed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessorRetryConfig {
+    /// Maximum number of times a message is processed, including the first attempt
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled after every failed retry
+    #[serde(default = "default_initial_delay_ms")]
+    pub initial_delay_ms: u64,
+    /// Upper bound of the delay between two attempts
+    #[serde(default = "default_max_delay_ms")]
+    pub max_delay_ms: u64,
+    /// Only errors whose message contains one of these strings are retried, all errors if empty
+    #[serde(default)]
+    pub retryable_errors: Vec<String>,
+}
+
+impl ProcessorRetryConfig {
+    /// Whether a message that failed with `error` on attempt `attempt` should be processed again
+    pub fn should_retry(&self, error: &Error, attempt: u32) -> bool {
+        if attempt >= self.max_attempts {
+            return false;
+        }
+        if self.retryable_errors.is_empty() {
+            return true;
+        }
+        let message = error.to_string();
+        self.retryable_errors
+            .iter()
+            .any(|pattern| message.contains(pattern.as_str()))
+    }
+
+    /// Delay before the attempt following attempt `attempt`
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u64 << attempt.saturating_sub(1).min(63);
+        Duration::from_millis(
+            self.initial_delay_ms
+                .saturating_mul(factor)
+                .min(self.max_delay_ms),
+        )
+    }
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_initial_delay_ms() -> u64 {
+    100
+}
+
+fn default_max_delay_ms() -> u64 {
+    10_000
 }
 
 impl PipelineConfig {
+    /// Start building a pipeline in code instead of from a configuration file
+    pub fn builder() -> PipelineBuilder {
+        PipelineBuilder {
+            config: PipelineConfig {
+                thread_num: default_thread_num(),
+                processors: Vec::new(),
+                schema_registry_path: None,
+                retry: None,
+                watermark: None,
+                input_schema: None,
+                on_cast_failure: CastFailurePolicy::default(),
+            },
+        }
+    }
+
     /// Build pipelines based on your configuration
     pub fn build(&self, resource: &Resource) -> Result<(Pipeline, u32), Error> {
         let mut processors = Vec::with_capacity(self.processors.len());
         for processor_config in &self.processors {
             processors.push(processor_config.build(resource)?);
         }
-        Ok((Pipeline::new(processors), self.thread_num))
+        let mut pipeline = Pipeline::new(processors);
+        if let Some(path) = &self.schema_registry_path {
+            pipeline = pipeline.with_schema_registry(Arc::new(SchemaRegistry::new(path)?));
+        }
+        if let Some(input_schema) = &self.input_schema {
+            pipeline = pipeline.with_schema_coercion(SchemaCoercionLayer::new(
+                input_schema,
+                self.on_cast_failure,
+            )?);
+        }
+        if let Some(retry) = &self.retry {
+            pipeline = pipeline.with_retry(retry.clone());
+        }
+        if let Some(watermark) = &self.watermark {
+            pipeline = pipeline.with_watermark(watermark.clone());
+        }
+        Ok((pipeline, self.thread_num))
+    }
+}
+
+/// Fluent construction of a [`PipelineConfig`], see [`PipelineConfig::builder`]
+pub struct PipelineBuilder {
+    config: PipelineConfig,
+}
+
+impl PipelineBuilder {
+    /// Append a processor of type `type_name` with a typed configuration
+    pub fn with_processor<T: Serialize>(
+        mut self,
+        config: &T,
+        type_name: &str,
+    ) -> Result<Self, Error> {
+        self.config
+            .processors
+            .push(crate::processor::ProcessorConfig::from_typed(
+                config, type_name,
+            )?);
+        Ok(self)
+    }
+
+    /// Set the number of processor workers
+    pub fn with_thread_num(mut self, thread_num: u32) -> Self {
+        self.config.thread_num = thread_num;
+        self
+    }
+
+    /// Retry messages whose processing failed according to `retry`
+    pub fn with_retry(mut self, retry: ProcessorRetryConfig) -> Self {
+        self.config.retry = Some(retry);
+        self
+    }
+
+    /// Build the pipeline and return it with its number of workers.
+    ///
+    /// The processors are built without temporary resources.
+    pub fn build(&self) -> Result<(Pipeline, u32), Error> {
+        let resource = Resource {
+            temporary: Default::default(),
+            input_names: Default::default(),
+        };
+        self.config.build(&resource)
+    }
+
+    /// The configuration built so far, e.g. to use in a [`crate::stream::StreamConfig`]
+    pub fn into_config(self) -> PipelineConfig {
+        self.config
     }
 }
 
 fn default_thread_num() -> u32 {
     num_cpus::get() as u32
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::ProcessorBuilder;
+    use crate::DEFAULT_BINARY_VALUE_FIELD;
+    use async_trait::async_trait;
+    use datafusion::arrow::array::{ArrayRef, Int64Array};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use std::sync::Mutex;
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    /// Processor replacing every message with a new one holding its value
+    struct ReplaceProcessor(String);
+
+    #[async_trait]
+    impl Processor for ReplaceProcessor {
+        async fn process(&self, _batch: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+            Ok(vec![MessageBatch::from_string(&self.0)?])
+        }
+
+        async fn close(&self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    fn replace(value: &str) -> Arc<dyn Processor> {
+        Arc::new(ReplaceProcessor(value.to_string()))
+    }
+
+    #[derive(Serialize)]
+    struct ReplaceConfig {
+        value: String,
+    }
+
+    struct ReplaceProcessorBuilder;
+
+    impl ProcessorBuilder for ReplaceProcessorBuilder {
+        fn build(
+            &self,
+            _name: Option<&String>,
+            config: &Option<serde_json::Value>,
+            _resource: &Resource,
+        ) -> Result<Arc<dyn Processor>, Error> {
+            let value = config
+                .as_ref()
+                .and_then(|config| config["value"].as_str())
+                .ok_or_else(|| Error::Config("Value is missing".to_string()))?;
+            Ok(replace(value))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_builder() {
+        crate::processor::register_processor_builder(
+            "pipeline_builder_replace",
+            Arc::new(ReplaceProcessorBuilder),
+        )
+        .unwrap();
+        let (pipeline, thread_num) = PipelineConfig::builder()
+            .with_processor(
+                &ReplaceConfig {
+                    value: "built".to_string(),
+                },
+                "pipeline_builder_replace",
+            )
+            .unwrap()
+            .with_thread_num(2)
+            .build()
+            .unwrap();
+        assert_eq!(thread_num, 2);
+        let results = pipeline
+            .process(MessageBatch::from_string("a").unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            results[0].to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap(),
+            vec![b"built".as_slice()]
+        );
+
+        // Configurations must be maps, and processor types are only checked when building
+        assert!(PipelineConfig::builder()
+            .with_processor(&42, "pipeline_builder_replace")
+            .is_err());
+        let unknown = PipelineConfig::builder()
+            .with_processor(&(), "no_such_processor")
+            .unwrap();
+        assert!(unknown.build().is_err());
+        assert_eq!(unknown.into_config().processors.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_propagates_trace_context() {
+        // Processors create new messages, which must still carry the trace context
+        let pipeline = Pipeline::new(vec![replace("replaced"), replace("replaced")]);
+
+        let parent = SpanContext::from_traceparent(TRACEPARENT).unwrap();
+        let mut msg = MessageBatch::from_string(r#"{"value": 1}"#).unwrap();
+        msg.inject_span_context(&parent);
+        let results = pipeline.process(msg).await.unwrap();
+
+        assert_eq!(results.len(), 1);
+        let context = results[0].extract_span_context().unwrap();
+        assert_eq!(context.trace_id, parent.trace_id);
+        assert_ne!(context.span_id, parent.span_id);
+
+        // Untraced messages stay untraced
+        let msg = MessageBatch::from_string(r#"{"value": 1}"#).unwrap();
+        let results = pipeline.process(msg).await.unwrap();
+        assert!(results[0].extract_span_context().is_none());
+    }
+
+    /// Processor recording the batches it is called with, emitting a string batch per watermark
+    #[derive(Default)]
+    struct RecordingProcessor {
+        accepts_watermarks: bool,
+        watermarks: Mutex<Vec<i64>>,
+        batches: Mutex<usize>,
+    }
+
+    #[async_trait]
+    impl Processor for RecordingProcessor {
+        async fn process(&self, batch: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+            if let Some(watermark) = watermark_of(&batch) {
+                self.watermarks.lock().unwrap().push(watermark);
+                return Ok(vec![MessageBatch::from_string(&format!(
+                    "window {}",
+                    watermark
+                ))?]);
+            }
+            *self.batches.lock().unwrap() += 1;
+            Ok(vec![batch])
+        }
+
+        async fn close(&self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn accepts_watermarks(&self) -> bool {
+            self.accepts_watermarks
+        }
+    }
+
+    fn batch(timestamps: Vec<i64>) -> MessageBatch {
+        MessageBatch::new_arrow(
+            RecordBatch::try_from_iter([(
+                "ts",
+                Arc::new(Int64Array::from(timestamps)) as ArrayRef,
+            )])
+            .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_watermarks_reach_accepting_processors_only() {
+        let window = Arc::new(RecordingProcessor {
+            accepts_watermarks: true,
+            ..Default::default()
+        });
+        let passthrough = Arc::new(RecordingProcessor::default());
+        let pipeline = Pipeline::new(vec![window.clone(), passthrough.clone()]).with_watermark(
+            WatermarkConfig {
+                timestamp_column: "ts".to_string(),
+                max_out_of_orderness_ms: 100,
+            },
+        );
+        assert_eq!(pipeline.watermark(), None);
+
+        let results = pipeline.process(batch(vec![1000, 1500])).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|msg| watermark_of(msg).is_none()));
+        assert_eq!(
+            results[1].to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap(),
+            vec![b"window 1400".as_slice()]
+        );
+
+        pipeline.process(batch(vec![1200])).await.unwrap();
+        assert_eq!(pipeline.watermark(), Some(1400));
+        assert_eq!(*window.watermarks.lock().unwrap(), vec![1400, 1400]);
+        // The window results reach the next processor, the watermarks do not
+        assert!(passthrough.watermarks.lock().unwrap().is_empty());
+        assert_eq!(*passthrough.batches.lock().unwrap(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_missing_timestamp_column() {
+        let pipeline = Pipeline::new(vec![]).with_watermark(WatermarkConfig {
+            timestamp_column: "event_time".to_string(),
+            max_out_of_orderness_ms: 0,
+        });
+        assert!(pipeline.process(batch(vec![1])).await.is_err());
+    }
+}
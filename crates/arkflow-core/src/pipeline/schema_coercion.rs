@@ -0,0 +1,233 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Schema coercion of the batches entering a pipeline
+//!
+//! Inputs do not always produce the types the processors expect, e.g. an Arrow IPC producer
+//! sending `Int32` for a column the SQL of a processor compares with `Int64` values. A pipeline
+//! with an input schema casts the declared columns of every incoming batch to their declared
+//! type before processing it.
+
+use crate::{Error, MessageBatch};
+use datafusion::arrow::array::{new_null_array, ArrayRef};
+use datafusion::arrow::compute::{cast_with_options, CastOptions};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Declared column of the input schema of a pipeline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldSpec {
+    /// Column name
+    pub name: String,
+    /// Arrow data type of the column, e.g. `Int64`, `Utf8` or `Timestamp(Millisecond, None)`
+    pub data_type: String,
+    /// Whether the column may hold nulls
+    #[serde(default = "default_nullable")]
+    pub nullable: bool,
+}
+
+fn default_nullable() -> bool {
+    true
+}
+
+/// What happens to values that cannot be cast to their declared type
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CastFailurePolicy {
+    /// The value is replaced with null
+    #[default]
+    Null,
+    /// Processing the batch fails
+    Error,
+}
+
+/// Casts the columns of incoming batches to the declared input schema.
+///
+/// Columns that are not declared are kept as they are, and declared columns missing from a
+/// batch are added as null columns.
+pub struct SchemaCoercionLayer {
+    fields: Vec<Field>,
+    on_cast_failure: CastFailurePolicy,
+}
+
+impl SchemaCoercionLayer {
+    pub fn new(fields: &[FieldSpec], on_cast_failure: CastFailurePolicy) -> Result<Self, Error> {
+        let fields = fields
+            .iter()
+            .map(|spec| {
+                let data_type = DataType::from_str(&spec.data_type).map_err(|e| {
+                    Error::Config(format!(
+                        "Invalid data type {} of input schema column {}: {}",
+                        spec.data_type, spec.name, e
+                    ))
+                })?;
+                Ok(Field::new(&spec.name, data_type, spec.nullable))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Self {
+            fields,
+            on_cast_failure,
+        })
+    }
+
+    /// Cast the declared columns of `msg` to their declared type
+    pub fn coerce(&self, msg: MessageBatch) -> Result<MessageBatch, Error> {
+        let schema = msg.schema();
+        if self.fields.iter().all(|field| {
+            schema
+                .field_with_name(field.name())
+                .is_ok_and(|current| current.data_type() == field.data_type())
+        }) {
+            return Ok(msg);
+        }
+
+        let options = CastOptions {
+            safe: self.on_cast_failure == CastFailurePolicy::Null,
+            ..Default::default()
+        };
+        let mut fields = Vec::with_capacity(schema.fields().len());
+        let mut columns: Vec<ArrayRef> = Vec::with_capacity(schema.fields().len());
+        for (current, column) in schema.fields().iter().zip(msg.columns()) {
+            match self
+                .fields
+                .iter()
+                .find(|field| field.name() == current.name())
+            {
+                Some(field) => {
+                    let column =
+                        cast_with_options(column, field.data_type(), &options).map_err(|e| {
+                            Error::Process(format!(
+                                "Casting column {} from {} to {} failed: {}",
+                                field.name(),
+                                current.data_type(),
+                                field.data_type(),
+                                e
+                            ))
+                        })?;
+                    fields.push(field.clone().with_metadata(current.metadata().clone()));
+                    columns.push(column);
+                }
+                None => {
+                    fields.push(current.as_ref().clone());
+                    columns.push(Arc::clone(column));
+                }
+            }
+        }
+        for field in &self.fields {
+            if schema.field_with_name(field.name()).is_err() {
+                fields.push(field.clone());
+                columns.push(new_null_array(field.data_type(), msg.num_rows()));
+            }
+        }
+
+        let schema = Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()));
+        let batch = RecordBatch::try_new(schema, columns)
+            .map_err(|e| Error::Process(format!("Creating an Arrow record batch failed: {}", e)))?;
+        let mut coerced = MessageBatch::new_arrow(batch);
+        coerced.set_input_name(msg.get_input_name());
+        if let Some(context) = msg.extract_span_context() {
+            coerced.inject_span_context(&context);
+        }
+        Ok(coerced)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Array, Int32Array, Int64Array, StringArray};
+
+    fn layer(on_cast_failure: CastFailurePolicy) -> SchemaCoercionLayer {
+        let spec = |name: &str, data_type: &str| FieldSpec {
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            nullable: true,
+        };
+        SchemaCoercionLayer::new(
+            &[
+                spec("id", "Int64"),
+                spec("amount", "Int64"),
+                spec("note", "Utf8"),
+            ],
+            on_cast_failure,
+        )
+        .unwrap()
+    }
+
+    fn batch() -> MessageBatch {
+        let batch = RecordBatch::try_from_iter([
+            ("id", Arc::new(Int32Array::from(vec![1, 2])) as ArrayRef),
+            (
+                "amount",
+                Arc::new(StringArray::from(vec!["10", "ten"])) as ArrayRef,
+            ),
+            ("other", Arc::new(Int32Array::from(vec![3, 4])) as ArrayRef),
+        ])
+        .unwrap();
+        let mut msg = MessageBatch::new_arrow(batch);
+        msg.set_input_name(Some("kafka".to_string()));
+        msg
+    }
+
+    #[test]
+    fn test_coerce_null_on_failure() {
+        let msg = layer(CastFailurePolicy::Null).coerce(batch()).unwrap();
+        assert_eq!(msg.get_input_name().as_deref(), Some("kafka"));
+
+        let schema = msg.schema();
+        let types: Vec<_> = schema
+            .fields()
+            .iter()
+            .map(|field| (field.name().as_str(), field.data_type().clone()))
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                ("id", DataType::Int64),
+                ("amount", DataType::Int64),
+                ("other", DataType::Int32),
+                ("note", DataType::Utf8),
+            ]
+        );
+        let amount = msg.column(1).as_any().downcast_ref::<Int64Array>().unwrap();
+        assert_eq!(amount.value(0), 10);
+        assert!(amount.is_null(1));
+        assert_eq!(msg.column(3).null_count(), 2);
+    }
+
+    #[test]
+    fn test_coerce_error_on_failure() {
+        let err = layer(CastFailurePolicy::Error)
+            .coerce(batch())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Casting column amount"), "{}", err);
+    }
+
+    #[test]
+    fn test_invalid_data_type() {
+        let spec = FieldSpec {
+            name: "id".to_string(),
+            data_type: "Integer".to_string(),
+            nullable: true,
+        };
+        assert!(matches!(
+            SchemaCoercionLayer::new(&[spec], CastFailurePolicy::Null),
+            Err(Error::Config(_))
+        ));
+    }
+}
@@ -0,0 +1,172 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Event-time watermarks
+//!
+//! The watermark of a pipeline is the maximum event timestamp seen so far minus the allowed
+//! out-of-orderness: no row older than the watermark is expected anymore. After every batch,
+//! the pipeline passes a watermark batch to the processors accepting watermarks, e.g. so that
+//! windows ending before the watermark are emitted. Watermark batches are binary batches
+//! holding the watermark in milliseconds, marked by the [`WATERMARK_METADATA_KEY`] schema
+//! metadata, and never leave the pipeline.
+
+use crate::{Error, MessageBatch};
+use datafusion::arrow::array::{Array, Int64Array};
+use datafusion::arrow::compute::{cast, max};
+use datafusion::arrow::datatypes::{DataType, TimeUnit};
+use datafusion::arrow::record_batch::RecordBatch;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Schema metadata key marking watermark batches
+pub const WATERMARK_METADATA_KEY: &str = "_watermark";
+
+/// Watermark configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatermarkConfig {
+    /// Column holding the event time of the rows entering the pipeline, a timestamp, an
+    /// integer of milliseconds since the Unix epoch, or a string
+    pub timestamp_column: String,
+    /// How late a row may arrive relative to the latest row
+    #[serde(default)]
+    pub max_out_of_orderness_ms: u64,
+}
+
+/// Running watermark of the batches entering a pipeline
+pub struct WatermarkGenerator {
+    config: WatermarkConfig,
+    /// Maximum event timestamp seen so far
+    max_timestamp: Mutex<Option<i64>>,
+}
+
+impl WatermarkGenerator {
+    pub fn new(config: WatermarkConfig) -> Self {
+        Self {
+            config,
+            max_timestamp: Mutex::new(None),
+        }
+    }
+
+    /// Account for the rows of `msg` and return the watermark, `None` until a timestamp is seen
+    pub fn observe(&self, msg: &MessageBatch) -> Result<Option<i64>, Error> {
+        let timestamps = timestamp_millis(msg, &self.config.timestamp_column)?;
+        let mut max_timestamp = self.max_timestamp.lock().unwrap();
+        if let Some(batch_max) = max(&timestamps) {
+            *max_timestamp = Some(max_timestamp.map_or(batch_max, |m| m.max(batch_max)));
+        }
+        Ok(self.watermark_of(*max_timestamp))
+    }
+
+    /// Current watermark, `None` until a timestamp is seen
+    pub fn watermark(&self) -> Option<i64> {
+        self.watermark_of(*self.max_timestamp.lock().unwrap())
+    }
+
+    fn watermark_of(&self, max_timestamp: Option<i64>) -> Option<i64> {
+        let out_of_orderness =
+            i64::try_from(self.config.max_out_of_orderness_ms).unwrap_or(i64::MAX);
+        max_timestamp.map(|m| m.saturating_sub(out_of_orderness))
+    }
+}
+
+/// Event timestamps of `column` in milliseconds since the Unix epoch
+pub fn timestamp_millis(batch: &RecordBatch, column: &str) -> Result<Int64Array, Error> {
+    let values = batch
+        .column_by_name(column)
+        .ok_or_else(|| Error::Process(format!("Timestamp column {} not found", column)))?;
+    let timestamps = cast(values, &DataType::Timestamp(TimeUnit::Millisecond, None))
+        .and_then(|timestamps| cast(&timestamps, &DataType::Int64))
+        .map_err(|e| {
+            Error::Process(format!("Failed to read timestamp column {}: {}", column, e))
+        })?;
+    timestamps
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .cloned()
+        .ok_or_else(|| Error::Process(format!("Invalid timestamp column {}", column)))
+}
+
+/// Batch carrying the watermark `watermark_ms` through the pipeline
+pub fn watermark_batch(watermark_ms: i64) -> Result<MessageBatch, Error> {
+    let batch: RecordBatch =
+        MessageBatch::new_binary(vec![watermark_ms.to_le_bytes().to_vec()])?.into();
+    let metadata = HashMap::from([(WATERMARK_METADATA_KEY.to_string(), "true".to_string())]);
+    let schema = Arc::new(batch.schema().as_ref().clone().with_metadata(metadata));
+    let batch = batch
+        .with_schema(schema)
+        .map_err(|e| Error::Process(format!("Creating an Arrow record batch failed: {}", e)))?;
+    Ok(MessageBatch::new_arrow(batch))
+}
+
+/// Watermark carried by `msg`, `None` if it is not a watermark batch
+pub fn watermark_of(msg: &MessageBatch) -> Option<i64> {
+    let schema = msg.schema();
+    if schema
+        .metadata()
+        .get(WATERMARK_METADATA_KEY)
+        .map(String::as_str)
+        != Some("true")
+    {
+        return None;
+    }
+    let bytes = msg.to_binary(crate::DEFAULT_BINARY_VALUE_FIELD).ok()?;
+    Some(i64::from_le_bytes(
+        bytes.first()?.get(..8)?.try_into().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{ArrayRef, StringArray};
+
+    fn batch(column: ArrayRef) -> MessageBatch {
+        MessageBatch::new_arrow(RecordBatch::try_from_iter([("ts", column)]).unwrap())
+    }
+
+    #[test]
+    fn test_watermark_generator() {
+        let generator = WatermarkGenerator::new(WatermarkConfig {
+            timestamp_column: "ts".to_string(),
+            max_out_of_orderness_ms: 1000,
+        });
+        assert_eq!(generator.watermark(), None);
+
+        let msg = batch(Arc::new(Int64Array::from(vec![
+            Some(5000),
+            None,
+            Some(8000),
+        ])));
+        assert_eq!(generator.observe(&msg).unwrap(), Some(7000));
+        // Late rows do not move the watermark back
+        let msg = batch(Arc::new(StringArray::from(vec!["1970-01-01T00:00:03Z"])));
+        assert_eq!(generator.observe(&msg).unwrap(), Some(7000));
+
+        let msg = batch(Arc::new(Int64Array::from(vec![None::<i64>])));
+        assert_eq!(generator.observe(&msg).unwrap(), Some(7000));
+        let msg = MessageBatch::from_string("x").unwrap();
+        assert!(generator.observe(&msg).is_err());
+    }
+
+    #[test]
+    fn test_watermark_batch() {
+        let msg = watermark_batch(-42).unwrap();
+        assert_eq!(watermark_of(&msg), Some(-42));
+        assert!(msg.is_binary());
+
+        let msg = MessageBatch::new_binary(vec![7i64.to_le_bytes().to_vec()]).unwrap();
+        assert_eq!(watermark_of(&msg), None);
+    }
+}
@@ -35,6 +35,35 @@ pub trait Processor: Send + Sync {
 
     /// Turn off the processor
     async fn close(&self) -> Result<(), Error>;
+
+    /// State store used by the processor to persist state across restarts, if any
+    fn state_store(&self) -> Option<Arc<dyn StateStore>> {
+        None
+    }
+
+    /// Whether the pipeline passes watermark batches to the processor, see
+    /// [`crate::pipeline::watermark`]. Watermarks skip the other processors.
+    fn accepts_watermarks(&self) -> bool {
+        false
+    }
+}
+
+/// Key-value pair returned by [`StateStore::scan_prefix`]
+pub type StateEntry = (Vec<u8>, Vec<u8>);
+
+/// Persistent key-value store for stateful processors
+pub trait StateStore: Send + Sync {
+    /// Get the value stored under `key`
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Error>;
+
+    /// Store `value` under `key`, replacing any previous value
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<(), Error>;
+
+    /// Remove the value stored under `key`
+    fn delete(&self, key: &[u8]) -> Result<(), Error>;
+
+    /// Get all key-value pairs whose key starts with `prefix`, ordered by key
+    fn scan_prefix(&self, prefix: &[u8]) -> Result<Vec<StateEntry>, Error>;
 }
 
 /// Processor configuration
@@ -48,6 +77,15 @@ pub struct ProcessorConfig {
 }
 
 impl ProcessorConfig {
+    /// Create the configuration of a processor of type `type_name` from a typed configuration
+    pub fn from_typed<T: Serialize>(config: &T, type_name: &str) -> Result<Self, Error> {
+        Ok(Self {
+            processor_type: type_name.to_string(),
+            name: None,
+            config: crate::config::component_config(config)?,
+        })
+    }
+
     /// Build the processor components according to the configuration
     pub fn build(&self, resource: &Resource) -> Result<Arc<dyn Processor>, Error> {
         let builders = PROCESSOR_BUILDERS.read().unwrap();
@@ -0,0 +1,325 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Stream admin server
+//!
+//! An optional HTTP server per stream to inspect, pause and resume a running pipeline.
+
+use crate::engine::schema_reflection::{describe_field, get_registered_schemas};
+use crate::Error;
+use axum::extract::{Path, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Json};
+use axum::routing::{get, post};
+use axum::Router;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+/// Admin server configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminConfig {
+    /// Listening address, e.g. `127.0.0.1:8081`
+    pub address: String,
+}
+
+/// Counters of a running stream
+#[derive(Default)]
+pub(crate) struct StreamMetrics {
+    pub(crate) input_batches: AtomicU64,
+    pub(crate) input_rows: AtomicU64,
+    pub(crate) processor_errors: AtomicU64,
+    pub(crate) output_batches: AtomicU64,
+    pub(crate) output_errors: AtomicU64,
+}
+
+/// State shared between a stream and its admin server
+pub(crate) struct AdminState {
+    started: Instant,
+    /// Whether reading from the input is paused
+    pub(crate) paused: AtomicBool,
+    pub(crate) metrics: StreamMetrics,
+    /// When a message was last processed or written, reported by the liveness probe
+    last_activity: Mutex<Instant>,
+    /// Components of the stream, as reported by `GET /pipeline`
+    description: Value,
+}
+
+impl AdminState {
+    pub(crate) fn new(description: Value) -> Self {
+        Self {
+            started: Instant::now(),
+            paused: AtomicBool::new(false),
+            metrics: StreamMetrics::default(),
+            last_activity: Mutex::new(Instant::now()),
+            description,
+        }
+    }
+
+    /// Record that a message was processed or written
+    pub(crate) fn touch(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+    }
+
+    /// Time since a message was last processed or written
+    pub(crate) fn idle_time(&self) -> Duration {
+        self.last_activity.lock().unwrap().elapsed()
+    }
+}
+
+/// Start the admin server on a separate task. It stops when `cancellation_token` is cancelled.
+pub(crate) async fn start_admin_server(
+    config: &AdminConfig,
+    state: Arc<AdminState>,
+    cancellation_token: CancellationToken,
+) -> Result<(), Error> {
+    let app = Router::new()
+        .route("/health", get(handle_health))
+        .route("/metrics", get(handle_metrics))
+        .route("/pipeline", get(handle_pipeline))
+        .route("/pipeline/pause", post(handle_pause))
+        .route("/pipeline/resume", post(handle_resume))
+        .route("/schema", get(handle_schemas))
+        .route("/schema/:table_name", get(handle_schema))
+        .with_state(state);
+
+    let listener = TcpListener::bind(&config.address).await.map_err(|e| {
+        Error::Config(format!(
+            "Unable to bind admin server to {}: {}",
+            config.address, e
+        ))
+    })?;
+    info!("Starting admin server on {}", config.address);
+
+    tokio::spawn(async move {
+        let server = axum::serve(listener, app.into_make_service())
+            .with_graceful_shutdown(async move { cancellation_token.cancelled().await });
+        if let Err(e) = server.await {
+            error!("Admin server error: {}", e);
+        } else {
+            info!("Admin server stopped");
+        }
+    });
+    Ok(())
+}
+
+async fn handle_health(State(state): State<Arc<AdminState>>) -> Json<Value> {
+    let status = if state.paused.load(Ordering::SeqCst) {
+        "paused"
+    } else {
+        "running"
+    };
+    Json(json!({
+        "status": status,
+        "uptime_secs": state.started.elapsed().as_secs(),
+    }))
+}
+
+async fn handle_metrics(State(state): State<Arc<AdminState>>) -> impl IntoResponse {
+    let metrics = &state.metrics;
+    let mut body = String::new();
+    for (name, help, kind, value) in [
+        (
+            "arkflow_input_batches_total",
+            "Message batches read from the input",
+            "counter",
+            metrics.input_batches.load(Ordering::Relaxed),
+        ),
+        (
+            "arkflow_input_rows_total",
+            "Rows read from the input",
+            "counter",
+            metrics.input_rows.load(Ordering::Relaxed),
+        ),
+        (
+            "arkflow_processor_errors_total",
+            "Message batches that failed in the pipeline",
+            "counter",
+            metrics.processor_errors.load(Ordering::Relaxed),
+        ),
+        (
+            "arkflow_output_batches_total",
+            "Message batches written to the output",
+            "counter",
+            metrics.output_batches.load(Ordering::Relaxed),
+        ),
+        (
+            "arkflow_output_errors_total",
+            "Message batches that failed to be written to the output",
+            "counter",
+            metrics.output_errors.load(Ordering::Relaxed),
+        ),
+        (
+            "arkflow_paused",
+            "Whether reading from the input is paused",
+            "gauge",
+            state.paused.load(Ordering::SeqCst) as u64,
+        ),
+        (
+            "arkflow_uptime_seconds",
+            "Seconds since the stream started",
+            "gauge",
+            state.started.elapsed().as_secs(),
+        ),
+    ] {
+        let _ = writeln!(body, "# HELP {} {}", name, help);
+        let _ = writeln!(body, "# TYPE {} {}", name, kind);
+        let _ = writeln!(body, "{} {}", name, value);
+    }
+
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
+
+async fn handle_pipeline(State(state): State<Arc<AdminState>>) -> Json<Value> {
+    Json(state.description.clone())
+}
+
+async fn handle_schemas() -> impl IntoResponse {
+    let tables: Vec<String> = get_registered_schemas().into_keys().collect();
+    Json(json!({ "tables": tables }))
+}
+
+async fn handle_schema(Path(table_name): Path<String>) -> impl IntoResponse {
+    match get_registered_schemas().get(&table_name) {
+        Some(schema) => {
+            let fields: Vec<Value> = schema.fields().iter().map(|f| describe_field(f)).collect();
+            (
+                StatusCode::OK,
+                Json(json!({ "table": table_name, "fields": fields })),
+            )
+        }
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("No schema registered for table {}", table_name) })),
+        ),
+    }
+}
+
+async fn handle_pause(State(state): State<Arc<AdminState>>) -> impl IntoResponse {
+    state.paused.store(true, Ordering::SeqCst);
+    info!("Stream paused by admin request");
+    (StatusCode::OK, Json(json!({ "status": "paused" })))
+}
+
+async fn handle_resume(State(state): State<Arc<AdminState>>) -> impl IntoResponse {
+    state.paused.store(false, Ordering::SeqCst);
+    info!("Stream resumed by admin request");
+    (StatusCode::OK, Json(json!({ "status": "running" })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{Ack, Input, NoopAck};
+    use crate::stream::{Stream, PAUSE_POLL_INTERVAL};
+    use crate::MessageBatch;
+    use async_trait::async_trait;
+
+    #[derive(Default)]
+    struct CountingInput {
+        reads: AtomicU64,
+    }
+
+    #[async_trait]
+    impl Input for CountingInput {
+        async fn connect(&self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            let n = self.reads.fetch_add(1, Ordering::SeqCst);
+            Ok((
+                MessageBatch::from_string(&n.to_string())?,
+                Arc::new(NoopAck),
+            ))
+        }
+
+        async fn close(&self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_health() {
+        let state = Arc::new(AdminState::new(Value::Null));
+        let Json(health) = handle_health(State(state.clone())).await;
+        assert_eq!(health["status"], "running");
+        assert!(health["uptime_secs"].is_u64());
+
+        handle_pause(State(state.clone())).await;
+        let Json(health) = handle_health(State(state.clone())).await;
+        assert_eq!(health["status"], "paused");
+
+        handle_resume(State(state.clone())).await;
+        let Json(health) = handle_health(State(state)).await;
+        assert_eq!(health["status"], "running");
+    }
+
+    #[tokio::test]
+    async fn test_pipeline() {
+        let description = json!({
+            "input": "memory",
+            "processors": ["json_to_arrow", "sql"],
+            "output": "stdout",
+        });
+        let state = Arc::new(AdminState::new(description.clone()));
+        let Json(pipeline) = handle_pipeline(State(state)).await;
+        assert_eq!(pipeline, description);
+    }
+
+    #[tokio::test]
+    async fn test_pause_stops_input() {
+        let state = Arc::new(AdminState::new(Value::Null));
+        let input = Arc::new(CountingInput::default());
+        let (sender, receiver) = flume::unbounded();
+        let token = CancellationToken::new();
+        let task = tokio::spawn(Stream::do_input(
+            token.clone(),
+            input.clone(),
+            sender,
+            None,
+            state.clone(),
+            Arc::new(AtomicBool::new(false)),
+        ));
+        receiver.recv_async().await.unwrap();
+
+        handle_pause(State(state.clone())).await;
+        // Let a read already in flight complete
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let reads = input.reads.load(Ordering::SeqCst);
+        receiver.drain();
+
+        tokio::time::sleep(PAUSE_POLL_INTERVAL * 3).await;
+        assert_eq!(input.reads.load(Ordering::SeqCst), reads);
+        assert!(receiver.is_empty());
+        assert_eq!(state.metrics.input_batches.load(Ordering::Relaxed), reads);
+
+        handle_resume(State(state)).await;
+        tokio::time::timeout(PAUSE_POLL_INTERVAL * 10, receiver.recv_async())
+            .await
+            .expect("the input was not resumed")
+            .unwrap();
+        assert!(input.reads.load(Ordering::SeqCst) > reads);
+
+        token.cancel();
+        task.await.unwrap();
+    }
+}
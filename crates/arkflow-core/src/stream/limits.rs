@@ -0,0 +1,227 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Resource limit enforcement
+//!
+//! Moves the process into a child cgroup v2 with `memory.max` and `cpu.max` set, falling back
+//! to `setrlimit(RLIMIT_AS)` for the memory limit when cgroups cannot be used. Memory cannot be
+//! limited per thread, so the limits apply to the whole process; only one stream may set them.
+
+use super::ResourceLimits;
+use crate::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+/// Enforcement period written to `cpu.max`, in microseconds
+const CPU_PERIOD_US: u64 = 100_000;
+
+/// Applied limits, used to detect resource pressure
+pub(crate) struct Limiter {
+    /// Cgroup the process was moved to, `None` when only the rlimit fallback is in effect
+    cgroup: Option<PathBuf>,
+    memory_events: u64,
+    cpu_periods: u64,
+    cpu_throttled: u64,
+}
+
+impl Limiter {
+    /// Apply `limits` to the current process.
+    pub(crate) fn apply(limits: &ResourceLimits) -> Result<Self, Error> {
+        match apply_cgroup(limits) {
+            Ok(cgroup) => {
+                info!("Resource limits applied with cgroup {}", cgroup.display());
+                let mut limiter = Self {
+                    cgroup: Some(cgroup),
+                    memory_events: 0,
+                    cpu_periods: 0,
+                    cpu_throttled: 0,
+                };
+                limiter.pressure();
+                Ok(limiter)
+            }
+            Err(e) => {
+                warn!(
+                    "Unable to apply resource limits with cgroup v2, falling back to setrlimit: {}",
+                    e
+                );
+                if let Some(max_memory_bytes) = limits.max_memory_bytes {
+                    limit_address_space(max_memory_bytes).map_err(|e| {
+                        Error::Config(format!("Failed to set the memory limit: {}", e))
+                    })?;
+                }
+                if limits.max_cpu_percent.is_some() {
+                    warn!("max_cpu_percent requires cgroup v2 and is ignored");
+                }
+                Ok(Self {
+                    cgroup: None,
+                    memory_events: 0,
+                    cpu_periods: 0,
+                    cpu_throttled: 0,
+                })
+            }
+        }
+    }
+
+    /// Describe the pressure observed since the last call, if any.
+    ///
+    /// Memory pressure means the cgroup hit `memory.max` or the OOM killer ran; CPU pressure
+    /// means more than half of the enforcement periods were throttled.
+    pub(crate) fn pressure(&mut self) -> Option<&'static str> {
+        let cgroup = self.cgroup.as_ref()?;
+        let memory_events = read_stat(&cgroup.join("memory.events"), "max")
+            + read_stat(&cgroup.join("memory.events"), "oom");
+        let cpu_periods = read_stat(&cgroup.join("cpu.stat"), "nr_periods");
+        let cpu_throttled = read_stat(&cgroup.join("cpu.stat"), "nr_throttled");
+
+        let periods = cpu_periods.saturating_sub(self.cpu_periods);
+        let throttled = cpu_throttled.saturating_sub(self.cpu_throttled);
+        let pressure = if memory_events > self.memory_events {
+            Some("memory limit reached")
+        } else if periods > 0 && throttled * 2 > periods {
+            Some("CPU quota exhausted")
+        } else {
+            None
+        };
+
+        self.memory_events = memory_events;
+        self.cpu_periods = cpu_periods;
+        self.cpu_throttled = cpu_throttled;
+        pressure
+    }
+}
+
+/// Move the process into a child of its current cgroup with the limits set
+fn apply_cgroup(limits: &ResourceLimits) -> io::Result<PathBuf> {
+    if !Path::new(CGROUP_ROOT).join("cgroup.controllers").exists() {
+        return Err(io::Error::other("cgroup v2 is not mounted"));
+    }
+    let parent = current_cgroup()?;
+    let pid = std::process::id();
+    let name = format!("arkflow-{}", pid);
+    // The process was already moved when the stream ran before
+    let cgroup = if parent.ends_with(&name) {
+        parent.clone()
+    } else {
+        parent.join(&name)
+    };
+
+    if cgroup != parent {
+        fs::create_dir_all(&cgroup)?;
+        // A cgroup with processes cannot enable controllers for its children, so leave the
+        // parent before enabling them there
+        fs::write(cgroup.join("cgroup.procs"), pid.to_string())?;
+        if let Err(e) = enable_controllers(&parent, limits) {
+            let _ = fs::write(parent.join("cgroup.procs"), pid.to_string());
+            let _ = fs::remove_dir(&cgroup);
+            return Err(e);
+        }
+    }
+
+    if let Some(max_memory_bytes) = limits.max_memory_bytes {
+        let path = cgroup.join("memory.max");
+        let current = fs::read_to_string(&path)
+            .map_err(|e| io::Error::other(format!("memory controller unavailable: {}", e)))?;
+        let bytes = current
+            .trim()
+            .parse::<u64>()
+            .map_or(max_memory_bytes, |current| current.min(max_memory_bytes));
+        fs::write(&path, bytes.to_string())?;
+    }
+    if let Some(max_cpu_percent) = limits.max_cpu_percent {
+        let path = cgroup.join("cpu.max");
+        let current = fs::read_to_string(&path)
+            .map_err(|e| io::Error::other(format!("cpu controller unavailable: {}", e)))?;
+        let quota = ((max_cpu_percent / 100.0) * CPU_PERIOD_US as f64).round() as u64;
+        // The kernel rejects quotas below 1ms
+        let quota = quota.max(1_000);
+        let quota = current
+            .split_whitespace()
+            .next()
+            .and_then(|current| current.parse::<u64>().ok())
+            .map_or(quota, |current| current.min(quota));
+        fs::write(&path, format!("{} {}", quota, CPU_PERIOD_US))?;
+    }
+    Ok(cgroup)
+}
+
+/// Enable the controllers needed by `limits` for the children of `parent`.
+///
+/// Fails when other processes remain in `parent`, or when the controllers were not delegated.
+fn enable_controllers(parent: &Path, limits: &ResourceLimits) -> io::Result<()> {
+    let path = parent.join("cgroup.subtree_control");
+    let enabled = fs::read_to_string(&path)?;
+    let missing: Vec<String> = [
+        ("memory", limits.max_memory_bytes.is_some()),
+        ("cpu", limits.max_cpu_percent.is_some()),
+    ]
+    .into_iter()
+    .filter(|(controller, needed)| *needed && !enabled.split_whitespace().any(|c| c == *controller))
+    .map(|(controller, _)| format!("+{}", controller))
+    .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    fs::write(&path, missing.join(" ")).map_err(|e| {
+        io::Error::other(format!(
+            "failed to enable {} in {}, the cgroup must only contain ArkFlow and have the \
+             controllers delegated: {}",
+            missing.join(" "),
+            path.display(),
+            e
+        ))
+    })
+}
+
+/// Path of the cgroup v2 the process belongs to
+fn current_cgroup() -> io::Result<PathBuf> {
+    let content = fs::read_to_string("/proc/self/cgroup")?;
+    // The unified hierarchy is listed as `0::<path>`
+    let path = content
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .ok_or_else(|| io::Error::other("process is not in a cgroup v2 hierarchy"))?;
+    Ok(Path::new(CGROUP_ROOT).join(path.trim().trim_start_matches('/')))
+}
+
+/// Lower the soft address space limit of the process to `bytes`
+fn limit_address_space(bytes: u64) -> io::Result<()> {
+    // SAFETY: `limit` is a valid rlimit for the duration of both calls
+    unsafe {
+        let mut limit: libc::rlimit = std::mem::zeroed();
+        if libc::getrlimit(libc::RLIMIT_AS, &mut limit) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        limit.rlim_cur = limit.rlim_cur.min(bytes as libc::rlim_t);
+        if libc::setrlimit(libc::RLIMIT_AS, &limit) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Read the `key value` entry of a cgroup stat file, 0 if missing
+fn read_stat(path: &Path, key: &str) -> u64 {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| {
+            content.lines().find_map(|line| {
+                let (name, value) = line.split_once(' ')?;
+                (name == key).then(|| value.trim().parse().ok()).flatten()
+            })
+        })
+        .unwrap_or(0)
+}
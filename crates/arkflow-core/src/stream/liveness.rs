@@ -0,0 +1,114 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Stream liveness probe
+//!
+//! An optional HTTP endpoint per stream reporting whether messages still flow through it, e.g.
+//! for a Kubernetes liveness probe. The stream is considered stuck once no message was
+//! processed or written for longer than the configured timeout.
+
+use crate::stream::admin::AdminState;
+use crate::Error;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::Router;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+/// Liveness probe configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LivenessConfig {
+    /// Listening address, e.g. `0.0.0.0:8082`
+    pub address: String,
+    /// Path of the probe, e.g. `/live`
+    pub path: String,
+    /// Time without activity after which the stream is reported as stuck
+    pub timeout_ms: u64,
+}
+
+struct LivenessState {
+    admin_state: Arc<AdminState>,
+    timeout: Duration,
+}
+
+/// Start the liveness server on a separate task. It stops when `cancellation_token` is cancelled.
+pub(crate) async fn start_liveness_server(
+    config: &LivenessConfig,
+    admin_state: Arc<AdminState>,
+    cancellation_token: CancellationToken,
+) -> Result<(), Error> {
+    let state = Arc::new(LivenessState {
+        admin_state,
+        timeout: Duration::from_millis(config.timeout_ms),
+    });
+    let app = Router::new()
+        .route(&config.path, get(handle_liveness))
+        .with_state(state);
+
+    let listener = TcpListener::bind(&config.address).await.map_err(|e| {
+        Error::Config(format!(
+            "Unable to bind liveness server to {}: {}",
+            config.address, e
+        ))
+    })?;
+    info!("Starting liveness server on {}", config.address);
+
+    tokio::spawn(async move {
+        let server = axum::serve(listener, app.into_make_service())
+            .with_graceful_shutdown(async move { cancellation_token.cancelled().await });
+        if let Err(e) = server.await {
+            error!("Liveness server error: {}", e);
+        } else {
+            info!("Liveness server stopped");
+        }
+    });
+    Ok(())
+}
+
+async fn handle_liveness(State(state): State<Arc<LivenessState>>) -> StatusCode {
+    if state.admin_state.idle_time() > state.timeout {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_liveness_timeout() {
+        let admin_state = Arc::new(AdminState::new(serde_json::Value::Null));
+        let state = Arc::new(LivenessState {
+            admin_state: admin_state.clone(),
+            timeout: Duration::from_millis(50),
+        });
+        assert_eq!(handle_liveness(State(state.clone())).await, StatusCode::OK);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            handle_liveness(State(state.clone())).await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        admin_state.touch();
+        assert_eq!(handle_liveness(State(state)).await, StatusCode::OK);
+    }
+}
@@ -17,23 +17,42 @@
 //! A stream is a complete data processing unit, containing input, pipeline, and output.
 
 use crate::buffer::Buffer;
+use crate::config::EngineConfig;
+use crate::dead_letter::{DeadLetterEnvelope, ErrorOutputFormat};
+use crate::engine::schema_reflection;
 use crate::input::Ack;
+use crate::stream::admin::{start_admin_server, AdminConfig, AdminState};
+use crate::stream::liveness::{start_liveness_server, LivenessConfig};
 use crate::{input::Input, output::Output, pipeline::Pipeline, Error, MessageBatch, Resource};
+use arc_swap::ArcSwap;
 use flume::{Receiver, Sender};
 use std::cell::RefCell;
 use std::collections::{BTreeMap, HashMap};
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::signal::unix::{signal, Signal, SignalKind};
 use tokio_util::sync::CancellationToken;
 use tokio_util::task::TaskTracker;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+
+pub mod admin;
+#[cfg(target_os = "linux")]
+mod limits;
+pub mod liveness;
 
 const BACKPRESSURE_THRESHOLD: u64 = 1024;
+/// How often a paused input checks whether it was resumed
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How often resource pressure is checked when resource limits are set
+const RESOURCE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
 
 /// A stream structure, containing input, pipe, output, and an optional buffer.
 pub struct Stream {
     input: Arc<dyn Input>,
-    pipeline: Arc<Pipeline>,
+    pipeline: Arc<ArcSwap<Pipeline>>,
     output: Arc<dyn Output>,
     error_output: Option<Arc<dyn Output>>,
     thread_num: u32,
@@ -41,10 +60,39 @@ pub struct Stream {
     resource: Resource,
     sequence_counter: Arc<AtomicU64>,
     next_seq: Arc<AtomicU64>,
+    /// Configuration file and stream index used to reload the pipeline on SIGHUP
+    reload_source: Option<(PathBuf, usize)>,
+    admin: Option<AdminConfig>,
+    admin_state: Arc<AdminState>,
+    liveness: Option<LivenessConfig>,
+    /// Stops the admin and liveness servers
+    admin_token: CancellationToken,
+    /// CPU ids the processor workers are pinned to
+    thread_affinity: Option<Vec<usize>>,
+    error_output_format: ErrorOutputFormat,
+    /// How long to wait for in-flight messages when the stream is stopped, unbounded if unset
+    drain_timeout: Option<Duration>,
+    resource_limits: Option<ResourceLimits>,
+}
+
+/// Stops a stream once a number of input messages have been acked
+struct AckLimit {
+    max_messages: u64,
+    acked: Arc<AtomicU64>,
+    cancellation_token: CancellationToken,
+}
+
+impl AckLimit {
+    fn record_ack(&self) {
+        if self.acked.fetch_add(1, Ordering::AcqRel) + 1 >= self.max_messages {
+            self.cancellation_token.cancel();
+        }
+    }
 }
 
 enum ProcessorData {
-    Err(MessageBatch, Error),
+    /// Message that failed processing, with the number of attempts made
+    Err(MessageBatch, Error, u32),
     Ok(Vec<MessageBatch>),
 }
 
@@ -61,7 +109,7 @@ impl Stream {
     ) -> Self {
         Self {
             input,
-            pipeline: Arc::new(pipeline),
+            pipeline: Arc::new(ArcSwap::from_pointee(pipeline)),
             output,
             error_output,
             buffer,
@@ -69,12 +117,110 @@ impl Stream {
             thread_num,
             sequence_counter: Arc::new(AtomicU64::new(0)),
             next_seq: Arc::new(AtomicU64::new(0)),
+            reload_source: None,
+            admin: None,
+            admin_state: Arc::new(AdminState::new(serde_json::Value::Null)),
+            liveness: None,
+            admin_token: CancellationToken::new(),
+            thread_affinity: None,
+            error_output_format: ErrorOutputFormat::default(),
+            drain_timeout: None,
+            resource_limits: None,
         }
     }
 
+    /// Reload the pipeline from the given configuration file on SIGHUP.
+    ///
+    /// `index` is the position of this stream in the `streams` list of the file.
+    pub fn set_reload_source(&mut self, path: PathBuf, index: usize) {
+        self.reload_source = Some((path, index));
+    }
+
+    /// Serve the admin endpoints on `config.address` while the stream runs.
+    ///
+    /// `description` is reported by `GET /pipeline`.
+    pub(crate) fn set_admin(&mut self, config: AdminConfig, description: serde_json::Value) {
+        self.admin = Some(config);
+        self.admin_state = Arc::new(AdminState::new(description));
+    }
+
+    /// Serve a liveness probe on `config.address` while the stream runs.
+    ///
+    /// The probe fails once no message was processed or written for `config.timeout_ms`.
+    pub fn set_liveness(&mut self, config: LivenessConfig) {
+        self.liveness = Some(config);
+    }
+
+    /// Pin the processor workers to the given CPUs, assigned round-robin.
+    ///
+    /// Each pinned worker runs on a dedicated thread with its own single-threaded runtime, so
+    /// tasks spawned while processing run on that thread too. Only supported on Linux; ignored
+    /// with a warning elsewhere.
+    pub fn set_thread_affinity(&mut self, cpus: Vec<usize>) {
+        self.thread_affinity = Some(cpus);
+    }
+
+    /// Set the format of the messages written to the error output
+    pub fn set_error_output_format(&mut self, format: ErrorOutputFormat) {
+        self.error_output_format = format;
+    }
+
+    /// Limit how long a stopped stream waits for messages already read to be written.
+    ///
+    /// Messages still in flight when the timeout elapses are dropped: the workers and the
+    /// output task are stopped before the output is closed. Does not apply when the stream
+    /// stops because its input reached the end.
+    pub fn set_drain_timeout(&mut self, timeout: Duration) {
+        self.drain_timeout = Some(timeout);
+    }
+
+    /// Limit the memory and CPU of the process running the stream before it connects.
+    ///
+    /// Memory cannot be limited per thread, so the limits cover every stream of the process;
+    /// [`EngineConfig::validate`](crate::config::EngineConfig::validate) rejects them on more
+    /// than one stream. Under memory or CPU pressure the number of active processor workers
+    /// is halved. Only supported on Linux; ignored with a warning elsewhere.
+    pub fn set_resource_limits(&mut self, limits: ResourceLimits) {
+        self.resource_limits = Some(limits);
+    }
+
     /// Running stream processing
     pub async fn run(&mut self, cancellation_token: CancellationToken) -> Result<(), Error> {
-        // Connect input and output
+        self.run_with_limit(cancellation_token, None).await
+    }
+
+    /// Run the stream until `max_messages` input messages have been acked, or the input ends.
+    ///
+    /// The input then stops reading; messages already read are still processed and written.
+    pub async fn run_until(&mut self, max_messages: u64) -> Result<(), Error> {
+        let cancellation_token = CancellationToken::new();
+        if max_messages == 0 {
+            cancellation_token.cancel();
+        }
+        self.run_with_limit(cancellation_token, Some(max_messages))
+            .await
+    }
+
+    /// Run the stream for `duration`, or until the input ends.
+    ///
+    /// The input then stops reading; messages already read are still processed and written.
+    pub async fn run_for(&mut self, duration: Duration) -> Result<(), Error> {
+        let cancellation_token = CancellationToken::new();
+        let timer_token = cancellation_token.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = tokio::time::sleep(duration) => timer_token.cancel(),
+                _ = timer_token.cancelled() => {}
+            }
+        });
+        let _timer_guard = cancellation_token.clone().drop_guard();
+        self.run(cancellation_token).await
+    }
+
+    /// Connect input and outputs, then start the admin server.
+    ///
+    /// Takes `&mut self` so that the future is `Send`: `Resource` is not `Sync`.
+    async fn connect_components(&mut self) -> Result<(), Error> {
         self.input.connect().await?;
         self.output.connect().await?;
         if let Some(ref error_output) = self.error_output {
@@ -83,6 +229,39 @@ impl Stream {
         for (_, temporary) in &self.resource.temporary {
             temporary.connect().await?
         }
+        if let Some(admin) = &self.admin {
+            start_admin_server(admin, self.admin_state.clone(), self.admin_token.clone()).await?;
+        }
+        Ok(())
+    }
+
+    /// Run the stream, cancelling `cancellation_token` once `max_messages` input messages have
+    /// been acked
+    async fn run_with_limit(
+        &mut self,
+        cancellation_token: CancellationToken,
+        max_messages: Option<u64>,
+    ) -> Result<(), Error> {
+        // Applied before anything connects or is spawned, so that a failure leaves nothing behind
+        let active_workers = Arc::new(AtomicU32::new(self.thread_num));
+        let limits_token = CancellationToken::new();
+        if let Some(limits) = &self.resource_limits {
+            Self::apply_resource_limits(limits, active_workers.clone(), limits_token.clone())?;
+        }
+        let _limits_guard = limits_token.drop_guard();
+
+        // Started first so that the probe answers while the components connect
+        self.admin_state.touch();
+        if let Some(liveness) = &self.liveness {
+            start_liveness_server(liveness, self.admin_state.clone(), self.admin_token.clone())
+                .await?;
+        }
+
+        if let Err(e) = self.connect_components().await {
+            // The stream stops before running, so `close` will not stop the probe
+            self.admin_token.cancel();
+            return Err(e);
+        }
 
         let (input_sender, input_receiver) =
             flume::bounded::<(MessageBatch, Arc<dyn Ack>)>(self.thread_num as usize * 4);
@@ -90,36 +269,61 @@ impl Stream {
             flume::bounded::<(ProcessorData, Arc<dyn Ack>, u64)>(self.thread_num as usize * 4);
 
         let tracker = TaskTracker::new();
+        // Stops the tasks still running when the drain timeout elapses
+        let abort_token = CancellationToken::new();
+        let input_eof = Arc::new(AtomicBool::new(false));
+        // Number of messages taken from the input channel and not yet written
+        let in_flight = Arc::new(AtomicU64::new(0));
 
         // Input
-        tracker.spawn(Self::do_input(
-            cancellation_token.clone(),
-            self.input.clone(),
-            input_sender.clone(),
-            self.buffer.clone(),
+        tracker.spawn(abortable(
+            abort_token.clone(),
+            Self::do_input(
+                cancellation_token.clone(),
+                self.input.clone(),
+                input_sender.clone(),
+                self.buffer.clone(),
+                self.admin_state.clone(),
+                input_eof.clone(),
+            ),
         ));
 
         // Buffer
         if let Some(buffer) = self.buffer.clone() {
-            tracker.spawn(Self::do_buffer(
-                cancellation_token.clone(),
-                buffer,
-                input_sender,
+            tracker.spawn(abortable(
+                abort_token.clone(),
+                Self::do_buffer(cancellation_token.clone(), buffer, input_sender),
             ));
         } else {
             drop(input_sender)
         }
 
         // Processor
+        let cpus = self
+            .thread_affinity
+            .as_deref()
+            .filter(|cpus| !cpus.is_empty());
+        if cpus.is_some() && !cfg!(target_os = "linux") {
+            warn!("thread_affinity is only supported on Linux and is ignored");
+        }
         for i in 0..self.thread_num {
-            tracker.spawn(Self::do_processor(
+            let cpu = cpus.map(|cpus| cpus[i as usize % cpus.len()]);
+            let worker = Self::do_processor(
                 i,
                 self.pipeline.clone(),
                 input_receiver.clone(),
                 output_sender.clone(),
                 self.sequence_counter.clone(),
                 self.next_seq.clone(),
-            ));
+                in_flight.clone(),
+                self.admin_state.clone(),
+                active_workers.clone(),
+            );
+            let worker = abortable(abort_token.clone(), worker);
+            match cpu {
+                Some(cpu) => tracker.spawn(run_pinned(i + 1, cpu, worker)),
+                None => tracker.spawn(worker),
+            };
         }
 
         // Close the output sender to notify all workers
@@ -127,15 +331,61 @@ impl Stream {
         // drop(error_output_sender);
 
         // Output
-        tracker.spawn(Self::do_output(
-            self.next_seq.clone(),
-            output_receiver,
-            self.output.clone(),
-            self.error_output.clone(),
+        let ack_limit = max_messages.map(|max| AckLimit {
+            max_messages: max,
+            acked: Arc::new(AtomicU64::new(0)),
+            cancellation_token: cancellation_token.clone(),
+        });
+        tracker.spawn(abortable(
+            abort_token.clone(),
+            Self::do_output(
+                self.next_seq.clone(),
+                output_receiver,
+                self.output.clone(),
+                self.error_output.clone(),
+                self.error_output_format,
+                self.admin_state.clone(),
+                ack_limit,
+                in_flight.clone(),
+            ),
         ));
 
         tracker.close();
-        tracker.wait().await;
+        let mut sighup = match self.reload_source {
+            Some(_) => Some(signal(SignalKind::hangup())?),
+            None => None,
+        };
+        let wait = tracker.wait();
+        tokio::pin!(wait);
+        loop {
+            tokio::select! {
+                _ = &mut wait => break,
+                Some(_) = recv_signal(&mut sighup) => self.reload_pipeline(),
+                _ = cancellation_token.cancelled() => {
+                    // The input stops reading; let the workers and the output drain what was read
+                    let drain_timeout = self
+                        .drain_timeout
+                        .filter(|_| !input_eof.load(Ordering::SeqCst));
+                    let Some(drain_timeout) = drain_timeout else {
+                        (&mut wait).await;
+                        break;
+                    };
+                    info!("Draining in-flight messages for up to {:?}", drain_timeout);
+                    if tokio::time::timeout(drain_timeout, &mut wait).await.is_err() {
+                        let dropped =
+                            input_receiver.len() as u64 + in_flight.load(Ordering::Acquire);
+                        warn!(
+                            "Drain timeout elapsed, dropping {} in-flight messages",
+                            dropped
+                        );
+                        // The output must not be written to once it is closed
+                        abort_token.cancel();
+                        (&mut wait).await;
+                    }
+                    break;
+                }
+            }
+        }
 
         info!("Closing....");
         self.close().await?;
@@ -145,13 +395,106 @@ impl Stream {
         Ok(())
     }
 
+    /// Apply the resource limits and halve `active_workers` whenever pressure is observed,
+    /// until `cancellation_token` is cancelled.
+    #[cfg(target_os = "linux")]
+    fn apply_resource_limits(
+        limits: &ResourceLimits,
+        active_workers: Arc<AtomicU32>,
+        cancellation_token: CancellationToken,
+    ) -> Result<(), Error> {
+        let mut limiter = limits::Limiter::apply(limits)?;
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = cancellation_token.cancelled() => break,
+                    _ = tokio::time::sleep(RESOURCE_CHECK_INTERVAL) => {}
+                }
+                let Some(pressure) = limiter.pressure() else {
+                    continue;
+                };
+                let current = active_workers.load(Ordering::Acquire);
+                if current > 1 {
+                    let reduced = current / 2;
+                    active_workers.store(reduced, Ordering::Release);
+                    warn!(
+                        "Resource pressure ({}), reducing processor workers from {} to {}",
+                        pressure, current, reduced
+                    );
+                } else {
+                    warn!(
+                        "Resource pressure ({}) with a single processor worker",
+                        pressure
+                    );
+                }
+            }
+        });
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn apply_resource_limits(
+        _limits: &ResourceLimits,
+        _active_workers: Arc<AtomicU32>,
+        _cancellation_token: CancellationToken,
+    ) -> Result<(), Error> {
+        warn!("resource_limits are only supported on Linux and are ignored");
+        Ok(())
+    }
+
+    /// Swap in a pipeline rebuilt from the configuration file.
+    ///
+    /// Workers pick up the new pipeline on their next message; messages already being
+    /// processed finish on the old one, which is closed once it is no longer in use.
+    fn reload_pipeline(&self) {
+        let Some((path, index)) = &self.reload_source else {
+            return;
+        };
+
+        info!(
+            "Received SIGHUP, reloading pipeline from {}",
+            path.display()
+        );
+        let pipeline = match StreamConfig::reload_from_file(path, *index, &self.resource) {
+            Ok(pipeline) => pipeline,
+            Err(e) => {
+                error!("Failed to reload pipeline, keeping the current one: {}", e);
+                return;
+            }
+        };
+
+        let old = self.pipeline.swap(Arc::new(pipeline));
+        info!("Pipeline reloaded");
+        tokio::spawn(async move {
+            while Arc::strong_count(&old) > 1 {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            if let Err(e) = old.close().await {
+                error!("Failed to close previous pipeline: {}", e);
+            }
+        });
+    }
+
     async fn do_input(
         cancellation_token: CancellationToken,
         input: Arc<dyn Input>,
         input_sender: Sender<(MessageBatch, Arc<dyn Ack>)>,
         buffer_option: Option<Arc<dyn Buffer>>,
+        admin_state: Arc<AdminState>,
+        input_eof: Arc<AtomicBool>,
     ) {
         loop {
+            if admin_state.paused.load(Ordering::SeqCst) {
+                tokio::select! {
+                    _ = cancellation_token.cancelled() => {
+                        break;
+                    },
+                    _ = tokio::time::sleep(PAUSE_POLL_INTERVAL) => {
+                        continue;
+                    }
+                }
+            }
+
             tokio::select! {
                 _ = cancellation_token.cancelled() => {
                     break;
@@ -159,6 +502,9 @@ impl Stream {
                 result = input.read() =>{
                     match result {
                     Ok(msg) => {
+                            let metrics = &admin_state.metrics;
+                            metrics.input_batches.fetch_add(1, Ordering::Relaxed);
+                            metrics.input_rows.fetch_add(msg.0.len() as u64, Ordering::Relaxed);
                             if let Some(buffer) = &buffer_option {
                                 if let Err(e) = buffer.write(msg.0, msg.1).await {
                                     error!("Failed to send input message: {}", e);
@@ -176,6 +522,7 @@ impl Stream {
                         match e {
                             Error::EOF => {
                                 // When input is complete, close the sender to notify all workers
+                                input_eof.store(true, Ordering::SeqCst);
                                 cancellation_token.cancel();
                                 break;
                             }
@@ -204,6 +551,8 @@ impl Stream {
                 }
             }
         }
+        // Dropping the sender tells the workers that no new messages will arrive
+        drop(input_sender);
         info!("Input stopped");
     }
 
@@ -251,17 +600,30 @@ impl Stream {
         info!("Buffer stopped");
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn do_processor(
         i: u32,
-        pipeline: Arc<Pipeline>,
+        pipeline: Arc<ArcSwap<Pipeline>>,
         input_receiver: Receiver<(MessageBatch, Arc<dyn Ack>)>,
         output_sender: Sender<(ProcessorData, Arc<dyn Ack>, u64)>,
         sequence_counter: Arc<AtomicU64>,
         next_seq: Arc<AtomicU64>,
+        in_flight: Arc<AtomicU64>,
+        admin_state: Arc<AdminState>,
+        active_workers: Arc<AtomicU32>,
     ) {
         let i = i + 1;
         info!("Processor worker {} started", i);
         loop {
+            // Workers beyond the active count idle after resource pressure was observed
+            if i > active_workers.load(Ordering::Acquire) {
+                if input_receiver.is_disconnected() && input_receiver.is_empty() {
+                    break;
+                }
+                tokio::time::sleep(PAUSE_POLL_INTERVAL).await;
+                continue;
+            }
+
             let pending_messages =
                 sequence_counter.load(Ordering::Acquire) - next_seq.load(Ordering::Acquire);
             if pending_messages > BACKPRESSURE_THRESHOLD {
@@ -276,14 +638,32 @@ impl Stream {
             let Ok((msg, ack)) = input_receiver.recv_async().await else {
                 break;
             };
+            in_flight.fetch_add(1, Ordering::AcqRel);
 
-            // Process messages through pipeline
-            let processed = pipeline.process(msg.clone()).await;
+            // Process messages through pipeline, retrying failures per the retry policy
+            let current = pipeline.load_full();
+            let retry = current.retry();
+            let mut attempt = 1;
+            let processed = loop {
+                match current.process(msg.clone()).await {
+                    Err(e) if retry.is_some_and(|r| r.should_retry(&e, attempt)) => {
+                        let delay = retry.map(|r| r.delay(attempt)).unwrap_or_default();
+                        warn!(
+                            "Processing attempt {} failed, retrying in {:?}: {}",
+                            attempt, delay, e
+                        );
+                        tokio::time::sleep(delay).await;
+                        attempt += 1;
+                    }
+                    result => break result,
+                }
+            };
             let seq = sequence_counter.fetch_add(1, Ordering::AcqRel);
 
             // Process result messages
             match processed {
                 Ok(msgs) => {
+                    admin_state.touch();
                     if let Err(e) = output_sender
                         .send_async((ProcessorData::Ok(msgs), ack, seq))
                         .await
@@ -293,8 +673,12 @@ impl Stream {
                     }
                 }
                 Err(e) => {
+                    admin_state
+                        .metrics
+                        .processor_errors
+                        .fetch_add(1, Ordering::Relaxed);
                     if let Err(e) = output_sender
-                        .send_async((ProcessorData::Err(msg, e), ack, seq))
+                        .send_async((ProcessorData::Err(msg, e, attempt), ack, seq))
                         .await
                     {
                         error!("Failed to send processed message: {}", e);
@@ -306,18 +690,32 @@ impl Stream {
         info!("Processor worker {} stopped", i);
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn do_output(
         next_seq: Arc<AtomicU64>,
         output_receiver: Receiver<(ProcessorData, Arc<dyn Ack>, u64)>,
         output: Arc<dyn Output>,
         err_output: Option<Arc<dyn Output>>,
+        error_output_format: ErrorOutputFormat,
+        admin_state: Arc<AdminState>,
+        ack_limit: Option<AckLimit>,
+        in_flight: Arc<AtomicU64>,
     ) {
         let mut tree_map: BTreeMap<u64, (ProcessorData, Arc<dyn Ack>)> = BTreeMap::new();
 
         loop {
             let Ok((data, new_ack, new_seq)) = output_receiver.recv_async().await else {
                 for (_, (data, x)) in tree_map {
-                    Self::output(data, &x, &output, err_output.as_ref()).await;
+                    Self::output(
+                        data,
+                        &x,
+                        &output,
+                        err_output.as_ref(),
+                        error_output_format,
+                        &admin_state,
+                    )
+                    .await;
+                    in_flight.fetch_sub(1, Ordering::AcqRel);
                 }
                 break;
             };
@@ -337,7 +735,19 @@ impl Stream {
                     break;
                 };
 
-                Self::output(data, &ack, &output, err_output.as_ref()).await;
+                let acked = Self::output(
+                    data,
+                    &ack,
+                    &output,
+                    err_output.as_ref(),
+                    error_output_format,
+                    &admin_state,
+                )
+                .await;
+                if let (true, Some(limit)) = (acked, &ack_limit) {
+                    limit.record_ack();
+                }
+                in_flight.fetch_sub(1, Ordering::AcqRel);
                 next_seq.fetch_add(1, Ordering::Release);
             }
         }
@@ -345,36 +755,60 @@ impl Stream {
         info!("Output stopped")
     }
 
+    /// Write the result of processing a message, returning whether the message was acked
     async fn output(
         data: ProcessorData,
         ack: &Arc<dyn Ack>,
         output: &Arc<dyn Output>,
         err_output: Option<&Arc<dyn Output>>,
-    ) {
+        error_output_format: ErrorOutputFormat,
+        admin_state: &AdminState,
+    ) -> bool {
+        let metrics = &admin_state.metrics;
         match data {
-            ProcessorData::Err(msg, e) => match err_output {
+            ProcessorData::Err(msg, e, attempts) => match err_output {
                 None => {
                     ack.ack().await;
                     error!("{e}");
+                    true
                 }
-                Some(err_output) => match err_output.write(msg).await {
-                    Ok(_) => {
-                        ack.ack().await;
-                    }
-                    Err(e) => {
-                        error!("{}", e);
+                Some(err_output) => {
+                    let msg = Self::format_error_message(msg, &e, error_output_format, attempts);
+                    match err_output.write(msg).await {
+                        Ok(_) => {
+                            ack.ack().await;
+                            true
+                        }
+                        Err(e) => {
+                            error!("{}", e);
+                            false
+                        }
                     }
-                },
+                }
             },
-            ProcessorData::Ok(msgs) => {
+            ProcessorData::Ok(mut msgs) => {
+                // Processor acks only run once everything was written, see `MessageBatch::on_ack`
+                let hooks: Vec<_> = msgs.iter_mut().flat_map(|x| x.take_acks()).collect();
                 let size = msgs.len();
                 let mut success_cnt = 0;
                 for x in msgs {
+                    // Outputs only see the Arrow content, struct items would be lost silently
+                    if x.try_as_struct().is_ok() {
+                        metrics.output_errors.fetch_add(1, Ordering::Relaxed);
+                        error!(
+                            "Struct messages must be converted by a processor before the output"
+                        );
+                        continue;
+                    }
+                    schema_reflection::register_message(&x);
                     match output.write(x).await {
                         Ok(_) => {
                             success_cnt = success_cnt + 1;
+                            metrics.output_batches.fetch_add(1, Ordering::Relaxed);
+                            admin_state.touch();
                         }
                         Err(e) => {
+                            metrics.output_errors.fetch_add(1, Ordering::Relaxed);
                             error!("{}", e);
                         }
                     }
@@ -382,7 +816,119 @@ impl Stream {
 
                 if success_cnt >= size {
                     ack.ack().await;
+                    for hook in hooks {
+                        hook.ack().await;
+                    }
+                    return true;
+                }
+                false
+            }
+        }
+    }
+
+    /// Prepare a message that failed processing for the error output
+    fn format_error_message(
+        msg: MessageBatch,
+        error: &Error,
+        format: ErrorOutputFormat,
+        attempt: u32,
+    ) -> MessageBatch {
+        match format {
+            ErrorOutputFormat::Raw => msg,
+            ErrorOutputFormat::DeadLetter => {
+                match msg.wrap_as_dead_letter(error, "pipeline", attempt) {
+                    Ok(envelope) => envelope,
+                    Err(e) => {
+                        error!("Failed to wrap message as dead letter: {}", e);
+                        msg
+                    }
+                }
+            }
+        }
+    }
+
+    /// Run dead letter messages through the pipeline again and write the results to the output.
+    ///
+    /// Messages that fail again are written to the error output with an incremented attempt
+    /// count. The input and buffer of the stream are not used. If a write fails, the replay
+    /// stops and the error tells how many messages were replayed before it.
+    pub async fn replay(&mut self, envelopes: Vec<DeadLetterEnvelope>) -> Result<(), Error> {
+        self.output.connect().await?;
+        if let Some(ref error_output) = self.error_output {
+            error_output.connect().await?;
+        }
+        for temporary in self.resource.temporary.values() {
+            temporary.connect().await?
+        }
+
+        let pipeline = self.pipeline.load_full();
+        let total = envelopes.len();
+        let (mut replayed, mut failed) = (0, 0);
+        let mut result = Ok(());
+        for envelope in envelopes {
+            match self.replay_one(&pipeline, &envelope).await {
+                Ok(true) => replayed += 1,
+                Ok(false) => failed += 1,
+                Err(e) => {
+                    result = Err(Error::Process(format!(
+                        "Replay stopped after {} of {} messages: {}",
+                        replayed + failed,
+                        total,
+                        e
+                    )));
+                    break;
+                }
+            }
+        }
+        info!("Replayed {} messages, {} failed again", replayed, failed);
+
+        if let Err(e) = pipeline.close().await {
+            error!("Failed to close pipeline: {}", e);
+        }
+        if let Err(e) = self.output.close().await {
+            error!("Failed to close output: {}", e);
+        }
+        if let Some(error_output) = &self.error_output {
+            if let Err(e) = error_output.close().await {
+                error!("Failed to close error output: {}", e);
+            }
+        }
+        result
+    }
+
+    /// Replay a dead letter message, returning whether it was processed successfully
+    async fn replay_one(
+        &self,
+        pipeline: &Pipeline,
+        envelope: &DeadLetterEnvelope,
+    ) -> Result<bool, Error> {
+        let msg = envelope.to_message_batch()?;
+        match pipeline.process(msg.clone()).await {
+            Ok(mut msgs) => {
+                let hooks: Vec<_> = msgs.iter_mut().flat_map(|x| x.take_acks()).collect();
+                for x in msgs {
+                    schema_reflection::register_message(&x);
+                    self.output.write(x).await?;
+                }
+                for hook in hooks {
+                    hook.ack().await;
+                }
+                Ok(true)
+            }
+            Err(e) => {
+                match &self.error_output {
+                    Some(error_output) => {
+                        let msg = Self::format_error_message(
+                            msg,
+                            &e,
+                            self.error_output_format,
+                            envelope.attempt_count + 1,
+                        );
+                        error_output.write(msg).await?;
+                    }
+                    None => error!("Replayed message failed again: {}", e),
                 }
+                Ok(false)
             }
         }
     }
@@ -404,7 +950,7 @@ impl Stream {
         info!("buffer closed");
 
         info!("pipeline close...");
-        if let Err(e) = self.pipeline.close().await {
+        if let Err(e) = self.pipeline.load().close().await {
             error!("Failed to close pipeline: {}", e);
         }
         info!("pipeline closed");
@@ -423,10 +969,95 @@ impl Stream {
         }
         info!("error output closed");
 
+        self.admin_token.cancel();
+
         Ok(())
     }
 }
 
+/// Run `future` until it completes or `abort_token` is cancelled
+async fn abortable<F>(abort_token: CancellationToken, future: F)
+where
+    F: Future<Output = ()>,
+{
+    tokio::select! {
+        _ = abort_token.cancelled() => {}
+        _ = future => {}
+    }
+}
+
+/// Run a processor worker on a dedicated thread pinned to `cpu`, with its own single-threaded
+/// runtime.
+///
+/// Pinning a thread of the shared runtime would also pin every other task scheduled on it,
+/// and the worker would move to another thread at its next `.await`.
+async fn run_pinned<F>(worker: u32, cpu: usize, future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let (done_sender, done_receiver) = tokio::sync::oneshot::channel::<()>();
+    let spawned = std::thread::Builder::new()
+        .name(format!("arkflow-worker-{}", worker))
+        .spawn(move || {
+            pin_to_cpu(worker, cpu);
+            match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(runtime) => runtime.block_on(future),
+                Err(e) => error!(
+                    "Failed to start the runtime of processor worker {}: {}",
+                    worker, e
+                ),
+            }
+            drop(done_sender);
+        });
+    match spawned {
+        // Resolves once the thread drops the sender
+        Ok(_) => {
+            let _ = done_receiver.await;
+        }
+        Err(e) => error!("Failed to start processor worker {}: {}", worker, e),
+    }
+}
+
+/// Pin the current thread to `cpu`
+#[cfg(target_os = "linux")]
+fn pin_to_cpu(worker: u32, cpu: usize) {
+    if cpu >= libc::CPU_SETSIZE as usize {
+        warn!(
+            "Failed to pin processor worker {} to CPU {}: CPU id out of range",
+            worker, cpu
+        );
+        return;
+    }
+    // SAFETY: the CPU set is zero-initialised and `cpu` is within its capacity
+    let result = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    if result != 0 {
+        warn!(
+            "Failed to pin processor worker {} to CPU {}: {}",
+            worker,
+            cpu,
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_to_cpu(_worker: u32, _cpu: usize) {}
+
+/// Receive the next signal, pending forever if there is no handler
+async fn recv_signal(signal: &mut Option<Signal>) -> Option<()> {
+    match signal {
+        Some(signal) => signal.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
 /// Stream configuration
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct StreamConfig {
@@ -436,6 +1067,36 @@ pub struct StreamConfig {
     pub error_output: Option<crate::output::OutputConfig>,
     pub buffer: Option<crate::buffer::BufferConfig>,
     pub temporary: Option<Vec<crate::temporary::TemporaryConfig>>,
+    /// Admin HTTP server for inspecting, pausing and resuming the stream (optional)
+    pub admin: Option<AdminConfig>,
+    /// CPU ids to pin the processor workers to, Linux only (optional)
+    pub thread_affinity: Option<Vec<usize>>,
+    /// Format of the messages written to the error output
+    #[serde(default)]
+    pub error_output_format: ErrorOutputFormat,
+    /// Seconds to wait for in-flight messages when the stream is stopped
+    #[serde(default = "default_drain_timeout_secs")]
+    pub drain_timeout_secs: u64,
+    /// Memory and CPU limits of the process, at most one stream may set them, Linux only
+    /// (optional)
+    #[serde(default)]
+    pub resource_limits: Option<ResourceLimits>,
+    /// HTTP liveness probe failing when no message flows through the stream (optional)
+    #[serde(default)]
+    pub liveness: Option<LivenessConfig>,
+}
+
+fn default_drain_timeout_secs() -> u64 {
+    30
+}
+
+/// Resource limits, enforced with cgroup v2 or, for memory only, `setrlimit(RLIMIT_AS)`
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ResourceLimits {
+    /// Maximum memory in bytes
+    pub max_memory_bytes: Option<u64>,
+    /// Maximum CPU usage, 100 being one full core
+    pub max_cpu_percent: Option<f64>,
 }
 
 impl StreamConfig {
@@ -470,7 +1131,7 @@ impl StreamConfig {
             None
         };
 
-        Ok(Stream::new(
+        let mut stream = Stream::new(
             input,
             pipeline,
             output,
@@ -478,6 +1139,882 @@ impl StreamConfig {
             buffer,
             resource,
             thread_num,
-        ))
+        );
+        if let Some(admin) = &self.admin {
+            stream.set_admin(admin.clone(), self.describe());
+        }
+        if let Some(liveness) = &self.liveness {
+            stream.set_liveness(liveness.clone());
+        }
+        if let Some(cpus) = &self.thread_affinity {
+            stream.set_thread_affinity(cpus.clone());
+        }
+        stream.set_error_output_format(self.error_output_format);
+        stream.set_drain_timeout(Duration::from_secs(self.drain_timeout_secs));
+        if let Some(limits) = &self.resource_limits {
+            if limits.max_memory_bytes == Some(0) {
+                return Err(Error::Config(
+                    "resource_limits.max_memory_bytes must be greater than 0".to_string(),
+                ));
+            }
+            if limits
+                .max_cpu_percent
+                .is_some_and(|p| !p.is_finite() || p <= 0.0)
+            {
+                return Err(Error::Config(
+                    "resource_limits.max_cpu_percent must be greater than 0".to_string(),
+                ));
+            }
+            stream.set_resource_limits(limits.clone());
+        }
+        Ok(stream)
+    }
+
+    /// Components of the stream, without their configuration
+    fn describe(&self) -> serde_json::Value {
+        let component = |component_type: &str, name: &Option<String>| serde_json::json!({ "type": component_type, "name": name });
+        serde_json::json!({
+            "input": component(&self.input.input_type, &self.input.name),
+            "buffer": self.buffer.as_ref().map(|b| component(&b.buffer_type, &b.name)),
+            "pipeline": {
+                "thread_num": self.pipeline.thread_num,
+                "processors": self
+                    .pipeline
+                    .processors
+                    .iter()
+                    .map(|p| component(&p.processor_type, &p.name))
+                    .collect::<Vec<_>>(),
+            },
+            "output": component(&self.output.output_type, &self.output.name),
+            "error_output": self
+                .error_output
+                .as_ref()
+                .map(|o| component(&o.output_type, &o.name)),
+        })
+    }
+
+    /// Re-read the configuration file and build the pipeline of the stream at `index`.
+    ///
+    /// Only the processors are rebuilt; they share the stream's existing resources.
+    pub fn reload_from_file(
+        path: &Path,
+        index: usize,
+        resource: &Resource,
+    ) -> Result<Pipeline, Error> {
+        let path = path
+            .to_str()
+            .ok_or_else(|| Error::Config(format!("Invalid configuration path: {:?}", path)))?;
+        let config = EngineConfig::from_file(path)?;
+        let stream_config = config.streams.get(index).ok_or_else(|| {
+            Error::Config(format!(
+                "Stream #{} no longer exists in the configuration file",
+                index + 1
+            ))
+        })?;
+
+        let (pipeline, _) = stream_config.pipeline.build(resource)?;
+        Ok(pipeline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::schema_reflection::get_registered_schemas;
+    use crate::input::NoopAck;
+    use crate::pipeline::ProcessorRetryConfig;
+    use crate::processor::Processor;
+    use async_trait::async_trait;
+    use datafusion::arrow::array::{Int64Array, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    /// Input returning a single message, then waiting forever
+    struct OnceInput(AtomicBool);
+
+    #[async_trait]
+    impl Input for OnceInput {
+        async fn connect(&self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+            if self.0.swap(true, Ordering::SeqCst) {
+                std::future::pending::<()>().await;
+            }
+            Ok((
+                MessageBatch::new_binary(vec![b"a".to_vec()])?,
+                Arc::new(NoopAck),
+            ))
+        }
+
+        async fn close(&self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    struct SlowProcessor(Duration);
+
+    #[async_trait]
+    impl Processor for SlowProcessor {
+        async fn process(&self, msg: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+            tokio::time::sleep(self.0).await;
+            Ok(vec![msg])
+        }
+
+        async fn close(&self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    /// Output recording the written messages and whether it was written to after being closed
+    #[derive(Default)]
+    struct RecordingOutput {
+        closed: AtomicBool,
+        writes_after_close: AtomicU64,
+        events: Mutex<Vec<&'static str>>,
+        messages: Mutex<Vec<MessageBatch>>,
+    }
+
+    #[async_trait]
+    impl Output for RecordingOutput {
+        async fn connect(&self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        async fn write(&self, msg: MessageBatch) -> Result<(), Error> {
+            if self.closed.load(Ordering::SeqCst) {
+                self.writes_after_close.fetch_add(1, Ordering::SeqCst);
+            }
+            self.events.lock().unwrap().push("write");
+            self.messages.lock().unwrap().push(msg);
+            Ok(())
+        }
+
+        async fn close(&self) -> Result<(), Error> {
+            self.closed.store(true, Ordering::SeqCst);
+            self.events.lock().unwrap().push("close");
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drain_timeout_stops_tasks_before_closing_output() {
+        let output = Arc::new(RecordingOutput::default());
+        let mut stream = Stream::new(
+            Arc::new(OnceInput(AtomicBool::new(false))),
+            Pipeline::new(vec![Arc::new(SlowProcessor(Duration::from_millis(300)))]),
+            output.clone(),
+            None,
+            None,
+            Resource {
+                temporary: Default::default(),
+                input_names: Default::default(),
+            },
+            1,
+        );
+        stream.set_drain_timeout(Duration::from_millis(50));
+
+        let token = CancellationToken::new();
+        let cancel = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            cancel.cancel();
+        });
+        stream.run(token).await.unwrap();
+
+        // Long enough for a leaked worker to finish processing and reach the output
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(*output.events.lock().unwrap(), vec!["close"]);
+        assert_eq!(output.writes_after_close.load(Ordering::SeqCst), 0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_run_pinned_uses_dedicated_thread() {
+        let caller = std::thread::current().id();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        run_pinned(1, 0, async move {
+            tokio::task::yield_now().await;
+            // SAFETY: the CPU set is zero-initialised and filled in by the kernel
+            let cpu0 = unsafe {
+                let mut set: libc::cpu_set_t = std::mem::zeroed();
+                libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set);
+                libc::CPU_ISSET(0, &set) && libc::CPU_COUNT(&set) == 1
+            };
+            let thread = std::thread::current();
+            sender
+                .send((thread.id(), thread.name().map(str::to_string), cpu0))
+                .unwrap();
+        })
+        .await;
+
+        let (id, name, pinned) = receiver.recv().unwrap();
+        assert_ne!(id, caller);
+        assert_eq!(name.as_deref(), Some("arkflow-worker-1"));
+        assert!(pinned);
+    }
+
+    /// Ack counting how often it was called
+    #[derive(Default)]
+    struct CountingAck(AtomicU64);
+
+    #[async_trait]
+    impl Ack for CountingAck {
+        async fn ack(&self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Processor adding an ack hook to every message
+    struct HookProcessor(Arc<CountingAck>);
+
+    #[async_trait]
+    impl Processor for HookProcessor {
+        async fn process(&self, mut msg: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+            msg.on_ack(self.0.clone());
+            Ok(vec![msg])
+        }
+
+        async fn close(&self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    /// Processor replacing every message with a new one
+    struct ReplaceProcessor;
+
+    #[async_trait]
+    impl Processor for ReplaceProcessor {
+        async fn process(&self, _msg: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+            Ok(vec![MessageBatch::new_binary(vec![b"b".to_vec()])?])
+        }
+
+        async fn close(&self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    /// Output accepting or rejecting every write
+    struct StaticOutput(bool);
+
+    #[async_trait]
+    impl Output for StaticOutput {
+        async fn connect(&self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        async fn write(&self, _msg: MessageBatch) -> Result<(), Error> {
+            if self.0 {
+                Ok(())
+            } else {
+                Err(Error::Process("write failed".to_string()))
+            }
+        }
+
+        async fn close(&self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_processor_acks_called_after_write() {
+        let hook = Arc::new(CountingAck::default());
+        let pipeline = Pipeline::new(vec![
+            Arc::new(HookProcessor(hook.clone())),
+            Arc::new(ReplaceProcessor),
+        ]);
+        let admin_state = AdminState::new(serde_json::Value::Null);
+        let input_ack = Arc::new(CountingAck::default());
+        let input_ack_dyn: Arc<dyn Ack> = input_ack.clone();
+        let msg = MessageBatch::new_binary(vec![b"a".to_vec()]).unwrap();
+
+        let failing: Arc<dyn Output> = Arc::new(StaticOutput(false));
+        let msgs = pipeline.process(msg.clone()).await.unwrap();
+        Stream::output(
+            ProcessorData::Ok(msgs),
+            &input_ack_dyn,
+            &failing,
+            None,
+            ErrorOutputFormat::Raw,
+            &admin_state,
+        )
+        .await;
+        assert_eq!(input_ack.0.load(Ordering::SeqCst), 0);
+        assert_eq!(hook.0.load(Ordering::SeqCst), 0);
+
+        let output: Arc<dyn Output> = Arc::new(StaticOutput(true));
+        let msgs = pipeline.process(msg).await.unwrap();
+        Stream::output(
+            ProcessorData::Ok(msgs),
+            &input_ack_dyn,
+            &output,
+            None,
+            ErrorOutputFormat::Raw,
+            &admin_state,
+        )
+        .await;
+        assert_eq!(input_ack.0.load(Ordering::SeqCst), 1);
+        assert_eq!(hook.0.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_struct_messages_not_written() {
+        let admin_state = AdminState::new(serde_json::Value::Null);
+        let ack = Arc::new(CountingAck::default());
+        let ack_dyn: Arc<dyn Ack> = ack.clone();
+        let output: Arc<dyn Output> = Arc::new(StaticOutput(true));
+        let msg = MessageBatch::new_struct(vec![Box::new(1u32)]).unwrap();
+
+        let acked = Stream::output(
+            ProcessorData::Ok(vec![msg]),
+            &ack_dyn,
+            &output,
+            None,
+            ErrorOutputFormat::Raw,
+            &admin_state,
+        )
+        .await;
+        assert!(!acked);
+        assert_eq!(ack.0.load(Ordering::SeqCst), 0);
+        assert_eq!(admin_state.metrics.output_errors.load(Ordering::SeqCst), 1);
+    }
+
+    /// Processor replacing every message with its configured tag
+    struct TagProcessor(String);
+
+    #[async_trait]
+    impl Processor for TagProcessor {
+        async fn process(&self, _msg: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+            Ok(vec![MessageBatch::new_binary(vec![self
+                .0
+                .clone()
+                .into_bytes()])?])
+        }
+
+        async fn close(&self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    struct TagProcessorBuilder;
+
+    impl crate::processor::ProcessorBuilder for TagProcessorBuilder {
+        fn build(
+            &self,
+            _name: Option<&String>,
+            config: &Option<serde_json::Value>,
+            _resource: &Resource,
+        ) -> Result<Arc<dyn Processor>, Error> {
+            let tag = config
+                .as_ref()
+                .and_then(|config| config["tag"].as_str())
+                .ok_or_else(|| Error::Config("Tag is missing".to_string()))?;
+            Ok(Arc::new(TagProcessor(tag.to_string())))
+        }
+    }
+
+    /// Stream whose pipeline is reloaded from a configuration file with a `reload_tag`
+    /// processor, and the path of the file
+    fn reloadable_stream(name: &str, tag: &str) -> (Stream, PathBuf) {
+        static REGISTER: std::sync::Once = std::sync::Once::new();
+        REGISTER.call_once(|| {
+            crate::processor::register_processor_builder(
+                "reload_tag",
+                Arc::new(TagProcessorBuilder),
+            )
+            .unwrap()
+        });
+
+        let dir = std::env::temp_dir().join(format!("arkflow-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.yaml");
+        let mut stream = Stream::new(
+            Arc::new(OnceInput(AtomicBool::new(false))),
+            Pipeline::new(vec![Arc::new(TagProcessor(tag.to_string()))]),
+            Arc::new(RecordingOutput::default()),
+            None,
+            None,
+            Resource {
+                temporary: Default::default(),
+                input_names: Default::default(),
+            },
+            1,
+        );
+        stream.set_reload_source(path.clone(), 0);
+        (stream, path)
+    }
+
+    fn write_reload_config(path: &Path, processor: &str) {
+        std::fs::write(
+            path,
+            format!(
+                r#"
+streams:
+  - input:
+      type: "once"
+    pipeline:
+      processors:
+        - {}
+    output:
+      type: "recording"
+"#,
+                processor
+            ),
+        )
+        .unwrap();
+    }
+
+    /// Tag of the message produced by the current pipeline of a stream
+    async fn current_tag(pipeline: &ArcSwap<Pipeline>) -> String {
+        let msg = MessageBatch::new_binary(vec![b"a".to_vec()]).unwrap();
+        let result = pipeline.load().process(msg).await.unwrap();
+        String::from_utf8(
+            result[0]
+                .to_binary(crate::DEFAULT_BINARY_VALUE_FIELD)
+                .unwrap()[0]
+                .to_vec(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_reloaded_on_sighup() {
+        let (mut stream, path) = reloadable_stream("reload", "a");
+        write_reload_config(&path, r#"{ type: "reload_tag", tag: "b" }"#);
+        let pipeline = stream.pipeline.clone();
+        let token = CancellationToken::new();
+        let run = tokio::spawn({
+            let token = token.clone();
+            async move { stream.run(token).await }
+        });
+
+        // Wait for the signal handler to be installed
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(current_tag(&pipeline).await, "a");
+        unsafe { libc::kill(libc::getpid(), libc::SIGHUP) };
+        let mut tag = String::new();
+        for _ in 0..50 {
+            tag = current_tag(&pipeline).await;
+            if tag == "b" {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(tag, "b");
+
+        token.cancel();
+        run.await.unwrap().unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failed_reload_keeps_pipeline() {
+        let (stream, path) = reloadable_stream("failed-reload", "a");
+
+        // The processor cannot be built without its tag
+        write_reload_config(&path, r#"{ type: "reload_tag" }"#);
+        stream.reload_pipeline();
+        assert_eq!(current_tag(&stream.pipeline).await, "a");
+
+        write_reload_config(&path, r#"{ type: "reload_tag", tag: "b" }"#);
+        stream.reload_pipeline();
+        assert_eq!(current_tag(&stream.pipeline).await, "b");
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    struct FailingProcessor;
+
+    #[async_trait]
+    impl Processor for FailingProcessor {
+        async fn process(&self, _msg: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+            Err(Error::Process("still failing".to_string()))
+        }
+
+        async fn close(&self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    fn dead_letters(payloads: &[&str]) -> Vec<DeadLetterEnvelope> {
+        let msg = MessageBatch::new_binary(
+            payloads
+                .iter()
+                .map(|payload| payload.as_bytes().to_vec())
+                .collect(),
+        )
+        .unwrap();
+        msg.wrap_as_dead_letter(&Error::Process("failed".to_string()), "pipeline", 1)
+            .unwrap()
+            .to_binary(crate::DEFAULT_BINARY_VALUE_FIELD)
+            .unwrap()
+            .into_iter()
+            .map(|envelope| serde_json::from_slice(envelope).unwrap())
+            .collect()
+    }
+
+    fn replay_stream(output: Arc<dyn Output>, error_output: Arc<dyn Output>) -> Stream {
+        let mut stream = Stream::new(
+            Arc::new(OnceInput(AtomicBool::new(false))),
+            Pipeline::new(vec![Arc::new(FailingProcessor)]),
+            output,
+            Some(error_output),
+            None,
+            Resource {
+                temporary: Default::default(),
+                input_names: Default::default(),
+            },
+            1,
+        );
+        stream.set_error_output_format(ErrorOutputFormat::DeadLetter);
+        stream
+    }
+
+    #[tokio::test]
+    async fn test_replay_increments_attempt_count() {
+        let output = Arc::new(RecordingOutput::default());
+        let error_output = Arc::new(RecordingOutput::default());
+        let mut stream = replay_stream(output.clone(), error_output.clone());
+
+        stream.replay(dead_letters(&["a"])).await.unwrap();
+        let messages = error_output.messages.lock().unwrap();
+        let envelopes: Vec<DeadLetterEnvelope> = messages[0]
+            .to_binary(crate::DEFAULT_BINARY_VALUE_FIELD)
+            .unwrap()
+            .into_iter()
+            .map(|envelope| serde_json::from_slice(envelope).unwrap())
+            .collect();
+        assert_eq!(envelopes.len(), 1);
+        assert_eq!(envelopes[0].attempt_count, 2);
+        assert_eq!(envelopes[0].error_message, "Process errors: still failing");
+        assert!(output.messages.lock().unwrap().is_empty());
+        assert!(output.closed.load(Ordering::SeqCst));
+        assert!(error_output.closed.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_replay_write_failure_closes_outputs() {
+        let output = Arc::new(RecordingOutput::default());
+        let mut stream = replay_stream(output.clone(), Arc::new(StaticOutput(false)));
+
+        let e = stream.replay(dead_letters(&["a", "b"])).await.unwrap_err();
+        assert!(e.to_string().contains("after 0 of 2 messages"), "{}", e);
+        assert!(output.closed.load(Ordering::SeqCst));
+    }
+
+    /// Input replaying a fixed list of batches, then returning [`Error::EOF`]
+    struct BatchesInput(Mutex<VecDeque<MessageBatch>>);
+
+    impl BatchesInput {
+        fn new(batches: Vec<MessageBatch>) -> Arc<Self> {
+            Arc::new(Self(Mutex::new(batches.into())))
+        }
+    }
+
+    #[async_trait]
+    impl Input for BatchesInput {
+        async fn connect(&self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+            match self.0.lock().unwrap().pop_front() {
+                Some(batch) => Ok((batch, Arc::new(NoopAck))),
+                None => Err(Error::EOF),
+            }
+        }
+
+        async fn close(&self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    /// Processor failing with `error` on its first `failures` calls
+    struct FlakyProcessor {
+        failures: u32,
+        error: &'static str,
+        calls: AtomicU32,
+    }
+
+    impl FlakyProcessor {
+        fn new(failures: u32, error: &'static str) -> Self {
+            Self {
+                failures,
+                error,
+                calls: AtomicU32::new(0),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Processor for FlakyProcessor {
+        async fn process(&self, msg: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err(Error::Process(self.error.to_string()));
+            }
+            Ok(vec![msg])
+        }
+
+        async fn close(&self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    fn retry_config(retryable_errors: Vec<String>) -> ProcessorRetryConfig {
+        ProcessorRetryConfig {
+            max_attempts: 3,
+            initial_delay_ms: 1,
+            max_delay_ms: 5,
+            retryable_errors,
+        }
+    }
+
+    /// Run one message through a stream with a single flaky processor and a dead letter error
+    /// output.
+    ///
+    /// Returns the number of processor calls, the number of written messages and the dead
+    /// letters.
+    async fn run_flaky(
+        processor: FlakyProcessor,
+        retry: ProcessorRetryConfig,
+    ) -> (u32, usize, Vec<DeadLetterEnvelope>) {
+        let processor = Arc::new(processor);
+        let output = Arc::new(RecordingOutput::default());
+        let error_output = Arc::new(RecordingOutput::default());
+        let mut stream = Stream::new(
+            BatchesInput::new(vec![MessageBatch::from_string("payload").unwrap()]),
+            Pipeline::new(vec![processor.clone()]).with_retry(retry),
+            output.clone(),
+            Some(error_output.clone()),
+            None,
+            Resource::default(),
+            1,
+        );
+        stream.set_error_output_format(ErrorOutputFormat::DeadLetter);
+        stream.run(CancellationToken::new()).await.unwrap();
+
+        let written = output.messages.lock().unwrap().len();
+        let dead_letters = error_output
+            .messages
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|msg| msg.to_binary(crate::DEFAULT_BINARY_VALUE_FIELD).unwrap())
+            .map(|envelope| serde_json::from_slice(envelope).unwrap())
+            .collect();
+        (
+            processor.calls.load(Ordering::SeqCst),
+            written,
+            dead_letters,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_retry_until_success() {
+        let (calls, written, dead_letters) = run_flaky(
+            FlakyProcessor::new(2, "connection reset"),
+            retry_config(vec![]),
+        )
+        .await;
+        assert_eq!(calls, 3);
+        assert_eq!(written, 1);
+        assert!(dead_letters.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_retries_go_to_error_output() {
+        let (calls, written, dead_letters) = run_flaky(
+            FlakyProcessor::new(10, "connection reset"),
+            retry_config(vec![]),
+        )
+        .await;
+        assert_eq!(calls, 3);
+        assert_eq!(written, 0);
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].attempt_count, 3);
+    }
+
+    #[tokio::test]
+    async fn test_non_retryable_error() {
+        let (calls, written, dead_letters) = run_flaky(
+            FlakyProcessor::new(1, "invalid payload"),
+            retry_config(vec!["connection".to_string()]),
+        )
+        .await;
+        assert_eq!(calls, 1);
+        assert_eq!(written, 0);
+        assert_eq!(dead_letters[0].attempt_count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_output_writes_register_schema() {
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("id", DataType::Int64, false),
+                Field::new("city", DataType::Utf8, false),
+            ])),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec!["Paris", "Lyon"])),
+            ],
+        )
+        .unwrap();
+        let mut msg = MessageBatch::new_arrow(batch.clone());
+        msg.set_input_name(Some("cities".to_string()));
+
+        let mut stream = Stream::new(
+            BatchesInput::new(vec![msg]),
+            Pipeline::new(vec![]),
+            Arc::new(RecordingOutput::default()),
+            None,
+            None,
+            Resource::default(),
+            1,
+        );
+        stream.run(CancellationToken::new()).await.unwrap();
+
+        assert_eq!(get_registered_schemas()["cities"], batch.schema());
+    }
+
+    /// Input producing numbered messages without end
+    #[derive(Default)]
+    struct EndlessInput {
+        count: AtomicU64,
+    }
+
+    #[async_trait]
+    impl Input for EndlessInput {
+        async fn connect(&self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            let n = self.count.fetch_add(1, Ordering::SeqCst);
+            Ok((
+                MessageBatch::from_string(&n.to_string())?,
+                Arc::new(NoopAck),
+            ))
+        }
+
+        async fn close(&self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    fn limited_stream(input: Arc<dyn Input>) -> (Stream, Arc<RecordingOutput>) {
+        let output = Arc::new(RecordingOutput::default());
+        let stream = Stream::new(
+            input,
+            Pipeline::new(vec![]),
+            output.clone(),
+            None,
+            None,
+            Resource::default(),
+            2,
+        );
+        (stream, output)
+    }
+
+    #[tokio::test]
+    async fn test_run_until_stops_after_max_messages() {
+        let (mut stream, output) = limited_stream(Arc::new(EndlessInput::default()));
+        tokio::time::timeout(Duration::from_secs(10), stream.run_until(5))
+            .await
+            .expect("the stream did not stop")
+            .unwrap();
+
+        // Messages read before the limit was reached are still written
+        let written = output.messages.lock().unwrap().len();
+        assert!(written >= 5, "{} messages written", written);
+    }
+
+    #[tokio::test]
+    async fn test_run_until_stops_at_end_of_input() {
+        let batches = (0..3)
+            .map(|i| MessageBatch::from_string(&i.to_string()).unwrap())
+            .collect();
+        let (mut stream, output) = limited_stream(BatchesInput::new(batches));
+        stream.run_until(100).await.unwrap();
+        assert_eq!(output.messages.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_run_until_counts_failed_messages() {
+        // Without an error output, messages failing processing are acked and dropped
+        let output = Arc::new(RecordingOutput::default());
+        let mut stream = Stream::new(
+            Arc::new(EndlessInput::default()),
+            Pipeline::new(vec![Arc::new(FlakyProcessor::new(u32::MAX, "invalid"))]),
+            output.clone(),
+            None,
+            None,
+            Resource::default(),
+            2,
+        );
+        tokio::time::timeout(Duration::from_secs(10), stream.run_until(5))
+            .await
+            .expect("the stream did not stop")
+            .unwrap();
+        assert!(output.messages.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_run_for_stops_after_duration() {
+        let (mut stream, output) = limited_stream(Arc::new(EndlessInput::default()));
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            stream.run_for(Duration::from_millis(100)),
+        )
+        .await
+        .expect("the stream did not stop")
+        .unwrap();
+        assert!(!output.messages.lock().unwrap().is_empty());
+    }
+
+    /// Input failing to connect
+    struct UnreachableInput;
+
+    #[async_trait]
+    impl Input for UnreachableInput {
+        async fn connect(&self) -> Result<(), Error> {
+            Err(Error::Connection("unreachable".to_string()))
+        }
+
+        async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+            Err(Error::EOF)
+        }
+
+        async fn close(&self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_liveness_stops_when_connect_fails() {
+        let address = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let (mut stream, _) = limited_stream(Arc::new(UnreachableInput));
+        stream.set_liveness(LivenessConfig {
+            address: address.to_string(),
+            path: "/live".to_string(),
+            timeout_ms: 1000,
+        });
+        assert!(stream.run(CancellationToken::new()).await.is_err());
+
+        // The probe must release its address once the stream failed
+        let mut released = false;
+        for _ in 0..50 {
+            if std::net::TcpListener::bind(address).is_ok() {
+                released = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(released, "the liveness server still listens on {}", address);
     }
 }
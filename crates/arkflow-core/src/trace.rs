@@ -0,0 +1,284 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Trace context propagation
+//!
+//! A [`SpanContext`] identifies the span a message was produced in, so that the traces of a
+//! message can be followed across systems. Inputs extract it from the W3C Trace Context
+//! `traceparent` header of incoming messages, the pipeline processes every message in a child
+//! span, and outputs inject it into the headers of outgoing messages.
+//!
+//! With the `otel` feature, contexts convert to and from OpenTelemetry contexts, see
+//! [`MessageBatch::extract_otel_context`](crate::MessageBatch::extract_otel_context).
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the W3C Trace Context header
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Name of the W3C Trace Context header carrying vendor-specific trace data
+pub const TRACESTATE_HEADER: &str = "tracestate";
+
+/// Identity of a span in a distributed trace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpanContext {
+    pub trace_id: u128,
+    pub span_id: u64,
+    /// Whether the trace is recorded by the caller
+    pub sampled: bool,
+    /// `tracestate` header value, passed on unchanged
+    pub trace_state: Option<String>,
+}
+
+impl SpanContext {
+    /// Start a new trace
+    pub fn new_root() -> Self {
+        Self {
+            trace_id: (u128::from(random_id()) << 64) | u128::from(random_id()),
+            span_id: random_id(),
+            sampled: true,
+            trace_state: None,
+        }
+    }
+
+    /// Parse a `traceparent` header value, `None` if it is invalid
+    pub fn from_traceparent(value: &str) -> Option<Self> {
+        let mut parts = value.trim().split('-');
+        let version = parts.next()?;
+        let trace_id = parts.next()?;
+        let span_id = parts.next()?;
+        let flags = parts.next()?;
+        // Later versions may append fields, version 00 must not
+        if version.len() != 2
+            || version == "ff"
+            || (version == "00" && parts.next().is_some())
+            || trace_id.len() != 32
+            || span_id.len() != 16
+            || flags.len() != 2
+        {
+            return None;
+        }
+
+        let trace_id = u128::from_str_radix(trace_id, 16).ok()?;
+        let span_id = u64::from_str_radix(span_id, 16).ok()?;
+        let flags = u8::from_str_radix(flags, 16).ok()?;
+        u8::from_str_radix(version, 16).ok()?;
+        if trace_id == 0 || span_id == 0 {
+            return None;
+        }
+        Some(Self {
+            trace_id,
+            span_id,
+            sampled: flags & 1 == 1,
+            trace_state: None,
+        })
+    }
+
+    /// Parse the `traceparent` and `tracestate` header values, `None` if `traceparent` is
+    /// invalid. An empty `tracestate` is ignored.
+    pub fn from_headers(traceparent: &str, tracestate: Option<&str>) -> Option<Self> {
+        let mut context = Self::from_traceparent(traceparent)?;
+        context.trace_state = tracestate
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string);
+        Some(context)
+    }
+
+    /// Format the context as a `traceparent` header value
+    pub fn to_traceparent(&self) -> String {
+        format!(
+            "00-{:032x}-{:016x}-{:02x}",
+            self.trace_id,
+            self.span_id,
+            u8::from(self.sampled)
+        )
+    }
+
+    /// Create the context of a new span of the same trace
+    pub fn child(&self) -> Self {
+        Self {
+            span_id: random_id(),
+            ..self.clone()
+        }
+    }
+}
+
+impl fmt::Display for SpanContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_traceparent())
+    }
+}
+
+/// Random non-zero id
+fn random_id() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    loop {
+        // RandomState is seeded from the OS once per thread and incremented per instance
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        hasher.write_u128(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos(),
+        );
+        let id = hasher.finish();
+        if id != 0 {
+            return id;
+        }
+    }
+}
+
+#[cfg(feature = "otel")]
+mod otel {
+    use super::SpanContext;
+    use crate::MessageBatch;
+    use opentelemetry::trace::{
+        SpanContext as OtelSpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState,
+    };
+    use opentelemetry::Context;
+
+    impl From<&SpanContext> for OtelSpanContext {
+        fn from(context: &SpanContext) -> Self {
+            let flags = if context.sampled {
+                TraceFlags::SAMPLED
+            } else {
+                TraceFlags::NOT_SAMPLED
+            };
+            let trace_state = context
+                .trace_state
+                .as_deref()
+                .and_then(|value| value.parse::<TraceState>().ok())
+                .unwrap_or_default();
+            OtelSpanContext::new(
+                TraceId::from(context.trace_id),
+                SpanId::from(context.span_id),
+                flags,
+                true,
+                trace_state,
+            )
+        }
+    }
+
+    impl SpanContext {
+        /// Context of an OpenTelemetry span, `None` if it is invalid
+        pub fn from_otel(context: &OtelSpanContext) -> Option<Self> {
+            let trace_state = context.trace_state().header();
+            context.is_valid().then(|| Self {
+                trace_id: u128::from_be_bytes(context.trace_id().to_bytes()),
+                span_id: u64::from_be_bytes(context.span_id().to_bytes()),
+                sampled: context.is_sampled(),
+                trace_state: Some(trace_state).filter(|value| !value.is_empty()),
+            })
+        }
+    }
+
+    impl MessageBatch {
+        /// OpenTelemetry context with the span the batch was produced in as remote parent
+        pub fn extract_otel_context(&self) -> Option<Context> {
+            self.extract_span_context()
+                .map(|context| Context::new().with_remote_span_context((&context).into()))
+        }
+
+        /// Attach the span of an OpenTelemetry context, if it has a valid one
+        pub fn inject_otel_context(&mut self, context: &Context) {
+            if let Some(context) = SpanContext::from_otel(context.span().span_context()) {
+                self.inject_span_context(&context);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn test_traceparent_round_trip() {
+        let context = SpanContext::from_traceparent(TRACEPARENT).unwrap();
+        assert_eq!(context.trace_id, 0x4bf92f3577b34da6a3ce929d0e0e4736);
+        assert_eq!(context.span_id, 0x00f067aa0ba902b7);
+        assert!(context.sampled);
+        assert_eq!(context.to_traceparent(), TRACEPARENT);
+
+        let child = context.child();
+        assert_eq!(child.trace_id, context.trace_id);
+        assert_ne!(child.span_id, context.span_id);
+    }
+
+    #[test]
+    fn test_from_headers() {
+        let context = SpanContext::from_headers(TRACEPARENT, Some("congo=t61rcWkgMzE")).unwrap();
+        assert_eq!(context.trace_state.as_deref(), Some("congo=t61rcWkgMzE"));
+        assert_eq!(context.child().trace_state, context.trace_state);
+
+        let context = SpanContext::from_headers(TRACEPARENT, Some(" ")).unwrap();
+        assert_eq!(context.trace_state, None);
+        assert!(SpanContext::from_headers("invalid", Some("congo=t61rcWkgMzE")).is_none());
+    }
+
+    #[cfg(feature = "otel")]
+    #[test]
+    fn test_otel_context_round_trip() {
+        use crate::MessageBatch;
+        use opentelemetry::trace::TraceContextExt;
+
+        let mut msg = MessageBatch::from_string("hello").unwrap();
+        assert!(msg.extract_otel_context().is_none());
+        let parent = SpanContext::from_headers(TRACEPARENT, Some("congo=t61rcWkgMzE")).unwrap();
+        msg.inject_span_context(&parent);
+
+        let context = msg.extract_otel_context().unwrap();
+        let span = context.span();
+        let otel = span.span_context();
+        assert_eq!(
+            otel.trace_id().to_string(),
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+        assert_eq!(otel.span_id().to_string(), "00f067aa0ba902b7");
+        assert!(otel.is_sampled() && otel.is_remote());
+        assert_eq!(otel.trace_state().header(), "congo=t61rcWkgMzE");
+
+        let mut copy = MessageBatch::from_string("hello").unwrap();
+        copy.inject_otel_context(&context);
+        assert_eq!(copy.extract_span_context(), Some(parent));
+    }
+
+    #[test]
+    fn test_invalid_traceparent() {
+        for value in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "00-xyz92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        ] {
+            assert!(SpanContext::from_traceparent(value).is_none(), "{}", value);
+        }
+        // Later versions may carry additional fields
+        assert!(SpanContext::from_traceparent(
+            "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-extra"
+        )
+        .is_some());
+    }
+}
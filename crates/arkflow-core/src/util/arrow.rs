@@ -0,0 +1,127 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Arrow utilities
+
+use crate::Error;
+use datafusion::arrow::array::RecordBatch;
+use datafusion::arrow::compute::concat_batches;
+
+/// Merge `batches` into batches of about `target_rows` rows, without holding more than
+/// about `max_bytes` of input in memory at once.
+///
+/// Incoming batches are accumulated until either limit is reached, then merged and yielded.
+/// Batches are never split, so a single batch larger than the limits is yielded on its own.
+/// All batches must have the same schema.
+pub fn streaming_concat(
+    batches: impl Iterator<Item = RecordBatch>,
+    target_rows: usize,
+    max_bytes: usize,
+) -> impl Iterator<Item = Result<RecordBatch, Error>> {
+    StreamingConcat {
+        batches,
+        target_rows,
+        max_bytes,
+    }
+}
+
+struct StreamingConcat<I> {
+    batches: I,
+    target_rows: usize,
+    max_bytes: usize,
+}
+
+impl<I: Iterator<Item = RecordBatch>> Iterator for StreamingConcat<I> {
+    type Item = Result<RecordBatch, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut pending = Vec::new();
+        let mut rows = 0;
+        let mut bytes = 0;
+        // Stop pulling as soon as a limit is reached, so that callers can tell which
+        // input batches went into the yielded batch.
+        for batch in self.batches.by_ref() {
+            rows += batch.num_rows();
+            bytes += batch.get_array_memory_size();
+            pending.push(batch);
+            if rows >= self.target_rows || bytes >= self.max_bytes {
+                break;
+            }
+        }
+
+        match pending.len() {
+            0 => None,
+            1 => pending.pop().map(Ok),
+            _ => Some(
+                concat_batches(&pending[0].schema(), &pending)
+                    .map_err(|e| Error::Process(format!("Merge batches failed: {}", e))),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{ArrayRef, Int64Array};
+    use std::sync::Arc;
+
+    fn batch(values: &[i64]) -> RecordBatch {
+        let array: ArrayRef = Arc::new(Int64Array::from(values.to_vec()));
+        RecordBatch::try_from_iter([("value", array)]).unwrap()
+    }
+
+    fn rows(batches: impl Iterator<Item = Result<RecordBatch, Error>>) -> Vec<usize> {
+        batches.map(|batch| batch.unwrap().num_rows()).collect()
+    }
+
+    #[test]
+    fn test_streaming_concat_target_rows() {
+        let batches = (0..5).map(|i| batch(&[i, i]));
+        assert_eq!(
+            rows(streaming_concat(batches, 3, usize::MAX)),
+            vec![4, 4, 2]
+        );
+
+        let merged = streaming_concat([batch(&[1]), batch(&[2, 3])].into_iter(), 10, usize::MAX)
+            .next()
+            .unwrap()
+            .unwrap();
+        let values = merged.column(0).as_any().downcast_ref::<Int64Array>();
+        assert_eq!(values.unwrap().values(), &[1, 2, 3]);
+    }
+
+    #[test]
+    fn test_streaming_concat_max_bytes() {
+        let size = batch(&[1]).get_array_memory_size();
+        let batches = (0..4).map(|i| batch(&[i]));
+        assert_eq!(rows(streaming_concat(batches, 100, size * 2)), vec![2, 2]);
+
+        // A batch above the limits is yielded on its own
+        let batches = [batch(&[1; 100]), batch(&[2])].into_iter();
+        assert_eq!(rows(streaming_concat(batches, 10, size)), vec![100, 1]);
+    }
+
+    #[test]
+    fn test_streaming_concat_pulls_lazily() {
+        let mut input = (0..10).map(|i| batch(&[i]));
+        let merged = streaming_concat(input.by_ref(), 3, usize::MAX).next();
+        assert_eq!(merged.unwrap().unwrap().num_rows(), 3);
+        assert_eq!(input.count(), 7);
+
+        assert!(streaming_concat(std::iter::empty(), 3, usize::MAX)
+            .next()
+            .is_none());
+    }
+}
@@ -0,0 +1,123 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Procedural macros of ArkFlow components
+
+use proc_macro::TokenStream;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, ItemStruct, LitStr};
+
+/// Register a processor without writing its builder.
+///
+/// Applied to a struct implementing `Processor` and `serde::Deserialize`, it generates:
+///
+/// - a `<Struct>Builder` implementing `ProcessorBuilder`, building the processor by deserializing
+///   the component configuration into the struct. A missing configuration is deserialized from
+///   an empty object, so that processors without required fields need none.
+/// - an `init()` function registering the builder under `name`.
+///
+/// Validation and derived state go in a `TryFrom` of a configuration struct, used with
+/// `#[serde(try_from = "...")]`. Deserialization errors are configuration errors.
+///
+/// ```ignore
+/// #[arkflow_processor(name = "sort")]
+/// #[derive(Deserialize)]
+/// #[serde(try_from = "SortProcessorConfig")]
+/// struct SortProcessor {
+///     config: SortProcessorConfig,
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn arkflow_processor(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut name: Option<LitStr> = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("name") {
+            name = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported arkflow_processor property"))
+        }
+    });
+    parse_macro_input!(args with parser);
+    let item = parse_macro_input!(input as ItemStruct);
+
+    let Some(name) = name else {
+        return syn::Error::new_spanned(&item.ident, "arkflow_processor requires a name")
+            .to_compile_error()
+            .into();
+    };
+    if !item.generics.params.is_empty() {
+        return syn::Error::new_spanned(
+            &item.generics,
+            "arkflow_processor structs must not be generic",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let ident = &item.ident;
+    let vis = &item.vis;
+    let builder = format_ident!("{}Builder", ident);
+    let title = title(&name.value());
+    let missing = format!("{} processor configuration is missing", title);
+    let invalid = format!("Invalid {} processor configuration: {{}}", name.value());
+
+    quote! {
+        #item
+
+        #vis struct #builder;
+        impl ::arkflow_core::processor::ProcessorBuilder for #builder {
+            fn build(
+                &self,
+                _name: ::std::option::Option<&::std::string::String>,
+                config: &::std::option::Option<::serde_json::Value>,
+                _resource: &::arkflow_core::Resource,
+            ) -> ::std::result::Result<
+                ::std::sync::Arc<dyn ::arkflow_core::processor::Processor>,
+                ::arkflow_core::Error,
+            > {
+                let processor: #ident = match config {
+                    ::std::option::Option::Some(config) => {
+                        ::serde_json::from_value(config.clone()).map_err(|e| {
+                            ::arkflow_core::Error::Config(::std::format!(#invalid, e))
+                        })?
+                    }
+                    ::std::option::Option::None => ::serde_json::from_value(
+                        ::serde_json::Value::Object(::std::default::Default::default()),
+                    )
+                    .map_err(|_| ::arkflow_core::Error::Config(#missing.to_string()))?,
+                };
+                ::std::result::Result::Ok(::std::sync::Arc::new(processor))
+            }
+        }
+
+        pub fn init() -> ::std::result::Result<(), ::arkflow_core::Error> {
+            ::arkflow_core::processor::register_processor_builder(
+                #name,
+                ::std::sync::Arc::new(#builder),
+            )
+        }
+    }
+    .into()
+}
+
+/// `session_window` -> `Session window`
+fn title(name: &str) -> String {
+    let name = name.replace(['_', '-'], " ");
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => name,
+    }
+}
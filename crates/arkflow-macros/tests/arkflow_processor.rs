@@ -0,0 +1,132 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use arkflow_core::processor::{Processor, ProcessorBuilder};
+use arkflow_core::{Error, MessageBatch, Resource, DEFAULT_BINARY_VALUE_FIELD};
+use arkflow_macros::arkflow_processor;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+
+mod prefix {
+    use super::*;
+
+    #[arkflow_processor(name = "macro_prefix")]
+    #[derive(Deserialize)]
+    pub struct PrefixProcessor {
+        prefix: String,
+    }
+
+    #[async_trait]
+    impl Processor for PrefixProcessor {
+        async fn process(&self, msg: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+            let value = msg.to_binary(DEFAULT_BINARY_VALUE_FIELD)?;
+            let value = String::from_utf8_lossy(value[0]);
+            Ok(vec![MessageBatch::from_string(&format!(
+                "{}{}",
+                self.prefix, value
+            ))?])
+        }
+
+        async fn close(&self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+}
+
+mod optional {
+    use super::*;
+
+    #[arkflow_processor(name = "macro_optional")]
+    #[derive(Deserialize)]
+    pub struct OptionalProcessor {}
+
+    #[async_trait]
+    impl Processor for OptionalProcessor {
+        async fn process(&self, msg: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+            Ok(vec![msg])
+        }
+
+        async fn close(&self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+}
+
+fn build(
+    builder: impl ProcessorBuilder,
+    config: Option<serde_json::Value>,
+) -> Result<Arc<dyn Processor>, Error> {
+    builder.build(None, &config, &Resource::default())
+}
+
+fn config_error(result: Result<Arc<dyn Processor>, Error>) -> String {
+    match result {
+        Err(Error::Config(message)) => message,
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("the processor was built"),
+    }
+}
+
+#[tokio::test]
+async fn test_build_from_config() {
+    let processor = build(
+        prefix::PrefixProcessorBuilder,
+        Some(json!({ "prefix": "a:" })),
+    )
+    .unwrap();
+    let results = processor
+        .process(MessageBatch::from_string("b").unwrap())
+        .await
+        .unwrap();
+    assert_eq!(
+        results[0].to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap(),
+        vec![b"a:b".as_slice()]
+    );
+}
+
+#[test]
+fn test_missing_config() {
+    assert_eq!(
+        config_error(build(prefix::PrefixProcessorBuilder, None)),
+        "Macro prefix processor configuration is missing"
+    );
+    // Processors without required fields need no configuration
+    assert!(build(optional::OptionalProcessorBuilder, None).is_ok());
+}
+
+#[test]
+fn test_bad_config() {
+    let message = config_error(build(
+        prefix::PrefixProcessorBuilder,
+        Some(json!({ "prefix": 1 })),
+    ));
+    assert!(
+        message.starts_with("Invalid macro_prefix processor configuration: "),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn test_init_registers_builder() {
+    optional::init().unwrap();
+    assert!(optional::init().is_err());
+}
+
+#[test]
+fn test_compile_errors() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
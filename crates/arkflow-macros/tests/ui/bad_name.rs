@@ -0,0 +1,6 @@
+use arkflow_macros::arkflow_processor;
+
+#[arkflow_processor(name = 42)]
+struct BadName;
+
+fn main() {}
@@ -0,0 +1,6 @@
+use arkflow_macros::arkflow_processor;
+
+#[arkflow_processor(name = "generic")]
+struct Generic<T>(T);
+
+fn main() {}
@@ -0,0 +1,6 @@
+use arkflow_macros::arkflow_processor;
+
+#[arkflow_processor]
+struct Unnamed;
+
+fn main() {}
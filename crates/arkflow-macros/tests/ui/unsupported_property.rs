@@ -0,0 +1,6 @@
+use arkflow_macros::arkflow_processor;
+
+#[arkflow_processor(name = "noop", threads = 2)]
+struct Threaded;
+
+fn main() {}
@@ -0,0 +1,97 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Compare renaming the columns of an Arrow batch, which only replaces the schema, with
+//! renaming the keys of the same rows as binary JSON messages.
+
+use arkflow_core::processor::{Processor, ProcessorConfig};
+use arkflow_core::{MessageBatch, Resource};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use datafusion::arrow::array::{ArrayRef, Float64Array, Int64Array, StringArray};
+use datafusion::arrow::record_batch::RecordBatch;
+use serde_json::json;
+use std::cell::RefCell;
+use std::sync::Arc;
+
+fn processor() -> Arc<dyn Processor> {
+    arkflow_plugin::processor::init().unwrap();
+    let config: ProcessorConfig = serde_json::from_value(json!({
+        "type": "rename",
+        "mappings": {"UserId": "user_id", "UserName": "user_name", "Amount": "amount"},
+    }))
+    .unwrap();
+    config
+        .build(&Resource {
+            temporary: Default::default(),
+            input_names: RefCell::new(Default::default()),
+        })
+        .unwrap()
+}
+
+fn arrow_message(rows: usize) -> MessageBatch {
+    let batch = RecordBatch::try_from_iter([
+        (
+            "UserId",
+            Arc::new(Int64Array::from_iter_values(0..rows as i64)) as ArrayRef,
+        ),
+        (
+            "UserName",
+            Arc::new(StringArray::from_iter_values(
+                (0..rows).map(|i| format!("user-{}", i)),
+            )) as ArrayRef,
+        ),
+        (
+            "Amount",
+            Arc::new(Float64Array::from_iter_values(
+                (0..rows).map(|i| i as f64 * 0.5),
+            )) as ArrayRef,
+        ),
+    ])
+    .unwrap();
+    MessageBatch::new_arrow(batch)
+}
+
+fn json_message(rows: usize) -> MessageBatch {
+    let messages = (0..rows)
+        .map(|i| {
+            serde_json::to_vec(&json!({
+                "UserId": i,
+                "UserName": format!("user-{}", i),
+                "Amount": i as f64 * 0.5,
+            }))
+            .unwrap()
+        })
+        .collect();
+    MessageBatch::new_binary(messages).unwrap()
+}
+
+fn bench_rename(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let processor = processor();
+    let mut group = c.benchmark_group("rename");
+    for rows in [100, 10_000] {
+        let msg = arrow_message(rows);
+        group.bench_with_input(BenchmarkId::new("arrow", rows), &msg, |b, msg| {
+            b.to_async(&runtime).iter(|| processor.process(msg.clone()))
+        });
+        let msg = json_message(rows);
+        group.bench_with_input(BenchmarkId::new("json", rows), &msg, |b, msg| {
+            b.to_async(&runtime).iter(|| processor.process(msg.clone()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_rename);
+criterion_main!(benches);
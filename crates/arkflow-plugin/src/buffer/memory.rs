@@ -17,31 +17,259 @@
 //! This module implements a memory-based buffer that accumulates messages until
 //! either a capacity threshold is reached or a timeout occurs. When either condition
 //! is met, the buffer releases all accumulated messages as a single batch.
+//!
+//! Messages are released oldest first by default. The order can be switched to newest first,
+//! or to the highest value of a priority column first.
 
 use crate::time::deserialize_duration;
 use arkflow_core::buffer::{register_buffer_builder, Buffer, BufferBuilder};
-use arkflow_core::input::Ack;
+use arkflow_core::input::{Ack, NoopAck};
+use arkflow_core::util::arrow::streaming_concat;
 use arkflow_core::{Error, MessageBatch, Resource};
 use async_trait::async_trait;
-use datafusion::arrow;
-use datafusion::arrow::array::RecordBatch;
+use datafusion::arrow::array::{Array, ArrayRef, AsArray, LargeBinaryArray, StringArray};
+use datafusion::arrow::compute::{cast, max};
+use datafusion::arrow::datatypes::{DataType, Field, Float64Type, Schema};
+use datafusion::arrow::error::ArrowError;
+use datafusion::arrow::ipc::reader::{FileReader, StreamReader};
+use datafusion::arrow::ipc::writer::{FileWriter, StreamWriter};
+use datafusion::arrow::record_batch::RecordBatch;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::VecDeque;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+use std::path::Path;
 use std::sync::Arc;
 use std::time;
 use tokio::sync::{Notify, RwLock};
 use tokio::time::sleep;
 use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
 
 /// Configuration for the memory buffer
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct MemoryBufferConfig {
     /// Maximum number of messages to accumulate before releasing
     capacity: u32,
+    /// Estimated heap size in bytes of the accumulated messages that triggers a release
+    capacity_bytes: Option<u64>,
     /// Maximum time to wait before releasing accumulated messages
     #[serde(deserialize_with = "deserialize_duration")]
     timeout: time::Duration,
+    /// Maximum size in bytes of a released batch, the rest stays queued for the next read
+    max_bytes: Option<usize>,
+    /// File the queued messages are saved to on close and restored from on startup
+    checkpoint_path: Option<String>,
+    /// Order in which queued messages are released
+    #[serde(default)]
+    order: BufferOrder,
+    /// Numeric column holding the priority of a message, required by the priority order
+    priority_field: Option<String>,
+}
+
+/// Order in which the memory buffer releases queued messages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum BufferOrder {
+    /// Oldest message first
+    #[default]
+    Fifo,
+    /// Newest message first
+    Lifo,
+    /// Highest priority first, oldest first among equal priorities
+    Priority,
+}
+
+type QueuedMessage = (MessageBatch, Arc<dyn Ack>);
+
+/// Message queued in priority order
+struct PrioritizedMessage {
+    /// Highest value of the priority column in the batch
+    priority: f64,
+    /// Arrival sequence number, breaks ties between equal priorities
+    seq: u64,
+    message: QueuedMessage,
+}
+
+impl PartialEq for PrioritizedMessage {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for PrioritizedMessage {}
+
+impl PartialOrd for PrioritizedMessage {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PrioritizedMessage {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap is a max-heap: higher priority wins, then the earlier arrival
+        self.priority
+            .total_cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+/// Queue of buffered messages, released in the configured order
+enum MessageQueue {
+    /// Messages are pushed to the back and popped from the front
+    Fifo(VecDeque<QueuedMessage>),
+    /// Messages are pushed to and popped from the end
+    Lifo(Vec<QueuedMessage>),
+    /// Messages are ordered by the priority column on every push
+    Priority {
+        heap: BinaryHeap<PrioritizedMessage>,
+        field: String,
+        next_seq: u64,
+    },
+}
+
+impl MessageQueue {
+    fn new(order: BufferOrder, priority_field: Option<&String>) -> Result<Self, Error> {
+        Ok(match order {
+            BufferOrder::Fifo => MessageQueue::Fifo(VecDeque::new()),
+            BufferOrder::Lifo => MessageQueue::Lifo(Vec::new()),
+            BufferOrder::Priority => {
+                let Some(field) = priority_field else {
+                    return Err(Error::Config(
+                        "Memory buffer with priority order requires priority_field".to_string(),
+                    ));
+                };
+                MessageQueue::Priority {
+                    heap: BinaryHeap::new(),
+                    field: field.clone(),
+                    next_seq: 0,
+                }
+            }
+        })
+    }
+
+    fn push(&mut self, message: QueuedMessage) {
+        match self {
+            MessageQueue::Fifo(queue) => queue.push_back(message),
+            MessageQueue::Lifo(stack) => stack.push(message),
+            MessageQueue::Priority {
+                heap,
+                field,
+                next_seq,
+            } => {
+                let priority = batch_priority(&message.0, field);
+                heap.push(PrioritizedMessage {
+                    priority,
+                    seq: *next_seq,
+                    message,
+                });
+                *next_seq += 1;
+            }
+        }
+    }
+
+    /// Removes the next message to release, with its arrival sequence number in the
+    /// priority order, which [`MessageQueue::unpop`] needs to put it back
+    fn pop(&mut self) -> Option<(QueuedMessage, u64)> {
+        match self {
+            MessageQueue::Fifo(queue) => queue.pop_front().map(|message| (message, 0)),
+            MessageQueue::Lifo(stack) => stack.pop().map(|message| (message, 0)),
+            MessageQueue::Priority { heap, .. } => {
+                heap.pop().map(|entry| (entry.message, entry.seq))
+            }
+        }
+    }
+
+    /// Puts back popped messages, in the order they were popped, so that they are the next
+    /// to be released again
+    fn unpop(&mut self, messages: Vec<(QueuedMessage, u64)>) {
+        for (message, seq) in messages.into_iter().rev() {
+            match self {
+                MessageQueue::Fifo(queue) => queue.push_front(message),
+                MessageQueue::Lifo(stack) => stack.push(message),
+                MessageQueue::Priority { heap, field, .. } => {
+                    let priority = batch_priority(&message.0, field);
+                    heap.push(PrioritizedMessage {
+                        priority,
+                        seq,
+                        message,
+                    });
+                }
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            MessageQueue::Fifo(queue) => queue.len(),
+            MessageQueue::Lifo(stack) => stack.len(),
+            MessageQueue::Priority { heap, .. } => heap.len(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn messages(&self) -> Box<dyn Iterator<Item = &MessageBatch> + '_> {
+        match self {
+            MessageQueue::Fifo(queue) => Box::new(queue.iter().map(|(msg, _)| msg)),
+            MessageQueue::Lifo(stack) => Box::new(stack.iter().map(|(msg, _)| msg)),
+            MessageQueue::Priority { heap, .. } => {
+                Box::new(heap.iter().map(|entry| &entry.message.0))
+            }
+        }
+    }
+
+    /// Removes all messages, oldest first, so that pushing them again restores the queue
+    fn drain_by_arrival(&mut self) -> Vec<QueuedMessage> {
+        match self {
+            MessageQueue::Fifo(queue) => std::mem::take(queue).into(),
+            MessageQueue::Lifo(stack) => std::mem::take(stack),
+            MessageQueue::Priority { heap, .. } => {
+                let mut entries = std::mem::take(heap).into_vec();
+                entries.sort_by_key(|entry| entry.seq);
+                entries.into_iter().map(|entry| entry.message).collect()
+            }
+        }
+    }
+}
+
+/// Highest value of the priority column in the batch. Batches without the column, or whose
+/// values are all null or not numeric, get the lowest priority.
+fn batch_priority(msg: &MessageBatch, field: &str) -> f64 {
+    msg.column_by_name(field)
+        .and_then(|column| cast(column, &DataType::Float64).ok())
+        .and_then(|column| max(column.as_primitive::<Float64Type>()))
+        .unwrap_or(f64::NEG_INFINITY)
+}
+
+/// Message batch of the merged `record_batch` of `messages`, keeping the input name and the
+/// trace context when all messages share them, as [`MessageBatch::concat`] does
+fn with_metadata(record_batch: RecordBatch, messages: &[MessageBatch]) -> MessageBatch {
+    let mut batch = MessageBatch::new_arrow(record_batch);
+    let (input_name, span_context) = (
+        messages[0].get_input_name(),
+        messages[0].extract_span_context(),
+    );
+    if messages
+        .iter()
+        .all(|msg| msg.get_input_name() == input_name)
+    {
+        batch.set_input_name(input_name);
+    }
+    if let Some(context) = &span_context {
+        if messages
+            .iter()
+            .all(|msg| msg.extract_span_context() == span_context)
+        {
+            batch.inject_span_context(context);
+        }
+    }
+    for ack in messages.iter().flat_map(|msg| msg.clone().take_acks()) {
+        batch.on_ack(ack);
+    }
+    batch
 }
 
 /// Memory buffer implementation
@@ -50,7 +278,7 @@ struct MemoryBuffer {
     /// Configuration parameters for the memory buffer
     config: MemoryBufferConfig,
     /// Thread-safe queue to store message batches and their acknowledgments
-    queue: Arc<RwLock<VecDeque<(MessageBatch, Arc<dyn Ack>)>>>,
+    queue: Arc<RwLock<MessageQueue>>,
     /// Notification mechanism for signaling between threads
     notify: Arc<Notify>,
     /// Token for cancellation of background tasks
@@ -66,6 +294,7 @@ impl MemoryBuffer {
     /// # Returns
     /// * `Result<Self, Error>` - A new memory buffer instance or an error
     fn new(config: MemoryBufferConfig) -> Result<Self, Error> {
+        let mut queue = MessageQueue::new(config.order, config.priority_field.as_ref())?;
         let notify = Arc::new(Notify::new());
         let notify_clone = Arc::clone(&notify);
         let duration = config.timeout.clone();
@@ -90,11 +319,27 @@ impl MemoryBuffer {
                 }
             }
         });
+        if let Some(path) = &config.checkpoint_path {
+            let path = Path::new(path);
+            if path.exists() {
+                let messages = read_checkpoint(path)?;
+                info!(
+                    "Restored {} message batches from checkpoint {}",
+                    messages.len(),
+                    path.display()
+                );
+                for msg in messages {
+                    queue.push((msg, Arc::new(NoopAck) as Arc<dyn Ack>));
+                }
+                std::fs::remove_file(path)?;
+            }
+        }
+
         Ok(Self {
             close,
             notify,
             config,
-            queue: Arc::new(Default::default()),
+            queue: Arc::new(RwLock::new(queue)),
         })
     }
 
@@ -111,24 +356,48 @@ impl MemoryBuffer {
             return Ok(None);
         }
 
-        let mut messages = Vec::new();
-        let mut acks = Vec::new();
-
-        while let Some((msg, ack)) = queue_lock.pop_back() {
-            messages.push(msg);
-            acks.push(ack);
-        }
-
-        if messages.is_empty() {
-            return Ok(None);
-        }
-        let schema = messages[0].schema();
-        let x: Vec<RecordBatch> = messages.into_iter().map(|batch| batch.into()).collect();
-        let new_batch = arrow::compute::concat_batches(&schema, &x)
-            .map_err(|e| Error::Process(format!("Merge batches failed: {}", e)))?;
+        // Only merge up to capacity rows or max_bytes at once, the remaining messages are
+        // released by the next read. streaming_concat stops pulling from the queue as soon as a
+        // limit is reached, so `popped` holds the messages of the merged batch.
+        let max_bytes = self.config.max_bytes.unwrap_or(usize::MAX);
+        let mut popped = Vec::new();
+        let merged = {
+            let batches = std::iter::from_fn(|| queue_lock.pop()).map(|(message, seq)| {
+                let batch = RecordBatch::clone(&message.0);
+                popped.push((message, seq));
+                batch
+            });
+            streaming_concat(batches, self.config.capacity as usize, max_bytes).next()
+        };
 
+        let messages: Vec<MessageBatch> = popped.iter().map(|((msg, _), _)| msg.clone()).collect();
+        let new_batch = match merged {
+            Some(Ok(_)) if messages.len() == 1 => messages[0].clone(),
+            // concat_batches only checks the column types, concat also requires the same names
+            Some(Ok(record_batch))
+                if messages
+                    .iter()
+                    .all(|msg| msg.is_compatible_with(&messages[0])) =>
+            {
+                with_metadata(record_batch, &messages)
+            }
+            // Binary and Arrow messages can be mixed in the buffer, concat converts them as
+            // needed. Messages that cannot be merged, e.g. Arrow messages with different
+            // schemas, are put back and the first one is released on its own.
+            _ => match MessageBatch::concat(&messages) {
+                Ok(new_batch) => new_batch,
+                Err(e) if popped.len() > 1 => {
+                    warn!("Releasing buffered messages one at a time: {}", e);
+                    let rest = popped.split_off(1);
+                    queue_lock.unpop(rest);
+                    messages[0].clone()
+                }
+                Err(e) => return Err(e),
+            },
+        };
+        let acks = popped.into_iter().map(|((_, ack), _)| ack).collect();
         let new_ack = Arc::new(ArrayAck(acks));
-        Ok(Some((MessageBatch::new_arrow(new_batch), new_ack)))
+        Ok(Some((new_batch, new_ack)))
     }
 }
 
@@ -146,16 +415,22 @@ impl Buffer for MemoryBuffer {
         let queue_arc = Arc::clone(&self.queue);
 
         let mut queue_lock = queue_arc.write().await;
-        queue_lock.push_front((msg, arc));
+        queue_lock.push((msg, arc));
 
         // Calculate the total number of messages in the buffer
-        let cnt = queue_lock.iter().map(|x| x.0.len()).reduce(|acc, x| {
+        let cnt = queue_lock.messages().map(|x| x.len()).reduce(|acc, x| {
             return acc + x;
         });
         let cnt = cnt.unwrap_or(0);
 
+        // Row counts say little about variable-length data, so a byte threshold can apply too
+        let bytes_reached = self.config.capacity_bytes.is_some_and(|capacity_bytes| {
+            let bytes: usize = queue_lock.messages().map(|x| x.estimate_heap_bytes()).sum();
+            bytes as u64 >= capacity_bytes
+        });
+
         // If capacity threshold is reached, notify readers to process the batch
-        if cnt >= self.config.capacity as usize {
+        if cnt >= self.config.capacity as usize || bytes_reached {
             let notify = self.notify.clone();
             notify.notify_waiters();
         }
@@ -213,9 +488,117 @@ impl Buffer for MemoryBuffer {
     /// * `Result<(), Error>` - Success or an error
     async fn close(&self) -> Result<(), Error> {
         self.close.cancel();
+
+        if let Some(path) = &self.config.checkpoint_path {
+            let mut queue_lock = self.queue.write().await;
+            if !queue_lock.is_empty() {
+                let messages = queue_lock.drain_by_arrival();
+                write_checkpoint(Path::new(path), messages.iter().map(|(msg, _)| msg))?;
+                info!(
+                    "Saved {} message batches to checkpoint {}",
+                    messages.len(),
+                    path
+                );
+                // The messages are persisted now, so upstream must not redeliver them
+                for (_, ack) in messages {
+                    ack.ack().await;
+                }
+            }
+        }
         Ok(())
     }
 }
+
+/// Schema of the checkpoint file: one row per message batch, holding the batch encoded as an
+/// Arrow IPC stream so that batches with different schemas can be stored together
+fn checkpoint_schema() -> Arc<Schema> {
+    Arc::new(Schema::new(vec![
+        Field::new("batch", DataType::LargeBinary, false),
+        Field::new("input_name", DataType::Utf8, true),
+    ]))
+}
+
+/// Write message batches to an Arrow IPC file at `path`
+fn write_checkpoint<'a>(
+    path: &Path,
+    messages: impl Iterator<Item = &'a MessageBatch>,
+) -> Result<(), Error> {
+    let mut batches = Vec::new();
+    let mut input_names = Vec::new();
+    for msg in messages {
+        let encoded = encode_batch(msg)
+            .map_err(|e| Error::Process(format!("Failed to encode checkpoint: {}", e)))?;
+        batches.push(encoded);
+        input_names.push(msg.get_input_name());
+    }
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(LargeBinaryArray::from_iter_values(batches)),
+        Arc::new(StringArray::from(input_names)),
+    ];
+    let schema = checkpoint_schema();
+    let batch = RecordBatch::try_new(schema.clone(), columns)
+        .map_err(|e| Error::Process(format!("Failed to encode checkpoint: {}", e)))?;
+
+    // Write to a temporary file first so that a crash never leaves a truncated checkpoint behind
+    let tmp_path = path.with_extension("tmp");
+    let file = std::fs::File::create(&tmp_path)?;
+    let mut writer = FileWriter::try_new(file, &schema)
+        .map_err(|e| Error::Process(format!("Failed to write checkpoint: {}", e)))?;
+    writer
+        .write(&batch)
+        .and_then(|_| writer.finish())
+        .map_err(|e| Error::Process(format!("Failed to write checkpoint: {}", e)))?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Encode a record batch as an Arrow IPC stream
+fn encode_batch(batch: &RecordBatch) -> Result<Vec<u8>, ArrowError> {
+    let mut writer = StreamWriter::try_new(Vec::new(), &batch.schema())?;
+    writer.write(batch)?;
+    writer.finish()?;
+    writer.into_inner()
+}
+
+/// Read the message batches of a checkpoint file, oldest first
+fn read_checkpoint(path: &Path) -> Result<Vec<MessageBatch>, Error> {
+    let file = std::fs::File::open(path)?;
+    let reader = FileReader::try_new(file, None)
+        .map_err(|e| Error::Read(format!("Invalid checkpoint {}: {}", path.display(), e)))?;
+
+    let mut messages = Vec::new();
+    for batch in reader {
+        let batch = batch
+            .map_err(|e| Error::Read(format!("Invalid checkpoint {}: {}", path.display(), e)))?;
+        let invalid = || Error::Read(format!("Invalid checkpoint {}", path.display()));
+        let encoded = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<LargeBinaryArray>()
+            .ok_or_else(invalid)?;
+        let input_names = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .ok_or_else(invalid)?;
+
+        for row in 0..batch.num_rows() {
+            let reader = StreamReader::try_new(encoded.value(row), None)
+                .map_err(|e| Error::Read(format!("Invalid checkpoint batch: {}", e)))?;
+            for record_batch in reader {
+                let record_batch = record_batch
+                    .map_err(|e| Error::Read(format!("Invalid checkpoint batch: {}", e)))?;
+                let mut msg = MessageBatch::new_arrow(record_batch);
+                if input_names.is_valid(row) {
+                    msg.set_input_name(Some(input_names.value(row).to_string()));
+                }
+                messages.push(msg);
+            }
+        }
+    }
+    Ok(messages)
+}
 /// Acknowledgment implementation that combines multiple acknowledgments
 /// When acknowledged, it acknowledges all contained acknowledgments
 struct ArrayAck(Vec<Arc<dyn Ack>>);
@@ -268,13 +651,17 @@ pub fn init() -> Result<(), Error> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use arkflow_core::input::NoopAck;
 
     #[tokio::test]
     async fn test_memory_buffer_capacity_limit() {
         let buf = MemoryBuffer::new(MemoryBufferConfig {
             capacity: 2,
+            capacity_bytes: None,
             timeout: time::Duration::from_millis(100),
+            max_bytes: None,
+            checkpoint_path: None,
+            order: BufferOrder::Fifo,
+            priority_field: None,
         })
         .unwrap();
         let msg1 = MessageBatch::new_binary(vec![b"a".to_vec()]).unwrap();
@@ -289,11 +676,46 @@ mod tests {
         assert!(batch.is_some());
     }
 
+    #[tokio::test]
+    async fn test_memory_buffer_capacity_bytes_limit() {
+        let buf = Arc::new(
+            MemoryBuffer::new(MemoryBufferConfig {
+                capacity: 100,
+                capacity_bytes: Some(1024),
+                timeout: time::Duration::from_secs(10),
+                max_bytes: None,
+                checkpoint_path: None,
+                order: BufferOrder::Fifo,
+                priority_field: None,
+            })
+            .unwrap(),
+        );
+        let reader = tokio::spawn({
+            let buf = Arc::clone(&buf);
+            async move { buf.read().await }
+        });
+        tokio::time::sleep(time::Duration::from_millis(50)).await;
+
+        // A single row is far below the row capacity but above the byte capacity
+        let msg = MessageBatch::new_binary(vec![vec![b'x'; 4096]]).unwrap();
+        assert!(msg.estimate_heap_bytes() >= 4096);
+        buf.write(msg, Arc::new(NoopAck)).await.unwrap();
+
+        let r = tokio::time::timeout(time::Duration::from_millis(500), reader).await;
+        let batch = r.unwrap().unwrap().unwrap();
+        assert!(batch.is_some());
+    }
+
     #[tokio::test]
     async fn test_memory_buffer_timeout_notify() {
         let buf = MemoryBuffer::new(MemoryBufferConfig {
             capacity: 10,
+            capacity_bytes: None,
             timeout: time::Duration::from_millis(100),
+            max_bytes: None,
+            checkpoint_path: None,
+            order: BufferOrder::Fifo,
+            priority_field: None,
         })
         .unwrap();
         let msg = MessageBatch::new_binary(vec![b"x".to_vec()]).unwrap();
@@ -308,7 +730,12 @@ mod tests {
     async fn test_memory_buffer_flush() {
         let buf = MemoryBuffer::new(MemoryBufferConfig {
             capacity: 10,
+            capacity_bytes: None,
             timeout: time::Duration::from_secs(10),
+            max_bytes: None,
+            checkpoint_path: None,
+            order: BufferOrder::Fifo,
+            priority_field: None,
         })
         .unwrap();
         let msg = MessageBatch::new_binary(vec![b"flush".to_vec()]).unwrap();
@@ -324,7 +751,12 @@ mod tests {
     async fn test_memory_buffer_close() {
         let buf = MemoryBuffer::new(MemoryBufferConfig {
             capacity: 10,
+            capacity_bytes: None,
             timeout: time::Duration::from_secs(10),
+            max_bytes: None,
+            checkpoint_path: None,
+            order: BufferOrder::Fifo,
+            priority_field: None,
         })
         .unwrap();
         let msg = MessageBatch::new_binary(vec![b"close".to_vec()]).unwrap();
@@ -339,7 +771,12 @@ mod tests {
         let buf = Arc::new(
             MemoryBuffer::new(MemoryBufferConfig {
                 capacity: 100,
+                capacity_bytes: None,
                 timeout: time::Duration::from_millis(100),
+                max_bytes: None,
+                checkpoint_path: None,
+                order: BufferOrder::Fifo,
+                priority_field: None,
             })
             .unwrap(),
         );
@@ -362,4 +799,291 @@ mod tests {
         handle.await.unwrap();
         assert_eq!(total, 10);
     }
+
+    #[tokio::test]
+    async fn test_memory_buffer_releases_capacity_rows_per_read() {
+        let buf = MemoryBuffer::new(MemoryBufferConfig {
+            capacity: 2,
+            capacity_bytes: None,
+            timeout: time::Duration::from_secs(10),
+            max_bytes: None,
+            checkpoint_path: None,
+            order: BufferOrder::Fifo,
+            priority_field: None,
+        })
+        .unwrap();
+        for value in ["a", "b", "c"] {
+            let msg = MessageBatch::new_binary(vec![value.as_bytes().to_vec()]).unwrap();
+            buf.write(msg, Arc::new(NoopAck)).await.unwrap();
+        }
+
+        let (batch, _) = buf.read().await.unwrap().unwrap();
+        assert_eq!(batch.len(), 2);
+        let (batch, _) = buf.read().await.unwrap().unwrap();
+        assert_eq!(batch.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_memory_buffer_max_bytes() {
+        let buf = MemoryBuffer::new(MemoryBufferConfig {
+            capacity: 10,
+            capacity_bytes: None,
+            timeout: time::Duration::from_secs(10),
+            max_bytes: Some(1),
+            checkpoint_path: None,
+            order: BufferOrder::Fifo,
+            priority_field: None,
+        })
+        .unwrap();
+        for value in ["a", "b"] {
+            let msg = MessageBatch::new_binary(vec![value.as_bytes().to_vec()]).unwrap();
+            buf.write(msg, Arc::new(NoopAck)).await.unwrap();
+        }
+        buf.flush().await.unwrap();
+
+        // Every message exceeds max_bytes on its own, so they are released one by one
+        let (batch, _) = buf.read().await.unwrap().unwrap();
+        assert_eq!(batch.len(), 1);
+        let (batch, _) = buf.read().await.unwrap().unwrap();
+        assert_eq!(batch.len(), 1);
+        assert!(buf.read().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_memory_buffer_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("buffer.arrow");
+        let config = MemoryBufferConfig {
+            capacity: 10,
+            capacity_bytes: None,
+            timeout: time::Duration::from_secs(10),
+            max_bytes: None,
+            checkpoint_path: Some(path.to_str().unwrap().to_string()),
+            order: BufferOrder::Fifo,
+            priority_field: None,
+        };
+
+        let buf = MemoryBuffer::new(config.clone()).unwrap();
+        for value in ["a", "b"] {
+            let mut msg = MessageBatch::new_binary(vec![value.as_bytes().to_vec()]).unwrap();
+            msg.set_input_name(Some("input".to_string()));
+            buf.write(msg, Arc::new(NoopAck)).await.unwrap();
+        }
+        buf.close().await.unwrap();
+        assert!(path.exists());
+        assert!(buf.read().await.unwrap().is_none());
+
+        let buf = MemoryBuffer::new(config).unwrap();
+        assert!(!path.exists());
+        buf.flush().await.unwrap();
+        let (batch, _) = buf.read().await.unwrap().unwrap();
+        assert_eq!(
+            batch
+                .to_binary(arkflow_core::DEFAULT_BINARY_VALUE_FIELD)
+                .unwrap(),
+            vec![b"a".as_slice(), b"b".as_slice()]
+        );
+    }
+
+    fn order_config(order: BufferOrder, priority_field: Option<&str>) -> MemoryBufferConfig {
+        MemoryBufferConfig {
+            capacity: 1,
+            capacity_bytes: None,
+            timeout: time::Duration::from_secs(10),
+            max_bytes: None,
+            checkpoint_path: None,
+            order,
+            priority_field: priority_field.map(str::to_string),
+        }
+    }
+
+    fn prioritized(value: &str, priority: Option<i64>) -> MessageBatch {
+        let batch = RecordBatch::try_from_iter([
+            (
+                "value",
+                Arc::new(StringArray::from(vec![value])) as ArrayRef,
+            ),
+            (
+                "priority",
+                Arc::new(datafusion::arrow::array::Int64Array::from(vec![priority])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        MessageBatch::new_arrow(batch)
+    }
+
+    async fn read_values(buf: &MemoryBuffer, count: usize) -> Vec<String> {
+        let mut values = Vec::new();
+        for _ in 0..count {
+            let (batch, _) = buf.read().await.unwrap().unwrap();
+            let column = batch.column_by_name("value").unwrap().as_string::<i32>();
+            values.extend(column.iter().map(|v| v.unwrap().to_string()));
+        }
+        values
+    }
+
+    #[tokio::test]
+    async fn test_memory_buffer_lifo_order() {
+        let buf = MemoryBuffer::new(order_config(BufferOrder::Lifo, None)).unwrap();
+        for value in ["a", "b", "c"] {
+            buf.write(prioritized(value, None), Arc::new(NoopAck))
+                .await
+                .unwrap();
+        }
+        assert_eq!(read_values(&buf, 3).await, vec!["c", "b", "a"]);
+    }
+
+    #[tokio::test]
+    async fn test_memory_buffer_priority_order() {
+        let buf = MemoryBuffer::new(order_config(BufferOrder::Priority, Some("priority"))).unwrap();
+        for (value, priority) in [
+            ("low", Some(1)),
+            ("none", None),
+            ("high", Some(5)),
+            ("low2", Some(1)),
+        ] {
+            buf.write(prioritized(value, priority), Arc::new(NoopAck))
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            read_values(&buf, 4).await,
+            vec!["high", "low", "low2", "none"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_memory_buffer_priority_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("buffer.arrow");
+        let config = MemoryBufferConfig {
+            checkpoint_path: Some(path.to_str().unwrap().to_string()),
+            ..order_config(BufferOrder::Priority, Some("priority"))
+        };
+
+        let buf = MemoryBuffer::new(config.clone()).unwrap();
+        for (value, priority) in [("a", Some(1)), ("b", Some(2)), ("c", Some(1))] {
+            buf.write(prioritized(value, priority), Arc::new(NoopAck))
+                .await
+                .unwrap();
+        }
+        buf.close().await.unwrap();
+
+        let buf = MemoryBuffer::new(config).unwrap();
+        assert_eq!(read_values(&buf, 3).await, vec!["b", "a", "c"]);
+    }
+
+    #[test]
+    fn test_memory_buffer_priority_requires_field() {
+        let config: MemoryBufferConfig = serde_json::from_value(serde_json::json!({
+            "capacity": 10,
+            "timeout": "1s",
+            "order": "priority",
+        }))
+        .unwrap();
+        assert!(matches!(MemoryBuffer::new(config), Err(Error::Config(_))));
+    }
+
+    #[tokio::test]
+    async fn test_memory_buffer_mixed_content() {
+        let buf = MemoryBuffer::new(MemoryBufferConfig {
+            capacity: 10,
+            ..order_config(BufferOrder::Fifo, None)
+        })
+        .unwrap();
+        let binary = MessageBatch::new_binary(vec![b"raw".to_vec()]).unwrap();
+        buf.write(binary, Arc::new(NoopAck)).await.unwrap();
+        buf.write(prioritized("a", Some(1)), Arc::new(NoopAck))
+            .await
+            .unwrap();
+        buf.flush().await.unwrap();
+
+        let (batch, _) = buf.read().await.unwrap().unwrap();
+        assert_eq!(
+            batch
+                .to_binary(arkflow_core::DEFAULT_BINARY_VALUE_FIELD)
+                .unwrap(),
+            vec![
+                b"raw".as_slice(),
+                br#"{"value":"a","priority":1}"#.as_slice()
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_memory_buffer_keeps_input_name() {
+        let buf = MemoryBuffer::new(MemoryBufferConfig {
+            capacity: 10,
+            ..order_config(BufferOrder::Fifo, None)
+        })
+        .unwrap();
+        for value in [b"a", b"b"] {
+            let mut msg = MessageBatch::new_binary(vec![value.to_vec()]).unwrap();
+            msg.set_input_name(Some("orders".to_string()));
+            buf.write(msg, Arc::new(NoopAck)).await.unwrap();
+        }
+        buf.flush().await.unwrap();
+
+        let (batch, _) = buf.read().await.unwrap().unwrap();
+        assert_eq!(batch.len(), 2);
+        assert_eq!(batch.get_input_name(), Some("orders".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_memory_buffer_incompatible_schemas_requeued() {
+        let buf = MemoryBuffer::new(MemoryBufferConfig {
+            capacity: 10,
+            ..order_config(BufferOrder::Priority, Some("priority"))
+        })
+        .unwrap();
+        let other_schema = MessageBatch::new_arrow(
+            RecordBatch::try_from_iter([
+                ("value", Arc::new(StringArray::from(vec!["x"])) as ArrayRef),
+                (
+                    "priority",
+                    Arc::new(datafusion::arrow::array::Int64Array::from(vec![1])) as ArrayRef,
+                ),
+                (
+                    "extra",
+                    Arc::new(datafusion::arrow::array::Int64Array::from(vec![0])) as ArrayRef,
+                ),
+            ])
+            .unwrap(),
+        );
+        for msg in [
+            prioritized("a", Some(1)),
+            other_schema,
+            prioritized("b", Some(1)),
+        ] {
+            buf.write(msg, Arc::new(NoopAck)).await.unwrap();
+        }
+        buf.flush().await.unwrap();
+
+        // The messages cannot be merged, none of them is lost and their order is kept
+        assert_eq!(read_values(&buf, 3).await, vec!["a", "x", "b"]);
+        assert!(buf.read().await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_checkpoint_mixed_schemas() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("buffer.arrow");
+
+        let binary = MessageBatch::new_binary(vec![b"payload".to_vec()]).unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let columns: Vec<ArrayRef> =
+            vec![Arc::new(datafusion::arrow::array::Int64Array::from(vec![
+                1, 2,
+            ]))];
+        let mut arrow = MessageBatch::new_arrow(RecordBatch::try_new(schema, columns).unwrap());
+        arrow.set_input_name(Some("orders".to_string()));
+
+        write_checkpoint(&path, [&binary, &arrow].into_iter()).unwrap();
+        let restored = read_checkpoint(&path).unwrap();
+        assert_eq!(restored.len(), 2);
+        assert_eq!(restored[0].schema(), binary.schema());
+        assert_eq!(restored[0].get_input_name(), None);
+        assert_eq!(*restored[1], *arrow);
+        assert_eq!(restored[1].get_input_name(), Some("orders".to_string()));
+    }
 }
@@ -13,6 +13,7 @@
  */
 mod join;
 pub mod memory;
+pub mod priority;
 pub mod session_window;
 pub mod sliding_window;
 pub mod tumbling_window;
@@ -22,6 +23,7 @@ use arkflow_core::Error;
 
 pub fn init() -> Result<(), Error> {
     memory::init()?;
+    priority::init()?;
     tumbling_window::init()?;
     sliding_window::init()?;
     session_window::init()?;
@@ -0,0 +1,434 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Priority Buffer Implementation
+//!
+//! This module implements a buffer with two queues: messages whose priority column is `high`
+//! are always released before messages of the low-priority queue. Each queue has its own
+//! capacity threshold, so that urgent messages are not held back until normal traffic fills up.
+
+use crate::time::deserialize_duration;
+use arkflow_core::buffer::{register_buffer_builder, Buffer, BufferBuilder};
+use arkflow_core::input::{Ack, VecAck};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use datafusion::arrow::array::AsArray;
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::DataType;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time;
+use tokio::sync::{Notify, RwLock};
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+/// Value of the priority column marking a high-priority message
+const HIGH_PRIORITY: &str = "high";
+
+/// Configuration for the priority buffer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PriorityBufferConfig {
+    /// Column holding the priority of a message, `high` or `low`
+    priority_field: String,
+    /// Number of high-priority messages that triggers a release
+    high_priority_capacity: u32,
+    /// Number of low-priority messages that triggers a release
+    low_priority_capacity: u32,
+    /// Maximum time to wait before releasing accumulated messages
+    #[serde(deserialize_with = "deserialize_duration")]
+    timeout: time::Duration,
+}
+
+type QueuedMessage = (MessageBatch, Arc<dyn Ack>);
+
+/// Messages of one priority and their total number of rows
+#[derive(Default)]
+struct PriorityQueue {
+    messages: VecDeque<QueuedMessage>,
+    rows: usize,
+}
+
+impl PriorityQueue {
+    fn push(&mut self, message: QueuedMessage) {
+        self.rows += message.0.len();
+        self.messages.push_back(message);
+    }
+
+    /// Removes the oldest messages, up to `capacity` rows. A single message above the capacity
+    /// is removed on its own.
+    fn pop(&mut self, capacity: usize) -> Vec<QueuedMessage> {
+        let mut messages = Vec::new();
+        let mut rows = 0;
+        while let Some(message) = self.messages.pop_front() {
+            rows += message.0.len();
+            self.rows -= message.0.len();
+            messages.push(message);
+            if rows >= capacity {
+                break;
+            }
+        }
+        messages
+    }
+
+    /// Puts back popped messages, in the order they were popped, so that they are the next
+    /// to be released again
+    fn unpop(&mut self, messages: Vec<QueuedMessage>) {
+        for message in messages.into_iter().rev() {
+            self.rows += message.0.len();
+            self.messages.push_front(message);
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+/// High- and low-priority queues
+#[derive(Default)]
+struct PriorityQueues {
+    high: PriorityQueue,
+    low: PriorityQueue,
+}
+
+impl PriorityQueues {
+    fn is_empty(&self) -> bool {
+        self.high.is_empty() && self.low.is_empty()
+    }
+}
+
+/// Whether any row of the batch has the high priority. Batches without the column, or whose
+/// values are null or anything else, have the low priority.
+fn is_high_priority(msg: &MessageBatch, field: &str) -> bool {
+    msg.column_by_name(field)
+        .and_then(|column| cast(column, &DataType::Utf8).ok())
+        .is_some_and(|column| {
+            column
+                .as_string::<i32>()
+                .iter()
+                .flatten()
+                .any(|value| value.eq_ignore_ascii_case(HIGH_PRIORITY))
+        })
+}
+
+/// Priority buffer implementation
+/// Releases high-priority messages before low-priority ones
+struct PriorityBuffer {
+    /// Configuration parameters for the priority buffer
+    config: PriorityBufferConfig,
+    /// Thread-safe queues of message batches and their acknowledgments
+    queues: Arc<RwLock<PriorityQueues>>,
+    /// Notification mechanism for signaling between threads
+    notify: Arc<Notify>,
+    /// Token for cancellation of background tasks
+    close: CancellationToken,
+}
+
+impl PriorityBuffer {
+    /// Creates a new priority buffer with the given configuration
+    fn new(config: PriorityBufferConfig) -> Result<Self, Error> {
+        if config.high_priority_capacity == 0 || config.low_priority_capacity == 0 {
+            return Err(Error::Config(
+                "Priority buffer capacities must be greater than 0".to_string(),
+            ));
+        }
+
+        let notify = Arc::new(Notify::new());
+        let notify_clone = Arc::clone(&notify);
+        let duration = config.timeout;
+        let close = CancellationToken::new();
+        let close_clone = close.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let timer = sleep(duration);
+                tokio::select! {
+                    _ = timer => {
+                        // notify read
+                        notify_clone.notify_waiters();
+                    }
+                    _ = close_clone.cancelled() => {
+                        // notify read
+                        notify_clone.notify_waiters();
+                        break;
+                    }
+                    _ = notify_clone.notified() => {
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            close,
+            notify,
+            config,
+            queues: Arc::new(RwLock::new(PriorityQueues::default())),
+        })
+    }
+
+    /// Merges the next messages into a single batch, taken from the high-priority queue as
+    /// long as it is not empty
+    async fn process_messages(&self) -> Result<Option<(MessageBatch, Arc<dyn Ack>)>, Error> {
+        let mut queues = self.queues.write().await;
+
+        let queues = &mut *queues;
+        let (queue, capacity) = if !queues.high.is_empty() {
+            (&mut queues.high, self.config.high_priority_capacity)
+        } else {
+            (&mut queues.low, self.config.low_priority_capacity)
+        };
+        let mut popped = queue.pop(capacity as usize);
+        if popped.is_empty() {
+            return Ok(None);
+        }
+
+        // Messages that cannot be merged, e.g. Arrow messages with different schemas, are put
+        // back and the first one is released on its own
+        let messages: Vec<MessageBatch> = popped.iter().map(|(msg, _)| msg.clone()).collect();
+        let new_batch = match MessageBatch::concat(&messages) {
+            Ok(new_batch) => new_batch,
+            Err(e) if popped.len() > 1 => {
+                warn!("Releasing buffered messages one at a time: {}", e);
+                queue.unpop(popped.split_off(1));
+                messages[0].clone()
+            }
+            Err(e) => return Err(e),
+        };
+        let acks = popped.into_iter().map(|(_, ack)| ack).collect();
+        let new_ack = Arc::new(VecAck(acks));
+        Ok(Some((new_batch, new_ack)))
+    }
+}
+
+#[async_trait]
+impl Buffer for PriorityBuffer {
+    /// Writes a message batch to the queue of its priority
+    async fn write(&self, msg: MessageBatch, arc: Arc<dyn Ack>) -> Result<(), Error> {
+        let mut queues = self.queues.write().await;
+
+        // Each queue triggers a release on reaching its own capacity
+        let capacity_reached = if is_high_priority(&msg, &self.config.priority_field) {
+            queues.high.push((msg, arc));
+            queues.high.rows >= self.config.high_priority_capacity as usize
+        } else {
+            queues.low.push((msg, arc));
+            queues.low.rows >= self.config.low_priority_capacity as usize
+        };
+
+        if capacity_reached {
+            self.notify.notify_waiters();
+        }
+        Ok(())
+    }
+
+    /// Reads a message batch from the priority buffer
+    /// Waits until either messages are available or the buffer is closed
+    async fn read(&self) -> Result<Option<(MessageBatch, Arc<dyn Ack>)>, Error> {
+        loop {
+            {
+                let queues = self.queues.read().await;
+                // If there are messages available, break the loop and process them
+                if !queues.is_empty() {
+                    break;
+                }
+                // If the buffer is closed, return None
+                if self.close.is_cancelled() {
+                    return Ok(None);
+                }
+            }
+            // Wait for notification from timer, write operation, or close
+            self.notify.notified().await;
+        }
+        self.process_messages().await
+    }
+
+    /// Flushes the buffer by cancelling the background task and notifying waiters
+    async fn flush(&self) -> Result<(), Error> {
+        self.close.cancel();
+
+        let queues = self.queues.read().await;
+        if !queues.is_empty() {
+            // Notify any waiting readers to process remaining messages
+            self.notify.notify_waiters();
+        }
+        Ok(())
+    }
+
+    /// Closes the buffer by cancelling the background task
+    async fn close(&self) -> Result<(), Error> {
+        self.close.cancel();
+        Ok(())
+    }
+}
+
+struct PriorityBufferBuilder;
+
+impl BufferBuilder for PriorityBufferBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Buffer>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Priority buffer configuration is missing".to_string(),
+            ));
+        }
+
+        let config: PriorityBufferConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(PriorityBuffer::new(config)?))
+    }
+}
+
+/// Initializes the priority buffer by registering its builder
+pub fn init() -> Result<(), Error> {
+    register_buffer_builder("priority", Arc::new(PriorityBufferBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arkflow_core::input::NoopAck;
+    use datafusion::arrow::array::{ArrayRef, StringArray};
+    use datafusion::arrow::record_batch::RecordBatch;
+
+    fn config(high_priority_capacity: u32, low_priority_capacity: u32) -> PriorityBufferConfig {
+        PriorityBufferConfig {
+            priority_field: "priority".to_string(),
+            high_priority_capacity,
+            low_priority_capacity,
+            timeout: time::Duration::from_secs(10),
+        }
+    }
+
+    fn message(value: &str, priority: Option<&str>) -> MessageBatch {
+        let batch = RecordBatch::try_from_iter([
+            (
+                "value",
+                Arc::new(StringArray::from(vec![value])) as ArrayRef,
+            ),
+            (
+                "priority",
+                Arc::new(StringArray::from(vec![priority])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        MessageBatch::new_arrow(batch)
+    }
+
+    async fn read_values(buf: &PriorityBuffer) -> Vec<String> {
+        let (batch, _) = buf.read().await.unwrap().unwrap();
+        let column = batch.column_by_name("value").unwrap().as_string::<i32>();
+        column.iter().map(|v| v.unwrap().to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_priority_buffer_high_priority_first() {
+        let buf = PriorityBuffer::new(config(10, 10)).unwrap();
+        for (value, priority) in [
+            ("a", Some("low")),
+            ("b", Some("high")),
+            ("c", None),
+            ("d", Some("HIGH")),
+        ] {
+            buf.write(message(value, priority), Arc::new(NoopAck))
+                .await
+                .unwrap();
+        }
+        buf.flush().await.unwrap();
+
+        assert_eq!(read_values(&buf).await, vec!["b", "d"]);
+        assert_eq!(read_values(&buf).await, vec!["a", "c"]);
+        assert!(buf.read().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_priority_buffer_incompatible_schemas_requeued() {
+        let buf = PriorityBuffer::new(config(10, 10)).unwrap();
+        let other_schema = MessageBatch::new_arrow(
+            RecordBatch::try_from_iter([
+                ("value", Arc::new(StringArray::from(vec!["x"])) as ArrayRef),
+                (
+                    "priority",
+                    Arc::new(StringArray::from(vec!["high"])) as ArrayRef,
+                ),
+                ("extra", Arc::new(StringArray::from(vec!["0"])) as ArrayRef),
+            ])
+            .unwrap(),
+        );
+        for msg in [
+            message("a", Some("high")),
+            other_schema,
+            message("b", Some("high")),
+        ] {
+            buf.write(msg, Arc::new(NoopAck)).await.unwrap();
+        }
+        buf.flush().await.unwrap();
+
+        // The messages cannot be merged, none of them is lost and their order is kept
+        assert_eq!(read_values(&buf).await, vec!["a"]);
+        assert_eq!(read_values(&buf).await, vec!["x"]);
+        assert_eq!(read_values(&buf).await, vec!["b"]);
+        assert!(buf.read().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_priority_buffer_capacity_per_queue() {
+        let buf = PriorityBuffer::new(config(1, 2)).unwrap();
+        for (value, priority) in [("a", "low"), ("b", "high"), ("c", "high"), ("d", "low")] {
+            buf.write(message(value, Some(priority)), Arc::new(NoopAck))
+                .await
+                .unwrap();
+        }
+        buf.flush().await.unwrap();
+
+        assert_eq!(read_values(&buf).await, vec!["b"]);
+        assert_eq!(read_values(&buf).await, vec!["c"]);
+        assert_eq!(read_values(&buf).await, vec!["a", "d"]);
+    }
+
+    #[tokio::test]
+    async fn test_priority_buffer_high_capacity_notify() {
+        let buf = Arc::new(PriorityBuffer::new(config(1, 100)).unwrap());
+        let reader = tokio::spawn({
+            let buf = Arc::clone(&buf);
+            async move { buf.read().await }
+        });
+        tokio::time::sleep(time::Duration::from_millis(50)).await;
+
+        // Reaching the high-priority capacity releases the message before the timeout
+        buf.write(message("urgent", Some("high")), Arc::new(NoopAck))
+            .await
+            .unwrap();
+        let r = tokio::time::timeout(time::Duration::from_millis(500), reader).await;
+        let batch = r.unwrap().unwrap().unwrap();
+        assert!(batch.is_some());
+    }
+
+    #[test]
+    fn test_priority_buffer_zero_capacity() {
+        let config: PriorityBufferConfig = serde_json::from_value(serde_json::json!({
+            "priority_field": "priority",
+            "high_priority_capacity": 0,
+            "low_priority_capacity": 10,
+            "timeout": "1s",
+        }))
+        .unwrap();
+        assert!(matches!(PriorityBuffer::new(config), Err(Error::Config(_))));
+    }
+}
@@ -0,0 +1,80 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Arrow Flight helpers shared by the Arrow Flight input and output
+
+use arkflow_core::Error;
+use serde::{Deserialize, Serialize};
+use tonic::transport::{Certificate, ClientTlsConfig, Identity, ServerTlsConfig};
+
+/// TLS configuration of an Arrow Flight connection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// PEM file of the CA certificate used to verify the peer
+    pub ca_cert: Option<String>,
+    /// PEM file of the certificate presented to the peer
+    pub cert: Option<String>,
+    /// PEM file of the private key of `cert`
+    pub key: Option<String>,
+    /// Name the server certificate is verified against, defaults to the endpoint host
+    pub domain: Option<String>,
+}
+
+impl TlsConfig {
+    /// TLS settings of a Flight client, trusting the bundled web PKI roots without `ca_cert`
+    pub fn client_config(&self) -> Result<ClientTlsConfig, Error> {
+        let mut config = ClientTlsConfig::new();
+        config = match &self.ca_cert {
+            Some(path) => config.ca_certificate(Certificate::from_pem(read_pem(path)?)),
+            None => config.with_enabled_roots(),
+        };
+        if let Some(domain) = &self.domain {
+            config = config.domain_name(domain);
+        }
+        if let Some(identity) = self.identity()? {
+            config = config.identity(identity);
+        }
+        Ok(config)
+    }
+
+    /// TLS settings of a Flight server, clients must present a certificate signed by `ca_cert` if set
+    pub fn server_config(&self) -> Result<ServerTlsConfig, Error> {
+        let Some(identity) = self.identity()? else {
+            return Err(Error::Config(
+                "A Flight server requires both cert and key to use TLS".to_string(),
+            ));
+        };
+        let mut config = ServerTlsConfig::new().identity(identity);
+        if let Some(path) = &self.ca_cert {
+            config = config.client_ca_root(Certificate::from_pem(read_pem(path)?));
+        }
+        Ok(config)
+    }
+
+    fn identity(&self) -> Result<Option<Identity>, Error> {
+        match (&self.cert, &self.key) {
+            (Some(cert), Some(key)) => {
+                Ok(Some(Identity::from_pem(read_pem(cert)?, read_pem(key)?)))
+            }
+            (None, None) => Ok(None),
+            _ => Err(Error::Config(
+                "Both cert and key must be set to use a TLS certificate".to_string(),
+            )),
+        }
+    }
+}
+
+fn read_pem(path: &str) -> Result<Vec<u8>, Error> {
+    std::fs::read(path).map_err(|e| Error::Config(format!("Failed to read {}: {}", path, e)))
+}
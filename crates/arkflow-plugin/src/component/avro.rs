@@ -0,0 +1,867 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Avro serialization with a Confluent schema registry
+//!
+//! Rows are encoded as Avro datums using a record schema derived from the Arrow schema and
+//! framed with the Confluent wire format: a zero magic byte followed by the big-endian
+//! schema ID. Framed messages are decoded with the writer schema looked up by that ID.
+
+use apache_avro::types::Value as AvroValue;
+use apache_avro::Schema as AvroSchema;
+use arkflow_core::{Error, DEFAULT_BINARY_VALUE_FIELD};
+use datafusion::arrow::array::{
+    new_null_array, Array, ArrayRef, BinaryArray, BooleanArray, Date32Array, Float32Array,
+    Float64Array, Int32Array, Int64Array, ListArray, StringArray, StructArray,
+    TimestampMicrosecondArray, TimestampMillisecondArray,
+};
+use datafusion::arrow::buffer::{NullBuffer, OffsetBuffer};
+use datafusion::arrow::datatypes::{DataType, Field, Fields, Schema, SchemaRef, TimeUnit};
+use datafusion::arrow::record_batch::RecordBatch;
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+
+/// Magic byte of the Confluent wire format
+const MAGIC_BYTE: u8 = 0;
+/// Length of the Confluent header: the magic byte and the schema ID
+const HEADER_LEN: usize = 5;
+/// Content type expected by the schema registry REST API
+const REGISTRY_CONTENT_TYPE: &str = "application/vnd.schemaregistry.v1+json";
+/// Number of writer schemas kept by a deserializer
+const SCHEMA_CACHE_SIZE: usize = 256;
+/// Stand-in for the fields of a null record
+const NULL: &AvroValue = &AvroValue::Null;
+
+/// How the registry subject of a record is named
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubjectNameStrategy {
+    /// `<topic>-value`
+    #[default]
+    Topic,
+    /// The fully qualified record name
+    Record,
+    /// `<topic>-<record name>`
+    TopicRecord,
+}
+
+/// Schema registry configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaRegistryConfig {
+    /// Base URL of the schema registry
+    pub url: String,
+    /// Subject name strategy
+    #[serde(default)]
+    pub subject_name_strategy: SubjectNameStrategy,
+    /// Fully qualified name of the generated Avro record
+    #[serde(default = "default_record_name")]
+    pub record_name: String,
+}
+
+fn default_record_name() -> String {
+    "arkflow.Record".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+struct RegisterResponse {
+    id: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct SchemaResponse {
+    schema: String,
+}
+
+/// Serializes record batches to Confluent-framed Avro
+pub struct AvroSerializer {
+    config: SchemaRegistryConfig,
+    http: reqwest::Client,
+    /// Schema IDs by subject and schema definition
+    ids: Mutex<HashMap<(String, String), u32>>,
+}
+
+impl AvroSerializer {
+    pub fn new(config: SchemaRegistryConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+            ids: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Encode every row of `batch`, `topic` giving the destination topic of a row
+    pub async fn serialize<'a>(
+        &self,
+        batch: &RecordBatch,
+        topic: impl Fn(usize) -> &'a str,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        let definition = avro_schema(&batch.schema(), &self.config.record_name)?;
+        let schema = apache_avro::Schema::parse(&definition)
+            .map_err(|e| Error::Process(format!("Invalid Avro schema: {}", e)))?;
+        let definition = definition.to_string();
+
+        let mut payloads = Vec::with_capacity(batch.num_rows());
+        for row in 0..batch.num_rows() {
+            let id = self
+                .schema_id(&self.subject(topic(row)), &definition)
+                .await?;
+            let datum = apache_avro::to_avro_datum(&schema, row_value(batch, row)?)
+                .map_err(|e| Error::Process(format!("Avro serialization failed: {}", e)))?;
+
+            let mut payload = Vec::with_capacity(datum.len() + 5);
+            payload.push(MAGIC_BYTE);
+            payload.extend_from_slice(&id.to_be_bytes());
+            payload.extend_from_slice(&datum);
+            payloads.push(payload);
+        }
+        Ok(payloads)
+    }
+
+    fn subject(&self, topic: &str) -> String {
+        match self.config.subject_name_strategy {
+            SubjectNameStrategy::Topic => format!("{}-value", topic),
+            SubjectNameStrategy::Record => self.config.record_name.clone(),
+            SubjectNameStrategy::TopicRecord => format!("{}-{}", topic, self.config.record_name),
+        }
+    }
+
+    /// Look up the ID of `definition` under `subject`, registering it on first use
+    async fn schema_id(&self, subject: &str, definition: &str) -> Result<u32, Error> {
+        let key = (subject.to_string(), definition.to_string());
+        if let Some(id) = self.ids.lock().unwrap().get(&key) {
+            return Ok(*id);
+        }
+
+        // Registering an existing schema returns its ID, so this doubles as the lookup
+        let url = format!(
+            "{}/subjects/{}/versions",
+            self.config.url.trim_end_matches('/'),
+            subject
+        );
+        let response = self
+            .http
+            .post(&url)
+            .header(reqwest::header::CONTENT_TYPE, REGISTRY_CONTENT_TYPE)
+            .json(&json!({ "schema": definition }))
+            .send()
+            .await
+            .map_err(|e| Error::Connection(format!("Schema registry request failed: {}", e)))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(Error::Process(format!(
+                "Schema registry rejected the schema for subject {}: {} {}",
+                subject, status, body
+            )));
+        }
+        let RegisterResponse { id } = response
+            .json()
+            .await
+            .map_err(|e| Error::Process(format!("Invalid schema registry response: {}", e)))?;
+
+        self.ids.lock().unwrap().insert(key, id);
+        Ok(id)
+    }
+}
+
+/// Writer schema registered under an ID, with the Arrow schema of the batches it decodes to
+struct WriterSchema {
+    avro: AvroSchema,
+    arrow: SchemaRef,
+}
+
+/// Deserializes Confluent-framed Avro messages to record batches
+pub struct AvroDeserializer {
+    config: SchemaRegistryConfig,
+    http: reqwest::Client,
+    /// Recently used writer schemas by ID
+    schemas: Mutex<LruCache<u32, Arc<WriterSchema>>>,
+}
+
+impl AvroDeserializer {
+    pub fn new(config: SchemaRegistryConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+            schemas: Mutex::new(LruCache::new(NonZeroUsize::new(SCHEMA_CACHE_SIZE).unwrap())),
+        }
+    }
+
+    /// Decode `payload` into a batch of one row, see [`arrow_schema`] for its columns
+    pub async fn deserialize(&self, payload: &[u8]) -> Result<RecordBatch, Error> {
+        if payload.len() < HEADER_LEN || payload[0] != MAGIC_BYTE {
+            return Err(Error::Process(
+                "The message is not framed with the Confluent wire format".to_string(),
+            ));
+        }
+        let id = u32::from_be_bytes([payload[1], payload[2], payload[3], payload[4]]);
+        let schema = self.writer_schema(id).await?;
+        let value = apache_avro::from_avro_datum(&schema.avro, &mut &payload[HEADER_LEN..], None)
+            .map_err(|e| Error::Process(format!("Avro deserialization failed: {}", e)))?;
+
+        let columns = match value {
+            AvroValue::Record(entries) => schema
+                .arrow
+                .fields()
+                .iter()
+                .zip(&entries)
+                .map(|(field, (_, value))| arrow_array(field, &[value]))
+                .collect::<Result<Vec<_>, Error>>()?,
+            value => vec![arrow_array(&schema.arrow.fields()[0], &[&value])?],
+        };
+        RecordBatch::try_new(schema.arrow.clone(), columns)
+            .map_err(|e| Error::Process(format!("Creating an Arrow record batch failed: {}", e)))
+    }
+
+    /// Look up the schema registered under `id`, fetching it from the registry on a cache miss
+    async fn writer_schema(&self, id: u32) -> Result<Arc<WriterSchema>, Error> {
+        if let Some(schema) = self.schemas.lock().unwrap().get(&id) {
+            return Ok(schema.clone());
+        }
+
+        let url = format!(
+            "{}/schemas/ids/{}",
+            self.config.url.trim_end_matches('/'),
+            id
+        );
+        let response = self
+            .http
+            .get(&url)
+            .header(reqwest::header::ACCEPT, REGISTRY_CONTENT_TYPE)
+            .send()
+            .await
+            .map_err(|e| Error::Connection(format!("Schema registry request failed: {}", e)))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(Error::Process(format!(
+                "Schema registry lookup of schema {} failed: {} {}",
+                id, status, body
+            )));
+        }
+        let SchemaResponse { schema } = response
+            .json()
+            .await
+            .map_err(|e| Error::Process(format!("Invalid schema registry response: {}", e)))?;
+
+        let avro = AvroSchema::parse_str(&schema)
+            .map_err(|e| Error::Process(format!("Invalid Avro schema {}: {}", id, e)))?;
+        let arrow = Arc::new(arrow_schema(&avro)?);
+        let schema = Arc::new(WriterSchema { avro, arrow });
+        self.schemas.lock().unwrap().put(id, schema.clone());
+        Ok(schema)
+    }
+}
+
+/// Derive the Arrow schema of the batches decoded with an Avro schema
+///
+/// A record becomes one column per field and any other schema a single `__value__` column.
+/// Arrays become lists, nested records structs and `[null, T]` unions nullable columns of `T`.
+pub fn arrow_schema(schema: &AvroSchema) -> Result<Schema, Error> {
+    let fields = match schema {
+        AvroSchema::Record(record) => record
+            .fields
+            .iter()
+            .map(|field| arrow_field(&field.name, &field.schema))
+            .collect::<Result<Vec<_>, Error>>()?,
+        other => vec![arrow_field(DEFAULT_BINARY_VALUE_FIELD, other)?],
+    };
+    Ok(Schema::new(fields))
+}
+
+fn arrow_field(name: &str, schema: &AvroSchema) -> Result<Field, Error> {
+    let (schema, nullable) = match schema {
+        AvroSchema::Union(union) => match union.variants() {
+            [AvroSchema::Null, inner] | [inner, AvroSchema::Null] => (inner, true),
+            _ => {
+                return Err(Error::Process(format!(
+                    "Field {} is a union other than [null, T] which cannot be mapped to Arrow",
+                    name
+                )))
+            }
+        },
+        schema => (schema, false),
+    };
+    let data_type = match schema {
+        AvroSchema::Null => return Ok(Field::new(name, DataType::Null, true)),
+        AvroSchema::Boolean => DataType::Boolean,
+        AvroSchema::Int => DataType::Int32,
+        AvroSchema::Long => DataType::Int64,
+        AvroSchema::Float => DataType::Float32,
+        AvroSchema::Double => DataType::Float64,
+        AvroSchema::Bytes | AvroSchema::Fixed(_) => DataType::Binary,
+        AvroSchema::String | AvroSchema::Enum(_) | AvroSchema::Uuid => DataType::Utf8,
+        AvroSchema::Date => DataType::Date32,
+        AvroSchema::TimestampMillis => DataType::Timestamp(TimeUnit::Millisecond, None),
+        AvroSchema::TimestampMicros => DataType::Timestamp(TimeUnit::Microsecond, None),
+        AvroSchema::Array(array) => DataType::List(Arc::new(arrow_field("item", &array.items)?)),
+        AvroSchema::Record(record) => DataType::Struct(
+            record
+                .fields
+                .iter()
+                .map(|field| arrow_field(&field.name, &field.schema))
+                .collect::<Result<Fields, Error>>()?,
+        ),
+        other => {
+            return Err(Error::Process(format!(
+                "Field {} has Avro type {:?} which cannot be mapped to Arrow",
+                name,
+                apache_avro::schema::SchemaKind::from(other)
+            )))
+        }
+    };
+    Ok(Field::new(name, data_type, nullable))
+}
+
+/// Build the column of `field` from the decoded values of its rows
+fn arrow_array(field: &Field, values: &[&AvroValue]) -> Result<ArrayRef, Error> {
+    let values: Vec<&AvroValue> = values
+        .iter()
+        .map(|value| match value {
+            AvroValue::Union(_, inner) => inner.as_ref(),
+            value => value,
+        })
+        .collect();
+
+    Ok(match field.data_type() {
+        DataType::Null => new_null_array(&DataType::Null, values.len()),
+        DataType::Boolean => Arc::new(collect::<_, BooleanArray>(field, &values, |v| match v {
+            AvroValue::Boolean(v) => Some(*v),
+            _ => None,
+        })?),
+        DataType::Int32 => Arc::new(collect::<_, Int32Array>(field, &values, |v| match v {
+            AvroValue::Int(v) => Some(*v),
+            _ => None,
+        })?),
+        DataType::Int64 => Arc::new(collect::<_, Int64Array>(field, &values, |v| match v {
+            AvroValue::Long(v) => Some(*v),
+            _ => None,
+        })?),
+        DataType::Float32 => Arc::new(collect::<_, Float32Array>(field, &values, |v| match v {
+            AvroValue::Float(v) => Some(*v),
+            _ => None,
+        })?),
+        DataType::Float64 => Arc::new(collect::<_, Float64Array>(field, &values, |v| match v {
+            AvroValue::Double(v) => Some(*v),
+            _ => None,
+        })?),
+        DataType::Binary => Arc::new(collect::<_, BinaryArray>(field, &values, |v| match v {
+            AvroValue::Bytes(v) | AvroValue::Fixed(_, v) => Some(v.as_slice()),
+            _ => None,
+        })?),
+        DataType::Utf8 => Arc::new(collect::<_, StringArray>(field, &values, |v| match v {
+            AvroValue::String(v) | AvroValue::Enum(_, v) => Some(v.clone()),
+            AvroValue::Uuid(v) => Some(v.to_string()),
+            _ => None,
+        })?),
+        DataType::Date32 => Arc::new(collect::<_, Date32Array>(field, &values, |v| match v {
+            AvroValue::Date(v) => Some(*v),
+            _ => None,
+        })?),
+        DataType::Timestamp(TimeUnit::Millisecond, None) => {
+            Arc::new(collect::<_, TimestampMillisecondArray>(
+                field,
+                &values,
+                |v| match v {
+                    AvroValue::TimestampMillis(v) => Some(*v),
+                    _ => None,
+                },
+            )?)
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, None) => {
+            Arc::new(collect::<_, TimestampMicrosecondArray>(
+                field,
+                &values,
+                |v| match v {
+                    AvroValue::TimestampMicros(v) => Some(*v),
+                    _ => None,
+                },
+            )?)
+        }
+        DataType::List(item) => {
+            let mut lengths = Vec::with_capacity(values.len());
+            let mut valid = Vec::with_capacity(values.len());
+            let mut items = Vec::new();
+            for value in &values {
+                match value {
+                    AvroValue::Null => {
+                        lengths.push(0);
+                        valid.push(false);
+                    }
+                    AvroValue::Array(elements) => {
+                        lengths.push(elements.len());
+                        valid.push(true);
+                        items.extend(elements);
+                    }
+                    _ => return Err(unexpected_value(field)),
+                }
+            }
+            let items = arrow_array(item, &items)?;
+            Arc::new(
+                ListArray::try_new(
+                    item.clone(),
+                    OffsetBuffer::from_lengths(lengths),
+                    items,
+                    Some(NullBuffer::from(valid)),
+                )
+                .map_err(|e| {
+                    Error::Process(format!(
+                        "Building list field {} failed: {}",
+                        field.name(),
+                        e
+                    ))
+                })?,
+            )
+        }
+        DataType::Struct(fields) => {
+            let mut valid = Vec::with_capacity(values.len());
+            let mut children = vec![Vec::with_capacity(values.len()); fields.len()];
+            for value in &values {
+                match value {
+                    AvroValue::Null => {
+                        valid.push(false);
+                        children.iter_mut().for_each(|child| child.push(NULL));
+                    }
+                    AvroValue::Record(entries) if entries.len() == fields.len() => {
+                        valid.push(true);
+                        for (child, (_, value)) in children.iter_mut().zip(entries) {
+                            child.push(value);
+                        }
+                    }
+                    _ => return Err(unexpected_value(field)),
+                }
+            }
+            let columns = fields
+                .iter()
+                .zip(&children)
+                .map(|(field, values)| arrow_array(field, values))
+                .collect::<Result<Vec<_>, Error>>()?;
+            Arc::new(
+                StructArray::try_new(fields.clone(), columns, Some(NullBuffer::from(valid)))
+                    .map_err(|e| {
+                        Error::Process(format!(
+                            "Building record field {} failed: {}",
+                            field.name(),
+                            e
+                        ))
+                    })?,
+            )
+        }
+        other => {
+            return Err(Error::Process(format!(
+                "Field {} has type {} which cannot be decoded from Avro",
+                field.name(),
+                other
+            )))
+        }
+    })
+}
+
+/// Collect the values of a primitive column, `get` extracting a non-null value
+fn collect<'a, T, A: FromIterator<Option<T>>>(
+    field: &Field,
+    values: &[&'a AvroValue],
+    get: impl Fn(&'a AvroValue) -> Option<T>,
+) -> Result<A, Error> {
+    values
+        .iter()
+        .map(|value| match value {
+            AvroValue::Null => Ok(None),
+            value => get(value).map(Some).ok_or_else(|| unexpected_value(field)),
+        })
+        .collect()
+}
+
+fn unexpected_value(field: &Field) -> Error {
+    Error::Process(format!(
+        "Unexpected Avro value for field {} of type {}",
+        field.name(),
+        field.data_type()
+    ))
+}
+
+/// Derive an Avro record schema from an Arrow schema
+///
+/// Nullable fields become a union with `null` that defaults to `null`.
+pub fn avro_schema(schema: &Schema, record_name: &str) -> Result<Value, Error> {
+    let fields = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            let avro_type = match field.data_type() {
+                DataType::Null => return Ok(json!({ "name": field.name(), "type": "null" })),
+                DataType::Utf8 => "string",
+                DataType::Int64 => "long",
+                DataType::Float64 => "double",
+                DataType::Boolean => "boolean",
+                DataType::Binary => "bytes",
+                other => {
+                    return Err(Error::Process(format!(
+                        "Field {} has type {} which cannot be mapped to Avro",
+                        field.name(),
+                        other
+                    )))
+                }
+            };
+            Ok(if field.is_nullable() {
+                json!({ "name": field.name(), "type": ["null", avro_type], "default": null })
+            } else {
+                json!({ "name": field.name(), "type": avro_type })
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok(json!({ "type": "record", "name": record_name, "fields": fields }))
+}
+
+fn row_value(batch: &RecordBatch, row: usize) -> Result<AvroValue, Error> {
+    let schema = batch.schema();
+    let mut fields = Vec::with_capacity(batch.num_columns());
+    for (field, column) in schema.fields().iter().zip(batch.columns()) {
+        if *field.data_type() == DataType::Null {
+            fields.push((field.name().clone(), AvroValue::Null));
+            continue;
+        }
+
+        let value = if column.is_null(row) {
+            AvroValue::Null
+        } else {
+            match field.data_type() {
+                DataType::Utf8 => AvroValue::String(
+                    downcast::<StringArray>(column, field.name())?
+                        .value(row)
+                        .to_string(),
+                ),
+                DataType::Int64 => {
+                    AvroValue::Long(downcast::<Int64Array>(column, field.name())?.value(row))
+                }
+                DataType::Float64 => {
+                    AvroValue::Double(downcast::<Float64Array>(column, field.name())?.value(row))
+                }
+                DataType::Boolean => {
+                    AvroValue::Boolean(downcast::<BooleanArray>(column, field.name())?.value(row))
+                }
+                DataType::Binary => AvroValue::Bytes(
+                    downcast::<BinaryArray>(column, field.name())?
+                        .value(row)
+                        .to_vec(),
+                ),
+                other => {
+                    return Err(Error::Process(format!(
+                        "Field {} has type {} which cannot be mapped to Avro",
+                        field.name(),
+                        other
+                    )))
+                }
+            }
+        };
+
+        let value = match (field.is_nullable(), value) {
+            (false, AvroValue::Null) => {
+                return Err(Error::Process(format!(
+                    "Field {} is not nullable but contains a null",
+                    field.name()
+                )))
+            }
+            (false, value) => value,
+            (true, AvroValue::Null) => AvroValue::Union(0, Box::new(AvroValue::Null)),
+            (true, value) => AvroValue::Union(1, Box::new(value)),
+        };
+        fields.push((field.name().clone(), value));
+    }
+    Ok(AvroValue::Record(fields))
+}
+
+fn downcast<'a, T: 'static>(column: &'a dyn Array, name: &str) -> Result<&'a T, Error> {
+    column
+        .as_any()
+        .downcast_ref::<T>()
+        .ok_or_else(|| Error::Process(format!("Unexpected array type for field {}", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::Path;
+    use axum::http::StatusCode;
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use datafusion::arrow::array::{AsArray, NullArray};
+    use datafusion::arrow::datatypes::Int64Type;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn batch() -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, false),
+            Field::new("count", DataType::Int64, true),
+            Field::new("ratio", DataType::Float64, false),
+            Field::new("ok", DataType::Boolean, false),
+            Field::new("raw", DataType::Binary, false),
+            Field::new("nothing", DataType::Null, true),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["a", "b"])),
+                Arc::new(Int64Array::from(vec![Some(1), None])),
+                Arc::new(Float64Array::from(vec![0.5, 1.5])),
+                Arc::new(BooleanArray::from(vec![true, false])),
+                Arc::new(BinaryArray::from_vec(vec![b"x", b"y"])),
+                Arc::new(NullArray::new(2)),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_avro_schema() {
+        let schema = avro_schema(&batch().schema(), "arkflow.Record").unwrap();
+        assert_eq!(
+            schema,
+            json!({
+                "type": "record",
+                "name": "arkflow.Record",
+                "fields": [
+                    {"name": "name", "type": "string"},
+                    {"name": "count", "type": ["null", "long"], "default": null},
+                    {"name": "ratio", "type": "double"},
+                    {"name": "ok", "type": "boolean"},
+                    {"name": "raw", "type": "bytes"},
+                    {"name": "nothing", "type": "null"}
+                ]
+            })
+        );
+
+        let unsupported = Schema::new(vec![Field::new("t", DataType::Date32, false)]);
+        assert!(avro_schema(&unsupported, "arkflow.Record").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_serialize_registers_schema_once() {
+        let subjects = Arc::new(Mutex::new(Vec::<String>::new()));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let (captured, counter) = (subjects.clone(), calls.clone());
+        let app = Router::new().route(
+            "/subjects/:subject/versions",
+            post(
+                move |Path(subject): Path<String>, Json(body): Json<Value>| {
+                    let captured = captured.clone();
+                    let counter = counter.clone();
+                    async move {
+                        assert!(body["schema"].is_string());
+                        counter.fetch_add(1, Ordering::SeqCst);
+                        captured.lock().unwrap().push(subject);
+                        Json(json!({ "id": 42 }))
+                    }
+                },
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let serializer = AvroSerializer::new(SchemaRegistryConfig {
+            url: format!("http://{}", addr),
+            subject_name_strategy: SubjectNameStrategy::Topic,
+            record_name: default_record_name(),
+        });
+        let batch = batch();
+        let payloads = serializer.serialize(&batch, |_| "events").await.unwrap();
+        serializer.serialize(&batch, |_| "events").await.unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(*subjects.lock().unwrap(), vec!["events-value".to_string()]);
+        assert_eq!(payloads.len(), 2);
+        assert_eq!(&payloads[0][..5], &[0, 0, 0, 0, 42]);
+
+        let definition = avro_schema(&batch.schema(), "arkflow.Record").unwrap();
+        let schema = apache_avro::Schema::parse(&definition).unwrap();
+        let decoded = apache_avro::from_avro_datum(&schema, &mut &payloads[1][5..], None).unwrap();
+        assert_eq!(
+            decoded,
+            AvroValue::Record(vec![
+                ("name".to_string(), AvroValue::String("b".to_string())),
+                (
+                    "count".to_string(),
+                    AvroValue::Union(0, Box::new(AvroValue::Null))
+                ),
+                ("ratio".to_string(), AvroValue::Double(1.5)),
+                ("ok".to_string(), AvroValue::Boolean(false)),
+                ("raw".to_string(), AvroValue::Bytes(b"y".to_vec())),
+                ("nothing".to_string(), AvroValue::Null),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_subject_name_strategy() {
+        let serializer = |strategy| {
+            AvroSerializer::new(SchemaRegistryConfig {
+                url: "http://localhost:8081".to_string(),
+                subject_name_strategy: strategy,
+                record_name: "com.example.Event".to_string(),
+            })
+        };
+        assert_eq!(
+            serializer(SubjectNameStrategy::Topic).subject("events"),
+            "events-value"
+        );
+        assert_eq!(
+            serializer(SubjectNameStrategy::Record).subject("events"),
+            "com.example.Event"
+        );
+        assert_eq!(
+            serializer(SubjectNameStrategy::TopicRecord).subject("events"),
+            "events-com.example.Event"
+        );
+    }
+
+    const ORDER_SCHEMA: &str = r#"{
+        "type": "record",
+        "name": "Order",
+        "fields": [
+            {"name": "id", "type": "long"},
+            {"name": "note", "type": ["null", "string"], "default": null},
+            {"name": "tags", "type": {"type": "array", "items": "string"}},
+            {"name": "customer", "type": {
+                "type": "record",
+                "name": "Customer",
+                "fields": [{"name": "name", "type": "string"}]
+            }}
+        ]
+    }"#;
+
+    fn registry_config(addr: std::net::SocketAddr) -> SchemaRegistryConfig {
+        SchemaRegistryConfig {
+            url: format!("http://{}", addr),
+            subject_name_strategy: SubjectNameStrategy::Topic,
+            record_name: default_record_name(),
+        }
+    }
+
+    #[test]
+    fn test_arrow_schema() {
+        let schema = arrow_schema(&AvroSchema::parse_str(ORDER_SCHEMA).unwrap()).unwrap();
+        assert_eq!(
+            schema,
+            Schema::new(vec![
+                Field::new("id", DataType::Int64, false),
+                Field::new("note", DataType::Utf8, true),
+                Field::new(
+                    "tags",
+                    DataType::List(Arc::new(Field::new("item", DataType::Utf8, false))),
+                    false
+                ),
+                Field::new(
+                    "customer",
+                    DataType::Struct(vec![Field::new("name", DataType::Utf8, false)].into()),
+                    false
+                ),
+            ])
+        );
+
+        let schema = arrow_schema(&AvroSchema::Long).unwrap();
+        assert_eq!(schema.field(0).name(), DEFAULT_BINARY_VALUE_FIELD);
+
+        let union = AvroSchema::parse_str(r#"["string", "long"]"#).unwrap();
+        assert!(arrow_schema(&union).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_deserialize_caches_schema() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let app = Router::new().route(
+            "/schemas/ids/:id",
+            get(move |Path(id): Path<u32>| {
+                let counter = counter.clone();
+                async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    match id {
+                        7 => Ok(Json(json!({ "schema": ORDER_SCHEMA }))),
+                        _ => Err(StatusCode::NOT_FOUND),
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let schema = AvroSchema::parse_str(ORDER_SCHEMA).unwrap();
+        let order = |id: i64, note: Option<&str>| {
+            let note = match note {
+                Some(note) => AvroValue::Union(1, Box::new(AvroValue::String(note.to_string()))),
+                None => AvroValue::Union(0, Box::new(AvroValue::Null)),
+            };
+            let value = AvroValue::Record(vec![
+                ("id".to_string(), AvroValue::Long(id)),
+                ("note".to_string(), note),
+                (
+                    "tags".to_string(),
+                    AvroValue::Array(vec![
+                        AvroValue::String("a".to_string()),
+                        AvroValue::String("b".to_string()),
+                    ]),
+                ),
+                (
+                    "customer".to_string(),
+                    AvroValue::Record(vec![(
+                        "name".to_string(),
+                        AvroValue::String("alice".to_string()),
+                    )]),
+                ),
+            ]);
+            let mut payload = vec![MAGIC_BYTE, 0, 0, 0, 7];
+            payload.extend(apache_avro::to_avro_datum(&schema, value).unwrap());
+            payload
+        };
+
+        let deserializer = AvroDeserializer::new(registry_config(addr));
+        let batch = deserializer
+            .deserialize(&order(1, Some("fragile")))
+            .await
+            .unwrap();
+        assert_eq!(batch.num_rows(), 1);
+        assert_eq!(batch.column(0).as_primitive::<Int64Type>().value(0), 1);
+        assert_eq!(batch.column(1).as_string::<i32>().value(0), "fragile");
+        let tags = batch.column(2).as_list::<i32>().value(0);
+        assert_eq!(
+            tags.as_string::<i32>().iter().collect::<Vec<_>>(),
+            vec![Some("a"), Some("b")]
+        );
+        let customer = batch.column(3).as_struct();
+        assert_eq!(customer.column(0).as_string::<i32>().value(0), "alice");
+
+        let batch = deserializer.deserialize(&order(2, None)).await.unwrap();
+        assert!(batch.column(1).is_null(0));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let mut unknown = order(3, None);
+        unknown[4] = 8;
+        assert!(deserializer.deserialize(&unknown).await.is_err());
+        assert!(matches!(
+            deserializer.deserialize(b"{}").await,
+            Err(Error::Process(_))
+        ));
+    }
+}
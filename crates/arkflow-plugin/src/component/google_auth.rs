@@ -0,0 +1,220 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Google Cloud authentication
+//!
+//! Access tokens of the Google Cloud REST clients, obtained from a service account key file or
+//! from the metadata server.
+
+use arkflow_core::Error;
+use base64::Engine;
+use ring::rand::SystemRandom;
+use ring::signature::{RsaKeyPair, RSA_PKCS1_SHA256};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+/// Token endpoint of the GCE metadata server
+const METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+/// Environment variable pointing to the application default credentials
+const CREDENTIALS_ENV: &str = "GOOGLE_APPLICATION_CREDENTIALS";
+/// Tokens are refreshed this long before they expire
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// Service account key file as downloaded from the Cloud console
+#[derive(Debug, Clone, Deserialize)]
+struct ServiceAccountKey {
+    client_email: String,
+    private_key: String,
+    token_uri: String,
+}
+
+/// Source of access tokens
+enum Credentials {
+    /// No authentication, e.g. for an emulator
+    None,
+    /// Self-signed JWT exchanged for an access token
+    ServiceAccount(ServiceAccountKey),
+    /// Token of the default service account of the GCE instance
+    MetadataServer,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct JwtClaims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: u64,
+    exp: u64,
+}
+
+/// Access tokens for an OAuth scope, cached until they are about to expire
+pub(crate) struct GoogleAuth {
+    http: reqwest::Client,
+    scope: &'static str,
+    credentials: Credentials,
+    token: Mutex<Option<(String, Instant)>>,
+}
+
+impl GoogleAuth {
+    /// Create a token source for `scope`.
+    ///
+    /// Credentials are read from `credentials_file`, falling back to `GOOGLE_APPLICATION_CREDENTIALS`.
+    /// Without a key file, requests to a custom endpoint (an emulator) are not authenticated and
+    /// requests to the production endpoint use the metadata server.
+    pub fn new(
+        http: reqwest::Client,
+        scope: &'static str,
+        credentials_file: Option<&str>,
+        custom_endpoint: bool,
+    ) -> Result<Self, Error> {
+        let credentials_file = credentials_file
+            .map(str::to_string)
+            .or_else(|| std::env::var(CREDENTIALS_ENV).ok());
+        let credentials = match (credentials_file, custom_endpoint) {
+            (Some(path), _) => {
+                let content = std::fs::read_to_string(&path).map_err(|e| {
+                    Error::Config(format!(
+                        "Failed to read Google credentials file {}: {}",
+                        path, e
+                    ))
+                })?;
+                let key: ServiceAccountKey = serde_json::from_str(&content).map_err(|e| {
+                    Error::Config(format!("Invalid Google credentials file {}: {}", path, e))
+                })?;
+                Credentials::ServiceAccount(key)
+            }
+            (None, true) => Credentials::None,
+            (None, false) => Credentials::MetadataServer,
+        };
+
+        Ok(Self {
+            http,
+            scope,
+            credentials,
+            token: Mutex::new(None),
+        })
+    }
+
+    /// Add the access token to `request`, if requests are authenticated
+    pub async fn authorize(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::RequestBuilder, Error> {
+        Ok(match self.access_token().await? {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        })
+    }
+
+    /// Return a valid access token, refreshing it if it is about to expire
+    async fn access_token(&self) -> Result<Option<String>, Error> {
+        if matches!(self.credentials, Credentials::None) {
+            return Ok(None);
+        }
+
+        let mut cached = self.token.lock().await;
+        if let Some((token, expires_at)) = cached.as_ref() {
+            if Instant::now() + TOKEN_REFRESH_MARGIN < *expires_at {
+                return Ok(Some(token.clone()));
+            }
+        }
+
+        let response = match &self.credentials {
+            Credentials::None => return Ok(None),
+            Credentials::ServiceAccount(key) => {
+                let assertion = sign_jwt(key, self.scope)?;
+                self.http
+                    .post(&key.token_uri)
+                    .form(&[
+                        ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                        ("assertion", assertion.as_str()),
+                    ])
+                    .send()
+                    .await
+            }
+            Credentials::MetadataServer => {
+                self.http
+                    .get(METADATA_TOKEN_URL)
+                    .header("Metadata-Flavor", "Google")
+                    .send()
+                    .await
+            }
+        };
+        let token: TokenResponse = response
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| Error::Connection(format!("Failed to obtain Google access token: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| Error::Connection(format!("Invalid Google token response: {}", e)))?;
+
+        let expires_at = Instant::now() + Duration::from_secs(token.expires_in);
+        *cached = Some((token.access_token.clone(), expires_at));
+        Ok(Some(token.access_token))
+    }
+}
+
+/// Create the self-signed JWT used to request an access token for a service account
+fn sign_jwt(key: &ServiceAccountKey, scope: &str) -> Result<String, Error> {
+    let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| Error::Unknown(e.to_string()))?
+        .as_secs();
+    let claims = JwtClaims {
+        iss: &key.client_email,
+        scope,
+        aud: &key.token_uri,
+        iat: now,
+        exp: now + 3600,
+    };
+
+    let header = engine.encode(br#"{"alg":"RS256","typ":"JWT"}"#);
+    let claims = engine.encode(serde_json::to_vec(&claims)?);
+    let message = format!("{}.{}", header, claims);
+
+    let der = pem_to_der(&key.private_key)?;
+    let key_pair = RsaKeyPair::from_pkcs8(&der)
+        .map_err(|e| Error::Config(format!("Invalid service account private key: {}", e)))?;
+    let mut signature = vec![0; key_pair.public().modulus_len()];
+    key_pair
+        .sign(
+            &RSA_PKCS1_SHA256,
+            &SystemRandom::new(),
+            message.as_bytes(),
+            &mut signature,
+        )
+        .map_err(|e| Error::Unknown(format!("Failed to sign JWT: {}", e)))?;
+
+    Ok(format!("{}.{}", message, engine.encode(signature)))
+}
+
+/// Decode a PEM encoded private key
+fn pem_to_der(pem: &str) -> Result<Vec<u8>, Error> {
+    let body: String = pem
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .map(str::trim)
+        .collect();
+    base64::engine::general_purpose::STANDARD
+        .decode(body)
+        .map_err(|e| Error::Config(format!("Invalid service account private key: {}", e)))
+}
@@ -0,0 +1,174 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Pairing of the transactional Kafka input and output
+//!
+//! The `kafka_tx` input registers its consumer by consumer group and records the position of
+//! every message in columns. The `kafka_tx` output reads these columns to commit the consumed
+//! offsets in the transaction writing the messages.
+
+use arkflow_core::{Error, MessageBatch};
+use datafusion::arrow::array::{Array, Int32Array, Int64Array, StringArray};
+use rdkafka::consumer::StreamConsumer;
+use rdkafka::{Offset, TopicPartitionList};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+
+/// Column holding the topic a message was consumed from
+pub(crate) const TOPIC_FIELD: &str = "kafka_topic";
+/// Column holding the partition a message was consumed from
+pub(crate) const PARTITION_FIELD: &str = "kafka_partition";
+/// Column holding the offset of a message
+pub(crate) const OFFSET_FIELD: &str = "kafka_offset";
+
+/// Consumer of a transactional input, `None` once the input is closed
+pub(crate) type SharedConsumer = Arc<tokio::sync::RwLock<Option<Arc<StreamConsumer>>>>;
+
+lazy_static::lazy_static! {
+    static ref CONSUMERS: RwLock<HashMap<String, SharedConsumer>> = RwLock::new(HashMap::new());
+}
+
+/// Register the consumer of the transactional input of `consumer_group`
+pub(crate) fn register_consumer(consumer_group: &str, consumer: SharedConsumer) {
+    CONSUMERS
+        .write()
+        .unwrap()
+        .insert(consumer_group.to_string(), consumer);
+}
+
+/// Unregister `consumer`, unless another input of `consumer_group` registered its own since
+pub(crate) fn unregister_consumer(consumer_group: &str, consumer: &SharedConsumer) {
+    let mut consumers = CONSUMERS.write().unwrap();
+    if consumers
+        .get(consumer_group)
+        .is_some_and(|registered| Arc::ptr_eq(registered, consumer))
+    {
+        consumers.remove(consumer_group);
+    }
+}
+
+/// Consumer of the transactional input of `consumer_group`
+pub(crate) fn get_consumer(consumer_group: &str) -> Option<SharedConsumer> {
+    CONSUMERS.read().unwrap().get(consumer_group).cloned()
+}
+
+/// Offsets to commit once the messages of `msg` are written: the offset following the last
+/// message of every partition. Fails when the batch does not have the position columns, as
+/// the offsets of its messages would never be committed.
+pub(crate) fn offsets_to_commit(msg: &MessageBatch) -> Result<TopicPartitionList, Error> {
+    let mut tpl = TopicPartitionList::new();
+    let (Some(topics), Some(partitions), Some(offsets)) = (
+        msg.column_by_name(TOPIC_FIELD),
+        msg.column_by_name(PARTITION_FIELD),
+        msg.column_by_name(OFFSET_FIELD),
+    ) else {
+        return Err(Error::Process(format!(
+            "The {}, {} and {} columns are missing, they must be kept by the processors",
+            TOPIC_FIELD, PARTITION_FIELD, OFFSET_FIELD
+        )));
+    };
+    let (Some(topics), Some(partitions), Some(offsets)) = (
+        topics.as_any().downcast_ref::<StringArray>(),
+        partitions.as_any().downcast_ref::<Int32Array>(),
+        offsets.as_any().downcast_ref::<Int64Array>(),
+    ) else {
+        return Err(Error::Process(format!(
+            "Invalid types of the {}, {} and {} columns",
+            TOPIC_FIELD, PARTITION_FIELD, OFFSET_FIELD
+        )));
+    };
+
+    let mut next_offsets: BTreeMap<(&str, i32), i64> = BTreeMap::new();
+    for i in 0..msg.num_rows() {
+        if topics.is_null(i) || partitions.is_null(i) || offsets.is_null(i) {
+            continue;
+        }
+        let next = next_offsets
+            .entry((topics.value(i), partitions.value(i)))
+            .or_insert(0);
+        *next = (*next).max(offsets.value(i) + 1);
+    }
+    for ((topic, partition), offset) in next_offsets {
+        tpl.add_partition_offset(topic, partition, Offset::Offset(offset))
+            .map_err(|e| Error::Process(format!("Invalid Kafka offset: {}", e)))?;
+    }
+    Ok(tpl)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+
+    #[test]
+    fn test_offsets_to_commit() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(TOPIC_FIELD, DataType::Utf8, true),
+            Field::new(PARTITION_FIELD, DataType::Int32, true),
+            Field::new(OFFSET_FIELD, DataType::Int64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec![
+                    Some("a"),
+                    Some("a"),
+                    Some("a"),
+                    Some("b"),
+                    None,
+                ])),
+                Arc::new(Int32Array::from(vec![0, 0, 1, 0, 0])),
+                Arc::new(Int64Array::from(vec![7, 5, 2, 10, 100])),
+            ],
+        )
+        .unwrap();
+
+        let tpl = offsets_to_commit(&MessageBatch::new_arrow(batch)).unwrap();
+        let offsets: Vec<_> = tpl
+            .elements()
+            .iter()
+            .map(|e| (e.topic().to_string(), e.partition(), e.offset()))
+            .collect();
+        assert_eq!(
+            offsets,
+            vec![
+                ("a".to_string(), 0, Offset::Offset(8)),
+                ("a".to_string(), 1, Offset::Offset(3)),
+                ("b".to_string(), 0, Offset::Offset(11)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_offsets_to_commit_without_columns() {
+        let msg = MessageBatch::new_binary(vec![b"x".to_vec()]).unwrap();
+        assert!(matches!(offsets_to_commit(&msg), Err(Error::Process(_))));
+    }
+
+    #[test]
+    fn test_unregister_consumer() {
+        let first: SharedConsumer = Arc::new(tokio::sync::RwLock::new(None));
+        let second: SharedConsumer = Arc::new(tokio::sync::RwLock::new(None));
+        register_consumer("test-unregister", first.clone());
+        register_consumer("test-unregister", second.clone());
+
+        // Closing a replaced input keeps the consumer of the new one
+        unregister_consumer("test-unregister", &first);
+        assert!(get_consumer("test-unregister").is_some_and(|c| Arc::ptr_eq(&c, &second)));
+
+        unregister_consumer("test-unregister", &second);
+        assert!(get_consumer("test-unregister").is_none());
+    }
+}
@@ -12,8 +12,15 @@
  *    limitations under the License.
  */
 
+pub(crate) mod arrow_flight;
+pub(crate) mod avro;
+pub(crate) mod google_auth;
 pub(crate) mod json;
+pub(crate) mod kafka;
+pub(crate) mod offset_store;
 pub(crate) mod protobuf;
+pub(crate) mod pubsub;
 pub(crate) mod redis;
 pub(crate) mod sql;
-
+pub(crate) mod unix_socket;
+pub(crate) mod zeromq;
@@ -0,0 +1,269 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Stores of Kafka consumer offsets
+//!
+//! The Kafka input commits the offsets of the acknowledged messages to the broker by default.
+//! An external store keeps them instead in Redis or PostgreSQL, independently of the
+//! `__consumer_offsets` topic, and the input starts from the stored offsets.
+
+use crate::component::redis::{Connection, Mode};
+use arkflow_core::Error;
+use async_trait::async_trait;
+use rdkafka::consumer::{Consumer, StreamConsumer};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Offset store configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OffsetStoreConfig {
+    /// Commit the offsets to the Kafka broker
+    #[default]
+    Kafka,
+    /// Store the offsets of every topic in a Redis hash keyed by partition
+    Redis {
+        url: String,
+        #[serde(default = "default_key_prefix")]
+        key_prefix: String,
+    },
+    /// Store the offsets in a PostgreSQL table, created if it does not exist
+    Postgres {
+        uri: String,
+        #[serde(default = "default_table_name")]
+        table_name: String,
+    },
+}
+
+fn default_key_prefix() -> String {
+    "arkflow:kafka_offsets:".to_string()
+}
+
+fn default_table_name() -> String {
+    "arkflow_kafka_offsets".to_string()
+}
+
+/// Offsets of a consumer group. The stored offset of a partition is the offset of the next
+/// message to read.
+#[async_trait]
+pub(crate) trait OffsetStore: Send + Sync {
+    /// Store the offset of the next message to read from the partition
+    async fn commit(&self, topic: &str, partition: i32, offset: i64) -> Result<(), Error>;
+
+    /// Stored offset of the partition, `None` if there is none or the broker positions the
+    /// consumer
+    async fn fetch(&self, topic: &str, partition: i32) -> Result<Option<i64>, Error>;
+}
+
+impl OffsetStoreConfig {
+    /// Whether the consumer is positioned from the stored offsets rather than by the broker
+    pub(crate) fn is_external(&self) -> bool {
+        !matches!(self, OffsetStoreConfig::Kafka)
+    }
+
+    /// Connect to the store of `consumer_group`. The Kafka store commits through `consumer`.
+    pub(crate) async fn build(
+        &self,
+        consumer_group: &str,
+        consumer: Arc<RwLock<Option<StreamConsumer>>>,
+    ) -> Result<Arc<dyn OffsetStore>, Error> {
+        match self {
+            OffsetStoreConfig::Kafka => Ok(Arc::new(KafkaOffsetStore { consumer })),
+            OffsetStoreConfig::Redis { url, key_prefix } => {
+                let connection = Connection::connect(&Mode::Single { url: url.clone() }).await?;
+                Ok(Arc::new(RedisOffsetStore {
+                    connection,
+                    key_prefix: format!("{}{}:", key_prefix, consumer_group),
+                }))
+            }
+            OffsetStoreConfig::Postgres { uri, table_name } => {
+                let store = PostgresOffsetStore::connect(uri, table_name, consumer_group).await?;
+                Ok(Arc::new(store))
+            }
+        }
+    }
+}
+
+/// Offsets committed to the broker with the offsets of the consumer group
+struct KafkaOffsetStore {
+    consumer: Arc<RwLock<Option<StreamConsumer>>>,
+}
+
+#[async_trait]
+impl OffsetStore for KafkaOffsetStore {
+    async fn commit(&self, topic: &str, partition: i32, offset: i64) -> Result<(), Error> {
+        let consumer = self.consumer.read().await;
+        let Some(consumer) = consumer.as_ref() else {
+            return Err(Error::Disconnection);
+        };
+        consumer
+            .store_offset(topic, partition, offset)
+            .map_err(|e| Error::Process(format!("Error committing Kafka offset: {}", e)))
+    }
+
+    async fn fetch(&self, _topic: &str, _partition: i32) -> Result<Option<i64>, Error> {
+        Ok(None)
+    }
+}
+
+/// Offsets of a topic in the hash `<key_prefix><consumer_group>:<topic>`
+struct RedisOffsetStore {
+    connection: Connection,
+    /// Key prefix including the consumer group
+    key_prefix: String,
+}
+
+#[async_trait]
+impl OffsetStore for RedisOffsetStore {
+    async fn commit(&self, topic: &str, partition: i32, offset: i64) -> Result<(), Error> {
+        redis::cmd("HSET")
+            .arg(format!("{}{}", self.key_prefix, topic))
+            .arg(partition)
+            .arg(offset)
+            .query_async::<()>(&mut self.connection.clone())
+            .await
+            .map_err(|e| Error::Process(format!("Failed to store Kafka offset in Redis: {}", e)))
+    }
+
+    async fn fetch(&self, topic: &str, partition: i32) -> Result<Option<i64>, Error> {
+        redis::cmd("HGET")
+            .arg(format!("{}{}", self.key_prefix, topic))
+            .arg(partition)
+            .query_async::<Option<i64>>(&mut self.connection.clone())
+            .await
+            .map_err(|e| {
+                Error::Connection(format!("Failed to read Kafka offset from Redis: {}", e))
+            })
+    }
+}
+
+/// Offsets in a table keyed by consumer group, topic and partition
+struct PostgresOffsetStore {
+    pool: PgPool,
+    table_name: String,
+    consumer_group: String,
+}
+
+impl PostgresOffsetStore {
+    async fn connect(uri: &str, table_name: &str, consumer_group: &str) -> Result<Self, Error> {
+        let pool = PgPool::connect(uri)
+            .await
+            .map_err(|e| Error::Connection(format!("Failed to connect to PostgreSQL: {}", e)))?;
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (\
+             consumer_group TEXT NOT NULL, \
+             topic TEXT NOT NULL, \
+             \"partition\" INTEGER NOT NULL, \
+             \"offset\" BIGINT NOT NULL, \
+             PRIMARY KEY (consumer_group, topic, \"partition\"))",
+            table_name
+        ))
+        .execute(&pool)
+        .await
+        .map_err(|e| Error::Connection(format!("Failed to create the offset table: {}", e)))?;
+
+        Ok(Self {
+            pool,
+            table_name: table_name.to_string(),
+            consumer_group: consumer_group.to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl OffsetStore for PostgresOffsetStore {
+    async fn commit(&self, topic: &str, partition: i32, offset: i64) -> Result<(), Error> {
+        sqlx::query(&format!(
+            "INSERT INTO {} (consumer_group, topic, \"partition\", \"offset\") \
+             VALUES ($1, $2, $3, $4) \
+             ON CONFLICT (consumer_group, topic, \"partition\") \
+             DO UPDATE SET \"offset\" = EXCLUDED.\"offset\"",
+            self.table_name
+        ))
+        .bind(&self.consumer_group)
+        .bind(topic)
+        .bind(partition)
+        .bind(offset)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+        .map_err(|e| Error::Process(format!("Failed to store Kafka offset in PostgreSQL: {}", e)))
+    }
+
+    async fn fetch(&self, topic: &str, partition: i32) -> Result<Option<i64>, Error> {
+        sqlx::query_scalar::<_, i64>(&format!(
+            "SELECT \"offset\" FROM {} \
+             WHERE consumer_group = $1 AND topic = $2 AND \"partition\" = $3",
+            self.table_name
+        ))
+        .bind(&self.consumer_group)
+        .bind(topic)
+        .bind(partition)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| {
+            Error::Connection(format!(
+                "Failed to read Kafka offset from PostgreSQL: {}",
+                e
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_offset_store_config() {
+        let config: OffsetStoreConfig = serde_json::from_value(json!({
+            "type": "redis",
+            "url": "redis://localhost:6379",
+        }))
+        .unwrap();
+        assert!(config.is_external());
+        assert!(matches!(
+            config,
+            OffsetStoreConfig::Redis { key_prefix, .. } if key_prefix == "arkflow:kafka_offsets:"
+        ));
+
+        let config: OffsetStoreConfig = serde_json::from_value(json!({
+            "type": "postgres",
+            "uri": "postgres://localhost/db",
+            "table_name": "offsets",
+        }))
+        .unwrap();
+        assert!(matches!(
+            config,
+            OffsetStoreConfig::Postgres { table_name, .. } if table_name == "offsets"
+        ));
+        assert!(!OffsetStoreConfig::default().is_external());
+    }
+
+    #[tokio::test]
+    async fn test_kafka_offset_store_not_connected() {
+        let store = OffsetStoreConfig::Kafka
+            .build("group", Arc::new(RwLock::new(None)))
+            .await
+            .unwrap();
+        assert!(matches!(
+            store.commit("topic", 0, 1).await,
+            Err(Error::Disconnection)
+        ));
+        assert_eq!(store.fetch("topic", 0).await.unwrap(), None);
+    }
+}
@@ -195,6 +195,23 @@ pub fn arrow_to_protobuf(
     descriptor: &MessageDescriptor,
     batch: &MessageBatch,
 ) -> Result<Vec<Bytes>, Error> {
+    arrow_to_dynamic_messages(descriptor, batch)?
+        .into_iter()
+        .map(|proto_msg| {
+            let mut buf = Vec::new();
+            proto_msg
+                .encode(&mut buf)
+                .map_err(|e| Error::Process(format!("Protobuf encoding failed: {}", e)))?;
+            Ok(buf)
+        })
+        .collect()
+}
+
+/// Convert every row of an Arrow batch to a dynamic Protobuf message
+pub fn arrow_to_dynamic_messages(
+    descriptor: &MessageDescriptor,
+    batch: &MessageBatch,
+) -> Result<Vec<DynamicMessage>, Error> {
     // Create a new dynamic message
     let mut vec = Vec::with_capacity(batch.len());
     let len = batch.len();
@@ -326,14 +343,5 @@ pub fn arrow_to_protobuf(
         }
     }
 
-    Ok(vec
-        .into_iter()
-        .map(|proto_msg| {
-            let mut buf = Vec::new();
-            proto_msg
-                .encode(&mut buf)
-                .map_err(|e| Error::Process(format!("Protobuf encoding failed: {}", e)))?;
-            Ok(buf)
-        })
-        .collect::<Result<Vec<_>, Error>>()?)
+    Ok(vec)
 }
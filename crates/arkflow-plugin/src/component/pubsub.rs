@@ -0,0 +1,177 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Google Cloud Pub/Sub REST client
+//!
+//! A small client for the Pub/Sub v1 REST API shared by the Pub/Sub input and output.
+
+use crate::component::google_auth::GoogleAuth;
+use arkflow_core::Error;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+
+/// Production endpoint of the Pub/Sub REST API
+pub(crate) const DEFAULT_ENDPOINT: &str = "https://pubsub.googleapis.com";
+/// OAuth scope required by Pub/Sub
+const PUBSUB_SCOPE: &str = "https://www.googleapis.com/auth/pubsub";
+
+/// Message received from a subscription
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ReceivedMessage {
+    pub ack_id: String,
+    pub message: PubsubMessage,
+}
+
+/// Pub/Sub message as represented by the REST API
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PubsubMessage {
+    /// Base64 encoded payload
+    #[serde(default)]
+    pub data: String,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub attributes: HashMap<String, String>,
+    #[serde(default, skip_serializing)]
+    pub message_id: String,
+    #[serde(default, skip_serializing)]
+    pub publish_time: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub ordering_key: String,
+}
+
+impl PubsubMessage {
+    /// Create a message with the given payload
+    pub fn new(data: &[u8]) -> Self {
+        Self {
+            data: base64::engine::general_purpose::STANDARD.encode(data),
+            ..Default::default()
+        }
+    }
+
+    /// Decode the payload
+    pub fn decode_data(&self) -> Result<Vec<u8>, Error> {
+        base64::engine::general_purpose::STANDARD
+            .decode(&self.data)
+            .map_err(|e| Error::Read(format!("Invalid Pub/Sub message data: {}", e)))
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PullResponse {
+    #[serde(default)]
+    received_messages: Vec<ReceivedMessage>,
+}
+
+/// Client for the Pub/Sub REST API
+pub(crate) struct PubSubClient {
+    http: reqwest::Client,
+    endpoint: String,
+    project_id: String,
+    auth: GoogleAuth,
+}
+
+impl PubSubClient {
+    /// Create a client for `project_id`.
+    ///
+    /// Credentials are read from `credentials_file`, falling back to `GOOGLE_APPLICATION_CREDENTIALS`.
+    /// Without a key file, requests to a custom `endpoint` (the emulator) are not authenticated
+    /// and requests to the production endpoint use the metadata server.
+    pub fn new(
+        project_id: &str,
+        credentials_file: Option<&str>,
+        endpoint: Option<&str>,
+    ) -> Result<Self, Error> {
+        let http = reqwest::Client::new();
+        let auth = GoogleAuth::new(
+            http.clone(),
+            PUBSUB_SCOPE,
+            credentials_file,
+            endpoint.is_some(),
+        )?;
+
+        Ok(Self {
+            http,
+            endpoint: endpoint
+                .unwrap_or(DEFAULT_ENDPOINT)
+                .trim_end_matches('/')
+                .to_string(),
+            project_id: project_id.to_string(),
+            auth,
+        })
+    }
+
+    /// Pull up to `max_messages` messages from a subscription
+    pub async fn pull(
+        &self,
+        subscription_id: &str,
+        max_messages: usize,
+    ) -> Result<Vec<ReceivedMessage>, Error> {
+        let url = format!(
+            "{}/v1/projects/{}/subscriptions/{}:pull",
+            self.endpoint, self.project_id, subscription_id
+        );
+        let response: PullResponse = self
+            .post(&url, json!({ "maxMessages": max_messages }))
+            .await?
+            .json()
+            .await
+            .map_err(|e| Error::Read(format!("Invalid Pub/Sub pull response: {}", e)))?;
+        Ok(response.received_messages)
+    }
+
+    /// Acknowledge received messages
+    pub async fn acknowledge(
+        &self,
+        subscription_id: &str,
+        ack_ids: &[String],
+    ) -> Result<(), Error> {
+        let url = format!(
+            "{}/v1/projects/{}/subscriptions/{}:acknowledge",
+            self.endpoint, self.project_id, subscription_id
+        );
+        self.post(&url, json!({ "ackIds": ack_ids })).await?;
+        Ok(())
+    }
+
+    /// Publish messages to a topic
+    pub async fn publish(&self, topic_id: &str, messages: &[PubsubMessage]) -> Result<(), Error> {
+        let url = format!(
+            "{}/v1/projects/{}/topics/{}:publish",
+            self.endpoint, self.project_id, topic_id
+        );
+        self.post(&url, json!({ "messages": messages })).await?;
+        Ok(())
+    }
+
+    async fn post(&self, url: &str, body: serde_json::Value) -> Result<reqwest::Response, Error> {
+        let request = self.auth.authorize(self.http.post(url).json(&body)).await?;
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Error::Connection(format!("Pub/Sub request failed: {}", e)))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(Error::Process(format!(
+                "Pub/Sub request failed with status {}: {}",
+                status, body
+            )));
+        }
+        Ok(response)
+    }
+}
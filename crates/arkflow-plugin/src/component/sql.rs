@@ -18,6 +18,7 @@ use datafusion::prelude::SessionContext;
 pub(crate) fn create_session_context() -> Result<SessionContext, Error> {
     let mut ctx = SessionContext::new();
     udf::init(&mut ctx)?;
+    udf::table_udf::init(&ctx)?;
     datafusion_functions_json::register_all(&mut ctx)
         .map_err(|e| Error::Process(format!("Registration JSON function failed: {}", e)))?;
     Ok(ctx)
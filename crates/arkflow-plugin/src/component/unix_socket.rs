@@ -0,0 +1,102 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+use arkflow_core::Error;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+
+/// Framing used for messages exchanged over a Unix domain socket
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageFormat {
+    /// Each message is preceded by a 4-byte big-endian length
+    #[default]
+    LengthPrefixed,
+    /// Each message is terminated by a newline
+    Newline,
+}
+
+/// Default upper bound of the size of a received frame
+pub(crate) const DEFAULT_MAX_FRAME_BYTES: usize = 16 * 1024 * 1024;
+
+/// Read the next frame from the reader. Returns `Ok(None)` when the peer has closed the connection.
+///
+/// Frames larger than `max_frame_bytes` are rejected before they are buffered.
+pub(crate) async fn read_frame<R>(
+    reader: &mut BufReader<R>,
+    format: MessageFormat,
+    max_frame_bytes: usize,
+) -> Result<Option<Vec<u8>>, Error>
+where
+    R: AsyncRead + Unpin,
+{
+    let too_large = || {
+        Error::Process(format!(
+            "Frame exceeds the limit of {} bytes",
+            max_frame_bytes
+        ))
+    };
+    match format {
+        MessageFormat::LengthPrefixed => {
+            let len = match reader.read_u32().await {
+                Ok(len) => len as usize,
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(Error::Io(e)),
+            };
+            if len > max_frame_bytes {
+                return Err(too_large());
+            }
+            let mut buf = vec![0u8; len];
+            reader.read_exact(&mut buf).await?;
+            Ok(Some(buf))
+        }
+        MessageFormat::Newline => {
+            let mut buf = Vec::new();
+            let limit = max_frame_bytes as u64 + 1;
+            if reader.take(limit).read_until(b'\n', &mut buf).await? == 0 {
+                return Ok(None);
+            }
+            if buf.last() == Some(&b'\n') {
+                buf.pop();
+            } else if buf.len() > max_frame_bytes {
+                return Err(too_large());
+            }
+            Ok(Some(buf))
+        }
+    }
+}
+
+/// Write a single frame to the writer.
+pub(crate) async fn write_frame<W>(
+    writer: &mut W,
+    format: MessageFormat,
+    data: &[u8],
+) -> Result<(), Error>
+where
+    W: AsyncWrite + Unpin,
+{
+    match format {
+        MessageFormat::LengthPrefixed => {
+            let len = u32::try_from(data.len()).map_err(|_| {
+                Error::Process(format!("Message too large to frame: {} bytes", data.len()))
+            })?;
+            writer.write_u32(len).await?;
+            writer.write_all(data).await?;
+        }
+        MessageFormat::Newline => {
+            writer.write_all(data).await?;
+            writer.write_all(b"\n").await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,68 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Shared helpers of the ZeroMQ input and output
+
+use arkflow_core::Error;
+use zeromq::Socket;
+
+/// Bind or connect `socket` to every endpoint.
+///
+/// Endpoints with a `*` host such as `tcp://*:5555` are bound on all interfaces,
+/// any other endpoint is connected to.
+pub(crate) async fn attach<S: Socket>(socket: &mut S, endpoints: &[String]) -> Result<(), Error> {
+    for endpoint in endpoints {
+        match bind_address(endpoint) {
+            Some(address) => {
+                socket.bind(&address).await.map_err(|e| {
+                    Error::Connection(format!(
+                        "Unable to bind ZeroMQ endpoint {}: {}",
+                        endpoint, e
+                    ))
+                })?;
+            }
+            None => {
+                socket.connect(endpoint).await.map_err(|e| {
+                    Error::Connection(format!(
+                        "Unable to connect to ZeroMQ endpoint {}: {}",
+                        endpoint, e
+                    ))
+                })?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Address to bind to if the endpoint is a bind endpoint
+fn bind_address(endpoint: &str) -> Option<String> {
+    endpoint
+        .strip_prefix("tcp://*:")
+        .map(|port| format!("tcp://0.0.0.0:{}", port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bind_address() {
+        assert_eq!(
+            bind_address("tcp://*:5555").as_deref(),
+            Some("tcp://0.0.0.0:5555")
+        );
+        assert_eq!(bind_address("tcp://127.0.0.1:5555"), None);
+        assert_eq!(bind_address("ipc:///tmp/feed.ipc"), None);
+    }
+}
@@ -0,0 +1,339 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Arrow Flight input component
+//!
+//! Fetch record batches from an Arrow Flight server
+
+use crate::component::arrow_flight::TlsConfig;
+use arkflow_core::input::{register_input_builder, Ack, Input, InputBuilder, NoopAck};
+use arkflow_core::{Error, MessageBatch, Resource};
+use arrow_flight::flight_service_client::FlightServiceClient;
+use arrow_flight::utils::flight_data_to_arrow_batch;
+use arrow_flight::{Action, FlightData, FlightDescriptor, Ticket};
+use async_trait::async_trait;
+use datafusion::arrow::datatypes::{Schema, SchemaRef};
+use datafusion::arrow::ipc::convert::try_schema_from_ipc_buffer;
+use datafusion::arrow::ipc::{root_as_message, MessageHeader};
+use datafusion::arrow::record_batch::RecordBatch;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tonic::transport::{Channel, Endpoint};
+use tonic::Streaming;
+
+/// Flight descriptor identifying the data set to fetch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FlightDescriptorConfig {
+    /// Path of a named data set
+    Path { path: Vec<String> },
+    /// Opaque command understood by the server, e.g. a query
+    Command { command: String },
+}
+
+impl From<&FlightDescriptorConfig> for FlightDescriptor {
+    fn from(config: &FlightDescriptorConfig) -> Self {
+        match config {
+            FlightDescriptorConfig::Path { path } => FlightDescriptor::new_path(path.clone()),
+            FlightDescriptorConfig::Command { command } => {
+                FlightDescriptor::new_cmd(command.clone())
+            }
+        }
+    }
+}
+
+/// Arrow Flight input configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArrowFlightInputConfig {
+    /// URL of the Flight server, e.g. `http://localhost:50051`
+    pub endpoint: String,
+    /// Type of an action to call with DoAction before fetching the data
+    pub action: Option<String>,
+    /// Data set to fetch
+    pub descriptor: FlightDescriptorConfig,
+    /// TLS settings, required for `https` endpoints with a private CA or client certificates
+    pub tls: Option<TlsConfig>,
+}
+
+/// Open DoGet streams of the data set
+struct FlightState {
+    client: FlightServiceClient<Channel>,
+    /// Tickets of the endpoints not fetched yet
+    tickets: VecDeque<Ticket>,
+    /// Schema announced in the flight info, used until a stream sends its own
+    info_schema: Option<SchemaRef>,
+    stream: Option<(Streaming<FlightData>, Option<SchemaRef>)>,
+}
+
+/// Arrow Flight input component
+pub struct ArrowFlightInput {
+    input_name: Option<String>,
+    config: ArrowFlightInputConfig,
+    state: Mutex<Option<FlightState>>,
+}
+
+impl ArrowFlightInput {
+    /// Create a new Arrow Flight input component
+    pub fn new(name: Option<&String>, config: ArrowFlightInputConfig) -> Result<Self, Error> {
+        Ok(Self {
+            input_name: name.cloned(),
+            config,
+            state: Mutex::new(None),
+        })
+    }
+
+    async fn channel(&self) -> Result<Channel, Error> {
+        let mut endpoint = Endpoint::from_shared(self.config.endpoint.clone()).map_err(|e| {
+            Error::Config(format!(
+                "Invalid Flight endpoint {}: {}",
+                self.config.endpoint, e
+            ))
+        })?;
+        if let Some(tls) = &self.config.tls {
+            endpoint = endpoint
+                .tls_config(tls.client_config()?)
+                .map_err(|e| Error::Config(format!("Invalid Flight TLS configuration: {}", e)))?;
+        }
+        endpoint.connect().await.map_err(|e| {
+            Error::Connection(format!(
+                "Failed to connect to Flight server {}: {}",
+                self.config.endpoint, e
+            ))
+        })
+    }
+}
+
+#[async_trait]
+impl Input for ArrowFlightInput {
+    async fn connect(&self) -> Result<(), Error> {
+        let mut client = FlightServiceClient::new(self.channel().await?);
+
+        if let Some(action) = &self.config.action {
+            let mut results = client
+                .do_action(Action::new(action.clone(), ""))
+                .await
+                .map_err(|e| Error::Connection(format!("Flight action {} failed: {}", action, e)))?
+                .into_inner();
+            while results
+                .message()
+                .await
+                .map_err(|e| Error::Connection(format!("Flight action {} failed: {}", action, e)))?
+                .is_some()
+            {}
+        }
+
+        let info = client
+            .get_flight_info(FlightDescriptor::from(&self.config.descriptor))
+            .await
+            .map_err(|e| Error::Connection(format!("Failed to get flight info: {}", e)))?
+            .into_inner();
+        let info_schema = if info.schema.is_empty() {
+            None
+        } else {
+            let schema = try_schema_from_ipc_buffer(&info.schema)
+                .map_err(|e| Error::Read(format!("Invalid flight info schema: {}", e)))?;
+            Some(Arc::new(schema))
+        };
+        // Endpoints are fetched from the configured server in order
+        let tickets = info
+            .endpoint
+            .into_iter()
+            .filter_map(|endpoint| endpoint.ticket)
+            .collect();
+
+        *self.state.lock().await = Some(FlightState {
+            client,
+            tickets,
+            info_schema,
+            stream: None,
+        });
+        Ok(())
+    }
+
+    async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+        let mut guard = self.state.lock().await;
+        let state = guard
+            .as_mut()
+            .ok_or_else(|| Error::Connection("The input is not connected".to_string()))?;
+
+        loop {
+            let Some((stream, schema)) = &mut state.stream else {
+                let Some(ticket) = state.tickets.pop_front() else {
+                    return Err(Error::EOF);
+                };
+                let stream = state
+                    .client
+                    .do_get(ticket)
+                    .await
+                    .map_err(|e| Error::Read(format!("Flight DoGet failed: {}", e)))?
+                    .into_inner();
+                state.stream = Some((stream, state.info_schema.clone()));
+                continue;
+            };
+
+            let data = stream
+                .message()
+                .await
+                .map_err(|e| Error::Read(format!("Failed to read Flight data: {}", e)))?;
+            let Some(data) = data else {
+                state.stream = None;
+                continue;
+            };
+            if let Some(batch) = decode(&data, schema)? {
+                let mut msg = MessageBatch::new_arrow(batch);
+                msg.set_input_name(self.input_name.clone());
+                return Ok((msg, Arc::new(NoopAck)));
+            }
+        }
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        self.state.lock().await.take();
+        Ok(())
+    }
+}
+
+/// Decode a Flight data message, remembering the schema of schema messages
+fn decode(data: &FlightData, schema: &mut Option<SchemaRef>) -> Result<Option<RecordBatch>, Error> {
+    let message = root_as_message(&data.data_header)
+        .map_err(|e| Error::Read(format!("Invalid Flight data header: {}", e)))?;
+    match message.header_type() {
+        MessageHeader::Schema => {
+            let decoded = Schema::try_from(data)
+                .map_err(|e| Error::Read(format!("Invalid Flight schema: {}", e)))?;
+            *schema = Some(Arc::new(decoded));
+            Ok(None)
+        }
+        MessageHeader::RecordBatch => {
+            let schema = schema.clone().ok_or_else(|| {
+                Error::Read("Flight record batch received before its schema".to_string())
+            })?;
+            flight_data_to_arrow_batch(data, schema, &HashMap::new())
+                .map(Some)
+                .map_err(|e| Error::Read(format!("Invalid Flight record batch: {}", e)))
+        }
+        other => Err(Error::Read(format!(
+            "Unsupported Flight message type {:?}",
+            other
+        ))),
+    }
+}
+
+pub(crate) struct ArrowFlightInputBuilder;
+impl InputBuilder for ArrowFlightInputBuilder {
+    fn build(
+        &self,
+        name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Input>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Arrow Flight input configuration is missing".to_string(),
+            ));
+        }
+        let config: ArrowFlightInputConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(ArrowFlightInput::new(name, config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_input_builder("arrow_flight", Arc::new(ArrowFlightInputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_flight::encode::FlightDataEncoderBuilder;
+    use datafusion::arrow::array::{Int64Array, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field};
+    use futures_util::TryStreamExt;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_decode_flight_data() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec!["a", "b"])),
+            ],
+        )
+        .unwrap();
+        let messages: Vec<FlightData> = FlightDataEncoderBuilder::new()
+            .build(futures_util::stream::iter(vec![Ok(batch.clone())]))
+            .try_collect()
+            .await
+            .unwrap();
+
+        let mut decoded_schema = None;
+        let mut batches = vec![];
+        for data in &messages {
+            if let Some(batch) = decode(data, &mut decoded_schema).unwrap() {
+                batches.push(batch);
+            }
+        }
+        assert_eq!(decoded_schema, Some(schema));
+        assert_eq!(batches, vec![batch]);
+
+        // A record batch without a preceding schema cannot be decoded
+        assert!(decode(&messages[1], &mut None).is_err());
+    }
+
+    #[test]
+    fn test_config() {
+        let config: ArrowFlightInputConfig = serde_json::from_value(json!({
+            "endpoint": "http://localhost:50051",
+            "descriptor": {"type": "path", "path": ["sales", "2024"]},
+        }))
+        .unwrap();
+        assert_eq!(
+            FlightDescriptor::from(&config.descriptor),
+            FlightDescriptor::new_path(vec!["sales".to_string(), "2024".to_string()])
+        );
+
+        let config: ArrowFlightInputConfig = serde_json::from_value(json!({
+            "endpoint": "http://localhost:50051",
+            "descriptor": {"type": "command", "command": "SELECT 1"},
+        }))
+        .unwrap();
+        assert_eq!(
+            FlightDescriptor::from(&config.descriptor),
+            FlightDescriptor::new_cmd("SELECT 1")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_not_connected() {
+        let input = ArrowFlightInput::new(
+            None,
+            ArrowFlightInputConfig {
+                endpoint: "http://localhost:50051".to_string(),
+                action: None,
+                descriptor: FlightDescriptorConfig::Command {
+                    command: "SELECT 1".to_string(),
+                },
+                tls: None,
+            },
+        )
+        .unwrap();
+        assert!(matches!(input.read().await, Err(Error::Connection(_))));
+    }
+}
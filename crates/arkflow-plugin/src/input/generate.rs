@@ -13,6 +13,7 @@
  */
 
 use crate::time::deserialize_duration;
+use arkflow_core::config::deserialize_optional_number;
 use arkflow_core::input::{register_input_builder, Ack, Input, InputBuilder, NoopAck};
 use arkflow_core::{Error, MessageBatch, Resource};
 use async_trait::async_trait;
@@ -26,7 +27,9 @@ struct GenerateInputConfig {
     context: String,
     #[serde(deserialize_with = "deserialize_duration")]
     interval: Duration,
+    #[serde(default, deserialize_with = "deserialize_optional_number")]
     count: Option<usize>,
+    #[serde(default, deserialize_with = "deserialize_optional_number")]
     batch_size: Option<usize>,
 }
 
@@ -0,0 +1,438 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! GraphQL subscription input component
+//!
+//! Subscribe to a GraphQL server over WebSocket using the graphql-ws protocol
+
+use arkflow_core::input::{register_input_builder, Ack, Input, InputBuilder, NoopAck};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use flume::{Receiver, Sender};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{HeaderName, HeaderValue};
+use tokio_tungstenite::tungstenite::protocol::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+/// WebSocket sub-protocol of the graphql-ws protocol
+const GRAPHQL_WS_PROTOCOL: &str = "graphql-transport-ws";
+/// Id of the single subscription of a connection
+const SUBSCRIPTION_ID: &str = "1";
+/// Time to wait for the server to acknowledge the connection
+const CONNECTION_ACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+type GraphQlStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// GraphQL subscription input configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphQlSubscriptionInputConfig {
+    /// WebSocket URL of the GraphQL server
+    pub url: String,
+    /// Subscription document
+    pub subscription_query: String,
+    /// Variables of the subscription
+    #[serde(default)]
+    pub variables: Value,
+    /// Headers to include in the WebSocket handshake
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+/// GraphQL subscription input component
+pub struct GraphQlSubscriptionInput {
+    input_name: Option<String>,
+    config: GraphQlSubscriptionInputConfig,
+    sender: Sender<Result<Vec<u8>, Error>>,
+    receiver: Receiver<Result<Vec<u8>, Error>>,
+    cancellation_token: Mutex<Option<CancellationToken>>,
+}
+
+impl GraphQlSubscriptionInput {
+    /// Create a new GraphQL subscription input component
+    pub fn new(
+        name: Option<&String>,
+        config: GraphQlSubscriptionInputConfig,
+    ) -> Result<Self, Error> {
+        let (sender, receiver) = flume::bounded::<Result<Vec<u8>, Error>>(1000);
+        Ok(Self {
+            input_name: name.cloned(),
+            config,
+            sender,
+            receiver,
+            cancellation_token: Mutex::new(None),
+        })
+    }
+
+    /// Open the WebSocket connection, initialise it and start the subscription
+    async fn subscribe(&self) -> Result<GraphQlStream, Error> {
+        let mut request = self
+            .config
+            .url
+            .as_str()
+            .into_client_request()
+            .map_err(|e| {
+                Error::Config(format!("Invalid GraphQL URL {}: {}", self.config.url, e))
+            })?;
+        let headers = request.headers_mut();
+        headers.insert(
+            "Sec-WebSocket-Protocol",
+            HeaderValue::from_static(GRAPHQL_WS_PROTOCOL),
+        );
+        for (key, value) in &self.config.headers {
+            let name = HeaderName::from_bytes(key.as_bytes())
+                .map_err(|e| Error::Config(format!("Invalid header name {}: {}", key, e)))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|e| Error::Config(format!("Invalid header value for {}: {}", key, e)))?;
+            headers.insert(name, value);
+        }
+
+        let (mut stream, _) = connect_async(request).await.map_err(|e| {
+            Error::Connection(format!("Failed to connect to GraphQL server: {}", e))
+        })?;
+
+        send_json(&mut stream, json!({"type": "connection_init"})).await?;
+        tokio::time::timeout(CONNECTION_ACK_TIMEOUT, wait_for_ack(&mut stream))
+            .await
+            .map_err(|_| {
+                Error::Connection("Timed out waiting for GraphQL connection_ack".to_string())
+            })??;
+
+        send_json(
+            &mut stream,
+            json!({
+                "id": SUBSCRIPTION_ID,
+                "type": "subscribe",
+                "payload": {
+                    "query": self.config.subscription_query,
+                    "variables": self.config.variables,
+                },
+            }),
+        )
+        .await?;
+        Ok(stream)
+    }
+
+    async fn handle_messages(
+        mut stream: GraphQlStream,
+        sender: Sender<Result<Vec<u8>, Error>>,
+        cancellation_token: CancellationToken,
+    ) {
+        loop {
+            tokio::select! {
+                result = stream.next() => {
+                    let event = match result {
+                        Some(Ok(Message::Text(text))) => handle_text(&mut stream, text.as_str()).await,
+                        Some(Ok(Message::Close(_))) | None => Some(Err(Error::Disconnection)),
+                        Some(Ok(_)) => None,
+                        Some(Err(e)) => {
+                            error!("GraphQL subscription read error: {}", e);
+                            Some(Err(Error::Disconnection))
+                        }
+                    };
+                    let Some(event) = event else {
+                        continue;
+                    };
+
+                    // The subscription is over once it is closed or completed
+                    let done = matches!(event, Err(Error::Disconnection | Error::EOF));
+                    if let Err(e) = sender.send_async(event).await {
+                        error!("Failed to forward GraphQL event: {}", e);
+                        break;
+                    }
+                    if done {
+                        break;
+                    }
+                }
+                _ = cancellation_token.cancelled() => {
+                    let _ = send_json(&mut stream, json!({"id": SUBSCRIPTION_ID, "type": "complete"})).await;
+                    let _ = stream.close(None).await;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn send_json(stream: &mut GraphQlStream, message: Value) -> Result<(), Error> {
+    stream
+        .send(Message::text(message.to_string()))
+        .await
+        .map_err(|e| Error::Connection(format!("Failed to send GraphQL message: {}", e)))
+}
+
+async fn wait_for_ack(stream: &mut GraphQlStream) -> Result<(), Error> {
+    while let Some(message) = stream.next().await {
+        let message =
+            message.map_err(|e| Error::Connection(format!("GraphQL handshake failed: {}", e)))?;
+        let Message::Text(text) = message else {
+            continue;
+        };
+        let value: Value = serde_json::from_str(text.as_str())?;
+        match value["type"].as_str() {
+            Some("connection_ack") => return Ok(()),
+            Some("ping") => send_json(stream, json!({"type": "pong"})).await?,
+            _ => {}
+        }
+    }
+    Err(Error::Connection(
+        "GraphQL server closed the connection during the handshake".to_string(),
+    ))
+}
+
+/// Handle a protocol message, returning the event to forward if any
+async fn handle_text(stream: &mut GraphQlStream, text: &str) -> Option<Result<Vec<u8>, Error>> {
+    let value: Value = match serde_json::from_str(text) {
+        Ok(value) => value,
+        Err(e) => return Some(Err(Error::Read(format!("Invalid GraphQL message: {}", e)))),
+    };
+
+    match value["type"].as_str() {
+        Some("next") | Some("data") => {
+            let payload = &value["payload"];
+            if let Some(errors) = payload.get("errors") {
+                warn!("GraphQL subscription returned errors: {}", errors);
+            }
+            match payload.get("data") {
+                Some(data) if !data.is_null() => Some(Ok(data.to_string().into_bytes())),
+                _ => None,
+            }
+        }
+        Some("error") => Some(Err(Error::Read(format!(
+            "GraphQL subscription error: {}",
+            value["payload"]
+        )))),
+        Some("complete") => Some(Err(Error::EOF)),
+        Some("ping") => {
+            if let Err(e) = send_json(stream, json!({"type": "pong"})).await {
+                error!("{}", e);
+            }
+            None
+        }
+        // Keep-alive messages need no reply
+        _ => None,
+    }
+}
+
+#[async_trait]
+impl Input for GraphQlSubscriptionInput {
+    async fn connect(&self) -> Result<(), Error> {
+        let mut token_guard = self.cancellation_token.lock().await;
+        // Drop the previous subscription when re-connecting
+        if let Some(token) = token_guard.take() {
+            token.cancel();
+        }
+
+        let stream = self.subscribe().await?;
+        info!("Subscribed to GraphQL server: {}", self.config.url);
+
+        let cancellation_token = CancellationToken::new();
+        tokio::spawn(Self::handle_messages(
+            stream,
+            self.sender.clone(),
+            cancellation_token.clone(),
+        ));
+        *token_guard = Some(cancellation_token);
+        Ok(())
+    }
+
+    async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+        let Some(cancellation_token) = self.cancellation_token.lock().await.clone() else {
+            return Err(Error::Disconnection);
+        };
+
+        tokio::select! {
+            result = self.receiver.recv_async() => {
+                match result {
+                    Ok(Ok(payload)) => {
+                        let mut msg = MessageBatch::new_binary(vec![payload])?;
+                        msg.set_input_name(self.input_name.clone());
+                        Ok((msg, Arc::new(NoopAck)))
+                    }
+                    Ok(Err(e)) => Err(e),
+                    Err(_) => Err(Error::EOF),
+                }
+            }
+            _ = cancellation_token.cancelled() => {
+                Err(Error::EOF)
+            }
+        }
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        if let Some(token) = self.cancellation_token.lock().await.take() {
+            token.cancel();
+        }
+        Ok(())
+    }
+}
+
+pub(crate) struct GraphQlSubscriptionInputBuilder;
+impl InputBuilder for GraphQlSubscriptionInputBuilder {
+    fn build(
+        &self,
+        name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Input>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "GraphQL subscription input configuration is missing".to_string(),
+            ));
+        }
+
+        let config: GraphQlSubscriptionInputConfig =
+            serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(GraphQlSubscriptionInput::new(name, config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_input_builder(
+        "graphql_subscription",
+        Arc::new(GraphQlSubscriptionInputBuilder),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arkflow_core::DEFAULT_BINARY_VALUE_FIELD;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+
+    async fn next_json(stream: &mut WebSocketStream<TcpStream>) -> Value {
+        loop {
+            if let Message::Text(text) = stream.next().await.unwrap().unwrap() {
+                return serde_json::from_str(text.as_str()).unwrap();
+            }
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn handshake(request: &Request, mut response: Response) -> Result<Response, ErrorResponse> {
+        assert_eq!(request.headers()["authorization"], "Bearer token");
+        response.headers_mut().insert(
+            "Sec-WebSocket-Protocol",
+            HeaderValue::from_static(GRAPHQL_WS_PROTOCOL),
+        );
+        Ok(response)
+    }
+
+    /// Serve one subscription per connection, sending the given events
+    async fn mock_server(listener: TcpListener, events: Vec<Vec<Value>>) {
+        for events in events {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_hdr_async(tcp, handshake)
+                .await
+                .unwrap();
+
+            assert_eq!(next_json(&mut ws).await["type"], "connection_init");
+            ws.send(Message::text(json!({"type": "connection_ack"}).to_string()))
+                .await
+                .unwrap();
+            let subscribe = next_json(&mut ws).await;
+            assert_eq!(subscribe["type"], "subscribe");
+            assert_eq!(subscribe["payload"]["variables"], json!({"room": "a"}));
+
+            ws.send(Message::text(json!({"type": "ping"}).to_string()))
+                .await
+                .unwrap();
+            assert_eq!(next_json(&mut ws).await["type"], "pong");
+            ws.send(Message::text(json!({"type": "ka"}).to_string()))
+                .await
+                .unwrap();
+
+            for event in events {
+                ws.send(Message::text(
+                    json!({"id": subscribe["id"], "type": "next", "payload": {"data": event}})
+                        .to_string(),
+                ))
+                .await
+                .unwrap();
+            }
+            ws.close(None).await.unwrap();
+        }
+    }
+
+    fn payload(msg: &MessageBatch) -> Value {
+        let values = msg.to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap();
+        serde_json::from_slice(values[0]).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_subscription_reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(mock_server(
+            listener,
+            vec![
+                vec![json!({"n": 1}), json!({"n": 2})],
+                vec![json!({"n": 3})],
+            ],
+        ));
+
+        let input = GraphQlSubscriptionInput::new(
+            None,
+            GraphQlSubscriptionInputConfig {
+                url: format!("ws://{}/graphql", addr),
+                subscription_query: "subscription($room: String!) { messages(room: $room) }"
+                    .to_string(),
+                variables: json!({"room": "a"}),
+                headers: HashMap::from([("Authorization".to_string(), "Bearer token".to_string())]),
+            },
+        )
+        .unwrap();
+        input.connect().await.unwrap();
+
+        let (msg, _) = input.read().await.unwrap();
+        assert_eq!(payload(&msg), json!({"n": 1}));
+        let (msg, _) = input.read().await.unwrap();
+        assert_eq!(payload(&msg), json!({"n": 2}));
+        assert!(matches!(input.read().await, Err(Error::Disconnection)));
+
+        // The stream re-connects on disconnection, which re-subscribes
+        input.connect().await.unwrap();
+        let (msg, _) = input.read().await.unwrap();
+        assert_eq!(payload(&msg), json!({"n": 3}));
+
+        input.close().await.unwrap();
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_not_connected() {
+        let input = GraphQlSubscriptionInput::new(
+            None,
+            GraphQlSubscriptionInputConfig {
+                url: "ws://127.0.0.1:1/graphql".to_string(),
+                subscription_query: "subscription { ticks }".to_string(),
+                variables: Value::Null,
+                headers: HashMap::new(),
+            },
+        )
+        .unwrap();
+        assert!(matches!(input.read().await, Err(Error::Disconnection)));
+        assert!(input.connect().await.is_err());
+    }
+}
@@ -17,30 +17,133 @@
 //! Receive data from HTTP endpoints
 
 use arkflow_core::input::{register_input_builder, Ack, Input, InputBuilder, NoopAck};
-use arkflow_core::{Error, MessageBatch, Resource};
+use arkflow_core::trace::{SpanContext, TRACEPARENT_HEADER, TRACESTATE_HEADER};
+use arkflow_core::{Error, MessageBatch, Resource, DEFAULT_BINARY_VALUE_FIELD};
 use async_trait::async_trait;
+use axum::body::{Body, Bytes};
+use axum::extract::{DefaultBodyLimit, Multipart, Request};
 use axum::http::header;
 use axum::http::header::HeaderMap;
-use axum::{extract::State, http::StatusCode, routing::post, Router};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
 use base64::Engine;
+use datafusion::arrow::array::{ArrayRef, BinaryArray, StringArray, UInt64Array};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
 use flume::{Receiver, Sender};
+use ring::hmac;
 use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::net::TcpListener;
 use tokio::sync::Mutex;
 use tower_http::cors::CorsLayer;
 
-/// HTTP input configuration
+/// HTTP input authentication
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum AuthType {
     /// Basic authentication
     Basic { username: String, password: String },
     /// Bearer token authentication
     Bearer { token: String },
+    /// HMAC-SHA256 signature of the request timestamp followed by the body
+    HmacSha256 {
+        secret: String,
+        /// Header carrying the hex encoded signature
+        header: String,
+        /// Header carrying the Unix timestamp (seconds) of the request
+        #[serde(default = "default_timestamp_header")]
+        timestamp_header: String,
+        /// Maximum difference between the request timestamp and the current time
+        timestamp_tolerance_secs: u64,
+    },
+}
+
+fn default_timestamp_header() -> String {
+    "X-Timestamp".to_string()
+}
+
+/// Format of the request bodies
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BodyFormat {
+    /// A JSON document, converted to Arrow
+    #[default]
+    Json,
+    /// The body as a single binary message
+    Raw,
+    /// One binary message per line
+    Ndjson,
+    /// An Arrow IPC stream
+    ArrowIpc,
+}
+
+impl BodyFormat {
+    /// Content types accepted for the format, any content type if empty
+    fn content_types(&self) -> &'static [&'static str] {
+        match self {
+            BodyFormat::Json => &["application/json"],
+            BodyFormat::Raw => &[],
+            BodyFormat::Ndjson => &["application/x-ndjson", "application/ndjson"],
+            BodyFormat::ArrowIpc => &["application/vnd.apache.arrow.stream"],
+        }
+    }
+
+    /// Whether a request with the `content_type` header can be read
+    fn accepts(&self, content_type: Option<&str>) -> bool {
+        let expected = self.content_types();
+        if expected.is_empty() {
+            return true;
+        }
+        let Some(content_type) = content_type else {
+            return false;
+        };
+        // Parameters such as the charset are ignored
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        expected.contains(&mime.as_str()) || (*self == BodyFormat::Json && mime.ends_with("+json"))
+    }
+
+    /// Decode a request body into a message, `None` if it holds no data
+    fn decode(&self, body: &[u8]) -> Result<Option<MessageBatch>, Error> {
+        match self {
+            BodyFormat::Json => {
+                let value: serde_json::Value = serde_json::from_slice(body)?;
+                MessageBatch::from_json(&value).map(Some)
+            }
+            BodyFormat::Raw => MessageBatch::new_binary(vec![body.to_vec()]).map(Some),
+            BodyFormat::Ndjson => {
+                let lines: Vec<Vec<u8>> = body
+                    .split(|b| *b == b'\n')
+                    .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+                    .filter(|line| !line.is_empty())
+                    .map(<[u8]>::to_vec)
+                    .collect();
+                if lines.is_empty() {
+                    return Ok(None);
+                }
+                MessageBatch::new_binary(lines).map(Some)
+            }
+            BodyFormat::ArrowIpc => {
+                let msg = MessageBatch::from_arrow_ipc(body)?;
+                if msg.is_empty() {
+                    return Ok(None);
+                }
+                Ok(Some(msg))
+            }
+        }
+    }
 }
 
+/// HTTP input configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HttpInputConfig {
     /// Listening address
@@ -51,13 +154,25 @@ pub struct HttpInputConfig {
     pub cors_enabled: Option<bool>,
     /// Authentication configuration
     pub auth: Option<AuthType>,
+    /// Format of the request bodies
+    #[serde(default)]
+    pub body_format: BodyFormat,
+    /// Maximum size of a request body, larger requests are rejected with 413
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: usize,
 }
 
+fn default_max_body_bytes() -> usize {
+    10 * 1024 * 1024
+}
+
+type ServerHandle = tokio::task::JoinHandle<Result<(), Error>>;
+
 /// HTTP input component
 pub struct HttpInput {
     input_name: Option<String>,
     config: HttpInputConfig,
-    server_handle: Arc<Mutex<Option<tokio::task::JoinHandle<Result<(), Error>>>>>,
+    server_handle: Arc<Mutex<Option<ServerHandle>>>,
     sender: Arc<Sender<MessageBatch>>,
     receiver: Arc<Receiver<MessageBatch>>,
     connected: AtomicBool,
@@ -66,7 +181,8 @@ pub struct HttpInput {
 
 struct AppStateInner {
     sender: Sender<MessageBatch>,
-    auth: Option<AuthType>,
+    body_format: BodyFormat,
+    max_body_bytes: usize,
 }
 
 type AppState = Arc<AppStateInner>;
@@ -87,21 +203,45 @@ impl HttpInput {
         })
     }
 
+    /// Build the router serving `path`, rejecting unauthenticated requests if `auth` is set
+    fn router(path: &str, state: AppState, auth: Option<AuthType>) -> Router {
+        let max_body_bytes = state.max_body_bytes;
+        let router = Router::new()
+            .route(path, post(Self::handle_request))
+            .with_state(state);
+        let router = match auth {
+            Some(auth) => router.layer(middleware::from_fn_with_state(
+                (Arc::new(auth), max_body_bytes),
+                authenticate,
+            )),
+            None => router,
+        };
+        // Also applies to chunked bodies, whose size is unknown until they are read
+        router.layer(DefaultBodyLimit::max(max_body_bytes))
+    }
+
     async fn handle_request(
         State(state): State<AppState>,
         headers: HeaderMap,
-        body: axum::extract::Json<serde_json::Value>,
+        body: Bytes,
     ) -> StatusCode {
-        if let Some(auth_config) = &state.auth {
-            if !validate_auth(&headers, auth_config).await {
-                return StatusCode::UNAUTHORIZED;
-            }
+        let content_type = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok());
+        if !state.body_format.accepts(content_type) {
+            return StatusCode::UNSUPPORTED_MEDIA_TYPE;
         }
-
-        let msg = match MessageBatch::from_json(&body.0) {
-            Ok(msg) => msg,
+        let mut msg = match state.body_format.decode(&body) {
+            Ok(Some(msg)) => msg,
+            Ok(None) => return StatusCode::OK,
             Err(_) => return StatusCode::BAD_REQUEST,
         };
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+        if let Some(context) = header(TRACEPARENT_HEADER).and_then(|traceparent| {
+            SpanContext::from_headers(traceparent, header(TRACESTATE_HEADER))
+        }) {
+            msg.inject_span_context(&context);
+        }
 
         let _ = state.sender.send_async(msg).await;
 
@@ -121,30 +261,17 @@ impl Input for HttpInput {
 
         let app_state = Arc::new(AppStateInner {
             sender: self.sender.as_ref().clone(),
-            auth: self.auth.clone(),
+            body_format: self.config.body_format,
+            max_body_bytes: self.config.max_body_bytes,
         });
 
-        let mut app = Router::new()
-            .route(&path, post(Self::handle_request))
-            .with_state(app_state);
+        let mut app = Self::router(&path, app_state, self.auth.clone());
 
         if self.config.cors_enabled.unwrap_or(false) {
             app = app.layer(CorsLayer::very_permissive());
         }
 
-        let addr: SocketAddr = address
-            .parse()
-            .map_err(|e| Error::Config(format!("Invalid address {}: {}", address, e)))?;
-
-        let server_handle = tokio::spawn(async move {
-            let server = axum::serve(
-                TcpListener::bind(&addr).await.expect("bind error"),
-                app.into_make_service(),
-            );
-            server
-                .await
-                .map_err(|e| Error::Connection(format!("HTTP server error: {}", e)))
-        });
+        let server_handle = serve(&address, app)?;
 
         let server_handle_arc = self.server_handle.clone();
         let mut server_handle_arc_mutex = server_handle_arc.lock().await;
@@ -197,8 +324,339 @@ impl InputBuilder for HttpInputBuilder {
     }
 }
 
+/// Serve `app` on `address` in a new task
+fn serve(address: &str, app: Router) -> Result<tokio::task::JoinHandle<Result<(), Error>>, Error> {
+    let addr: SocketAddr = address
+        .parse()
+        .map_err(|e| Error::Config(format!("Invalid address {}: {}", address, e)))?;
+
+    Ok(tokio::spawn(async move {
+        let server = axum::serve(
+            TcpListener::bind(&addr).await.expect("bind error"),
+            app.into_make_service(),
+        );
+        server
+            .await
+            .map_err(|e| Error::Connection(format!("HTTP server error: {}", e)))
+    }))
+}
+
+/// Column holding the file name of an uploaded file
+pub const FILENAME_FIELD: &str = "filename";
+/// Column holding the content type of an uploaded file
+pub const CONTENT_TYPE_FIELD: &str = "content_type";
+/// Column holding the size of an uploaded file in bytes
+pub const SIZE_FIELD: &str = "size";
+
+/// HTTP multipart upload input configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultipartInputConfig {
+    /// Listening address
+    pub address: String,
+    /// Path
+    pub path: String,
+    /// Name of the form field holding the files, other fields are ignored
+    pub field_name: String,
+    /// Maximum size of a file, requests with larger files are rejected with 413
+    #[serde(default = "default_max_body_bytes")]
+    pub max_file_size_bytes: usize,
+    /// MIME types of the files accepted, any type if empty.
+    /// Requests with other files are rejected with 415
+    #[serde(default)]
+    pub accepted_mime_types: Vec<String>,
+}
+
+/// HTTP input receiving files uploaded as `multipart/form-data`, one message per file
+pub struct MultipartInput {
+    input_name: Option<String>,
+    config: MultipartInputConfig,
+    server_handle: Arc<Mutex<Option<ServerHandle>>>,
+    sender: Arc<Sender<MessageBatch>>,
+    receiver: Arc<Receiver<MessageBatch>>,
+    connected: AtomicBool,
+}
+
+struct MultipartStateInner {
+    sender: Sender<MessageBatch>,
+    field_name: String,
+    max_file_size_bytes: usize,
+    accepted_mime_types: Vec<String>,
+}
+
+type MultipartState = Arc<MultipartStateInner>;
+
+/// A file read from a multipart request
+struct UploadedFile {
+    filename: Option<String>,
+    content_type: Option<String>,
+    content: Vec<u8>,
+}
+
+impl MultipartStateInner {
+    /// Whether a file of `content_type` can be received, ignoring its parameters
+    fn accepts(&self, content_type: Option<&str>) -> bool {
+        if self.accepted_mime_types.is_empty() {
+            return true;
+        }
+        let Some(content_type) = content_type else {
+            return false;
+        };
+        let mime = content_type.split(';').next().unwrap_or_default().trim();
+        self.accepted_mime_types
+            .iter()
+            .any(|accepted| accepted.eq_ignore_ascii_case(mime))
+    }
+}
+
+impl MultipartInput {
+    pub fn new(name: Option<&String>, config: MultipartInputConfig) -> Result<Self, Error> {
+        let (sender, receiver) = flume::bounded::<MessageBatch>(1000);
+
+        Ok(Self {
+            input_name: name.cloned(),
+            config,
+            server_handle: Arc::new(Mutex::new(None)),
+            connected: AtomicBool::new(false),
+            sender: Arc::new(sender),
+            receiver: Arc::new(receiver),
+        })
+    }
+
+    /// Build the router receiving the uploads on `path`
+    fn router(path: &str, state: MultipartState) -> Router {
+        Router::new()
+            .route(path, post(Self::handle_request))
+            .with_state(state)
+            // The size of the files is checked while they are read
+            .layer(DefaultBodyLimit::disable())
+    }
+
+    async fn handle_request(
+        State(state): State<MultipartState>,
+        mut multipart: Multipart,
+    ) -> StatusCode {
+        // The files are only sent once the whole request is accepted
+        let mut files = Vec::new();
+        loop {
+            let mut field = match multipart.next_field().await {
+                Ok(Some(field)) => field,
+                Ok(None) => break,
+                Err(e) => return e.status(),
+            };
+            if field.name() != Some(state.field_name.as_str()) {
+                continue;
+            }
+            if !state.accepts(field.content_type()) {
+                return StatusCode::UNSUPPORTED_MEDIA_TYPE;
+            }
+            let filename = field.file_name().map(str::to_string);
+            let content_type = field.content_type().map(str::to_string);
+            let mut content = Vec::new();
+            loop {
+                match field.chunk().await {
+                    Ok(Some(chunk)) => {
+                        if content.len() + chunk.len() > state.max_file_size_bytes {
+                            return StatusCode::PAYLOAD_TOO_LARGE;
+                        }
+                        content.extend_from_slice(&chunk);
+                    }
+                    Ok(None) => break,
+                    Err(e) => return e.status(),
+                }
+            }
+            files.push(UploadedFile {
+                filename,
+                content_type,
+                content,
+            });
+        }
+        if files.is_empty() {
+            return StatusCode::BAD_REQUEST;
+        }
+
+        for file in files {
+            match file_message(file) {
+                Ok(msg) => {
+                    let _ = state.sender.send_async(msg).await;
+                }
+                Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
+            }
+        }
+
+        StatusCode::OK
+    }
+}
+
+/// Build a message holding the content and the metadata of an uploaded file
+fn file_message(file: UploadedFile) -> Result<MessageBatch, Error> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new(DEFAULT_BINARY_VALUE_FIELD, DataType::Binary, false),
+        Field::new(FILENAME_FIELD, DataType::Utf8, true),
+        Field::new(CONTENT_TYPE_FIELD, DataType::Utf8, true),
+        Field::new(SIZE_FIELD, DataType::UInt64, false),
+    ]));
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(BinaryArray::from_vec(vec![file.content.as_slice()])),
+        Arc::new(StringArray::from(vec![file.filename])),
+        Arc::new(StringArray::from(vec![file.content_type])),
+        Arc::new(UInt64Array::from(vec![file.content.len() as u64])),
+    ];
+    let batch = RecordBatch::try_new(schema, columns)
+        .map_err(|e| Error::Process(format!("Creating an Arrow record batch failed: {}", e)))?;
+    Ok(MessageBatch::new_arrow(batch))
+}
+
+#[async_trait]
+impl Input for MultipartInput {
+    async fn connect(&self) -> Result<(), Error> {
+        if self.connected.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let state = Arc::new(MultipartStateInner {
+            sender: self.sender.as_ref().clone(),
+            field_name: self.config.field_name.clone(),
+            max_file_size_bytes: self.config.max_file_size_bytes,
+            accepted_mime_types: self.config.accepted_mime_types.clone(),
+        });
+        let app = Self::router(&self.config.path, state);
+        let server_handle = serve(&self.config.address, app)?;
+
+        *self.server_handle.lock().await = Some(server_handle);
+        self.connected.store(true, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+        if !self.connected.load(Ordering::SeqCst) {
+            return Err(Error::Connection("The input is not connected".to_string()));
+        }
+
+        if let Ok(mut msg) = self.receiver.recv_async().await {
+            msg.set_input_name(self.input_name.clone());
+            Ok((msg, Arc::new(NoopAck)))
+        } else {
+            Err(Error::Process("The queue is empty".to_string()))
+        }
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        if let Some(handle) = self.server_handle.lock().await.take() {
+            handle.abort();
+        }
+
+        self.connected.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+pub(crate) struct MultipartInputBuilder;
+impl InputBuilder for MultipartInputBuilder {
+    fn build(
+        &self,
+        name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Input>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Http multipart input configuration is missing".to_string(),
+            ));
+        }
+
+        let config: MultipartInputConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(MultipartInput::new(name, config)?))
+    }
+}
+
 pub fn init() -> Result<(), Error> {
-    register_input_builder("http", Arc::new(HttpInputBuilder))
+    register_input_builder("http", Arc::new(HttpInputBuilder))?;
+    register_input_builder("http_multipart", Arc::new(MultipartInputBuilder))
+}
+
+/// Middleware rejecting requests that fail the configured authentication with 401
+async fn authenticate(
+    State((auth, max_body_bytes)): State<(Arc<AuthType>, usize)>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let request = match auth.as_ref() {
+        AuthType::HmacSha256 {
+            secret,
+            header,
+            timestamp_header,
+            timestamp_tolerance_secs,
+        } => {
+            let (parts, body) = request.into_parts();
+            let Ok(body) = axum::body::to_bytes(body, max_body_bytes).await else {
+                return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+            };
+            if let Err(reason) = validate_hmac(
+                &parts.headers,
+                &body,
+                secret,
+                header,
+                timestamp_header,
+                *timestamp_tolerance_secs,
+            ) {
+                return unauthorized(reason);
+            }
+            Request::from_parts(parts, Body::from(body))
+        }
+        auth => {
+            if !validate_auth(request.headers(), auth).await {
+                return unauthorized("invalid credentials");
+            }
+            request
+        }
+    };
+    next.run(request).await
+}
+
+fn unauthorized(reason: &str) -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        Json(serde_json::json!({ "error": reason })),
+    )
+        .into_response()
+}
+
+/// Verify the timestamp and the HMAC-SHA256 signature of `timestamp + body`
+fn validate_hmac(
+    headers: &HeaderMap,
+    body: &[u8],
+    secret: &str,
+    signature_header: &str,
+    timestamp_header: &str,
+    tolerance_secs: u64,
+) -> Result<(), &'static str> {
+    let timestamp = headers
+        .get(timestamp_header)
+        .and_then(|v| v.to_str().ok())
+        .ok_or("missing timestamp")?;
+    let request_time: u64 = timestamp.trim().parse().map_err(|_| "invalid timestamp")?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| "invalid system time")?
+        .as_secs();
+    if now.abs_diff(request_time) > tolerance_secs {
+        return Err("timestamp outside of tolerance");
+    }
+
+    let signature = headers
+        .get(signature_header)
+        .and_then(|v| v.to_str().ok())
+        .ok_or("missing signature")?;
+    let signature = signature.trim();
+    let signature = signature.strip_prefix("sha256=").unwrap_or(signature);
+    let signature = hex::decode(signature).map_err(|_| "invalid signature")?;
+
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let mut message = Vec::with_capacity(timestamp.len() + body.len());
+    message.extend_from_slice(timestamp.as_bytes());
+    message.extend_from_slice(body);
+    hmac::verify(&key, &message, &signature).map_err(|_| "invalid signature")
 }
 
 async fn validate_auth(headers: &HeaderMap, auth_config: &AuthType) -> bool {
@@ -237,6 +695,7 @@ async fn validate_auth(headers: &HeaderMap, auth_config: &AuthType) -> bool {
             }
             false
         }
+        AuthType::HmacSha256 { .. } => false,
     }
 }
 
@@ -245,11 +704,18 @@ mod tests {
     use super::*;
     use axum::body::Body;
     use axum::http::{Request, StatusCode};
-    use axum::Router;
     use serde_json::json;
     use tower::util::ServiceExt;
     // for `oneshot` method
 
+    fn state(sender: Sender<MessageBatch>, body_format: BodyFormat) -> AppState {
+        Arc::new(AppStateInner {
+            sender,
+            body_format,
+            max_body_bytes: 1024,
+        })
+    }
+
     #[tokio::test]
     async fn test_handle_request_ok() {
         let config = HttpInputConfig {
@@ -257,16 +723,13 @@ mod tests {
             path: "/test".to_string(),
             cors_enabled: Some(false),
             auth: None,
+            body_format: BodyFormat::Json,
+            max_body_bytes: default_max_body_bytes(),
         };
         let input = HttpInput::new(None, config).unwrap();
-        let app_state = Arc::new(AppStateInner {
-            sender: input.sender.as_ref().clone(),
-            auth: input.auth.clone(),
-        });
+        let app_state = state(input.sender.as_ref().clone(), BodyFormat::Json);
 
-        let app = Router::new()
-            .route("/test", axum::routing::post(HttpInput::handle_request))
-            .with_state(app_state);
+        let app = HttpInput::router("/test", app_state, input.auth.clone());
 
         let request = Request::builder()
             .method("POST")
@@ -279,6 +742,38 @@ mod tests {
         assert_eq!(response.status(), StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_handle_request_trace_context() {
+        let config = HttpInputConfig {
+            address: "127.0.0.1:3000".to_string(),
+            path: "/test".to_string(),
+            cors_enabled: Some(false),
+            auth: None,
+            body_format: BodyFormat::Json,
+            max_body_bytes: default_max_body_bytes(),
+        };
+        let input = HttpInput::new(None, config).unwrap();
+        let app_state = state(input.sender.as_ref().clone(), BodyFormat::Json);
+        let app = HttpInput::router("/test", app_state, None);
+
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let request = Request::builder()
+            .method("POST")
+            .uri("/test")
+            .header("content-type", "application/json")
+            .header("traceparent", traceparent)
+            .header("tracestate", "congo=t61rcWkgMzE")
+            .body(Body::from(json!({"key": "value"}).to_string()))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let msg = input.receiver.recv_async().await.unwrap();
+        let context = msg.extract_span_context().unwrap();
+        assert_eq!(context.to_traceparent(), traceparent);
+        assert_eq!(context.trace_state.as_deref(), Some("congo=t61rcWkgMzE"));
+    }
+
     #[tokio::test]
     async fn test_handle_request_unauthorized() {
         let config = HttpInputConfig {
@@ -289,16 +784,13 @@ mod tests {
                 username: "user".to_string(),
                 password: "pass".to_string(),
             }),
+            body_format: BodyFormat::Json,
+            max_body_bytes: default_max_body_bytes(),
         };
         let input = HttpInput::new(None, config).unwrap();
-        let app_state = Arc::new(AppStateInner {
-            sender: input.sender.as_ref().clone(),
-            auth: input.auth.clone(),
-        });
+        let app_state = state(input.sender.as_ref().clone(), BodyFormat::Json);
 
-        let app = Router::new()
-            .route("/test", axum::routing::post(HttpInput::handle_request))
-            .with_state(app_state);
+        let app = HttpInput::router("/test", app_state, input.auth.clone());
 
         let request = Request::builder()
             .method("POST")
@@ -310,4 +802,330 @@ mod tests {
         let response = app.oneshot(request).await.unwrap();
         assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
     }
+
+    fn hmac_app() -> Router {
+        let (sender, _receiver) = flume::bounded(10);
+        let auth = AuthType::HmacSha256 {
+            secret: "secret".to_string(),
+            header: "X-Signature".to_string(),
+            timestamp_header: default_timestamp_header(),
+            timestamp_tolerance_secs: 300,
+        };
+        HttpInput::router("/test", state(sender, BodyFormat::Json), Some(auth))
+    }
+
+    fn signed_request(timestamp: u64, body: &str, secret: &str) -> Request<Body> {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+        let signature = hmac::sign(&key, format!("{}{}", timestamp, body).as_bytes());
+        Request::builder()
+            .method("POST")
+            .uri("/test")
+            .header("content-type", "application/json")
+            .header("X-Timestamp", timestamp.to_string())
+            .header("X-Signature", format!("sha256={}", hex::encode(signature)))
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[tokio::test]
+    async fn test_hmac_valid_signature() {
+        let request = signed_request(now(), r#"{"key":"value"}"#, "secret");
+        let response = hmac_app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_hmac_rejected() {
+        for request in [
+            signed_request(now(), r#"{"key":"value"}"#, "wrong"),
+            signed_request(now() - 3600, r#"{"key":"value"}"#, "secret"),
+        ] {
+            let response = hmac_app().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert!(body["error"].is_string());
+        }
+    }
+
+    #[test]
+    fn test_auth_config() {
+        let auth: AuthType = serde_json::from_value(json!({
+            "type": "hmac_sha256",
+            "secret": "secret",
+            "header": "X-Signature",
+            "timestamp_tolerance_secs": 300,
+        }))
+        .unwrap();
+        assert!(matches!(
+            auth,
+            AuthType::HmacSha256 { timestamp_header, .. } if timestamp_header == "X-Timestamp"
+        ));
+        let auth: AuthType =
+            serde_json::from_value(json!({"type": "bearer", "token": "token"})).unwrap();
+        assert!(matches!(auth, AuthType::Bearer { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_bearer_token() {
+        let (sender, _receiver) = flume::bounded(10);
+        let auth = AuthType::Bearer {
+            token: "token".to_string(),
+        };
+        let app = HttpInput::router("/test", state(sender, BodyFormat::Json), Some(auth));
+        let request = Request::builder()
+            .method("POST")
+            .uri("/test")
+            .header("content-type", "application/json")
+            .header("Authorization", "Bearer token")
+            .body(Body::from(json!({"key": "value"}).to_string()))
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    fn format_app(body_format: BodyFormat) -> (Router, Receiver<MessageBatch>) {
+        let (sender, receiver) = flume::bounded(10);
+        let app = HttpInput::router("/test", state(sender, body_format), None);
+        (app, receiver)
+    }
+
+    fn body_request(content_type: &str, body: impl Into<Body>) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri("/test")
+            .header("content-type", content_type)
+            .body(body.into())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_raw_body() {
+        let (app, receiver) = format_app(BodyFormat::Raw);
+        let response = app
+            .oneshot(body_request("text/plain", "hello\nworld"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let msg = receiver.recv_async().await.unwrap();
+        assert_eq!(
+            msg.to_binary(arkflow_core::DEFAULT_BINARY_VALUE_FIELD)
+                .unwrap(),
+            vec![b"hello\nworld".as_slice()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_body() {
+        let (app, receiver) = format_app(BodyFormat::Ndjson);
+        let response = app
+            .clone()
+            .oneshot(body_request(
+                "application/x-ndjson",
+                "{\"a\":1}\r\n\n{\"a\":2}\n",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let msg = receiver.recv_async().await.unwrap();
+        assert_eq!(
+            msg.to_binary(arkflow_core::DEFAULT_BINARY_VALUE_FIELD)
+                .unwrap(),
+            vec![br#"{"a":1}"#.as_slice(), br#"{"a":2}"#.as_slice()]
+        );
+
+        let response = app
+            .oneshot(body_request("application/json", "{\"a\":1}"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[tokio::test]
+    async fn test_arrow_ipc_body() {
+        use datafusion::arrow::array::{ArrayRef, Int64Array};
+        use datafusion::arrow::ipc::writer::StreamWriter;
+        use datafusion::arrow::record_batch::RecordBatch;
+
+        let batch = RecordBatch::try_from_iter([(
+            "id",
+            Arc::new(Int64Array::from(vec![1, 2])) as ArrayRef,
+        )])
+        .unwrap();
+        let mut writer = StreamWriter::try_new(Vec::new(), &batch.schema()).unwrap();
+        writer.write(&batch).unwrap();
+        writer.write(&batch).unwrap();
+        writer.finish().unwrap();
+        let body = writer.into_inner().unwrap();
+
+        let (app, receiver) = format_app(BodyFormat::ArrowIpc);
+        let response = app
+            .clone()
+            .oneshot(body_request("application/vnd.apache.arrow.stream", body))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let msg = receiver.recv_async().await.unwrap();
+        assert_eq!(msg.num_rows(), 4);
+        assert_eq!(msg.schema(), batch.schema());
+
+        let response = app
+            .oneshot(body_request(
+                "application/vnd.apache.arrow.stream",
+                "garbage",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_body_too_large() {
+        let (app, _receiver) = format_app(BodyFormat::Raw);
+        let response = app
+            .clone()
+            .oneshot(body_request("text/plain", vec![b'x'; 2048]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // Chunked bodies have no content length, the limit applies while reading them
+        let chunks = futures::stream::iter(vec![
+            Ok::<_, std::io::Error>(vec![b'x'; 1000]),
+            Ok(vec![b'x'; 1000]),
+        ]);
+        let response = app
+            .oneshot(body_request("text/plain", Body::from_stream(chunks)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn test_body_format_content_type() {
+        assert!(BodyFormat::Json.accepts(Some("application/json; charset=utf-8")));
+        assert!(BodyFormat::Json.accepts(Some("application/cloudevents+json")));
+        assert!(!BodyFormat::Json.accepts(None));
+        assert!(BodyFormat::Raw.accepts(None));
+        assert!(!BodyFormat::Ndjson.accepts(Some("text/plain")));
+    }
+
+    fn multipart_app(accepted_mime_types: Vec<String>) -> (Router, Receiver<MessageBatch>) {
+        let (sender, receiver) = flume::bounded(10);
+        let state = Arc::new(MultipartStateInner {
+            sender,
+            field_name: "file".to_string(),
+            max_file_size_bytes: 16,
+            accepted_mime_types,
+        });
+        (MultipartInput::router("/test", state), receiver)
+    }
+
+    /// A multipart request with one part per `(field name, file name, content type, content)`
+    fn multipart_request(parts: &[(&str, &str, &str, &str)]) -> Request<Body> {
+        let mut body = String::new();
+        for (name, filename, content_type, content) in parts {
+            body.push_str(&format!(
+                "--boundary\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\
+                 Content-Type: {}\r\n\r\n{}\r\n",
+                name, filename, content_type, content
+            ));
+        }
+        body.push_str("--boundary--\r\n");
+        body_request("multipart/form-data; boundary=boundary", body)
+    }
+
+    #[tokio::test]
+    async fn test_multipart_upload() {
+        let (app, receiver) = multipart_app(vec![]);
+        let response = app
+            .oneshot(multipart_request(&[
+                ("file", "a.txt", "text/plain", "hello"),
+                ("other", "b.txt", "text/plain", "ignored"),
+                ("file", "c.csv", "text/csv", "x,y"),
+            ]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let msg = receiver.recv_async().await.unwrap();
+        assert_eq!(
+            msg.to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap(),
+            vec![b"hello".as_slice()]
+        );
+        let column = |name: &str| {
+            msg.column_by_name(name)
+                .unwrap()
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap()
+                .value(0)
+                .to_string()
+        };
+        assert_eq!(column(FILENAME_FIELD), "a.txt");
+        assert_eq!(column(CONTENT_TYPE_FIELD), "text/plain");
+        let size = msg
+            .column_by_name(SIZE_FIELD)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(size.value(0), 5);
+
+        let msg = receiver.recv_async().await.unwrap();
+        assert_eq!(
+            msg.to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap(),
+            vec![b"x,y".as_slice()]
+        );
+        assert!(receiver.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_multipart_rejected() {
+        let (app, receiver) = multipart_app(vec!["text/plain".to_string()]);
+        let response = app
+            .clone()
+            .oneshot(multipart_request(&[
+                ("file", "a.txt", "text/plain", "hello"),
+                ("file", "b.png", "image/png", "png"),
+            ]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let response = app
+            .clone()
+            .oneshot(multipart_request(&[(
+                "file",
+                "a.txt",
+                "text/plain; charset=utf-8",
+                "more than sixteen bytes",
+            )]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let response = app
+            .oneshot(multipart_request(&[(
+                "other",
+                "a.txt",
+                "text/plain",
+                "hello",
+            )]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        // Files of rejected requests are not sent
+        assert!(receiver.is_empty());
+    }
 }
@@ -0,0 +1,419 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! JMX input component
+//!
+//! Periodically read MBean attributes of a Java service through the Jolokia REST API
+
+use arkflow_core::input::{register_input_builder, Ack, Input, InputBuilder, NoopAck};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use datafusion::arrow::array::{ArrayRef, Float64Array, Int64Array, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tracing::warn;
+
+/// Timeout of a Jolokia request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Attributes to read from one MBean
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MBeanConfig {
+    /// Object name of the MBean, may be a pattern such as `java.lang:type=GarbageCollector,*`
+    pub mbean: String,
+    /// Attributes to read
+    pub attributes: Vec<String>,
+}
+
+/// JMX input configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JmxInputConfig {
+    /// Base URL of the Jolokia agent, e.g. `http://localhost:8778/jolokia`
+    pub jolokia_url: String,
+    /// MBeans to read
+    pub mbeans: Vec<MBeanConfig>,
+    /// Interval between two reads
+    #[serde(default = "default_poll_interval_secs")]
+    pub poll_interval_secs: u64,
+}
+
+fn default_poll_interval_secs() -> u64 {
+    10
+}
+
+/// One attribute value read from an MBean
+#[derive(Debug, Clone, PartialEq)]
+struct AttributeValue {
+    mbean: String,
+    attribute: String,
+    value: Value,
+}
+
+/// JMX input component
+pub struct JmxInput {
+    input_name: Option<String>,
+    config: JmxInputConfig,
+    client: Mutex<Option<reqwest::Client>>,
+    next_poll: Mutex<Instant>,
+}
+
+impl JmxInput {
+    /// Create a new JMX input component
+    pub fn new(name: Option<&String>, config: JmxInputConfig) -> Result<Self, Error> {
+        if config.mbeans.is_empty() {
+            return Err(Error::Config(
+                "JMX input requires at least one MBean".to_string(),
+            ));
+        }
+        if config.poll_interval_secs == 0 {
+            return Err(Error::Config(
+                "poll_interval_secs must be greater than 0".to_string(),
+            ));
+        }
+        Ok(Self {
+            input_name: name.cloned(),
+            config,
+            client: Mutex::new(None),
+            next_poll: Mutex::new(Instant::now()),
+        })
+    }
+
+    /// Read all configured attributes with a single bulk request
+    async fn read_attributes(
+        &self,
+        client: &reqwest::Client,
+    ) -> Result<Vec<AttributeValue>, Error> {
+        let requests: Vec<Value> = self
+            .config
+            .mbeans
+            .iter()
+            .map(|m| json!({"type": "read", "mbean": m.mbean, "attribute": m.attributes}))
+            .collect();
+
+        let response = client
+            .post(&self.config.jolokia_url)
+            .json(&requests)
+            .send()
+            .await
+            .map_err(|e| Error::Read(format!("Jolokia request failed: {}", e)))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::Read(format!(
+                "Jolokia request failed with status {}",
+                status
+            )));
+        }
+        let responses: Vec<Value> = response
+            .json()
+            .await
+            .map_err(|e| Error::Read(format!("Invalid Jolokia response: {}", e)))?;
+
+        let mut values = Vec::new();
+        for (config, response) in self.config.mbeans.iter().zip(responses) {
+            parse_response(config, response, &mut values);
+        }
+        Ok(values)
+    }
+}
+
+/// Collect the attribute values of one Jolokia read response
+///
+/// A failed read is logged and skipped so that one missing MBean does not stop the others.
+fn parse_response(config: &MBeanConfig, response: Value, values: &mut Vec<AttributeValue>) {
+    if response["status"].as_i64() != Some(200) {
+        warn!(
+            "Failed to read MBean {}: {}",
+            config.mbean,
+            response["error"].as_str().unwrap_or("unknown error")
+        );
+        return;
+    }
+
+    let Value::Object(value) = response["value"].clone() else {
+        return;
+    };
+    // Patterns return the attributes of every matching MBean keyed by its name
+    let per_mbean = if config.mbean.contains('*') {
+        value.into_iter().collect()
+    } else {
+        vec![(config.mbean.clone(), Value::Object(value))]
+    };
+
+    for (mbean, attributes) in per_mbean {
+        let Value::Object(attributes) = attributes else {
+            continue;
+        };
+        for attribute in &config.attributes {
+            if let Some(value) = attributes.get(attribute) {
+                values.push(AttributeValue {
+                    mbean: mbean.clone(),
+                    attribute: attribute.clone(),
+                    value: value.clone(),
+                });
+            }
+        }
+    }
+}
+
+/// Build the batch, numbers go to `value` and anything else JSON-encoded to `value_text`
+fn attributes_batch(values: &[AttributeValue], timestamp_ms: i64) -> Result<RecordBatch, Error> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("mbean", DataType::Utf8, false),
+        Field::new("attribute", DataType::Utf8, false),
+        Field::new("value", DataType::Float64, true),
+        Field::new("value_text", DataType::Utf8, true),
+        Field::new("timestamp_ms", DataType::Int64, false),
+    ]));
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(
+            values.iter().map(|v| v.mbean.as_str()),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            values.iter().map(|v| v.attribute.as_str()),
+        )),
+        Arc::new(Float64Array::from_iter(
+            values.iter().map(|v| v.value.as_f64()),
+        )),
+        Arc::new(StringArray::from_iter(values.iter().map(
+            |v| match &v.value {
+                Value::Number(_) => None,
+                Value::String(s) => Some(s.clone()),
+                other => Some(other.to_string()),
+            },
+        ))),
+        Arc::new(Int64Array::from_iter_values(
+            values.iter().map(|_| timestamp_ms),
+        )),
+    ];
+    RecordBatch::try_new(schema, columns)
+        .map_err(|e| Error::Process(format!("Unable to build the JMX batch: {}", e)))
+}
+
+#[async_trait]
+impl Input for JmxInput {
+    async fn connect(&self) -> Result<(), Error> {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| Error::Connection(format!("Failed to create HTTP client: {}", e)))?;
+        *self.client.lock().await = Some(client);
+        *self.next_poll.lock().await = Instant::now();
+        Ok(())
+    }
+
+    async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+        let Some(client) = self.client.lock().await.clone() else {
+            return Err(Error::Connection("The input is not connected".to_string()));
+        };
+
+        {
+            let mut next_poll = self.next_poll.lock().await;
+            tokio::time::sleep_until(*next_poll).await;
+            *next_poll = Instant::now() + Duration::from_secs(self.config.poll_interval_secs);
+        }
+
+        let values = self.read_attributes(&client).await?;
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or_default();
+        let mut msg = MessageBatch::new_arrow(attributes_batch(&values, timestamp_ms)?);
+        msg.set_input_name(self.input_name.clone());
+        Ok((msg, Arc::new(NoopAck)))
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        self.client.lock().await.take();
+        Ok(())
+    }
+}
+
+pub(crate) struct JmxInputBuilder;
+impl InputBuilder for JmxInputBuilder {
+    fn build(
+        &self,
+        name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Input>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "JMX input configuration is missing".to_string(),
+            ));
+        }
+        let config: JmxInputConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(JmxInput::new(name, config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_input_builder("jmx", Arc::new(JmxInputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::post;
+    use axum::{Json, Router};
+
+    fn config(mbean: &str, attributes: &[&str]) -> MBeanConfig {
+        MBeanConfig {
+            mbean: mbean.to_string(),
+            attributes: attributes.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let mut values = Vec::new();
+        parse_response(
+            &config("java.lang:type=Memory", &["HeapMemoryUsage", "Verbose"]),
+            json!({
+                "status": 200,
+                "value": {"HeapMemoryUsage": {"used": 1024, "max": 4096}, "Verbose": false}
+            }),
+            &mut values,
+        );
+        parse_response(
+            &config("java.lang:type=GarbageCollector,*", &["CollectionCount"]),
+            json!({
+                "status": 200,
+                "value": {
+                    "java.lang:name=G1 Young Generation,type=GarbageCollector": {"CollectionCount": 12},
+                    "java.lang:name=G1 Old Generation,type=GarbageCollector": {"CollectionCount": 1}
+                }
+            }),
+            &mut values,
+        );
+        parse_response(
+            &config("java.lang:type=Missing", &["Value"]),
+            json!({"status": 404, "error": "InstanceNotFoundException"}),
+            &mut values,
+        );
+
+        assert_eq!(values.len(), 4);
+        assert_eq!(values[0].mbean, "java.lang:type=Memory");
+        assert_eq!(values[0].value, json!({"used": 1024, "max": 4096}));
+        assert_eq!(values[1].value, json!(false));
+        assert!(values[2..]
+            .iter()
+            .all(|v| v.attribute == "CollectionCount" && v.mbean.contains("GarbageCollector")));
+    }
+
+    #[test]
+    fn test_attributes_batch() {
+        let values = vec![
+            AttributeValue {
+                mbean: "java.lang:type=Threading".to_string(),
+                attribute: "ThreadCount".to_string(),
+                value: json!(42),
+            },
+            AttributeValue {
+                mbean: "java.lang:type=Runtime".to_string(),
+                attribute: "VmName".to_string(),
+                value: json!("OpenJDK 64-Bit Server VM"),
+            },
+            AttributeValue {
+                mbean: "java.lang:type=Memory".to_string(),
+                attribute: "HeapMemoryUsage".to_string(),
+                value: json!({"used": 1024}),
+            },
+        ];
+        let batch = attributes_batch(&values, 1_700_000_000_000).unwrap();
+        assert_eq!(batch.num_rows(), 3);
+        let value = batch
+            .column_by_name("value")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(
+            value.iter().collect::<Vec<_>>(),
+            vec![Some(42.0), None, None]
+        );
+        let text = batch
+            .column_by_name("value_text")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(
+            text.iter().collect::<Vec<_>>(),
+            vec![
+                None,
+                Some("OpenJDK 64-Bit Server VM"),
+                Some(r#"{"used":1024}"#)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_from_jolokia() {
+        let app = Router::new().route(
+            "/jolokia",
+            post(|Json(body): Json<Value>| async move {
+                assert_eq!(body[0]["type"], "read");
+                assert_eq!(body[0]["attribute"], json!(["ThreadCount"]));
+                Json(json!([
+                    {"status": 200, "value": {"ThreadCount": 17}}
+                ]))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let input = JmxInput::new(
+            Some(&"jvm".to_string()),
+            JmxInputConfig {
+                jolokia_url: format!("http://{}/jolokia", addr),
+                mbeans: vec![config("java.lang:type=Threading", &["ThreadCount"])],
+                poll_interval_secs: 10,
+            },
+        )
+        .unwrap();
+        assert!(matches!(input.read().await, Err(Error::Connection(_))));
+
+        input.connect().await.unwrap();
+        let (msg, _) = input.read().await.unwrap();
+        assert_eq!(msg.get_input_name(), Some("jvm".to_string()));
+        assert_eq!(msg.num_rows(), 1);
+        let value = msg
+            .column_by_name("value")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(value.value(0), 17.0);
+        input.close().await.unwrap();
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let config = JmxInputConfig {
+            jolokia_url: "http://localhost:8778/jolokia".to_string(),
+            mbeans: vec![],
+            poll_interval_secs: 10,
+        };
+        assert!(matches!(JmxInput::new(None, config), Err(Error::Config(_))));
+    }
+}
@@ -16,16 +16,27 @@
 //!
 //! Receive data from a Kafka topic
 
+use crate::component::avro::{AvroDeserializer, SchemaRegistryConfig};
+use crate::component::offset_store::{OffsetStore, OffsetStoreConfig};
 use arkflow_core::input::{register_input_builder, Ack, Input, InputBuilder};
 use arkflow_core::{Error, MessageBatch, Resource};
 use async_trait::async_trait;
+use rdkafka::admin::{AdminClient, AdminOptions, NewTopic, TopicReplication};
+use rdkafka::client::DefaultClientContext;
 use rdkafka::config::ClientConfig;
-use rdkafka::consumer::{Consumer, StreamConsumer};
+use rdkafka::consumer::{BaseConsumer, Consumer, StreamConsumer};
 use rdkafka::message::Message as KafkaMessage;
+use rdkafka::types::RDKafkaErrorCode;
+use rdkafka::{Offset, TopicPartitionList};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
+/// Timeout of the metadata and topic creation requests
+const ADMIN_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// Kafka input configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KafkaInputConfig {
@@ -39,6 +50,22 @@ pub struct KafkaInputConfig {
     pub client_id: Option<String>,
     /// Start with the most news
     pub start_from_latest: bool,
+    /// Create the topics that do not exist before subscribing
+    #[serde(default)]
+    pub auto_create_topic: bool,
+    /// Number of partitions of the created topics
+    #[serde(default = "default_auto_create_partitions")]
+    pub auto_create_partitions: u32,
+    /// Where the offsets of the acknowledged messages are committed
+    #[serde(default)]
+    pub offset_store: OffsetStoreConfig,
+    /// Decode Confluent-framed Avro messages with the schemas of this registry
+    #[serde(default)]
+    pub schema_registry: Option<SchemaRegistryConfig>,
+}
+
+fn default_auto_create_partitions() -> u32 {
+    1
 }
 
 /// Kafka input component
@@ -46,57 +73,235 @@ pub struct KafkaInput {
     input_name: Option<String>,
     config: KafkaInputConfig,
     consumer: Arc<RwLock<Option<StreamConsumer>>>,
+    offset_store: RwLock<Option<Arc<dyn OffsetStore>>>,
+    avro: Option<AvroDeserializer>,
 }
 
 impl KafkaInput {
     /// Create a new Kafka input component
     pub fn new(name: Option<&String>, config: KafkaInputConfig) -> Result<Self, Error> {
+        let avro = config.schema_registry.clone().map(AvroDeserializer::new);
         Ok(Self {
             input_name: name.cloned(),
             config,
             consumer: Arc::new(RwLock::new(None)),
+            offset_store: RwLock::new(None),
+            avro,
         })
     }
 }
 
-#[async_trait]
-impl Input for KafkaInput {
-    async fn connect(&self) -> Result<(), Error> {
-        let mut client_config = ClientConfig::new();
+/// Create the subscribed topics missing from the cluster.
+///
+/// Kafka and Redpanda brokers both support topic creation through the Kafka admin API.
+async fn create_missing_topics(config: &KafkaInputConfig) -> Result<(), Error> {
+    let mut client_config = ClientConfig::new();
+    client_config.set("bootstrap.servers", config.brokers.join(","));
+    if let Some(client_id) = &config.client_id {
+        client_config.set("client.id", client_id);
+    }
+    let admin: Arc<AdminClient<DefaultClientContext>> =
+        Arc::new(client_config.create().map_err(|e| {
+            Error::Connection(format!("Unable to create a Kafka admin client: {}", e))
+        })?);
+
+    let metadata_admin = admin.clone();
+    let existing: HashSet<String> = tokio::task::spawn_blocking(move || {
+        metadata_admin
+            .inner()
+            .fetch_metadata(None, ADMIN_TIMEOUT)
+            .map(|metadata| {
+                metadata
+                    .topics()
+                    .iter()
+                    .map(|topic| topic.name().to_string())
+                    .collect()
+            })
+    })
+    .await
+    .map_err(|e| Error::Process(format!("Failed to spawn blocking task: {}", e)))?
+    .map_err(|e| Error::Connection(format!("Unable to fetch Kafka metadata: {}", e)))?;
+    let missing = missing_topics(&config.topics, &existing);
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let partitions = i32::try_from(config.auto_create_partitions).map_err(|_| {
+        Error::Config(format!(
+            "Invalid auto_create_partitions: {}",
+            config.auto_create_partitions
+        ))
+    })?;
+    let new_topics: Vec<NewTopic> = missing
+        .iter()
+        // A replication factor of -1 uses the default of the broker
+        .map(|topic| NewTopic::new(topic, partitions, TopicReplication::Fixed(-1)))
+        .collect();
+    let options = AdminOptions::new().operation_timeout(Some(ADMIN_TIMEOUT));
+    let results = admin
+        .create_topics(&new_topics, &options)
+        .await
+        .map_err(|e| Error::Connection(format!("Unable to create Kafka topics: {}", e)))?;
+    for result in results {
+        match result {
+            Ok(topic) => tracing::info!("Created Kafka topic {}", topic),
+            // Another client created the topic in the meantime
+            Err((_, RDKafkaErrorCode::TopicAlreadyExists)) => {}
+            Err((topic, code)) => {
+                return Err(Error::Connection(format!(
+                    "Unable to create Kafka topic {}: {}",
+                    topic, code
+                )))
+            }
+        }
+    }
+    Ok(())
+}
 
-        // Configure the Kafka server address
-        client_config.set("bootstrap.servers", &self.config.brokers.join(","));
+/// Topics of `topics` not in `existing`
+fn missing_topics<'a>(topics: &'a [String], existing: &HashSet<String>) -> Vec<&'a str> {
+    topics
+        .iter()
+        .map(|topic| topic.as_str())
+        .filter(|topic| !existing.contains(*topic))
+        .collect()
+}
 
-        // Set the consumer group ID
-        client_config.set("group.id", &self.config.consumer_group);
+/// Create a consumer subscribed to the topics of `config`, creating the missing topics first if
+/// `auto_create_topic` is set. `overrides` are applied to the consumer configuration last.
+pub(crate) async fn create_consumer(
+    config: &KafkaInputConfig,
+    overrides: &[(&str, &str)],
+) -> Result<StreamConsumer, Error> {
+    let consumer = new_consumer(config, overrides).await?;
+
+    // Subscribe to a topic
+    let x: Vec<&str> = config.topics.iter().map(|topic| topic.as_str()).collect();
+    consumer
+        .subscribe(&x)
+        .map_err(|e| Error::Connection(format!("You cannot subscribe to a Kafka topic: {}", e)))?;
+    Ok(consumer)
+}
 
-        // Set the client ID
-        if let Some(client_id) = &self.config.client_id {
-            client_config.set("client.id", client_id);
-        }
+/// Create a consumer assigned every partition of the topics of `config`, each starting from
+/// its offset in `store`, or from the position set by `start_from_latest` if it has none.
+///
+/// Partitions are assigned rather than subscribed to, so that the consumer group does not
+/// rebalance them and move the consumer away from the stored offsets.
+async fn create_assigned_consumer(
+    config: &KafkaInputConfig,
+    store: &dyn OffsetStore,
+) -> Result<StreamConsumer, Error> {
+    let consumer = new_consumer(config, &[("enable.auto.commit", "false")]).await?;
+
+    let mut assignment = TopicPartitionList::new();
+    for (topic, partition) in topic_partitions(config).await? {
+        let offset = match store.fetch(&topic, partition).await? {
+            Some(offset) => Offset::Offset(offset),
+            None if config.start_from_latest => Offset::End,
+            None => Offset::Beginning,
+        };
+        assignment
+            .add_partition_offset(&topic, partition, offset)
+            .map_err(|e| Error::Connection(format!("Invalid Kafka offset: {}", e)))?;
+    }
+    consumer
+        .assign(&assignment)
+        .map_err(|e| Error::Connection(format!("You cannot assign Kafka partitions: {}", e)))?;
+    Ok(consumer)
+}
 
-        // Set the offset reset policy
-        if self.config.start_from_latest {
-            client_config.set("auto.offset.reset", "latest");
-        } else {
-            client_config.set("auto.offset.reset", "earliest");
+/// Partitions of the topics of `config`
+async fn topic_partitions(config: &KafkaInputConfig) -> Result<Vec<(String, i32)>, Error> {
+    let mut client_config = ClientConfig::new();
+    client_config.set("bootstrap.servers", config.brokers.join(","));
+    if let Some(client_id) = &config.client_id {
+        client_config.set("client.id", client_id);
+    }
+    let consumer: BaseConsumer = client_config
+        .create()
+        .map_err(|e| Error::Connection(format!("Unable to create a Kafka consumer: {}", e)))?;
+
+    let topics = config.topics.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut partitions = Vec::new();
+        for topic in topics {
+            let metadata = consumer
+                .fetch_metadata(Some(&topic), ADMIN_TIMEOUT)
+                .map_err(|e| Error::Connection(format!("Unable to fetch Kafka metadata: {}", e)))?;
+            for topic_metadata in metadata.topics() {
+                if let Some(e) = topic_metadata.error() {
+                    return Err(Error::Connection(format!(
+                        "Unable to fetch the partitions of Kafka topic {}: {:?}",
+                        topic,
+                        RDKafkaErrorCode::from(e)
+                    )));
+                }
+                for partition in topic_metadata.partitions() {
+                    partitions.push((topic.clone(), partition.id()));
+                }
+            }
         }
+        Ok(partitions)
+    })
+    .await
+    .map_err(|e| Error::Process(format!("Failed to spawn blocking task: {}", e)))?
+}
+
+/// Create a consumer with the configuration of `config`, creating the missing topics first if
+/// `auto_create_topic` is set
+async fn new_consumer(
+    config: &KafkaInputConfig,
+    overrides: &[(&str, &str)],
+) -> Result<StreamConsumer, Error> {
+    if config.auto_create_topic {
+        create_missing_topics(config).await?;
+    }
 
-        // Create consumers
-        let consumer: StreamConsumer = client_config
-            .create()
-            .map_err(|e| Error::Connection(format!("Unable to create a Kafka consumer: {}", e)))?;
+    let mut client_config = ClientConfig::new();
 
-        // Subscribe to a topic
-        let x: Vec<&str> = self
+    // Configure the Kafka server address
+    client_config.set("bootstrap.servers", config.brokers.join(","));
+
+    // Set the consumer group ID
+    client_config.set("group.id", &config.consumer_group);
+
+    // Set the client ID
+    if let Some(client_id) = &config.client_id {
+        client_config.set("client.id", client_id);
+    }
+
+    // Set the offset reset policy
+    if config.start_from_latest {
+        client_config.set("auto.offset.reset", "latest");
+    } else {
+        client_config.set("auto.offset.reset", "earliest");
+    }
+
+    for (key, value) in overrides {
+        client_config.set(*key, *value);
+    }
+
+    // Create consumers
+    client_config
+        .create()
+        .map_err(|e| Error::Connection(format!("Unable to create a Kafka consumer: {}", e)))
+}
+
+#[async_trait]
+impl Input for KafkaInput {
+    async fn connect(&self) -> Result<(), Error> {
+        let offset_store = self
             .config
-            .topics
-            .iter()
-            .map(|topic| topic.as_str())
-            .collect();
-        consumer.subscribe(&x).map_err(|e| {
-            Error::Connection(format!("You cannot subscribe to a Kafka topic: {}", e))
-        })?;
+            .offset_store
+            .build(&self.config.consumer_group, self.consumer.clone())
+            .await?;
+        let consumer = if self.config.offset_store.is_external() {
+            create_assigned_consumer(&self.config, offset_store.as_ref()).await?
+        } else {
+            create_consumer(&self.config, &[]).await?
+        };
+        *self.offset_store.write().await = Some(offset_store);
 
         // Update consumer and connection status
         let consumer_arc = self.consumer.clone();
@@ -113,6 +318,9 @@ impl Input for KafkaInput {
             return Err(Error::Connection("The input is not connected".to_string()));
         }
         let consumer = consumer_guard.as_ref().unwrap();
+        let Some(offset_store) = self.offset_store.read().await.clone() else {
+            return Err(Error::Connection("The input is not connected".to_string()));
+        };
 
         match consumer.recv().await {
             Ok(kafka_message) => {
@@ -121,9 +329,10 @@ impl Input for KafkaInput {
                     Error::Process("The Kafka message has no content".to_string())
                 })?;
 
-                let mut binary_data = Vec::new();
-                binary_data.push(payload.to_vec());
-                let mut msg_batch = MessageBatch::new_binary(binary_data)?;
+                let mut msg_batch = match &self.avro {
+                    Some(avro) => MessageBatch::new_arrow(avro.deserialize(payload).await?),
+                    None => MessageBatch::new_binary(vec![payload.to_vec()])?,
+                };
                 msg_batch.set_input_name(self.input_name.clone());
 
                 // Create acknowledgment object
@@ -132,7 +341,7 @@ impl Input for KafkaInput {
                 let offset = kafka_message.offset();
 
                 let ack = KafkaAck {
-                    consumer: self.consumer.clone(),
+                    offset_store,
                     topic,
                     partition,
                     offset,
@@ -160,7 +369,7 @@ impl Input for KafkaInput {
 
 /// Kafka message acknowledgment
 pub struct KafkaAck {
-    consumer: Arc<RwLock<Option<StreamConsumer>>>,
+    offset_store: Arc<dyn OffsetStore>,
     topic: String,
     partition: i32,
     offset: i64,
@@ -169,12 +378,13 @@ pub struct KafkaAck {
 #[async_trait]
 impl Ack for KafkaAck {
     async fn ack(&self) {
-        // Commit offsets
-        let consumer_mutex_guard = self.consumer.read().await;
-        if let Some(v) = &*consumer_mutex_guard {
-            if let Err(e) = v.store_offset(&self.topic, self.partition, self.offset) {
-                tracing::error!("Error committing Kafka offset: {}", e);
-            }
+        // Commit the offset of the next message to read
+        if let Err(e) = self
+            .offset_store
+            .commit(&self.topic, self.partition, self.offset + 1)
+            .await
+        {
+            tracing::error!("Error committing Kafka offset: {}", e);
         }
     }
 }
@@ -213,6 +423,10 @@ mod tests {
             consumer_group: "test-group".to_string(),
             client_id: Some("test-client".to_string()),
             start_from_latest: false,
+            auto_create_topic: false,
+            auto_create_partitions: 1,
+            offset_store: OffsetStoreConfig::Kafka,
+            schema_registry: None,
         };
 
         let input = KafkaInput::new(None, config);
@@ -233,6 +447,10 @@ mod tests {
             consumer_group: "test-group".to_string(),
             client_id: None,
             start_from_latest: true,
+            auto_create_topic: false,
+            auto_create_partitions: 1,
+            offset_store: OffsetStoreConfig::Kafka,
+            schema_registry: None,
         };
 
         let input = KafkaInput::new(None, config).unwrap();
@@ -247,6 +465,27 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_kafka_input_config_auto_create_defaults() {
+        let config: KafkaInputConfig = serde_json::from_value(serde_json::json!({
+            "brokers": ["localhost:9092"],
+            "topics": ["test-topic"],
+            "consumer_group": "test-group",
+            "start_from_latest": false,
+        }))
+        .unwrap();
+        assert!(!config.auto_create_topic);
+        assert_eq!(config.auto_create_partitions, 1);
+        assert!(!config.offset_store.is_external());
+    }
+
+    #[test]
+    fn test_missing_topics() {
+        let topics = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let existing = HashSet::from(["b".to_string(), "__consumer_offsets".to_string()]);
+        assert_eq!(missing_topics(&topics, &existing), vec!["a", "c"]);
+    }
+
     #[tokio::test]
     async fn test_kafka_ack() {
         let config = KafkaInputConfig {
@@ -255,11 +494,21 @@ mod tests {
             consumer_group: "test-group".to_string(),
             client_id: None,
             start_from_latest: true,
+            auto_create_topic: false,
+            auto_create_partitions: 1,
+            offset_store: OffsetStoreConfig::Kafka,
+            schema_registry: None,
         };
 
         let input = KafkaInput::new(None, config).unwrap();
+        let offset_store = input
+            .config
+            .offset_store
+            .build(&input.config.consumer_group, input.consumer.clone())
+            .await
+            .unwrap();
         let ack = KafkaAck {
-            consumer: input.consumer.clone(),
+            offset_store,
             topic: "test-topic".to_string(),
             partition: 0,
             offset: 100,
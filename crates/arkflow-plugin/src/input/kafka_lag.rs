@@ -0,0 +1,326 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Kafka consumer lag monitor input component
+//!
+//! Periodically emit the lag of consumer groups as Arrow batches instead of consuming messages
+
+use arkflow_core::input::{register_input_builder, Ack, Input, InputBuilder, NoopAck};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use datafusion::arrow::array::{ArrayRef, Int32Array, Int64Array, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{BaseConsumer, Consumer};
+use rdkafka::{Offset, TopicPartitionList};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tokio::time::{Instant, MissedTickBehavior};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Kafka lag monitor input configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KafkaLagMonitorInputConfig {
+    /// List of Kafka server addresses
+    pub brokers: Vec<String>,
+    /// Consumer groups to monitor
+    pub group_ids: Vec<String>,
+    /// Interval between two lag measurements
+    #[serde(default = "default_poll_interval_secs")]
+    pub poll_interval_secs: u64,
+}
+
+fn default_poll_interval_secs() -> u64 {
+    30
+}
+
+/// Lag of one partition for one consumer group
+#[derive(Debug, Clone, PartialEq)]
+struct PartitionLag {
+    group_id: String,
+    topic: String,
+    partition: i32,
+    committed_offset: i64,
+    end_offset: i64,
+}
+
+/// Kafka lag monitor input component
+pub struct KafkaLagMonitorInput {
+    input_name: Option<String>,
+    config: KafkaLagMonitorInputConfig,
+    /// One consumer per group, only used to query offsets and never subscribed
+    consumers: Arc<Mutex<Vec<(String, BaseConsumer)>>>,
+    next_poll: Mutex<Instant>,
+}
+
+impl KafkaLagMonitorInput {
+    /// Create a new Kafka lag monitor input component
+    pub fn new(name: Option<&String>, config: KafkaLagMonitorInputConfig) -> Result<Self, Error> {
+        if config.group_ids.is_empty() {
+            return Err(Error::Config(
+                "Kafka lag monitor requires at least one group id".to_string(),
+            ));
+        }
+        if config.poll_interval_secs == 0 {
+            return Err(Error::Config(
+                "poll_interval_secs must be greater than 0".to_string(),
+            ));
+        }
+        Ok(Self {
+            input_name: name.cloned(),
+            config,
+            consumers: Arc::new(Mutex::new(Vec::new())),
+            next_poll: Mutex::new(Instant::now()),
+        })
+    }
+}
+
+/// Collect the lag of every partition the group has committed offsets for
+fn group_lag(group_id: &str, consumer: &BaseConsumer) -> Result<Vec<PartitionLag>, Error> {
+    let metadata = consumer
+        .fetch_metadata(None, REQUEST_TIMEOUT)
+        .map_err(|e| Error::Read(format!("Unable to fetch Kafka metadata: {}", e)))?;
+
+    let mut tpl = TopicPartitionList::new();
+    for topic in metadata.topics() {
+        // Skip internal topics such as __consumer_offsets
+        if topic.name().starts_with("__") {
+            continue;
+        }
+        for partition in topic.partitions() {
+            tpl.add_partition(topic.name(), partition.id());
+        }
+    }
+
+    let committed = consumer
+        .committed_offsets(tpl, REQUEST_TIMEOUT)
+        .map_err(|e| {
+            Error::Read(format!(
+                "Unable to fetch committed offsets of group {}: {}",
+                group_id, e
+            ))
+        })?;
+
+    let mut lags = Vec::new();
+    for element in committed.elements() {
+        // Partitions the group never committed to are not consumed by it
+        let Offset::Offset(committed_offset) = element.offset() else {
+            continue;
+        };
+        let (_, end_offset) = consumer
+            .fetch_watermarks(element.topic(), element.partition(), REQUEST_TIMEOUT)
+            .map_err(|e| {
+                Error::Read(format!(
+                    "Unable to fetch end offset of {}/{}: {}",
+                    element.topic(),
+                    element.partition(),
+                    e
+                ))
+            })?;
+        lags.push(PartitionLag {
+            group_id: group_id.to_string(),
+            topic: element.topic().to_string(),
+            partition: element.partition(),
+            committed_offset,
+            end_offset,
+        });
+    }
+    Ok(lags)
+}
+
+/// Build the lag batch, the lag is never negative
+fn lag_batch(lags: &[PartitionLag], timestamp_ms: i64) -> Result<RecordBatch, Error> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("group_id", DataType::Utf8, false),
+        Field::new("topic", DataType::Utf8, false),
+        Field::new("partition", DataType::Int32, false),
+        Field::new("committed_offset", DataType::Int64, false),
+        Field::new("end_offset", DataType::Int64, false),
+        Field::new("lag", DataType::Int64, false),
+        Field::new("timestamp_ms", DataType::Int64, false),
+    ]));
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(
+            lags.iter().map(|l| l.group_id.as_str()),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            lags.iter().map(|l| l.topic.as_str()),
+        )),
+        Arc::new(Int32Array::from_iter_values(
+            lags.iter().map(|l| l.partition),
+        )),
+        Arc::new(Int64Array::from_iter_values(
+            lags.iter().map(|l| l.committed_offset),
+        )),
+        Arc::new(Int64Array::from_iter_values(
+            lags.iter().map(|l| l.end_offset),
+        )),
+        Arc::new(Int64Array::from_iter_values(
+            lags.iter()
+                .map(|l| (l.end_offset - l.committed_offset).max(0)),
+        )),
+        Arc::new(Int64Array::from_iter_values(
+            lags.iter().map(|_| timestamp_ms),
+        )),
+    ];
+    RecordBatch::try_new(schema, columns)
+        .map_err(|e| Error::Process(format!("Unable to build the lag batch: {}", e)))
+}
+
+#[async_trait]
+impl Input for KafkaLagMonitorInput {
+    async fn connect(&self) -> Result<(), Error> {
+        let mut consumers = Vec::with_capacity(self.config.group_ids.len());
+        for group_id in &self.config.group_ids {
+            let consumer: BaseConsumer = ClientConfig::new()
+                .set("bootstrap.servers", self.config.brokers.join(","))
+                .set("group.id", group_id)
+                .set("enable.auto.commit", "false")
+                .create()
+                .map_err(|e| {
+                    Error::Connection(format!("Unable to create a Kafka consumer: {}", e))
+                })?;
+            consumers.push((group_id.clone(), consumer));
+        }
+
+        *self.consumers.lock().await = consumers;
+        *self.next_poll.lock().await = Instant::now();
+        Ok(())
+    }
+
+    async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+        if self.consumers.lock().await.is_empty() {
+            return Err(Error::Connection("The input is not connected".to_string()));
+        }
+
+        {
+            let mut next_poll = self.next_poll.lock().await;
+            tokio::time::sleep_until(*next_poll).await;
+            *next_poll = Instant::now() + Duration::from_secs(self.config.poll_interval_secs);
+        }
+
+        // The rdkafka calls are blocking
+        let consumers = self.consumers.clone();
+        let lags = tokio::task::spawn_blocking(move || {
+            let consumers = consumers.blocking_lock();
+            let mut lags = Vec::new();
+            for (group_id, consumer) in consumers.iter() {
+                lags.extend(group_lag(group_id, consumer)?);
+            }
+            Ok::<_, Error>(lags)
+        })
+        .await
+        .map_err(|e| Error::Process(format!("Lag collection task failed: {}", e)))??;
+
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or_default();
+        let mut msg = MessageBatch::new_arrow(lag_batch(&lags, timestamp_ms)?);
+        msg.set_input_name(self.input_name.clone());
+        Ok((msg, Arc::new(NoopAck)))
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        self.consumers.lock().await.clear();
+        Ok(())
+    }
+}
+
+pub(crate) struct KafkaLagMonitorInputBuilder;
+impl InputBuilder for KafkaLagMonitorInputBuilder {
+    fn build(
+        &self,
+        name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Input>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Kafka lag monitor input configuration is missing".to_string(),
+            ));
+        }
+        let config: KafkaLagMonitorInputConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(KafkaLagMonitorInput::new(name, config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_input_builder("kafka_lag_monitor", Arc::new(KafkaLagMonitorInputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lag_batch() {
+        let lags = vec![
+            PartitionLag {
+                group_id: "group".to_string(),
+                topic: "events".to_string(),
+                partition: 0,
+                committed_offset: 90,
+                end_offset: 100,
+            },
+            PartitionLag {
+                group_id: "group".to_string(),
+                topic: "events".to_string(),
+                partition: 1,
+                committed_offset: 120,
+                end_offset: 100,
+            },
+        ];
+        let batch = lag_batch(&lags, 1_700_000_000_000).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        let lag = batch
+            .column_by_name("lag")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(lag.values().to_vec(), vec![10, 0]);
+    }
+
+    #[tokio::test]
+    async fn test_read_not_connected() {
+        let input = KafkaLagMonitorInput::new(
+            None,
+            KafkaLagMonitorInputConfig {
+                brokers: vec!["localhost:9092".to_string()],
+                group_ids: vec!["group".to_string()],
+                poll_interval_secs: 30,
+            },
+        )
+        .unwrap();
+        assert!(matches!(input.read().await, Err(Error::Connection(_))));
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let config = KafkaLagMonitorInputConfig {
+            brokers: vec!["localhost:9092".to_string()],
+            group_ids: vec![],
+            poll_interval_secs: 30,
+        };
+        assert!(matches!(
+            KafkaLagMonitorInput::new(None, config),
+            Err(Error::Config(_))
+        ));
+    }
+}
@@ -0,0 +1,144 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Transactional Kafka input component
+//!
+//! Consume committed messages from Kafka topics without committing offsets. The offsets are
+//! committed by the `kafka_tx` output in the transaction writing the messages, from the
+//! `kafka_topic`, `kafka_partition` and `kafka_offset` columns of the messages.
+
+use crate::component::kafka::{
+    register_consumer, unregister_consumer, SharedConsumer, OFFSET_FIELD, PARTITION_FIELD,
+    TOPIC_FIELD,
+};
+use crate::input::kafka::{create_consumer, KafkaInputConfig};
+use arkflow_core::input::{register_input_builder, Ack, Input, InputBuilder, NoopAck};
+use arkflow_core::{Error, MessageBatch, Resource, DEFAULT_BINARY_VALUE_FIELD};
+use async_trait::async_trait;
+use datafusion::arrow::array::{ArrayRef, BinaryArray, Int32Array, Int64Array, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use rdkafka::consumer::Consumer;
+use rdkafka::message::{BorrowedMessage, Message as KafkaMessage};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Transactional Kafka input component
+pub struct TransactionalKafkaInput {
+    input_name: Option<String>,
+    config: KafkaInputConfig,
+    consumer: SharedConsumer,
+}
+
+impl TransactionalKafkaInput {
+    /// Create a new transactional Kafka input component
+    pub fn new(name: Option<&String>, config: KafkaInputConfig) -> Result<Self, Error> {
+        Ok(Self {
+            input_name: name.cloned(),
+            config,
+            consumer: Arc::new(RwLock::new(None)),
+        })
+    }
+}
+
+/// Build a message holding the payload and the position of a Kafka message
+fn message_batch(message: &BorrowedMessage) -> Result<MessageBatch, Error> {
+    let payload = message
+        .payload()
+        .ok_or_else(|| Error::Process("The Kafka message has no content".to_string()))?;
+    let schema = Arc::new(Schema::new(vec![
+        Field::new(DEFAULT_BINARY_VALUE_FIELD, DataType::Binary, false),
+        Field::new(TOPIC_FIELD, DataType::Utf8, false),
+        Field::new(PARTITION_FIELD, DataType::Int32, false),
+        Field::new(OFFSET_FIELD, DataType::Int64, false),
+    ]));
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(BinaryArray::from_vec(vec![payload])),
+        Arc::new(StringArray::from(vec![message.topic()])),
+        Arc::new(Int32Array::from(vec![message.partition()])),
+        Arc::new(Int64Array::from(vec![message.offset()])),
+    ];
+    let batch = RecordBatch::try_new(schema, columns)
+        .map_err(|e| Error::Process(format!("Creating an Arrow record batch failed: {}", e)))?;
+    Ok(MessageBatch::new_arrow(batch))
+}
+
+#[async_trait]
+impl Input for TransactionalKafkaInput {
+    async fn connect(&self) -> Result<(), Error> {
+        // Offsets are committed by the transactions of the output, and only messages of
+        // committed transactions are read
+        let consumer = create_consumer(
+            &self.config,
+            &[
+                ("enable.auto.commit", "false"),
+                ("isolation.level", "read_committed"),
+            ],
+        )
+        .await?;
+        *self.consumer.write().await = Some(Arc::new(consumer));
+        register_consumer(&self.config.consumer_group, self.consumer.clone());
+        Ok(())
+    }
+
+    async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+        // Not holding the lock while waiting, so that the input can be closed meanwhile
+        let Some(consumer) = self.consumer.read().await.clone() else {
+            return Err(Error::Connection("The input is not connected".to_string()));
+        };
+
+        let message = consumer
+            .recv()
+            .await
+            .map_err(|e| Error::Connection(format!("Error receiving Kafka message: {}", e)))?;
+        let mut msg_batch = message_batch(&message)?;
+        msg_batch.set_input_name(self.input_name.clone());
+
+        // The offset is committed with the transaction of the output
+        Ok((msg_batch, Arc::new(NoopAck)))
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        unregister_consumer(&self.config.consumer_group, &self.consumer);
+        let mut consumer_guard = self.consumer.write().await;
+        if let Some(consumer) = consumer_guard.take() {
+            if let Err(e) = consumer.unassign() {
+                tracing::warn!("Error unassigning Kafka consumer: {}", e);
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) struct TransactionalKafkaInputBuilder;
+impl InputBuilder for TransactionalKafkaInputBuilder {
+    fn build(
+        &self,
+        name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Input>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Transactional Kafka input configuration is missing".to_string(),
+            ));
+        }
+        let config: KafkaInputConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(TransactionalKafkaInput::new(name, config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_input_builder("kafka_tx", Arc::new(TransactionalKafkaInputBuilder))
+}
@@ -18,23 +18,44 @@
 
 use arkflow_core::Error;
 
+pub mod arrow_flight;
 pub mod file;
 pub mod generate;
+pub mod graphql;
 pub mod http;
+pub mod jmx;
 pub mod kafka;
+pub mod kafka_lag;
+pub mod kafka_tx;
 pub mod memory;
 pub mod modbus;
 pub mod mqtt;
 pub mod multiple_inputs;
+pub mod mysql_binlog;
 pub mod nats;
+pub mod parquet;
+pub mod parquet_listing;
+pub mod postgres_replication;
+pub mod process;
+pub mod pubsub;
 pub mod redis;
+pub mod slack_command;
+pub mod snmp;
+pub mod spanner;
 pub mod sql;
+pub mod sse;
+pub mod ssh_tunnel;
+pub mod syslog;
+pub mod unix_socket;
 pub mod websocket;
+pub mod zeromq;
 
 pub fn init() -> Result<(), Error> {
     generate::init()?;
     http::init()?;
     kafka::init()?;
+    kafka_lag::init()?;
+    kafka_tx::init()?;
     memory::init()?;
     mqtt::init()?;
     nats::init()?;
@@ -44,5 +65,22 @@ pub fn init() -> Result<(), Error> {
     multiple_inputs::init()?;
     modbus::init()?;
     file::init()?;
+    unix_socket::init()?;
+    process::init()?;
+    syslog::init()?;
+    graphql::init()?;
+    pubsub::init()?;
+    ssh_tunnel::init()?;
+    zeromq::init()?;
+    arrow_flight::init()?;
+    jmx::init()?;
+    parquet::init()?;
+    parquet_listing::init()?;
+    snmp::init()?;
+    slack_command::init()?;
+    sse::init()?;
+    postgres_replication::init()?;
+    mysql_binlog::init()?;
+    spanner::init()?;
     Ok(())
 }
@@ -14,19 +14,45 @@
 
 //! MQTT input component
 //!
-//! Receive data from the MQTT broker
+//! Receive data from the MQTT broker, over MQTT v3.1.1 or v5
 
 use arkflow_core::input::{register_input_builder, Ack, Input, InputBuilder};
-use arkflow_core::{Error, MessageBatch, Resource};
+use arkflow_core::trace::{SpanContext, TRACEPARENT_HEADER, TRACESTATE_HEADER};
+use arkflow_core::{Error, MessageBatch, Resource, DEFAULT_BINARY_VALUE_FIELD};
 
 use async_trait::async_trait;
+use datafusion::arrow::array::{ArrayRef, BinaryArray, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
 use flume::{Receiver, Sender};
-use rumqttc::{AsyncClient, Event, MqttOptions, Packet, Publish, QoS};
+use rumqttc::v5::mqttbytes::v5::{Publish as PublishV5, PublishProperties};
+use rumqttc::v5::mqttbytes::QoS as QoSV5;
+use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Packet, Publish, QoS};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tokio_util::sync::CancellationToken;
 use tracing::error;
+
+/// Column holding the topic a message was published to
+const TOPIC_FIELD: &str = "mqtt_topic";
+/// Column holding the content type of an MQTT v5 message
+const CONTENT_TYPE_FIELD: &str = "mqtt_content_type";
+/// Column holding the user properties of an MQTT v5 message as a JSON object
+const USER_PROPERTIES_FIELD: &str = "mqtt_user_properties";
+
+/// MQTT protocol version
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MqttVersion {
+    /// MQTT v3.1.1
+    #[default]
+    V3,
+    /// MQTT v5, whose publish packets carry properties
+    V5,
+}
+
 /// MQTT input configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MqttInputConfig {
@@ -44,30 +70,57 @@ pub struct MqttInputConfig {
     pub topics: Vec<String>,
     /// Quality of Service (0, 1, 2)
     pub qos: Option<u8>,
+    /// Quality of Service of individual topics, overriding `qos`
+    pub qos_per_topic: Option<HashMap<String, u8>>,
     /// Whether to use clean session
     pub clean_session: Option<bool>,
     /// Keep alive interval (in seconds)
     pub keep_alive: Option<u64>,
+    /// MQTT protocol version
+    #[serde(default)]
+    pub mqtt_version: MqttVersion,
+    /// Column to store the response topic of MQTT v5 messages in, for request-reply
+    pub response_topic_field: Option<String>,
 }
 
 /// MQTT input component
 pub struct MqttInput {
     input_name: Option<String>,
     config: MqttInputConfig,
-    client: Arc<Mutex<Option<AsyncClient>>>,
+    client: Arc<Mutex<Option<MqttClient>>>,
     sender: Sender<MqttMsg>,
     receiver: Receiver<MqttMsg>,
     cancellation_token: CancellationToken,
 }
 
+/// Client of the configured protocol version
+enum MqttClient {
+    V3(AsyncClient),
+    V5(rumqttc::v5::AsyncClient),
+}
+
+/// Publish packet of the configured protocol version
+enum MqttPublish {
+    V3(Publish),
+    V5(PublishV5),
+}
+
 enum MqttMsg {
-    Publish(Publish),
+    Publish(MqttPublish),
     Err(Error),
 }
 
 impl MqttInput {
     /// Create a new MQTT input component
     pub fn new(name: Option<&String>, config: MqttInputConfig) -> Result<Self, Error> {
+        if let Some(qos_per_topic) = &config.qos_per_topic {
+            if let Some((topic, qos)) = qos_per_topic.iter().find(|(_, qos)| **qos > 2) {
+                return Err(Error::Config(format!(
+                    "Invalid QoS {} for MQTT topic {}, expected 0, 1 or 2",
+                    qos, topic
+                )));
+            }
+        }
         let (sender, receiver) = flume::bounded::<MqttMsg>(1000);
         let cancellation_token = CancellationToken::new();
         Ok(Self {
@@ -79,11 +132,168 @@ impl MqttInput {
             cancellation_token,
         })
     }
+
+    /// QoS to subscribe to a topic with
+    fn topic_qos(&self, topic: &str) -> QoS {
+        let qos = self
+            .config
+            .qos_per_topic
+            .as_ref()
+            .and_then(|qos_per_topic| qos_per_topic.get(topic).copied())
+            .or(self.config.qos);
+        match qos {
+            Some(0) => QoS::AtMostOnce,
+            Some(1) => QoS::AtLeastOnce,
+            Some(2) => QoS::ExactlyOnce,
+            _ => QoS::AtLeastOnce, // Default is QoS 1
+        }
+    }
 }
 
-#[async_trait]
-impl Input for MqttInput {
-    async fn connect(&self) -> Result<(), Error> {
+/// Build a message holding the payload and the topic of a publish packet. The content type,
+/// response topic and user properties of MQTT v5 packets are stored in columns as well, the
+/// same columns for every packet so that the messages of a stream share one schema.
+fn publish_batch(
+    topic: &str,
+    payload: &[u8],
+    properties: Option<&PublishProperties>,
+    response_topic_field: Option<&str>,
+) -> Result<MessageBatch, Error> {
+    let mut fields = vec![
+        Field::new(DEFAULT_BINARY_VALUE_FIELD, DataType::Binary, false),
+        Field::new(TOPIC_FIELD, DataType::Utf8, false),
+    ];
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(BinaryArray::from_vec(vec![payload])),
+        Arc::new(StringArray::from(vec![topic])),
+    ];
+
+    if let Some(properties) = properties {
+        let mut add_column = |name: String, value: Option<&str>| {
+            fields.push(Field::new(name, DataType::Utf8, true));
+            columns.push(Arc::new(StringArray::from(vec![value])));
+        };
+        add_column(
+            CONTENT_TYPE_FIELD.to_string(),
+            properties.content_type.as_deref(),
+        );
+        if let Some(field) = response_topic_field {
+            add_column(field.to_string(), properties.response_topic.as_deref());
+        }
+        // A key may be repeated, the last value wins
+        let mut user_properties = serde_json::Map::new();
+        for (key, value) in &properties.user_properties {
+            user_properties.insert(key.clone(), serde_json::Value::String(value.clone()));
+        }
+        let user_properties = serde_json::Value::Object(user_properties).to_string();
+        add_column(USER_PROPERTIES_FIELD.to_string(), Some(&user_properties));
+    }
+
+    let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+        .map_err(|e| Error::Process(format!("Creating an Arrow record batch failed: {}", e)))?;
+    Ok(MessageBatch::new_arrow(batch))
+}
+
+/// Trace context in the `traceparent` and `tracestate` user properties of an MQTT v5 packet.
+/// As in the user properties column, the last value of a repeated key wins.
+fn trace_context(properties: &PublishProperties) -> Option<SpanContext> {
+    let property = |name: &str| {
+        properties
+            .user_properties
+            .iter()
+            .rev()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+    SpanContext::from_headers(property(TRACEPARENT_HEADER)?, property(TRACESTATE_HEADER))
+}
+
+/// QoS of MQTT v5 matching the QoS of MQTT v3.1.1
+fn qos_v5(qos: QoS) -> QoSV5 {
+    match qos {
+        QoS::AtMostOnce => QoSV5::AtMostOnce,
+        QoS::AtLeastOnce => QoSV5::AtLeastOnce,
+        QoS::ExactlyOnce => QoSV5::ExactlyOnce,
+    }
+}
+
+/// Queue a message for `read`
+async fn forward(sender: &Sender<MqttMsg>, msg: MqttMsg) {
+    if let Err(e) = sender.send_async(msg).await {
+        error!("{}", e)
+    }
+}
+
+/// Forward the publish packets of an MQTT v3.1.1 event loop until cancelled
+fn spawn_event_loop(
+    mut eventloop: EventLoop,
+    sender: Sender<MqttMsg>,
+    cancellation_token: CancellationToken,
+) {
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                result = eventloop.poll() => {
+                    match result {
+                        Ok(Event::Incoming(Packet::Publish(publish))) => {
+                            // Add messages to the queue
+                            forward(&sender, MqttMsg::Publish(MqttPublish::V3(publish))).await;
+                        }
+                        Ok(_) => {}
+                        Err(e) => {
+                            // Log the error and wait a short time before continuing
+                            error!("MQTT event loop error: {}", e);
+                            forward(&sender, MqttMsg::Err(Error::Disconnection)).await;
+                            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                        }
+                    }
+                }
+                _ = cancellation_token.cancelled() => {
+                    break;
+                }
+            }
+        }
+    });
+}
+
+/// Forward the publish packets of an MQTT v5 event loop until cancelled
+fn spawn_event_loop_v5(
+    mut eventloop: rumqttc::v5::EventLoop,
+    sender: Sender<MqttMsg>,
+    cancellation_token: CancellationToken,
+) {
+    use rumqttc::v5::mqttbytes::v5::Packet as PacketV5;
+    use rumqttc::v5::Event as EventV5;
+
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                result = eventloop.poll() => {
+                    match result {
+                        Ok(EventV5::Incoming(PacketV5::Publish(publish))) => {
+                            // Add messages to the queue
+                            forward(&sender, MqttMsg::Publish(MqttPublish::V5(publish))).await;
+                        }
+                        Ok(_) => {}
+                        Err(e) => {
+                            // Log the error and wait a short time before continuing
+                            error!("MQTT event loop error: {}", e);
+                            forward(&sender, MqttMsg::Err(Error::Disconnection)).await;
+                            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                        }
+                    }
+                }
+                _ = cancellation_token.cancelled() => {
+                    break;
+                }
+            }
+        }
+    });
+}
+
+impl MqttInput {
+    /// Create an MQTT v3.1.1 client and subscribe to the topics
+    async fn connect_v3(&self) -> Result<MqttClient, Error> {
         // Create MQTT options
         let mut mqtt_options =
             MqttOptions::new(&self.config.client_id, &self.config.host, self.config.port);
@@ -104,67 +314,99 @@ impl Input for MqttInput {
         }
 
         // Create an MQTT client
-        let (client, mut eventloop) = AsyncClient::new(mqtt_options, 10);
+        let (client, eventloop) = AsyncClient::new(mqtt_options, 10);
         // Subscribe to topics
-        let qos_level = match self.config.qos {
-            Some(0) => QoS::AtMostOnce,
-            Some(1) => QoS::AtLeastOnce,
-            Some(2) => QoS::ExactlyOnce,
-            _ => QoS::AtLeastOnce, // Default is QoS 1
-        };
-
         for topic in &self.config.topics {
-            client.subscribe(topic, qos_level).await.map_err(|e| {
-                Error::Connection(format!(
-                    "Unable to subscribe to MQTT topics {}: {}",
-                    topic, e
-                ))
-            })?;
+            client
+                .subscribe(topic, self.topic_qos(topic))
+                .await
+                .map_err(|e| subscribe_error(topic, e))?;
         }
 
-        let client_arc = Arc::new(&self.client);
-        let mut client_guard = client_arc.lock().await;
-        *client_guard = Some(client);
+        spawn_event_loop(
+            eventloop,
+            Sender::clone(&self.sender),
+            self.cancellation_token.clone(),
+        );
+        Ok(MqttClient::V3(client))
+    }
 
-        let sender_clone = Sender::clone(&self.sender);
+    /// Create an MQTT v5 client and subscribe to the topics
+    async fn connect_v5(&self) -> Result<MqttClient, Error> {
+        let mut mqtt_options = rumqttc::v5::MqttOptions::new(
+            &self.config.client_id,
+            &self.config.host,
+            self.config.port,
+        );
+        mqtt_options.set_manual_acks(true);
+        if let (Some(username), Some(password)) = (&self.config.username, &self.config.password) {
+            mqtt_options.set_credentials(username, password);
+        }
+        if let Some(keep_alive) = self.config.keep_alive {
+            mqtt_options.set_keep_alive(std::time::Duration::from_secs(keep_alive));
+        }
+        // MQTT v5 names the clean session a clean start
+        if let Some(clean_session) = self.config.clean_session {
+            mqtt_options.set_clean_start(clean_session);
+        }
 
-        let cancellation_token = self.cancellation_token.clone();
+        let (client, eventloop) = rumqttc::v5::AsyncClient::new(mqtt_options, 10);
+        for topic in &self.config.topics {
+            client
+                .subscribe(topic, qos_v5(self.topic_qos(topic)))
+                .await
+                .map_err(|e| subscribe_error(topic, e))?;
+        }
 
-        tokio::spawn(async move {
-            loop {
-                tokio::select! {
-                    result = eventloop.poll() => {
-                        match result {
-                            Ok(event) => {
-                                if let Event::Incoming(Packet::Publish(publish)) = event {
-                                    // Add messages to the queue
-                                    match sender_clone.send_async(MqttMsg::Publish(publish)).await {
-                                        Ok(_) => {}
-                                        Err(e) => {
-                                            error!("{}",e)
-                                        }
-                                    };
-                                }
-                            }
-                            Err(e) => {
-                               // Log the error and wait a short time before continuing
-                                error!("MQTT event loop error: {}", e);
-                                match sender_clone.send_async(MqttMsg::Err(Error::Disconnection)).await {
-                                        Ok(_) => {}
-                                        Err(e) => {
-                                            error!("{}",e)
-                                        }
-                                };
-                                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-                            }
-                        }
-                    }
-                    _ = cancellation_token.cancelled() => {
-                        break;
-                    }
+        spawn_event_loop_v5(
+            eventloop,
+            Sender::clone(&self.sender),
+            self.cancellation_token.clone(),
+        );
+        Ok(MqttClient::V5(client))
+    }
+
+    /// Build the message of a publish packet
+    fn message_batch(&self, publish: &MqttPublish) -> Result<MessageBatch, Error> {
+        match publish {
+            MqttPublish::V3(publish) => publish_batch(&publish.topic, &publish.payload, None, None),
+            MqttPublish::V5(publish) => {
+                // Messages without properties get the same columns, holding nulls
+                let no_properties = PublishProperties::default();
+                let properties = publish.properties.as_ref().unwrap_or(&no_properties);
+                let mut msg = publish_batch(
+                    &String::from_utf8_lossy(&publish.topic),
+                    &publish.payload,
+                    Some(properties),
+                    self.config.response_topic_field.as_deref(),
+                )?;
+                if let Some(context) = trace_context(properties) {
+                    msg.inject_span_context(&context);
                 }
+                Ok(msg)
             }
-        });
+        }
+    }
+}
+
+fn subscribe_error(topic: &str, e: impl std::fmt::Display) -> Error {
+    Error::Connection(format!(
+        "Unable to subscribe to MQTT topics {}: {}",
+        topic, e
+    ))
+}
+
+#[async_trait]
+impl Input for MqttInput {
+    async fn connect(&self) -> Result<(), Error> {
+        let client = match self.config.mqtt_version {
+            MqttVersion::V3 => self.connect_v3().await?,
+            MqttVersion::V5 => self.connect_v5().await?,
+        };
+
+        let client_arc = Arc::new(&self.client);
+        let mut client_guard = client_arc.lock().await;
+        *client_guard = Some(client);
 
         Ok(())
     }
@@ -184,8 +426,7 @@ impl Input for MqttInput {
                     Ok(msg) => {
                         match msg{
                             MqttMsg::Publish(publish) => {
-                                 let payload = publish.payload.to_vec();
-                            let mut msg = MessageBatch::new_binary(vec![payload])?;
+                            let mut msg = self.message_batch(&publish)?;
                             msg.set_input_name(self.input_name.clone());
 
                             Ok((msg, Arc::new(MqttAck {
@@ -216,9 +457,15 @@ impl Input for MqttInput {
         // Disconnect the MQTT connection
         let client_arc = Arc::clone(&self.client);
         let client_guard = client_arc.lock().await;
-        if let Some(client) = &*client_guard {
-            // Try to disconnect, but don't wait for the result
-            let _ = client.disconnect().await;
+        // Try to disconnect, but don't wait for the result
+        match &*client_guard {
+            Some(MqttClient::V3(client)) => {
+                let _ = client.disconnect().await;
+            }
+            Some(MqttClient::V5(client)) => {
+                let _ = client.disconnect().await;
+            }
+            None => {}
         }
 
         Ok(())
@@ -226,17 +473,24 @@ impl Input for MqttInput {
 }
 
 struct MqttAck {
-    client: Arc<Mutex<Option<AsyncClient>>>,
-    publish: Publish,
+    client: Arc<Mutex<Option<MqttClient>>>,
+    publish: MqttPublish,
 }
 #[async_trait]
 impl Ack for MqttAck {
     async fn ack(&self) {
         let mutex_guard = self.client.lock().await;
-        if let Some(client) = &*mutex_guard {
-            if let Err(e) = client.ack(&self.publish).await {
-                error!("{}", e);
+        let result = match (&*mutex_guard, &self.publish) {
+            (Some(MqttClient::V3(client)), MqttPublish::V3(publish)) => {
+                client.ack(publish).await.map_err(|e| e.to_string())
             }
+            (Some(MqttClient::V5(client)), MqttPublish::V5(publish)) => {
+                client.ack(publish).await.map_err(|e| e.to_string())
+            }
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            error!("{}", e);
         }
     }
 }
@@ -263,3 +517,173 @@ impl InputBuilder for MqttInputBuilder {
 pub fn init() -> Result<(), Error> {
     register_input_builder("mqtt", Arc::new(MqttInputBuilder))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(qos_per_topic: Option<HashMap<String, u8>>) -> MqttInputConfig {
+        MqttInputConfig {
+            host: "localhost".to_string(),
+            port: 1883,
+            client_id: "arkflow".to_string(),
+            username: None,
+            password: None,
+            topics: vec!["sensors/#".to_string(), "alerts".to_string()],
+            qos: Some(0),
+            qos_per_topic,
+            clean_session: None,
+            keep_alive: None,
+            mqtt_version: MqttVersion::V3,
+            response_topic_field: None,
+        }
+    }
+
+    #[test]
+    fn test_topic_qos() {
+        let input = MqttInput::new(
+            None,
+            config(Some(HashMap::from([("alerts".to_string(), 2)]))),
+        )
+        .unwrap();
+        assert_eq!(input.topic_qos("alerts"), QoS::ExactlyOnce);
+        assert_eq!(input.topic_qos("sensors/#"), QoS::AtMostOnce);
+
+        assert!(matches!(
+            MqttInput::new(
+                None,
+                config(Some(HashMap::from([("alerts".to_string(), 3)])))
+            ),
+            Err(Error::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_publish_batch() {
+        let publish = Publish::new("sensors/room1", QoS::AtLeastOnce, "21.5");
+        let msg = publish_batch(&publish.topic, &publish.payload, None, None).unwrap();
+        assert_eq!(
+            msg.to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap(),
+            vec![b"21.5".as_slice()]
+        );
+        let topic = msg
+            .column_by_name(TOPIC_FIELD)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(topic.value(0), "sensors/room1");
+    }
+
+    fn value_of(msg: &MessageBatch, name: &str) -> String {
+        msg.column_by_name(name)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap()
+            .value(0)
+            .to_string()
+    }
+
+    #[test]
+    fn test_publish_batch_v5_properties() {
+        let mut input_config = config(None);
+        input_config.mqtt_version = MqttVersion::V5;
+        input_config.response_topic_field = Some("reply_to".to_string());
+        let input = MqttInput::new(None, input_config).unwrap();
+
+        let properties = PublishProperties {
+            content_type: Some("application/json".to_string()),
+            response_topic: Some("replies/room1".to_string()),
+            user_properties: vec![
+                ("unit".to_string(), "celsius".to_string()),
+                ("source".to_string(), "a".to_string()),
+                ("unit".to_string(), "kelvin".to_string()),
+            ],
+            ..Default::default()
+        };
+        let publish = PublishV5::new(
+            "sensors/room1",
+            QoSV5::AtLeastOnce,
+            "21.5",
+            Some(properties),
+        );
+        let msg = input.message_batch(&MqttPublish::V5(publish)).unwrap();
+
+        let value = |name: &str| value_of(&msg, name);
+        assert_eq!(value(TOPIC_FIELD), "sensors/room1");
+        assert_eq!(value(CONTENT_TYPE_FIELD), "application/json");
+        assert_eq!(value("reply_to"), "replies/room1");
+        let user_properties: serde_json::Value =
+            serde_json::from_str(&value(USER_PROPERTIES_FIELD)).unwrap();
+        assert_eq!(
+            user_properties,
+            serde_json::json!({"unit": "kelvin", "source": "a"})
+        );
+
+        // Messages without properties have the same schema, with null property columns
+        let schema = msg.schema();
+        let publish = PublishV5::new("sensors/room1", QoSV5::AtLeastOnce, "21.5", None);
+        let msg = input.message_batch(&MqttPublish::V5(publish)).unwrap();
+        assert_eq!(msg.schema(), schema);
+        assert!(msg.column_by_name(CONTENT_TYPE_FIELD).unwrap().is_null(0));
+        assert!(msg.column_by_name("reply_to").unwrap().is_null(0));
+        assert_eq!(value_of(&msg, USER_PROPERTIES_FIELD), "{}");
+    }
+
+    #[test]
+    fn test_publish_batch_v5_trace_context() {
+        let mut input_config = config(None);
+        input_config.mqtt_version = MqttVersion::V5;
+        let input = MqttInput::new(None, input_config).unwrap();
+
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let properties = PublishProperties {
+            user_properties: vec![
+                ("traceparent".to_string(), traceparent.to_string()),
+                ("tracestate".to_string(), "congo=t61rcWkgMzE".to_string()),
+            ],
+            ..Default::default()
+        };
+        let publish = PublishV5::new(
+            "sensors/room1",
+            QoSV5::AtLeastOnce,
+            "21.5",
+            Some(properties),
+        );
+        let msg = input.message_batch(&MqttPublish::V5(publish)).unwrap();
+        let context = msg.extract_span_context().unwrap();
+        assert_eq!(context.to_traceparent(), traceparent);
+        assert_eq!(context.trace_state.as_deref(), Some("congo=t61rcWkgMzE"));
+
+        // Invalid or missing trace contexts are ignored
+        let properties = PublishProperties {
+            user_properties: vec![("traceparent".to_string(), "invalid".to_string())],
+            ..Default::default()
+        };
+        let publish = PublishV5::new(
+            "sensors/room1",
+            QoSV5::AtLeastOnce,
+            "21.5",
+            Some(properties),
+        );
+        let msg = input.message_batch(&MqttPublish::V5(publish)).unwrap();
+        assert!(msg.extract_span_context().is_none());
+        let publish = PublishV5::new("sensors/room1", QoSV5::AtLeastOnce, "21.5", None);
+        let msg = input.message_batch(&MqttPublish::V5(publish)).unwrap();
+        assert!(msg.extract_span_context().is_none());
+    }
+
+    #[test]
+    fn test_mqtt_version_config() {
+        let config: MqttInputConfig = serde_json::from_value(serde_json::json!({
+            "host": "localhost",
+            "port": 1883,
+            "client_id": "arkflow",
+            "topics": ["sensors/#"],
+            "mqtt_version": "v5",
+        }))
+        .unwrap();
+        assert_eq!(config.mqtt_version, MqttVersion::V5);
+    }
+}
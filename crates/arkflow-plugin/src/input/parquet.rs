@@ -0,0 +1,473 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Parquet input component
+//!
+//! Read local Parquet files matching a glob pattern, skipping the row groups that cannot match the filters
+
+use arkflow_core::input::{register_input_builder, Ack, Input, InputBuilder, NoopAck};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use datafusion::arrow::array::AsArray;
+use datafusion::arrow::compute::filter_record_batch;
+use datafusion::arrow::datatypes::{Schema, SchemaRef};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::common::DFSchema;
+use datafusion::datasource::physical_plan::parquet::{
+    ParquetAccessPlan, ParquetFileMetrics, RowGroupAccessPlanFilter,
+};
+use datafusion::logical_expr::utils::conjunction;
+use datafusion::parquet::arrow::async_reader::ParquetRecordBatchStream;
+use datafusion::parquet::arrow::{ParquetRecordBatchStreamBuilder, ProjectionMask};
+use datafusion::physical_expr::PhysicalExpr;
+use datafusion::physical_optimizer::pruning::PruningPredicate;
+use datafusion::physical_plan::metrics::ExecutionPlanMetricsSet;
+use datafusion::prelude::{Expr, SessionContext};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, VecDeque};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::fs::File;
+use tokio::sync::Mutex;
+
+/// Parquet input configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParquetInputConfig {
+    /// Glob pattern of the files to read, e.g. `/data/*.parquet`
+    pub path: String,
+    /// SQL conditions the rows must all satisfy, e.g. `amount > 100`
+    #[serde(default)]
+    pub filters: Vec<String>,
+    /// Columns to read, all columns if not set
+    pub projection: Option<Vec<String>>,
+    /// Maximum number of rows per message
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Read the most recently modified files first, otherwise files are read in path order
+    #[serde(default = "default_read_from_latest")]
+    pub read_from_latest: bool,
+}
+
+fn default_batch_size() -> usize {
+    8192
+}
+
+fn default_read_from_latest() -> bool {
+    true
+}
+
+/// Batches of the file being read
+struct FileReader {
+    stream: ParquetRecordBatchStream<File>,
+    /// Filters bound to the columns being read
+    filter: Option<Arc<dyn PhysicalExpr>>,
+    /// Indices of the projected columns among the columns being read
+    projection: Option<Vec<usize>>,
+}
+
+struct ParquetState {
+    /// Files not opened yet
+    files: VecDeque<PathBuf>,
+    current: Option<FileReader>,
+}
+
+/// Parquet input component
+pub struct ParquetInput {
+    input_name: Option<String>,
+    config: ParquetInputConfig,
+    ctx: SessionContext,
+    state: Mutex<Option<ParquetState>>,
+}
+
+impl ParquetInput {
+    /// Create a new Parquet input component
+    pub fn new(name: Option<&String>, config: ParquetInputConfig) -> Result<Self, Error> {
+        if config.batch_size == 0 {
+            return Err(Error::Config(
+                "batch_size must be greater than 0".to_string(),
+            ));
+        }
+        Ok(Self {
+            input_name: name.cloned(),
+            config,
+            ctx: SessionContext::new(),
+            state: Mutex::new(None),
+        })
+    }
+
+    /// List the files matching the pattern in reading order
+    fn list_files(&self) -> Result<VecDeque<PathBuf>, Error> {
+        let paths = glob::glob(&self.config.path).map_err(|e| {
+            Error::Config(format!("Invalid path pattern {}: {}", self.config.path, e))
+        })?;
+        let mut files = Vec::new();
+        for path in paths {
+            let path = path.map_err(|e| Error::Read(format!("Failed to list files: {}", e)))?;
+            let metadata = std::fs::metadata(&path)?;
+            if metadata.is_file() {
+                files.push((metadata.modified()?, path));
+            }
+        }
+
+        if self.config.read_from_latest {
+            files.sort_by(|a, b| b.cmp(a));
+        } else {
+            files.sort_by(|a, b| a.1.cmp(&b.1));
+        }
+        Ok(files.into_iter().map(|(_, path)| path).collect())
+    }
+
+    /// Parse the filters of the configuration against `schema`
+    fn filter_expr(&self, schema: &Schema) -> Result<Option<Expr>, Error> {
+        let df_schema = DFSchema::try_from(schema.clone())
+            .map_err(|e| Error::Process(format!("Invalid Parquet schema: {}", e)))?;
+        let filters = self
+            .config
+            .filters
+            .iter()
+            .map(|filter| {
+                self.ctx
+                    .parse_sql_expr(filter, &df_schema)
+                    .map_err(|e| Error::Config(format!("Invalid filter {}: {}", filter, e)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(conjunction(filters))
+    }
+
+    fn physical_expr(&self, expr: Expr, schema: &Schema) -> Result<Arc<dyn PhysicalExpr>, Error> {
+        let df_schema = DFSchema::try_from(schema.clone())
+            .map_err(|e| Error::Process(format!("Invalid Parquet schema: {}", e)))?;
+        self.ctx
+            .create_physical_expr(expr, &df_schema)
+            .map_err(|e| Error::Config(format!("Invalid filter: {}", e)))
+    }
+
+    /// Open a file, reading only the row groups and the columns the filters and projection need
+    async fn open(&self, path: &PathBuf) -> Result<FileReader, Error> {
+        let file = File::open(path).await?;
+        let builder = ParquetRecordBatchStreamBuilder::new(file)
+            .await
+            .map_err(|e| Error::Read(format!("Failed to open {}: {}", path.display(), e)))?;
+        let file_schema: SchemaRef = Arc::clone(builder.schema());
+        let filter = self.filter_expr(&file_schema)?;
+
+        let mut row_groups = ParquetAccessPlan::new_all(builder.metadata().num_row_groups());
+        if let Some(filter) = &filter {
+            let predicate = self.physical_expr(filter.clone(), &file_schema)?;
+            let pruning = PruningPredicate::try_new(predicate, Arc::clone(&file_schema))
+                .map_err(|e| Error::Process(format!("Invalid filter: {}", e)))?;
+            let file_name = path.display().to_string();
+            let metrics = ParquetFileMetrics::new(0, &file_name, &ExecutionPlanMetricsSet::new());
+            let mut access_plan = RowGroupAccessPlanFilter::new(row_groups);
+            access_plan.prune_by_statistics(
+                &file_schema,
+                builder.parquet_schema(),
+                builder.metadata().row_groups(),
+                &pruning,
+                &metrics,
+            );
+            row_groups = access_plan.build();
+        }
+
+        // Read the projected columns and those the filters refer to
+        let projection = match &self.config.projection {
+            Some(projection) => {
+                let mut names: BTreeSet<&str> = projection.iter().map(String::as_str).collect();
+                if let Some(filter) = &filter {
+                    names.extend(filter.column_refs().into_iter().map(|c| c.name.as_str()));
+                }
+                let mut indices = Vec::with_capacity(names.len());
+                for name in names {
+                    indices.push(file_schema.index_of(name).map_err(|_| {
+                        Error::Config(format!("Column {} not found in {}", name, path.display()))
+                    })?);
+                }
+                indices.sort_unstable();
+                Some(indices)
+            }
+            None => None,
+        };
+        let read_schema = match &projection {
+            Some(indices) => Arc::new(
+                file_schema
+                    .project(indices)
+                    .map_err(|e| Error::Process(format!("Invalid projection: {}", e)))?,
+            ),
+            None => Arc::clone(&file_schema),
+        };
+        let mask = match &projection {
+            Some(indices) => ProjectionMask::roots(builder.parquet_schema(), indices.clone()),
+            None => ProjectionMask::all(),
+        };
+
+        let stream = builder
+            .with_row_groups(row_groups.row_group_indexes())
+            .with_projection(mask)
+            .with_batch_size(self.config.batch_size)
+            .build()
+            .map_err(|e| Error::Read(format!("Failed to read {}: {}", path.display(), e)))?;
+
+        Ok(FileReader {
+            stream,
+            filter: filter
+                .map(|filter| self.physical_expr(filter, &read_schema))
+                .transpose()?,
+            projection: self
+                .config
+                .projection
+                .as_ref()
+                .map(|projection| {
+                    projection
+                        .iter()
+                        .map(|name| read_schema.index_of(name))
+                        .collect::<Result<Vec<_>, _>>()
+                })
+                .transpose()
+                .map_err(|e| Error::Config(format!("Invalid projection: {}", e)))?,
+        })
+    }
+}
+
+/// Keep the rows matching the filter and the projected columns
+fn apply(reader: &FileReader, batch: RecordBatch) -> Result<RecordBatch, Error> {
+    let mut batch = batch;
+    if let Some(filter) = &reader.filter {
+        let mask = filter
+            .evaluate(&batch)
+            .map_err(|e| Error::Process(format!("Failed to evaluate the filters: {}", e)))?
+            .into_array(batch.num_rows())
+            .map_err(|e| Error::Process(format!("Failed to evaluate the filters: {}", e)))?;
+        batch = filter_record_batch(&batch, mask.as_boolean())
+            .map_err(|e| Error::Process(format!("Failed to filter rows: {}", e)))?;
+    }
+    if let Some(projection) = &reader.projection {
+        batch = batch
+            .project(projection)
+            .map_err(|e| Error::Process(format!("Failed to project columns: {}", e)))?;
+    }
+    Ok(batch)
+}
+
+#[async_trait]
+impl Input for ParquetInput {
+    async fn connect(&self) -> Result<(), Error> {
+        let files = self.list_files()?;
+        *self.state.lock().await = Some(ParquetState {
+            files,
+            current: None,
+        });
+        Ok(())
+    }
+
+    async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+        let mut state = self.state.lock().await;
+        let Some(state) = state.as_mut() else {
+            return Err(Error::Connection("The input is not connected".to_string()));
+        };
+
+        loop {
+            let reader = match &mut state.current {
+                Some(reader) => reader,
+                None => {
+                    let Some(path) = state.files.pop_front() else {
+                        return Err(Error::EOF);
+                    };
+                    state.current.insert(self.open(&path).await?)
+                }
+            };
+
+            // Batches never span row groups
+            let Some(batch) = reader.stream.next().await else {
+                state.current = None;
+                continue;
+            };
+            let batch = batch.map_err(|e| Error::Read(format!("Failed to read Parquet: {}", e)))?;
+            let batch = apply(reader, batch)?;
+            if batch.num_rows() == 0 {
+                continue;
+            }
+
+            let mut msg = MessageBatch::new_arrow(batch);
+            msg.set_input_name(self.input_name.clone());
+            return Ok((msg, Arc::new(NoopAck)));
+        }
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        self.state.lock().await.take();
+        Ok(())
+    }
+}
+
+pub(crate) struct ParquetInputBuilder;
+impl InputBuilder for ParquetInputBuilder {
+    fn build(
+        &self,
+        name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Input>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Parquet input configuration is missing".to_string(),
+            ));
+        }
+        let config: ParquetInputConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(ParquetInput::new(name, config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_input_builder("parquet", Arc::new(ParquetInputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Int64Array, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field};
+    use datafusion::parquet::arrow::ArrowWriter;
+    use datafusion::parquet::file::properties::WriterProperties;
+    use std::path::Path;
+
+    /// Write rows `ids` to a file with row groups of two rows
+    fn write_file(path: &Path, ids: Vec<i64>) {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let names: Vec<String> = ids.iter().map(|id| format!("name-{}", id)).collect();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(ids)),
+                Arc::new(StringArray::from(names)),
+            ],
+        )
+        .unwrap();
+        let properties = WriterProperties::builder()
+            .set_max_row_group_size(2)
+            .build();
+        let mut writer = ArrowWriter::try_new(
+            std::fs::File::create(path).unwrap(),
+            schema,
+            Some(properties),
+        )
+        .unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+    }
+
+    fn config(path: String) -> ParquetInputConfig {
+        ParquetInputConfig {
+            path,
+            filters: vec![],
+            projection: None,
+            batch_size: default_batch_size(),
+            read_from_latest: false,
+        }
+    }
+
+    async fn read_all(input: &ParquetInput) -> Vec<RecordBatch> {
+        let mut batches = Vec::new();
+        loop {
+            match input.read().await {
+                Ok((msg, _)) => batches.push(msg.into()),
+                Err(Error::EOF) => return batches,
+                Err(e) => panic!("{}", e),
+            }
+        }
+    }
+
+    fn ids(batch: &RecordBatch) -> Vec<i64> {
+        batch
+            .column_by_name("id")
+            .unwrap()
+            .as_primitive::<datafusion::arrow::datatypes::Int64Type>()
+            .values()
+            .to_vec()
+    }
+
+    #[tokio::test]
+    async fn test_read_row_groups_in_path_order() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(&dir.path().join("b.parquet"), vec![4, 5]);
+        write_file(&dir.path().join("a.parquet"), vec![1, 2, 3]);
+        std::fs::write(dir.path().join("ignored.txt"), "").unwrap();
+
+        let input =
+            ParquetInput::new(None, config(format!("{}/*.parquet", dir.path().display()))).unwrap();
+        input.connect().await.unwrap();
+        let batches = read_all(&input).await;
+        let ids: Vec<Vec<i64>> = batches.iter().map(ids).collect();
+        // One message per row group
+        assert_eq!(ids, vec![vec![1, 2], vec![3], vec![4, 5]]);
+    }
+
+    #[tokio::test]
+    async fn test_filters_and_projection() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(&dir.path().join("data.parquet"), vec![1, 2, 3, 4, 5, 6]);
+
+        let mut config = config(format!("{}/*.parquet", dir.path().display()));
+        config.filters = vec!["id >= 3".to_string(), "id <> 4".to_string()];
+        config.projection = Some(vec!["name".to_string()]);
+        let input = ParquetInput::new(None, config).unwrap();
+        input.connect().await.unwrap();
+
+        let batches = read_all(&input).await;
+        let names: Vec<Vec<String>> = batches
+            .iter()
+            .map(|batch| {
+                assert_eq!(batch.num_columns(), 1);
+                batch
+                    .column(0)
+                    .as_string::<i32>()
+                    .iter()
+                    .map(|name| name.unwrap().to_string())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                vec!["name-3".to_string()],
+                vec!["name-5".to_string(), "name-6".to_string()]
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prune_row_groups() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.parquet");
+        write_file(&path, vec![1, 2, 3, 4, 5, 6]);
+
+        let mut config = config(path.display().to_string());
+        config.filters = vec!["id > 4".to_string()];
+        let input = ParquetInput::new(None, config).unwrap();
+        let reader = input.open(&path).await.unwrap();
+        let batches: Vec<RecordBatch> = reader.stream.map(|batch| batch.unwrap()).collect().await;
+        // Only the last row group is read
+        assert_eq!(batches.len(), 1);
+        assert_eq!(ids(&batches[0]), vec![5, 6]);
+    }
+
+    #[tokio::test]
+    async fn test_read_not_connected() {
+        let input = ParquetInput::new(None, config("/tmp/*.parquet".to_string())).unwrap();
+        assert!(matches!(input.read().await, Err(Error::Connection(_))));
+    }
+}
@@ -0,0 +1,303 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Parquet listing input component
+//!
+//! Query a directory or glob of Parquet files as a DataFusion listing table, so that the filters
+//! are pushed down to the scan: Hive-style partitions and row groups that cannot match are skipped
+
+use arkflow_core::input::{register_input_builder, Ack, Input, InputBuilder, NoopAck};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use datafusion::arrow::datatypes::DataType;
+use datafusion::datasource::file_format::parquet::ParquetFormat;
+use datafusion::datasource::listing::ListingOptions;
+use datafusion::execution::SendableRecordBatchStream;
+use datafusion::prelude::{SQLOptions, SessionConfig, SessionContext};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Name of the listing table the filters are applied to
+const TABLE_NAME: &str = "data";
+
+/// Parquet listing input configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParquetListingInputConfig {
+    /// Glob pattern or directory of the files to read, e.g. `/data/*.parquet` or `/data/`
+    pub path_pattern: String,
+    /// SQL conditions the rows must all satisfy, e.g. `amount > 100`
+    #[serde(default)]
+    pub filters: Vec<String>,
+    /// Hive-style partition columns, in the order of the directories, e.g. `/data/year=2024/`
+    #[serde(default)]
+    pub partition_columns: Vec<PartitionColumn>,
+    /// Maximum number of rows per message
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+}
+
+/// Column whose values are read from the directory names
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartitionColumn {
+    pub column: String,
+    /// Arrow type of the column, e.g. `Utf8` or `Int32`
+    #[serde(rename = "type")]
+    pub data_type: String,
+}
+
+fn default_batch_size() -> usize {
+    8192
+}
+
+/// Parquet listing input component
+pub struct ParquetListingInput {
+    input_name: Option<String>,
+    config: ParquetListingInputConfig,
+    partition_columns: Vec<(String, DataType)>,
+    stream: Mutex<Option<SendableRecordBatchStream>>,
+}
+
+impl ParquetListingInput {
+    /// Create a new Parquet listing input component
+    pub fn new(name: Option<&String>, config: ParquetListingInputConfig) -> Result<Self, Error> {
+        if config.batch_size == 0 {
+            return Err(Error::Config(
+                "batch_size must be greater than 0".to_string(),
+            ));
+        }
+        let partition_columns = config
+            .partition_columns
+            .iter()
+            .map(|c| {
+                let data_type = c.data_type.parse::<DataType>().map_err(|e| {
+                    Error::Config(format!(
+                        "Invalid type {} of partition column {}: {}",
+                        c.data_type, c.column, e
+                    ))
+                })?;
+                Ok((c.column.clone(), data_type))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(Self {
+            input_name: name.cloned(),
+            config,
+            partition_columns,
+            stream: Mutex::new(None),
+        })
+    }
+
+    /// Query selecting the rows of the listing table that satisfy all the filters
+    fn query(&self) -> String {
+        let mut sql = format!("SELECT * FROM {}", TABLE_NAME);
+        if !self.config.filters.is_empty() {
+            let filters: Vec<String> = self
+                .config
+                .filters
+                .iter()
+                .map(|filter| format!("({})", filter))
+                .collect();
+            sql.push_str(" WHERE ");
+            sql.push_str(&filters.join(" AND "));
+        }
+        sql
+    }
+}
+
+#[async_trait]
+impl Input for ParquetListingInput {
+    async fn connect(&self) -> Result<(), Error> {
+        let ctx = SessionContext::new_with_config(
+            SessionConfig::new().with_batch_size(self.config.batch_size),
+        );
+        let options = ListingOptions::new(Arc::new(ParquetFormat::default()))
+            .with_file_extension(".parquet")
+            .with_table_partition_cols(self.partition_columns.clone());
+        ctx.register_listing_table(TABLE_NAME, &self.config.path_pattern, options, None, None)
+            .await
+            .map_err(|e| {
+                Error::Config(format!(
+                    "Failed to list Parquet files of {}: {}",
+                    self.config.path_pattern, e
+                ))
+            })?;
+
+        let sql_options = SQLOptions::new()
+            .with_allow_ddl(false)
+            .with_allow_dml(false)
+            .with_allow_statements(false);
+        let df = ctx
+            .sql_with_options(&self.query(), sql_options)
+            .await
+            .map_err(|e| Error::Config(format!("Invalid filters: {}", e)))?;
+        let stream = df
+            .execute_stream()
+            .await
+            .map_err(|e| Error::Process(format!("Failed to read Parquet files: {}", e)))?;
+
+        *self.stream.lock().await = Some(stream);
+        Ok(())
+    }
+
+    async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+        let mut stream = self.stream.lock().await;
+        let Some(stream) = stream.as_mut() else {
+            return Err(Error::Connection("The input is not connected".to_string()));
+        };
+
+        loop {
+            let Some(batch) = stream.next().await else {
+                return Err(Error::EOF);
+            };
+            let batch = batch.map_err(|e| Error::Read(format!("Failed to read Parquet: {}", e)))?;
+            if batch.num_rows() == 0 {
+                continue;
+            }
+
+            let mut msg = MessageBatch::new_arrow(batch);
+            msg.set_input_name(self.input_name.clone());
+            return Ok((msg, Arc::new(NoopAck)));
+        }
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        self.stream.lock().await.take();
+        Ok(())
+    }
+}
+
+pub(crate) struct ParquetListingInputBuilder;
+impl InputBuilder for ParquetListingInputBuilder {
+    fn build(
+        &self,
+        name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Input>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Parquet listing input configuration is missing".to_string(),
+            ));
+        }
+        let config: ParquetListingInputConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(ParquetListingInput::new(name, config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_input_builder("parquet_listing", Arc::new(ParquetListingInputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{AsArray, Int64Array, RecordBatch};
+    use datafusion::arrow::datatypes::{Field, Int64Type, Schema};
+    use datafusion::parquet::arrow::ArrowWriter;
+    use serde_json::json;
+    use std::path::Path;
+
+    fn write_file(path: &Path, ids: Vec<i64>) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batch =
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int64Array::from(ids))]).unwrap();
+        let mut writer =
+            ArrowWriter::try_new(std::fs::File::create(path).unwrap(), schema, None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+    }
+
+    fn input(config: serde_json::Value) -> ParquetListingInput {
+        ParquetListingInput::new(None, serde_json::from_value(config).unwrap()).unwrap()
+    }
+
+    async fn read_all(input: &ParquetListingInput) -> Vec<RecordBatch> {
+        let mut batches = Vec::new();
+        loop {
+            match input.read().await {
+                Ok((msg, _)) => batches.push(msg.into()),
+                Err(Error::EOF) => return batches,
+                Err(e) => panic!("{}", e),
+            }
+        }
+    }
+
+    fn ids(batches: &[RecordBatch]) -> Vec<i64> {
+        let mut ids: Vec<i64> = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column_by_name("id")
+                    .unwrap()
+                    .as_primitive::<Int64Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[tokio::test]
+    async fn test_partition_columns_and_filters() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(&dir.path().join("region=eu/a.parquet"), vec![1, 2, 3]);
+        write_file(&dir.path().join("region=us/b.parquet"), vec![4, 5, 6]);
+        std::fs::write(dir.path().join("region=eu/ignored.txt"), "").unwrap();
+
+        let input = input(json!({
+            "path_pattern": format!("{}/", dir.path().display()),
+            "filters": ["region = 'eu'", "id > 1"],
+            "partition_columns": [{"column": "region", "type": "Utf8"}],
+        }));
+        input.connect().await.unwrap();
+        let batches = read_all(&input).await;
+        assert_eq!(ids(&batches), vec![2, 3]);
+        assert!(batches
+            .iter()
+            .all(|batch| batch.column_by_name("region").is_some()));
+    }
+
+    #[tokio::test]
+    async fn test_glob_and_batch_size() {
+        let dir = tempfile::tempdir().unwrap();
+        write_file(&dir.path().join("a.parquet"), vec![1, 2, 3, 4, 5]);
+        write_file(&dir.path().join("b.parquet"), vec![6, 7]);
+
+        let input = input(json!({
+            "path_pattern": format!("{}/a*.parquet", dir.path().display()),
+            "batch_size": 2,
+        }));
+        input.connect().await.unwrap();
+        let batches = read_all(&input).await;
+        assert_eq!(ids(&batches), vec![1, 2, 3, 4, 5]);
+        assert!(batches.iter().all(|batch| batch.num_rows() <= 2));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_config() {
+        let config = json!({
+            "path_pattern": "/tmp/",
+            "partition_columns": [{"column": "region", "type": "NotAType"}],
+        });
+        let result = ParquetListingInput::new(None, serde_json::from_value(config).unwrap());
+        assert!(matches!(result, Err(Error::Config(_))));
+
+        let input = input(json!({"path_pattern": "/tmp/"}));
+        assert!(matches!(input.read().await, Err(Error::Connection(_))));
+    }
+}
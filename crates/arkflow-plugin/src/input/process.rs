@@ -0,0 +1,452 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Process input component
+//!
+//! Spawn an external command and read messages from its standard output
+
+use crate::component;
+use arkflow_core::input::{register_input_builder, Ack, Input, InputBuilder, NoopAck};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use datafusion::arrow::buffer::Buffer;
+use datafusion::arrow::ipc::reader::StreamDecoder;
+use flume::{Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+use tokio::process::{Child, ChildStdout, Command};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+/// Time to wait for the process to exit after SIGTERM before it is killed
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Process input configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessInputConfig {
+    /// Command to execute
+    pub command: String,
+    /// Command arguments
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Working directory of the process (optional)
+    pub working_dir: Option<String>,
+    /// Extra environment variables for the process
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Whether to restart the process when it exits with a non-zero code
+    #[serde(default)]
+    pub restart_on_exit: bool,
+    /// Delay before restarting the process (ms)
+    #[serde(default = "default_restart_delay_ms")]
+    pub restart_delay_ms: u64,
+    /// Format of the process standard output
+    #[serde(default)]
+    pub output_format: OutputFormat,
+}
+
+/// Format of the data written by the process to its standard output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// Each line becomes a binary message
+    #[default]
+    Lines,
+    /// Each line is a JSON object converted to an Arrow batch
+    Ndjson,
+    /// The output is an Arrow IPC stream; each record batch becomes a message
+    ArrowIpc,
+}
+
+enum ProcessMsg {
+    Batch(MessageBatch),
+    Err(Error),
+}
+
+/// Process input component
+pub struct ProcessInput {
+    input_name: Option<String>,
+    config: ProcessInputConfig,
+    sender: Sender<ProcessMsg>,
+    receiver: Receiver<ProcessMsg>,
+    cancellation_token: CancellationToken,
+    supervisor: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl ProcessInput {
+    /// Create a new process input component
+    pub fn new(name: Option<&String>, config: ProcessInputConfig) -> Result<Self, Error> {
+        let (sender, receiver) = flume::bounded::<ProcessMsg>(1000);
+        Ok(Self {
+            input_name: name.cloned(),
+            config,
+            sender,
+            receiver,
+            cancellation_token: CancellationToken::new(),
+            supervisor: Mutex::new(None),
+        })
+    }
+
+    fn spawn_process(config: &ProcessInputConfig) -> Result<Child, Error> {
+        let mut command = Command::new(&config.command);
+        command
+            .args(&config.args)
+            .envs(&config.env)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        if let Some(working_dir) = &config.working_dir {
+            command.current_dir(working_dir);
+        }
+
+        command.spawn().map_err(|e| {
+            Error::Connection(format!("Unable to spawn process {}: {}", config.command, e))
+        })
+    }
+
+    /// Keep the process running, restarting it when configured to do so
+    async fn supervise(
+        config: ProcessInputConfig,
+        mut child: Child,
+        sender: Sender<ProcessMsg>,
+        cancellation_token: CancellationToken,
+    ) {
+        loop {
+            let stdout = child.stdout.take();
+            let stderr = child.stderr.take();
+
+            // Collect stderr in the background so a chatty process never blocks on a full pipe
+            let stderr_task = tokio::spawn(async move {
+                let mut buf = String::new();
+                if let Some(mut stderr) = stderr {
+                    let _ = stderr.read_to_string(&mut buf).await;
+                }
+                buf
+            });
+
+            tokio::select! {
+                _ = Self::forward_stdout(stdout, config.output_format, &sender) => {}
+                _ = cancellation_token.cancelled() => {
+                    Self::terminate(&mut child).await;
+                    return;
+                }
+            }
+
+            let status = tokio::select! {
+                status = child.wait() => status,
+                _ = cancellation_token.cancelled() => {
+                    Self::terminate(&mut child).await;
+                    return;
+                }
+            };
+            let stderr = stderr_task.await.unwrap_or_default();
+
+            match status {
+                Ok(status) if !status.success() && config.restart_on_exit => {
+                    warn!(
+                        "Process {} exited with {}, restarting in {}ms. stderr: {}",
+                        config.command,
+                        status,
+                        config.restart_delay_ms,
+                        stderr.trim()
+                    );
+                }
+                Ok(status) => {
+                    info!("Process {} exited with {}", config.command, status);
+                    let _ = sender.send_async(ProcessMsg::Err(Error::EOF)).await;
+                    return;
+                }
+                Err(e) => {
+                    error!("Failed to wait for process {}: {}", config.command, e);
+                    let _ = sender.send_async(ProcessMsg::Err(Error::EOF)).await;
+                    return;
+                }
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_millis(config.restart_delay_ms)) => {}
+                _ = cancellation_token.cancelled() => return,
+            }
+
+            child = match Self::spawn_process(&config) {
+                Ok(child) => child,
+                Err(e) => {
+                    error!("{}", e);
+                    let _ = sender.send_async(ProcessMsg::Err(Error::EOF)).await;
+                    return;
+                }
+            };
+        }
+    }
+
+    async fn forward_stdout(
+        stdout: Option<ChildStdout>,
+        format: OutputFormat,
+        sender: &Sender<ProcessMsg>,
+    ) {
+        let Some(stdout) = stdout else {
+            return;
+        };
+
+        match format {
+            OutputFormat::Lines | OutputFormat::Ndjson => {
+                let mut lines = BufReader::new(stdout).lines();
+                loop {
+                    let line = match lines.next_line().await {
+                        Ok(Some(line)) => line,
+                        Ok(None) => break,
+                        Err(e) => {
+                            error!("Failed to read process output: {}", e);
+                            break;
+                        }
+                    };
+                    if format == OutputFormat::Ndjson && line.trim().is_empty() {
+                        continue;
+                    }
+
+                    let msg = match format {
+                        OutputFormat::Ndjson => {
+                            component::json::try_to_arrow(line.as_bytes(), None)
+                                .map(MessageBatch::new_arrow)
+                        }
+                        _ => MessageBatch::new_binary(vec![line.into_bytes()]),
+                    };
+                    let msg = match msg {
+                        Ok(msg) => ProcessMsg::Batch(msg),
+                        Err(e) => ProcessMsg::Err(e),
+                    };
+                    if sender.send_async(msg).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            OutputFormat::ArrowIpc => Self::forward_arrow_ipc(stdout, sender).await,
+        }
+    }
+
+    async fn forward_arrow_ipc<R: AsyncRead + Unpin>(mut reader: R, sender: &Sender<ProcessMsg>) {
+        let mut decoder = StreamDecoder::new();
+        let mut chunk = vec![0u8; 64 * 1024];
+        loop {
+            let n = match reader.read(&mut chunk).await {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) => {
+                    error!("Failed to read process output: {}", e);
+                    break;
+                }
+            };
+
+            let mut buffer = Buffer::from(chunk[..n].to_vec());
+            while !buffer.is_empty() {
+                let msg = match decoder.decode(&mut buffer) {
+                    Ok(Some(batch)) => ProcessMsg::Batch(MessageBatch::new_arrow(batch)),
+                    Ok(None) => continue,
+                    Err(e) => {
+                        let _ = sender
+                            .send_async(ProcessMsg::Err(Error::Process(format!(
+                                "Arrow IPC decode error: {}",
+                                e
+                            ))))
+                            .await;
+                        return;
+                    }
+                };
+                if sender.send_async(msg).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Send SIGTERM to the process and kill it if it does not exit in time
+    async fn terminate(child: &mut Child) {
+        if let Some(pid) = child.id() {
+            // SAFETY: `kill` has no memory-safety preconditions; the pid belongs to our child process
+            unsafe {
+                libc::kill(pid as libc::pid_t, libc::SIGTERM);
+            }
+        }
+
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, child.wait())
+            .await
+            .is_err()
+        {
+            warn!("Process did not exit after SIGTERM, killing it");
+            if let Err(e) = child.kill().await {
+                error!("Failed to kill process: {}", e);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Input for ProcessInput {
+    async fn connect(&self) -> Result<(), Error> {
+        let mut supervisor = self.supervisor.lock().await;
+        if supervisor.is_some() {
+            return Ok(());
+        }
+
+        let child = Self::spawn_process(&self.config)?;
+        info!("Started process: {}", self.config.command);
+
+        *supervisor = Some(tokio::spawn(Self::supervise(
+            self.config.clone(),
+            child,
+            self.sender.clone(),
+            self.cancellation_token.clone(),
+        )));
+        Ok(())
+    }
+
+    async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+        if self.supervisor.lock().await.is_none() {
+            return Err(Error::Connection("The input is not connected".to_string()));
+        }
+
+        tokio::select! {
+            result = self.receiver.recv_async() => {
+                match result {
+                    Ok(ProcessMsg::Batch(mut msg)) => {
+                        msg.set_input_name(self.input_name.clone());
+                        Ok((msg, Arc::new(NoopAck)))
+                    }
+                    Ok(ProcessMsg::Err(e)) => Err(e),
+                    Err(_) => Err(Error::EOF),
+                }
+            }
+            _ = self.cancellation_token.cancelled() => {
+                Err(Error::EOF)
+            }
+        }
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        self.cancellation_token.cancel();
+        if let Some(handle) = self.supervisor.lock().await.take() {
+            let _ = handle.await;
+        }
+        Ok(())
+    }
+}
+
+fn default_restart_delay_ms() -> u64 {
+    1000
+}
+
+pub(crate) struct ProcessInputBuilder;
+impl InputBuilder for ProcessInputBuilder {
+    fn build(
+        &self,
+        name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Input>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Process input configuration is missing".to_string(),
+            ));
+        }
+
+        let config: ProcessInputConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(ProcessInput::new(name, config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_input_builder("process", Arc::new(ProcessInputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arkflow_core::DEFAULT_BINARY_VALUE_FIELD;
+
+    fn config(script: &str, output_format: OutputFormat) -> ProcessInputConfig {
+        ProcessInputConfig {
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            working_dir: None,
+            env: HashMap::new(),
+            restart_on_exit: false,
+            restart_delay_ms: 10,
+            output_format,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_lines_until_exit() {
+        let input =
+            ProcessInput::new(None, config("printf 'a\\nb\\n'", OutputFormat::Lines)).unwrap();
+        input.connect().await.unwrap();
+
+        for expected in ["a", "b"] {
+            let (msg, _) = input.read().await.unwrap();
+            assert_eq!(
+                msg.to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap(),
+                vec![expected.as_bytes()]
+            );
+        }
+        assert!(matches!(input.read().await, Err(Error::EOF)));
+        input.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_ndjson() {
+        let input = ProcessInput::new(
+            None,
+            config("echo '{\"a\": 1, \"b\": \"x\"}'", OutputFormat::Ndjson),
+        )
+        .unwrap();
+        input.connect().await.unwrap();
+
+        let (msg, _) = input.read().await.unwrap();
+        assert_eq!(msg.num_columns(), 2);
+        assert_eq!(msg.len(), 1);
+        input.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_env_is_passed() {
+        let mut config = config("echo $ARKFLOW_TEST", OutputFormat::Lines);
+        config
+            .env
+            .insert("ARKFLOW_TEST".to_string(), "value".to_string());
+        let input = ProcessInput::new(None, config).unwrap();
+        input.connect().await.unwrap();
+
+        let (msg, _) = input.read().await.unwrap();
+        assert_eq!(
+            msg.to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap(),
+            vec![b"value".as_slice()]
+        );
+        input.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_close_terminates_process() {
+        let input = ProcessInput::new(None, config("sleep 60", OutputFormat::Lines)).unwrap();
+        input.connect().await.unwrap();
+        let result = tokio::time::timeout(Duration::from_secs(3), input.close()).await;
+        assert!(result.is_ok());
+    }
+}
@@ -0,0 +1,388 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Google Cloud Pub/Sub input component
+//!
+//! Receive messages from a Pub/Sub subscription
+
+use crate::component::pubsub::{PubSubClient, ReceivedMessage};
+use arkflow_core::input::{register_input_builder, Ack, Input, InputBuilder};
+use arkflow_core::{Error, MessageBatch, Resource, DEFAULT_BINARY_VALUE_FIELD};
+use async_trait::async_trait;
+use datafusion::arrow::array::{ArrayRef, BinaryArray, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use flume::{Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::CancellationToken;
+use tracing::error;
+
+/// Maximum number of messages returned by a single pull request
+const MAX_PULL_MESSAGES: usize = 1000;
+/// Time to wait before pulling again after a failed pull
+const PULL_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Google Pub/Sub input configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PubSubInputConfig {
+    /// Google Cloud project id
+    pub project_id: String,
+    /// Subscription to receive messages from
+    pub subscription_id: String,
+    /// Maximum number of received messages that have not been acknowledged yet
+    #[serde(default = "default_max_outstanding_messages")]
+    pub max_outstanding_messages: i64,
+    /// Path to a service account key file
+    pub credentials_file: Option<String>,
+    /// Custom API endpoint, e.g. of the Pub/Sub emulator
+    pub endpoint: Option<String>,
+}
+
+fn default_max_outstanding_messages() -> i64 {
+    1000
+}
+
+enum PubSubMsg {
+    Messages(Vec<ReceivedMessage>, OwnedSemaphorePermit),
+    Err(Error),
+}
+
+/// Google Pub/Sub input component
+pub struct PubSubInput {
+    input_name: Option<String>,
+    config: PubSubInputConfig,
+    client: Arc<Mutex<Option<Arc<PubSubClient>>>>,
+    sender: Sender<PubSubMsg>,
+    receiver: Receiver<PubSubMsg>,
+    cancellation_token: Mutex<Option<CancellationToken>>,
+}
+
+impl PubSubInput {
+    /// Create a new Google Pub/Sub input component
+    pub fn new(name: Option<&String>, config: PubSubInputConfig) -> Result<Self, Error> {
+        if config.max_outstanding_messages <= 0 {
+            return Err(Error::Config(
+                "max_outstanding_messages must be greater than 0".to_string(),
+            ));
+        }
+        let (sender, receiver) = flume::bounded::<PubSubMsg>(1000);
+        Ok(Self {
+            input_name: name.cloned(),
+            config,
+            client: Arc::new(Mutex::new(None)),
+            sender,
+            receiver,
+            cancellation_token: Mutex::new(None),
+        })
+    }
+}
+
+#[async_trait]
+impl Input for PubSubInput {
+    async fn connect(&self) -> Result<(), Error> {
+        let client = Arc::new(PubSubClient::new(
+            &self.config.project_id,
+            self.config.credentials_file.as_deref(),
+            self.config.endpoint.as_deref(),
+        )?);
+
+        let token = CancellationToken::new();
+        if let Some(old) = self.cancellation_token.lock().await.replace(token.clone()) {
+            old.cancel();
+        }
+        *self.client.lock().await = Some(Arc::clone(&client));
+
+        let subscription_id = self.config.subscription_id.clone();
+        let outstanding = Arc::new(Semaphore::new(
+            self.config.max_outstanding_messages as usize,
+        ));
+        let sender = self.sender.clone();
+        tokio::spawn(async move {
+            loop {
+                // Wait until at least one more message may be outstanding, then take as many
+                // permits as are available for this pull
+                let mut permit = tokio::select! {
+                    _ = token.cancelled() => break,
+                    permit = Arc::clone(&outstanding).acquire_owned() => match permit {
+                        Ok(permit) => permit,
+                        Err(_) => break,
+                    },
+                };
+                let extra = outstanding.available_permits().min(MAX_PULL_MESSAGES - 1);
+                if extra > 0 {
+                    if let Ok(more) = Arc::clone(&outstanding).try_acquire_many_owned(extra as u32)
+                    {
+                        permit.merge(more);
+                    }
+                }
+
+                let result = tokio::select! {
+                    _ = token.cancelled() => break,
+                    result = client.pull(&subscription_id, permit.num_permits()) => result,
+                };
+                match result {
+                    Ok(messages) if messages.is_empty() => {}
+                    Ok(messages) => {
+                        // Keep one permit per delivered message, the rest is released here
+                        let permit = match permit.split(messages.len()) {
+                            Some(delivered) => delivered,
+                            None => permit,
+                        };
+                        if sender
+                            .send_async(PubSubMsg::Messages(messages, permit))
+                            .await
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        error!("Failed to pull Pub/Sub messages: {}", e);
+                        if sender.send_async(PubSubMsg::Err(e)).await.is_err() {
+                            break;
+                        }
+                        tokio::time::sleep(PULL_RETRY_INTERVAL).await;
+                    }
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+        let Some(client) = self.client.lock().await.clone() else {
+            return Err(Error::Disconnection);
+        };
+        let cancellation_token = self
+            .cancellation_token
+            .lock()
+            .await
+            .clone()
+            .unwrap_or_default();
+
+        tokio::select! {
+            result = self.receiver.recv_async() => {
+                match result {
+                    Ok(PubSubMsg::Messages(messages, permit)) => {
+                        let mut msg_batch = MessageBatch::new_arrow(to_record_batch(&messages)?);
+                        msg_batch.set_input_name(self.input_name.clone());
+                        let ack = PubSubAck {
+                            client,
+                            subscription_id: self.config.subscription_id.clone(),
+                            ack_ids: messages.into_iter().map(|m| m.ack_id).collect(),
+                            _permit: permit,
+                        };
+                        Ok((msg_batch, Arc::new(ack) as Arc<dyn Ack>))
+                    }
+                    Ok(PubSubMsg::Err(e)) => Err(e),
+                    Err(_) => Err(Error::EOF),
+                }
+            }
+            _ = cancellation_token.cancelled() => Err(Error::EOF),
+        }
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        if let Some(token) = self.cancellation_token.lock().await.take() {
+            token.cancel();
+        }
+        *self.client.lock().await = None;
+        Ok(())
+    }
+}
+
+/// Convert received messages to a batch with the payload, message id and publish time
+fn to_record_batch(messages: &[ReceivedMessage]) -> Result<RecordBatch, Error> {
+    let payloads = messages
+        .iter()
+        .map(|m| m.message.decode_data())
+        .collect::<Result<Vec<_>, _>>()?;
+    let schema = Arc::new(Schema::new(vec![
+        Field::new(DEFAULT_BINARY_VALUE_FIELD, DataType::Binary, false),
+        Field::new("message_id", DataType::Utf8, false),
+        Field::new("publish_time", DataType::Utf8, false),
+    ]));
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(BinaryArray::from_iter_values(payloads)),
+        Arc::new(StringArray::from_iter_values(
+            messages.iter().map(|m| m.message.message_id.as_str()),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            messages.iter().map(|m| m.message.publish_time.as_str()),
+        )),
+    ];
+    RecordBatch::try_new(schema, columns)
+        .map_err(|e| Error::Process(format!("Creating an Arrow record batch failed: {}", e)))
+}
+
+/// Acknowledges the messages of a batch; the outstanding message permits are released on drop
+struct PubSubAck {
+    client: Arc<PubSubClient>,
+    subscription_id: String,
+    ack_ids: Vec<String>,
+    _permit: OwnedSemaphorePermit,
+}
+
+#[async_trait]
+impl Ack for PubSubAck {
+    async fn ack(&self) {
+        if let Err(e) = self
+            .client
+            .acknowledge(&self.subscription_id, &self.ack_ids)
+            .await
+        {
+            error!("Failed to acknowledge Pub/Sub messages: {}", e);
+        }
+    }
+}
+
+struct PubSubInputBuilder;
+
+impl InputBuilder for PubSubInputBuilder {
+    fn build(
+        &self,
+        name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Input>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Google Pub/Sub input configuration is missing".to_string(),
+            ));
+        }
+        let config: PubSubInputConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(PubSubInput::new(name, config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_input_builder("google_pubsub", Arc::new(PubSubInputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Uri;
+    use axum::Json;
+    use axum::Router;
+    use datafusion::arrow::array::Array;
+    use serde_json::{json, Value};
+
+    type AckIds = Arc<std::sync::Mutex<Vec<Value>>>;
+
+    async fn mock_server(acked: AckIds) -> String {
+        let pulled = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let app = Router::new().fallback(move |uri: Uri, Json(body): Json<Value>| {
+            let acked = acked.clone();
+            let pulled = pulled.clone();
+            async move {
+                match uri.path() {
+                    "/v1/projects/project/subscriptions/sub:pull" => {
+                        assert_eq!(body["maxMessages"], 2);
+                        if pulled.swap(true, std::sync::atomic::Ordering::SeqCst) {
+                            return Json(json!({}));
+                        }
+                        Json(json!({
+                            "receivedMessages": [
+                                {
+                                    "ackId": "a1",
+                                    "message": {
+                                        "data": "aGVsbG8=",
+                                        "messageId": "1",
+                                        "publishTime": "2024-01-01T00:00:00Z"
+                                    }
+                                },
+                                {
+                                    "ackId": "a2",
+                                    "message": {
+                                        "data": "d29ybGQ=",
+                                        "messageId": "2",
+                                        "publishTime": "2024-01-01T00:00:01Z"
+                                    }
+                                }
+                            ]
+                        }))
+                    }
+                    "/v1/projects/project/subscriptions/sub:acknowledge" => {
+                        acked
+                            .lock()
+                            .unwrap()
+                            .extend(body["ackIds"].as_array().unwrap().clone());
+                        Json(json!({}))
+                    }
+                    path => panic!("unexpected request to {}", path),
+                }
+            }
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_receive_and_ack() {
+        let acked = AckIds::default();
+        let endpoint = mock_server(acked.clone()).await;
+        let input = PubSubInput::new(
+            Some(&"pubsub".to_string()),
+            PubSubInputConfig {
+                project_id: "project".to_string(),
+                subscription_id: "sub".to_string(),
+                max_outstanding_messages: 2,
+                credentials_file: None,
+                endpoint: Some(endpoint),
+            },
+        )
+        .unwrap();
+        input.connect().await.unwrap();
+
+        let (batch, ack) = input.read().await.unwrap();
+        assert_eq!(batch.get_input_name(), Some("pubsub".to_string()));
+        let values = batch.to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap();
+        assert_eq!(values, vec![b"hello".as_slice(), b"world".as_slice()]);
+        let ids = batch
+            .column_by_name("message_id")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap()
+            .clone();
+        assert_eq!(ids.value(0), "1");
+        assert_eq!(ids.value(1), "2");
+        assert_eq!(batch.column_by_name("publish_time").unwrap().len(), 2);
+
+        ack.ack().await;
+        assert_eq!(*acked.lock().unwrap(), vec![json!("a1"), json!("a2")]);
+        input.close().await.unwrap();
+    }
+
+    #[test]
+    fn test_invalid_max_outstanding_messages() {
+        let config = PubSubInputConfig {
+            project_id: "project".to_string(),
+            subscription_id: "sub".to_string(),
+            max_outstanding_messages: 0,
+            credentials_file: None,
+            endpoint: None,
+        };
+        assert!(PubSubInput::new(None, config).is_err());
+    }
+}
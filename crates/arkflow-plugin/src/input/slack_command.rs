@@ -0,0 +1,387 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Slack slash command input component
+//!
+//! Receive Slack slash commands on an HTTP endpoint, verifying their request signatures
+
+use arkflow_core::input::{register_input_builder, Ack, Input, InputBuilder, NoopAck};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use axum::Router;
+use datafusion::arrow::array::{ArrayRef, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use flume::{Receiver, Sender};
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+/// Maximum age of a request, as recommended by Slack to prevent replay attacks
+const MAX_REQUEST_AGE_SECS: u64 = 5 * 60;
+/// Version prefix of Slack request signatures
+const SIGNATURE_VERSION: &str = "v0";
+/// Form fields of a slash command turned into columns
+const COLUMNS: [&str; 5] = ["command", "text", "user_id", "channel_id", "team_id"];
+
+/// Slack slash command input configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SlackCommandInputConfig {
+    /// Listening address, e.g. `0.0.0.0:3000`
+    bind_address: String,
+    /// Signing secret of the Slack app
+    signing_secret: String,
+    /// Path of the request URL configured for the command
+    path: String,
+}
+
+/// Slack slash command input component
+struct SlackCommandInput {
+    input_name: Option<String>,
+    config: SlackCommandInputConfig,
+    sender: Sender<MessageBatch>,
+    receiver: Receiver<MessageBatch>,
+    server_handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+struct AppState {
+    signing_secret: hmac::Key,
+    sender: Sender<MessageBatch>,
+}
+
+impl SlackCommandInput {
+    fn new(name: Option<&String>, config: SlackCommandInputConfig) -> Result<Self, Error> {
+        if config.signing_secret.is_empty() {
+            return Err(Error::Config(
+                "Slack command input signing_secret must not be empty".to_string(),
+            ));
+        }
+        if !config.path.starts_with('/') {
+            return Err(Error::Config(format!(
+                "Slack command input path must start with '/': {}",
+                config.path
+            )));
+        }
+        let (sender, receiver) = flume::bounded::<MessageBatch>(1000);
+        Ok(Self {
+            input_name: name.cloned(),
+            config,
+            sender,
+            receiver,
+            server_handle: Mutex::new(None),
+        })
+    }
+
+    fn router(&self) -> Router {
+        let state = Arc::new(AppState {
+            signing_secret: hmac::Key::new(
+                hmac::HMAC_SHA256,
+                self.config.signing_secret.as_bytes(),
+            ),
+            sender: self.sender.clone(),
+        });
+        Router::new()
+            .route(&self.config.path, post(handle_command))
+            .with_state(state)
+    }
+}
+
+/// Verify the request and queue the command.
+///
+/// Slack shows an error to the user unless the response is a 200, so accepted commands get
+/// an empty 200 response.
+async fn handle_command(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    if let Err(reason) = verify_signature(&state.signing_secret, &headers, &body, now_secs()) {
+        warn!("Rejected Slack command request: {}", reason);
+        return StatusCode::UNAUTHORIZED;
+    }
+    let msg = match parse_command(&body) {
+        Ok(msg) => msg,
+        Err(e) => {
+            warn!("Invalid Slack command request: {}", e);
+            return StatusCode::BAD_REQUEST;
+        }
+    };
+    if state.sender.send_async(msg).await.is_err() {
+        return StatusCode::SERVICE_UNAVAILABLE;
+    }
+    StatusCode::OK
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Verify `X-Slack-Signature`, the HMAC-SHA256 of `v0:{timestamp}:{body}`
+fn verify_signature(
+    key: &hmac::Key,
+    headers: &HeaderMap,
+    body: &[u8],
+    now: u64,
+) -> Result<(), &'static str> {
+    let timestamp = headers
+        .get("X-Slack-Request-Timestamp")
+        .and_then(|v| v.to_str().ok())
+        .ok_or("missing timestamp")?;
+    let request_time: u64 = timestamp.trim().parse().map_err(|_| "invalid timestamp")?;
+    if now.abs_diff(request_time) > MAX_REQUEST_AGE_SECS {
+        return Err("timestamp too old");
+    }
+    let signature = headers
+        .get("X-Slack-Signature")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix(SIGNATURE_VERSION)?.strip_prefix('='))
+        .ok_or("missing signature")?;
+    let signature = hex::decode(signature).map_err(|_| "invalid signature")?;
+
+    let mut message = format!("{}:{}:", SIGNATURE_VERSION, timestamp).into_bytes();
+    message.extend_from_slice(body);
+    hmac::verify(key, &message, &signature).map_err(|_| "invalid signature")
+}
+
+/// Turn the form encoded command into a single-row batch
+fn parse_command(body: &[u8]) -> Result<MessageBatch, Error> {
+    let mut values: [Option<String>; COLUMNS.len()] = Default::default();
+    for (key, value) in url::form_urlencoded::parse(body) {
+        if let Some(i) = COLUMNS.iter().position(|column| *column == key) {
+            values[i] = Some(value.into_owned());
+        }
+    }
+    if values[0].is_none() {
+        return Err(Error::Process("The request has no command".to_string()));
+    }
+
+    let schema = Arc::new(Schema::new(
+        COLUMNS
+            .iter()
+            .map(|name| Field::new(*name, DataType::Utf8, true))
+            .collect::<Vec<_>>(),
+    ));
+    let columns = values
+        .into_iter()
+        .map(|value| Arc::new(StringArray::from(vec![value])) as ArrayRef)
+        .collect();
+    let batch = RecordBatch::try_new(schema, columns)
+        .map_err(|e| Error::Process(format!("Creating an Arrow record batch failed: {}", e)))?;
+    Ok(MessageBatch::new_arrow(batch))
+}
+
+#[async_trait]
+impl Input for SlackCommandInput {
+    async fn connect(&self) -> Result<(), Error> {
+        let mut server_handle = self.server_handle.lock().await;
+        if server_handle.is_some() {
+            return Ok(());
+        }
+        let listener = TcpListener::bind(&self.config.bind_address)
+            .await
+            .map_err(|e| {
+                Error::Connection(format!(
+                    "Failed to bind Slack command input to {}: {}",
+                    self.config.bind_address, e
+                ))
+            })?;
+        let app = self.router();
+        *server_handle = Some(tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                warn!("Slack command input server error: {}", e);
+            }
+        }));
+        Ok(())
+    }
+
+    async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+        let mut msg = self
+            .receiver
+            .recv_async()
+            .await
+            .map_err(|_| Error::Disconnection)?;
+        msg.set_input_name(self.input_name.clone());
+        Ok((msg, Arc::new(NoopAck)))
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        if let Some(handle) = self.server_handle.lock().await.take() {
+            handle.abort();
+        }
+        Ok(())
+    }
+}
+
+struct SlackCommandInputBuilder;
+impl InputBuilder for SlackCommandInputBuilder {
+    fn build(
+        &self,
+        name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Input>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Slack command input configuration is missing".to_string(),
+            ));
+        }
+        let config: SlackCommandInputConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(SlackCommandInput::new(name, config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_input_builder("slack_command", Arc::new(SlackCommandInputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use datafusion::arrow::array::AsArray;
+    use tower::util::ServiceExt;
+
+    const SECRET: &str = "8f742231b10e8888abcd99yyyzzz85a5";
+    const BODY: &str = "token=xyzz0WbapA4vBCDEFasx0q6G&team_id=T1DC2JH3J&team_domain=testteamnow&channel_id=G8PSS9T3V&channel_name=foobar&user_id=U2CERLKJA&user_name=roadrunner&command=%2Fwebhook-collect&text=hello+world&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2FT1DC2JH3J%2F397700885554%2F96rGlfmibIGlgcZRskXaIFfN&trigger_id=398738663015.47445629121.803a0bc887a14d10d2c447fce8b6703c";
+
+    fn input() -> SlackCommandInput {
+        SlackCommandInput::new(
+            Some(&"slack".to_string()),
+            SlackCommandInputConfig {
+                bind_address: "127.0.0.1:0".to_string(),
+                signing_secret: SECRET.to_string(),
+                path: "/slack/commands".to_string(),
+            },
+        )
+        .unwrap()
+    }
+
+    fn sign(timestamp: u64, body: &str, secret: &str) -> String {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+        let signature = hmac::sign(&key, format!("v0:{}:{}", timestamp, body).as_bytes());
+        format!("v0={}", hex::encode(signature))
+    }
+
+    fn request(timestamp: u64, signature: &str) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri("/slack/commands")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .header("X-Slack-Request-Timestamp", timestamp.to_string())
+            .header("X-Slack-Signature", signature)
+            .body(Body::from(BODY))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, SECRET.as_bytes());
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Slack-Request-Timestamp", "1531420618".parse().unwrap());
+        headers.insert(
+            "X-Slack-Signature",
+            sign(1531420618, BODY, SECRET).parse().unwrap(),
+        );
+        assert_eq!(
+            verify_signature(&key, &headers, BODY.as_bytes(), 1531420618 + 60),
+            Ok(())
+        );
+        assert_eq!(
+            verify_signature(&key, &headers, BODY.as_bytes(), 1531420618 + 301),
+            Err("timestamp too old")
+        );
+        assert_eq!(
+            verify_signature(&key, &headers, b"text=tampered", 1531420618),
+            Err("invalid signature")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_command_request() {
+        let input = input();
+        let timestamp = now_secs();
+        let response = input
+            .router()
+            .oneshot(request(timestamp, &sign(timestamp, BODY, SECRET)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(body.is_empty());
+
+        let (msg, _) = input.read().await.unwrap();
+        assert_eq!(msg.get_input_name(), Some("slack".to_string()));
+        let value = |name: &str| {
+            msg.column_by_name(name)
+                .unwrap()
+                .as_string::<i32>()
+                .value(0)
+                .to_string()
+        };
+        assert_eq!(value("command"), "/webhook-collect");
+        assert_eq!(value("text"), "hello world");
+        assert_eq!(value("user_id"), "U2CERLKJA");
+        assert_eq!(value("channel_id"), "G8PSS9T3V");
+        assert_eq!(value("team_id"), "T1DC2JH3J");
+    }
+
+    #[tokio::test]
+    async fn test_rejected_requests() {
+        let input = input();
+        let timestamp = now_secs();
+        for request in [
+            request(timestamp, &sign(timestamp, BODY, "wrong secret")),
+            request(timestamp - 600, &sign(timestamp - 600, BODY, SECRET)),
+            request(timestamp, "v1=00"),
+        ] {
+            let response = input.router().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+        assert!(input.receiver.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let config = SlackCommandInputConfig {
+            bind_address: "127.0.0.1:0".to_string(),
+            signing_secret: String::new(),
+            path: "/slack".to_string(),
+        };
+        assert!(SlackCommandInput::new(None, config.clone()).is_err());
+        assert!(SlackCommandInput::new(
+            None,
+            SlackCommandInputConfig {
+                signing_secret: SECRET.to_string(),
+                path: "slack".to_string(),
+                ..config
+            }
+        )
+        .is_err());
+    }
+}
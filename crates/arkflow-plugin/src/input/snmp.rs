@@ -0,0 +1,1504 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! SNMP input component
+//!
+//! Periodically read OIDs from a network device with SNMP GET requests. SNMPv2c uses a
+//! community string, SNMPv3 the user-based security model (RFC 3414) with optional
+//! authentication (HMAC-MD5-96, HMAC-SHA-96) and privacy (DES-CBC, AES-128-CFB).
+
+use arkflow_core::input::{register_input_builder, Ack, Input, InputBuilder, NoopAck};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use cbc::cipher::block_padding::NoPadding;
+use cbc::cipher::{AsyncStreamCipher, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use datafusion::arrow::array::{ArrayRef, Int64Array, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use hmac::{Hmac, Mac};
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::ops::Range;
+use std::sync::atomic::{AtomicI32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// Timeout of an SNMP request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Largest message accepted from the agent
+const MAX_MESSAGE_SIZE: usize = 65507;
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_NULL: u8 = 0x05;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_IP_ADDRESS: u8 = 0x40;
+const TAG_COUNTER32: u8 = 0x41;
+const TAG_GAUGE32: u8 = 0x42;
+const TAG_TIMETICKS: u8 = 0x43;
+const TAG_OPAQUE: u8 = 0x44;
+const TAG_COUNTER64: u8 = 0x46;
+const TAG_NO_SUCH_OBJECT: u8 = 0x80;
+const TAG_NO_SUCH_INSTANCE: u8 = 0x81;
+const TAG_END_OF_MIB_VIEW: u8 = 0x82;
+
+const PDU_GET_REQUEST: u8 = 0xa0;
+const PDU_RESPONSE: u8 = 0xa2;
+const PDU_REPORT: u8 = 0xa8;
+
+const VERSION_V2C: i64 = 1;
+const VERSION_V3: i64 = 3;
+/// User-based security model
+const SECURITY_MODEL_USM: i64 = 3;
+const FLAG_AUTH: u8 = 0x01;
+const FLAG_PRIV: u8 = 0x02;
+const FLAG_REPORTABLE: u8 = 0x04;
+/// Length of the truncated HMAC sent in the authentication parameters
+const AUTH_PARAMS_LEN: usize = 12;
+
+/// Counters an SNMPv3 agent reports when it rejects a request
+const USM_REPORTS: [(&str, &str); 6] = [
+    ("1.3.6.1.6.3.15.1.1.1.0", "unsupported security level"),
+    ("1.3.6.1.6.3.15.1.1.2.0", "not in time window"),
+    ("1.3.6.1.6.3.15.1.1.3.0", "unknown user name"),
+    ("1.3.6.1.6.3.15.1.1.4.0", "unknown engine ID"),
+    ("1.3.6.1.6.3.15.1.1.5.0", "wrong digest"),
+    ("1.3.6.1.6.3.15.1.1.6.0", "decryption error"),
+];
+
+/// SNMP protocol version
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnmpVersion {
+    #[default]
+    V2c,
+    V3,
+}
+
+/// SNMPv3 authentication protocol
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthProtocol {
+    Md5,
+    #[default]
+    Sha,
+}
+
+/// SNMPv3 privacy protocol
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PrivProtocol {
+    Des,
+    #[default]
+    Aes,
+}
+
+/// SNMPv3 user settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnmpV3Config {
+    /// Security name of the user
+    pub user: String,
+    /// Authentication password, requests are not authenticated without it
+    pub auth_password: Option<String>,
+    /// Privacy password, requests are not encrypted without it
+    pub priv_password: Option<String>,
+    #[serde(default)]
+    pub auth_protocol: AuthProtocol,
+    #[serde(default)]
+    pub priv_protocol: PrivProtocol,
+}
+
+/// OID to read
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OidConfig {
+    /// Name written to the `oid_name` column
+    pub name: String,
+    /// Numeric OID such as `1.3.6.1.2.1.1.3.0`
+    pub oid: String,
+}
+
+/// SNMP input configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnmpInputConfig {
+    /// Address of the agent, e.g. `192.168.1.1:161`
+    pub agent_address: String,
+    /// Community string of SNMPv2c
+    #[serde(default = "default_community")]
+    pub community: String,
+    #[serde(default)]
+    pub version: SnmpVersion,
+    /// User settings, required by SNMPv3
+    pub v3_config: Option<SnmpV3Config>,
+    /// OIDs read on every poll
+    pub oids: Vec<OidConfig>,
+    /// Interval between two polls
+    #[serde(default = "default_poll_interval_secs")]
+    pub poll_interval_secs: u64,
+}
+
+fn default_community() -> String {
+    "public".to_string()
+}
+
+fn default_poll_interval_secs() -> u64 {
+    10
+}
+
+/// Value of one variable binding in a response
+#[derive(Debug, Clone, PartialEq)]
+struct VarBind {
+    oid: String,
+    value: Option<String>,
+    value_type: &'static str,
+}
+
+/// Master keys derived from the SNMPv3 passwords
+struct UsmKeys {
+    user: String,
+    auth: Option<(AuthProtocol, Vec<u8>)>,
+    privacy: Option<(PrivProtocol, Vec<u8>)>,
+}
+
+/// Authoritative engine of the agent, learned by discovery
+struct Engine {
+    id: Vec<u8>,
+    boots: i64,
+    time: i64,
+    discovered: Instant,
+    auth_key: Vec<u8>,
+    priv_key: Vec<u8>,
+}
+
+impl Engine {
+    /// Estimated engine time of the agent
+    fn time(&self) -> i64 {
+        self.time + self.discovered.elapsed().as_secs() as i64
+    }
+}
+
+/// SNMP input component
+pub struct SnmpInput {
+    input_name: Option<String>,
+    config: SnmpInputConfig,
+    oids: Vec<Vec<u32>>,
+    /// Configured name of each OID in dotted notation
+    oid_names: HashMap<String, String>,
+    usm: Option<UsmKeys>,
+    socket: Mutex<Option<UdpSocket>>,
+    engine: Mutex<Option<Engine>>,
+    next_poll: Mutex<Instant>,
+    request_id: AtomicI32,
+    salt: AtomicU64,
+}
+
+impl SnmpInput {
+    /// Create a new SNMP input component
+    pub fn new(name: Option<&String>, config: SnmpInputConfig) -> Result<Self, Error> {
+        if config.oids.is_empty() {
+            return Err(Error::Config(
+                "SNMP input requires at least one OID".to_string(),
+            ));
+        }
+        if config.poll_interval_secs == 0 {
+            return Err(Error::Config(
+                "poll_interval_secs must be greater than 0".to_string(),
+            ));
+        }
+        let oids = config
+            .oids
+            .iter()
+            .map(|o| parse_oid(&o.oid))
+            .collect::<Result<Vec<_>, _>>()?;
+        let oid_names = oids
+            .iter()
+            .zip(&config.oids)
+            .map(|(oid, o)| (format_oid(oid), o.name.clone()))
+            .collect();
+
+        let usm = match (config.version, &config.v3_config) {
+            (SnmpVersion::V2c, _) => None,
+            (SnmpVersion::V3, None) => {
+                return Err(Error::Config("SNMPv3 requires v3_config".to_string()));
+            }
+            (SnmpVersion::V3, Some(v3)) => Some(UsmKeys::new(v3)?),
+        };
+
+        // Different start values keep restarted inputs from reusing request IDs and salts
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        Ok(Self {
+            input_name: name.cloned(),
+            config,
+            oids,
+            oid_names,
+            usm,
+            socket: Mutex::new(None),
+            engine: Mutex::new(None),
+            next_poll: Mutex::new(Instant::now()),
+            request_id: AtomicI32::new((seed & 0x3fff_ffff) as i32),
+            salt: AtomicU64::new(seed),
+        })
+    }
+
+    fn next_request_id(&self) -> i64 {
+        (self.request_id.fetch_add(1, Ordering::Relaxed) & 0x7fff_ffff) as i64
+    }
+
+    /// Send a request and wait for the response carrying the same ID
+    async fn exchange(
+        &self,
+        socket: &UdpSocket,
+        request: &[u8],
+        id: i64,
+        response_id: fn(&[u8]) -> Result<i64, Error>,
+    ) -> Result<Vec<u8>, Error> {
+        socket
+            .send(request)
+            .await
+            .map_err(|e| Error::Read(format!("Failed to send SNMP request: {}", e)))?;
+
+        let deadline = Instant::now() + REQUEST_TIMEOUT;
+        let mut buf = vec![0u8; MAX_MESSAGE_SIZE];
+        loop {
+            let len = tokio::time::timeout_at(deadline, socket.recv(&mut buf))
+                .await
+                .map_err(|_| Error::Read("SNMP request timed out".to_string()))?
+                .map_err(|e| Error::Read(format!("Failed to receive SNMP response: {}", e)))?;
+            // Late responses to earlier requests are dropped
+            if response_id(&buf[..len]).is_ok_and(|response_id| response_id == id) {
+                buf.truncate(len);
+                return Ok(buf);
+            }
+        }
+    }
+
+    async fn get_v2c(&self, socket: &UdpSocket) -> Result<Vec<VarBind>, Error> {
+        let request_id = self.next_request_id();
+        let request = sequence(&[
+            integer(VERSION_V2C),
+            octet_string(self.config.community.as_bytes()),
+            get_request(request_id, &self.oids),
+        ]);
+        let response = self
+            .exchange(socket, &request, request_id, v2c_request_id)
+            .await?;
+
+        let mut message = BerReader::new(&response).sequence()?;
+        message.integer()?;
+        message.octet_string()?;
+        let (tag, pdu) = message.read()?;
+        parse_response_pdu(tag, pdu)
+    }
+
+    async fn get_v3(&self, socket: &UdpSocket, usm: &UsmKeys) -> Result<Vec<VarBind>, Error> {
+        let mut engine_lock = self.engine.lock().await;
+        // A report usually means the engine rebooted or its clock drifted, so the engine is
+        // discovered again and the request retried once
+        let mut retried = false;
+        loop {
+            let engine = match engine_lock.as_ref() {
+                Some(engine) => engine,
+                None => engine_lock.insert(self.discover(socket, usm).await?),
+            };
+            let (tag, pdu) = self.request_v3(socket, usm, engine).await?;
+            if tag != PDU_REPORT {
+                return parse_response_pdu(tag, &pdu);
+            }
+            engine_lock.take();
+            if retried {
+                return Err(report_error(&pdu));
+            }
+            retried = true;
+        }
+    }
+
+    /// Learn the engine ID, boots and time of the agent from the report to an empty request
+    async fn discover(&self, socket: &UdpSocket, usm: &UsmKeys) -> Result<Engine, Error> {
+        let msg_id = self.next_request_id();
+        let scoped_pdu = sequence(&[
+            octet_string(&[]),
+            octet_string(&[]),
+            get_request(self.next_request_id(), &[]),
+        ]);
+        let request = v3_message(msg_id, FLAG_REPORTABLE, &UsmParams::default(), scoped_pdu);
+        let response = self
+            .exchange(socket, &request, msg_id, v3_message_id)
+            .await?;
+
+        let message = V3Message::parse(&response)?;
+        if message.engine_id.is_empty() {
+            return Err(Error::Read(
+                "SNMP agent did not report its engine ID".to_string(),
+            ));
+        }
+        Ok(usm.localize(message.engine_id, message.boots, message.time))
+    }
+
+    /// Send an authenticated and encrypted GET and return the decoded response PDU
+    async fn request_v3(
+        &self,
+        socket: &UdpSocket,
+        usm: &UsmKeys,
+        engine: &Engine,
+    ) -> Result<(u8, Vec<u8>), Error> {
+        let msg_id = self.next_request_id();
+        let scoped_pdu = sequence(&[
+            octet_string(&engine.id),
+            octet_string(&[]),
+            get_request(self.next_request_id(), &self.oids),
+        ]);
+        let engine_time = engine.time();
+
+        let mut flags = FLAG_REPORTABLE;
+        let mut priv_params = Vec::new();
+        let data = match &usm.privacy {
+            Some((protocol, _)) => {
+                flags |= FLAG_PRIV;
+                let salt = self.salt.fetch_add(1, Ordering::Relaxed);
+                let (encrypted, params) =
+                    encrypt(*protocol, engine, engine_time, salt, scoped_pdu)?;
+                priv_params = params;
+                octet_string(&encrypted)
+            }
+            None => scoped_pdu,
+        };
+        if usm.auth.is_some() {
+            flags |= FLAG_AUTH;
+        }
+
+        let params = UsmParams {
+            engine_id: &engine.id,
+            boots: engine.boots,
+            time: engine_time,
+            user: usm.user.as_bytes(),
+            auth_params: if usm.auth.is_some() {
+                &[0; AUTH_PARAMS_LEN]
+            } else {
+                &[]
+            },
+            priv_params: &priv_params,
+        };
+        let mut request = v3_message(msg_id, flags, &params, data);
+        if let Some((protocol, _)) = &usm.auth {
+            let range = V3Message::parse(&request)?.auth_params_range(&request);
+            let mac = hmac_96(*protocol, &engine.auth_key, &request)?;
+            request[range].copy_from_slice(&mac);
+        }
+
+        let response = self
+            .exchange(socket, &request, msg_id, v3_message_id)
+            .await?;
+        let message = V3Message::parse(&response)?;
+
+        // Reports to unauthenticated problems, such as an unknown user, come back in plain text
+        if message.flags & FLAG_AUTH != 0 {
+            let Some((protocol, _)) = &usm.auth else {
+                return Err(Error::Read(
+                    "Unexpected authenticated SNMP response".to_string(),
+                ));
+            };
+            let range = message.auth_params_range(&response);
+            let mut unsigned = response.clone();
+            unsigned[range.clone()].fill(0);
+            if hmac_96(*protocol, &engine.auth_key, &unsigned)? != response[range] {
+                return Err(Error::Read(
+                    "SNMP response failed authentication".to_string(),
+                ));
+            }
+        }
+
+        let scoped_pdu = if message.flags & FLAG_PRIV != 0 {
+            let Some((protocol, _)) = &usm.privacy else {
+                return Err(Error::Read(
+                    "Unexpected encrypted SNMP response".to_string(),
+                ));
+            };
+            let encrypted = BerReader::new(message.data).octet_string()?;
+            decrypt(
+                *protocol,
+                engine,
+                message.boots,
+                message.time,
+                message.priv_params,
+                encrypted,
+            )?
+        } else {
+            message.data.to_vec()
+        };
+
+        let mut scoped_pdu = BerReader::new(&scoped_pdu).sequence()?;
+        scoped_pdu.octet_string()?;
+        scoped_pdu.octet_string()?;
+        let (tag, pdu) = scoped_pdu.read()?;
+        Ok((tag, pdu.to_vec()))
+    }
+}
+
+impl UsmKeys {
+    fn new(config: &SnmpV3Config) -> Result<Self, Error> {
+        let password_key = |password: &String| {
+            // RFC 3414 requires passwords of at least 8 characters
+            if password.len() < 8 {
+                return Err(Error::Config(
+                    "SNMPv3 passwords must have at least 8 characters".to_string(),
+                ));
+            }
+            Ok(password_to_key(config.auth_protocol, password.as_bytes()))
+        };
+
+        let auth = match &config.auth_password {
+            Some(password) => Some((config.auth_protocol, password_key(password)?)),
+            None => None,
+        };
+        let privacy = match &config.priv_password {
+            Some(_) if auth.is_none() => {
+                return Err(Error::Config(
+                    "SNMPv3 privacy requires an auth_password".to_string(),
+                ));
+            }
+            Some(password) => Some((config.priv_protocol, password_key(password)?)),
+            None => None,
+        };
+        Ok(Self {
+            user: config.user.clone(),
+            auth,
+            privacy,
+        })
+    }
+
+    /// Localize the master keys to the engine of the agent
+    fn localize(&self, engine_id: &[u8], boots: i64, time: i64) -> Engine {
+        // The privacy key is localized with the hash of the authentication protocol too
+        let localize = |key: Option<&Vec<u8>>| match (&self.auth, key) {
+            (Some((protocol, _)), Some(key)) => localize_key(*protocol, key, engine_id),
+            _ => Vec::new(),
+        };
+        Engine {
+            id: engine_id.to_vec(),
+            boots,
+            time,
+            discovered: Instant::now(),
+            auth_key: localize(self.auth.as_ref().map(|(_, key)| key)),
+            priv_key: localize(self.privacy.as_ref().map(|(_, key)| key)),
+        }
+    }
+}
+
+/// Turn a password into a master key by hashing a megabyte of its repetitions (RFC 3414 A.2)
+fn password_to_key(protocol: AuthProtocol, password: &[u8]) -> Vec<u8> {
+    fn hash<D: Digest>(password: &[u8]) -> Vec<u8> {
+        let mut hasher = D::new();
+        let mut chunk = [0u8; 64];
+        let mut index = 0;
+        for _ in 0..(1_048_576 / chunk.len()) {
+            for byte in chunk.iter_mut() {
+                *byte = password[index % password.len()];
+                index += 1;
+            }
+            hasher.update(chunk);
+        }
+        hasher.finalize().to_vec()
+    }
+
+    match protocol {
+        AuthProtocol::Md5 => hash::<Md5>(password),
+        AuthProtocol::Sha => hash::<Sha1>(password),
+    }
+}
+
+fn localize_key(protocol: AuthProtocol, key: &[u8], engine_id: &[u8]) -> Vec<u8> {
+    fn hash<D: Digest>(key: &[u8], engine_id: &[u8]) -> Vec<u8> {
+        D::new()
+            .chain_update(key)
+            .chain_update(engine_id)
+            .chain_update(key)
+            .finalize()
+            .to_vec()
+    }
+
+    match protocol {
+        AuthProtocol::Md5 => hash::<Md5>(key, engine_id),
+        AuthProtocol::Sha => hash::<Sha1>(key, engine_id),
+    }
+}
+
+fn hmac_96(protocol: AuthProtocol, key: &[u8], message: &[u8]) -> Result<Vec<u8>, Error> {
+    let invalid = |_| Error::Process("Invalid SNMPv3 authentication key".to_string());
+    let mac = match protocol {
+        AuthProtocol::Md5 => {
+            let mut mac = Hmac::<Md5>::new_from_slice(key).map_err(invalid)?;
+            mac.update(message);
+            mac.finalize().into_bytes().to_vec()
+        }
+        AuthProtocol::Sha => {
+            let mut mac = Hmac::<Sha1>::new_from_slice(key).map_err(invalid)?;
+            mac.update(message);
+            mac.finalize().into_bytes().to_vec()
+        }
+    };
+    Ok(mac[..AUTH_PARAMS_LEN].to_vec())
+}
+
+/// Encrypt a scoped PDU, returning the ciphertext and the privacy parameters (the salt)
+fn encrypt(
+    protocol: PrivProtocol,
+    engine: &Engine,
+    engine_time: i64,
+    salt: u64,
+    mut data: Vec<u8>,
+) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    let invalid = |_| Error::Process("Invalid SNMPv3 privacy key".to_string());
+    match protocol {
+        // RFC 3414 8.1.1: the salt is the engine boots followed by a local counter
+        PrivProtocol::Des => {
+            let mut params = (engine.boots as u32).to_be_bytes().to_vec();
+            params.extend_from_slice(&(salt as u32).to_be_bytes());
+            let (key, iv) = des_key_iv(&engine.priv_key, &params)?;
+
+            // The receiver ignores the padding after the scoped PDU
+            let padded_len = data.len().div_ceil(8) * 8;
+            data.resize(padded_len, 0);
+            cbc::Encryptor::<des::Des>::new_from_slices(key, &iv)
+                .map_err(invalid)?
+                .encrypt_padded_mut::<NoPadding>(&mut data, padded_len)
+                .map_err(|_| Error::Process("Failed to encrypt SNMPv3 PDU".to_string()))?;
+            Ok((data, params))
+        }
+        // RFC 3826 3.1.2: the IV is the engine boots and time followed by the salt
+        PrivProtocol::Aes => {
+            let params = salt.to_be_bytes().to_vec();
+            let iv = aes_iv(engine.boots, engine_time, &params);
+            cfb_mode::Encryptor::<aes::Aes128>::new_from_slices(aes_key(&engine.priv_key)?, &iv)
+                .map_err(invalid)?
+                .encrypt(&mut data);
+            Ok((data, params))
+        }
+    }
+}
+
+fn decrypt(
+    protocol: PrivProtocol,
+    engine: &Engine,
+    boots: i64,
+    time: i64,
+    params: &[u8],
+    encrypted: &[u8],
+) -> Result<Vec<u8>, Error> {
+    fn invalid<E>(_: E) -> Error {
+        Error::Read("Failed to decrypt SNMP response".to_string())
+    }
+    if params.len() != 8 {
+        return Err(Error::Read(
+            "Invalid privacy parameters in SNMP response".to_string(),
+        ));
+    }
+    let mut data = encrypted.to_vec();
+    match protocol {
+        PrivProtocol::Des => {
+            let (key, iv) = des_key_iv(&engine.priv_key, params)?;
+            let len = cbc::Decryptor::<des::Des>::new_from_slices(key, &iv)
+                .map_err(invalid)?
+                .decrypt_padded_mut::<NoPadding>(&mut data)
+                .map_err(invalid)?
+                .len();
+            data.truncate(len);
+        }
+        PrivProtocol::Aes => {
+            let iv = aes_iv(boots, time, params);
+            cfb_mode::Decryptor::<aes::Aes128>::new_from_slices(aes_key(&engine.priv_key)?, &iv)
+                .map_err(invalid)?
+                .decrypt(&mut data);
+        }
+    }
+    Ok(data)
+}
+
+/// DES key and IV, the IV being the pre-IV XOR the salt
+fn des_key_iv<'a>(priv_key: &'a [u8], salt: &[u8]) -> Result<(&'a [u8], Vec<u8>), Error> {
+    if priv_key.len() < 16 {
+        return Err(Error::Process("Invalid SNMPv3 privacy key".to_string()));
+    }
+    let iv = priv_key[8..16]
+        .iter()
+        .zip(salt)
+        .map(|(pre_iv, salt)| pre_iv ^ salt)
+        .collect();
+    Ok((&priv_key[..8], iv))
+}
+
+fn aes_key(priv_key: &[u8]) -> Result<&[u8], Error> {
+    priv_key
+        .get(..16)
+        .ok_or_else(|| Error::Process("Invalid SNMPv3 privacy key".to_string()))
+}
+
+fn aes_iv(boots: i64, time: i64, salt: &[u8]) -> Vec<u8> {
+    let mut iv = (boots as u32).to_be_bytes().to_vec();
+    iv.extend_from_slice(&(time as u32).to_be_bytes());
+    iv.extend_from_slice(salt);
+    iv
+}
+
+/// Security parameters of an SNMPv3 message
+#[derive(Default)]
+struct UsmParams<'a> {
+    engine_id: &'a [u8],
+    boots: i64,
+    time: i64,
+    user: &'a [u8],
+    auth_params: &'a [u8],
+    priv_params: &'a [u8],
+}
+
+fn v3_message(msg_id: i64, flags: u8, params: &UsmParams, data: Vec<u8>) -> Vec<u8> {
+    let global_data = sequence(&[
+        integer(msg_id),
+        integer(MAX_MESSAGE_SIZE as i64),
+        octet_string(&[flags]),
+        integer(SECURITY_MODEL_USM),
+    ]);
+    let security_params = sequence(&[
+        octet_string(params.engine_id),
+        integer(params.boots),
+        integer(params.time),
+        octet_string(params.user),
+        octet_string(params.auth_params),
+        octet_string(params.priv_params),
+    ]);
+    sequence(&[
+        integer(VERSION_V3),
+        global_data,
+        octet_string(&security_params),
+        data,
+    ])
+}
+
+/// Decoded header of an SNMPv3 message, borrowing from the raw message
+struct V3Message<'a> {
+    msg_id: i64,
+    flags: u8,
+    engine_id: &'a [u8],
+    boots: i64,
+    time: i64,
+    auth_params: &'a [u8],
+    priv_params: &'a [u8],
+    /// Scoped PDU, or an octet string holding it encrypted
+    data: &'a [u8],
+}
+
+impl<'a> V3Message<'a> {
+    fn parse(raw: &'a [u8]) -> Result<Self, Error> {
+        let mut message = BerReader::new(raw).sequence()?;
+        if message.integer()? != VERSION_V3 {
+            return Err(Error::Read("Unexpected SNMP version".to_string()));
+        }
+        let mut global_data = message.sequence()?;
+        let msg_id = global_data.integer()?;
+        global_data.integer()?;
+        let flags = global_data.octet_string()?.first().copied().unwrap_or(0);
+
+        let mut params = BerReader::new(message.octet_string()?).sequence()?;
+        let engine_id = params.octet_string()?;
+        let boots = params.integer()?;
+        let time = params.integer()?;
+        params.octet_string()?;
+        let auth_params = params.octet_string()?;
+        let priv_params = params.octet_string()?;
+        Ok(Self {
+            msg_id,
+            flags,
+            engine_id,
+            boots,
+            time,
+            auth_params,
+            priv_params,
+            data: message.remaining(),
+        })
+    }
+
+    /// Position of the authentication parameters in the raw message
+    fn auth_params_range(&self, raw: &[u8]) -> Range<usize> {
+        let start = self.auth_params.as_ptr() as usize - raw.as_ptr() as usize;
+        start..start + self.auth_params.len()
+    }
+}
+
+fn v2c_request_id(raw: &[u8]) -> Result<i64, Error> {
+    let mut message = BerReader::new(raw).sequence()?;
+    message.integer()?;
+    message.octet_string()?;
+    let (_, pdu) = message.read()?;
+    BerReader::new(pdu).integer()
+}
+
+fn v3_message_id(raw: &[u8]) -> Result<i64, Error> {
+    V3Message::parse(raw).map(|message| message.msg_id)
+}
+
+/// Build a GET request PDU for the OIDs
+fn get_request(request_id: i64, oids: &[Vec<u32>]) -> Vec<u8> {
+    let var_binds: Vec<Vec<u8>> = oids
+        .iter()
+        .map(|oid| sequence(&[tlv(TAG_OID, &encode_oid(oid)), tlv(TAG_NULL, &[])]))
+        .collect();
+    tlv(
+        PDU_GET_REQUEST,
+        &[
+            integer(request_id),
+            integer(0),
+            integer(0),
+            sequence(&var_binds),
+        ]
+        .concat(),
+    )
+}
+
+fn parse_response_pdu(tag: u8, pdu: &[u8]) -> Result<Vec<VarBind>, Error> {
+    if tag != PDU_RESPONSE {
+        return Err(Error::Read(format!("Unexpected SNMP PDU type {:#x}", tag)));
+    }
+    let mut pdu = BerReader::new(pdu);
+    pdu.integer()?;
+    let error_status = pdu.integer()?;
+    let error_index = pdu.integer()?;
+    if error_status != 0 {
+        return Err(Error::Read(format!(
+            "SNMP agent returned error status {} for variable {}",
+            error_status, error_index
+        )));
+    }
+
+    let mut var_binds = pdu.sequence()?;
+    let mut values = Vec::new();
+    while !var_binds.is_empty() {
+        let mut var_bind = var_binds.sequence()?;
+        let oid = decode_oid(var_bind.expect(TAG_OID)?)?;
+        let (tag, content) = var_bind.read()?;
+        let (value, value_type) = decode_value(tag, content)?;
+        values.push(VarBind {
+            oid,
+            value,
+            value_type,
+        });
+    }
+    Ok(values)
+}
+
+fn report_error(pdu: &[u8]) -> Error {
+    let oid = parse_response_pdu(PDU_RESPONSE, pdu)
+        .ok()
+        .and_then(|values| values.into_iter().next())
+        .map(|value| value.oid)
+        .unwrap_or_default();
+    let reason = USM_REPORTS
+        .iter()
+        .find(|(report_oid, _)| *report_oid == oid)
+        .map(|(_, reason)| reason.to_string())
+        .unwrap_or(oid);
+    Error::Read(format!("SNMP agent rejected the request: {}", reason))
+}
+
+/// Convert a value to text and name its type
+fn decode_value(tag: u8, content: &[u8]) -> Result<(Option<String>, &'static str), Error> {
+    Ok(match tag {
+        TAG_INTEGER => (Some(decode_integer(content)?.to_string()), "integer"),
+        TAG_OCTET_STRING => {
+            // Printable strings are kept as text, anything else such as MAC addresses as hex
+            let text = match std::str::from_utf8(content) {
+                Ok(s) if !s.chars().any(|c| c.is_control() && !c.is_whitespace()) => s.to_string(),
+                _ => hex::encode(content),
+            };
+            (Some(text), "octet_string")
+        }
+        TAG_NULL => (None, "null"),
+        TAG_OID => (Some(decode_oid(content)?), "oid"),
+        TAG_IP_ADDRESS => {
+            let octets: [u8; 4] = content
+                .try_into()
+                .map_err(|_| Error::Read("Invalid IP address in SNMP response".to_string()))?;
+            (Some(Ipv4Addr::from(octets).to_string()), "ip_address")
+        }
+        TAG_COUNTER32 => (Some(decode_unsigned(content)?.to_string()), "counter32"),
+        TAG_GAUGE32 => (Some(decode_unsigned(content)?.to_string()), "gauge32"),
+        TAG_TIMETICKS => (Some(decode_unsigned(content)?.to_string()), "timeticks"),
+        TAG_OPAQUE => (Some(hex::encode(content)), "opaque"),
+        TAG_COUNTER64 => (Some(decode_unsigned(content)?.to_string()), "counter64"),
+        TAG_NO_SUCH_OBJECT => (None, "no_such_object"),
+        TAG_NO_SUCH_INSTANCE => (None, "no_such_instance"),
+        TAG_END_OF_MIB_VIEW => (None, "end_of_mib_view"),
+        _ => (Some(hex::encode(content)), "unknown"),
+    })
+}
+
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(content.len() + 6);
+    out.push(tag);
+    if content.len() < 0x80 {
+        out.push(content.len() as u8);
+    } else {
+        let len = (content.len() as u32).to_be_bytes();
+        let skip = len.iter().take_while(|b| **b == 0).count();
+        out.push(0x80 | (len.len() - skip) as u8);
+        out.extend_from_slice(&len[skip..]);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+fn sequence(parts: &[Vec<u8>]) -> Vec<u8> {
+    tlv(TAG_SEQUENCE, &parts.concat())
+}
+
+fn octet_string(value: &[u8]) -> Vec<u8> {
+    tlv(TAG_OCTET_STRING, value)
+}
+
+/// Encode an integer in the fewest two's complement bytes
+fn integer(value: i64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let mut start = 0;
+    while start < bytes.len() - 1
+        && ((bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0)
+            || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0))
+    {
+        start += 1;
+    }
+    tlv(TAG_INTEGER, &bytes[start..])
+}
+
+fn decode_integer(content: &[u8]) -> Result<i64, Error> {
+    if content.is_empty() || content.len() > 8 {
+        return Err(Error::Read("Invalid integer in SNMP message".to_string()));
+    }
+    let sign = if content[0] & 0x80 != 0 { -1i64 } else { 0 };
+    Ok(content
+        .iter()
+        .fold(sign, |acc, byte| (acc << 8) | *byte as i64))
+}
+
+/// Decode the unsigned counters, gauges and time ticks, which may carry a leading zero byte
+fn decode_unsigned(content: &[u8]) -> Result<u64, Error> {
+    let content = match content {
+        [0, rest @ ..] => rest,
+        content => content,
+    };
+    if content.len() > 8 {
+        return Err(Error::Read("Invalid counter in SNMP message".to_string()));
+    }
+    Ok(content
+        .iter()
+        .fold(0u64, |acc, byte| (acc << 8) | *byte as u64))
+}
+
+fn parse_oid(oid: &str) -> Result<Vec<u32>, Error> {
+    let invalid = || Error::Config(format!("Invalid OID: {}", oid));
+    let arcs = oid
+        .trim_start_matches('.')
+        .split('.')
+        .map(|arc| arc.parse::<u32>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+    if arcs.len() < 2 || arcs[0] > 2 || (arcs[0] < 2 && arcs[1] >= 40) {
+        return Err(invalid());
+    }
+    Ok(arcs)
+}
+
+fn format_oid<T: ToString>(arcs: &[T]) -> String {
+    arcs.iter()
+        .map(|arc| arc.to_string())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn encode_oid(oid: &[u32]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let first = oid[0] as u64 * 40 + oid[1] as u64;
+    for arc in std::iter::once(first).chain(oid[2..].iter().map(|arc| *arc as u64)) {
+        let mut bytes = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            bytes.push((rest & 0x7f) as u8 | 0x80);
+            rest >>= 7;
+        }
+        out.extend(bytes.iter().rev());
+    }
+    out
+}
+
+fn decode_oid(content: &[u8]) -> Result<String, Error> {
+    let mut arcs = Vec::new();
+    let mut arc = 0u64;
+    for byte in content {
+        if arc > u64::MAX >> 7 {
+            return Err(Error::Read("Invalid OID in SNMP message".to_string()));
+        }
+        arc = (arc << 7) | (byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            if arcs.is_empty() {
+                let first = (arc / 40).min(2);
+                arcs.push(first);
+                arcs.push(arc - first * 40);
+            } else {
+                arcs.push(arc);
+            }
+            arc = 0;
+        }
+    }
+    if arcs.is_empty() {
+        return Err(Error::Read("Invalid OID in SNMP message".to_string()));
+    }
+    Ok(format_oid(&arcs))
+}
+
+/// Reader of consecutive BER encoded values
+struct BerReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> BerReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn remaining(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Read the next value, returning its tag and content
+    fn read(&mut self) -> Result<(u8, &'a [u8]), Error> {
+        let truncated = || Error::Read("Truncated SNMP message".to_string());
+        let [tag, first, ..] = *self.data else {
+            return Err(truncated());
+        };
+        let (len, header) = if first < 0x80 {
+            (first as usize, 2)
+        } else {
+            let count = (first & 0x7f) as usize;
+            if count == 0 || count > 4 {
+                return Err(Error::Read("Invalid length in SNMP message".to_string()));
+            }
+            let bytes = self.data.get(2..2 + count).ok_or_else(truncated)?;
+            let len = bytes
+                .iter()
+                .fold(0usize, |acc, byte| (acc << 8) | *byte as usize);
+            (len, 2 + count)
+        };
+        let content = self.data.get(header..header + len).ok_or_else(truncated)?;
+        self.data = &self.data[header + len..];
+        Ok((tag, content))
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<&'a [u8], Error> {
+        let (tag, content) = self.read()?;
+        if tag != expected {
+            return Err(Error::Read(format!(
+                "Expected tag {:#x} in SNMP message, found {:#x}",
+                expected, tag
+            )));
+        }
+        Ok(content)
+    }
+
+    fn sequence(&mut self) -> Result<BerReader<'a>, Error> {
+        self.expect(TAG_SEQUENCE).map(BerReader::new)
+    }
+
+    fn integer(&mut self) -> Result<i64, Error> {
+        self.expect(TAG_INTEGER).and_then(decode_integer)
+    }
+
+    fn octet_string(&mut self) -> Result<&'a [u8], Error> {
+        self.expect(TAG_OCTET_STRING)
+    }
+}
+
+/// Build the batch, one row per variable, named after the configured OID it answers
+fn var_binds_batch(
+    oid_names: &HashMap<String, String>,
+    values: &[VarBind],
+    timestamp_ms: i64,
+) -> Result<RecordBatch, Error> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("oid_name", DataType::Utf8, false),
+        Field::new("value", DataType::Utf8, true),
+        Field::new("type", DataType::Utf8, false),
+        Field::new("timestamp_ms", DataType::Int64, false),
+    ]));
+    let names = values
+        .iter()
+        .map(|v| oid_names.get(&v.oid).unwrap_or(&v.oid).as_str());
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(names)),
+        Arc::new(StringArray::from_iter(
+            values.iter().map(|v| v.value.as_deref()),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            values.iter().map(|v| v.value_type),
+        )),
+        Arc::new(Int64Array::from_iter_values(
+            values.iter().map(|_| timestamp_ms),
+        )),
+    ];
+    RecordBatch::try_new(schema, columns)
+        .map_err(|e| Error::Process(format!("Unable to build the SNMP batch: {}", e)))
+}
+
+#[async_trait]
+impl Input for SnmpInput {
+    async fn connect(&self) -> Result<(), Error> {
+        let address = tokio::net::lookup_host(&self.config.agent_address)
+            .await
+            .ok()
+            .and_then(|mut addresses| addresses.next())
+            .ok_or_else(|| {
+                Error::Connection(format!(
+                    "Failed to resolve SNMP agent {}",
+                    self.config.agent_address
+                ))
+            })?;
+        let local: SocketAddr = match address {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(local)
+            .await
+            .map_err(|e| Error::Connection(format!("Failed to bind UDP socket: {}", e)))?;
+        socket
+            .connect(address)
+            .await
+            .map_err(|e| Error::Connection(format!("Failed to connect to {}: {}", address, e)))?;
+
+        *self.socket.lock().await = Some(socket);
+        self.engine.lock().await.take();
+        *self.next_poll.lock().await = Instant::now();
+        Ok(())
+    }
+
+    async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+        let socket_lock = self.socket.lock().await;
+        let Some(socket) = socket_lock.as_ref() else {
+            return Err(Error::Connection("The input is not connected".to_string()));
+        };
+
+        {
+            let mut next_poll = self.next_poll.lock().await;
+            tokio::time::sleep_until(*next_poll).await;
+            *next_poll = Instant::now() + Duration::from_secs(self.config.poll_interval_secs);
+        }
+
+        let values = match &self.usm {
+            Some(usm) => self.get_v3(socket, usm).await?,
+            None => self.get_v2c(socket).await?,
+        };
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or_default();
+        let mut msg =
+            MessageBatch::new_arrow(var_binds_batch(&self.oid_names, &values, timestamp_ms)?);
+        msg.set_input_name(self.input_name.clone());
+        Ok((msg, Arc::new(NoopAck)))
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        self.socket.lock().await.take();
+        Ok(())
+    }
+}
+
+pub(crate) struct SnmpInputBuilder;
+impl InputBuilder for SnmpInputBuilder {
+    fn build(
+        &self,
+        name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Input>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "SNMP input configuration is missing".to_string(),
+            ));
+        }
+        let config: SnmpInputConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(SnmpInput::new(name, config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_input_builder("snmp", Arc::new(SnmpInputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYS_UPTIME: &str = "1.3.6.1.2.1.1.3.0";
+    const SYS_NAME: &str = "1.3.6.1.2.1.1.5.0";
+
+    fn config(agent_address: String, v3_config: Option<SnmpV3Config>) -> SnmpInputConfig {
+        let oid = |name: &str, oid: &str| OidConfig {
+            name: name.to_string(),
+            oid: oid.to_string(),
+        };
+        SnmpInputConfig {
+            agent_address,
+            community: "public".to_string(),
+            version: if v3_config.is_some() {
+                SnmpVersion::V3
+            } else {
+                SnmpVersion::V2c
+            },
+            v3_config,
+            oids: vec![
+                oid("uptime", SYS_UPTIME),
+                oid("name", &format!(".{}", SYS_NAME)),
+                oid("missing", "1.3.6.1.2.1.1.99.0"),
+            ],
+            poll_interval_secs: 1,
+        }
+    }
+
+    /// Request ID and OIDs of a GET request PDU
+    fn parse_get(pdu: &[u8]) -> (i64, Vec<String>) {
+        let mut pdu = BerReader::new(pdu);
+        let request_id = pdu.integer().unwrap();
+        pdu.integer().unwrap();
+        pdu.integer().unwrap();
+        let mut var_binds = pdu.sequence().unwrap();
+        let mut oids = Vec::new();
+        while !var_binds.is_empty() {
+            let mut var_bind = var_binds.sequence().unwrap();
+            oids.push(decode_oid(var_bind.expect(TAG_OID).unwrap()).unwrap());
+        }
+        (request_id, oids)
+    }
+
+    fn response_pdu(request_id: i64, oids: &[String]) -> Vec<u8> {
+        let var_binds: Vec<Vec<u8>> = oids
+            .iter()
+            .map(|oid| {
+                let value = match oid.as_str() {
+                    SYS_UPTIME => tlv(TAG_TIMETICKS, &[0x01, 0x00]),
+                    SYS_NAME => octet_string(b"router"),
+                    _ => tlv(TAG_NO_SUCH_INSTANCE, &[]),
+                };
+                sequence(&[tlv(TAG_OID, &encode_oid(&parse_oid(oid).unwrap())), value])
+            })
+            .collect();
+        tlv(
+            PDU_RESPONSE,
+            &[
+                integer(request_id),
+                integer(0),
+                integer(0),
+                sequence(&var_binds),
+            ]
+            .concat(),
+        )
+    }
+
+    async fn read_batch(config: SnmpInputConfig) -> RecordBatch {
+        let input = SnmpInput::new(None, config).unwrap();
+        input.connect().await.unwrap();
+        let (msg, _) = input.read().await.unwrap();
+        input.close().await.unwrap();
+        msg.into()
+    }
+
+    fn assert_batch(batch: &RecordBatch) {
+        let column = |name: &str| {
+            let column = batch.column_by_name(name).unwrap();
+            let column = column.as_any().downcast_ref::<StringArray>().unwrap();
+            column
+                .iter()
+                .map(|v| v.map(str::to_string))
+                .collect::<Vec<_>>()
+        };
+        let strings = |values: &[&str]| {
+            values
+                .iter()
+                .map(|v| Some(v.to_string()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(column("oid_name"), strings(&["uptime", "name", "missing"]));
+        assert_eq!(
+            column("value"),
+            vec![Some("256".to_string()), Some("router".to_string()), None]
+        );
+        assert_eq!(
+            column("type"),
+            strings(&["timeticks", "octet_string", "no_such_instance"])
+        );
+    }
+
+    #[test]
+    fn test_ber_encoding() {
+        assert_eq!(integer(0), vec![TAG_INTEGER, 1, 0]);
+        assert_eq!(integer(128), vec![TAG_INTEGER, 2, 0, 0x80]);
+        assert_eq!(integer(-129), vec![TAG_INTEGER, 2, 0xff, 0x7f]);
+        for value in [0, 1, -1, 127, 128, -128, 65_535, i64::MIN, i64::MAX] {
+            assert_eq!(BerReader::new(&integer(value)).integer().unwrap(), value);
+        }
+        assert_eq!(
+            decode_unsigned(&[0, 0xff, 0xff, 0xff, 0xff]).unwrap(),
+            4_294_967_295
+        );
+
+        let long = octet_string(&[7; 200]);
+        assert_eq!(long[..3], [TAG_OCTET_STRING, 0x81, 200]);
+        assert_eq!(BerReader::new(&long).octet_string().unwrap(), &[7; 200]);
+        assert!(BerReader::new(&long[..100]).octet_string().is_err());
+
+        let oid = parse_oid(".1.3.6.1.4.1.2021.10.1.3.1").unwrap();
+        assert_eq!(
+            decode_oid(&encode_oid(&oid)).unwrap(),
+            "1.3.6.1.4.1.2021.10.1.3.1"
+        );
+        assert!(parse_oid("1.3.x").is_err());
+        assert!(parse_oid("3.1").is_err());
+
+        assert_eq!(
+            decode_value(TAG_IP_ADDRESS, &[10, 0, 0, 1]).unwrap(),
+            (Some("10.0.0.1".to_string()), "ip_address")
+        );
+        assert_eq!(
+            decode_value(TAG_OCTET_STRING, &[0x00, 0x1a, 0x2b]).unwrap(),
+            (Some("001a2b".to_string()), "octet_string")
+        );
+    }
+
+    #[test]
+    fn test_password_to_key() {
+        // Test vectors of RFC 3414 A.3
+        let engine_id = hex::decode("000000000000000000000002").unwrap();
+        let key = password_to_key(AuthProtocol::Md5, b"maplesyrup");
+        assert_eq!(hex::encode(&key), "9faf3283884e92834ebc9847d8edd963");
+        assert_eq!(
+            hex::encode(localize_key(AuthProtocol::Md5, &key, &engine_id)),
+            "526f5eed9fcce26f8964c2930787d82b"
+        );
+        let key = password_to_key(AuthProtocol::Sha, b"maplesyrup");
+        assert_eq!(
+            hex::encode(&key),
+            "9fb5cc0381497b3793528939ff788d5d79145211"
+        );
+        assert_eq!(
+            hex::encode(localize_key(AuthProtocol::Sha, &key, &engine_id)),
+            "6695febc9288e36282235fc7151f128497b38f3f"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_snmp_v2c_get() {
+        let agent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = agent.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = vec![0u8; MAX_MESSAGE_SIZE];
+            loop {
+                let (len, peer) = agent.recv_from(&mut buf).await.unwrap();
+                let mut message = BerReader::new(&buf[..len]).sequence().unwrap();
+                assert_eq!(message.integer().unwrap(), VERSION_V2C);
+                assert_eq!(message.octet_string().unwrap(), b"public");
+                let (tag, pdu) = message.read().unwrap();
+                assert_eq!(tag, PDU_GET_REQUEST);
+                let (request_id, oids) = parse_get(pdu);
+
+                let response = sequence(&[
+                    integer(VERSION_V2C),
+                    octet_string(b"public"),
+                    response_pdu(request_id, &oids),
+                ]);
+                agent.send_to(&response, peer).await.unwrap();
+            }
+        });
+
+        assert_batch(&read_batch(config(address.to_string(), None)).await);
+    }
+
+    /// Answer SNMPv3 requests the way an agent with the given user would
+    async fn run_v3_agent(agent: UdpSocket, v3_config: SnmpV3Config) {
+        let keys = UsmKeys::new(&v3_config).unwrap();
+        let engine = keys.localize(b"\x80\x00\x1f\x88\x04arkflow", 5, 100);
+        let (auth_protocol, _) = keys.auth.unwrap();
+        let (priv_protocol, _) = keys.privacy.unwrap();
+
+        let mut buf = vec![0u8; MAX_MESSAGE_SIZE];
+        loop {
+            let (len, peer) = agent.recv_from(&mut buf).await.unwrap();
+            let request = &buf[..len];
+            let message = V3Message::parse(request).unwrap();
+
+            let mut params = UsmParams {
+                engine_id: &engine.id,
+                boots: engine.boots,
+                time: engine.time,
+                user: v3_config.user.as_bytes(),
+                ..Default::default()
+            };
+            let response = if message.engine_id.is_empty() {
+                let report = tlv(
+                    PDU_REPORT,
+                    &[
+                        integer(0),
+                        integer(0),
+                        integer(0),
+                        sequence(&[sequence(&[
+                            tlv(TAG_OID, &encode_oid(&parse_oid(USM_REPORTS[3].0).unwrap())),
+                            tlv(TAG_COUNTER32, &[1]),
+                        ])]),
+                    ]
+                    .concat(),
+                );
+                let scoped_pdu = sequence(&[octet_string(&engine.id), octet_string(&[]), report]);
+                v3_message(message.msg_id, 0, &params, scoped_pdu)
+            } else {
+                assert_eq!(
+                    message.flags & (FLAG_AUTH | FLAG_PRIV),
+                    FLAG_AUTH | FLAG_PRIV
+                );
+                let range = message.auth_params_range(request);
+                let mut unsigned = request.to_vec();
+                unsigned[range.clone()].fill(0);
+                assert_eq!(
+                    hmac_96(auth_protocol, &engine.auth_key, &unsigned).unwrap(),
+                    request[range]
+                );
+
+                let encrypted = BerReader::new(message.data).octet_string().unwrap();
+                let scoped_pdu = decrypt(
+                    priv_protocol,
+                    &engine,
+                    message.boots,
+                    message.time,
+                    message.priv_params,
+                    encrypted,
+                )
+                .unwrap();
+                let mut scoped_pdu = BerReader::new(&scoped_pdu).sequence().unwrap();
+                assert_eq!(scoped_pdu.octet_string().unwrap(), engine.id);
+                scoped_pdu.octet_string().unwrap();
+                let (_, pdu) = scoped_pdu.read().unwrap();
+                let (request_id, oids) = parse_get(pdu);
+
+                let scoped_pdu = sequence(&[
+                    octet_string(&engine.id),
+                    octet_string(&[]),
+                    response_pdu(request_id, &oids),
+                ]);
+                let (encrypted, priv_params) =
+                    encrypt(priv_protocol, &engine, engine.time, 42, scoped_pdu).unwrap();
+                params.auth_params = &[0; AUTH_PARAMS_LEN];
+                params.priv_params = &priv_params;
+                let mut response = v3_message(
+                    message.msg_id,
+                    FLAG_AUTH | FLAG_PRIV,
+                    &params,
+                    octet_string(&encrypted),
+                );
+                let range = V3Message::parse(&response)
+                    .unwrap()
+                    .auth_params_range(&response);
+                let mac = hmac_96(auth_protocol, &engine.auth_key, &response).unwrap();
+                response[range].copy_from_slice(&mac);
+                response
+            };
+            agent.send_to(&response, peer).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_snmp_v3_auth_priv() {
+        for (auth_protocol, priv_protocol) in [
+            (AuthProtocol::Sha, PrivProtocol::Aes),
+            (AuthProtocol::Md5, PrivProtocol::Des),
+        ] {
+            let v3_config = SnmpV3Config {
+                user: "monitor".to_string(),
+                auth_password: Some("authpassword".to_string()),
+                priv_password: Some("privpassword".to_string()),
+                auth_protocol,
+                priv_protocol,
+            };
+            let agent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let address = agent.local_addr().unwrap();
+            let handle = tokio::spawn(run_v3_agent(agent, v3_config.clone()));
+
+            assert_batch(&read_batch(config(address.to_string(), Some(v3_config))).await);
+            handle.abort();
+        }
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let base = config("127.0.0.1:161".to_string(), None);
+        assert!(SnmpInput::new(None, base.clone()).is_ok());
+
+        let invalid = [
+            SnmpInputConfig {
+                oids: vec![],
+                ..base.clone()
+            },
+            SnmpInputConfig {
+                version: SnmpVersion::V3,
+                ..base.clone()
+            },
+            SnmpInputConfig {
+                oids: vec![OidConfig {
+                    name: "bad".to_string(),
+                    oid: "sysName.0".to_string(),
+                }],
+                ..base.clone()
+            },
+            config(
+                base.agent_address.clone(),
+                Some(SnmpV3Config {
+                    user: "monitor".to_string(),
+                    auth_password: None,
+                    priv_password: Some("privpassword".to_string()),
+                    auth_protocol: AuthProtocol::Sha,
+                    priv_protocol: PrivProtocol::Aes,
+                }),
+            ),
+            config(
+                base.agent_address.clone(),
+                Some(SnmpV3Config {
+                    user: "monitor".to_string(),
+                    auth_password: Some("short".to_string()),
+                    priv_password: None,
+                    auth_protocol: AuthProtocol::Md5,
+                    priv_protocol: PrivProtocol::Des,
+                }),
+            ),
+        ];
+        for config in invalid {
+            assert!(matches!(
+                SnmpInput::new(None, config),
+                Err(Error::Config(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,774 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Google Cloud Spanner input component
+//!
+//! Run a SQL query against a Spanner database through the `executeStreamingSql` REST API,
+//! once or on an interval, and emit the rows as Arrow batches as they are streamed.
+
+use crate::component::google_auth::GoogleAuth;
+use arkflow_core::input::{register_input_builder, Ack, Input, InputBuilder, NoopAck};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use base64::Engine;
+use datafusion::arrow::array::{
+    ArrayRef, BinaryArray, BooleanArray, Float64Array, Int64Array, StringArray,
+};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use datafusion::arrow::record_batch::RecordBatch;
+use flume::{Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+/// Production endpoint of the Spanner REST API
+const DEFAULT_ENDPOINT: &str = "https://spanner.googleapis.com";
+/// OAuth scope required to query Spanner databases
+const SPANNER_SCOPE: &str = "https://www.googleapis.com/auth/spanner.data";
+
+/// Google Cloud Spanner input configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpannerInputConfig {
+    /// Google Cloud project id
+    pub project_id: String,
+    /// Spanner instance id
+    pub instance_id: String,
+    /// Database id
+    pub database_id: String,
+    /// SQL query, parameters are referenced as `@name`
+    pub query: String,
+    /// Values of the query parameters, by name
+    #[serde(default)]
+    pub params: HashMap<String, Value>,
+    /// Run the query again every `poll_interval_secs`. Without it, the query runs once and the
+    /// input ends with its last row.
+    pub poll_interval_secs: Option<u64>,
+    /// Path to a service account key file
+    pub credentials_file: Option<String>,
+    /// Custom API endpoint, e.g. of the Spanner emulator
+    pub endpoint: Option<String>,
+}
+
+enum SpannerMsg {
+    Batch(RecordBatch),
+    Err(Error),
+    /// The query of a one-shot read completed
+    Done,
+}
+
+/// Google Cloud Spanner input component
+pub struct SpannerInput {
+    input_name: Option<String>,
+    config: SpannerInputConfig,
+    params: Value,
+    param_types: Value,
+    sender: Sender<SpannerMsg>,
+    receiver: Receiver<SpannerMsg>,
+    cancellation_token: Mutex<Option<CancellationToken>>,
+}
+
+impl SpannerInput {
+    /// Create a new Google Cloud Spanner input component
+    pub fn new(name: Option<&String>, config: SpannerInputConfig) -> Result<Self, Error> {
+        if config.poll_interval_secs == Some(0) {
+            return Err(Error::Config(
+                "poll_interval_secs must be greater than 0".to_string(),
+            ));
+        }
+        let (params, param_types) = query_params(&config.params)?;
+        let (sender, receiver) = flume::bounded::<SpannerMsg>(16);
+        Ok(Self {
+            input_name: name.cloned(),
+            config,
+            params,
+            param_types,
+            sender,
+            receiver,
+            cancellation_token: Mutex::new(None),
+        })
+    }
+}
+
+#[async_trait]
+impl Input for SpannerInput {
+    async fn connect(&self) -> Result<(), Error> {
+        let client = Arc::new(SpannerClient::new(&self.config)?);
+
+        let token = CancellationToken::new();
+        if let Some(old) = self.cancellation_token.lock().await.replace(token.clone()) {
+            old.cancel();
+        }
+
+        let request = json!({
+            "sql": self.config.query,
+            "params": self.params,
+            "paramTypes": self.param_types,
+        });
+        let poll_interval = self.config.poll_interval_secs.map(Duration::from_secs);
+        let sender = self.sender.clone();
+        tokio::spawn(async move {
+            loop {
+                let result = tokio::select! {
+                    _ = token.cancelled() => break,
+                    result = client.execute(&request, &sender) => result,
+                };
+                if let Err(e) = result {
+                    if sender.send_async(SpannerMsg::Err(e)).await.is_err() {
+                        break;
+                    }
+                }
+                let Some(poll_interval) = poll_interval else {
+                    let _ = sender.send_async(SpannerMsg::Done).await;
+                    break;
+                };
+                tokio::select! {
+                    _ = token.cancelled() => break,
+                    _ = tokio::time::sleep(poll_interval) => {}
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+        let Some(cancellation_token) = self.cancellation_token.lock().await.clone() else {
+            return Err(Error::Disconnection);
+        };
+
+        tokio::select! {
+            result = self.receiver.recv_async() => {
+                match result {
+                    Ok(SpannerMsg::Batch(batch)) => {
+                        let mut msg = MessageBatch::new_arrow(batch);
+                        msg.set_input_name(self.input_name.clone());
+                        Ok((msg, Arc::new(NoopAck)))
+                    }
+                    Ok(SpannerMsg::Err(e)) => Err(e),
+                    Ok(SpannerMsg::Done) | Err(_) => Err(Error::EOF),
+                }
+            }
+            _ = cancellation_token.cancelled() => Err(Error::EOF),
+        }
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        if let Some(token) = self.cancellation_token.lock().await.take() {
+            token.cancel();
+        }
+        Ok(())
+    }
+}
+
+/// Values and types of the query parameters in the representation of the REST API
+fn query_params(params: &HashMap<String, Value>) -> Result<(Value, Value), Error> {
+    let mut values = Map::new();
+    let mut types = Map::new();
+    for (name, value) in params {
+        let (value, code) = match value {
+            Value::Null => (Value::Null, None),
+            Value::Bool(_) => (value.clone(), Some("BOOL")),
+            // 64 bit integers are encoded as strings
+            Value::Number(n) if n.is_i64() || n.is_u64() => {
+                (Value::String(n.to_string()), Some("INT64"))
+            }
+            Value::Number(_) => (value.clone(), Some("FLOAT64")),
+            Value::String(_) => (value.clone(), Some("STRING")),
+            Value::Array(_) | Value::Object(_) => {
+                return Err(Error::Config(format!(
+                    "Unsupported type of Spanner query parameter {}, expected a scalar",
+                    name
+                )))
+            }
+        };
+        if let Some(code) = code {
+            types.insert(name.clone(), json!({ "code": code }));
+        }
+        values.insert(name.clone(), value);
+    }
+    Ok((Value::Object(values), Value::Object(types)))
+}
+
+/// Client for the session and query endpoints of the Spanner REST API
+struct SpannerClient {
+    http: reqwest::Client,
+    endpoint: String,
+    /// `projects/<project>/instances/<instance>/databases/<database>`
+    database: String,
+    auth: GoogleAuth,
+}
+
+#[derive(Debug, Deserialize)]
+struct Session {
+    name: String,
+}
+
+impl SpannerClient {
+    fn new(config: &SpannerInputConfig) -> Result<Self, Error> {
+        let http = reqwest::Client::new();
+        let auth = GoogleAuth::new(
+            http.clone(),
+            SPANNER_SCOPE,
+            config.credentials_file.as_deref(),
+            config.endpoint.is_some(),
+        )?;
+        Ok(Self {
+            http,
+            endpoint: config
+                .endpoint
+                .as_deref()
+                .unwrap_or(DEFAULT_ENDPOINT)
+                .trim_end_matches('/')
+                .to_string(),
+            database: format!(
+                "projects/{}/instances/{}/databases/{}",
+                config.project_id, config.instance_id, config.database_id
+            ),
+            auth,
+        })
+    }
+
+    /// Run a query in a new session, sending the rows to `sender` as they arrive
+    async fn execute(&self, request: &Value, sender: &Sender<SpannerMsg>) -> Result<(), Error> {
+        let url = format!("{}/v1/{}/sessions", self.endpoint, self.database);
+        let session: Session = self
+            .send(self.http.post(url).json(&json!({})))
+            .await?
+            .json()
+            .await
+            .map_err(|e| Error::Connection(format!("Invalid Spanner session response: {}", e)))?;
+
+        let result = self.stream_query(&session.name, request, sender).await;
+
+        let url = format!("{}/v1/{}", self.endpoint, session.name);
+        if let Err(e) = self.send(self.http.delete(url)).await {
+            warn!("Failed to delete Spanner session {}: {}", session.name, e);
+        }
+        result
+    }
+
+    async fn stream_query(
+        &self,
+        session: &str,
+        request: &Value,
+        sender: &Sender<SpannerMsg>,
+    ) -> Result<(), Error> {
+        let url = format!("{}/v1/{}:executeStreamingSql", self.endpoint, session);
+        let mut response = self.send(self.http.post(url).json(request)).await?;
+
+        let mut splitter = JsonArraySplitter::default();
+        let mut decoder = ResultDecoder::default();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| Error::Read(format!("Failed to read Spanner results: {}", e)))?
+        {
+            for object in splitter.push(&chunk) {
+                let set: PartialResultSet = serde_json::from_slice(&object)
+                    .map_err(|e| Error::Read(format!("Invalid Spanner result set: {}", e)))?;
+                if let Some(error) = set.error {
+                    return Err(Error::Read(format!("Spanner query failed: {}", error)));
+                }
+                if let Some(batch) = decoder.push(set)? {
+                    sender
+                        .send_async(SpannerMsg::Batch(batch))
+                        .await
+                        .map_err(|_| Error::EOF)?;
+                }
+            }
+        }
+        decoder.finish()
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response, Error> {
+        let response = self
+            .auth
+            .authorize(request)
+            .await?
+            .send()
+            .await
+            .map_err(|e| Error::Connection(format!("Spanner request failed: {}", e)))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(Error::Read(format!(
+                "Spanner request failed with status {}: {}",
+                status, body
+            )));
+        }
+        Ok(response)
+    }
+}
+
+/// Splits the JSON array streamed by `executeStreamingSql` into its objects
+#[derive(Default)]
+struct JsonArraySplitter {
+    object: Vec<u8>,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl JsonArraySplitter {
+    /// Feed the next bytes of the response, returning the objects completed by them
+    fn push(&mut self, bytes: &[u8]) -> Vec<Vec<u8>> {
+        let mut objects = Vec::new();
+        for &byte in bytes {
+            if self.depth == 0 {
+                // Brackets, commas and whitespace between the objects of the array
+                if byte == b'{' {
+                    self.depth = 1;
+                    self.object.push(byte);
+                }
+                continue;
+            }
+            self.object.push(byte);
+            if self.in_string {
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => self.in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match byte {
+                b'"' => self.in_string = true,
+                b'{' => self.depth += 1,
+                b'}' => {
+                    self.depth -= 1;
+                    if self.depth == 0 {
+                        objects.push(std::mem::take(&mut self.object));
+                    }
+                }
+                _ => {}
+            }
+        }
+        objects
+    }
+}
+
+/// Part of the results of a streaming query
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PartialResultSet {
+    /// Only set on the first part
+    metadata: Option<ResultSetMetadata>,
+    /// Values of the rows, row after row
+    #[serde(default)]
+    values: Vec<Value>,
+    /// Whether the last value is continued by the first value of the next part
+    #[serde(default)]
+    chunked_value: bool,
+    /// Error of a query failing while its results are streamed
+    error: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ResultSetMetadata {
+    row_type: StructType,
+}
+
+#[derive(Debug, Deserialize)]
+struct StructType {
+    #[serde(default)]
+    fields: Vec<StructField>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StructField {
+    #[serde(default)]
+    name: String,
+    #[serde(rename = "type")]
+    field_type: SpannerType,
+}
+
+#[derive(Debug, Deserialize)]
+struct SpannerType {
+    code: String,
+}
+
+/// Arrow type of a Spanner type code. Types without an Arrow equivalent are kept as strings.
+fn arrow_type(code: &str) -> DataType {
+    match code {
+        "INT64" => DataType::Int64,
+        "FLOAT64" | "FLOAT32" => DataType::Float64,
+        "BOOL" => DataType::Boolean,
+        "BYTES" => DataType::Binary,
+        _ => DataType::Utf8,
+    }
+}
+
+/// Assembles the values of partial result sets into rows
+#[derive(Default)]
+struct ResultDecoder {
+    schema: Option<SchemaRef>,
+    /// Values not yet emitted, the last one incomplete if `chunked`
+    values: Vec<Value>,
+    chunked: bool,
+}
+
+impl ResultDecoder {
+    /// Add a part of the results, returning the rows it completes
+    fn push(&mut self, set: PartialResultSet) -> Result<Option<RecordBatch>, Error> {
+        if let Some(metadata) = set.metadata {
+            let fields: Vec<Field> = metadata
+                .row_type
+                .fields
+                .iter()
+                .map(|field| Field::new(&field.name, arrow_type(&field.field_type.code), true))
+                .collect();
+            self.schema = Some(Arc::new(Schema::new(fields)));
+        }
+        let Some(schema) = self.schema.clone() else {
+            return Err(Error::Read("Spanner results without metadata".to_string()));
+        };
+
+        let mut values = set.values.into_iter();
+        if let Some(first) = values.next() {
+            if self.chunked {
+                let last = self.values.pop().unwrap_or(Value::Null);
+                self.values.push(merge_chunks(last, first)?);
+            } else {
+                self.values.push(first);
+            }
+            self.values.extend(values);
+            self.chunked = set.chunked_value;
+        }
+
+        let num_fields = schema.fields().len();
+        let complete = self.values.len() - usize::from(self.chunked);
+        if num_fields == 0 || complete < num_fields {
+            return Ok(None);
+        }
+        let rows = complete / num_fields;
+        let values: Vec<Value> = self.values.drain(..rows * num_fields).collect();
+        to_record_batch(schema, &values).map(Some)
+    }
+
+    /// Check that no value is left once the results are complete
+    fn finish(&self) -> Result<(), Error> {
+        if self.values.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Read("Incomplete Spanner results".to_string()))
+        }
+    }
+}
+
+/// Merge a chunked value with its continuation: strings are concatenated, and lists are
+/// concatenated with their adjacent string or list elements merged
+fn merge_chunks(first: Value, second: Value) -> Result<Value, Error> {
+    match (first, second) {
+        (Value::String(mut a), Value::String(b)) => {
+            a.push_str(&b);
+            Ok(Value::String(a))
+        }
+        (Value::Array(mut a), Value::Array(b)) => {
+            let mut b = b.into_iter();
+            match (a.pop(), b.next()) {
+                (Some(last @ (Value::String(_) | Value::Array(_))), Some(next)) => {
+                    a.push(merge_chunks(last, next)?);
+                }
+                (last, next) => a.extend(last.into_iter().chain(next)),
+            }
+            a.extend(b);
+            Ok(Value::Array(a))
+        }
+        (first, second) => Err(Error::Read(format!(
+            "Cannot merge chunked Spanner values {} and {}",
+            first, second
+        ))),
+    }
+}
+
+/// Convert row-major values to a record batch of `schema`
+fn to_record_batch(schema: SchemaRef, values: &[Value]) -> Result<RecordBatch, Error> {
+    let num_fields = schema.fields().len();
+    let columns = schema
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let column = values.iter().skip(i).step_by(num_fields);
+            to_array(field, column)
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    RecordBatch::try_new(schema, columns)
+        .map_err(|e| Error::Process(format!("Creating an Arrow record batch failed: {}", e)))
+}
+
+fn to_array<'a>(field: &Field, values: impl Iterator<Item = &'a Value>) -> Result<ArrayRef, Error> {
+    let invalid = |value: &Value| {
+        Error::Read(format!(
+            "Invalid Spanner value {} of column {}",
+            value,
+            field.name()
+        ))
+    };
+    let array: ArrayRef = match field.data_type() {
+        DataType::Int64 => Arc::new(
+            values
+                .map(|value| match value {
+                    Value::Null => Ok(None),
+                    Value::String(s) => s.parse().map(Some).map_err(|_| invalid(value)),
+                    Value::Number(n) => n.as_i64().map(Some).ok_or_else(|| invalid(value)),
+                    _ => Err(invalid(value)),
+                })
+                .collect::<Result<Int64Array, Error>>()?,
+        ),
+        DataType::Float64 => Arc::new(
+            values
+                .map(|value| match value {
+                    Value::Null => Ok(None),
+                    // NaN and infinities are encoded as strings
+                    Value::String(s) => s.parse().map(Some).map_err(|_| invalid(value)),
+                    Value::Number(n) => n.as_f64().map(Some).ok_or_else(|| invalid(value)),
+                    _ => Err(invalid(value)),
+                })
+                .collect::<Result<Float64Array, Error>>()?,
+        ),
+        DataType::Boolean => Arc::new(
+            values
+                .map(|value| match value {
+                    Value::Null => Ok(None),
+                    Value::Bool(b) => Ok(Some(*b)),
+                    _ => Err(invalid(value)),
+                })
+                .collect::<Result<BooleanArray, Error>>()?,
+        ),
+        DataType::Binary => {
+            let values = values
+                .map(|value| match value {
+                    Value::Null => Ok(None),
+                    Value::String(s) => base64::engine::general_purpose::STANDARD
+                        .decode(s)
+                        .map(Some)
+                        .map_err(|_| invalid(value)),
+                    _ => Err(invalid(value)),
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            Arc::new(BinaryArray::from_iter(values))
+        }
+        // Arrays and structs are kept as JSON
+        _ => Arc::new(
+            values
+                .map(|value| match value {
+                    Value::Null => None,
+                    Value::String(s) => Some(s.clone()),
+                    value => Some(value.to_string()),
+                })
+                .collect::<StringArray>(),
+        ),
+    };
+    Ok(array)
+}
+
+struct SpannerInputBuilder;
+
+impl InputBuilder for SpannerInputBuilder {
+    fn build(
+        &self,
+        name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Input>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Spanner input configuration is missing".to_string(),
+            ));
+        }
+        let config: SpannerInputConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(SpannerInput::new(name, config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_input_builder("spanner", Arc::new(SpannerInputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Bytes;
+    use axum::http::{Method, Uri};
+    use axum::Router;
+    use datafusion::arrow::array::Array;
+
+    const SESSION: &str = "projects/project/instances/instance/databases/db/sessions/s1";
+
+    type Requests = Arc<std::sync::Mutex<Vec<(Method, String, Bytes)>>>;
+
+    async fn mock_server(requests: Requests) -> String {
+        let app = Router::new().fallback(move |method: Method, uri: Uri, body: Bytes| {
+            let requests = requests.clone();
+            async move {
+                requests
+                    .lock()
+                    .unwrap()
+                    .push((method.clone(), uri.path().to_string(), body));
+                match (method, uri.path()) {
+                    (
+                        Method::POST,
+                        "/v1/projects/project/instances/instance/databases/db/sessions",
+                    ) => json!({ "name": SESSION }).to_string(),
+                    (Method::POST, path)
+                        if path == format!("/v1/{}:executeStreamingSql", SESSION) =>
+                    {
+                        // The name of the second row is split across two parts
+                        json!([
+                            {
+                                "metadata": { "rowType": { "fields": [
+                                    { "name": "id", "type": { "code": "INT64" } },
+                                    { "name": "name", "type": { "code": "STRING" } },
+                                    { "name": "score", "type": { "code": "FLOAT64" } },
+                                    { "name": "active", "type": { "code": "BOOL" } },
+                                    { "name": "payload", "type": { "code": "BYTES" } },
+                                    { "name": "created", "type": { "code": "TIMESTAMP" } }
+                                ] } },
+                                "values": [
+                                    "1", "alice", 1.5, true, "aGk=", "2024-01-01T00:00:00Z",
+                                    "2", "bo"
+                                ],
+                                "chunkedValue": true
+                            },
+                            {
+                                "values": ["b", "NaN", null, null, null]
+                            }
+                        ])
+                        .to_string()
+                    }
+                    (Method::DELETE, path) if path == format!("/v1/{}", SESSION) => {
+                        "{}".to_string()
+                    }
+                    (method, path) => panic!("unexpected request {} {}", method, path),
+                }
+            }
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    fn config(endpoint: String) -> SpannerInputConfig {
+        SpannerInputConfig {
+            project_id: "project".to_string(),
+            instance_id: "instance".to_string(),
+            database_id: "db".to_string(),
+            query: "SELECT * FROM users WHERE id > @min_id".to_string(),
+            params: HashMap::from([("min_id".to_string(), json!(0))]),
+            poll_interval_secs: None,
+            credentials_file: None,
+            endpoint: Some(endpoint),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_one_shot_query() {
+        let requests = Requests::default();
+        let endpoint = mock_server(requests.clone()).await;
+        let input = SpannerInput::new(Some(&"spanner".to_string()), config(endpoint)).unwrap();
+        input.connect().await.unwrap();
+
+        let mut rows = 0;
+        let mut batches = Vec::new();
+        loop {
+            match input.read().await {
+                Ok((batch, _)) => {
+                    assert_eq!(batch.get_input_name(), Some("spanner".to_string()));
+                    rows += batch.num_rows();
+                    batches.push(batch);
+                }
+                Err(Error::EOF) => break,
+                Err(e) => panic!("{}", e),
+            }
+        }
+        assert_eq!(rows, 2);
+        let batch = MessageBatch::concat(&batches).unwrap();
+
+        let ids = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(ids.values(), &[1, 2]);
+        let names = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(names.value(1), "bob");
+        let scores = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(scores.value(0), 1.5);
+        assert!(scores.value(1).is_nan());
+        let active = batch
+            .column(3)
+            .as_any()
+            .downcast_ref::<BooleanArray>()
+            .unwrap();
+        assert!(active.value(0));
+        assert!(active.is_null(1));
+        let payload = batch
+            .column(4)
+            .as_any()
+            .downcast_ref::<BinaryArray>()
+            .unwrap();
+        assert_eq!(payload.value(0), b"hi");
+        assert_eq!(batch.schema().field(5).data_type(), &DataType::Utf8);
+
+        let requests = requests.lock().unwrap();
+        let query: Value = serde_json::from_slice(&requests[1].2).unwrap();
+        assert_eq!(query["params"]["min_id"], json!("0"));
+        assert_eq!(query["paramTypes"]["min_id"]["code"], json!("INT64"));
+        // The session is deleted once the query completed
+        assert_eq!(requests[2].0, Method::DELETE);
+    }
+
+    #[test]
+    fn test_merge_chunks() {
+        assert_eq!(merge_chunks(json!("ab"), json!("c")).unwrap(), json!("abc"));
+        assert_eq!(
+            merge_chunks(json!(["a", "b"]), json!(["c", "d"])).unwrap(),
+            json!(["a", "bc", "d"])
+        );
+        assert_eq!(
+            merge_chunks(json!([true]), json!([false])).unwrap(),
+            json!([true, false])
+        );
+        assert!(merge_chunks(json!(true), json!(false)).is_err());
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        let mut config = config("http://localhost".to_string());
+        config.params.insert("ids".to_string(), json!([1, 2]));
+        assert!(matches!(
+            SpannerInput::new(None, config),
+            Err(Error::Config(_))
+        ));
+    }
+}
@@ -0,0 +1,423 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! SSE input component
+//!
+//! Receive Server-Sent Events from an HTTP event stream. Every event becomes a message holding
+//! its data, with its type and ID in the `sse_event_type` and `sse_id` columns.
+
+use arkflow_core::input::{register_input_builder, Ack, Input, InputBuilder, NoopAck};
+use arkflow_core::{Error, MessageBatch, Resource, DEFAULT_BINARY_VALUE_FIELD};
+use async_trait::async_trait;
+use datafusion::arrow::array::{ArrayRef, BinaryArray, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use flume::{Receiver, Sender};
+use futures_util::StreamExt;
+use reqwest::header::{ACCEPT, CACHE_CONTROL};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+/// Column holding the type of an event
+const EVENT_TYPE_FIELD: &str = "sse_event_type";
+/// Column holding the ID of the last event received
+const ID_FIELD: &str = "sse_id";
+/// Type of the events without an `event:` field
+const DEFAULT_EVENT_TYPE: &str = "message";
+
+/// SSE input configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SseInputConfig {
+    /// URL of the event stream
+    pub url: String,
+    /// Headers to include in the request
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Delay before reconnecting once the stream is closed
+    #[serde(default = "default_reconnect_delay_ms")]
+    pub reconnect_delay_ms: u64,
+    /// Only pass through events of this type, all events if not set
+    pub event_field: Option<String>,
+}
+
+fn default_reconnect_delay_ms() -> u64 {
+    1000
+}
+
+/// An event dispatched by the server
+#[derive(Debug, Clone, PartialEq)]
+struct SseEvent {
+    event_type: String,
+    id: Option<String>,
+    data: String,
+}
+
+/// Incremental parser of the `text/event-stream` format
+#[derive(Default)]
+struct SseParser {
+    /// Bytes of the line not terminated yet
+    line: Vec<u8>,
+    data: Vec<String>,
+    event_type: Option<String>,
+    /// ID of the last event, kept across events as the specification requires
+    last_event_id: Option<String>,
+}
+
+impl SseParser {
+    /// Parse a chunk of the stream, returning the events it completes
+    fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        let mut events = Vec::new();
+        for &byte in chunk {
+            if byte != b'\n' {
+                self.line.push(byte);
+                continue;
+            }
+            let mut line = std::mem::take(&mut self.line);
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            if let Some(event) = self.process_line(&String::from_utf8_lossy(&line)) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<SseEvent> {
+        // A blank line terminates the event
+        if line.is_empty() {
+            let event_type = self.event_type.take();
+            if self.data.is_empty() {
+                return None;
+            }
+            return Some(SseEvent {
+                event_type: event_type.unwrap_or_else(|| DEFAULT_EVENT_TYPE.to_string()),
+                id: self.last_event_id.clone(),
+                data: std::mem::take(&mut self.data).join("\n"),
+            });
+        }
+        // Comments keep the connection alive
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "data" => self.data.push(value.to_string()),
+            "event" => self.event_type = Some(value.to_string()),
+            "id" if !value.contains('\0') => self.last_event_id = Some(value.to_string()),
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Build a message holding the data, type and ID of an event
+fn message_batch(event: SseEvent) -> Result<MessageBatch, Error> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new(DEFAULT_BINARY_VALUE_FIELD, DataType::Binary, false),
+        Field::new(EVENT_TYPE_FIELD, DataType::Utf8, false),
+        Field::new(ID_FIELD, DataType::Utf8, true),
+    ]));
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(BinaryArray::from_vec(vec![event.data.as_bytes()])),
+        Arc::new(StringArray::from(vec![event.event_type])),
+        Arc::new(StringArray::from(vec![event.id])),
+    ];
+    let batch = RecordBatch::try_new(schema, columns)
+        .map_err(|e| Error::Process(format!("Creating an Arrow record batch failed: {}", e)))?;
+    Ok(MessageBatch::new_arrow(batch))
+}
+
+/// SSE input component
+pub struct SseInput {
+    input_name: Option<String>,
+    config: SseInputConfig,
+    client: reqwest::Client,
+    sender: Sender<Result<SseEvent, Error>>,
+    receiver: Receiver<Result<SseEvent, Error>>,
+    /// ID of the last event received, sent as `Last-Event-ID` when reconnecting
+    last_event_id: Arc<Mutex<Option<String>>>,
+    /// Cancels the task reading the current connection
+    connection_token: Mutex<Option<CancellationToken>>,
+    cancellation_token: CancellationToken,
+}
+
+impl SseInput {
+    /// Create a new SSE input component
+    pub fn new(name: Option<&String>, config: SseInputConfig) -> Result<Self, Error> {
+        let (sender, receiver) = flume::bounded::<Result<SseEvent, Error>>(1000);
+        Ok(Self {
+            input_name: name.cloned(),
+            config,
+            client: reqwest::Client::new(),
+            sender,
+            receiver,
+            last_event_id: Arc::new(Mutex::new(None)),
+            connection_token: Mutex::new(None),
+            cancellation_token: CancellationToken::new(),
+        })
+    }
+
+    async fn handle_events(
+        response: reqwest::Response,
+        event_field: Option<String>,
+        last_event_id: Arc<Mutex<Option<String>>>,
+        reconnect_delay: Duration,
+        sender: Sender<Result<SseEvent, Error>>,
+        cancellation_token: CancellationToken,
+    ) {
+        let mut parser = SseParser {
+            last_event_id: last_event_id.lock().unwrap().clone(),
+            ..Default::default()
+        };
+        let mut stream = response.bytes_stream();
+        loop {
+            tokio::select! {
+                chunk = stream.next() => {
+                    let chunk = match chunk {
+                        Some(Ok(chunk)) => chunk,
+                        Some(Err(e)) => {
+                            error!("SSE read error: {}", e);
+                            break;
+                        }
+                        None => {
+                            info!("SSE stream closed by the server");
+                            break;
+                        }
+                    };
+                    for event in parser.feed(&chunk) {
+                        *last_event_id.lock().unwrap() = event.id.clone();
+                        if event_field.as_ref().is_some_and(|t| *t != event.event_type) {
+                            continue;
+                        }
+                        if sender.send_async(Ok(event)).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                _ = cancellation_token.cancelled() => {
+                    return;
+                }
+            }
+        }
+
+        // Wait before letting the stream reconnect
+        tokio::select! {
+            _ = tokio::time::sleep(reconnect_delay) => {}
+            _ = cancellation_token.cancelled() => return,
+        }
+        if let Err(e) = sender.send_async(Err(Error::Disconnection)).await {
+            error!("Failed to send disconnection notification: {}", e);
+        }
+    }
+}
+
+#[async_trait]
+impl Input for SseInput {
+    async fn connect(&self) -> Result<(), Error> {
+        let mut request = self
+            .client
+            .get(&self.config.url)
+            .header(ACCEPT, "text/event-stream")
+            .header(CACHE_CONTROL, "no-cache");
+        for (key, value) in &self.config.headers {
+            request = request.header(key, value);
+        }
+        let last_event_id = self.last_event_id.lock().unwrap().clone();
+        if let Some(id) = last_event_id {
+            request = request.header("Last-Event-ID", id);
+        }
+
+        let response = request.send().await.map_err(|e| {
+            Error::Connection(format!(
+                "Failed to connect to SSE stream {}: {}",
+                self.config.url, e
+            ))
+        })?;
+        if !response.status().is_success() {
+            return Err(Error::Connection(format!(
+                "SSE stream {} returned status {}",
+                self.config.url,
+                response.status()
+            )));
+        }
+        info!("Connected to SSE stream: {}", self.config.url);
+
+        let connection_token = self.cancellation_token.child_token();
+        if let Some(previous) = self
+            .connection_token
+            .lock()
+            .unwrap()
+            .replace(connection_token.clone())
+        {
+            previous.cancel();
+        }
+        tokio::spawn(Self::handle_events(
+            response,
+            self.config.event_field.clone(),
+            Arc::clone(&self.last_event_id),
+            Duration::from_millis(self.config.reconnect_delay_ms),
+            Sender::clone(&self.sender),
+            connection_token,
+        ));
+        Ok(())
+    }
+
+    async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+        if self.connection_token.lock().unwrap().is_none() {
+            return Err(Error::Connection("The input is not connected".to_string()));
+        }
+
+        tokio::select! {
+            result = self.receiver.recv_async() => {
+                let event = result.map_err(|_| Error::EOF)??;
+                let mut msg = message_batch(event)?;
+                msg.set_input_name(self.input_name.clone());
+                Ok((msg, Arc::new(NoopAck)))
+            }
+            _ = self.cancellation_token.cancelled() => {
+                Err(Error::EOF)
+            }
+        }
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        self.cancellation_token.cancel();
+        Ok(())
+    }
+}
+
+pub(crate) struct SseInputBuilder;
+impl InputBuilder for SseInputBuilder {
+    fn build(
+        &self,
+        name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Input>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "SSE input configuration is missing".to_string(),
+            ));
+        }
+
+        let config: SseInputConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(SseInput::new(name, config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_input_builder("sse", Arc::new(SseInputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::AsArray;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_parse_events() {
+        let mut parser = SseParser::default();
+        let mut events =
+            parser.feed(b": keep-alive\ndata: first\ndata:second\r\nid: 1\n\nevent: upd");
+        events.extend(parser.feed(b"ate\ndata: {\"a\":1}\n\nid\ndata\n\n\n"));
+        assert_eq!(
+            events,
+            vec![
+                SseEvent {
+                    event_type: "message".to_string(),
+                    id: Some("1".to_string()),
+                    data: "first\nsecond".to_string(),
+                },
+                SseEvent {
+                    event_type: "update".to_string(),
+                    id: Some("1".to_string()),
+                    data: "{\"a\":1}".to_string(),
+                },
+                SseEvent {
+                    event_type: "message".to_string(),
+                    id: Some("".to_string()),
+                    data: "".to_string(),
+                },
+            ]
+        );
+    }
+
+    /// Serve one response holding `body` to every connection, returning the URL
+    async fn serve(body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = [0u8; 1024];
+                let _ = socket.read(&mut request).await;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n{}",
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        format!("http://{}/events", addr)
+    }
+
+    #[tokio::test]
+    async fn test_read_events() {
+        let url =
+            serve("event: ping\ndata: skipped\n\nevent: update\nid: 7\ndata: hello\n\n").await;
+        let input = SseInput::new(
+            Some(&"events".to_string()),
+            SseInputConfig {
+                url,
+                headers: HashMap::new(),
+                reconnect_delay_ms: 10,
+                event_field: Some("update".to_string()),
+            },
+        )
+        .unwrap();
+        assert!(matches!(input.read().await, Err(Error::Connection(_))));
+
+        input.connect().await.unwrap();
+        let (msg, _) = input.read().await.unwrap();
+        assert_eq!(
+            msg.to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap(),
+            vec![b"hello".as_slice()]
+        );
+        let event_type = msg.column_by_name(EVENT_TYPE_FIELD).unwrap();
+        assert_eq!(event_type.as_string::<i32>().value(0), "update");
+        let id = msg.column_by_name(ID_FIELD).unwrap();
+        assert_eq!(id.as_string::<i32>().value(0), "7");
+        assert_eq!(msg.get_input_name(), Some("events".to_string()));
+
+        // The server closes the stream, which reconnects
+        assert!(matches!(input.read().await, Err(Error::Disconnection)));
+        assert_eq!(input.last_event_id.lock().unwrap().as_deref(), Some("7"));
+        input.connect().await.unwrap();
+        assert!(input.read().await.is_ok());
+
+        input.close().await.unwrap();
+        assert!(matches!(input.read().await, Err(Error::EOF)));
+    }
+}
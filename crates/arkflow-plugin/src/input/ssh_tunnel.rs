@@ -0,0 +1,333 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! SSH tunnel input component
+//!
+//! Forward a port of a host in a private network over SSH and read from it with an inner input
+
+use arkflow_core::input::{register_input_builder, Ack, Input, InputBuilder, InputConfig};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+use tokio::process::{Child, Command};
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+/// Placeholder replaced by the local address of the tunnel in the inner input configuration
+const ADDRESS_PLACEHOLDER: &str = "{{tunnel_address}}";
+/// Placeholder replaced by the local port of the tunnel in the inner input configuration
+const PORT_PLACEHOLDER: &str = "{{tunnel_port}}";
+/// Maximum time to wait for the forwarded port to accept connections
+const TUNNEL_READY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Interval between checks whether the forwarded port is ready
+const TUNNEL_READY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// SSH tunnel input configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SshTunnelInputConfig {
+    /// SSH server host
+    pub ssh_host: String,
+    /// SSH server port
+    #[serde(default = "default_ssh_port")]
+    pub ssh_port: u16,
+    /// SSH user
+    pub ssh_user: String,
+    /// Path to the private key used for authentication
+    pub ssh_key_path: String,
+    /// Host to forward to, as seen from the SSH server
+    pub remote_host: String,
+    /// Port to forward to
+    pub remote_port: u16,
+    /// Local port of the tunnel. An ephemeral port is chosen if absent
+    pub local_port: Option<u16>,
+    /// Input reading from the tunnel
+    pub inner_input: InputConfig,
+}
+
+fn default_ssh_port() -> u16 {
+    22
+}
+
+/// A running `ssh` port forwarding process
+struct Tunnel {
+    /// Cancelled when the SSH session ends
+    closed: CancellationToken,
+    /// Cancel to shut the SSH session down
+    shutdown: CancellationToken,
+}
+
+/// SSH tunnel input component
+pub struct SshTunnelInput {
+    config: SshTunnelInputConfig,
+    local_port: u16,
+    inner: Arc<dyn Input>,
+    tunnel: Mutex<Option<Tunnel>>,
+}
+
+impl SshTunnelInput {
+    /// Create a new SSH tunnel input component forwarding `local_port` and reading with `inner`
+    pub fn new(config: SshTunnelInputConfig, local_port: u16, inner: Arc<dyn Input>) -> Self {
+        Self {
+            config,
+            local_port,
+            inner,
+            tunnel: Mutex::new(None),
+        }
+    }
+
+    fn spawn_ssh(&self) -> Result<Child, Error> {
+        let forward = format!(
+            "127.0.0.1:{}:{}:{}",
+            self.local_port, self.config.remote_host, self.config.remote_port
+        );
+        Command::new("ssh")
+            .arg("-N")
+            .args(["-o", "BatchMode=yes"])
+            .args(["-o", "ExitOnForwardFailure=yes"])
+            .args(["-o", "ServerAliveInterval=15"])
+            .args(["-i", &self.config.ssh_key_path])
+            .args(["-p", &self.config.ssh_port.to_string()])
+            .args(["-L", &forward])
+            .arg(format!("{}@{}", self.config.ssh_user, self.config.ssh_host))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| Error::Connection(format!("Unable to start ssh: {}", e)))
+    }
+
+    /// Start the SSH session and wait until the forwarded port accepts connections
+    async fn open_tunnel(&self) -> Result<Tunnel, Error> {
+        let mut child = self.spawn_ssh()?;
+        let address = format!("127.0.0.1:{}", self.local_port);
+
+        let deadline = tokio::time::Instant::now() + TUNNEL_READY_TIMEOUT;
+        loop {
+            if let Some(status) = child.try_wait()? {
+                let mut stderr = String::new();
+                if let Some(mut pipe) = child.stderr.take() {
+                    let _ = pipe.read_to_string(&mut stderr).await;
+                }
+                return Err(Error::Connection(format!(
+                    "SSH tunnel to {} exited with {}: {}",
+                    self.config.ssh_host,
+                    status,
+                    stderr.trim()
+                )));
+            }
+            if TcpStream::connect(&address).await.is_ok() {
+                break;
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(Error::Timeout);
+            }
+            tokio::time::sleep(TUNNEL_READY_POLL_INTERVAL).await;
+        }
+        info!(
+            "SSH tunnel {} -> {}:{} via {} established",
+            address, self.config.remote_host, self.config.remote_port, self.config.ssh_host
+        );
+
+        let closed = CancellationToken::new();
+        let shutdown = CancellationToken::new();
+        let tunnel = Tunnel {
+            closed: closed.clone(),
+            shutdown: shutdown.clone(),
+        };
+        let ssh_host = self.config.ssh_host.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                status = child.wait() => {
+                    warn!("SSH tunnel via {} closed: {:?}", ssh_host, status);
+                }
+                _ = shutdown.cancelled() => {
+                    let _ = child.kill().await;
+                }
+            }
+            closed.cancel();
+        });
+        Ok(tunnel)
+    }
+}
+
+#[async_trait]
+impl Input for SshTunnelInput {
+    async fn connect(&self) -> Result<(), Error> {
+        {
+            let mut tunnel = self.tunnel.lock().await;
+            let alive = tunnel.as_ref().is_some_and(|t| !t.closed.is_cancelled());
+            if !alive {
+                *tunnel = Some(self.open_tunnel().await?);
+            }
+        }
+        self.inner.connect().await
+    }
+
+    async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+        let closed = match self.tunnel.lock().await.as_ref() {
+            Some(tunnel) => tunnel.closed.clone(),
+            None => return Err(Error::Disconnection),
+        };
+        if closed.is_cancelled() {
+            return Err(Error::Disconnection);
+        }
+
+        tokio::select! {
+            result = self.inner.read() => result,
+            _ = closed.cancelled() => Err(Error::Disconnection),
+        }
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        let result = self.inner.close().await;
+        if let Some(tunnel) = self.tunnel.lock().await.take() {
+            tunnel.shutdown.cancel();
+            tunnel.closed.cancelled().await;
+        }
+        result
+    }
+}
+
+/// Replace the tunnel placeholders in the string values of `value`.
+///
+/// A string consisting only of the port placeholder becomes a number.
+fn substitute_placeholders(value: &mut Value, local_port: u16) {
+    match value {
+        Value::String(s) if s == PORT_PLACEHOLDER => *value = Value::from(local_port),
+        Value::String(s) => {
+            *s = s
+                .replace(ADDRESS_PLACEHOLDER, &format!("127.0.0.1:{}", local_port))
+                .replace(PORT_PLACEHOLDER, &local_port.to_string());
+        }
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|v| substitute_placeholders(v, local_port)),
+        Value::Object(map) => map
+            .values_mut()
+            .for_each(|v| substitute_placeholders(v, local_port)),
+        _ => {}
+    }
+}
+
+/// Reserve an ephemeral local port for the tunnel
+fn ephemeral_port() -> Result<u16, Error> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    Ok(listener.local_addr()?.port())
+}
+
+struct SshTunnelInputBuilder;
+
+impl InputBuilder for SshTunnelInputBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<Value>,
+        resource: &Resource,
+    ) -> Result<Arc<dyn Input>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "SSH tunnel input configuration is missing".to_string(),
+            ));
+        }
+        let config: SshTunnelInputConfig = serde_json::from_value(config.clone().unwrap())?;
+
+        let local_port = match config.local_port {
+            Some(port) => port,
+            None => ephemeral_port()?,
+        };
+        let mut inner_config = config.inner_input.clone();
+        if let Some(value) = inner_config.config.as_mut() {
+            substitute_placeholders(value, local_port);
+        }
+        let inner = inner_config.build(resource)?;
+
+        Ok(Arc::new(SshTunnelInput::new(config, local_port, inner)))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_input_builder("ssh_tunnel", Arc::new(SshTunnelInputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arkflow_core::input::NoopAck;
+    use serde_json::json;
+
+    struct EmptyInput;
+
+    #[async_trait]
+    impl Input for EmptyInput {
+        async fn connect(&self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+            Ok((MessageBatch::new_binary(vec![])?, Arc::new(NoopAck)))
+        }
+
+        async fn close(&self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    fn config() -> SshTunnelInputConfig {
+        serde_json::from_value(json!({
+            "ssh_host": "127.0.0.1",
+            "ssh_port": ephemeral_port().unwrap(),
+            "ssh_user": "arkflow",
+            "ssh_key_path": "/nonexistent/id_ed25519",
+            "remote_host": "10.0.0.5",
+            "remote_port": 1883,
+            "inner_input": {"type": "memory", "messages": []},
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_substitute_placeholders() {
+        let mut value = json!({
+            "url": "tcp://{{tunnel_address}}",
+            "port": "{{tunnel_port}}",
+            "hosts": ["localhost:{{tunnel_port}}"],
+            "keep": 1,
+        });
+        substitute_placeholders(&mut value, 4000);
+        assert_eq!(
+            value,
+            json!({
+                "url": "tcp://127.0.0.1:4000",
+                "port": 4000,
+                "hosts": ["localhost:4000"],
+                "keep": 1,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_fails_when_ssh_fails() {
+        let input = SshTunnelInput::new(config(), ephemeral_port().unwrap(), Arc::new(EmptyInput));
+        assert!(input.connect().await.is_err());
+        assert!(matches!(input.read().await, Err(Error::Disconnection)));
+    }
+}
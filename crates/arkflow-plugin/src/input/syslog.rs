@@ -0,0 +1,546 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Syslog input component
+//!
+//! Receive syslog messages over UDP or TCP and parse them as RFC 3164 or RFC 5424
+
+use arkflow_core::input::{register_input_builder, Ack, Input, InputBuilder, NoopAck};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use chrono::Datelike;
+use datafusion::arrow::array::{ArrayRef, StringArray, UInt8Array};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use flume::{Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use syslog_loose::Variant;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+/// Severity reported for messages that cannot be parsed
+const MALFORMED_SEVERITY: u8 = 255;
+/// Maximum size of a UDP datagram
+const MAX_DATAGRAM_SIZE: usize = 65535;
+
+/// Syslog input configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyslogInputConfig {
+    /// Transport protocol
+    pub transport: SyslogTransport,
+    /// Address to listen on, e.g. `0.0.0.0:514`
+    pub bind_address: String,
+    /// Expected message format
+    #[serde(default)]
+    pub format: SyslogFormat,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyslogTransport {
+    Udp,
+    Tcp,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyslogFormat {
+    Rfc3164,
+    Rfc5424,
+    /// Try RFC 5424 first and fall back to RFC 3164
+    #[default]
+    Auto,
+}
+
+impl From<SyslogFormat> for Variant {
+    fn from(format: SyslogFormat) -> Self {
+        match format {
+            SyslogFormat::Rfc3164 => Variant::RFC3164,
+            SyslogFormat::Rfc5424 => Variant::RFC5424,
+            SyslogFormat::Auto => Variant::Either,
+        }
+    }
+}
+
+/// Syslog input component
+pub struct SyslogInput {
+    input_name: Option<String>,
+    config: SyslogInputConfig,
+    sender: Sender<Vec<u8>>,
+    receiver: Receiver<Vec<u8>>,
+    cancellation_token: Mutex<Option<CancellationToken>>,
+}
+
+impl SyslogInput {
+    /// Create a new syslog input component
+    pub fn new(name: Option<&String>, config: SyslogInputConfig) -> Result<Self, Error> {
+        let (sender, receiver) = flume::bounded::<Vec<u8>>(1000);
+        Ok(Self {
+            input_name: name.cloned(),
+            config,
+            sender,
+            receiver,
+            cancellation_token: Mutex::new(None),
+        })
+    }
+
+    async fn receive_udp(socket: UdpSocket, sender: Sender<Vec<u8>>, token: CancellationToken) {
+        let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+        loop {
+            tokio::select! {
+                result = socket.recv_from(&mut buf) => {
+                    match result {
+                        Ok((len, _)) => {
+                            if let Err(e) = sender.send_async(buf[..len].to_vec()).await {
+                                error!("Failed to forward syslog message: {}", e);
+                                break;
+                            }
+                        }
+                        Err(e) => {
+                            error!("Syslog UDP receive error: {}", e);
+                        }
+                    }
+                }
+                _ = token.cancelled() => {
+                    break;
+                }
+            }
+        }
+    }
+
+    async fn accept_tcp(listener: TcpListener, sender: Sender<Vec<u8>>, token: CancellationToken) {
+        loop {
+            tokio::select! {
+                result = listener.accept() => {
+                    match result {
+                        Ok((stream, _)) => {
+                            tokio::spawn(Self::handle_tcp_connection(
+                                BufReader::new(stream),
+                                sender.clone(),
+                                token.clone(),
+                            ));
+                        }
+                        Err(e) => {
+                            error!("Failed to accept syslog TCP connection: {}", e);
+                        }
+                    }
+                }
+                _ = token.cancelled() => {
+                    break;
+                }
+            }
+        }
+    }
+
+    async fn handle_tcp_connection<R: AsyncRead + Unpin>(
+        mut reader: BufReader<R>,
+        sender: Sender<Vec<u8>>,
+        token: CancellationToken,
+    ) {
+        loop {
+            tokio::select! {
+                result = read_tcp_frame(&mut reader) => {
+                    match result {
+                        Ok(Some(frame)) => {
+                            if frame.is_empty() {
+                                continue;
+                            }
+                            if let Err(e) = sender.send_async(frame).await {
+                                error!("Failed to forward syslog message: {}", e);
+                                break;
+                            }
+                        }
+                        Ok(None) => break,
+                        Err(e) => {
+                            error!("Syslog TCP read error: {}", e);
+                            break;
+                        }
+                    }
+                }
+                _ = token.cancelled() => {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Read one message from a TCP stream (RFC 6587).
+///
+/// Frames starting with a digit use octet counting (`<length> <message>`),
+/// all others are terminated by a newline.
+async fn read_tcp_frame<R: AsyncRead + Unpin>(
+    reader: &mut BufReader<R>,
+) -> Result<Option<Vec<u8>>, Error> {
+    let buf = reader.fill_buf().await?;
+    if buf.is_empty() {
+        return Ok(None);
+    }
+
+    if buf[0].is_ascii_digit() {
+        let mut length = Vec::new();
+        reader.read_until(b' ', &mut length).await?;
+        let length = std::str::from_utf8(&length)
+            .ok()
+            .and_then(|s| s.trim_end().parse::<usize>().ok())
+            .ok_or_else(|| Error::Read("Invalid syslog octet count".to_string()))?;
+        let mut frame = vec![0u8; length];
+        reader.read_exact(&mut frame).await?;
+        return Ok(Some(frame));
+    }
+
+    let mut frame = Vec::new();
+    reader.read_until(b'\n', &mut frame).await?;
+    while matches!(frame.last(), Some(b'\n' | b'\r')) {
+        frame.pop();
+    }
+    Ok(Some(frame))
+}
+
+/// A parsed syslog message
+#[derive(Debug, Default, PartialEq)]
+struct SyslogRecord {
+    priority: Option<u8>,
+    facility: Option<u8>,
+    severity: Option<u8>,
+    timestamp: Option<String>,
+    hostname: Option<String>,
+    app_name: Option<String>,
+    proc_id: Option<String>,
+    msg_id: Option<String>,
+    message: String,
+}
+
+/// Parse a syslog message. Messages that are not valid UTF-8, cannot be parsed or
+/// have no priority are reported with severity 255 and the raw bytes as hex.
+fn parse_syslog(data: &[u8], format: SyslogFormat) -> SyslogRecord {
+    let malformed = || SyslogRecord {
+        severity: Some(MALFORMED_SEVERITY),
+        message: hex::encode(data),
+        ..Default::default()
+    };
+
+    let Ok(input) = std::str::from_utf8(data) else {
+        return malformed();
+    };
+    let Ok(msg) = syslog_loose::parse_message_with_year_exact(
+        input,
+        |_| chrono::Local::now().year(),
+        format.into(),
+    ) else {
+        return malformed();
+    };
+    let (Some(facility), Some(severity)) = (msg.facility, msg.severity) else {
+        return malformed();
+    };
+
+    let facility = facility as u8;
+    let severity = severity as u8;
+    SyslogRecord {
+        priority: Some(facility * 8 + severity),
+        facility: Some(facility),
+        severity: Some(severity),
+        timestamp: msg.timestamp.map(|t| t.to_rfc3339()),
+        hostname: msg.hostname.map(str::to_string),
+        app_name: msg.appname.map(str::to_string),
+        proc_id: msg.procid.map(|p| p.to_string()),
+        msg_id: msg.msgid.map(str::to_string),
+        message: msg.msg.to_string(),
+    }
+}
+
+fn to_record_batch(records: &[SyslogRecord]) -> Result<RecordBatch, Error> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("priority", DataType::UInt8, true),
+        Field::new("facility", DataType::UInt8, true),
+        Field::new("severity", DataType::UInt8, true),
+        Field::new("timestamp", DataType::Utf8, true),
+        Field::new("hostname", DataType::Utf8, true),
+        Field::new("app_name", DataType::Utf8, true),
+        Field::new("proc_id", DataType::Utf8, true),
+        Field::new("msg_id", DataType::Utf8, true),
+        Field::new("message", DataType::Utf8, false),
+    ]));
+
+    let u8_column = |f: fn(&SyslogRecord) -> Option<u8>| -> ArrayRef {
+        Arc::new(records.iter().map(f).collect::<UInt8Array>())
+    };
+    let str_column = |f: fn(&SyslogRecord) -> Option<&str>| -> ArrayRef {
+        Arc::new(records.iter().map(f).collect::<StringArray>())
+    };
+
+    RecordBatch::try_new(
+        schema,
+        vec![
+            u8_column(|r| r.priority),
+            u8_column(|r| r.facility),
+            u8_column(|r| r.severity),
+            str_column(|r| r.timestamp.as_deref()),
+            str_column(|r| r.hostname.as_deref()),
+            str_column(|r| r.app_name.as_deref()),
+            str_column(|r| r.proc_id.as_deref()),
+            str_column(|r| r.msg_id.as_deref()),
+            str_column(|r| Some(r.message.as_str())),
+        ],
+    )
+    .map_err(|e| Error::Process(format!("Creating an Arrow record batch failed: {}", e)))
+}
+
+#[async_trait]
+impl Input for SyslogInput {
+    async fn connect(&self) -> Result<(), Error> {
+        let mut token_guard = self.cancellation_token.lock().await;
+        if token_guard.is_some() {
+            return Ok(());
+        }
+
+        let cancellation_token = CancellationToken::new();
+        let token = cancellation_token.clone();
+        let sender = self.sender.clone();
+        let bind_address = &self.config.bind_address;
+
+        match self.config.transport {
+            SyslogTransport::Udp => {
+                let socket = UdpSocket::bind(bind_address).await.map_err(|e| {
+                    Error::Connection(format!(
+                        "Unable to bind syslog UDP socket {}: {}",
+                        bind_address, e
+                    ))
+                })?;
+                info!("Syslog input listening on udp://{}", bind_address);
+                tokio::spawn(Self::receive_udp(socket, sender, token));
+            }
+            SyslogTransport::Tcp => {
+                let listener = TcpListener::bind(bind_address).await.map_err(|e| {
+                    Error::Connection(format!(
+                        "Unable to bind syslog TCP listener {}: {}",
+                        bind_address, e
+                    ))
+                })?;
+                info!("Syslog input listening on tcp://{}", bind_address);
+                tokio::spawn(Self::accept_tcp(listener, sender, token));
+            }
+        }
+
+        *token_guard = Some(cancellation_token);
+        Ok(())
+    }
+
+    async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+        let Some(cancellation_token) = self.cancellation_token.lock().await.clone() else {
+            return Err(Error::Connection("The input is not connected".to_string()));
+        };
+
+        tokio::select! {
+            result = self.receiver.recv_async() => {
+                match result {
+                    Ok(payload) => {
+                        let record = parse_syslog(&payload, self.config.format);
+                        let mut msg = MessageBatch::new_arrow(to_record_batch(&[record])?);
+                        msg.set_input_name(self.input_name.clone());
+                        Ok((msg, Arc::new(NoopAck)))
+                    }
+                    Err(_) => Err(Error::EOF),
+                }
+            }
+            _ = cancellation_token.cancelled() => {
+                Err(Error::EOF)
+            }
+        }
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        if let Some(token) = self.cancellation_token.lock().await.take() {
+            token.cancel();
+        }
+        Ok(())
+    }
+}
+
+pub(crate) struct SyslogInputBuilder;
+impl InputBuilder for SyslogInputBuilder {
+    fn build(
+        &self,
+        name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Input>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Syslog input configuration is missing".to_string(),
+            ));
+        }
+
+        let config: SyslogInputConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(SyslogInput::new(name, config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_input_builder("syslog", Arc::new(SyslogInputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::Array;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpStream;
+
+    fn string_value(msg: &MessageBatch, column: &str) -> Option<String> {
+        let array = msg
+            .column_by_name(column)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        (!array.is_null(0)).then(|| array.value(0).to_string())
+    }
+
+    fn u8_value(msg: &MessageBatch, column: &str) -> Option<u8> {
+        let array = msg
+            .column_by_name(column)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<UInt8Array>()
+            .unwrap();
+        (!array.is_null(0)).then(|| array.value(0))
+    }
+
+    #[test]
+    fn test_parse_rfc5424() {
+        let record = parse_syslog(
+            b"<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog 1234 ID47 - An application event",
+            SyslogFormat::Rfc5424,
+        );
+        assert_eq!(record.priority, Some(165));
+        assert_eq!(record.facility, Some(20));
+        assert_eq!(record.severity, Some(5));
+        assert_eq!(record.hostname.as_deref(), Some("mymachine.example.com"));
+        assert_eq!(record.app_name.as_deref(), Some("evntslog"));
+        assert_eq!(record.proc_id.as_deref(), Some("1234"));
+        assert_eq!(record.msg_id.as_deref(), Some("ID47"));
+        assert_eq!(record.message, "An application event");
+        assert!(record
+            .timestamp
+            .unwrap()
+            .starts_with("2003-10-11T22:14:15.003"));
+    }
+
+    #[test]
+    fn test_parse_rfc3164() {
+        let record = parse_syslog(
+            b"<34>Oct 11 22:14:15 mymachine su: 'su root' failed for lonvick on /dev/pts/8",
+            SyslogFormat::Auto,
+        );
+        assert_eq!(record.priority, Some(34));
+        assert_eq!(record.facility, Some(4));
+        assert_eq!(record.severity, Some(2));
+        assert_eq!(record.hostname.as_deref(), Some("mymachine"));
+        assert_eq!(record.app_name.as_deref(), Some("su"));
+        assert_eq!(record.message, "'su root' failed for lonvick on /dev/pts/8");
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        for data in [&b"\xff\xfe"[..], b"no priority here"] {
+            let record = parse_syslog(data, SyslogFormat::Auto);
+            assert_eq!(record.severity, Some(MALFORMED_SEVERITY));
+            assert_eq!(record.priority, None);
+            assert_eq!(record.message, hex::encode(data));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_tcp_frame() {
+        let data: &[u8] = b"<34>first\r\n17 <34>octet\ncounted";
+        let mut reader = BufReader::new(data);
+        assert_eq!(
+            read_tcp_frame(&mut reader).await.unwrap(),
+            Some(b"<34>first".to_vec())
+        );
+        assert_eq!(
+            read_tcp_frame(&mut reader).await.unwrap(),
+            Some(b"<34>octet\ncounted".to_vec())
+        );
+        assert_eq!(read_tcp_frame(&mut reader).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_udp_input() {
+        let port = std::net::UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let input = SyslogInput::new(
+            None,
+            SyslogInputConfig {
+                transport: SyslogTransport::Udp,
+                bind_address: format!("127.0.0.1:{}", port),
+                format: SyslogFormat::Auto,
+            },
+        )
+        .unwrap();
+        input.connect().await.unwrap();
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client
+            .send_to(b"<13>Oct 11 22:14:15 host app: hello", ("127.0.0.1", port))
+            .await
+            .unwrap();
+
+        let (msg, _) = input.read().await.unwrap();
+        assert_eq!(u8_value(&msg, "severity"), Some(5));
+        assert_eq!(u8_value(&msg, "facility"), Some(1));
+        assert_eq!(string_value(&msg, "hostname").as_deref(), Some("host"));
+        assert_eq!(string_value(&msg, "message").as_deref(), Some("hello"));
+        input.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_tcp_input() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let input = SyslogInput::new(
+            None,
+            SyslogInputConfig {
+                transport: SyslogTransport::Tcp,
+                bind_address: format!("127.0.0.1:{}", port),
+                format: SyslogFormat::Rfc5424,
+            },
+        )
+        .unwrap();
+        input.connect().await.unwrap();
+
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        stream
+            .write_all(b"<165>1 2003-10-11T22:14:15.003Z host app - - - hello\n")
+            .await
+            .unwrap();
+
+        let (msg, _) = input.read().await.unwrap();
+        assert_eq!(u8_value(&msg, "priority"), Some(165));
+        assert_eq!(string_value(&msg, "proc_id"), None);
+        assert_eq!(string_value(&msg, "message").as_deref(), Some("hello"));
+        input.close().await.unwrap();
+    }
+}
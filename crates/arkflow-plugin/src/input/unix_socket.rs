@@ -0,0 +1,365 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Unix domain socket input component
+//!
+//! Listen on a Unix domain socket and receive framed messages from local processes
+
+use crate::component::unix_socket::{read_frame, MessageFormat, DEFAULT_MAX_FRAME_BYTES};
+use arkflow_core::input::{register_input_builder, Ack, Input, InputBuilder, NoopAck};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use flume::{Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::BufReader;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+/// Unix domain socket input configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnixSocketInputConfig {
+    /// Path of the socket file to listen on
+    pub socket_path: String,
+    /// Message framing
+    #[serde(default)]
+    pub message_format: MessageFormat,
+    /// Largest accepted frame; the connection of a peer sending a larger one is closed
+    #[serde(default = "default_max_frame_bytes")]
+    pub max_frame_bytes: usize,
+}
+
+fn default_max_frame_bytes() -> usize {
+    DEFAULT_MAX_FRAME_BYTES
+}
+
+/// Unix domain socket input component
+pub struct UnixSocketInput {
+    input_name: Option<String>,
+    config: UnixSocketInputConfig,
+    sender: Sender<Vec<u8>>,
+    receiver: Receiver<Vec<u8>>,
+    cancellation_token: Mutex<Option<CancellationToken>>,
+}
+
+impl UnixSocketInput {
+    /// Create a new Unix domain socket input component
+    pub fn new(name: Option<&String>, config: UnixSocketInputConfig) -> Result<Self, Error> {
+        let (sender, receiver) = flume::bounded::<Vec<u8>>(1000);
+        Ok(Self {
+            input_name: name.cloned(),
+            config,
+            sender,
+            receiver,
+            cancellation_token: Mutex::new(None),
+        })
+    }
+
+    async fn handle_connection(
+        stream: UnixStream,
+        format: MessageFormat,
+        max_frame_bytes: usize,
+        sender: Sender<Vec<u8>>,
+        cancellation_token: CancellationToken,
+    ) {
+        let mut reader = BufReader::new(stream);
+        loop {
+            tokio::select! {
+                result = read_frame(&mut reader, format, max_frame_bytes) => {
+                    match result {
+                        Ok(Some(frame)) => {
+                            if let Err(e) = sender.send_async(frame).await {
+                                error!("Failed to forward Unix socket message: {}", e);
+                                break;
+                            }
+                        }
+                        Ok(None) => break,
+                        Err(e) => {
+                            error!("Unix socket read error: {}", e);
+                            break;
+                        }
+                    }
+                }
+                _ = cancellation_token.cancelled() => {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Input for UnixSocketInput {
+    async fn connect(&self) -> Result<(), Error> {
+        let mut token_guard = self.cancellation_token.lock().await;
+        if token_guard.is_some() {
+            return Ok(());
+        }
+
+        // Remove a stale socket file left behind by a previous run, but never
+        // anything that isn't a socket
+        let path = Path::new(&self.config.socket_path);
+        if let Ok(metadata) = std::fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                return Err(Error::Config(format!(
+                    "socket_path {} exists and is not a socket",
+                    self.config.socket_path
+                )));
+            }
+            std::fs::remove_file(path).map_err(|e| {
+                Error::Connection(format!(
+                    "Unable to remove stale socket file {}: {}",
+                    self.config.socket_path, e
+                ))
+            })?;
+        }
+
+        let listener = UnixListener::bind(path).map_err(|e| {
+            Error::Connection(format!(
+                "Unable to bind Unix socket {}: {}",
+                self.config.socket_path, e
+            ))
+        })?;
+        info!("Listening on Unix socket: {}", self.config.socket_path);
+
+        let cancellation_token = CancellationToken::new();
+        let token = cancellation_token.clone();
+        let sender = self.sender.clone();
+        let format = self.config.message_format;
+        let max_frame_bytes = self.config.max_frame_bytes;
+
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    result = listener.accept() => {
+                        match result {
+                            Ok((stream, _)) => {
+                                tokio::spawn(Self::handle_connection(
+                                    stream,
+                                    format,
+                                    max_frame_bytes,
+                                    sender.clone(),
+                                    token.clone(),
+                                ));
+                            }
+                            Err(e) => {
+                                error!("Failed to accept Unix socket connection: {}", e);
+                            }
+                        }
+                    }
+                    _ = token.cancelled() => {
+                        break;
+                    }
+                }
+            }
+        });
+
+        *token_guard = Some(cancellation_token);
+        Ok(())
+    }
+
+    async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+        let Some(cancellation_token) = self.cancellation_token.lock().await.clone() else {
+            return Err(Error::Connection("The input is not connected".to_string()));
+        };
+
+        tokio::select! {
+            result = self.receiver.recv_async() => {
+                match result {
+                    Ok(payload) => {
+                        let mut msg = MessageBatch::new_binary(vec![payload])?;
+                        msg.set_input_name(self.input_name.clone());
+                        Ok((msg, Arc::new(NoopAck)))
+                    }
+                    Err(_) => Err(Error::EOF),
+                }
+            }
+            _ = cancellation_token.cancelled() => {
+                Err(Error::EOF)
+            }
+        }
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        // Only remove the socket file this input bound
+        if let Some(token) = self.cancellation_token.lock().await.take() {
+            token.cancel();
+            let path = Path::new(&self.config.socket_path);
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) struct UnixSocketInputBuilder;
+impl InputBuilder for UnixSocketInputBuilder {
+    fn build(
+        &self,
+        name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Input>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Unix socket input configuration is missing".to_string(),
+            ));
+        }
+
+        let config: UnixSocketInputConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(UnixSocketInput::new(name, config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_input_builder("unix_socket", Arc::new(UnixSocketInputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::unix_socket::write_frame;
+    use arkflow_core::DEFAULT_BINARY_VALUE_FIELD;
+
+    async fn round_trip(format: MessageFormat) {
+        let dir = tempfile::tempdir().unwrap();
+        round_trip_at(&dir.path().join("input.sock"), format).await;
+    }
+
+    async fn round_trip_at(socket_path: &Path, format: MessageFormat) {
+        let input = UnixSocketInput::new(
+            None,
+            UnixSocketInputConfig {
+                socket_path: socket_path.to_string_lossy().to_string(),
+                message_format: format,
+                max_frame_bytes: DEFAULT_MAX_FRAME_BYTES,
+            },
+        )
+        .unwrap();
+        input.connect().await.unwrap();
+
+        let mut stream = UnixStream::connect(socket_path).await.unwrap();
+        write_frame(&mut stream, format, b"hello").await.unwrap();
+        write_frame(&mut stream, format, b"world").await.unwrap();
+
+        for expected in ["hello", "world"] {
+            let (msg, _) = input.read().await.unwrap();
+            let values = msg.to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap();
+            assert_eq!(values, vec![expected.as_bytes()]);
+        }
+
+        input.close().await.unwrap();
+        assert!(!socket_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_length_prefixed_round_trip() {
+        round_trip(MessageFormat::LengthPrefixed).await;
+    }
+
+    #[tokio::test]
+    async fn test_newline_round_trip() {
+        round_trip(MessageFormat::Newline).await;
+    }
+
+    #[tokio::test]
+    async fn test_read_not_connected() {
+        let input = UnixSocketInput::new(
+            None,
+            UnixSocketInputConfig {
+                socket_path: "/tmp/arkflow-not-connected.sock".to_string(),
+                message_format: MessageFormat::Newline,
+                max_frame_bytes: DEFAULT_MAX_FRAME_BYTES,
+            },
+        )
+        .unwrap();
+        assert!(matches!(input.read().await, Err(Error::Connection(_))));
+    }
+
+    #[tokio::test]
+    async fn test_existing_non_socket_path_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("input.sock");
+        std::fs::write(&socket_path, b"data").unwrap();
+        let input = UnixSocketInput::new(
+            None,
+            UnixSocketInputConfig {
+                socket_path: socket_path.to_string_lossy().to_string(),
+                message_format: MessageFormat::Newline,
+                max_frame_bytes: DEFAULT_MAX_FRAME_BYTES,
+            },
+        )
+        .unwrap();
+        assert!(matches!(input.connect().await, Err(Error::Config(_))));
+        input.close().await.unwrap();
+        assert_eq!(std::fs::read(&socket_path).unwrap(), b"data");
+    }
+
+    #[tokio::test]
+    async fn test_stale_socket_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("input.sock");
+        drop(std::os::unix::net::UnixListener::bind(&socket_path).unwrap());
+        assert!(socket_path.exists());
+
+        round_trip_at(&socket_path, MessageFormat::Newline).await;
+    }
+
+    async fn oversized_frame_dropped(format: MessageFormat) {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("input.sock");
+        let input = UnixSocketInput::new(
+            None,
+            UnixSocketInputConfig {
+                socket_path: socket_path.to_string_lossy().to_string(),
+                message_format: format,
+                max_frame_bytes: 5,
+            },
+        )
+        .unwrap();
+        input.connect().await.unwrap();
+
+        // The connection sending the oversized frame is closed
+        let mut stream = UnixStream::connect(&socket_path).await.unwrap();
+        write_frame(&mut stream, format, b"too large")
+            .await
+            .unwrap();
+        write_frame(&mut stream, format, b"lost").await.unwrap();
+
+        let mut stream = UnixStream::connect(&socket_path).await.unwrap();
+        write_frame(&mut stream, format, b"hello").await.unwrap();
+
+        let (msg, _) = input.read().await.unwrap();
+        let values = msg.to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap();
+        assert_eq!(values, vec![b"hello".as_slice()]);
+        assert!(input.receiver.is_empty());
+
+        input.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_length_prefixed_oversized_frame() {
+        oversized_frame_dropped(MessageFormat::LengthPrefixed).await;
+    }
+
+    #[tokio::test]
+    async fn test_newline_oversized_frame() {
+        oversized_frame_dropped(MessageFormat::Newline).await;
+    }
+}
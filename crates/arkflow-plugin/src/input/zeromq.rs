@@ -0,0 +1,363 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! ZeroMQ input component
+//!
+//! Receive messages from a ZeroMQ SUB, PULL or REP socket
+
+use crate::component::zeromq::attach;
+use arkflow_core::input::{register_input_builder, Ack, Input, InputBuilder, NoopAck};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use flume::{Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::{oneshot, Mutex};
+use tokio_util::sync::CancellationToken;
+use tracing::error;
+use zeromq::{PullSocket, RepSocket, Socket, SocketRecv, SocketSend, SubSocket, ZmqMessage};
+
+/// ZeroMQ input configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZeroMqInputConfig {
+    /// Socket type
+    pub socket_type: ZeroMqInputSocketType,
+    /// Endpoints to bind (`tcp://*:port`) or connect to
+    pub endpoints: Vec<String>,
+    /// Topic prefixes to subscribe to (SUB sockets only, all messages if empty)
+    #[serde(default)]
+    pub subscribe_topics: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ZeroMqInputSocketType {
+    Sub,
+    Pull,
+    Rep,
+}
+
+/// A received message with the reply channel of a REP socket
+struct Received {
+    frames: Vec<Vec<u8>>,
+    reply: Option<oneshot::Sender<()>>,
+}
+
+/// ZeroMQ input component
+pub struct ZeroMqInput {
+    input_name: Option<String>,
+    config: ZeroMqInputConfig,
+    sender: Sender<Received>,
+    receiver: Receiver<Received>,
+    cancellation_token: Mutex<Option<CancellationToken>>,
+}
+
+impl ZeroMqInput {
+    /// Create a new ZeroMQ input component
+    pub fn new(name: Option<&String>, config: ZeroMqInputConfig) -> Result<Self, Error> {
+        if config.endpoints.is_empty() {
+            return Err(Error::Config(
+                "ZeroMQ input requires at least one endpoint".to_string(),
+            ));
+        }
+        let (sender, receiver) = flume::bounded::<Received>(1000);
+        Ok(Self {
+            input_name: name.cloned(),
+            config,
+            sender,
+            receiver,
+            cancellation_token: Mutex::new(None),
+        })
+    }
+
+    /// Forward every message of a one-way socket
+    async fn receive_loop<S: Socket + SocketRecv>(
+        mut socket: S,
+        sender: Sender<Received>,
+        cancellation_token: CancellationToken,
+    ) {
+        loop {
+            tokio::select! {
+                result = socket.recv() => {
+                    match result {
+                        Ok(message) => {
+                            let received = Received { frames: frames(message), reply: None };
+                            if sender.send_async(received).await.is_err() {
+                                break;
+                            }
+                        }
+                        Err(e) => {
+                            error!("ZeroMQ receive error: {}", e);
+                            break;
+                        }
+                    }
+                }
+                _ = cancellation_token.cancelled() => {
+                    break;
+                }
+            }
+        }
+        socket.close().await;
+    }
+
+    /// Receive requests one at a time, replying once the request has been acknowledged
+    async fn reply_loop(
+        mut socket: RepSocket,
+        sender: Sender<Received>,
+        cancellation_token: CancellationToken,
+    ) {
+        loop {
+            let message = tokio::select! {
+                result = socket.recv() => match result {
+                    Ok(message) => message,
+                    Err(e) => {
+                        error!("ZeroMQ receive error: {}", e);
+                        break;
+                    }
+                },
+                _ = cancellation_token.cancelled() => break,
+            };
+
+            let (reply_sender, reply_receiver) = oneshot::channel();
+            let received = Received {
+                frames: frames(message),
+                reply: Some(reply_sender),
+            };
+            if sender.send_async(received).await.is_err() {
+                break;
+            }
+
+            // A REP socket must answer before it can receive the next request. A dropped
+            // acknowledgement still gets a reply so that the requester is not blocked forever.
+            tokio::select! {
+                _ = reply_receiver => {}
+                _ = cancellation_token.cancelled() => break,
+            }
+            if let Err(e) = socket.send(ZmqMessage::from(Vec::<u8>::new())).await {
+                error!("ZeroMQ reply error: {}", e);
+            }
+        }
+        socket.close().await;
+    }
+}
+
+fn frames(message: ZmqMessage) -> Vec<Vec<u8>> {
+    message.into_vec().into_iter().map(|f| f.to_vec()).collect()
+}
+
+#[async_trait]
+impl Input for ZeroMqInput {
+    async fn connect(&self) -> Result<(), Error> {
+        let mut token_guard = self.cancellation_token.lock().await;
+        if token_guard.is_some() {
+            return Ok(());
+        }
+
+        let cancellation_token = CancellationToken::new();
+        let token = cancellation_token.clone();
+        let sender = self.sender.clone();
+        let endpoints = &self.config.endpoints;
+
+        match self.config.socket_type {
+            ZeroMqInputSocketType::Sub => {
+                let mut socket = SubSocket::new();
+                attach(&mut socket, endpoints).await?;
+                // An empty prefix subscribes to every message
+                let topics = if self.config.subscribe_topics.is_empty() {
+                    vec![String::new()]
+                } else {
+                    self.config.subscribe_topics.clone()
+                };
+                for topic in &topics {
+                    socket.subscribe(topic).await.map_err(|e| {
+                        Error::Connection(format!("Unable to subscribe to {:?}: {}", topic, e))
+                    })?;
+                }
+                tokio::spawn(Self::receive_loop(socket, sender, token));
+            }
+            ZeroMqInputSocketType::Pull => {
+                let mut socket = PullSocket::new();
+                attach(&mut socket, endpoints).await?;
+                tokio::spawn(Self::receive_loop(socket, sender, token));
+            }
+            ZeroMqInputSocketType::Rep => {
+                let mut socket = RepSocket::new();
+                attach(&mut socket, endpoints).await?;
+                tokio::spawn(Self::reply_loop(socket, sender, token));
+            }
+        }
+
+        *token_guard = Some(cancellation_token);
+        Ok(())
+    }
+
+    async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+        let Some(cancellation_token) = self.cancellation_token.lock().await.clone() else {
+            return Err(Error::Connection("The input is not connected".to_string()));
+        };
+
+        tokio::select! {
+            result = self.receiver.recv_async() => {
+                match result {
+                    Ok(received) => {
+                        let mut msg = MessageBatch::new_binary(received.frames)?;
+                        msg.set_input_name(self.input_name.clone());
+                        let ack: Arc<dyn Ack> = match received.reply {
+                            Some(reply) => Arc::new(ZeroMqReplyAck {
+                                reply: std::sync::Mutex::new(Some(reply)),
+                            }),
+                            None => Arc::new(NoopAck),
+                        };
+                        Ok((msg, ack))
+                    }
+                    Err(_) => Err(Error::EOF),
+                }
+            }
+            _ = cancellation_token.cancelled() => {
+                Err(Error::EOF)
+            }
+        }
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        if let Some(token) = self.cancellation_token.lock().await.take() {
+            token.cancel();
+        }
+        Ok(())
+    }
+}
+
+/// Acknowledgement sending the reply of a REP socket
+struct ZeroMqReplyAck {
+    reply: std::sync::Mutex<Option<oneshot::Sender<()>>>,
+}
+
+#[async_trait]
+impl Ack for ZeroMqReplyAck {
+    async fn ack(&self) {
+        let reply = self.reply.lock().ok().and_then(|mut reply| reply.take());
+        if let Some(reply) = reply {
+            let _ = reply.send(());
+        }
+    }
+}
+
+pub(crate) struct ZeroMqInputBuilder;
+impl InputBuilder for ZeroMqInputBuilder {
+    fn build(
+        &self,
+        name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Input>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "ZeroMQ input configuration is missing".to_string(),
+            ));
+        }
+
+        let config: ZeroMqInputConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(ZeroMqInput::new(name, config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_input_builder("zeromq", Arc::new(ZeroMqInputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arkflow_core::DEFAULT_BINARY_VALUE_FIELD;
+    use zeromq::{PushSocket, ReqSocket};
+
+    /// Send once the bound socket has registered the connected input
+    async fn send_when_connected<S: SocketSend>(socket: &mut S, payload: &str) {
+        for _ in 0..100 {
+            match socket.send(ZmqMessage::from(payload)).await {
+                Ok(()) => return,
+                Err(_) => tokio::time::sleep(std::time::Duration::from_millis(20)).await,
+            }
+        }
+        panic!("the input never connected");
+    }
+
+    fn config(socket_type: ZeroMqInputSocketType, endpoint: String) -> ZeroMqInputConfig {
+        ZeroMqInputConfig {
+            socket_type,
+            endpoints: vec![endpoint],
+            subscribe_topics: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pull_input() {
+        let mut push = PushSocket::new();
+        let endpoint = push.bind("tcp://127.0.0.1:0").await.unwrap();
+
+        let input = ZeroMqInput::new(
+            None,
+            config(ZeroMqInputSocketType::Pull, endpoint.to_string()),
+        )
+        .unwrap();
+        input.connect().await.unwrap();
+
+        send_when_connected(&mut push, "hello").await;
+        let (msg, _) = input.read().await.unwrap();
+        let values = msg.to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap();
+        assert_eq!(values, vec![b"hello".as_slice()]);
+
+        input.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rep_input_replies_on_ack() {
+        let mut req = ReqSocket::new();
+        let endpoint = req.bind("tcp://127.0.0.1:0").await.unwrap();
+
+        let input = ZeroMqInput::new(
+            None,
+            config(ZeroMqInputSocketType::Rep, endpoint.to_string()),
+        )
+        .unwrap();
+        input.connect().await.unwrap();
+
+        send_when_connected(&mut req, "request").await;
+        let (msg, ack) = input.read().await.unwrap();
+        let values = msg.to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap();
+        assert_eq!(values, vec![b"request".as_slice()]);
+
+        ack.ack().await;
+        let reply = tokio::time::timeout(std::time::Duration::from_secs(5), req.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(reply.get(0).unwrap().is_empty());
+
+        input.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_empty_endpoints() {
+        let result = ZeroMqInput::new(
+            None,
+            ZeroMqInputConfig {
+                socket_type: ZeroMqInputSocketType::Sub,
+                endpoints: vec![],
+                subscribe_topics: vec![],
+            },
+        );
+        assert!(matches!(result, Err(Error::Config(_))));
+    }
+}
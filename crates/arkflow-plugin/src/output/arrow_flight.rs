@@ -0,0 +1,410 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Arrow Flight output component
+//!
+//! Serve the processed record batches to Arrow Flight clients
+
+use crate::component::arrow_flight::TlsConfig;
+use arkflow_core::output::{register_output_builder, Output, OutputBuilder};
+use arkflow_core::{Error, MessageBatch, Resource};
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::error::FlightError;
+use arrow_flight::flight_service_server::{FlightService, FlightServiceServer};
+use arrow_flight::{
+    Action, ActionType, Criteria, Empty, FlightData, FlightDescriptor, FlightEndpoint, FlightInfo,
+    HandshakeRequest, HandshakeResponse, PollInfo, PutResult, SchemaResult, Ticket,
+};
+use async_trait::async_trait;
+use datafusion::arrow::record_batch::RecordBatch;
+use futures_util::stream::{self, BoxStream};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status, Streaming};
+use tracing::{debug, error, warn};
+
+/// Arrow Flight output configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArrowFlightOutputConfig {
+    /// Address the Flight server listens on, e.g. `0.0.0.0:50051`
+    pub bind_address: String,
+    /// TLS settings, the server certificate and key are required
+    pub tls: Option<TlsConfig>,
+    /// Maximum size in bytes of a gRPC message sent or received
+    #[serde(default = "default_max_grpc_message_size")]
+    pub max_grpc_message_size: usize,
+}
+
+fn default_max_grpc_message_size() -> usize {
+    4 * 1024 * 1024
+}
+
+/// Batches written and not yet served
+#[derive(Default)]
+struct BatchQueue {
+    batches: RwLock<VecDeque<RecordBatch>>,
+    /// Number of queued batches
+    in_flight: AtomicUsize,
+}
+
+impl BatchQueue {
+    async fn push(&self, batch: RecordBatch) {
+        self.batches.write().await.push_back(batch);
+        let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        debug!("Arrow Flight output has {} batches in flight", in_flight);
+    }
+
+    /// Take the leading batches that share the schema of the first one
+    async fn take(&self) -> Vec<RecordBatch> {
+        let mut batches = self.batches.write().await;
+        let Some(schema) = batches.front().map(|batch| batch.schema()) else {
+            return vec![];
+        };
+        let count = batches
+            .iter()
+            .take_while(|batch| batch.schema() == schema)
+            .count();
+        self.in_flight.fetch_sub(count, Ordering::SeqCst);
+        batches.drain(..count).collect()
+    }
+}
+
+/// Flight service serving the queued batches with DoGet
+struct QueueFlightService {
+    queue: Arc<BatchQueue>,
+}
+
+#[tonic::async_trait]
+impl FlightService for QueueFlightService {
+    type HandshakeStream = BoxStream<'static, Result<HandshakeResponse, Status>>;
+    type ListFlightsStream = BoxStream<'static, Result<FlightInfo, Status>>;
+    type DoGetStream = BoxStream<'static, Result<FlightData, Status>>;
+    type DoPutStream = BoxStream<'static, Result<PutResult, Status>>;
+    type DoExchangeStream = BoxStream<'static, Result<FlightData, Status>>;
+    type DoActionStream = BoxStream<'static, Result<arrow_flight::Result, Status>>;
+    type ListActionsStream = BoxStream<'static, Result<ActionType, Status>>;
+
+    async fn handshake(
+        &self,
+        _request: Request<Streaming<HandshakeRequest>>,
+    ) -> Result<Response<Self::HandshakeStream>, Status> {
+        Err(Status::unimplemented("Handshake is not supported"))
+    }
+
+    async fn list_flights(
+        &self,
+        _request: Request<Criteria>,
+    ) -> Result<Response<Self::ListFlightsStream>, Status> {
+        Err(Status::unimplemented("ListFlights is not supported"))
+    }
+
+    /// Every descriptor refers to the queue, fetched with a single endpoint
+    async fn get_flight_info(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let mut info = FlightInfo::new()
+            .with_descriptor(request.into_inner())
+            .with_endpoint(FlightEndpoint::new().with_ticket(Ticket::new("")));
+        if let Some(batch) = self.queue.batches.read().await.front() {
+            info = info
+                .try_with_schema(&batch.schema())
+                .map_err(|e| Status::internal(e.to_string()))?;
+        }
+        Ok(Response::new(info))
+    }
+
+    async fn poll_flight_info(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<PollInfo>, Status> {
+        Err(Status::unimplemented("PollFlightInfo is not supported"))
+    }
+
+    async fn get_schema(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<SchemaResult>, Status> {
+        Err(Status::unimplemented("GetSchema is not supported"))
+    }
+
+    // The Flight service API returns tonic statuses
+    #[allow(clippy::result_large_err)]
+    async fn do_get(
+        &self,
+        _request: Request<Ticket>,
+    ) -> Result<Response<Self::DoGetStream>, Status> {
+        let batches = self.queue.take().await;
+        let stream = FlightDataEncoderBuilder::new()
+            .build(stream::iter(batches.into_iter().map(Ok::<_, FlightError>)))
+            .map(|data| data.map_err(Status::from));
+        Ok(Response::new(stream.boxed()))
+    }
+
+    async fn do_put(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoPutStream>, Status> {
+        Err(Status::unimplemented("DoPut is not supported"))
+    }
+
+    async fn do_exchange(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoExchangeStream>, Status> {
+        Err(Status::unimplemented("DoExchange is not supported"))
+    }
+
+    async fn do_action(
+        &self,
+        _request: Request<Action>,
+    ) -> Result<Response<Self::DoActionStream>, Status> {
+        Err(Status::unimplemented("DoAction is not supported"))
+    }
+
+    async fn list_actions(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<Self::ListActionsStream>, Status> {
+        Ok(Response::new(stream::empty().boxed()))
+    }
+}
+
+struct RunningServer {
+    address: SocketAddr,
+    cancellation_token: CancellationToken,
+    handle: JoinHandle<()>,
+}
+
+/// Arrow Flight output component
+struct ArrowFlightOutput {
+    config: ArrowFlightOutputConfig,
+    queue: Arc<BatchQueue>,
+    server: Mutex<Option<RunningServer>>,
+}
+
+impl ArrowFlightOutput {
+    /// Create a new Arrow Flight output component
+    fn new(config: ArrowFlightOutputConfig) -> Result<Self, Error> {
+        Ok(Self {
+            config,
+            queue: Arc::new(BatchQueue::default()),
+            server: Mutex::new(None),
+        })
+    }
+}
+
+#[async_trait]
+impl Output for ArrowFlightOutput {
+    async fn connect(&self) -> Result<(), Error> {
+        let mut server = self.server.lock().await;
+        if server.is_some() {
+            return Ok(());
+        }
+
+        let mut builder = Server::builder();
+        if let Some(tls) = &self.config.tls {
+            builder = builder
+                .tls_config(tls.server_config()?)
+                .map_err(|e| Error::Config(format!("Invalid Flight TLS configuration: {}", e)))?;
+        }
+        let service = FlightServiceServer::new(QueueFlightService {
+            queue: Arc::clone(&self.queue),
+        })
+        .max_decoding_message_size(self.config.max_grpc_message_size)
+        .max_encoding_message_size(self.config.max_grpc_message_size);
+
+        let listener = TcpListener::bind(&self.config.bind_address)
+            .await
+            .map_err(|e| {
+                Error::Connection(format!(
+                    "Failed to bind Flight server to {}: {}",
+                    self.config.bind_address, e
+                ))
+            })?;
+        let address = listener.local_addr()?;
+        let incoming = TcpIncoming::from_listener(listener, true, None)
+            .map_err(|e| Error::Connection(format!("Failed to start Flight server: {}", e)))?;
+
+        let cancellation_token = CancellationToken::new();
+        let shutdown = cancellation_token.clone();
+        let router = builder.add_service(service);
+        let handle = tokio::spawn(async move {
+            if let Err(e) = router
+                .serve_with_incoming_shutdown(incoming, shutdown.cancelled_owned())
+                .await
+            {
+                error!("Arrow Flight server failed: {}", e);
+            }
+        });
+
+        *server = Some(RunningServer {
+            address,
+            cancellation_token,
+            handle,
+        });
+        Ok(())
+    }
+
+    async fn write(&self, msg: MessageBatch) -> Result<(), Error> {
+        if self.server.lock().await.is_none() {
+            return Err(Error::Connection("The output is not connected".to_string()));
+        }
+        if msg.num_rows() > 0 {
+            self.queue.push(msg.into()).await;
+        }
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        let Some(server) = self.server.lock().await.take() else {
+            return Ok(());
+        };
+        server.cancellation_token.cancel();
+        if let Err(e) = server.handle.await {
+            error!("Arrow Flight server task failed: {}", e);
+        }
+
+        let in_flight = self.queue.in_flight.load(Ordering::SeqCst);
+        if in_flight > 0 {
+            warn!(
+                "Arrow Flight output closed with {} batches never fetched",
+                in_flight
+            );
+        }
+        Ok(())
+    }
+}
+
+pub(crate) struct ArrowFlightOutputBuilder;
+impl OutputBuilder for ArrowFlightOutputBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Output>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Arrow Flight output configuration is missing".to_string(),
+            ));
+        }
+        let config: ArrowFlightOutputConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(ArrowFlightOutput::new(config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_output_builder("arrow_flight", Arc::new(ArrowFlightOutputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::arrow_flight::{
+        ArrowFlightInput, ArrowFlightInputConfig, FlightDescriptorConfig,
+    };
+    use arkflow_core::input::Input;
+    use datafusion::arrow::array::{Int64Array, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+
+    fn batch(ids: Vec<i64>) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        RecordBatch::try_new(schema, vec![Arc::new(Int64Array::from(ids))]).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_serve_queued_batches() {
+        let output = ArrowFlightOutput::new(ArrowFlightOutputConfig {
+            bind_address: "127.0.0.1:0".to_string(),
+            tls: None,
+            max_grpc_message_size: default_max_grpc_message_size(),
+        })
+        .unwrap();
+        output.connect().await.unwrap();
+        let address = output.server.lock().await.as_ref().unwrap().address;
+
+        output
+            .write(MessageBatch::new_arrow(batch(vec![1, 2])))
+            .await
+            .unwrap();
+        output
+            .write(MessageBatch::new_arrow(batch(vec![3])))
+            .await
+            .unwrap();
+        let other_schema = Arc::new(Schema::new(vec![Field::new("name", DataType::Utf8, false)]));
+        let other =
+            RecordBatch::try_new(other_schema, vec![Arc::new(StringArray::from(vec!["a"]))])
+                .unwrap();
+        output
+            .write(MessageBatch::new_arrow(other.clone()))
+            .await
+            .unwrap();
+        assert_eq!(output.queue.in_flight.load(Ordering::SeqCst), 3);
+
+        let input = ArrowFlightInput::new(
+            None,
+            ArrowFlightInputConfig {
+                endpoint: format!("http://{}", address),
+                action: None,
+                descriptor: FlightDescriptorConfig::Path {
+                    path: vec!["queue".to_string()],
+                },
+                tls: None,
+            },
+        )
+        .unwrap();
+
+        // The first fetch serves the batches sharing the schema of the oldest batch
+        input.connect().await.unwrap();
+        let (first, _) = input.read().await.unwrap();
+        let (second, _) = input.read().await.unwrap();
+        assert_eq!(RecordBatch::from(first), batch(vec![1, 2]));
+        assert_eq!(RecordBatch::from(second), batch(vec![3]));
+        assert!(matches!(input.read().await, Err(Error::EOF)));
+        assert_eq!(output.queue.in_flight.load(Ordering::SeqCst), 1);
+
+        input.connect().await.unwrap();
+        let (third, _) = input.read().await.unwrap();
+        assert_eq!(RecordBatch::from(third), other);
+        assert_eq!(output.queue.in_flight.load(Ordering::SeqCst), 0);
+
+        input.close().await.unwrap();
+        output.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_write_not_connected() {
+        let output = ArrowFlightOutput::new(ArrowFlightOutputConfig {
+            bind_address: "127.0.0.1:0".to_string(),
+            tls: None,
+            max_grpc_message_size: default_max_grpc_message_size(),
+        })
+        .unwrap();
+        assert!(matches!(
+            output.write(MessageBatch::new_arrow(batch(vec![1]))).await,
+            Err(Error::Connection(_))
+        ));
+    }
+}
@@ -0,0 +1,161 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Content type router output component
+//!
+//! Write binary messages and Arrow messages to different outputs
+
+use arkflow_core::output::{register_output_builder, Output, OutputBuilder, OutputConfig};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Content type router output configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ContentTypeRouterOutputConfig {
+    /// Output for Arrow messages
+    arrow_output: OutputConfig,
+    /// Output for binary messages
+    binary_output: OutputConfig,
+}
+
+/// Content type router output component
+struct ContentTypeRouterOutput {
+    arrow_output: Arc<dyn Output>,
+    binary_output: Arc<dyn Output>,
+}
+
+impl ContentTypeRouterOutput {
+    /// Create a new content type router output component
+    fn new(config: ContentTypeRouterOutputConfig, resource: &Resource) -> Result<Self, Error> {
+        Ok(Self {
+            arrow_output: config.arrow_output.build(resource)?,
+            binary_output: config.binary_output.build(resource)?,
+        })
+    }
+}
+
+#[async_trait]
+impl Output for ContentTypeRouterOutput {
+    async fn connect(&self) -> Result<(), Error> {
+        self.arrow_output.connect().await?;
+        self.binary_output.connect().await
+    }
+
+    async fn write(&self, msg: MessageBatch) -> Result<(), Error> {
+        if msg.is_binary() {
+            self.binary_output.write(msg).await
+        } else {
+            self.arrow_output.write(msg).await
+        }
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        let arrow_result = self.arrow_output.close().await;
+        let binary_result = self.binary_output.close().await;
+        arrow_result.and(binary_result)
+    }
+}
+
+struct ContentTypeRouterOutputBuilder;
+
+impl OutputBuilder for ContentTypeRouterOutputBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        resource: &Resource,
+    ) -> Result<Arc<dyn Output>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Content type router output configuration is missing".to_string(),
+            ));
+        }
+        let config: ContentTypeRouterOutputConfig =
+            serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(ContentTypeRouterOutput::new(config, resource)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_output_builder(
+        "content_type_router",
+        Arc::new(ContentTypeRouterOutputBuilder),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::Int64Array;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct CaptureOutput {
+        written: Mutex<Vec<MessageBatch>>,
+        connected: Mutex<bool>,
+    }
+
+    #[async_trait]
+    impl Output for CaptureOutput {
+        async fn connect(&self) -> Result<(), Error> {
+            *self.connected.lock().unwrap() = true;
+            Ok(())
+        }
+
+        async fn write(&self, msg: MessageBatch) -> Result<(), Error> {
+            self.written.lock().unwrap().push(msg);
+            Ok(())
+        }
+
+        async fn close(&self) -> Result<(), Error> {
+            *self.connected.lock().unwrap() = false;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_route_by_content_type() {
+        let arrow_output = Arc::new(CaptureOutput::default());
+        let binary_output = Arc::new(CaptureOutput::default());
+        let router = ContentTypeRouterOutput {
+            arrow_output: arrow_output.clone(),
+            binary_output: binary_output.clone(),
+        };
+        router.connect().await.unwrap();
+        assert!(*arrow_output.connected.lock().unwrap());
+        assert!(*binary_output.connected.lock().unwrap());
+
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(Int64Array::from(vec![1, 2]))]).unwrap();
+        router.write(MessageBatch::new_arrow(batch)).await.unwrap();
+        router
+            .write(MessageBatch::new_binary(vec![b"{\"id\":3}".to_vec()]).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(arrow_output.written.lock().unwrap()[0].len(), 2);
+        assert_eq!(binary_output.written.lock().unwrap()[0].len(), 1);
+        assert_eq!(arrow_output.written.lock().unwrap().len(), 1);
+        assert_eq!(binary_output.written.lock().unwrap().len(), 1);
+
+        router.close().await.unwrap();
+        assert!(!*arrow_output.connected.lock().unwrap());
+        assert!(!*binary_output.connected.lock().unwrap());
+    }
+}
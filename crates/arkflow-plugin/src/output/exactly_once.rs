@@ -0,0 +1,360 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Exactly-once output component
+//!
+//! Wraps an output with a write-ahead log so that batches written before a crash are
+//! re-submitted on restart. Every batch is given an increasing id and appended to the log
+//! as an Arrow IPC stream before it is written to the inner output; the id is recorded as
+//! committed once the write succeeds. When the output connects, log entries above the last
+//! committed id are written again, which gives exactly-once results with idempotent sinks.
+//!
+//! Writes are serialized: a batch is logged, written to the inner output and committed before
+//! the next batch is logged. The log is read and written on the blocking thread pool.
+
+use arkflow_core::output::{register_output_builder, Output, OutputBuilder, OutputConfig};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use datafusion::arrow::ipc::reader::StreamReader;
+use datafusion::arrow::ipc::writer::StreamWriter;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+const WAL_FILE: &str = "wal";
+const COMMITTED_FILE: &str = "committed";
+/// Size of the id and length header of a log entry
+const ENTRY_HEADER_LEN: usize = 16;
+
+/// Exactly-once output configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ExactlyOnceOutputConfig {
+    /// Output the batches are written to
+    inner: OutputConfig,
+    /// Directory of the write-ahead log and the committed batch id
+    checkpoint_path: String,
+}
+
+/// Exactly-once output component
+struct ExactlyOnceOutput {
+    inner: Arc<dyn Output>,
+    committed_path: PathBuf,
+    /// Held for the whole write of a batch, which serializes writes
+    state: Mutex<WalState>,
+}
+
+struct WalState {
+    wal: File,
+    /// Id of the next batch
+    next_id: u64,
+    /// Highest committed batch id, 0 if none
+    committed: u64,
+}
+
+impl ExactlyOnceOutput {
+    /// Create a new exactly-once output component
+    fn new(inner: Arc<dyn Output>, checkpoint_path: &Path) -> Result<Self, Error> {
+        std::fs::create_dir_all(checkpoint_path)?;
+        let committed_path = checkpoint_path.join(COMMITTED_FILE);
+        let committed = read_committed(&committed_path)?;
+
+        let mut wal = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(checkpoint_path.join(WAL_FILE))?;
+        let last_id = read_wal(&mut wal)?
+            .last()
+            .map_or(0, |(batch_id, _)| *batch_id);
+
+        Ok(Self {
+            inner,
+            committed_path,
+            state: Mutex::new(WalState {
+                wal,
+                next_id: committed.max(last_id) + 1,
+                committed,
+            }),
+        })
+    }
+
+    /// Record `batch_id` as committed and discard the log entries, which are all committed
+    async fn commit(&self, state: &mut WalState, batch_id: u64) -> Result<(), Error> {
+        let committed_path = self.committed_path.clone();
+        blocking(move || write_committed(&committed_path, batch_id)).await?;
+        state.committed = batch_id;
+        truncate_wal(&state.wal).await
+    }
+}
+
+/// Run blocking file I/O on the blocking thread pool
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, Error> + Send + 'static,
+) -> Result<T, Error> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| Error::Process(format!("Write-ahead log task failed: {}", e)))?
+}
+
+/// Discard all the entries of the write-ahead log
+async fn truncate_wal(wal: &File) -> Result<(), Error> {
+    let wal = wal.try_clone()?;
+    blocking(move || Ok(wal.set_len(0)?)).await
+}
+
+/// Record `batch_id` as the highest committed batch id
+fn write_committed(path: &Path, batch_id: u64) -> Result<(), Error> {
+    // Write to a temporary file first so that a crash never leaves a truncated id behind
+    let tmp_path = path.with_extension("tmp");
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(batch_id.to_string().as_bytes())?;
+    file.sync_all()?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+#[async_trait]
+impl Output for ExactlyOnceOutput {
+    async fn connect(&self) -> Result<(), Error> {
+        self.inner.connect().await?;
+
+        let mut state = self.state.lock().await;
+        let mut wal = state.wal.try_clone()?;
+        let entries = blocking(move || read_wal(&mut wal)).await?;
+        for (batch_id, msg) in entries {
+            // Written before the last commit, the crash happened before the log was cleared
+            if batch_id <= state.committed {
+                continue;
+            }
+            self.inner.write(msg).await?;
+            self.commit(&mut state, batch_id).await?;
+        }
+        truncate_wal(&state.wal).await
+    }
+
+    async fn write(&self, msg: MessageBatch) -> Result<(), Error> {
+        if msg.try_as_struct().is_ok() {
+            return Err(Error::Process(
+                "Struct messages cannot be written to the exactly-once log".to_string(),
+            ));
+        }
+
+        let mut state = self.state.lock().await;
+        let batch_id = state.next_id;
+        state.next_id += 1;
+
+        let entry = encode_entry(batch_id, &msg)?;
+        let mut wal = state.wal.try_clone()?;
+        blocking(move || {
+            wal.write_all(&entry)?;
+            wal.sync_data()?;
+            Ok(())
+        })
+        .await?;
+
+        match self.inner.write(msg).await {
+            Ok(()) => self.commit(&mut state, batch_id).await,
+            Err(e) => {
+                // The failure is handled by the stream, the batch must not be replayed
+                truncate_wal(&state.wal).await?;
+                Err(e)
+            }
+        }
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        self.inner.close().await
+    }
+}
+
+/// Encode a log entry: the batch id and the length of the batch as little-endian `u64`,
+/// followed by the batch as an Arrow IPC stream
+fn encode_entry(batch_id: u64, msg: &MessageBatch) -> Result<Vec<u8>, Error> {
+    let mut entry = Vec::new();
+    entry.extend_from_slice(&batch_id.to_le_bytes());
+    entry.extend_from_slice(&[0; 8]);
+    let mut writer = StreamWriter::try_new(&mut entry, &msg.schema())
+        .map_err(|e| Error::Process(format!("Failed to encode batch: {}", e)))?;
+    writer
+        .write(msg)
+        .and_then(|_| writer.finish())
+        .map_err(|e| Error::Process(format!("Failed to encode batch: {}", e)))?;
+    drop(writer);
+    let len = (entry.len() - ENTRY_HEADER_LEN) as u64;
+    entry[8..ENTRY_HEADER_LEN].copy_from_slice(&len.to_le_bytes());
+    Ok(entry)
+}
+
+/// Read the highest committed batch id, 0 if none was committed
+fn read_committed(path: &Path) -> Result<u64, Error> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => content.trim().parse().map_err(|e| {
+            Error::Read(format!(
+                "Invalid committed batch id {}: {}",
+                path.display(),
+                e
+            ))
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Read the entries of the write-ahead log, oldest first.
+///
+/// A truncated last entry, left by a crash while it was appended, is ignored.
+fn read_wal(wal: &mut File) -> Result<Vec<(u64, MessageBatch)>, Error> {
+    let mut data = Vec::new();
+    wal.seek(SeekFrom::Start(0))?;
+    wal.read_to_end(&mut data)?;
+
+    let mut entries = Vec::new();
+    let mut rest = data.as_slice();
+    while rest.len() >= ENTRY_HEADER_LEN {
+        let batch_id = u64::from_le_bytes(rest[..8].try_into().unwrap());
+        let len = u64::from_le_bytes(rest[8..ENTRY_HEADER_LEN].try_into().unwrap()) as usize;
+        let Some(encoded) = rest[ENTRY_HEADER_LEN..].get(..len) else {
+            break;
+        };
+        let reader = StreamReader::try_new(encoded, None)
+            .map_err(|e| Error::Read(format!("Invalid write-ahead log entry: {}", e)))?;
+        for batch in reader {
+            let batch =
+                batch.map_err(|e| Error::Read(format!("Invalid write-ahead log entry: {}", e)))?;
+            entries.push((batch_id, MessageBatch::new_arrow(batch)));
+        }
+        rest = &rest[ENTRY_HEADER_LEN + len..];
+    }
+    Ok(entries)
+}
+
+struct ExactlyOnceOutputBuilder;
+
+impl OutputBuilder for ExactlyOnceOutputBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        resource: &Resource,
+    ) -> Result<Arc<dyn Output>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Exactly-once output configuration is missing".to_string(),
+            ));
+        }
+        let config: ExactlyOnceOutputConfig = serde_json::from_value(config.clone().unwrap())?;
+        let inner = config.inner.build(resource)?;
+        Ok(Arc::new(ExactlyOnceOutput::new(
+            inner,
+            Path::new(&config.checkpoint_path),
+        )?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_output_builder("exactly_once", Arc::new(ExactlyOnceOutputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Array, Int64Array};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[derive(Default)]
+    struct CaptureOutput {
+        written: std::sync::Mutex<Vec<i64>>,
+        fail: AtomicBool,
+    }
+
+    #[async_trait]
+    impl Output for CaptureOutput {
+        async fn connect(&self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        async fn write(&self, msg: MessageBatch) -> Result<(), Error> {
+            if self.fail.load(Ordering::SeqCst) {
+                return Err(Error::Disconnection);
+            }
+            let values = msg.column(0).as_any().downcast_ref::<Int64Array>().unwrap();
+            self.written.lock().unwrap().extend(values.values());
+            Ok(())
+        }
+
+        async fn close(&self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    fn batch(value: i64) -> MessageBatch {
+        let schema = Arc::new(Schema::new(vec![Field::new("v", DataType::Int64, false)]));
+        MessageBatch::new_arrow(
+            RecordBatch::try_new(schema, vec![Arc::new(Int64Array::from(vec![value]))]).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_write_commits_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let inner = Arc::new(CaptureOutput::default());
+        let output = ExactlyOnceOutput::new(inner.clone(), dir.path()).unwrap();
+        output.connect().await.unwrap();
+
+        output.write(batch(1)).await.unwrap();
+        output.write(batch(2)).await.unwrap();
+        assert_eq!(*inner.written.lock().unwrap(), vec![1, 2]);
+        assert_eq!(read_committed(&dir.path().join(COMMITTED_FILE)).unwrap(), 2);
+        assert_eq!(
+            std::fs::metadata(dir.path().join(WAL_FILE)).unwrap().len(),
+            0
+        );
+
+        // A failed write is left to the stream and not replayed
+        inner.fail.store(true, Ordering::SeqCst);
+        assert!(output.write(batch(3)).await.is_err());
+        inner.fail.store(false, Ordering::SeqCst);
+        let output = ExactlyOnceOutput::new(inner.clone(), dir.path()).unwrap();
+        output.connect().await.unwrap();
+        assert_eq!(*inner.written.lock().unwrap(), vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_replay_uncommitted_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        // Batch 1 was committed but the crash happened before the log was cleared, and the
+        // crash happened while batch 4 was appended
+        let mut wal = Vec::new();
+        for value in 1..=3 {
+            wal.extend(encode_entry(value as u64, &batch(value)).unwrap());
+        }
+        wal.extend_from_slice(&4u64.to_le_bytes());
+        std::fs::write(dir.path().join(WAL_FILE), &wal).unwrap();
+        std::fs::write(dir.path().join(COMMITTED_FILE), "1").unwrap();
+
+        let inner = Arc::new(CaptureOutput::default());
+        let output = ExactlyOnceOutput::new(inner.clone(), dir.path()).unwrap();
+        output.connect().await.unwrap();
+        assert_eq!(*inner.written.lock().unwrap(), vec![2, 3]);
+        assert_eq!(read_committed(&dir.path().join(COMMITTED_FILE)).unwrap(), 3);
+
+        output.write(batch(4)).await.unwrap();
+        assert_eq!(read_committed(&dir.path().join(COMMITTED_FILE)).unwrap(), 4);
+    }
+}
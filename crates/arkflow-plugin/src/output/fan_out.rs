@@ -0,0 +1,322 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Fan-out output component
+//!
+//! Write every batch to several outputs. Outputs supporting transactions are written with a
+//! two-phase commit: the batch is written in a transaction on each of them before any commits,
+//! so a failed write leaves no output with the batch. The commits themselves are not atomic: if
+//! one fails, the outputs committed before it keep the batch. Other outputs are written once the
+//! transactions are committed.
+
+use arkflow_core::output::{
+    register_output_builder, Output, OutputBuilder, OutputConfig, TransactionCoordinator,
+    TransactionId,
+};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::error;
+
+/// Fan-out output configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FanOutOutputConfig {
+    /// Outputs every batch is written to
+    outputs: Vec<OutputConfig>,
+}
+
+/// Fan-out output component
+struct FanOutOutput {
+    outputs: Vec<Arc<dyn Output>>,
+    /// Held for the duration of a two-phase commit, so that transactions do not overlap
+    transaction_lock: Mutex<()>,
+}
+
+impl FanOutOutput {
+    fn new(outputs: Vec<Arc<dyn Output>>) -> Result<Self, Error> {
+        if outputs.is_empty() {
+            return Err(Error::Config(
+                "Fan-out output requires at least one output".to_string(),
+            ));
+        }
+        Ok(Self {
+            outputs,
+            transaction_lock: Mutex::new(()),
+        })
+    }
+}
+
+/// Roll back the transactions, logging the ones that cannot be rolled back
+async fn rollback(transactions: &[(&dyn TransactionCoordinator, TransactionId)]) {
+    for (coordinator, id) in transactions {
+        if let Err(e) = coordinator.rollback(*id).await {
+            error!("Failed to roll back transaction {}: {}", id, e);
+        }
+    }
+}
+
+#[async_trait]
+impl Output for FanOutOutput {
+    async fn connect(&self) -> Result<(), Error> {
+        for output in &self.outputs {
+            output.connect().await?;
+        }
+        Ok(())
+    }
+
+    async fn write(&self, msg: MessageBatch) -> Result<(), Error> {
+        let _guard = self.transaction_lock.lock().await;
+        // Position of each transactional output in `outputs`, used in logs
+        let transactional: Vec<(usize, &Arc<dyn Output>, &dyn TransactionCoordinator)> = self
+            .outputs
+            .iter()
+            .enumerate()
+            .filter_map(|(i, output)| Some((i, output, output.transaction_coordinator()?)))
+            .collect();
+
+        // Phase 1: open a transaction on every output and write the batch in it
+        let mut transactions = Vec::with_capacity(transactional.len());
+        for (_, _, coordinator) in &transactional {
+            match coordinator.begin().await {
+                Ok(id) => transactions.push((*coordinator, id)),
+                Err(e) => {
+                    rollback(&transactions).await;
+                    return Err(e);
+                }
+            }
+        }
+        for (_, output, _) in &transactional {
+            if let Err(e) = output.write(msg.clone()).await {
+                rollback(&transactions).await;
+                return Err(e);
+            }
+        }
+
+        // Phase 2: commit every transaction. A failed commit cannot undo the ones before it, so
+        // only the transactions not yet committed are rolled back.
+        for (i, (coordinator, id)) in transactions.iter().enumerate() {
+            if let Err(e) = coordinator.commit(*id).await {
+                if i > 0 {
+                    let committed: Vec<String> = transactional[..i]
+                        .iter()
+                        .map(|(position, _, _)| format!("#{}", position + 1))
+                        .collect();
+                    error!(
+                        "Fan-out commit of output #{} failed, outputs {} already committed the batch",
+                        transactional[i].0 + 1,
+                        committed.join(", ")
+                    );
+                }
+                rollback(&transactions[i + 1..]).await;
+                return Err(Error::Process(format!("2PC failed: {}", e)));
+            }
+        }
+
+        for output in &self.outputs {
+            if output.transaction_coordinator().is_none() {
+                output.write(msg.clone()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        let mut result = Ok(());
+        for output in &self.outputs {
+            let closed = output.close().await;
+            result = result.and(closed);
+        }
+        result
+    }
+}
+
+struct FanOutOutputBuilder;
+impl OutputBuilder for FanOutOutputBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        resource: &Resource,
+    ) -> Result<Arc<dyn Output>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Fan-out output configuration is missing".to_string(),
+            ));
+        }
+        let config: FanOutOutputConfig = serde_json::from_value(config.clone().unwrap())?;
+        let outputs = config
+            .outputs
+            .iter()
+            .map(|output| output.build(resource))
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Arc::new(FanOutOutput::new(outputs)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_output_builder("fan_out", Arc::new(FanOutOutputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex as StdMutex;
+
+    /// Output recording its writes and transaction calls in a shared log
+    struct RecordingOutput {
+        name: &'static str,
+        log: Arc<StdMutex<Vec<String>>>,
+        transactional: bool,
+        fail_write: bool,
+        fail_commit: bool,
+    }
+
+    impl RecordingOutput {
+        fn new(name: &'static str, log: &Arc<StdMutex<Vec<String>>>) -> Self {
+            Self {
+                name,
+                log: log.clone(),
+                transactional: true,
+                fail_write: false,
+                fail_commit: false,
+            }
+        }
+
+        fn record(&self, event: &str) {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("{} {}", self.name, event));
+        }
+    }
+
+    #[async_trait]
+    impl Output for RecordingOutput {
+        async fn connect(&self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        async fn write(&self, _msg: MessageBatch) -> Result<(), Error> {
+            self.record("write");
+            if self.fail_write {
+                return Err(Error::Process("write failed".to_string()));
+            }
+            Ok(())
+        }
+
+        async fn close(&self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn transaction_coordinator(&self) -> Option<&dyn TransactionCoordinator> {
+            self.transactional
+                .then_some(self as &dyn TransactionCoordinator)
+        }
+    }
+
+    #[async_trait]
+    impl TransactionCoordinator for RecordingOutput {
+        async fn begin(&self) -> Result<TransactionId, Error> {
+            self.record("begin");
+            Ok(1)
+        }
+
+        async fn commit(&self, _id: TransactionId) -> Result<(), Error> {
+            self.record("commit");
+            if self.fail_commit {
+                return Err(Error::Process("commit failed".to_string()));
+            }
+            Ok(())
+        }
+
+        async fn rollback(&self, _id: TransactionId) -> Result<(), Error> {
+            self.record("rollback");
+            Ok(())
+        }
+    }
+
+    fn msg() -> MessageBatch {
+        MessageBatch::new_binary(vec![b"x".to_vec()]).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_two_phase_commit() {
+        let log = Arc::new(StdMutex::new(Vec::new()));
+        let mut stdout = RecordingOutput::new("stdout", &log);
+        stdout.transactional = false;
+        let output = FanOutOutput::new(vec![
+            Arc::new(stdout),
+            Arc::new(RecordingOutput::new("sql", &log)),
+            Arc::new(RecordingOutput::new("kafka", &log)),
+        ])
+        .unwrap();
+
+        output.write(msg()).await.unwrap();
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                "sql begin",
+                "kafka begin",
+                "sql write",
+                "kafka write",
+                "sql commit",
+                "kafka commit",
+                "stdout write",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_failure_rolls_back() {
+        let log = Arc::new(StdMutex::new(Vec::new()));
+        let mut kafka = RecordingOutput::new("kafka", &log);
+        kafka.fail_write = true;
+        let output = FanOutOutput::new(vec![
+            Arc::new(RecordingOutput::new("sql", &log)),
+            Arc::new(kafka),
+        ])
+        .unwrap();
+
+        assert!(output.write(msg()).await.is_err());
+        let log = log.lock().unwrap();
+        assert!(!log.iter().any(|event| event.ends_with("commit")));
+        assert!(log.contains(&"sql rollback".to_string()));
+        assert!(log.contains(&"kafka rollback".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_commit_failure_rolls_back_uncommitted() {
+        let log = Arc::new(StdMutex::new(Vec::new()));
+        let mut kafka = RecordingOutput::new("kafka", &log);
+        kafka.fail_commit = true;
+        let output = FanOutOutput::new(vec![
+            Arc::new(RecordingOutput::new("sql", &log)),
+            Arc::new(kafka),
+            Arc::new(RecordingOutput::new("redis", &log)),
+        ])
+        .unwrap();
+
+        let result = output.write(msg()).await;
+        assert!(matches!(result, Err(Error::Process(e)) if e.starts_with("2PC failed")));
+        // sql was committed before kafka failed and keeps the batch
+        let log = log.lock().unwrap();
+        assert!(log.contains(&"sql commit".to_string()));
+        assert!(!log.contains(&"sql rollback".to_string()));
+        assert!(log.contains(&"redis rollback".to_string()));
+        assert!(!log.contains(&"kafka rollback".to_string()));
+        assert!(!log.contains(&"redis commit".to_string()));
+    }
+}
@@ -0,0 +1,615 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! gRPC output component
+//!
+//! Send every row as a Protobuf message of a client streaming RPC. Rows are buffered and each
+//! flush sends the buffered messages with a single call.
+
+use crate::component::arrow_flight::TlsConfig;
+use crate::component::protobuf::arrow_to_dynamic_messages;
+use arkflow_core::output::{register_output_builder, Output, OutputBuilder};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use futures_util::stream;
+use prost_reflect::prost::bytes::Buf;
+use prost_reflect::prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tonic::client::Grpc;
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::metadata::{Ascii, MetadataKey, MetadataMap, MetadataValue};
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Request, Status};
+use tracing::{error, warn};
+
+/// gRPC output configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GrpcOutputConfig {
+    /// URL of the gRPC server, e.g. `http://localhost:50051`
+    pub endpoint: String,
+    /// Fully qualified name of the service, e.g. `ingest.v1.IngestService`
+    pub service: String,
+    /// Name of the client streaming method of the service
+    pub method: String,
+    /// File descriptor set containing the service, as written by `protoc --descriptor_set_out`
+    pub proto_descriptor: String,
+    /// TLS settings, required for `https` endpoints with a private CA or client certificates
+    pub tls: Option<TlsConfig>,
+    /// Metadata headers sent with every call
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// Buffered messages are sent at least this often
+    #[serde(default = "default_flush_interval_ms")]
+    pub flush_interval_ms: u64,
+    /// Buffered messages are sent as soon as there are this many
+    #[serde(default = "default_flush_batch_size")]
+    pub flush_batch_size: usize,
+}
+
+fn default_flush_interval_ms() -> u64 {
+    1000
+}
+
+fn default_flush_batch_size() -> usize {
+    100
+}
+
+/// Connection and messages waiting to be sent
+struct GrpcSender {
+    client: RwLock<Option<Grpc<Channel>>>,
+    path: PathAndQuery,
+    metadata: MetadataMap,
+    buffer: Mutex<Vec<DynamicMessage>>,
+}
+
+impl GrpcSender {
+    /// Send the buffered messages with one streaming call. They stay buffered if the call fails.
+    async fn flush(&self, buffer: &mut Vec<DynamicMessage>) -> Result<(), Error> {
+        if buffer.is_empty() {
+            return Ok(());
+        }
+        let mut client = self
+            .client
+            .read()
+            .await
+            .clone()
+            .ok_or(Error::Disconnection)?;
+        client.ready().await.map_err(|e| {
+            warn!("gRPC output connection is not ready: {}", e);
+            Error::Disconnection
+        })?;
+
+        let mut request = Request::new(stream::iter(buffer.clone()));
+        *request.metadata_mut() = self.metadata.clone();
+        let mut responses = client
+            .streaming(request, self.path.clone(), DynamicMessageCodec)
+            .await
+            .map_err(status_error)?
+            .into_inner();
+        // Drain the responses so that errors reported at the end of the call are not missed
+        while responses.message().await.map_err(status_error)?.is_some() {}
+
+        buffer.clear();
+        Ok(())
+    }
+}
+
+fn status_error(status: Status) -> Error {
+    warn!("gRPC output call failed: {}", status);
+    Error::Disconnection
+}
+
+/// gRPC output component
+struct GrpcOutput {
+    config: GrpcOutputConfig,
+    descriptor: MessageDescriptor,
+    sender: Arc<GrpcSender>,
+    flusher: Mutex<Option<(CancellationToken, JoinHandle<()>)>>,
+}
+
+impl GrpcOutput {
+    /// Create a new gRPC output component
+    fn new(config: GrpcOutputConfig) -> Result<Self, Error> {
+        if config.flush_batch_size == 0 || config.flush_interval_ms == 0 {
+            return Err(Error::Config(
+                "flush_batch_size and flush_interval_ms must be greater than 0".to_string(),
+            ));
+        }
+
+        let content = std::fs::read(&config.proto_descriptor).map_err(|e| {
+            Error::Config(format!(
+                "Failed to read proto descriptor {}: {}",
+                config.proto_descriptor, e
+            ))
+        })?;
+        let pool = DescriptorPool::decode(content.as_slice()).map_err(|e| {
+            Error::Config(format!(
+                "Invalid proto descriptor {}: {}",
+                config.proto_descriptor, e
+            ))
+        })?;
+        let service = pool.get_service_by_name(&config.service).ok_or_else(|| {
+            Error::Config(format!(
+                "Service {} not found in the proto descriptor",
+                config.service
+            ))
+        })?;
+        let method = service
+            .methods()
+            .find(|method| method.name() == config.method)
+            .ok_or_else(|| {
+                Error::Config(format!(
+                    "Method {} not found in service {}",
+                    config.method, config.service
+                ))
+            })?;
+        if !method.is_client_streaming() {
+            return Err(Error::Config(format!(
+                "Method {} of service {} is not a client streaming RPC",
+                config.method, config.service
+            )));
+        }
+
+        let path = PathAndQuery::try_from(format!("/{}/{}", config.service, config.method))
+            .map_err(|e| Error::Config(format!("Invalid gRPC method path: {}", e)))?;
+        let mut metadata = MetadataMap::new();
+        for (key, value) in &config.metadata {
+            let key = MetadataKey::<Ascii>::from_bytes(key.as_bytes())
+                .map_err(|e| Error::Config(format!("Invalid gRPC metadata key {}: {}", key, e)))?;
+            let value = MetadataValue::try_from(value.as_str()).map_err(|e| {
+                Error::Config(format!("Invalid gRPC metadata value of {}: {}", key, e))
+            })?;
+            metadata.insert(key, value);
+        }
+
+        Ok(Self {
+            descriptor: method.input(),
+            sender: Arc::new(GrpcSender {
+                client: RwLock::new(None),
+                path,
+                metadata,
+                buffer: Mutex::new(Vec::new()),
+            }),
+            flusher: Mutex::new(None),
+            config,
+        })
+    }
+
+    async fn channel(&self) -> Result<Channel, Error> {
+        let mut endpoint = Endpoint::from_shared(self.config.endpoint.clone()).map_err(|e| {
+            Error::Config(format!(
+                "Invalid gRPC endpoint {}: {}",
+                self.config.endpoint, e
+            ))
+        })?;
+        if let Some(tls) = &self.config.tls {
+            endpoint = endpoint
+                .tls_config(tls.client_config()?)
+                .map_err(|e| Error::Config(format!("Invalid gRPC TLS configuration: {}", e)))?;
+        }
+        endpoint.connect().await.map_err(|e| {
+            Error::Connection(format!(
+                "Failed to connect to gRPC server {}: {}",
+                self.config.endpoint, e
+            ))
+        })
+    }
+}
+
+/// Flush the buffered messages every `interval` until cancelled
+async fn flush_periodically(
+    sender: Arc<GrpcSender>,
+    interval: Duration,
+    cancellation_token: CancellationToken,
+) {
+    loop {
+        tokio::select! {
+            _ = cancellation_token.cancelled() => return,
+            _ = tokio::time::sleep(interval) => {}
+        }
+        let mut buffer = sender.buffer.lock().await;
+        if let Err(e) = sender.flush(&mut buffer).await {
+            error!("Failed to flush the gRPC output: {}", e);
+        }
+    }
+}
+
+#[async_trait]
+impl Output for GrpcOutput {
+    async fn connect(&self) -> Result<(), Error> {
+        let channel = self.channel().await?;
+        *self.sender.client.write().await = Some(Grpc::new(channel));
+
+        let mut flusher = self.flusher.lock().await;
+        if flusher.is_none() {
+            let cancellation_token = CancellationToken::new();
+            let handle = tokio::spawn(flush_periodically(
+                Arc::clone(&self.sender),
+                Duration::from_millis(self.config.flush_interval_ms),
+                cancellation_token.clone(),
+            ));
+            *flusher = Some((cancellation_token, handle));
+        }
+        Ok(())
+    }
+
+    async fn write(&self, msg: MessageBatch) -> Result<(), Error> {
+        if self.sender.client.read().await.is_none() {
+            return Err(Error::Connection("The output is not connected".to_string()));
+        }
+        let messages = arrow_to_dynamic_messages(&self.descriptor, &msg)?;
+
+        let mut buffer = self.sender.buffer.lock().await;
+        buffer.extend(messages);
+        if buffer.len() >= self.config.flush_batch_size {
+            self.sender.flush(&mut buffer).await?;
+        }
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        if let Some((cancellation_token, handle)) = self.flusher.lock().await.take() {
+            cancellation_token.cancel();
+            if let Err(e) = handle.await {
+                error!("gRPC output flush task failed: {}", e);
+            }
+        }
+
+        let mut buffer = self.sender.buffer.lock().await;
+        if let Err(e) = self.sender.flush(&mut buffer).await {
+            warn!(
+                "gRPC output closed with {} messages not sent: {}",
+                buffer.len(),
+                e
+            );
+        }
+        *self.sender.client.write().await = None;
+        Ok(())
+    }
+}
+
+/// Encodes dynamic messages and discards the content of the responses
+#[derive(Debug, Clone, Copy)]
+struct DynamicMessageCodec;
+
+impl Codec for DynamicMessageCodec {
+    type Encode = DynamicMessage;
+    type Decode = ();
+    type Encoder = DynamicMessageCodec;
+    type Decoder = DynamicMessageCodec;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        *self
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        *self
+    }
+}
+
+impl Encoder for DynamicMessageCodec {
+    type Item = DynamicMessage;
+    type Error = Status;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        item.encode(dst)
+            .map_err(|e| Status::internal(format!("Protobuf encoding failed: {}", e)))
+    }
+}
+
+impl Decoder for DynamicMessageCodec {
+    type Item = ();
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        src.advance(src.remaining());
+        Ok(Some(()))
+    }
+}
+
+pub(crate) struct GrpcOutputBuilder;
+impl OutputBuilder for GrpcOutputBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Output>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "gRPC output configuration is missing".to_string(),
+            ));
+        }
+        let config: GrpcOutputConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(GrpcOutput::new(config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_output_builder("grpc", Arc::new(GrpcOutputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Int64Array, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use prost_reflect::prost_types::field_descriptor_proto::{Label, Type};
+    use prost_reflect::prost_types::{
+        DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet,
+        MethodDescriptorProto, ServiceDescriptorProto,
+    };
+    use std::convert::Infallible;
+    use std::task::{Context, Poll};
+    use tempfile::NamedTempFile;
+    use tokio::net::TcpListener;
+    use tonic::body::BoxBody;
+    use tonic::codegen::http;
+    use tonic::codegen::{BoxFuture, Service};
+    use tonic::server::{ClientStreamingService, NamedService};
+    use tonic::transport::server::TcpIncoming;
+    use tonic::transport::Server;
+    use tonic::{Response, Streaming};
+
+    /// Descriptor set of `test.Ingest`, with the client streaming `Send` and the unary `Get`
+    fn descriptor_file() -> NamedTempFile {
+        let field = |name: &str, number: i32, kind: Type| FieldDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            label: Some(Label::Optional as i32),
+            r#type: Some(kind as i32),
+            ..Default::default()
+        };
+        let method = |name: &str, client_streaming: bool| MethodDescriptorProto {
+            name: Some(name.to_string()),
+            input_type: Some(".test.Event".to_string()),
+            output_type: Some(".test.Event".to_string()),
+            client_streaming: Some(client_streaming),
+            ..Default::default()
+        };
+        let set = FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("ingest.proto".to_string()),
+                package: Some("test".to_string()),
+                message_type: vec![DescriptorProto {
+                    name: Some("Event".to_string()),
+                    field: vec![field("id", 1, Type::Int64), field("name", 2, Type::String)],
+                    ..Default::default()
+                }],
+                service: vec![ServiceDescriptorProto {
+                    name: Some("Ingest".to_string()),
+                    method: vec![method("Send", true), method("Get", false)],
+                    ..Default::default()
+                }],
+                syntax: Some("proto3".to_string()),
+                ..Default::default()
+            }],
+        };
+        let file = NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), set.encode_to_vec()).unwrap();
+        file
+    }
+
+    fn config(descriptor: &NamedTempFile, endpoint: &str, method: &str) -> GrpcOutputConfig {
+        GrpcOutputConfig {
+            endpoint: endpoint.to_string(),
+            service: "test.Ingest".to_string(),
+            method: method.to_string(),
+            proto_descriptor: descriptor.path().to_str().unwrap().to_string(),
+            tls: None,
+            metadata: HashMap::from([("x-api-key".to_string(), "secret".to_string())]),
+            flush_interval_ms: 60_000,
+            flush_batch_size: 3,
+        }
+    }
+
+    fn batch(ids: Vec<i64>) -> MessageBatch {
+        let names: Vec<String> = ids.iter().map(|id| format!("event-{}", id)).collect();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        MessageBatch::new_arrow(
+            RecordBatch::try_new(
+                schema,
+                vec![
+                    Arc::new(Int64Array::from(ids)),
+                    Arc::new(StringArray::from(names)),
+                ],
+            )
+            .unwrap(),
+        )
+    }
+
+    /// Server side codec passing the raw message bytes through
+    #[derive(Clone, Copy)]
+    struct RawCodec;
+
+    impl Codec for RawCodec {
+        type Encode = Vec<u8>;
+        type Decode = Vec<u8>;
+        type Encoder = RawCodec;
+        type Decoder = RawCodec;
+
+        fn encoder(&mut self) -> Self::Encoder {
+            *self
+        }
+
+        fn decoder(&mut self) -> Self::Decoder {
+            *self
+        }
+    }
+
+    impl Encoder for RawCodec {
+        type Item = Vec<u8>;
+        type Error = Status;
+
+        fn encode(&mut self, item: Vec<u8>, dst: &mut EncodeBuf<'_>) -> Result<(), Status> {
+            prost_reflect::prost::bytes::BufMut::put_slice(dst, &item);
+            Ok(())
+        }
+    }
+
+    impl Decoder for RawCodec {
+        type Item = Vec<u8>;
+        type Error = Status;
+
+        fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Vec<u8>>, Status> {
+            Ok(Some(src.copy_to_bytes(src.remaining()).to_vec()))
+        }
+    }
+
+    /// `x-api-key` metadata and encoded messages of a call
+    type Call = (Option<String>, Vec<Vec<u8>>);
+
+    /// `test.Ingest` server recording every call
+    #[derive(Clone, Default)]
+    struct IngestServer {
+        calls: Arc<std::sync::Mutex<Vec<Call>>>,
+    }
+
+    impl NamedService for IngestServer {
+        const NAME: &'static str = "test.Ingest";
+    }
+
+    impl ClientStreamingService<Vec<u8>> for IngestServer {
+        type Response = Vec<u8>;
+        type Future = BoxFuture<Response<Vec<u8>>, Status>;
+
+        fn call(&mut self, request: Request<Streaming<Vec<u8>>>) -> Self::Future {
+            let calls = self.calls.clone();
+            Box::pin(async move {
+                let api_key = request
+                    .metadata()
+                    .get("x-api-key")
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string);
+                let mut messages = Vec::new();
+                let mut stream = request.into_inner();
+                while let Some(message) = stream.message().await? {
+                    messages.push(message);
+                }
+                calls.lock().unwrap().push((api_key, messages));
+                Ok(Response::new(Vec::new()))
+            })
+        }
+    }
+
+    impl Service<http::Request<BoxBody>> for IngestServer {
+        type Response = http::Response<BoxBody>;
+        type Error = Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: http::Request<BoxBody>) -> Self::Future {
+            let service = self.clone();
+            Box::pin(async move {
+                assert_eq!(request.uri().path(), "/test.Ingest/Send");
+                Ok(tonic::server::Grpc::new(RawCodec)
+                    .client_streaming(service, request)
+                    .await)
+            })
+        }
+    }
+
+    #[test]
+    fn test_build_validation() {
+        let descriptor = descriptor_file();
+        let endpoint = "http://127.0.0.1:50051";
+        assert!(GrpcOutput::new(config(&descriptor, endpoint, "Send")).is_ok());
+        assert!(matches!(
+            GrpcOutput::new(config(&descriptor, endpoint, "Get")),
+            Err(Error::Config(_))
+        ));
+        assert!(matches!(
+            GrpcOutput::new(config(&descriptor, endpoint, "Missing")),
+            Err(Error::Config(_))
+        ));
+
+        let mut invalid = config(&descriptor, endpoint, "Send");
+        invalid.metadata = HashMap::from([("invalid key".to_string(), "value".to_string())]);
+        assert!(matches!(GrpcOutput::new(invalid), Err(Error::Config(_))));
+    }
+
+    #[tokio::test]
+    async fn test_write_not_connected() {
+        let descriptor = descriptor_file();
+        let output =
+            GrpcOutput::new(config(&descriptor, "http://127.0.0.1:50051", "Send")).unwrap();
+        assert!(matches!(
+            output.write(batch(vec![1])).await,
+            Err(Error::Connection(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_write_flushes_batches() {
+        let server = IngestServer::default();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(server.clone())
+                .serve_with_incoming(incoming),
+        );
+
+        let descriptor = descriptor_file();
+        let output =
+            GrpcOutput::new(config(&descriptor, &format!("http://{}", address), "Send")).unwrap();
+        output.connect().await.unwrap();
+
+        output.write(batch(vec![1, 2])).await.unwrap();
+        assert!(server.calls.lock().unwrap().is_empty());
+        output.write(batch(vec![3])).await.unwrap();
+        output.write(batch(vec![4])).await.unwrap();
+        output.close().await.unwrap();
+
+        let calls = server.calls.lock().unwrap();
+        let decoded: Vec<(Option<String>, Vec<i64>)> = calls
+            .iter()
+            .map(|(api_key, messages)| {
+                let ids = messages
+                    .iter()
+                    .map(|message| {
+                        let message =
+                            DynamicMessage::decode(output.descriptor.clone(), message.as_slice())
+                                .unwrap();
+                        message.get_field_by_name("id").unwrap().as_i64().unwrap()
+                    })
+                    .collect();
+                (api_key.clone(), ids)
+            })
+            .collect();
+        assert_eq!(
+            decoded,
+            vec![
+                (Some("secret".to_string()), vec![1, 2, 3]),
+                (Some("secret".to_string()), vec![4]),
+            ]
+        );
+    }
+}
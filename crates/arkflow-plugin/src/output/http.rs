@@ -16,12 +16,15 @@
 //!
 //! Send the processed data to the HTTP endpoint
 
-use arkflow_core::output::{register_output_builder, Output, OutputBuilder};
+use arkflow_core::output::{register_output_builder, Output, OutputBuilder, OutputFormat};
 use arkflow_core::{Error, MessageBatch, Resource, DEFAULT_BINARY_VALUE_FIELD};
 use async_trait::async_trait;
 use base64::Engine;
+use hmac::{Hmac, Mac};
 use reqwest::{header, Client};
 use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::Sha256;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::Mutex;
@@ -35,6 +38,26 @@ enum AuthType {
     Bearer { token: String },
 }
 
+/// Signature of the request bodies, as required by webhook receivers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct WebhookSigningConfig {
+    secret: String,
+    /// Header carrying the hex encoded signature
+    header: String,
+    #[serde(default)]
+    algorithm: SigningAlgorithm,
+    /// Prepended to the signature, e.g. `sha256=`
+    prefix: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SigningAlgorithm {
+    #[default]
+    HmacSha256,
+    HmacSha1,
+}
+
 /// HTTP output configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct HttpOutputConfig {
@@ -52,6 +75,11 @@ struct HttpOutputConfig {
     body_field: Option<String>,
     /// Authentication configuration
     auth: Option<AuthType>,
+    /// Serialization format of the request bodies
+    #[serde(default)]
+    output_format: OutputFormat,
+    /// Signature of the request bodies
+    signing: Option<WebhookSigningConfig>,
 }
 
 /// HTTP output component
@@ -98,13 +126,18 @@ impl Output for HttpOutput {
             .body_field
             .as_deref()
             .unwrap_or(DEFAULT_BINARY_VALUE_FIELD);
-        let content = msg.to_binary(body_field)?;
-        if content.is_empty() {
+        // Binary messages are sent as is
+        if self.config.output_format == OutputFormat::Json
+            && msg.column_by_name(body_field).is_some()
+        {
+            for x in msg.to_binary(body_field)? {
+                self.send(x).await?
+            }
             return Ok(());
         }
 
-        for x in content {
-            self.send(x).await?
+        for x in msg.serialize_field_to(body_field, self.config.output_format)? {
+            self.send(&x).await?
         }
         Ok(())
     }
@@ -127,7 +160,8 @@ impl HttpOutput {
 
         let client = client_arc_guard.as_ref().unwrap();
         // Build the request
-        let mut request_builder = match self.config.method.to_uppercase().as_str() {
+        let method = self.config.method.to_uppercase();
+        let mut request_builder = match method.as_str() {
             "GET" => client.get(&self.config.url),
             "POST" => client.post(&self.config.url).body(data.to_vec()), // Content-Type由统一逻辑添加
             "PUT" => client.put(&self.config.url).body(data.to_vec()),
@@ -166,12 +200,22 @@ impl HttpOutput {
 
         // Add content type header (if not specified)
         // 始终添加Content-Type头（如果未指定）
+        let content_type = self.config.output_format.content_type();
         if let Some(headers) = &self.config.headers {
             if !headers.contains_key("Content-Type") {
-                request_builder = request_builder.header(header::CONTENT_TYPE, "application/json");
+                request_builder = request_builder.header(header::CONTENT_TYPE, content_type);
             }
         } else {
-            request_builder = request_builder.header(header::CONTENT_TYPE, "application/json");
+            request_builder = request_builder.header(header::CONTENT_TYPE, content_type);
+        }
+
+        // Sign the body once, so that every attempt carries the same signature
+        if let Some(signing) = &self.config.signing {
+            let body = match method.as_str() {
+                "GET" | "DELETE" => &[][..],
+                _ => data,
+            };
+            request_builder = request_builder.header(&signing.header, sign(signing, body));
         }
 
         // Send a request
@@ -213,6 +257,30 @@ impl HttpOutput {
         Err(last_error.unwrap_or_else(|| Error::Unknown("Unknown HTTP error".to_string())))
     }
 }
+/// Hex encoded signature of `body`, preceded by the configured prefix
+fn sign(signing: &WebhookSigningConfig, body: &[u8]) -> String {
+    // HMAC accepts keys of any length
+    let signature = match signing.algorithm {
+        SigningAlgorithm::HmacSha256 => {
+            let mut mac = Hmac::<Sha256>::new_from_slice(signing.secret.as_bytes())
+                .expect("HMAC key of any length");
+            mac.update(body);
+            hex::encode(mac.finalize().into_bytes())
+        }
+        SigningAlgorithm::HmacSha1 => {
+            let mut mac = Hmac::<Sha1>::new_from_slice(signing.secret.as_bytes())
+                .expect("HMAC key of any length");
+            mac.update(body);
+            hex::encode(mac.finalize().into_bytes())
+        }
+    };
+    format!(
+        "{}{}",
+        signing.prefix.as_deref().unwrap_or_default(),
+        signature
+    )
+}
+
 pub(crate) struct HttpOutputBuilder;
 impl OutputBuilder for HttpOutputBuilder {
     fn build(
@@ -235,3 +303,83 @@ impl OutputBuilder for HttpOutputBuilder {
 pub fn init() -> Result<(), Error> {
     register_output_builder("http", Arc::new(HttpOutputBuilder))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::response::IntoResponse;
+    use axum::Router;
+    use std::sync::atomic::AtomicUsize;
+
+    fn signing(algorithm: SigningAlgorithm, prefix: Option<&str>) -> WebhookSigningConfig {
+        WebhookSigningConfig {
+            secret: "It's a Secret to Everybody".to_string(),
+            header: "X-Hub-Signature-256".to_string(),
+            algorithm,
+            prefix: prefix.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_sign() {
+        assert_eq!(
+            sign(
+                &signing(SigningAlgorithm::HmacSha256, Some("sha256=")),
+                b"Hello, World!"
+            ),
+            "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17"
+        );
+        assert_eq!(
+            sign(&signing(SigningAlgorithm::HmacSha1, None), b"Hello, World!").len(),
+            40
+        );
+    }
+
+    #[tokio::test]
+    async fn test_signature_is_kept_across_retries() {
+        let signatures = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let (captured, counter) = (signatures.clone(), calls.clone());
+        let app = Router::new().fallback(move |headers: HeaderMap| {
+            let captured = captured.clone();
+            let counter = counter.clone();
+            async move {
+                captured
+                    .lock()
+                    .unwrap()
+                    .push(headers["X-Hub-Signature-256"].to_str().unwrap().to_string());
+                if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                    return StatusCode::SERVICE_UNAVAILABLE.into_response();
+                }
+                StatusCode::OK.into_response()
+            }
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let output = HttpOutput::new(HttpOutputConfig {
+            url: format!("http://{}/webhook", addr),
+            method: "POST".to_string(),
+            timeout_ms: 5000,
+            retry_count: 1,
+            headers: None,
+            body_field: None,
+            auth: None,
+            output_format: OutputFormat::Json,
+            signing: Some(signing(SigningAlgorithm::HmacSha256, Some("sha256="))),
+        })
+        .unwrap();
+        output.connect().await.unwrap();
+        output
+            .write(MessageBatch::new_binary(vec![b"Hello, World!".to_vec()]).unwrap())
+            .await
+            .unwrap();
+
+        let expected = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+        assert_eq!(*signatures.lock().unwrap(), vec![expected, expected]);
+    }
+}
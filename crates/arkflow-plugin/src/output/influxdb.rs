@@ -0,0 +1,420 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! InfluxDB output component
+//!
+//! Write rows to InfluxDB 2.x using the line protocol
+
+use arkflow_core::output::{register_output_builder, Output, OutputBuilder};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use datafusion::arrow::array::{Array, ArrayRef, Int64Array, TimestampNanosecondArray};
+use datafusion::arrow::datatypes::{DataType, TimeUnit};
+use datafusion::arrow::util::display::{ArrayFormatter, FormatOptions};
+use reqwest::{header, Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+/// Maximum number of retries when InfluxDB is rate limiting writes
+const MAX_RETRIES: u32 = 5;
+/// Initial backoff after a `429 Too Many Requests` response
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
+/// InfluxDB output configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct InfluxDbOutputConfig {
+    /// InfluxDB server URL, e.g. `http://localhost:8086`
+    url: String,
+    /// API token
+    token: String,
+    /// Organization name
+    org: String,
+    /// Destination bucket
+    bucket: String,
+    /// Column holding the measurement name
+    measurement_field: String,
+    /// Columns written as tags
+    #[serde(default)]
+    tag_fields: Vec<String>,
+    /// Columns written as fields
+    field_columns: Vec<String>,
+    /// Nanosecond timestamp column. Defaults to the ingest time
+    timestamp_field: Option<String>,
+}
+
+/// InfluxDB output component
+struct InfluxDbOutput {
+    config: InfluxDbOutputConfig,
+    client: Mutex<Option<Client>>,
+}
+
+impl InfluxDbOutput {
+    /// Create a new InfluxDB output component
+    fn new(config: InfluxDbOutputConfig) -> Result<Self, Error> {
+        Ok(Self {
+            config,
+            client: Mutex::new(None),
+        })
+    }
+
+    /// Serialize every row of the batch as a line protocol string
+    fn to_lines(&self, msg: &MessageBatch) -> Result<Vec<String>, Error> {
+        let column = |name: &str| -> Result<ArrayRef, Error> {
+            msg.column_by_name(name)
+                .cloned()
+                .ok_or_else(|| Error::Process(format!("Column {} not found", name)))
+        };
+
+        let measurements = column(&self.config.measurement_field)?;
+        let tags = self
+            .config
+            .tag_fields
+            .iter()
+            .map(|name| Ok((name.as_str(), column(name)?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let fields = self
+            .config
+            .field_columns
+            .iter()
+            .map(|name| Ok((name.as_str(), column(name)?)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let timestamps = match &self.config.timestamp_field {
+            Some(name) => Some(timestamp_column(&column(name)?)?),
+            None => None,
+        };
+        let ingest_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as i64)
+            .unwrap_or_default();
+
+        let options = FormatOptions::default();
+        let measurement_formatter = formatter(&measurements, &options)?;
+        let tag_formatters = tags
+            .iter()
+            .map(|(_, array)| formatter(array, &options))
+            .collect::<Result<Vec<_>, _>>()?;
+        let field_formatters = fields
+            .iter()
+            .map(|(_, array)| formatter(array, &options))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut lines = Vec::with_capacity(msg.num_rows());
+        for row in 0..msg.num_rows() {
+            if measurements.is_null(row) {
+                continue;
+            }
+
+            let mut line = escape(&measurement_formatter.value(row).to_string(), &[',', ' ']);
+            for ((name, array), formatter) in tags.iter().zip(&tag_formatters) {
+                if array.is_null(row) {
+                    continue;
+                }
+                line.push(',');
+                line.push_str(&escape(name, &[',', '=', ' ']));
+                line.push('=');
+                line.push_str(&escape(&formatter.value(row).to_string(), &[',', '=', ' ']));
+            }
+
+            let mut field_set = Vec::with_capacity(fields.len());
+            for ((name, array), formatter) in fields.iter().zip(&field_formatters) {
+                if array.is_null(row) {
+                    continue;
+                }
+                let value = formatter.value(row).to_string();
+                let value = match array.data_type() {
+                    DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => {
+                        format!("{}i", value)
+                    }
+                    DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64 => {
+                        format!("{}u", value)
+                    }
+                    DataType::Float16
+                    | DataType::Float32
+                    | DataType::Float64
+                    | DataType::Boolean => value,
+                    _ => format!("\"{}\"", escape(&value, &['"', '\\'])),
+                };
+                field_set.push(format!("{}={}", escape(name, &[',', '=', ' ']), value));
+            }
+            // A point without fields is rejected by InfluxDB
+            if field_set.is_empty() {
+                continue;
+            }
+
+            line.push(' ');
+            line.push_str(&field_set.join(","));
+            line.push(' ');
+            let timestamp = match &timestamps {
+                Some(array) if !array.is_null(row) => array.value(row),
+                _ => ingest_time,
+            };
+            line.push_str(&timestamp.to_string());
+            lines.push(line);
+        }
+
+        Ok(lines)
+    }
+}
+
+fn formatter<'a>(
+    array: &'a ArrayRef,
+    options: &'a FormatOptions<'a>,
+) -> Result<ArrayFormatter<'a>, Error> {
+    ArrayFormatter::try_new(array.as_ref(), options)
+        .map_err(|e| Error::Process(format!("Failed to format column: {}", e)))
+}
+
+/// Read a nanosecond timestamp column as `Int64`
+fn timestamp_column(array: &ArrayRef) -> Result<Int64Array, Error> {
+    match array.data_type() {
+        DataType::Int64 => Ok(array
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .cloned()
+            .unwrap_or_else(|| Int64Array::from(Vec::<i64>::new()))),
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => {
+            let array = array
+                .as_any()
+                .downcast_ref::<TimestampNanosecondArray>()
+                .ok_or_else(|| Error::Process("Invalid timestamp column".to_string()))?;
+            Ok(Int64Array::new(
+                array.values().clone(),
+                array.nulls().cloned(),
+            ))
+        }
+        data_type => Err(Error::Config(format!(
+            "Timestamp column must be a nanosecond Int64 or Timestamp, got {}",
+            data_type
+        ))),
+    }
+}
+
+/// Escape the given special characters with a backslash
+fn escape(value: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[async_trait]
+impl Output for InfluxDbOutput {
+    async fn connect(&self) -> Result<(), Error> {
+        let client = Client::builder()
+            .build()
+            .map_err(|e| Error::Connection(format!("Unable to create an HTTP client: {}", e)))?;
+        self.client.lock().await.replace(client);
+        Ok(())
+    }
+
+    async fn write(&self, msg: MessageBatch) -> Result<(), Error> {
+        let client_guard = self.client.lock().await;
+        let client = client_guard
+            .as_ref()
+            .ok_or_else(|| Error::Connection("The output is not connected".to_string()))?;
+
+        let lines = self.to_lines(&msg)?;
+        if lines.is_empty() {
+            return Ok(());
+        }
+        let body = lines.join("\n");
+
+        let url = format!("{}/api/v2/write", self.config.url.trim_end_matches('/'));
+        let mut backoff = INITIAL_BACKOFF;
+        let mut retries = 0;
+        loop {
+            let response = client
+                .post(&url)
+                .query(&[
+                    ("org", self.config.org.as_str()),
+                    ("bucket", self.config.bucket.as_str()),
+                    ("precision", "ns"),
+                ])
+                .header(
+                    header::AUTHORIZATION,
+                    format!("Token {}", self.config.token),
+                )
+                .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+                .body(body.clone())
+                .send()
+                .await
+                .map_err(|e| Error::Connection(format!("InfluxDB request error: {}", e)))?;
+
+            let status = response.status();
+            if status.is_success() {
+                return Ok(());
+            }
+            if status == StatusCode::TOO_MANY_REQUESTS && retries < MAX_RETRIES {
+                retries += 1;
+                warn!(
+                    "InfluxDB is rate limiting writes, retrying in {:?} ({}/{})",
+                    backoff, retries, MAX_RETRIES
+                );
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                continue;
+            }
+
+            let body = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "<Unable to read response body>".to_string());
+            return Err(Error::Process(format!(
+                "InfluxDB write failed: Status code {}, response: {}",
+                status, body
+            )));
+        }
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        self.client.lock().await.take();
+        Ok(())
+    }
+}
+
+pub(crate) struct InfluxDbOutputBuilder;
+impl OutputBuilder for InfluxDbOutputBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Output>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "InfluxDB output configuration is missing".to_string(),
+            ));
+        }
+        let config: InfluxDbOutputConfig = serde_json::from_value(config.clone().unwrap())?;
+
+        Ok(Arc::new(InfluxDbOutput::new(config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_output_builder("influxdb", Arc::new(InfluxDbOutputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode as AxumStatusCode;
+    use axum::routing::post;
+    use axum::Router;
+    use datafusion::arrow::array::{BooleanArray, Float64Array, StringArray};
+    use datafusion::arrow::datatypes::{Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn config(url: &str) -> InfluxDbOutputConfig {
+        InfluxDbOutputConfig {
+            url: url.to_string(),
+            token: "token".to_string(),
+            org: "org".to_string(),
+            bucket: "bucket".to_string(),
+            measurement_field: "measurement".to_string(),
+            tag_fields: vec!["host".to_string()],
+            field_columns: vec![
+                "value".to_string(),
+                "count".to_string(),
+                "ok".to_string(),
+                "note".to_string(),
+            ],
+            timestamp_field: Some("ts".to_string()),
+        }
+    }
+
+    fn batch() -> MessageBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("measurement", DataType::Utf8, false),
+            Field::new("host", DataType::Utf8, true),
+            Field::new("value", DataType::Float64, true),
+            Field::new("count", DataType::Int64, true),
+            Field::new("ok", DataType::Boolean, true),
+            Field::new("note", DataType::Utf8, true),
+            Field::new("ts", DataType::Int64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["cpu load", "cpu"])),
+                Arc::new(StringArray::from(vec![Some("a,b"), None])),
+                Arc::new(Float64Array::from(vec![Some(0.5), None])),
+                Arc::new(Int64Array::from(vec![Some(3), Some(4)])),
+                Arc::new(BooleanArray::from(vec![Some(true), None])),
+                Arc::new(StringArray::from(vec![Some("say \"hi\""), None])),
+                Arc::new(Int64Array::from(vec![Some(1000), Some(2000)])),
+            ],
+        )
+        .unwrap();
+        MessageBatch::new_arrow(batch)
+    }
+
+    #[test]
+    fn test_to_lines() {
+        let output = InfluxDbOutput::new(config("http://localhost:8086")).unwrap();
+        let lines = output.to_lines(&batch()).unwrap();
+        assert_eq!(
+            lines,
+            vec![
+                r#"cpu\ load,host=a\,b value=0.5,count=3i,ok=true,note="say \"hi\"" 1000"#,
+                "cpu count=4i 2000",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_column() {
+        let mut config = config("http://localhost:8086");
+        config.field_columns = vec!["missing".to_string()];
+        let output = InfluxDbOutput::new(config).unwrap();
+        assert!(output.to_lines(&batch()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_write_retries_on_rate_limit() {
+        let attempts = Arc::new(AtomicU32::new(0));
+        let counter = attempts.clone();
+        let app = Router::new().route(
+            "/api/v2/write",
+            post(move || {
+                let counter = counter.clone();
+                async move {
+                    if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                        AxumStatusCode::TOO_MANY_REQUESTS
+                    } else {
+                        AxumStatusCode::NO_CONTENT
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let output = InfluxDbOutput::new(config(&format!("http://{}", addr))).unwrap();
+        output.connect().await.unwrap();
+        output.write(batch()).await.unwrap();
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+}
@@ -18,13 +18,19 @@
 
 use serde::{Deserialize, Serialize};
 
-use arkflow_core::output::{register_output_builder, Output, OutputBuilder};
+use arkflow_core::output::{register_output_builder, Output, OutputBuilder, OutputFormat};
+use arkflow_core::trace::{SpanContext, TRACEPARENT_HEADER, TRACESTATE_HEADER};
 use arkflow_core::{Error, MessageBatch, Resource, DEFAULT_BINARY_VALUE_FIELD};
 
+use crate::component::avro::{AvroSerializer, SchemaRegistryConfig};
 use crate::expr::{EvaluateResult, Expr};
 use async_trait::async_trait;
+use datafusion::arrow::array::{Array, StringArray};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::DataType;
 use rdkafka::config::ClientConfig;
 use rdkafka::error::KafkaError;
+use rdkafka::message::{Header, OwnedHeaders};
 use rdkafka::producer::{DeliveryFuture, FutureProducer, FutureRecord, Producer};
 use rdkafka::util::Timeout;
 use rdkafka_sys::RDKafkaErrorCode;
@@ -57,13 +63,17 @@ impl std::fmt::Display for CompressionType {
 
 /// Kafka output configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
-struct KafkaOutputConfig {
+pub(crate) struct KafkaOutputConfig {
     /// List of Kafka server addresses
     brokers: Vec<String>,
     /// Target topic
     topic: Expr<String>,
     /// Partition key (optional)
     key: Option<Expr<String>>,
+    /// Column holding the topic of each message, overriding `topic` where it is not empty
+    topic_field: Option<String>,
+    /// Column holding the partition key of each message, overriding `key` where it is not empty
+    key_field: Option<String>,
     /// Client ID
     client_id: Option<String>,
     /// Compression type
@@ -72,11 +82,16 @@ struct KafkaOutputConfig {
     acks: Option<String>,
     /// Value type
     value_field: Option<String>,
+    /// Serialize messages as Avro registered in a schema registry instead of writing raw bytes
+    schema_registry: Option<SchemaRegistryConfig>,
+    /// Serialization format of the record values
+    #[serde(default)]
+    output_format: OutputFormat,
 }
 
 /// Kafka output component
 struct KafkaOutput {
-    config: KafkaOutputConfig,
+    encoder: KafkaRecordEncoder,
     inner_kafka_output: Arc<InnerKafkaOutput>,
     cancellation_token: CancellationToken,
 }
@@ -86,9 +101,156 @@ struct InnerKafkaOutput {
     send_futures: Arc<Mutex<Vec<DeliveryFuture>>>,
 }
 
+/// Record of a message, ready to be produced
+pub(crate) struct KafkaRecord {
+    pub(crate) topic: String,
+    pub(crate) key: Option<String>,
+    pub(crate) payload: Vec<u8>,
+}
+
+/// Records of a batch
+pub(crate) struct KafkaRecords {
+    pub(crate) records: Vec<KafkaRecord>,
+    /// Trace context of the records, for sampled messages
+    pub(crate) trace_context: Option<SpanContext>,
+}
+
+impl KafkaRecords {
+    /// Headers of the records
+    pub(crate) fn headers(&self) -> Option<OwnedHeaders> {
+        self.trace_context.as_ref().map(|context| {
+            let traceparent = context.to_traceparent();
+            let headers = OwnedHeaders::new().insert(Header {
+                key: TRACEPARENT_HEADER,
+                value: Some(traceparent.as_str()),
+            });
+            match &context.trace_state {
+                Some(trace_state) => headers.insert(Header {
+                    key: TRACESTATE_HEADER,
+                    value: Some(trace_state.as_str()),
+                }),
+                None => headers,
+            }
+        })
+    }
+}
+
+/// Turns batches into Kafka records according to the output configuration
+pub(crate) struct KafkaRecordEncoder {
+    config: KafkaOutputConfig,
+    avro: Option<AvroSerializer>,
+}
+
+impl KafkaRecordEncoder {
+    pub(crate) fn new(config: KafkaOutputConfig) -> Result<Self, Error> {
+        if config.schema_registry.is_some() && config.output_format != OutputFormat::Json {
+            return Err(Error::Config(
+                "output_format cannot be set together with schema_registry".to_string(),
+            ));
+        }
+        let avro = config.schema_registry.clone().map(AvroSerializer::new);
+        Ok(Self { config, avro })
+    }
+
+    /// Producer configuration: brokers, client ID, compression and acknowledgment level
+    pub(crate) fn client_config(&self) -> ClientConfig {
+        let mut client_config = ClientConfig::new();
+
+        // Configure the Kafka server address
+        client_config.set("bootstrap.servers", self.config.brokers.join(","));
+
+        // Set the client ID
+        if let Some(client_id) = &self.config.client_id {
+            client_config.set("client.id", client_id);
+        }
+
+        // Set the compression type
+        if let Some(compression) = &self.config.compression {
+            client_config.set("compression.type", compression.to_string().to_lowercase());
+        }
+
+        // Set the confirmation level (default to "all" for reliability)
+        if let Some(acks) = &self.config.acks {
+            client_config.set("acks", acks);
+        }
+        client_config
+    }
+
+    /// Resolve the topic, key and payload of every message of the batch
+    pub(crate) async fn encode(&self, msg: &MessageBatch) -> Result<KafkaRecords, Error> {
+        let value_field = self
+            .config
+            .value_field
+            .as_deref()
+            .unwrap_or(DEFAULT_BINARY_VALUE_FIELD);
+
+        let topic = self.get_topic(msg).await?;
+        let key = self.get_key(msg).await?;
+        let topic_values = string_column(msg, self.config.topic_field.as_deref())?;
+        let key_values = string_column(msg, self.config.key_field.as_deref())?;
+
+        // Resolve the topic of every message, preferring the per-message topic
+        let topics = (0..msg.len())
+            .map(|i| {
+                field_value(topic_values.as_ref(), i)
+                    .or_else(|| topic.get(i).map(String::as_str))
+                    .ok_or_else(|| Error::Process(format!("No Kafka topic for message {}", i)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let payloads = match &self.avro {
+            Some(serializer) => serializer.serialize(msg, |i| topics[i]).await?,
+            // Binary messages are written as is
+            None if self.config.output_format == OutputFormat::Json
+                && msg.column_by_name(value_field).is_some() =>
+            {
+                msg.to_binary(value_field)?
+                    .into_iter()
+                    .map(<[u8]>::to_vec)
+                    .collect()
+            }
+            None => msg.serialize_field_to(value_field, self.config.output_format)?,
+        };
+
+        let records = payloads
+            .into_iter()
+            .enumerate()
+            .map(|(i, payload)| KafkaRecord {
+                topic: topics[i].to_string(),
+                key: field_value(key_values.as_ref(), i)
+                    .or_else(|| key.as_ref().and_then(|k| k.get(i)).map(String::as_str))
+                    .map(str::to_string),
+                payload,
+            })
+            .collect();
+
+        // Contexts of unsampled messages are not propagated
+        let trace_context = msg.extract_span_context().filter(|context| context.sampled);
+
+        Ok(KafkaRecords {
+            records,
+            trace_context,
+        })
+    }
+
+    async fn get_topic(&self, msg: &MessageBatch) -> Result<EvaluateResult<String>, Error> {
+        self.config.topic.evaluate_expr(msg).await
+    }
+
+    async fn get_key(&self, msg: &MessageBatch) -> Result<Option<EvaluateResult<String>>, Error> {
+        let Some(v) = &self.config.key else {
+            return Ok(None);
+        };
+
+        Ok(Some(v.evaluate_expr(msg).await?))
+    }
+}
+
 impl KafkaOutput {
     /// Create a new Kafka output component
     pub fn new(config: KafkaOutputConfig) -> Result<Self, Error> {
+        let encoder = KafkaRecordEncoder::new(config)?;
+
         let cancellation_token = CancellationToken::new();
         let inner_kafka_output = Arc::new(InnerKafkaOutput {
             producer: Arc::new(RwLock::new(None)),
@@ -112,7 +274,7 @@ impl KafkaOutput {
         });
 
         Ok(Self {
-            config,
+            encoder,
             inner_kafka_output,
             cancellation_token,
         })
@@ -139,30 +301,11 @@ impl InnerKafkaOutput {
 #[async_trait]
 impl Output for KafkaOutput {
     async fn connect(&self) -> Result<(), Error> {
-        let mut client_config = ClientConfig::new();
-
-        // Configure the Kafka server address
-        client_config.set("bootstrap.servers", &self.config.brokers.join(","));
-
-        // Set the client ID
-        if let Some(client_id) = &self.config.client_id {
-            client_config.set("client.id", client_id);
-        }
-
-        // Set the compression type
-        if let Some(compression) = &self.config.compression {
-            client_config.set("compression.type", compression.to_string().to_lowercase());
-        }
-
-        // Set the confirmation level (default to "all" for reliability)
-        if let Some(acks) = &self.config.acks {
-            client_config.set("acks", acks);
-        }
-
         // Create a producer
-        let producer = client_config
-            .create()
-            .map_err(|e| Error::Connection(format!("A Kafka producer cannot be created: {}", e)))?;
+        let producer =
+            self.encoder.client_config().create().map_err(|e| {
+                Error::Connection(format!("A Kafka producer cannot be created: {}", e))
+            })?;
 
         // Save the producer instance
         let producer_arc = self.inner_kafka_output.producer.clone();
@@ -179,38 +322,28 @@ impl Output for KafkaOutput {
             Error::Connection("The Kafka producer is not initialized".to_string())
         })?;
 
-        let value_field = self
-            .config
-            .value_field
-            .as_deref()
-            .unwrap_or(DEFAULT_BINARY_VALUE_FIELD);
-        let payloads = msg.to_binary(value_field)?;
-        if payloads.is_empty() {
+        if msg.is_empty() {
             return Ok(());
         }
 
-        let topic = self.get_topic(&msg).await?;
-        let key = self.get_key(&msg).await?;
+        let records = self.encoder.encode(&msg).await?;
+        let headers = records.headers();
 
         // Prepare all records for sending
-        for (i, x) in payloads.into_iter().enumerate() {
+        for x in &records.records {
             // Create record
-            let mut record = match &topic {
-                EvaluateResult::Scalar(s) => FutureRecord::to(s).payload(x),
-                EvaluateResult::Vec(v) => FutureRecord::to(&*v[i]).payload(x),
-            };
+            let mut record = FutureRecord::to(&x.topic).payload(&x.payload);
+            if let Some(headers) = &headers {
+                record = record.headers(headers.clone());
+            }
 
             // Add key if available
-            match &key {
-                Some(EvaluateResult::Scalar(s)) => record = record.key(s),
-                Some(EvaluateResult::Vec(v)) if i < v.len() => {
-                    record = record.key(&v[i]);
-                }
-                _ => {}
+            if let Some(record_key) = &x.key {
+                record = record.key(record_key);
             }
 
             // Send the record
-            debug!("send payload:{}", String::from_utf8_lossy(x));
+            debug!("send payload:{}", String::from_utf8_lossy(&x.payload));
 
             loop {
                 match producer.send_result(record) {
@@ -271,18 +404,23 @@ impl Output for KafkaOutput {
         Ok(())
     }
 }
-impl KafkaOutput {
-    async fn get_topic(&self, msg: &MessageBatch) -> Result<EvaluateResult<String>, Error> {
-        self.config.topic.evaluate_expr(msg).await
-    }
 
-    async fn get_key(&self, msg: &MessageBatch) -> Result<Option<EvaluateResult<String>>, Error> {
-        let Some(v) = &self.config.key else {
-            return Ok(None);
-        };
+/// Read `field` as a string column, `None` if no field is configured or the batch lacks it
+fn string_column(msg: &MessageBatch, field: Option<&str>) -> Result<Option<StringArray>, Error> {
+    let Some(column) = field.and_then(|field| msg.column_by_name(field)) else {
+        return Ok(None);
+    };
+    let column = cast(column, &DataType::Utf8)
+        .map_err(|e| Error::Process(format!("Failed to read field as a string: {}", e)))?;
+    Ok(column.as_any().downcast_ref::<StringArray>().cloned())
+}
 
-        Ok(Some(v.evaluate_expr(msg).await?))
-    }
+/// Value of row `i`, `None` if it is null or empty
+fn field_value(values: Option<&StringArray>, i: usize) -> Option<&str> {
+    values
+        .filter(|values| i < values.len() && values.is_valid(i))
+        .map(|values| values.value(i))
+        .filter(|value| !value.is_empty())
 }
 
 pub(crate) struct KafkaOutputBuilder;
@@ -308,3 +446,33 @@ impl OutputBuilder for KafkaOutputBuilder {
 pub fn init() -> Result<(), Error> {
     register_output_builder("kafka", Arc::new(KafkaOutputBuilder))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::datatypes::{Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+
+    #[test]
+    fn test_topic_field_values() {
+        let schema = Arc::new(Schema::new(vec![Field::new("route", DataType::Utf8, true)]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(StringArray::from(vec![
+                Some("orders"),
+                Some(""),
+                None,
+            ]))],
+        )
+        .unwrap();
+        let msg = MessageBatch::new_arrow(batch);
+
+        let values = string_column(&msg, Some("route")).unwrap();
+        assert_eq!(field_value(values.as_ref(), 0), Some("orders"));
+        assert_eq!(field_value(values.as_ref(), 1), None);
+        assert_eq!(field_value(values.as_ref(), 2), None);
+
+        assert!(string_column(&msg, Some("missing")).unwrap().is_none());
+        assert!(string_column(&msg, None).unwrap().is_none());
+    }
+}
@@ -0,0 +1,399 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Transactional Kafka output component
+//!
+//! Write each batch to Kafka in its own transaction. When paired with the `kafka_tx` input of
+//! `consumer_group`, the offsets of the consumed messages are committed in the same
+//! transaction, so that reading, processing and writing happen exactly once.
+//!
+//! As a [`TransactionCoordinator`], the output keeps the transaction open across writes between
+//! `begin` and its commit or abort, so that it can take part in a two-phase commit.
+
+use crate::component::kafka::{get_consumer, offsets_to_commit};
+use crate::output::kafka::{KafkaOutputConfig, KafkaRecordEncoder, KafkaRecords};
+use arkflow_core::output::{
+    register_output_builder, Output, OutputBuilder, TransactionCoordinator, TransactionId,
+};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use rdkafka::consumer::{Consumer, ConsumerGroupMetadata};
+use rdkafka::error::KafkaError;
+use rdkafka::producer::{BaseProducer, BaseRecord, Producer};
+use rdkafka::types::RDKafkaErrorCode;
+use rdkafka::TopicPartitionList;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::error;
+
+/// Transactional Kafka output configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TransactionalKafkaOutputConfig {
+    #[serde(flatten)]
+    kafka: KafkaOutputConfig,
+    /// Transactional ID of the producer, stable across restarts
+    transactional_id: String,
+    /// Time after which the broker aborts a transaction that is not committed
+    #[serde(default = "default_transaction_timeout_ms")]
+    transaction_timeout_ms: u64,
+    /// Consumer group of the `kafka_tx` input whose offsets are committed with the messages
+    consumer_group: Option<String>,
+}
+
+fn default_transaction_timeout_ms() -> u64 {
+    60_000
+}
+
+/// Transactional Kafka output component
+struct TransactionalKafkaOutput {
+    config: TransactionalKafkaOutputConfig,
+    encoder: KafkaRecordEncoder,
+    /// Held for the duration of a transaction, so that transactions do not overlap
+    producer: Mutex<Option<Arc<BaseProducer>>>,
+    /// Transaction opened by `begin`, which writes are made in until it is committed or aborted
+    open_transaction: Mutex<Option<TransactionId>>,
+    next_transaction_id: AtomicU64,
+}
+
+impl TransactionalKafkaOutput {
+    fn new(config: TransactionalKafkaOutputConfig) -> Result<Self, Error> {
+        if config.transactional_id.is_empty() {
+            return Err(Error::Config(
+                "transactional_id must not be empty".to_string(),
+            ));
+        }
+        Ok(Self {
+            encoder: KafkaRecordEncoder::new(config.kafka.clone())?,
+            config,
+            producer: Mutex::new(None),
+            open_transaction: Mutex::new(None),
+            next_transaction_id: AtomicU64::new(0),
+        })
+    }
+
+    /// Close transaction `id`, which must be the open one, returning the producer to end it with
+    async fn end_transaction(&self, id: TransactionId) -> Result<Arc<BaseProducer>, Error> {
+        let producer_guard = self.producer.lock().await;
+        let producer = producer_guard.as_ref().cloned().ok_or_else(|| {
+            Error::Connection("The Kafka producer is not initialized".to_string())
+        })?;
+        let mut open_transaction = self.open_transaction.lock().await;
+        if *open_transaction != Some(id) {
+            return Err(Error::Process(format!(
+                "Kafka transaction {} is not open",
+                id
+            )));
+        }
+        *open_transaction = None;
+        Ok(producer)
+    }
+
+    fn transaction_timeout(&self) -> Duration {
+        Duration::from_millis(self.config.transaction_timeout_ms)
+    }
+
+    /// Current group metadata of the paired input's consumer
+    async fn group_metadata(&self) -> Result<Option<ConsumerGroupMetadata>, Error> {
+        let Some(consumer_group) = &self.config.consumer_group else {
+            return Ok(None);
+        };
+        let consumer = get_consumer(consumer_group).ok_or_else(|| {
+            Error::Process(format!(
+                "No kafka_tx input is connected for consumer group {}",
+                consumer_group
+            ))
+        })?;
+        let consumer = consumer.read().await;
+        consumer
+            .as_ref()
+            .and_then(|consumer| consumer.group_metadata())
+            .map(Some)
+            .ok_or_else(|| {
+                Error::Process(format!(
+                    "The kafka_tx input of consumer group {} is closed",
+                    consumer_group
+                ))
+            })
+    }
+}
+
+/// Write `records` and commit `offsets` in a single transaction, aborting it on failure
+fn run_transaction(
+    producer: &BaseProducer,
+    records: &KafkaRecords,
+    offsets: Option<(TopicPartitionList, ConsumerGroupMetadata)>,
+    timeout: Duration,
+) -> Result<(), Error> {
+    producer
+        .begin_transaction()
+        .map_err(|e| Error::Connection(format!("Failed to begin Kafka transaction: {}", e)))?;
+
+    let result = send_in_transaction(producer, records, offsets, timeout).and_then(|_| {
+        producer
+            .commit_transaction(timeout)
+            .map_err(|e| Error::Connection(format!("Failed to commit Kafka transaction: {}", e)))
+    });
+    if result.is_err() {
+        if let Err(e) = producer.abort_transaction(timeout) {
+            error!("Failed to abort Kafka transaction: {}", e);
+        }
+    }
+    result
+}
+
+/// Send `records` and `offsets` to the open transaction
+fn send_in_transaction(
+    producer: &BaseProducer,
+    records: &KafkaRecords,
+    offsets: Option<(TopicPartitionList, ConsumerGroupMetadata)>,
+    timeout: Duration,
+) -> Result<(), Error> {
+    let headers = records.headers();
+    for x in &records.records {
+        let mut record = BaseRecord::to(&x.topic).payload(&x.payload);
+        if let Some(headers) = &headers {
+            record = record.headers(headers.clone());
+        }
+        if let Some(key) = &x.key {
+            record = record.key(key);
+        }
+
+        loop {
+            match producer.send(record) {
+                Ok(()) => break,
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), r)) => {
+                    // Serve delivery reports to make room in the queue
+                    producer.poll(Duration::from_millis(50));
+                    record = r;
+                }
+                Err((e, _)) => {
+                    return Err(Error::Connection(format!("Failed to write to Kafka: {e}")));
+                }
+            }
+        }
+    }
+
+    if let Some((offsets, group_metadata)) = offsets {
+        if offsets.count() > 0 {
+            producer
+                .send_offsets_to_transaction(&offsets, &group_metadata, timeout)
+                .map_err(|e| {
+                    Error::Connection(format!(
+                        "Failed to send offsets to Kafka transaction: {}",
+                        e
+                    ))
+                })?;
+        }
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl Output for TransactionalKafkaOutput {
+    async fn connect(&self) -> Result<(), Error> {
+        let mut client_config = self.encoder.client_config();
+        client_config.set("transactional.id", &self.config.transactional_id);
+        client_config.set(
+            "transaction.timeout.ms",
+            self.config.transaction_timeout_ms.to_string(),
+        );
+
+        let producer: Arc<BaseProducer> = Arc::new(client_config.create().map_err(|e| {
+            Error::Connection(format!("A Kafka producer cannot be created: {}", e))
+        })?);
+
+        // Fences previous producers with the same transactional ID
+        let timeout = self.transaction_timeout();
+        let init_producer = producer.clone();
+        tokio::task::spawn_blocking(move || init_producer.init_transactions(timeout))
+            .await
+            .map_err(|e| Error::Process(format!("Failed to spawn blocking task: {}", e)))?
+            .map_err(|e| {
+                Error::Connection(format!("Failed to initialize Kafka transactions: {}", e))
+            })?;
+
+        *self.producer.lock().await = Some(producer);
+        Ok(())
+    }
+
+    async fn write(&self, msg: MessageBatch) -> Result<(), Error> {
+        let producer_guard = self.producer.lock().await;
+        let producer = producer_guard.as_ref().cloned().ok_or_else(|| {
+            Error::Connection("The Kafka producer is not initialized".to_string())
+        })?;
+        if msg.is_empty() {
+            return Ok(());
+        }
+
+        let records = self.encoder.encode(&msg).await?;
+        let offsets = match self.group_metadata().await? {
+            Some(group_metadata) => Some((offsets_to_commit(&msg)?, group_metadata)),
+            None => None,
+        };
+
+        let timeout = self.transaction_timeout();
+        let in_open_transaction = self.open_transaction.lock().await.is_some();
+        tokio::task::spawn_blocking(move || {
+            if in_open_transaction {
+                send_in_transaction(&producer, &records, offsets, timeout)
+            } else {
+                run_transaction(&producer, &records, offsets, timeout)
+            }
+        })
+        .await
+        .map_err(|e| Error::Process(format!("Failed to spawn blocking task: {}", e)))?
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        // A transaction left open is aborted by the broker once it times out
+        self.open_transaction.lock().await.take();
+        self.producer.lock().await.take();
+        Ok(())
+    }
+
+    fn transaction_coordinator(&self) -> Option<&dyn TransactionCoordinator> {
+        Some(self)
+    }
+}
+
+#[async_trait]
+impl TransactionCoordinator for TransactionalKafkaOutput {
+    async fn begin(&self) -> Result<TransactionId, Error> {
+        let producer_guard = self.producer.lock().await;
+        let producer = producer_guard.as_ref().ok_or_else(|| {
+            Error::Connection("The Kafka producer is not initialized".to_string())
+        })?;
+        let mut open_transaction = self.open_transaction.lock().await;
+        if open_transaction.is_some() {
+            return Err(Error::Process(
+                "A Kafka transaction is already open".to_string(),
+            ));
+        }
+        producer
+            .begin_transaction()
+            .map_err(|e| Error::Connection(format!("Failed to begin Kafka transaction: {}", e)))?;
+        let id = self.next_transaction_id.fetch_add(1, Ordering::Relaxed);
+        *open_transaction = Some(id);
+        Ok(id)
+    }
+
+    async fn commit(&self, id: TransactionId) -> Result<(), Error> {
+        let producer = self.end_transaction(id).await?;
+        let timeout = self.transaction_timeout();
+        tokio::task::spawn_blocking(move || {
+            let result = producer.commit_transaction(timeout).map_err(|e| {
+                Error::Connection(format!("Failed to commit Kafka transaction: {}", e))
+            });
+            if result.is_err() {
+                if let Err(e) = producer.abort_transaction(timeout) {
+                    error!("Failed to abort Kafka transaction: {}", e);
+                }
+            }
+            result
+        })
+        .await
+        .map_err(|e| Error::Process(format!("Failed to spawn blocking task: {}", e)))?
+    }
+
+    async fn rollback(&self, id: TransactionId) -> Result<(), Error> {
+        let producer = self.end_transaction(id).await?;
+        let timeout = self.transaction_timeout();
+        tokio::task::spawn_blocking(move || {
+            producer
+                .abort_transaction(timeout)
+                .map_err(|e| Error::Connection(format!("Failed to abort Kafka transaction: {}", e)))
+        })
+        .await
+        .map_err(|e| Error::Process(format!("Failed to spawn blocking task: {}", e)))?
+    }
+}
+
+struct TransactionalKafkaOutputBuilder;
+impl OutputBuilder for TransactionalKafkaOutputBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Output>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Transactional Kafka output configuration is missing".to_string(),
+            ));
+        }
+        let config: TransactionalKafkaOutputConfig =
+            serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(TransactionalKafkaOutput::new(config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_output_builder("kafka_tx", Arc::new(TransactionalKafkaOutputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config(value: serde_json::Value) -> Result<TransactionalKafkaOutputConfig, Error> {
+        Ok(serde_json::from_value(value)?)
+    }
+
+    #[test]
+    fn test_config_extends_kafka_output_config() {
+        let config = config(json!({
+            "brokers": ["localhost:9092"],
+            "topic": {"type": "value", "value": "out"},
+            "transactional_id": "pipeline-1",
+            "consumer_group": "group-1",
+        }))
+        .unwrap();
+        assert_eq!(config.transactional_id, "pipeline-1");
+        assert_eq!(config.transaction_timeout_ms, 60_000);
+        assert_eq!(config.consumer_group.as_deref(), Some("group-1"));
+    }
+
+    #[tokio::test]
+    async fn test_write_not_connected() {
+        let output = TransactionalKafkaOutput::new(
+            config(json!({
+                "brokers": ["localhost:9092"],
+                "topic": {"type": "value", "value": "out"},
+                "transactional_id": "pipeline-1",
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+        let msg = MessageBatch::new_binary(vec![b"x".to_vec()]).unwrap();
+        assert!(matches!(output.write(msg).await, Err(Error::Connection(_))));
+        assert!(matches!(output.begin().await, Err(Error::Connection(_))));
+        assert!(matches!(output.commit(0).await, Err(Error::Connection(_))));
+        assert!(output.transaction_coordinator().is_some());
+    }
+
+    #[test]
+    fn test_empty_transactional_id() {
+        let result = config(json!({
+            "brokers": ["localhost:9092"],
+            "topic": {"type": "value", "value": "out"},
+            "transactional_id": "",
+        }))
+        .and_then(TransactionalKafkaOutput::new);
+        assert!(matches!(result, Err(Error::Config(_))));
+    }
+}
@@ -18,23 +18,45 @@
 
 use arkflow_core::Error;
 
+pub mod arrow_flight;
+pub mod content_type_router;
 pub mod drop;
+pub mod exactly_once;
+pub mod fan_out;
+pub mod grpc;
 pub mod http;
+pub mod influxdb;
 pub mod kafka;
+pub mod kafka_tx;
 pub mod mqtt;
 pub mod sql;
 pub mod nats;
+pub mod pubsub;
 pub mod redis;
+pub mod slack;
 pub mod stdout;
+pub mod unix_socket;
+pub mod zeromq;
 
 pub fn init() -> Result<(), Error> {
     drop::init()?;
     http::init()?;
     kafka::init()?;
+    kafka_tx::init()?;
     mqtt::init()?;
     stdout::init()?;
     sql::init()?;
     nats::init()?;
     redis::init()?;
+    unix_socket::init()?;
+    influxdb::init()?;
+    pubsub::init()?;
+    content_type_router::init()?;
+    slack::init()?;
+    zeromq::init()?;
+    arrow_flight::init()?;
+    exactly_once::init()?;
+    fan_out::init()?;
+    grpc::init()?;
     Ok(())
 }
@@ -0,0 +1,249 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Google Cloud Pub/Sub output component
+//!
+//! Publish messages to a Pub/Sub topic
+
+use crate::component::pubsub::{PubSubClient, PubsubMessage};
+use arkflow_core::output::{register_output_builder, Output, OutputBuilder};
+use arkflow_core::{Error, MessageBatch, Resource, DEFAULT_BINARY_VALUE_FIELD};
+use async_trait::async_trait;
+use datafusion::arrow::array::{Array, StringArray};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::DataType;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Maximum number of messages in a single publish request
+const MAX_PUBLISH_MESSAGES: usize = 1000;
+
+/// Google Pub/Sub output configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PubSubOutputConfig {
+    /// Google Cloud project id
+    project_id: String,
+    /// Topic to publish to
+    topic_id: String,
+    /// Attributes added to every message
+    #[serde(default)]
+    attributes: HashMap<String, String>,
+    /// Column holding the ordering key of each message
+    ordering_key_field: Option<String>,
+    /// Value field to use for message payload
+    value_field: Option<String>,
+    /// Path to a service account key file
+    credentials_file: Option<String>,
+    /// Custom API endpoint, e.g. of the Pub/Sub emulator
+    endpoint: Option<String>,
+}
+
+/// Google Pub/Sub output component
+struct PubSubOutput {
+    config: PubSubOutputConfig,
+    client: RwLock<Option<PubSubClient>>,
+}
+
+impl PubSubOutput {
+    /// Create a new Google Pub/Sub output component
+    fn new(config: PubSubOutputConfig) -> Result<Self, Error> {
+        Ok(Self {
+            config,
+            client: RwLock::new(None),
+        })
+    }
+
+    /// Read the ordering key of each row, if configured
+    fn ordering_keys(&self, msg: &MessageBatch) -> Result<Option<StringArray>, Error> {
+        let Some(field) = &self.config.ordering_key_field else {
+            return Ok(None);
+        };
+        let column = msg
+            .column_by_name(field)
+            .ok_or_else(|| Error::Process(format!("Ordering key field {} not found", field)))?;
+        let keys = cast(column, &DataType::Utf8)
+            .map_err(|e| Error::Process(format!("Invalid ordering key field {}: {}", field, e)))?;
+        Ok(keys.as_any().downcast_ref::<StringArray>().cloned())
+    }
+}
+
+#[async_trait]
+impl Output for PubSubOutput {
+    async fn connect(&self) -> Result<(), Error> {
+        let client = PubSubClient::new(
+            &self.config.project_id,
+            self.config.credentials_file.as_deref(),
+            self.config.endpoint.as_deref(),
+        )?;
+        *self.client.write().await = Some(client);
+        Ok(())
+    }
+
+    async fn write(&self, msg: MessageBatch) -> Result<(), Error> {
+        let client_guard = self.client.read().await;
+        let client = client_guard
+            .as_ref()
+            .ok_or_else(|| Error::Connection("Pub/Sub client not connected".to_string()))?;
+
+        let value_field = self
+            .config
+            .value_field
+            .as_deref()
+            .unwrap_or(DEFAULT_BINARY_VALUE_FIELD);
+        let payloads = msg.to_binary(value_field)?;
+        if payloads.is_empty() {
+            return Ok(());
+        }
+        let ordering_keys = self.ordering_keys(&msg)?;
+
+        let messages: Vec<PubsubMessage> = payloads
+            .iter()
+            .enumerate()
+            .map(|(i, payload)| {
+                let mut message = PubsubMessage::new(payload);
+                message.attributes = self.config.attributes.clone();
+                if let Some(keys) = &ordering_keys {
+                    if keys.is_valid(i) {
+                        message.ordering_key = keys.value(i).to_string();
+                    }
+                }
+                message
+            })
+            .collect();
+
+        for chunk in messages.chunks(MAX_PUBLISH_MESSAGES) {
+            client.publish(&self.config.topic_id, chunk).await?;
+        }
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        *self.client.write().await = None;
+        Ok(())
+    }
+}
+
+struct PubSubOutputBuilder;
+
+impl OutputBuilder for PubSubOutputBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Output>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Google Pub/Sub output configuration is missing".to_string(),
+            ));
+        }
+        let config: PubSubOutputConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(PubSubOutput::new(config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_output_builder("google_pubsub", Arc::new(PubSubOutputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Uri;
+    use axum::Json;
+    use axum::Router;
+    use datafusion::arrow::array::{ArrayRef, BinaryArray};
+    use datafusion::arrow::datatypes::{Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use serde_json::{json, Value};
+
+    #[tokio::test]
+    async fn test_publish() {
+        let published = Arc::new(std::sync::Mutex::new(Vec::<Value>::new()));
+        let captured = published.clone();
+        let app = Router::new().fallback(move |uri: Uri, Json(body): Json<Value>| {
+            let captured = captured.clone();
+            async move {
+                assert_eq!(uri.path(), "/v1/projects/project/topics/topic:publish");
+                captured.lock().unwrap().push(body);
+                Json(json!({ "messageIds": ["1", "2"] }))
+            }
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let output = PubSubOutput::new(PubSubOutputConfig {
+            project_id: "project".to_string(),
+            topic_id: "topic".to_string(),
+            attributes: HashMap::from([("source".to_string(), "arkflow".to_string())]),
+            ordering_key_field: Some("key".to_string()),
+            value_field: None,
+            credentials_file: None,
+            endpoint: Some(format!("http://{}", addr)),
+        })
+        .unwrap();
+        output.connect().await.unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new(DEFAULT_BINARY_VALUE_FIELD, DataType::Binary, false),
+            Field::new("key", DataType::Utf8, true),
+        ]));
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(BinaryArray::from_vec(vec![b"hello", b"world"])),
+            Arc::new(StringArray::from(vec![Some("k1"), None])),
+        ];
+        let batch = RecordBatch::try_new(schema, columns).unwrap();
+        output.write(MessageBatch::new_arrow(batch)).await.unwrap();
+
+        let published = published.lock().unwrap();
+        assert_eq!(
+            published[0],
+            json!({
+                "messages": [
+                    {
+                        "data": "aGVsbG8=",
+                        "attributes": { "source": "arkflow" },
+                        "orderingKey": "k1"
+                    },
+                    {
+                        "data": "d29ybGQ=",
+                        "attributes": { "source": "arkflow" }
+                    }
+                ]
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_ordering_key_field() {
+        let output = PubSubOutput::new(PubSubOutputConfig {
+            project_id: "project".to_string(),
+            topic_id: "topic".to_string(),
+            attributes: HashMap::new(),
+            ordering_key_field: Some("key".to_string()),
+            value_field: None,
+            credentials_file: None,
+            endpoint: Some("http://127.0.0.1:1".to_string()),
+        })
+        .unwrap();
+        output.connect().await.unwrap();
+        let batch = MessageBatch::new_binary(vec![b"hello".to_vec()]).unwrap();
+        assert!(output.write(batch).await.is_err());
+    }
+}
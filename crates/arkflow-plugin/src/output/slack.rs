@@ -0,0 +1,394 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Slack output component
+//!
+//! Send a message rendered from a template to a Slack incoming webhook for each row
+
+use arkflow_core::output::{register_output_builder, Output, OutputBuilder};
+use arkflow_core::{Error, MessageBatch, Resource, DEFAULT_BINARY_VALUE_FIELD};
+use async_trait::async_trait;
+use datafusion::arrow;
+use datafusion::arrow::array::{Array, BinaryArray};
+use reqwest::{header, Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, Semaphore};
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+/// Maximum number of attempts for a message that keeps being rate limited by Slack
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Slack output configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SlackOutputConfig {
+    /// Incoming webhook URL
+    webhook_url: String,
+    /// Message template, `{{ field }}` is replaced by the value of the field
+    template: String,
+    /// Overrides the name of the webhook's bot user
+    username: Option<String>,
+    /// Overrides the icon of the webhook's bot user, e.g. `:rotating_light:`
+    icon_emoji: Option<String>,
+    /// Overrides the channel of the webhook
+    channel: Option<String>,
+    /// Maximum number of messages sent per minute
+    max_messages_per_minute: u32,
+}
+
+/// Slack output component
+struct SlackOutput {
+    config: SlackOutputConfig,
+    client: Mutex<Option<Client>>,
+    permits: Arc<Semaphore>,
+    refill: Mutex<Option<CancellationToken>>,
+}
+
+impl SlackOutput {
+    /// Create a new Slack output component
+    fn new(config: SlackOutputConfig) -> Result<Self, Error> {
+        if config.max_messages_per_minute == 0 {
+            return Err(Error::Config(
+                "max_messages_per_minute must be greater than 0".to_string(),
+            ));
+        }
+        let permits = Arc::new(Semaphore::new(config.max_messages_per_minute as usize));
+        Ok(Self {
+            config,
+            client: Mutex::new(None),
+            permits,
+            refill: Mutex::new(None),
+        })
+    }
+
+    /// Build the Block Kit payload for a rendered message
+    fn payload(&self, text: &str) -> Value {
+        let mut payload = json!({
+            "text": text,
+            "blocks": [
+                {
+                    "type": "section",
+                    "text": { "type": "mrkdwn", "text": text }
+                }
+            ]
+        });
+        let overrides = [
+            ("username", &self.config.username),
+            ("icon_emoji", &self.config.icon_emoji),
+            ("channel", &self.config.channel),
+        ];
+        for (key, value) in overrides {
+            if let Some(value) = value {
+                payload[key] = Value::String(value.clone());
+            }
+        }
+        payload
+    }
+
+    /// Post a payload to the webhook, waiting for `Retry-After` when rate limited
+    async fn send(&self, client: &Client, payload: &Value) -> Result<(), Error> {
+        let mut attempt = 1;
+        loop {
+            let response = client
+                .post(&self.config.webhook_url)
+                .json(payload)
+                .send()
+                .await
+                .map_err(|e| Error::Connection(format!("Slack request error: {}", e)))?;
+
+            let status = response.status();
+            if status.is_success() {
+                return Ok(());
+            }
+            if status == StatusCode::TOO_MANY_REQUESTS && attempt < MAX_ATTEMPTS {
+                let retry_after = response
+                    .headers()
+                    .get(header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.trim().parse::<u64>().ok())
+                    .unwrap_or(1);
+                warn!("Rate limited by Slack, retrying in {}s", retry_after);
+                tokio::time::sleep(Duration::from_secs(retry_after)).await;
+                attempt += 1;
+                continue;
+            }
+
+            let body = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "<Unable to read response body>".to_string());
+            return Err(Error::Process(format!(
+                "Slack request failed: Status code {}, response: {}",
+                status, body
+            )));
+        }
+    }
+}
+
+#[async_trait]
+impl Output for SlackOutput {
+    async fn connect(&self) -> Result<(), Error> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .map_err(|e| Error::Connection(format!("Unable to create an HTTP client: {}", e)))?;
+        self.client.lock().await.replace(client);
+
+        // Return one permit at a time so that the limit is spread over the minute
+        let token = CancellationToken::new();
+        let permits = self.permits.clone();
+        let max_permits = self.config.max_messages_per_minute as usize;
+        let period = Duration::from_secs(60) / self.config.max_messages_per_minute;
+        let cancel = token.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    _ = interval.tick() => {
+                        if permits.available_permits() < max_permits {
+                            permits.add_permits(1);
+                        }
+                    }
+                }
+            }
+        });
+        if let Some(previous) = self.refill.lock().await.replace(token) {
+            previous.cancel();
+        }
+        Ok(())
+    }
+
+    async fn write(&self, msg: MessageBatch) -> Result<(), Error> {
+        let client = self
+            .client
+            .lock()
+            .await
+            .clone()
+            .ok_or_else(|| Error::Connection("The output is not connected".to_string()))?;
+
+        for row in rows(&msg)? {
+            let text = render(&self.config.template, &row);
+            self.permits
+                .acquire()
+                .await
+                .map_err(|_| Error::Process("Rate limiter closed".to_string()))?
+                .forget();
+            self.send(&client, &self.payload(&text)).await?;
+        }
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        if let Some(token) = self.refill.lock().await.take() {
+            token.cancel();
+        }
+        self.client.lock().await.take();
+        Ok(())
+    }
+}
+
+/// JSON objects of the rows of a message batch.
+///
+/// Binary messages are parsed as JSON, other batches are converted row by row.
+fn rows(msg: &MessageBatch) -> Result<Vec<Value>, Error> {
+    if let Some(values) = msg
+        .column_by_name(DEFAULT_BINARY_VALUE_FIELD)
+        .and_then(|column| column.as_any().downcast_ref::<BinaryArray>())
+    {
+        // Payloads that are not JSON can still be referenced as `{{ __value__ }}`
+        return Ok(values
+            .iter()
+            .flatten()
+            .map(|value| {
+                serde_json::from_slice(value).unwrap_or_else(
+                    |_| json!({ DEFAULT_BINARY_VALUE_FIELD: String::from_utf8_lossy(value) }),
+                )
+            })
+            .collect());
+    }
+
+    let mut buf = Vec::new();
+    let mut writer = arrow::json::LineDelimitedWriter::new(&mut buf);
+    writer
+        .write(msg)
+        .map_err(|e| Error::Process(format!("Arrow JSON Serialization error: {}", e)))?;
+    writer
+        .finish()
+        .map_err(|e| Error::Process(format!("Arrow JSON Serialization Complete Error: {}", e)))?;
+    buf.split(|b| *b == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| Ok(serde_json::from_slice(line)?))
+        .collect()
+}
+
+/// Replace each `{{ field }}` in `template` with the value of the field in `row`.
+///
+/// Nested fields are addressed with dots, e.g. `{{ host.name }}`. Missing fields render as an
+/// empty string.
+fn render(template: &str, row: &Value) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        output.push_str(&rest[..start]);
+        let name = rest[start + 2..start + end].trim();
+        let pointer = format!("/{}", name.replace('.', "/"));
+        match row.pointer(&pointer) {
+            Some(Value::String(s)) => output.push_str(s),
+            Some(Value::Null) | None => {}
+            Some(value) => output.push_str(&value.to_string()),
+        }
+        rest = &rest[start + end + 2..];
+    }
+    output.push_str(rest);
+    output
+}
+
+struct SlackOutputBuilder;
+impl OutputBuilder for SlackOutputBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Output>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Slack output configuration is missing".to_string(),
+            ));
+        }
+        let config: SlackOutputConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(SlackOutput::new(config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_output_builder("slack", Arc::new(SlackOutputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderMap;
+    use axum::response::IntoResponse;
+    use axum::Json;
+    use axum::Router;
+    use datafusion::arrow::array::{Int64Array, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn config(webhook_url: String) -> SlackOutputConfig {
+        SlackOutputConfig {
+            webhook_url,
+            template: "*{{ host }}* is at {{ cpu }}%".to_string(),
+            username: Some("arkflow".to_string()),
+            icon_emoji: None,
+            channel: None,
+            max_messages_per_minute: 60,
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let row = json!({"host": "web-1", "cpu": 93.5, "tags": {"env": "prod"}, "empty": null});
+        assert_eq!(
+            render(
+                "{{host}} {{ cpu }} {{ tags.env }} {{ empty }}{{ missing }}",
+                &row
+            ),
+            "web-1 93.5 prod "
+        );
+        assert_eq!(
+            render("no fields {{ unclosed", &row),
+            "no fields {{ unclosed"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_retries_after_rate_limit() {
+        let received = Arc::new(std::sync::Mutex::new(Vec::<Value>::new()));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let (captured, counter) = (received.clone(), calls.clone());
+        let app = Router::new().fallback(move |Json(body): Json<Value>| {
+            let captured = captured.clone();
+            let counter = counter.clone();
+            async move {
+                if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                    let mut headers = HeaderMap::new();
+                    headers.insert(header::RETRY_AFTER, "0".parse().unwrap());
+                    return (StatusCode::TOO_MANY_REQUESTS, headers, "rate_limited")
+                        .into_response();
+                }
+                captured.lock().unwrap().push(body);
+                "ok".into_response()
+            }
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let output = SlackOutput::new(config(format!("http://{}/hook", addr))).unwrap();
+        output.connect().await.unwrap();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("host", DataType::Utf8, false),
+            Field::new("cpu", DataType::Int64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["web-1", "web-2"])),
+                Arc::new(Int64Array::from(vec![93, 97])),
+            ],
+        )
+        .unwrap();
+        output.write(MessageBatch::new_arrow(batch)).await.unwrap();
+        output.close().await.unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        let received = received.lock().unwrap();
+        assert_eq!(
+            received[0],
+            json!({
+                "text": "*web-1* is at 93%",
+                "blocks": [
+                    {"type": "section", "text": {"type": "mrkdwn", "text": "*web-1* is at 93%"}}
+                ],
+                "username": "arkflow"
+            })
+        );
+        assert_eq!(received[1]["text"], "*web-2* is at 97%");
+    }
+
+    #[tokio::test]
+    async fn test_binary_rows() {
+        let msg = MessageBatch::new_binary(vec![br#"{"host": "db-1"}"#.to_vec()]).unwrap();
+        assert_eq!(rows(&msg).unwrap(), vec![json!({"host": "db-1"})]);
+    }
+
+    #[test]
+    fn test_zero_rate_limit() {
+        let mut config = config("http://127.0.0.1:1".to_string());
+        config.max_messages_per_minute = 0;
+        assert!(matches!(SlackOutput::new(config), Err(Error::Config(_))));
+    }
+}
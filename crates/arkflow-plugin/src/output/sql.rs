@@ -11,25 +11,51 @@
  *    See the License for the specific language governing permissions and
  *    limitations under the License.
  */
-use arkflow_core::output::{register_output_builder, Output, OutputBuilder};
+use arkflow_core::output::{
+    register_output_builder, Output, OutputBuilder, TransactionCoordinator, TransactionId,
+};
 use arkflow_core::{Error, MessageBatch, Resource};
 
 use async_trait::async_trait;
 use datafusion::arrow::array::{
-    Array, BooleanArray, Float64Array, Int64Array, StringArray, UInt64Array,
+    Array, AsArray, BooleanArray, Float64Array, Int64Array, StringArray, UInt64Array,
+};
+use datafusion::arrow::compute::{lexsort_to_indices, take_record_batch, SortColumn};
+use datafusion::arrow::datatypes::{
+    DataType, Date32Type, Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, Int8Type,
+    TimeUnit, TimestampMicrosecondType, TimestampMillisecondType, TimestampNanosecondType,
+    TimestampSecondType, UInt16Type, UInt32Type, UInt8Type,
 };
-use datafusion::arrow::datatypes::DataType;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::temporal_conversions::as_datetime;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 use tokio::sync::Mutex;
 use tokio_util::sync::CancellationToken;
-use tracing::warn;
+use tracing::{info, warn};
+
+use sqlx::mysql::{MySqlConnectOptions, MySqlPoolOptions, MySqlSslMode};
+use sqlx::pool::PoolConnection;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions, PgSslMode};
+use sqlx::{
+    Connection, MySql, MySqlConnection, MySqlPool, PgConnection, PgPool, Postgres, QueryBuilder,
+    Transaction,
+};
 
-use sqlx::mysql::{MySqlConnectOptions, MySqlSslMode};
-use sqlx::postgres::{PgConnectOptions, PgSslMode};
-use sqlx::{Connection, MySqlConnection, PgConnection, QueryBuilder};
+/// Temporary table rows are copied to before being inserted into a hypertable
+const COPY_STAGING_TABLE: &str = "arkflow_copy_staging";
+/// Seconds between the Unix epoch and the PostgreSQL epoch (2000-01-01)
+const PG_EPOCH_OFFSET_SECS: i64 = 946_684_800;
+/// Days between the Unix epoch and the PostgreSQL epoch (2000-01-01)
+const PG_EPOCH_OFFSET_DAYS: i32 = 10_957;
+/// First delay between reconnection attempts after a failed health check
+const RECONNECT_INITIAL_DELAY: Duration = Duration::from_secs(1);
+/// Upper bound of the delay between reconnection attempts
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
 
 #[derive(Debug, Clone)]
 enum SqlValue {
@@ -49,13 +75,124 @@ enum DatabaseType {
     // Sqlite,
 }
 
-enum DatabaseConnection {
-    Mysql(MySqlConnection),
-    Postgres(PgConnection),
-    // Sqlite(SqliteConnection),
+#[derive(Clone)]
+enum DatabasePool {
+    Mysql(MySqlPool),
+    Postgres(PgPool),
+    // Sqlite(SqlitePool),
+}
+
+/// Map a failure to acquire a pooled connection to an engine error
+fn acquire_error(e: sqlx::Error) -> Error {
+    match e {
+        sqlx::Error::PoolTimedOut => Error::Timeout,
+        sqlx::Error::PoolClosed => Error::Disconnection,
+        e => Error::Connection(format!("Failed to acquire a database connection: {}", e)),
+    }
+}
+
+impl DatabasePool {
+    /// Close all connections of the pool
+    async fn close(&self) {
+        match self {
+            DatabasePool::Mysql(pool) => pool.close().await,
+            DatabasePool::Postgres(pool) => pool.close().await,
+        }
+    }
+
+    /// Run `SELECT 1` on a connection of the pool
+    async fn ping(&self) -> Result<(), sqlx::Error> {
+        match self {
+            DatabasePool::Mysql(pool) => sqlx::query("SELECT 1").execute(pool).await.map(|_| ()),
+            DatabasePool::Postgres(pool) => sqlx::query("SELECT 1").execute(pool).await.map(|_| ()),
+        }
+    }
+
+    /// Acquire a connection for a single write
+    async fn acquire(&self) -> Result<PooledConnection, Error> {
+        match self {
+            DatabasePool::Mysql(pool) => pool
+                .acquire()
+                .await
+                .map(PooledConnection::Mysql)
+                .map_err(acquire_error),
+            DatabasePool::Postgres(pool) => pool
+                .acquire()
+                .await
+                .map(PooledConnection::Postgres)
+                .map_err(acquire_error),
+        }
+    }
+
+    /// Begin a transaction on a connection of the pool
+    async fn begin(&self) -> Result<DatabaseTransaction, Error> {
+        match self {
+            DatabasePool::Mysql(pool) => pool
+                .begin()
+                .await
+                .map(DatabaseTransaction::Mysql)
+                .map_err(acquire_error),
+            DatabasePool::Postgres(pool) => pool
+                .begin()
+                .await
+                .map(DatabaseTransaction::Postgres)
+                .map_err(acquire_error),
+        }
+    }
+}
+
+/// Connection acquired from the pool for a single write
+enum PooledConnection {
+    Mysql(PoolConnection<MySql>),
+    Postgres(PoolConnection<Postgres>),
+}
+
+impl PooledConnection {
+    fn connection(&mut self) -> DatabaseConnection<'_> {
+        match self {
+            PooledConnection::Mysql(conn) => DatabaseConnection::Mysql(conn),
+            PooledConnection::Postgres(conn) => DatabaseConnection::Postgres(conn),
+        }
+    }
+}
+
+/// Transaction opened by [`TransactionCoordinator::begin`]
+enum DatabaseTransaction {
+    Mysql(Transaction<'static, MySql>),
+    Postgres(Transaction<'static, Postgres>),
+}
+
+impl DatabaseTransaction {
+    fn connection(&mut self) -> DatabaseConnection<'_> {
+        match self {
+            DatabaseTransaction::Mysql(tx) => DatabaseConnection::Mysql(tx),
+            DatabaseTransaction::Postgres(tx) => DatabaseConnection::Postgres(tx),
+        }
+    }
+
+    async fn commit(self) -> Result<(), sqlx::Error> {
+        match self {
+            DatabaseTransaction::Mysql(tx) => tx.commit().await,
+            DatabaseTransaction::Postgres(tx) => tx.commit().await,
+        }
+    }
+
+    async fn rollback(self) -> Result<(), sqlx::Error> {
+        match self {
+            DatabaseTransaction::Mysql(tx) => tx.rollback().await,
+            DatabaseTransaction::Postgres(tx) => tx.rollback().await,
+        }
+    }
+}
+
+/// Connection a batch is written with: a pooled connection, or the connection of the open
+/// transaction
+enum DatabaseConnection<'a> {
+    Mysql(&'a mut MySqlConnection),
+    Postgres(&'a mut PgConnection),
 }
 
-impl DatabaseConnection {
+impl DatabaseConnection<'_> {
     /// Executes an INSERT query with the given columns and rows
     /// Handles type conversion and proper escaping for different database types
     /// Returns a Result indicating success or detailed error information
@@ -92,7 +229,7 @@ impl DatabaseConnection {
 
                 let query = query_builder.build();
                 query
-                    .execute(conn)
+                    .execute(&mut **conn)
                     .await
                     .map_err(|e| Error::Process(format!("Failed to execute MySQL query: {}", e)))?;
 
@@ -120,9 +257,13 @@ impl DatabaseConnection {
                         };
                     }
                 });
+                // Rows already stored in the hypertable are skipped
+                if output_config.timescaledb.is_some() {
+                    query_builder.push(" ON CONFLICT DO NOTHING");
+                }
 
                 let query = query_builder.build();
-                query.execute(conn).await.map_err(|e| {
+                query.execute(&mut **conn).await.map_err(|e| {
                     Error::Process(format!("Failed to execute PostgresSQL query: {}", e))
                 })?;
 
@@ -130,6 +271,73 @@ impl DatabaseConnection {
             }
         }
     }
+
+    /// Writes the batch with the binary COPY protocol of PostgreSQL.
+    ///
+    /// COPY cannot skip conflicting rows, so rows bound for a hypertable are first copied to a
+    /// temporary table and then inserted with `ON CONFLICT DO NOTHING` in the same transaction.
+    async fn execute_copy(
+        &mut self,
+        output_config: &SqlOutputConfig,
+        batch: &RecordBatch,
+    ) -> Result<(), Error> {
+        let DatabaseConnection::Postgres(conn) = self else {
+            return Err(Error::Config(
+                "COPY is only supported by PostgreSQL".to_string(),
+            ));
+        };
+        let columns = batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| format!("\"{}\"", f.name()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let data = pg_copy_binary(batch)?;
+        let copy_error =
+            |e: sqlx::Error| Error::Process(format!("Failed to copy rows to PostgreSQL: {}", e));
+
+        if output_config.timescaledb.is_none() {
+            let mut copy = conn
+                .copy_in_raw(&format!(
+                    "COPY {} ({}) FROM STDIN WITH (FORMAT binary)",
+                    output_config.table_name, columns
+                ))
+                .await
+                .map_err(copy_error)?;
+            copy.send(data).await.map_err(copy_error)?;
+            copy.finish().await.map_err(copy_error)?;
+            return Ok(());
+        }
+
+        // A savepoint when the connection is in an open transaction
+        let mut tx = conn.begin().await.map_err(acquire_error)?;
+        sqlx::query(&format!(
+            "CREATE TEMP TABLE IF NOT EXISTS {} (LIKE {} INCLUDING DEFAULTS) ON COMMIT DELETE ROWS",
+            COPY_STAGING_TABLE, output_config.table_name
+        ))
+        .execute(&mut *tx)
+        .await
+        .map_err(copy_error)?;
+        let mut copy = tx
+            .copy_in_raw(&format!(
+                "COPY {} ({}) FROM STDIN WITH (FORMAT binary)",
+                COPY_STAGING_TABLE, columns
+            ))
+            .await
+            .map_err(copy_error)?;
+        copy.send(data).await.map_err(copy_error)?;
+        copy.finish().await.map_err(copy_error)?;
+        sqlx::query(&format!(
+            "INSERT INTO {} ({}) SELECT {} FROM {} ON CONFLICT DO NOTHING",
+            output_config.table_name, columns, columns, COPY_STAGING_TABLE
+        ))
+        .execute(&mut *tx)
+        .await
+        .map_err(copy_error)?;
+        tx.commit().await.map_err(copy_error)?;
+        Ok(())
+    }
 }
 
 /// Configuration for SQL output
@@ -138,6 +346,45 @@ struct SqlOutputConfig {
     /// SQL query statement
     output_type: DatabaseType,
     table_name: String,
+    /// Cast column types without a direct SQL mapping instead of failing
+    #[serde(default = "default_type_coercion")]
+    type_coercion: bool,
+    /// Minimum number of idle connections kept in the pool
+    #[serde(default)]
+    pool_min_connections: u32,
+    /// Maximum number of connections shared by concurrent writes
+    #[serde(default = "default_pool_max_connections")]
+    pool_max_connections: u32,
+    /// Maximum time to wait for a connection from the pool (ms)
+    #[serde(default = "default_timeout_ms")]
+    timeout_ms: u64,
+    /// TimescaleDB hypertable the rows are written to (PostgreSQL only)
+    timescaledb: Option<TimescaleConfig>,
+    /// Write rows with the binary COPY protocol instead of INSERT statements (PostgreSQL only)
+    #[serde(default)]
+    use_copy: bool,
+    /// Check the database with `SELECT 1` every this many seconds and reconnect on failure
+    health_check_interval_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TimescaleConfig {
+    /// Time column the hypertable is partitioned by
+    time_column: String,
+    /// Column of the space partitions of the hypertable
+    partition_column: Option<String>,
+}
+
+fn default_type_coercion() -> bool {
+    true
+}
+
+fn default_pool_max_connections() -> u32 {
+    10
+}
+
+fn default_timeout_ms() -> u64 {
+    30_000
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -230,68 +477,265 @@ impl SslConfig {
 
 struct SqlOutput {
     sql_config: SqlOutputConfig,
-    conn_lock: Arc<Mutex<Option<DatabaseConnection>>>,
+    /// Connection pool, only locked to clone or replace the pool handle
+    pool: Arc<RwLock<Option<DatabasePool>>>,
+    /// Cleared while the health check is reconnecting
+    connected: Arc<AtomicBool>,
+    health_check_started: AtomicBool,
     cancellation_token: CancellationToken,
+    /// Transaction the writes are made in, between `begin` and its commit or rollback
+    transaction: Mutex<Option<(TransactionId, DatabaseTransaction)>>,
+    next_transaction_id: AtomicU64,
 }
 
 impl SqlOutput {
     fn new(sql_config: SqlOutputConfig) -> Result<Self, Error> {
+        if sql_config.pool_max_connections == 0 {
+            return Err(Error::Config(
+                "pool_max_connections must be greater than 0".to_string(),
+            ));
+        }
+        if sql_config.pool_min_connections > sql_config.pool_max_connections {
+            return Err(Error::Config(
+                "pool_min_connections must not exceed pool_max_connections".to_string(),
+            ));
+        }
+        let is_postgres = matches!(sql_config.output_type, DatabaseType::Postgres(_));
+        if (sql_config.use_copy || sql_config.timescaledb.is_some()) && !is_postgres {
+            return Err(Error::Config(
+                "use_copy and timescaledb require a PostgreSQL output".to_string(),
+            ));
+        }
+        if sql_config.health_check_interval_secs == Some(0) {
+            return Err(Error::Config(
+                "health_check_interval_secs must be greater than 0".to_string(),
+            ));
+        }
         let cancellation_token = CancellationToken::new();
 
         Ok(Self {
             sql_config,
-            conn_lock: Arc::new(Mutex::new(None)),
+            pool: Arc::new(RwLock::new(None)),
+            connected: Arc::new(AtomicBool::new(false)),
+            health_check_started: AtomicBool::new(false),
             cancellation_token,
+            transaction: Mutex::new(None),
+            next_transaction_id: AtomicU64::new(0),
         })
     }
+
+    fn pool(&self) -> Result<Option<DatabasePool>, Error> {
+        read_pool(&self.pool)
+    }
+
+    /// Close transaction `id`, which must be the open one
+    async fn take_transaction(&self, id: TransactionId) -> Result<DatabaseTransaction, Error> {
+        let mut transaction = self.transaction.lock().await;
+        match transaction.take() {
+            Some((open_id, tx)) if open_id == id => Ok(tx),
+            other => {
+                *transaction = other;
+                Err(Error::Process(format!(
+                    "SQL transaction {} is not open",
+                    id
+                )))
+            }
+        }
+    }
+}
+
+fn read_pool(pool: &RwLock<Option<DatabasePool>>) -> Result<Option<DatabasePool>, Error> {
+    pool.read()
+        .map(|pool| pool.clone())
+        .map_err(|_| Error::Unknown("SQL output pool lock poisoned".to_string()))
+}
+
+/// Replace the pool, closing the previous one
+async fn replace_pool(
+    pool: &RwLock<Option<DatabasePool>>,
+    new_pool: Option<DatabasePool>,
+) -> Result<(), Error> {
+    let previous = {
+        let mut pool = pool
+            .write()
+            .map_err(|_| Error::Unknown("SQL output pool lock poisoned".to_string()))?;
+        std::mem::replace(&mut *pool, new_pool)
+    };
+    if let Some(previous) = previous {
+        previous.close().await;
+    }
+    Ok(())
 }
 
 #[async_trait]
 impl Output for SqlOutput {
     async fn connect(&self) -> Result<(), Error> {
-        let conn = self.init_connect().await?;
-        let mut conn_guard = self.conn_lock.lock().await;
-        *conn_guard = Some(conn);
+        let pool = init_pool(&self.sql_config).await?;
+        replace_pool(&self.pool, Some(pool)).await?;
+        self.connected.store(true, Ordering::Release);
 
+        if let Some(interval) = self.sql_config.health_check_interval_secs {
+            if !self.health_check_started.swap(true, Ordering::AcqRel) {
+                tokio::spawn(health_check(
+                    self.sql_config.clone(),
+                    Duration::from_secs(interval),
+                    self.pool.clone(),
+                    self.connected.clone(),
+                    self.cancellation_token.clone(),
+                ));
+            }
+        }
         Ok(())
     }
 
     async fn write(&self, msg: MessageBatch) -> Result<(), Error> {
-        let mut conn_guard = self.conn_lock.lock().await;
-        let conn = conn_guard.as_mut().ok_or_else(|| Error::Disconnection)?;
+        if !self.connected.load(Ordering::Acquire) {
+            return Err(Error::Disconnection);
+        }
+        let mut transaction = self.transaction.lock().await;
+        if let Some((_, tx)) = transaction.as_mut() {
+            return self.insert_row(&mut tx.connection(), &msg).await;
+        }
+        drop(transaction);
 
-        self.insert_row(conn, &msg).await?;
+        let pool = self.pool()?.ok_or(Error::Disconnection)?;
+        let mut conn = pool.acquire().await?;
+        self.insert_row(&mut conn.connection(), &msg).await?;
         Ok(())
     }
 
     async fn close(&self) -> Result<(), Error> {
         self.cancellation_token.cancel();
-        Ok(())
+        self.connected.store(false, Ordering::Release);
+        // Dropping an open transaction rolls it back
+        self.transaction.lock().await.take();
+        replace_pool(&self.pool, None).await
+    }
+
+    fn transaction_coordinator(&self) -> Option<&dyn TransactionCoordinator> {
+        Some(self)
     }
 }
 
-impl SqlOutput {
-    /// Initialize a new DB connection.  
-    /// If `ssl` is configured, apply root certificates to the SSL options.
-    async fn init_connect(&self) -> Result<DatabaseConnection, Error> {
-        let conn = match &self.sql_config.output_type {
-            DatabaseType::Mysql(config) => self.generate_mysql_conn(config).await?,
-            DatabaseType::Postgres(config) => self.generate_postgres_conn(config).await?,
+#[async_trait]
+impl TransactionCoordinator for SqlOutput {
+    async fn begin(&self) -> Result<TransactionId, Error> {
+        if !self.connected.load(Ordering::Acquire) {
+            return Err(Error::Disconnection);
+        }
+        let mut transaction = self.transaction.lock().await;
+        if transaction.is_some() {
+            return Err(Error::Process(
+                "A SQL transaction is already open".to_string(),
+            ));
+        }
+        let pool = self.pool()?.ok_or(Error::Disconnection)?;
+        let tx = pool.begin().await?;
+        let id = self.next_transaction_id.fetch_add(1, Ordering::Relaxed);
+        *transaction = Some((id, tx));
+        Ok(id)
+    }
+
+    async fn commit(&self, id: TransactionId) -> Result<(), Error> {
+        let tx = self.take_transaction(id).await?;
+        tx.commit()
+            .await
+            .map_err(|e| Error::Process(format!("Failed to commit SQL transaction: {}", e)))
+    }
+
+    async fn rollback(&self, id: TransactionId) -> Result<(), Error> {
+        let tx = self.take_transaction(id).await?;
+        tx.rollback()
+            .await
+            .map_err(|e| Error::Process(format!("Failed to roll back SQL transaction: {}", e)))
+    }
+}
+
+/// Initialize a new DB connection pool.
+/// If `ssl` is configured, apply root certificates to the SSL options.
+async fn init_pool(sql_config: &SqlOutputConfig) -> Result<DatabasePool, Error> {
+    let pool = match &sql_config.output_type {
+        DatabaseType::Mysql(config) => generate_mysql_pool(sql_config, config).await?,
+        DatabaseType::Postgres(config) => generate_postgres_pool(sql_config, config).await?,
+    };
+    Ok(pool)
+}
+
+/// Ping the database every `interval`; on failure, mark the output as disconnected and
+/// replace the pool, retrying with exponential backoff until a new pool connects.
+async fn health_check(
+    sql_config: SqlOutputConfig,
+    interval: Duration,
+    pool: Arc<RwLock<Option<DatabasePool>>>,
+    connected: Arc<AtomicBool>,
+    cancellation_token: CancellationToken,
+) {
+    loop {
+        tokio::select! {
+            _ = cancellation_token.cancelled() => return,
+            _ = tokio::time::sleep(interval) => {}
+        }
+        let Ok(Some(current)) = read_pool(&pool) else {
+            continue;
         };
-        Ok(conn)
+        let Err(e) = current.ping().await else {
+            continue;
+        };
+
+        warn!("SQL output health check failed, reconnecting: {}", e);
+        connected.store(false, Ordering::Release);
+        let mut delay = RECONNECT_INITIAL_DELAY;
+        loop {
+            match init_pool(&sql_config).await {
+                Ok(new_pool) => {
+                    if let Err(e) = replace_pool(&pool, Some(new_pool)).await {
+                        warn!("Failed to replace the SQL output pool: {}", e);
+                        return;
+                    }
+                    connected.store(true, Ordering::Release);
+                    info!("SQL output reconnected");
+                    break;
+                }
+                Err(e) => {
+                    warn!(
+                        "SQL output reconnect failed, retrying in {:?}: {}",
+                        delay, e
+                    );
+                    tokio::select! {
+                        _ = cancellation_token.cancelled() => return,
+                        _ = tokio::time::sleep(delay) => {}
+                    }
+                    delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+                }
+            }
+        }
     }
+}
 
+impl SqlOutput {
     /// Processes a batch of Arrow data and inserts it into the database
     /// 1. Extracts schema and column names
     /// 2. Converts each row to SQL-compatible values
     /// 3. Executes the insert query with proper batching
     async fn insert_row(
         &self,
-        conn: &mut DatabaseConnection,
+        conn: &mut DatabaseConnection<'_>,
         msg: &MessageBatch,
     ) -> Result<(), Error> {
+        let sorted;
+        let msg: &RecordBatch = match &self.sql_config.timescaledb {
+            Some(timescale) => {
+                sorted = sort_for_hypertable(msg, timescale)?;
+                &sorted
+            }
+            None => msg,
+        };
+        if self.sql_config.use_copy {
+            return conn.execute_copy(&self.sql_config, msg).await;
+        }
+
         let schema = msg.schema();
-        let num_rows = msg.len();
+        let num_rows = msg.num_rows();
         let num_columns = schema.fields().len();
         let columns: Vec<String> = (0..num_columns)
             .map(|i| schema.field(i).name().clone())
@@ -364,45 +808,273 @@ impl SqlOutput {
                     Ok(SqlValue::Boolean(bool_array.value(row_index)))
                 }
             }
+            _ if self.sql_config.type_coercion => coerce_value(column, row_index),
             _ => Err(Error::Process(format!(
                 "Unsupported data type: {:?}",
                 column_type
             ))),
         }
     }
+}
 
-    /// Generates MySQL SSL connection options based on configuration
-    /// Validates SSL mode and sets up certificates if provided
-    async fn generate_mysql_conn(&self, config: &MysqlConfig) -> Result<DatabaseConnection, Error> {
-        let mysql_conn = if let Some(ssl) = &config.ssl {
-            let opts = ssl.generate_mysql_ssl_opts(config).await?;
-            MySqlConnection::connect_with(&opts)
-                .await
-                .map_err(|e| Error::Config(format!("Failed to connect to MySQL with SSL: {}", e)))?
-        } else {
-            MySqlConnection::connect(&config.uri)
-                .await
-                .map_err(|e| Error::Config(format!("Failed to connect to MySQL: {}", e)))?
+/// Generates MySQL SSL connection options based on configuration
+/// Validates SSL mode and sets up certificates if provided
+async fn generate_mysql_pool(
+    sql_config: &SqlOutputConfig,
+    config: &MysqlConfig,
+) -> Result<DatabasePool, Error> {
+    let opts = match &config.ssl {
+        Some(ssl) => ssl.generate_mysql_ssl_opts(config).await?,
+        None => MySqlConnectOptions::from_str(&config.uri)
+            .map_err(|e| Error::Config(format!("Invalid MySQL URI: {}", e)))?,
+    };
+    let pool = MySqlPoolOptions::new()
+        .min_connections(sql_config.pool_min_connections)
+        .max_connections(sql_config.pool_max_connections)
+        .acquire_timeout(Duration::from_millis(sql_config.timeout_ms))
+        .connect_with(opts)
+        .await
+        .map_err(|e| Error::Config(format!("Failed to connect to MySQL: {}", e)))?;
+    Ok(DatabasePool::Mysql(pool))
+}
+
+async fn generate_postgres_pool(
+    sql_config: &SqlOutputConfig,
+    config: &PostgresConfig,
+) -> Result<DatabasePool, Error> {
+    let opts = match &config.ssl {
+        Some(ssl) => ssl.generate_postgres_ssl_opts(config).await?,
+        None => PgConnectOptions::from_str(&config.uri)
+            .map_err(|e| Error::Config(format!("Invalid PostgreSQL URI: {}", e)))?,
+    };
+    let pool = PgPoolOptions::new()
+        .min_connections(sql_config.pool_min_connections)
+        .max_connections(sql_config.pool_max_connections)
+        .acquire_timeout(Duration::from_millis(sql_config.timeout_ms))
+        .connect_with(opts)
+        .await
+        .map_err(|e| Error::Config(format!("Failed to connect to PostgreSQL: {}", e)))?;
+    Ok(DatabasePool::Postgres(pool))
+}
+
+/// Order the rows by partition and time so that consecutive rows land in the same chunk
+fn sort_for_hypertable(
+    batch: &RecordBatch,
+    config: &TimescaleConfig,
+) -> Result<RecordBatch, Error> {
+    let mut names = Vec::with_capacity(2);
+    names.extend(config.partition_column.as_deref());
+    names.push(config.time_column.as_str());
+
+    let mut sort_columns = Vec::with_capacity(names.len());
+    for name in names {
+        let Some(values) = batch.column_by_name(name) else {
+            return Err(Error::Process(format!(
+                "Hypertable column {} is missing from the batch",
+                name
+            )));
         };
-        Ok(DatabaseConnection::Mysql(mysql_conn))
+        sort_columns.push(SortColumn {
+            values: Arc::clone(values),
+            options: None,
+        });
     }
 
-    async fn generate_postgres_conn(
-        &self,
-        config: &PostgresConfig,
-    ) -> Result<DatabaseConnection, Error> {
-        let postgres_conn = if let Some(ssl) = &config.ssl {
-            let opts = ssl.generate_postgres_ssl_opts(config).await?;
-            PgConnection::connect_with(&opts).await.map_err(|e| {
-                Error::Config(format!("Failed to connect to PostgreSQL with SSL: {}", e))
-            })?
-        } else {
-            PgConnection::connect(&config.uri)
-                .await
-                .map_err(|e| Error::Config(format!("Failed to connect to PostgreSQL: {}", e)))?
+    let indices = lexsort_to_indices(&sort_columns, None)
+        .map_err(|e| Error::Process(format!("Failed to sort rows: {}", e)))?;
+    take_record_batch(batch, &indices)
+        .map_err(|e| Error::Process(format!("Failed to sort rows: {}", e)))
+}
+
+/// Encode the batch in the binary COPY format of PostgreSQL.
+///
+/// The binary format is not converted by the server, so the column types of the table must
+/// match: integers map to `int2`, `int4` or `int8` by width, floats to `float4` or `float8`,
+/// strings to `text`, binaries to `bytea`, dates to `date` and timestamps to `timestamp` or
+/// `timestamptz`.
+fn pg_copy_binary(batch: &RecordBatch) -> Result<Vec<u8>, Error> {
+    let mut buf = Vec::with_capacity(batch.get_array_memory_size());
+    // Signature, flags and header extension length
+    buf.extend_from_slice(b"PGCOPY\n\xff\r\n\0");
+    buf.extend_from_slice(&0i32.to_be_bytes());
+    buf.extend_from_slice(&0i32.to_be_bytes());
+
+    let field_count = i16::try_from(batch.num_columns())
+        .map_err(|_| Error::Process("Too many columns for COPY".to_string()))?;
+    for row in 0..batch.num_rows() {
+        buf.extend_from_slice(&field_count.to_be_bytes());
+        for column in batch.columns() {
+            encode_copy_value(column.as_ref(), row, &mut buf)?;
+        }
+    }
+    buf.extend_from_slice(&(-1i16).to_be_bytes());
+    Ok(buf)
+}
+
+/// Append the length and binary representation of a value
+fn encode_copy_value(column: &dyn Array, row: usize, buf: &mut Vec<u8>) -> Result<(), Error> {
+    if column.is_null(row) {
+        buf.extend_from_slice(&(-1i32).to_be_bytes());
+        return Ok(());
+    }
+
+    let micros = |value: i64, per_second: i64| {
+        let micros = match per_second {
+            1 => value.saturating_mul(1_000_000),
+            1_000 => value.saturating_mul(1_000),
+            1_000_000 => value,
+            _ => value / 1_000,
         };
-        Ok(DatabaseConnection::Postgres(postgres_conn))
+        micros - PG_EPOCH_OFFSET_SECS * 1_000_000
+    };
+    let bytes: Vec<u8> = match column.data_type() {
+        DataType::Boolean => vec![column.as_boolean().value(row) as u8],
+        DataType::Int8 => i16::from(column.as_primitive::<Int8Type>().value(row))
+            .to_be_bytes()
+            .to_vec(),
+        DataType::UInt8 => i16::from(column.as_primitive::<UInt8Type>().value(row))
+            .to_be_bytes()
+            .to_vec(),
+        DataType::Int16 => column
+            .as_primitive::<Int16Type>()
+            .value(row)
+            .to_be_bytes()
+            .to_vec(),
+        DataType::UInt16 => i32::from(column.as_primitive::<UInt16Type>().value(row))
+            .to_be_bytes()
+            .to_vec(),
+        DataType::Int32 => column
+            .as_primitive::<Int32Type>()
+            .value(row)
+            .to_be_bytes()
+            .to_vec(),
+        DataType::UInt32 => i64::from(column.as_primitive::<UInt32Type>().value(row))
+            .to_be_bytes()
+            .to_vec(),
+        DataType::Int64 => column
+            .as_primitive::<Int64Type>()
+            .value(row)
+            .to_be_bytes()
+            .to_vec(),
+        DataType::Float32 => column
+            .as_primitive::<Float32Type>()
+            .value(row)
+            .to_be_bytes()
+            .to_vec(),
+        DataType::Float64 => column
+            .as_primitive::<Float64Type>()
+            .value(row)
+            .to_be_bytes()
+            .to_vec(),
+        DataType::Utf8 => column.as_string::<i32>().value(row).as_bytes().to_vec(),
+        DataType::LargeUtf8 => column.as_string::<i64>().value(row).as_bytes().to_vec(),
+        DataType::Binary => column.as_binary::<i32>().value(row).to_vec(),
+        DataType::LargeBinary => column.as_binary::<i64>().value(row).to_vec(),
+        DataType::Date32 => (column.as_primitive::<Date32Type>().value(row) - PG_EPOCH_OFFSET_DAYS)
+            .to_be_bytes()
+            .to_vec(),
+        DataType::Timestamp(unit, _) => {
+            let value = match unit {
+                TimeUnit::Second => {
+                    micros(column.as_primitive::<TimestampSecondType>().value(row), 1)
+                }
+                TimeUnit::Millisecond => micros(
+                    column.as_primitive::<TimestampMillisecondType>().value(row),
+                    1_000,
+                ),
+                TimeUnit::Microsecond => micros(
+                    column.as_primitive::<TimestampMicrosecondType>().value(row),
+                    1_000_000,
+                ),
+                TimeUnit::Nanosecond => micros(
+                    column.as_primitive::<TimestampNanosecondType>().value(row),
+                    1_000_000_000,
+                ),
+            };
+            value.to_be_bytes().to_vec()
+        }
+        data_type => {
+            return Err(Error::Process(format!(
+                "Unsupported data type for COPY: {:?}",
+                data_type
+            )))
+        }
+    };
+
+    let len = i32::try_from(bytes.len())
+        .map_err(|_| Error::Process("Value too large for COPY".to_string()))?;
+    buf.extend_from_slice(&len.to_be_bytes());
+    buf.extend_from_slice(&bytes);
+    Ok(())
+}
+
+/// Convert a value of a type without a direct SQL mapping.
+///
+/// Smaller integers and floats are widened, dates are written as ISO 8601 strings and
+/// timestamps as RFC 3339 strings in UTC.
+fn coerce_value(column: &dyn Array, row_index: usize) -> Result<SqlValue, Error> {
+    if column.is_null(row_index) {
+        return Ok(SqlValue::Null);
     }
+
+    let value = match column.data_type() {
+        DataType::Int8 => {
+            SqlValue::Int64(column.as_primitive::<Int8Type>().value(row_index).into())
+        }
+        DataType::Int16 => {
+            SqlValue::Int64(column.as_primitive::<Int16Type>().value(row_index).into())
+        }
+        DataType::Int32 => {
+            SqlValue::Int64(column.as_primitive::<Int32Type>().value(row_index).into())
+        }
+        DataType::Float32 => {
+            SqlValue::Float64(column.as_primitive::<Float32Type>().value(row_index).into())
+        }
+        DataType::Date32 => {
+            let date = column
+                .as_primitive::<Date32Type>()
+                .value_as_date(row_index)
+                .ok_or_else(|| Error::Process("Date32 value out of range".to_string()))?;
+            SqlValue::String(date.format("%Y-%m-%d").to_string())
+        }
+        DataType::Timestamp(unit, _) => {
+            let datetime = match unit {
+                TimeUnit::Second => as_datetime::<TimestampSecondType>(
+                    column
+                        .as_primitive::<TimestampSecondType>()
+                        .value(row_index),
+                ),
+                TimeUnit::Millisecond => as_datetime::<TimestampMillisecondType>(
+                    column
+                        .as_primitive::<TimestampMillisecondType>()
+                        .value(row_index),
+                ),
+                TimeUnit::Microsecond => as_datetime::<TimestampMicrosecondType>(
+                    column
+                        .as_primitive::<TimestampMicrosecondType>()
+                        .value(row_index),
+                ),
+                TimeUnit::Nanosecond => as_datetime::<TimestampNanosecondType>(
+                    column
+                        .as_primitive::<TimestampNanosecondType>()
+                        .value(row_index),
+                ),
+            }
+            .ok_or_else(|| Error::Process("Timestamp value out of range".to_string()))?;
+            SqlValue::String(
+                datetime
+                    .and_utc()
+                    .to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true),
+            )
+        }
+        data_type => {
+            return Err(Error::Process(format!(
+                "Unsupported data type: {:?}",
+                data_type
+            )))
+        }
+    };
+    Ok(value)
 }
 
 struct SqlOutputBuilder;
@@ -428,3 +1100,205 @@ impl OutputBuilder for SqlOutputBuilder {
 pub fn init() -> Result<(), Error> {
     register_output_builder("sql", Arc::new(SqlOutputBuilder))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{
+        ArrayRef, Date32Array, Float32Array, Int32Array, Int8Array, TimestampMillisecondArray,
+    };
+
+    fn coerce(column: &dyn Array) -> Vec<String> {
+        (0..column.len())
+            .map(|i| format!("{:?}", coerce_value(column, i).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_coerce_numbers() {
+        assert_eq!(
+            coerce(&Int32Array::from(vec![Some(7), None])),
+            vec!["Int64(7)", "Null"]
+        );
+        assert_eq!(coerce(&Int8Array::from(vec![-3])), vec!["Int64(-3)"]);
+        assert_eq!(coerce(&Float32Array::from(vec![1.5])), vec!["Float64(1.5)"]);
+    }
+
+    #[test]
+    fn test_coerce_temporal() {
+        assert_eq!(
+            coerce(&Date32Array::from(vec![19723])),
+            vec![r#"String("2024-01-01")"#]
+        );
+        let timestamps =
+            TimestampMillisecondArray::from(vec![1704067200123]).with_timezone("+08:00");
+        assert_eq!(
+            coerce(&timestamps),
+            vec![r#"String("2024-01-01T00:00:00.123Z")"#]
+        );
+    }
+
+    #[test]
+    fn test_pool_config() {
+        let config: SqlOutputConfig = serde_json::from_value(serde_json::json!({
+            "output_type": {"type": "postgres", "uri": "postgres://localhost/db"},
+            "table_name": "events",
+        }))
+        .unwrap();
+        assert_eq!(config.pool_min_connections, 0);
+        assert_eq!(config.pool_max_connections, 10);
+        assert_eq!(config.timeout_ms, 30_000);
+        assert!(SqlOutput::new(config.clone()).is_ok());
+
+        let invalid = SqlOutputConfig {
+            pool_min_connections: 5,
+            pool_max_connections: 2,
+            ..config
+        };
+        assert!(matches!(SqlOutput::new(invalid), Err(Error::Config(_))));
+    }
+
+    #[tokio::test]
+    async fn test_health_check_config() {
+        let config: SqlOutputConfig = serde_json::from_value(serde_json::json!({
+            "output_type": {"type": "mysql", "uri": "mysql://localhost/db"},
+            "table_name": "events",
+            "health_check_interval_secs": 0,
+        }))
+        .unwrap();
+        assert!(matches!(
+            SqlOutput::new(config.clone()),
+            Err(Error::Config(_))
+        ));
+
+        let output = SqlOutput::new(SqlOutputConfig {
+            health_check_interval_secs: Some(30),
+            ..config
+        })
+        .unwrap();
+        let msg = MessageBatch::from_string("row").unwrap();
+        assert!(matches!(output.write(msg).await, Err(Error::Disconnection)));
+    }
+
+    #[tokio::test]
+    async fn test_transaction_not_open() {
+        let config: SqlOutputConfig = serde_json::from_value(serde_json::json!({
+            "output_type": {"type": "postgres", "uri": "postgres://localhost/db"},
+            "table_name": "events",
+        }))
+        .unwrap();
+        let output = SqlOutput::new(config).unwrap();
+        assert!(output.transaction_coordinator().is_some());
+        assert!(matches!(output.begin().await, Err(Error::Disconnection)));
+        assert!(matches!(output.commit(0).await, Err(Error::Process(_))));
+        assert!(matches!(output.rollback(0).await, Err(Error::Process(_))));
+    }
+
+    #[test]
+    fn test_coerce_unsupported() {
+        let column = datafusion::arrow::array::BinaryArray::from_vec(vec![b"x"]);
+        assert!(coerce_value(&column, 0).is_err());
+    }
+
+    #[test]
+    fn test_timescale_requires_postgres() {
+        let config: SqlOutputConfig = serde_json::from_value(serde_json::json!({
+            "output_type": {"type": "mysql", "uri": "mysql://localhost/db"},
+            "table_name": "metrics",
+            "timescaledb": {"time_column": "time"},
+        }))
+        .unwrap();
+        assert!(matches!(SqlOutput::new(config), Err(Error::Config(_))));
+
+        let config: SqlOutputConfig = serde_json::from_value(serde_json::json!({
+            "output_type": {"type": "postgres", "uri": "postgres://localhost/db"},
+            "table_name": "metrics",
+            "timescaledb": {"time_column": "time", "partition_column": "device"},
+            "use_copy": true,
+        }))
+        .unwrap();
+        assert!(SqlOutput::new(config).is_ok());
+    }
+
+    #[test]
+    fn test_sort_for_hypertable() {
+        let batch = RecordBatch::try_from_iter([
+            (
+                "device",
+                Arc::new(StringArray::from(vec!["b", "a", "b", "a"])) as ArrayRef,
+            ),
+            (
+                "time",
+                Arc::new(Int64Array::from(vec![2, 3, 1, 1])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        let config = TimescaleConfig {
+            time_column: "time".to_string(),
+            partition_column: Some("device".to_string()),
+        };
+
+        let sorted = sort_for_hypertable(&batch, &config).unwrap();
+        let devices = sorted.column(0).as_string::<i32>();
+        let times = sorted.column(1).as_primitive::<Int64Type>();
+        assert_eq!(
+            (0..4)
+                .map(|i| (devices.value(i), times.value(i)))
+                .collect::<Vec<_>>(),
+            vec![("a", 1), ("a", 3), ("b", 1), ("b", 2)]
+        );
+
+        let missing = TimescaleConfig {
+            time_column: "ts".to_string(),
+            partition_column: None,
+        };
+        assert!(sort_for_hypertable(&batch, &missing).is_err());
+    }
+
+    #[test]
+    fn test_pg_copy_binary() {
+        let batch = RecordBatch::try_from_iter([
+            (
+                "id",
+                Arc::new(Int32Array::from(vec![Some(1), None])) as ArrayRef,
+            ),
+            (
+                "name",
+                Arc::new(StringArray::from(vec!["ab", "c"])) as ArrayRef,
+            ),
+            (
+                "time",
+                Arc::new(TimestampMillisecondArray::from(vec![946_684_800_001, 0])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        let data = pg_copy_binary(&batch).unwrap();
+
+        let mut expected = b"PGCOPY\n\xff\r\n\0".to_vec();
+        expected.extend_from_slice(&[0; 8]);
+        // First row
+        expected.extend_from_slice(&3i16.to_be_bytes());
+        expected.extend_from_slice(&4i32.to_be_bytes());
+        expected.extend_from_slice(&1i32.to_be_bytes());
+        expected.extend_from_slice(&2i32.to_be_bytes());
+        expected.extend_from_slice(b"ab");
+        expected.extend_from_slice(&8i32.to_be_bytes());
+        expected.extend_from_slice(&1_000i64.to_be_bytes());
+        // Second row
+        expected.extend_from_slice(&3i16.to_be_bytes());
+        expected.extend_from_slice(&(-1i32).to_be_bytes());
+        expected.extend_from_slice(&1i32.to_be_bytes());
+        expected.extend_from_slice(b"c");
+        expected.extend_from_slice(&8i32.to_be_bytes());
+        expected.extend_from_slice(&(-946_684_800_000_000i64).to_be_bytes());
+        expected.extend_from_slice(&(-1i16).to_be_bytes());
+        assert_eq!(data, expected);
+
+        let unsupported = RecordBatch::try_from_iter([(
+            "amount",
+            Arc::new(datafusion::arrow::array::Decimal128Array::from(vec![1])) as ArrayRef,
+        )])
+        .unwrap();
+        assert!(pg_copy_binary(&unsupported).is_err());
+    }
+}
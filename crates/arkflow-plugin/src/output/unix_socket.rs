@@ -0,0 +1,194 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Unix domain socket output component
+//!
+//! Send framed messages to a process listening on a Unix domain socket
+
+use crate::component::unix_socket::{write_frame, MessageFormat};
+use arkflow_core::output::{register_output_builder, Output, OutputBuilder};
+use arkflow_core::{Error, MessageBatch, Resource, DEFAULT_BINARY_VALUE_FIELD};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::net::UnixStream;
+use tokio::sync::Mutex;
+
+/// Unix domain socket output configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UnixSocketOutputConfig {
+    /// Path of the socket file to connect to
+    socket_path: String,
+    /// Message framing
+    #[serde(default)]
+    message_format: MessageFormat,
+    /// Value field to use for message payload
+    value_field: Option<String>,
+}
+
+/// Unix domain socket output component
+struct UnixSocketOutput {
+    config: UnixSocketOutputConfig,
+    stream: Mutex<Option<UnixStream>>,
+}
+
+impl UnixSocketOutput {
+    /// Create a new Unix domain socket output component
+    fn new(config: UnixSocketOutputConfig) -> Result<Self, Error> {
+        Ok(Self {
+            config,
+            stream: Mutex::new(None),
+        })
+    }
+
+    async fn open(&self) -> Result<UnixStream, Error> {
+        UnixStream::connect(&self.config.socket_path)
+            .await
+            .map_err(|e| {
+                Error::Connection(format!(
+                    "Unable to connect to Unix socket {}: {}",
+                    self.config.socket_path, e
+                ))
+            })
+    }
+
+    async fn write_payloads(
+        stream: &mut UnixStream,
+        format: MessageFormat,
+        payloads: Vec<&[u8]>,
+    ) -> Result<(), Error> {
+        for payload in payloads {
+            write_frame(stream, format, payload).await?;
+        }
+        stream.flush().await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Output for UnixSocketOutput {
+    async fn connect(&self) -> Result<(), Error> {
+        let stream = self.open().await?;
+        let mut stream_guard = self.stream.lock().await;
+        *stream_guard = Some(stream);
+        Ok(())
+    }
+
+    async fn write(&self, msg: MessageBatch) -> Result<(), Error> {
+        let value_field = self
+            .config
+            .value_field
+            .as_deref()
+            .unwrap_or(DEFAULT_BINARY_VALUE_FIELD);
+        let payloads = msg.to_binary(value_field)?;
+
+        // The stream is dropped after a failed write, reconnect to the restarted peer
+        let mut stream_guard = self.stream.lock().await;
+        let stream = match stream_guard.as_mut() {
+            Some(stream) => stream,
+            None => stream_guard.insert(self.open().await?),
+        };
+
+        let result = Self::write_payloads(stream, self.config.message_format, payloads).await;
+        if let Err(e) = result {
+            // The peer has most likely gone away; drop the stream so the next write reconnects
+            *stream_guard = None;
+            return Err(match e {
+                Error::Io(_) => Error::Disconnection,
+                e => e,
+            });
+        }
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        let mut stream_guard = self.stream.lock().await;
+        if let Some(mut stream) = stream_guard.take() {
+            let _ = stream.shutdown().await;
+        }
+        Ok(())
+    }
+}
+
+struct UnixSocketOutputBuilder;
+impl OutputBuilder for UnixSocketOutputBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Output>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Unix socket output configuration is missing".to_string(),
+            ));
+        }
+
+        let config: UnixSocketOutputConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(UnixSocketOutput::new(config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_output_builder("unix_socket", Arc::new(UnixSocketOutputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::unix_socket::{read_frame, DEFAULT_MAX_FRAME_BYTES};
+    use tokio::io::BufReader;
+    use tokio::net::UnixListener;
+
+    #[tokio::test]
+    async fn test_reconnect_after_write_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("output.sock");
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let output = UnixSocketOutput::new(UnixSocketOutputConfig {
+            socket_path: socket_path.to_string_lossy().to_string(),
+            message_format: MessageFormat::Newline,
+            value_field: None,
+        })
+        .unwrap();
+        output.connect().await.unwrap();
+
+        // The peer goes away, writing fails once the closed connection is noticed
+        drop(listener.accept().await.unwrap());
+        let mut failed = false;
+        for _ in 0..100 {
+            let msg = MessageBatch::new_binary(vec![b"lost".to_vec()]).unwrap();
+            if output.write(msg).await.is_err() {
+                failed = true;
+                break;
+            }
+        }
+        assert!(failed);
+
+        let msg = MessageBatch::new_binary(vec![b"hello".to_vec()]).unwrap();
+        output.write(msg).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        let frame = read_frame(
+            &mut BufReader::new(stream),
+            MessageFormat::Newline,
+            DEFAULT_MAX_FRAME_BYTES,
+        )
+        .await
+        .unwrap();
+        assert_eq!(frame, Some(b"hello".to_vec()));
+
+        output.close().await.unwrap();
+    }
+}
@@ -0,0 +1,203 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! ZeroMQ output component
+//!
+//! Send messages to a ZeroMQ PUB, PUSH or REQ socket
+
+use crate::component::zeromq::attach;
+use arkflow_core::output::{register_output_builder, Output, OutputBuilder};
+use arkflow_core::{Error, MessageBatch, Resource, DEFAULT_BINARY_VALUE_FIELD};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use zeromq::{PubSocket, PushSocket, ReqSocket, Socket, SocketRecv, SocketSend, ZmqMessage};
+
+/// ZeroMQ output configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ZeroMqOutputConfig {
+    /// Socket type
+    socket_type: ZeroMqOutputSocketType,
+    /// Endpoints to bind (`tcp://*:port`) or connect to
+    endpoints: Vec<String>,
+    /// Value field to use for message payload
+    value_field: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ZeroMqOutputSocketType {
+    Pub,
+    Push,
+    Req,
+}
+
+enum OutputSocket {
+    Pub(PubSocket),
+    Push(PushSocket),
+    Req(ReqSocket),
+}
+
+impl OutputSocket {
+    async fn send(&mut self, payload: Vec<u8>) -> Result<(), Error> {
+        let message = ZmqMessage::from(payload);
+        let result = match self {
+            OutputSocket::Pub(socket) => socket.send(message).await,
+            OutputSocket::Push(socket) => socket.send(message).await,
+            OutputSocket::Req(socket) => {
+                // A REQ socket must receive the reply before sending the next request
+                match socket.send(message).await {
+                    Ok(()) => socket.recv().await.map(|_| ()),
+                    Err(e) => Err(e),
+                }
+            }
+        };
+        result.map_err(|e| Error::Process(format!("Failed to send ZeroMQ message: {}", e)))
+    }
+
+    async fn close(self) {
+        match self {
+            OutputSocket::Pub(socket) => socket.close().await,
+            OutputSocket::Push(socket) => socket.close().await,
+            OutputSocket::Req(socket) => socket.close().await,
+        };
+    }
+}
+
+/// ZeroMQ output component
+struct ZeroMqOutput {
+    config: ZeroMqOutputConfig,
+    socket: Mutex<Option<OutputSocket>>,
+}
+
+impl ZeroMqOutput {
+    /// Create a new ZeroMQ output component
+    fn new(config: ZeroMqOutputConfig) -> Result<Self, Error> {
+        if config.endpoints.is_empty() {
+            return Err(Error::Config(
+                "ZeroMQ output requires at least one endpoint".to_string(),
+            ));
+        }
+        Ok(Self {
+            config,
+            socket: Mutex::new(None),
+        })
+    }
+}
+
+#[async_trait]
+impl Output for ZeroMqOutput {
+    async fn connect(&self) -> Result<(), Error> {
+        let endpoints = &self.config.endpoints;
+        let socket = match self.config.socket_type {
+            ZeroMqOutputSocketType::Pub => {
+                let mut socket = PubSocket::new();
+                attach(&mut socket, endpoints).await?;
+                OutputSocket::Pub(socket)
+            }
+            ZeroMqOutputSocketType::Push => {
+                let mut socket = PushSocket::new();
+                attach(&mut socket, endpoints).await?;
+                OutputSocket::Push(socket)
+            }
+            ZeroMqOutputSocketType::Req => {
+                let mut socket = ReqSocket::new();
+                attach(&mut socket, endpoints).await?;
+                OutputSocket::Req(socket)
+            }
+        };
+
+        let mut socket_guard = self.socket.lock().await;
+        *socket_guard = Some(socket);
+        Ok(())
+    }
+
+    async fn write(&self, msg: MessageBatch) -> Result<(), Error> {
+        let mut socket_guard = self.socket.lock().await;
+        let socket = socket_guard
+            .as_mut()
+            .ok_or_else(|| Error::Connection("The output is not connected".to_string()))?;
+
+        let value_field = self
+            .config
+            .value_field
+            .as_deref()
+            .unwrap_or(DEFAULT_BINARY_VALUE_FIELD);
+        for payload in msg.to_binary(value_field)? {
+            socket.send(payload.to_vec()).await?;
+        }
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        if let Some(socket) = self.socket.lock().await.take() {
+            socket.close().await;
+        }
+        Ok(())
+    }
+}
+
+struct ZeroMqOutputBuilder;
+impl OutputBuilder for ZeroMqOutputBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Output>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "ZeroMQ output configuration is missing".to_string(),
+            ));
+        }
+
+        let config: ZeroMqOutputConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(ZeroMqOutput::new(config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_output_builder("zeromq", Arc::new(ZeroMqOutputBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zeromq::PullSocket;
+
+    #[tokio::test]
+    async fn test_push_output() {
+        let mut pull = PullSocket::new();
+        let endpoint = pull.bind("tcp://127.0.0.1:0").await.unwrap();
+
+        let output = ZeroMqOutput::new(ZeroMqOutputConfig {
+            socket_type: ZeroMqOutputSocketType::Push,
+            endpoints: vec![endpoint.to_string()],
+            value_field: None,
+        })
+        .unwrap();
+        output.connect().await.unwrap();
+
+        let msg = MessageBatch::new_binary(vec![b"first".to_vec(), b"second".to_vec()]).unwrap();
+        output.write(msg).await.unwrap();
+
+        for expected in ["first", "second"] {
+            let message = pull.recv().await.unwrap();
+            assert_eq!(message.get(0).unwrap().as_ref(), expected.as_bytes());
+        }
+
+        output.close().await.unwrap();
+    }
+}
@@ -0,0 +1,398 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Encrypt Processor Component
+//!
+//! Encrypt or decrypt sensitive fields with AES-256-GCM before they leave the pipeline
+
+use arkflow_core::processor::{register_processor_builder, Processor, ProcessorBuilder};
+use arkflow_core::{Error, MessageBatch, Resource, DEFAULT_BINARY_VALUE_FIELD};
+use async_trait::async_trait;
+use base64::Engine;
+use datafusion::arrow::array::{Array, ArrayRef, BinaryArray, StringArray};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Encrypt processor configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EncryptProcessorConfig {
+    /// Whether to encrypt or decrypt the fields
+    mode: EncryptMode,
+    /// Encryption algorithm
+    #[serde(default)]
+    algorithm: EncryptAlgorithm,
+    /// Hex encoded 32-byte key
+    key_hex: String,
+    /// Column names for Arrow messages, dot-separated JSON paths for binary messages
+    fields: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum EncryptMode {
+    Encrypt,
+    Decrypt,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum EncryptAlgorithm {
+    #[default]
+    Aes256gcm,
+}
+
+struct EncryptProcessor {
+    config: EncryptProcessorConfig,
+    key: LessSafeKey,
+    rng: SystemRandom,
+}
+
+impl EncryptProcessor {
+    fn new(config: EncryptProcessorConfig) -> Result<Self, Error> {
+        let key_bytes = hex::decode(&config.key_hex)
+            .map_err(|e| Error::Config(format!("Invalid encryption key: {}", e)))?;
+        if key_bytes.len() != 32 {
+            return Err(Error::Config(format!(
+                "Encryption key must be 32 bytes, got {}",
+                key_bytes.len()
+            )));
+        }
+        let algorithm = match config.algorithm {
+            EncryptAlgorithm::Aes256gcm => &AES_256_GCM,
+        };
+        let key = UnboundKey::new(algorithm, &key_bytes)
+            .map_err(|_| Error::Config("Invalid encryption key".to_string()))?;
+
+        Ok(Self {
+            config,
+            key: LessSafeKey::new(key),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// Encrypt `plaintext` as base64 of the random nonce followed by the ciphertext
+    fn encrypt(&self, plaintext: &[u8]) -> Result<String, Error> {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| Error::Process("Failed to generate a nonce".to_string()))?;
+
+        let mut in_out = plaintext.to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut in_out,
+            )
+            .map_err(|_| Error::Process("Encryption failed".to_string()))?;
+
+        let mut output = nonce.to_vec();
+        output.extend_from_slice(&in_out);
+        Ok(base64::engine::general_purpose::STANDARD.encode(output))
+    }
+
+    /// Decrypt a value produced by [`EncryptProcessor::encrypt`]
+    fn decrypt(&self, ciphertext: &str) -> Result<Vec<u8>, Error> {
+        let data = base64::engine::general_purpose::STANDARD
+            .decode(ciphertext)
+            .map_err(|e| Error::Process(format!("Invalid encrypted value: {}", e)))?;
+        if data.len() < NONCE_LEN {
+            return Err(Error::Process("Invalid encrypted value".to_string()));
+        }
+
+        let (nonce, sealed) = data.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce)
+            .map_err(|_| Error::Process("Invalid encrypted value".to_string()))?;
+        let mut in_out = sealed.to_vec();
+        let plaintext = self
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut in_out)
+            .map_err(|_| Error::Process("Decryption failed".to_string()))?;
+        Ok(plaintext.to_vec())
+    }
+
+    /// Encrypt or decrypt a single string value
+    fn transform_str(&self, value: &str) -> Result<String, Error> {
+        match self.config.mode {
+            EncryptMode::Encrypt => self.encrypt(value.as_bytes()),
+            EncryptMode::Decrypt => String::from_utf8(self.decrypt(value)?)
+                .map_err(|e| Error::Process(format!("Decrypted value is not UTF-8: {}", e))),
+        }
+    }
+
+    /// Transform the named string columns of an Arrow batch
+    fn process_arrow(&self, batch: &RecordBatch) -> Result<RecordBatch, Error> {
+        let schema = batch.schema();
+        let mut fields: Vec<Arc<Field>> = schema.fields().iter().cloned().collect();
+        let mut columns: Vec<ArrayRef> = batch.columns().to_vec();
+
+        for name in &self.config.fields {
+            let (index, field) = schema.column_with_name(name).ok_or_else(|| {
+                Error::Process(format!("Column {} not found in message batch", name))
+            })?;
+            let column = cast(&columns[index], &DataType::Utf8)
+                .map_err(|e| Error::Process(format!("Column {} is not a string: {}", name, e)))?;
+            let values = column
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .ok_or_else(|| Error::Process(format!("Column {} is not a string", name)))?;
+
+            let transformed = values
+                .iter()
+                .map(|value| value.map(|v| self.transform_str(v)).transpose())
+                .collect::<Result<StringArray, Error>>()?;
+
+            fields[index] = Arc::new(field.clone().with_data_type(DataType::Utf8));
+            columns[index] = Arc::new(transformed);
+        }
+
+        RecordBatch::try_new(
+            Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone())),
+            columns,
+        )
+        .map_err(|e| Error::Process(format!("Creating an Arrow record batch failed: {}", e)))
+    }
+
+    /// Transform the fields of a JSON document.
+    ///
+    /// Values are encrypted as their JSON serialization so that non-string values keep
+    /// their type after decryption. Missing fields are left untouched.
+    fn process_json(&self, payload: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut document: Value = serde_json::from_slice(payload)?;
+        for path in &self.config.fields {
+            let pointer = format!("/{}", path.replace('.', "/"));
+            let Some(value) = document.pointer_mut(&pointer) else {
+                continue;
+            };
+            *value = match self.config.mode {
+                EncryptMode::Encrypt => Value::String(self.encrypt(&serde_json::to_vec(value)?)?),
+                EncryptMode::Decrypt => {
+                    let Value::String(ciphertext) = value else {
+                        return Err(Error::Process(format!(
+                            "Field {} is not an encrypted string",
+                            path
+                        )));
+                    };
+                    serde_json::from_slice(&self.decrypt(ciphertext)?)?
+                }
+            };
+        }
+        Ok(serde_json::to_vec(&document)?)
+    }
+
+    /// Transform the JSON payload of each binary message
+    fn process_binary(
+        &self,
+        batch: &RecordBatch,
+        index: usize,
+        values: &BinaryArray,
+    ) -> Result<RecordBatch, Error> {
+        let transformed = values
+            .iter()
+            .map(|value| value.map(|v| self.process_json(v)).transpose())
+            .collect::<Result<BinaryArray, Error>>()?;
+
+        let mut columns: Vec<ArrayRef> = batch.columns().to_vec();
+        columns[index] = Arc::new(transformed);
+        RecordBatch::try_new(batch.schema(), columns)
+            .map_err(|e| Error::Process(format!("Creating an Arrow record batch failed: {}", e)))
+    }
+}
+
+#[async_trait]
+impl Processor for EncryptProcessor {
+    async fn process(&self, msg_batch: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+        if msg_batch.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let binary = msg_batch
+            .schema()
+            .column_with_name(DEFAULT_BINARY_VALUE_FIELD)
+            .map(|(index, _)| index)
+            .and_then(|index| {
+                msg_batch
+                    .column(index)
+                    .as_any()
+                    .downcast_ref::<BinaryArray>()
+                    .map(|values| (index, values))
+            });
+        let batch = match binary {
+            Some((index, values)) => self.process_binary(&msg_batch, index, values)?,
+            None => self.process_arrow(&msg_batch)?,
+        };
+
+        let mut result = MessageBatch::new_arrow(batch);
+        result.set_input_name(msg_batch.get_input_name());
+        Ok(vec![result])
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+struct EncryptProcessorBuilder;
+impl ProcessorBuilder for EncryptProcessorBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Processor>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Encrypt processor configuration is missing".to_string(),
+            ));
+        }
+        let config: EncryptProcessorConfig = serde_json::from_value(config.clone().unwrap())?;
+
+        Ok(Arc::new(EncryptProcessor::new(config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_processor_builder("encrypt", Arc::new(EncryptProcessorBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::Int64Array;
+    use serde_json::json;
+    use std::cell::RefCell;
+
+    const KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    fn build(mode: &str, fields: Value) -> Result<Arc<dyn Processor>, Error> {
+        EncryptProcessorBuilder.build(
+            None,
+            &Some(json!({
+                "mode": mode,
+                "algorithm": "aes256gcm",
+                "key_hex": KEY,
+                "fields": fields,
+            })),
+            &Resource {
+                temporary: Default::default(),
+                input_names: RefCell::new(Default::default()),
+            },
+        )
+    }
+
+    fn strings(msg: &MessageBatch, field: &str) -> Vec<String> {
+        let array = msg
+            .column_by_name(field)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        array.iter().map(|v| v.unwrap().to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_arrow_round_trip() {
+        let encrypt = build("encrypt", json!(["email"])).unwrap();
+        let decrypt = build("decrypt", json!(["email"])).unwrap();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("email", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec!["a@example.com", "a@example.com"])),
+            ],
+        )
+        .unwrap();
+
+        let encrypted = encrypt
+            .process(MessageBatch::new_arrow(batch.clone()))
+            .await
+            .unwrap();
+        let values = strings(&encrypted[0], "email");
+        assert!(!values.contains(&"a@example.com".to_string()));
+        // Random nonces give different ciphertexts for equal values
+        assert_ne!(values[0], values[1]);
+
+        let decrypted = decrypt
+            .process(encrypted.into_iter().next().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(*decrypted[0], batch);
+    }
+
+    #[tokio::test]
+    async fn test_binary_round_trip() {
+        let encrypt = build("encrypt", json!(["user.email", "user.age", "missing"])).unwrap();
+        let decrypt = build("decrypt", json!(["user.email", "user.age", "missing"])).unwrap();
+        let payload = json!({"user": {"email": "a@example.com", "age": 42}, "id": 1});
+        let msg = MessageBatch::new_binary(vec![serde_json::to_vec(&payload).unwrap()]).unwrap();
+
+        let encrypted = encrypt.process(msg).await.unwrap();
+        let document: Value =
+            serde_json::from_slice(encrypted[0].to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap()[0])
+                .unwrap();
+        assert_eq!(document["id"], json!(1));
+        assert!(document["user"]["email"].is_string());
+        assert!(document["user"]["age"].is_string());
+        assert_ne!(document["user"]["email"], payload["user"]["email"]);
+
+        let decrypted = decrypt
+            .process(encrypted.into_iter().next().unwrap())
+            .await
+            .unwrap();
+        let document: Value =
+            serde_json::from_slice(decrypted[0].to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap()[0])
+                .unwrap();
+        assert_eq!(document, payload);
+    }
+
+    #[tokio::test]
+    async fn test_decrypt_tampered_value() {
+        let decrypt = build("decrypt", json!(["secret"])).unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "secret",
+            DataType::Utf8,
+            false,
+        )]));
+        let value = base64::engine::general_purpose::STANDARD.encode([0u8; 32]);
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(StringArray::from(vec![value]))]).unwrap();
+        assert!(decrypt
+            .process(MessageBatch::new_arrow(batch))
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        let result = EncryptProcessorBuilder.build(
+            None,
+            &Some(json!({"mode": "encrypt", "key_hex": "abcd", "fields": ["a"]})),
+            &Resource {
+                temporary: Default::default(),
+                input_names: RefCell::new(Default::default()),
+            },
+        );
+        assert!(matches!(result, Err(Error::Config(_))));
+    }
+}
@@ -0,0 +1,232 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Group Split Processor Component
+//!
+//! Split a batch into one batch per distinct value of a column, so that outputs can write one
+//! file or make one request per group
+
+use arkflow_core::processor::{register_processor_builder, Processor, ProcessorBuilder};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use datafusion::arrow::array::{Array, ArrayRef, BooleanArray, StringArray};
+use datafusion::arrow::compute::{cast, filter_record_batch};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Column holding the group value of every row of a split batch
+const GROUP_VALUE_FIELD: &str = "_group_value";
+
+/// Group split processor configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct GroupSplitProcessorConfig {
+    /// Column whose distinct values define the groups
+    group_column: String,
+}
+
+struct GroupSplitProcessor {
+    config: GroupSplitProcessorConfig,
+}
+
+impl GroupSplitProcessor {
+    /// Rows of `batch` whose group is `value`, with the group value column
+    fn group_batch(
+        &self,
+        batch: &RecordBatch,
+        groups: &StringArray,
+        value: Option<&str>,
+    ) -> Result<RecordBatch, Error> {
+        let mask: BooleanArray = (0..groups.len())
+            .map(|i| Some(groups.is_valid(i).then(|| groups.value(i)) == value))
+            .collect();
+        let rows = filter_record_batch(batch, &mask)
+            .map_err(|e| Error::Process(format!("Failed to filter messages: {}", e)))?;
+        with_group_value(&rows, value)
+    }
+}
+
+/// Set the group value column of `batch`, replacing the value of a previous split
+fn with_group_value(batch: &RecordBatch, value: Option<&str>) -> Result<RecordBatch, Error> {
+    let mut fields = Vec::with_capacity(batch.num_columns() + 1);
+    let mut columns: Vec<ArrayRef> = Vec::with_capacity(batch.num_columns() + 1);
+    for (field, column) in batch.schema().fields().iter().zip(batch.columns()) {
+        if field.name() != GROUP_VALUE_FIELD {
+            fields.push(field.as_ref().clone());
+            columns.push(column.clone());
+        }
+    }
+    fields.push(Field::new(GROUP_VALUE_FIELD, DataType::Utf8, true));
+    columns.push(Arc::new(StringArray::from(vec![value; batch.num_rows()])));
+
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+        .map_err(|e| Error::Process(format!("Creating an Arrow record batch failed: {}", e)))
+}
+
+#[async_trait]
+impl Processor for GroupSplitProcessor {
+    async fn process(&self, msg_batch: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+        if msg_batch.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let column = msg_batch
+            .column_by_name(&self.config.group_column)
+            .ok_or_else(|| {
+                Error::Process(format!(
+                    "Group column {} not found",
+                    self.config.group_column
+                ))
+            })?;
+        let groups = cast(column, &DataType::Utf8).map_err(|e| {
+            Error::Process(format!(
+                "Failed to read group column {} as a string: {}",
+                self.config.group_column, e
+            ))
+        })?;
+        let groups = groups
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .ok_or_else(|| Error::Process("Invalid group column".to_string()))?;
+
+        // Groups are emitted in the order of their first row, nulls forming a group of their own
+        let mut seen = HashSet::new();
+        let values: Vec<Option<&str>> = groups.iter().filter(|value| seen.insert(*value)).collect();
+
+        values
+            .into_iter()
+            .map(|value| {
+                let batch = self.group_batch(&msg_batch, groups, value)?;
+                let mut result = MessageBatch::new_arrow(batch);
+                result.set_input_name(msg_batch.get_input_name());
+                Ok(result)
+            })
+            .collect()
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+struct GroupSplitProcessorBuilder;
+impl ProcessorBuilder for GroupSplitProcessorBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Processor>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Group split processor configuration is missing".to_string(),
+            ));
+        }
+        let config: GroupSplitProcessorConfig = serde_json::from_value(config.clone().unwrap())?;
+
+        Ok(Arc::new(GroupSplitProcessor { config }))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_processor_builder("group_split", Arc::new(GroupSplitProcessorBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::Int64Array;
+    use serde_json::json;
+    use std::cell::RefCell;
+
+    fn build(config: serde_json::Value) -> Arc<dyn Processor> {
+        GroupSplitProcessorBuilder
+            .build(
+                None,
+                &Some(config),
+                &Resource {
+                    temporary: Default::default(),
+                    input_names: RefCell::new(Default::default()),
+                },
+            )
+            .unwrap()
+    }
+
+    fn strings(msg: &MessageBatch, name: &str) -> Vec<Option<String>> {
+        let column = cast(msg.column_by_name(name).unwrap(), &DataType::Utf8).unwrap();
+        let column = column.as_any().downcast_ref::<StringArray>().unwrap();
+        column.iter().map(|v| v.map(str::to_string)).collect()
+    }
+
+    #[tokio::test]
+    async fn test_group_split() {
+        let batch = RecordBatch::try_from_iter([
+            (
+                "date",
+                Arc::new(StringArray::from(vec![
+                    Some("2024-01-02"),
+                    Some("2024-01-01"),
+                    None,
+                    Some("2024-01-02"),
+                ])) as ArrayRef,
+            ),
+            (
+                "id",
+                Arc::new(Int64Array::from(vec![1, 2, 3, 4])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        let mut msg = MessageBatch::new_arrow(batch);
+        msg.set_input_name(Some("events".to_string()));
+
+        let processor = build(json!({"group_column": "date"}));
+        let result = processor.process(msg).await.unwrap();
+        assert_eq!(result.len(), 3);
+
+        assert_eq!(
+            strings(&result[0], "id"),
+            vec![Some("1".to_string()), Some("4".to_string())]
+        );
+        assert_eq!(
+            strings(&result[0], GROUP_VALUE_FIELD),
+            vec![Some("2024-01-02".to_string()); 2]
+        );
+        assert_eq!(strings(&result[1], "id"), vec![Some("2".to_string())]);
+        assert_eq!(strings(&result[2], "id"), vec![Some("3".to_string())]);
+        assert_eq!(strings(&result[2], GROUP_VALUE_FIELD), vec![None]);
+        assert_eq!(result[2].get_input_name(), Some("events".to_string()));
+
+        // Splitting again replaces the group value
+        let processor = build(json!({"group_column": "id"}));
+        let result = processor.process(result[0].clone()).await.unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[1].num_columns(), 3);
+        assert_eq!(
+            strings(&result[1], GROUP_VALUE_FIELD),
+            vec![Some("4".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_group_split_missing_column() {
+        let processor = build(json!({"group_column": "date"}));
+        let msg = MessageBatch::new_binary(vec![b"x".to_vec()]).unwrap();
+        assert!(matches!(
+            processor.process(msg).await,
+            Err(Error::Process(_))
+        ));
+    }
+}
@@ -0,0 +1,436 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Hash Processor Component
+//!
+//! Compute a content hash per message, e.g. for fingerprinting or as a deduplication key
+
+use arkflow_core::processor::{register_processor_builder, Processor, ProcessorBuilder};
+use arkflow_core::{Error, MessageBatch, Resource, DEFAULT_BINARY_VALUE_FIELD};
+use async_trait::async_trait;
+use base64::Engine;
+use datafusion::arrow::array::{
+    Array, ArrayRef, BinaryArray, LargeBinaryArray, LargeStringArray, StringArray,
+};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::util::display::{ArrayFormatter, FormatOptions};
+use serde::{Deserialize, Serialize};
+use sha1::Digest;
+use std::hash::Hasher;
+use std::sync::Arc;
+
+/// Hash processor configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct HashProcessorConfig {
+    /// Hash algorithm
+    algorithm: HashAlgorithm,
+    /// Columns to hash. If absent, the binary payload is hashed
+    target_fields: Option<Vec<String>>,
+    /// Name of the new column holding the hash
+    output_field: String,
+    /// Encoding of the hash
+    #[serde(default)]
+    output_format: HashOutputFormat,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum HashAlgorithm {
+    Sha256,
+    Sha1,
+    Md5,
+    Xxhash64,
+    Blake3,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum HashOutputFormat {
+    #[default]
+    Hex,
+    Base64,
+}
+
+/// Incremental hasher over the supported algorithms
+enum RowHasher {
+    Sha256(sha2::Sha256),
+    Sha1(sha1::Sha1),
+    Md5(md5::Md5),
+    Xxhash64(twox_hash::XxHash64),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl RowHasher {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Sha256 => RowHasher::Sha256(sha2::Sha256::new()),
+            HashAlgorithm::Sha1 => RowHasher::Sha1(sha1::Sha1::new()),
+            HashAlgorithm::Md5 => RowHasher::Md5(md5::Md5::new()),
+            HashAlgorithm::Xxhash64 => RowHasher::Xxhash64(twox_hash::XxHash64::with_seed(0)),
+            HashAlgorithm::Blake3 => RowHasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            RowHasher::Sha256(h) => h.update(data),
+            RowHasher::Sha1(h) => h.update(data),
+            RowHasher::Md5(h) => h.update(data),
+            RowHasher::Xxhash64(h) => h.write(data),
+            RowHasher::Blake3(h) => {
+                h.update(data);
+            }
+        }
+    }
+
+    fn finalize(self) -> Vec<u8> {
+        match self {
+            RowHasher::Sha256(h) => h.finalize().to_vec(),
+            RowHasher::Sha1(h) => h.finalize().to_vec(),
+            RowHasher::Md5(h) => h.finalize().to_vec(),
+            RowHasher::Xxhash64(h) => h.finish().to_be_bytes().to_vec(),
+            RowHasher::Blake3(h) => h.finalize().as_bytes().to_vec(),
+        }
+    }
+}
+
+struct HashProcessor {
+    config: HashProcessorConfig,
+}
+
+impl HashProcessor {
+    fn encode(&self, digest: &[u8]) -> String {
+        match self.config.output_format {
+            HashOutputFormat::Hex => hex::encode(digest),
+            HashOutputFormat::Base64 => base64::engine::general_purpose::STANDARD.encode(digest),
+        }
+    }
+
+    /// Hash the binary payload of each row as-is
+    fn hash_payloads(&self, column: &dyn Array) -> Result<Vec<String>, Error> {
+        (0..column.len())
+            .map(|row| {
+                let mut hasher = RowHasher::new(self.config.algorithm);
+                if !column.is_null(row) {
+                    let bytes = raw_bytes(column, row).ok_or_else(|| {
+                        Error::Process(format!(
+                            "Column {} is not binary",
+                            DEFAULT_BINARY_VALUE_FIELD
+                        ))
+                    })?;
+                    hasher.update(bytes);
+                }
+                Ok(self.encode(&hasher.finalize()))
+            })
+            .collect()
+    }
+
+    /// Hash each row over the given columns.
+    ///
+    /// Every value is prefixed with its length so that ("ab", "c") and ("a", "bc") differ,
+    /// and nulls are written as a marker that no length can take. Binary and string values
+    /// are hashed as-is, fixed-width primitive values by their little-endian bytes and other
+    /// values by their display representation.
+    fn hash_rows(&self, batch: &RecordBatch, columns: &[ArrayRef]) -> Result<Vec<String>, Error> {
+        let options = FormatOptions::default();
+        let formatters = columns
+            .iter()
+            .map(|column| ArrayFormatter::try_new(column.as_ref(), &options))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Error::Process(format!("Failed to format column: {}", e)))?;
+        let fixed_width: Vec<_> = columns
+            .iter()
+            .map(|column| {
+                let data_type = column.data_type();
+                let width = data_type
+                    .primitive_width()
+                    .filter(|_| data_type.is_primitive())?;
+                Some((column.to_data(), width))
+            })
+            .collect();
+
+        let mut hashes = Vec::with_capacity(batch.num_rows());
+        for row in 0..batch.num_rows() {
+            let mut hasher = RowHasher::new(self.config.algorithm);
+            for ((column, formatter), fixed) in columns.iter().zip(&formatters).zip(&fixed_width) {
+                if column.is_null(row) {
+                    hasher.update(&u64::MAX.to_be_bytes());
+                    continue;
+                }
+                let display;
+                let bytes = match (raw_bytes(column.as_ref(), row), fixed) {
+                    (Some(bytes), _) => bytes,
+                    (None, Some((data, width))) => {
+                        let start = (data.offset() + row) * width;
+                        &data.buffers()[0].as_slice()[start..start + width]
+                    }
+                    (None, None) => {
+                        display = formatter.value(row).to_string();
+                        display.as_bytes()
+                    }
+                };
+                hasher.update(&(bytes.len() as u64).to_be_bytes());
+                hasher.update(bytes);
+            }
+            hashes.push(self.encode(&hasher.finalize()));
+        }
+        Ok(hashes)
+    }
+}
+
+/// Raw byte representation of binary and string values
+fn raw_bytes(column: &dyn Array, row: usize) -> Option<&[u8]> {
+    let any = column.as_any();
+    match column.data_type() {
+        DataType::Binary => any
+            .downcast_ref::<BinaryArray>()
+            .map(|array| array.value(row)),
+        DataType::LargeBinary => any
+            .downcast_ref::<LargeBinaryArray>()
+            .map(|array| array.value(row)),
+        DataType::Utf8 => any
+            .downcast_ref::<StringArray>()
+            .map(|array| array.value(row).as_bytes()),
+        DataType::LargeUtf8 => any
+            .downcast_ref::<LargeStringArray>()
+            .map(|array| array.value(row).as_bytes()),
+        _ => None,
+    }
+}
+
+#[async_trait]
+impl Processor for HashProcessor {
+    async fn process(&self, msg_batch: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+        if msg_batch.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let schema = msg_batch.schema();
+        let target_fields = match &self.config.target_fields {
+            Some(fields) => fields.clone(),
+            None => vec![DEFAULT_BINARY_VALUE_FIELD.to_string()],
+        };
+        let columns = target_fields
+            .iter()
+            .map(|name| {
+                msg_batch.column_by_name(name).cloned().ok_or_else(|| {
+                    Error::Process(format!("Column {} not found in message batch", name))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let hashes = match &self.config.target_fields {
+            Some(_) => self.hash_rows(&msg_batch, &columns)?,
+            None => self.hash_payloads(columns[0].as_ref())?,
+        };
+
+        let mut fields: Vec<Arc<Field>> = schema.fields().iter().cloned().collect();
+        fields.push(Arc::new(Field::new(
+            &self.config.output_field,
+            DataType::Utf8,
+            false,
+        )));
+        let mut columns: Vec<ArrayRef> = msg_batch.columns().to_vec();
+        columns.push(Arc::new(StringArray::from(hashes)));
+
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone())),
+            columns,
+        )
+        .map_err(|e| Error::Process(format!("Creating an Arrow record batch failed: {}", e)))?;
+
+        let mut result = MessageBatch::new_arrow(batch);
+        result.set_input_name(msg_batch.get_input_name());
+        Ok(vec![result])
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+struct HashProcessorBuilder;
+impl ProcessorBuilder for HashProcessorBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Processor>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Hash processor configuration is missing".to_string(),
+            ));
+        }
+        let config: HashProcessorConfig = serde_json::from_value(config.clone().unwrap())?;
+
+        Ok(Arc::new(HashProcessor { config }))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_processor_builder("hash", Arc::new(HashProcessorBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::Int64Array;
+    use serde_json::json;
+    use std::cell::RefCell;
+
+    fn build(config: serde_json::Value) -> Arc<dyn Processor> {
+        HashProcessorBuilder
+            .build(
+                None,
+                &Some(config),
+                &Resource {
+                    temporary: Default::default(),
+                    input_names: RefCell::new(Default::default()),
+                },
+            )
+            .unwrap()
+    }
+
+    fn hashes(msg: &MessageBatch, field: &str) -> Vec<String> {
+        let array = msg
+            .column_by_name(field)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        array.iter().map(|v| v.unwrap().to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_hash_binary_payload() {
+        let processor = build(json!({"algorithm": "sha256", "output_field": "hash"}));
+        let msg = MessageBatch::new_binary(vec![b"hello".to_vec(), b"world".to_vec()]).unwrap();
+
+        let result = processor.process(msg).await.unwrap();
+        assert_eq!(
+            hashes(&result[0], "hash"),
+            vec![
+                "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+                "486ea46224d1bb4fb680f34f7c9ad96a8f24ec88be73ea8e5a6c65260e9cb8a7",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hash_algorithms_and_formats() {
+        for (algorithm, format, expected) in [
+            ("md5", "hex", "5d41402abc4b2a76b9719d911017c592"),
+            ("sha1", "hex", "aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d"),
+            ("xxhash64", "hex", "26c7827d889f6da3"),
+            ("md5", "base64", "XUFAKrxLKna5cZ2REBfFkg=="),
+        ] {
+            let processor = build(json!({
+                "algorithm": algorithm,
+                "output_field": "hash",
+                "output_format": format,
+            }));
+            let msg = MessageBatch::new_binary(vec![b"hello".to_vec()]).unwrap();
+            let result = processor.process(msg).await.unwrap();
+            assert_eq!(hashes(&result[0], "hash"), vec![expected], "{}", algorithm);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hash_target_fields() {
+        let processor = build(json!({
+            "algorithm": "blake3",
+            "target_fields": ["id", "name"],
+            "output_field": "fingerprint",
+        }));
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![1, 1, 2])),
+                Arc::new(StringArray::from(vec!["a", "a", "a"])),
+            ],
+        )
+        .unwrap();
+
+        let result = processor
+            .process(MessageBatch::new_arrow(batch))
+            .await
+            .unwrap();
+        assert_eq!(result[0].num_columns(), 3);
+        let values = hashes(&result[0], "fingerprint");
+        assert_eq!(values[0], values[1]);
+        assert_ne!(values[0], values[2]);
+        let mut expected = blake3::Hasher::new();
+        expected.update(&8u64.to_be_bytes());
+        expected.update(&1i64.to_le_bytes());
+        expected.update(&1u64.to_be_bytes());
+        expected.update(b"a");
+        assert_eq!(values[0], expected.finalize().to_hex().to_string());
+    }
+
+    #[tokio::test]
+    async fn test_hash_values_are_delimited() {
+        let processor = build(json!({
+            "algorithm": "sha256",
+            "target_fields": ["a", "b"],
+            "output_field": "hash",
+        }));
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Utf8, true),
+            Field::new("b", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec![
+                    Some("ab"),
+                    Some("a"),
+                    None,
+                    Some(""),
+                ])),
+                Arc::new(StringArray::from(vec![
+                    Some("c"),
+                    Some("bc"),
+                    Some("x"),
+                    Some("x"),
+                ])),
+            ],
+        )
+        .unwrap();
+
+        let result = processor
+            .process(MessageBatch::new_arrow(batch))
+            .await
+            .unwrap();
+        let values = hashes(&result[0], "hash");
+        assert_ne!(values[0], values[1]);
+        assert_ne!(values[2], values[3]);
+    }
+
+    #[tokio::test]
+    async fn test_hash_missing_column() {
+        let processor = build(json!({
+            "algorithm": "sha256",
+            "target_fields": ["missing"],
+            "output_field": "hash",
+        }));
+        let msg = MessageBatch::new_binary(vec![b"hello".to_vec()]).unwrap();
+        assert!(processor.process(msg).await.is_err());
+    }
+}
@@ -0,0 +1,604 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Incremental Aggregate Processor Component
+//!
+//! Maintain `sum`, `count`, `min` and `max` aggregates by group as rows arrive, without keeping
+//! the rows. The aggregates are emitted, and the state reset, every flush interval or when the
+//! number of groups exceeds the limit.
+
+use arkflow_core::processor::{register_processor_builder, Processor, ProcessorBuilder};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use datafusion::arrow::array::{Array, ArrayRef};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::DataType;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::scalar::ScalarValue;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Incremental aggregate processor configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IncrementalAggregateProcessorConfig {
+    /// Columns identifying the group of a row, all rows belong to the same group if empty
+    #[serde(default)]
+    group_by: Vec<String>,
+    aggregations: Vec<IncrementalAggSpec>,
+    /// Time between emissions of the aggregates
+    flush_interval_ms: u64,
+    /// Number of groups above which the aggregates are emitted before the flush interval
+    #[serde(default = "default_max_groups")]
+    max_groups: usize,
+}
+
+fn default_max_groups() -> usize {
+    10000
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum AggFunction {
+    Sum,
+    Count,
+    Min,
+    Max,
+}
+
+impl AggFunction {
+    fn name(&self) -> &'static str {
+        match self {
+            AggFunction::Sum => "sum",
+            AggFunction::Count => "count",
+            AggFunction::Min => "min",
+            AggFunction::Max => "max",
+        }
+    }
+}
+
+/// Aggregate of a column
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IncrementalAggSpec {
+    function: AggFunction,
+    /// Aggregated column. `count` without a column counts the rows.
+    column: Option<String>,
+    /// Output column name, `<function>_<column>` by default
+    alias: Option<String>,
+}
+
+impl IncrementalAggSpec {
+    fn name(&self) -> String {
+        match (&self.alias, &self.column) {
+            (Some(alias), _) => alias.clone(),
+            (None, Some(column)) => format!("{}_{}", self.function.name(), column),
+            (None, None) => self.function.name().to_string(),
+        }
+    }
+}
+
+/// Values of the `group_by` columns of a group
+type GroupKey = Vec<ScalarValue>;
+
+/// Running state of an aggregate
+#[derive(Debug, Clone, PartialEq)]
+enum AggState {
+    Count(i64),
+    /// Sum, min or max of the non-null values, `None` until the first one
+    Value(Option<ScalarValue>),
+}
+
+impl AggState {
+    fn new(function: AggFunction) -> Self {
+        match function {
+            AggFunction::Count => AggState::Count(0),
+            _ => AggState::Value(None),
+        }
+    }
+
+    fn update(&mut self, function: AggFunction, value: Option<ScalarValue>) -> Result<(), Error> {
+        match self {
+            AggState::Count(count) => {
+                if value.is_none_or(|value| !value.is_null()) {
+                    *count += 1;
+                }
+            }
+            AggState::Value(state) => {
+                let Some(value) = value.filter(|value| !value.is_null()) else {
+                    return Ok(());
+                };
+                let Some(current) = state else {
+                    *state = Some(value);
+                    return Ok(());
+                };
+                match function {
+                    AggFunction::Sum => {
+                        *current = current
+                            .add_checked(&value)
+                            .map_err(|e| Error::Process(format!("Sum failed: {}", e)))?;
+                    }
+                    AggFunction::Min | AggFunction::Max => {
+                        let ordering = value.partial_cmp(current).ok_or_else(|| {
+                            Error::Process(format!(
+                                "Cannot compare {} and {}",
+                                value.data_type(),
+                                current.data_type()
+                            ))
+                        })?;
+                        let replace = match function {
+                            AggFunction::Min => ordering == Ordering::Less,
+                            _ => ordering == Ordering::Greater,
+                        };
+                        if replace {
+                            *current = value;
+                        }
+                    }
+                    AggFunction::Count => unreachable!(),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Aggregate states of every group
+#[derive(Default)]
+struct AggregateState {
+    groups: HashMap<GroupKey, HashMap<String, AggState>>,
+    /// Types of the `group_by` and aggregate columns, from the first batch
+    types: Option<(Vec<DataType>, Vec<DataType>)>,
+}
+
+struct IncrementalAggregateProcessor {
+    config: IncrementalAggregateProcessorConfig,
+    /// Output column name of every aggregation
+    names: Vec<String>,
+    state: Mutex<AggregateState>,
+    last_flush: Mutex<Instant>,
+}
+
+impl IncrementalAggregateProcessor {
+    fn new(config: IncrementalAggregateProcessorConfig) -> Result<Self, Error> {
+        if config.aggregations.is_empty() {
+            return Err(Error::Config(
+                "Incremental aggregate requires at least one aggregation".to_string(),
+            ));
+        }
+        if config.flush_interval_ms == 0 {
+            return Err(Error::Config(
+                "Incremental aggregate flush_interval_ms must be positive".to_string(),
+            ));
+        }
+        for spec in &config.aggregations {
+            if spec.column.is_none() && spec.function != AggFunction::Count {
+                return Err(Error::Config(format!(
+                    "{} aggregation requires a column",
+                    spec.function.name()
+                )));
+            }
+        }
+        let names: Vec<String> = config.aggregations.iter().map(|spec| spec.name()).collect();
+        for (i, name) in names.iter().enumerate() {
+            if names[..i].contains(name) || config.group_by.contains(name) {
+                return Err(Error::Config(format!("Duplicate output column {}", name)));
+            }
+        }
+        Ok(Self {
+            config,
+            names,
+            state: Mutex::new(AggregateState::default()),
+            last_flush: Mutex::new(Instant::now()),
+        })
+    }
+
+    /// Update the aggregate states with the rows of `batch`
+    fn update(&self, batch: &RecordBatch) -> Result<(), Error> {
+        let column = |name: &str| {
+            batch
+                .column_by_name(name)
+                .cloned()
+                .ok_or_else(|| Error::Process(format!("Column {} not found", name)))
+        };
+        let group_columns = self
+            .config
+            .group_by
+            .iter()
+            .map(|name| column(name))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let agg_columns = self
+            .config
+            .aggregations
+            .iter()
+            .map(|spec| match (&spec.column, spec.function) {
+                (None, _) => Ok(None),
+                (Some(name), AggFunction::Sum) => {
+                    let values = column(name)?;
+                    let data_type = sum_type(values.data_type()).ok_or_else(|| {
+                        Error::Process(format!(
+                            "Cannot sum column {} of type {}",
+                            name,
+                            values.data_type()
+                        ))
+                    })?;
+                    cast(&values, &data_type)
+                        .map(Some)
+                        .map_err(|e| Error::Process(format!("Failed to sum {}: {}", name, e)))
+                }
+                (Some(name), _) => column(name).map(Some),
+            })
+            .collect::<Result<Vec<Option<ArrayRef>>, Error>>()?;
+
+        let mut state = self.state.lock().unwrap();
+        let types = (
+            group_columns
+                .iter()
+                .map(|c| c.data_type().clone())
+                .collect(),
+            agg_columns
+                .iter()
+                .zip(&self.config.aggregations)
+                .map(|(column, spec)| match (column, spec.function) {
+                    (Some(column), AggFunction::Sum | AggFunction::Min | AggFunction::Max) => {
+                        column.data_type().clone()
+                    }
+                    _ => DataType::Int64,
+                })
+                .collect(),
+        );
+        match &state.types {
+            Some(current) if *current != types => {
+                return Err(Error::Process(
+                    "Column types differ from the previous batches".to_string(),
+                ))
+            }
+            Some(_) => {}
+            None => state.types = Some(types),
+        }
+
+        for row in 0..batch.num_rows() {
+            let key = group_columns
+                .iter()
+                .map(|column| scalar(column, row))
+                .collect::<Result<GroupKey, Error>>()?;
+            let aggregates = state.groups.entry(key).or_insert_with(|| {
+                self.config
+                    .aggregations
+                    .iter()
+                    .zip(&self.names)
+                    .map(|(spec, name)| (name.clone(), AggState::new(spec.function)))
+                    .collect()
+            });
+            for ((spec, name), column) in self
+                .config
+                .aggregations
+                .iter()
+                .zip(&self.names)
+                .zip(&agg_columns)
+            {
+                let value = match column {
+                    Some(column) => Some(scalar(column, row)?),
+                    None => None,
+                };
+                if let Some(state) = aggregates.get_mut(name) {
+                    state.update(spec.function, value)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Batch of the aggregates of every group, resetting the state
+    fn flush(&self) -> Result<Option<MessageBatch>, Error> {
+        *self.last_flush.lock().unwrap() = Instant::now();
+        let (groups, types) = {
+            let mut state = self.state.lock().unwrap();
+            (std::mem::take(&mut state.groups), state.types.clone())
+        };
+        let Some((group_types, agg_types)) = types.filter(|_| !groups.is_empty()) else {
+            return Ok(None);
+        };
+
+        let mut columns: Vec<(String, ArrayRef)> = Vec::new();
+        for (i, name) in self.config.group_by.iter().enumerate() {
+            let values = groups.keys().map(|key| key[i].clone());
+            columns.push((name.clone(), to_array(values, &group_types[i])?));
+        }
+        for (name, data_type) in self.names.iter().zip(&agg_types) {
+            let values = groups.values().map(|aggregates| match &aggregates[name] {
+                AggState::Count(count) => ScalarValue::Int64(Some(*count)),
+                AggState::Value(Some(value)) => value.clone(),
+                AggState::Value(None) => {
+                    ScalarValue::try_from(data_type).unwrap_or(ScalarValue::Null)
+                }
+            });
+            columns.push((name.clone(), to_array(values, data_type)?));
+        }
+
+        let batch = RecordBatch::try_from_iter(columns)
+            .map_err(|e| Error::Process(format!("Creating an Arrow record batch failed: {}", e)))?;
+        Ok(Some(MessageBatch::new_arrow(batch)))
+    }
+
+    fn should_flush(&self) -> bool {
+        self.state.lock().unwrap().groups.len() > self.config.max_groups
+            || self.last_flush.lock().unwrap().elapsed().as_millis()
+                >= self.config.flush_interval_ms as u128
+    }
+}
+
+/// Type of the sum of a column of type `data_type`
+fn sum_type(data_type: &DataType) -> Option<DataType> {
+    match data_type {
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => {
+            Some(DataType::Int64)
+        }
+        DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64 => {
+            Some(DataType::UInt64)
+        }
+        DataType::Float16 | DataType::Float32 | DataType::Float64 => Some(DataType::Float64),
+        DataType::Decimal128(_, _) | DataType::Decimal256(_, _) => Some(data_type.clone()),
+        _ => None,
+    }
+}
+
+fn scalar(column: &ArrayRef, row: usize) -> Result<ScalarValue, Error> {
+    ScalarValue::try_from_array(column, row)
+        .map_err(|e| Error::Process(format!("Failed to read a value: {}", e)))
+}
+
+fn to_array(
+    values: impl Iterator<Item = ScalarValue>,
+    data_type: &DataType,
+) -> Result<ArrayRef, Error> {
+    let array = ScalarValue::iter_to_array(values)
+        .map_err(|e| Error::Process(format!("Failed to build an aggregate column: {}", e)))?;
+    cast(&array, data_type)
+        .map_err(|e| Error::Process(format!("Failed to build an aggregate column: {}", e)))
+}
+
+#[async_trait]
+impl Processor for IncrementalAggregateProcessor {
+    async fn process(&self, msg: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+        if !msg.is_empty() {
+            self.update(&msg)?;
+        }
+        if !self.should_flush() {
+            return Ok(vec![]);
+        }
+        Ok(self.flush()?.into_iter().collect())
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+struct IncrementalAggregateProcessorBuilder;
+impl ProcessorBuilder for IncrementalAggregateProcessorBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Processor>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Incremental aggregate processor configuration is missing".to_string(),
+            ));
+        }
+        let config: IncrementalAggregateProcessorConfig =
+            serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(IncrementalAggregateProcessor::new(config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_processor_builder(
+        "incremental_aggregate",
+        Arc::new(IncrementalAggregateProcessorBuilder),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Float64Array, Int32Array, Int64Array, StringArray};
+    use serde_json::json;
+
+    fn processor(config: serde_json::Value) -> IncrementalAggregateProcessor {
+        IncrementalAggregateProcessor::new(serde_json::from_value(config).unwrap()).unwrap()
+    }
+
+    fn batch(users: Vec<Option<&str>>, amounts: Vec<Option<i32>>) -> MessageBatch {
+        MessageBatch::new_arrow(
+            RecordBatch::try_from_iter([
+                ("user", Arc::new(StringArray::from(users)) as ArrayRef),
+                ("amount", Arc::new(Int32Array::from(amounts))),
+            ])
+            .unwrap(),
+        )
+    }
+
+    /// Rows of the result as (user, values of the aggregate columns as strings)
+    fn rows(result: &MessageBatch) -> Vec<(Option<String>, Vec<String>)> {
+        let users = result
+            .column_by_name("user")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let mut rows: Vec<_> = (0..result.num_rows())
+            .map(|row| {
+                let user = users.is_valid(row).then(|| users.value(row).to_string());
+                let values = result.columns()[1..]
+                    .iter()
+                    .map(|column| scalar(column, row).unwrap().to_string())
+                    .collect();
+                (user, values)
+            })
+            .collect();
+        rows.sort();
+        rows
+    }
+
+    #[tokio::test]
+    async fn test_incremental_aggregate() {
+        let processor = processor(json!({
+            "group_by": ["user"],
+            "aggregations": [
+                {"function": "sum", "column": "amount"},
+                {"function": "count", "alias": "events"},
+                {"function": "count", "column": "amount"},
+                {"function": "min", "column": "amount"},
+                {"function": "max", "column": "amount", "alias": "largest"},
+            ],
+            "flush_interval_ms": 60000,
+            "max_groups": 2,
+        }));
+
+        let result = processor
+            .process(batch(
+                vec![Some("a"), Some("b"), Some("a")],
+                vec![Some(10), None, Some(5)],
+            ))
+            .await
+            .unwrap();
+        assert!(result.is_empty());
+
+        // A third group exceeds max_groups
+        let result = processor
+            .process(batch(vec![Some("a"), None], vec![Some(7), Some(1)]))
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 1);
+        let schema = result[0].schema();
+        let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "user",
+                "sum_amount",
+                "events",
+                "count_amount",
+                "min_amount",
+                "largest"
+            ]
+        );
+        assert_eq!(schema.field(1).data_type(), &DataType::Int64);
+        assert_eq!(schema.field(4).data_type(), &DataType::Int32);
+        assert_eq!(
+            rows(&result[0]),
+            vec![
+                (
+                    None,
+                    vec!["1", "1", "1", "1", "1"]
+                        .into_iter()
+                        .map(String::from)
+                        .collect()
+                ),
+                (
+                    Some("a".to_string()),
+                    vec!["22", "3", "3", "5", "10"]
+                        .into_iter()
+                        .map(String::from)
+                        .collect()
+                ),
+                (
+                    Some("b".to_string()),
+                    vec!["NULL", "1", "0", "NULL", "NULL"]
+                        .into_iter()
+                        .map(String::from)
+                        .collect()
+                ),
+            ]
+        );
+
+        // The state is reset after an emission
+        let result = processor
+            .process(batch(vec![Some("a")], vec![Some(3)]))
+            .await
+            .unwrap();
+        assert!(result.is_empty());
+        assert_eq!(processor.state.lock().unwrap().groups.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_flush_interval() {
+        let processor = processor(json!({
+            "aggregations": [{"function": "sum", "column": "value"}],
+            "flush_interval_ms": 20,
+        }));
+        let batch = |values: Vec<f64>| {
+            MessageBatch::new_arrow(
+                RecordBatch::try_from_iter([(
+                    "value",
+                    Arc::new(Float64Array::from(values)) as ArrayRef,
+                )])
+                .unwrap(),
+            )
+        };
+
+        assert!(processor
+            .process(batch(vec![1.5, 2.0]))
+            .await
+            .unwrap()
+            .is_empty());
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let result = processor.process(batch(vec![0.5])).await.unwrap();
+        let sum = result[0]
+            .column_by_name("sum_value")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap()
+            .value(0);
+        assert_eq!(sum, 4.0);
+
+        let invalid = processor
+            .process(MessageBatch::new_arrow(
+                RecordBatch::try_from_iter([(
+                    "value",
+                    Arc::new(Int64Array::from(vec![1])) as ArrayRef,
+                )])
+                .unwrap(),
+            ))
+            .await;
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let config = |aggregations: serde_json::Value| {
+            serde_json::from_value::<IncrementalAggregateProcessorConfig>(json!({
+                "group_by": ["user"],
+                "aggregations": aggregations,
+                "flush_interval_ms": 1000,
+            }))
+            .unwrap()
+        };
+        assert!(IncrementalAggregateProcessor::new(config(json!([]))).is_err());
+        assert!(IncrementalAggregateProcessor::new(config(json!([{"function": "sum"}]))).is_err());
+        assert!(IncrementalAggregateProcessor::new(config(json!([
+            {"function": "count"},
+            {"function": "sum", "column": "x", "alias": "count"},
+        ])))
+        .is_err());
+        assert!(IncrementalAggregateProcessor::new(config(json!([
+            {"function": "max", "column": "x", "alias": "user"},
+        ])))
+        .is_err());
+    }
+}
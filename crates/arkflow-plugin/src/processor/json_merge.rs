@@ -0,0 +1,546 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! JSON Merge Processor Component
+//!
+//! Apply a JSON Merge Patch (RFC 7396) or a JSON Patch (RFC 6902) to every message
+
+use crate::component;
+use arkflow_core::processor::{register_processor_builder, Processor, ProcessorBuilder};
+use arkflow_core::{Error, MessageBatch, Resource, DEFAULT_BINARY_VALUE_FIELD};
+use async_trait::async_trait;
+use datafusion::arrow::json::LineDelimitedWriter;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::sync::Arc;
+
+/// JSON merge processor configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JsonMergeProcessorConfig {
+    /// How the patch documents are applied
+    mode: MergeMode,
+    /// Patch document applied to every message
+    patch: Option<Value>,
+    /// Field of each message holding a patch document for that message
+    patch_field: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum MergeMode {
+    /// JSON Merge Patch, RFC 7396
+    Merge,
+    /// JSON Patch, RFC 6902
+    Patch,
+}
+
+/// Operation of a JSON Patch document
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum PatchOperation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+/// Patch document, parsed according to the mode
+enum Patch {
+    Merge(Value),
+    Operations(Vec<PatchOperation>),
+}
+
+impl Patch {
+    fn parse(mode: MergeMode, document: Value) -> Result<Self, Error> {
+        match mode {
+            MergeMode::Merge => Ok(Patch::Merge(document)),
+            MergeMode::Patch => serde_json::from_value(document)
+                .map(Patch::Operations)
+                .map_err(|e| Error::Process(format!("Invalid JSON Patch document: {}", e))),
+        }
+    }
+
+    fn apply(&self, doc: &mut Value) -> Result<(), Error> {
+        match self {
+            Patch::Merge(patch) => {
+                merge(doc, patch);
+                Ok(())
+            }
+            Patch::Operations(operations) => {
+                // Operations are applied atomically: a failed operation leaves the message unchanged
+                let mut patched = doc.clone();
+                for operation in operations {
+                    apply_operation(&mut patched, operation)?;
+                }
+                *doc = patched;
+                Ok(())
+            }
+        }
+    }
+}
+
+struct JsonMergeProcessor {
+    config: JsonMergeProcessorConfig,
+    patch: Option<Patch>,
+}
+
+impl JsonMergeProcessor {
+    fn new(config: JsonMergeProcessorConfig) -> Result<Self, Error> {
+        if config.patch.is_none() && config.patch_field.is_none() {
+            return Err(Error::Config(
+                "JSON merge processor requires patch or patch_field".to_string(),
+            ));
+        }
+        let patch = config
+            .patch
+            .clone()
+            .map(|document| Patch::parse(config.mode, document))
+            .transpose()
+            .map_err(|e| Error::Config(e.to_string()))?;
+        Ok(Self { config, patch })
+    }
+
+    /// Apply the static patch, then the patch of the message's `patch_field`
+    fn patch_message(&self, mut doc: Value) -> Result<Value, Error> {
+        let dynamic = match (&self.config.patch_field, doc.as_object_mut()) {
+            (Some(field), Some(object)) => object.remove(field),
+            _ => None,
+        };
+
+        if let Some(patch) = &self.patch {
+            patch.apply(&mut doc)?;
+        }
+        match dynamic {
+            None | Some(Value::Null) => {}
+            // Patch documents may be embedded as strings, e.g. in an Arrow string column
+            Some(Value::String(s)) => {
+                let document = serde_json::from_str(&s).map_err(|e| {
+                    Error::Process(format!("Invalid patch document in message: {}", e))
+                })?;
+                Patch::parse(self.config.mode, document)?.apply(&mut doc)?;
+            }
+            Some(document) => Patch::parse(self.config.mode, document)?.apply(&mut doc)?,
+        }
+        Ok(doc)
+    }
+
+    fn patch_binary(&self, msg: &MessageBatch) -> Result<MessageBatch, Error> {
+        let messages = msg
+            .to_binary(DEFAULT_BINARY_VALUE_FIELD)?
+            .into_iter()
+            .map(|content| {
+                let doc = serde_json::from_slice(content)
+                    .map_err(|e| Error::Process(format!("Invalid JSON message: {}", e)))?;
+                Ok(serde_json::to_vec(&self.patch_message(doc)?)?)
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        msg.with_binary_values(messages)
+    }
+
+    /// Convert the rows to NDJSON, patch them and infer the schema of the result
+    fn patch_arrow(&self, msg: &MessageBatch) -> Result<MessageBatch, Error> {
+        let mut buf = Vec::new();
+        let mut writer = LineDelimitedWriter::new(&mut buf);
+        writer
+            .write(msg)
+            .map_err(|e| Error::Process(format!("Arrow JSON Serialization error: {}", e)))?;
+        writer.finish().map_err(|e| {
+            Error::Process(format!("Arrow JSON Serialization Complete Error: {}", e))
+        })?;
+
+        let mut content = Vec::with_capacity(buf.len());
+        for line in buf.split(|b| *b == b'\n').filter(|line| !line.is_empty()) {
+            let doc = serde_json::from_slice(line)?;
+            serde_json::to_writer(&mut content, &self.patch_message(doc)?)?;
+            content.push(b'\n');
+        }
+        Ok(MessageBatch::new_arrow(component::json::try_to_arrow(
+            &content, None,
+        )?))
+    }
+}
+
+#[async_trait]
+impl Processor for JsonMergeProcessor {
+    async fn process(&self, msg: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+        if msg.is_empty() {
+            return Ok(vec![msg]);
+        }
+        let mut patched = if msg.is_binary() {
+            self.patch_binary(&msg)?
+        } else {
+            self.patch_arrow(&msg)?
+        };
+        patched.set_input_name(msg.get_input_name());
+        Ok(vec![patched])
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Merge `patch` into `target` as described in RFC 7396
+fn merge(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let Value::Object(target) = target else {
+        unreachable!()
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge(target.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+/// Split a JSON Pointer (RFC 6901) into its unescaped reference tokens
+fn parse_pointer(pointer: &str) -> Result<Vec<String>, Error> {
+    if pointer.is_empty() {
+        return Ok(vec![]);
+    }
+    let Some(rest) = pointer.strip_prefix('/') else {
+        return Err(Error::Process(format!(
+            "Invalid JSON Pointer '{}': must start with '/'",
+            pointer
+        )));
+    };
+    Ok(rest
+        .split('/')
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect())
+}
+
+/// Index of an existing array element
+fn array_index(token: &str, len: usize, pointer: &str) -> Result<usize, Error> {
+    let index = token
+        .parse::<usize>()
+        .ok()
+        .filter(|_| token == "0" || !token.starts_with('0'));
+    match index {
+        Some(index) if index < len => Ok(index),
+        _ => Err(Error::Process(format!(
+            "JSON Pointer '{}' does not reference an array element",
+            pointer
+        ))),
+    }
+}
+
+fn resolve<'a>(doc: &'a Value, tokens: &[String], pointer: &str) -> Result<&'a Value, Error> {
+    let mut current = doc;
+    for token in tokens {
+        current = match current {
+            Value::Object(object) => object.get(token),
+            Value::Array(array) => Some(&array[array_index(token, array.len(), pointer)?]),
+            _ => None,
+        }
+        .ok_or_else(|| Error::Process(format!("JSON Pointer '{}' does not exist", pointer)))?;
+    }
+    Ok(current)
+}
+
+fn resolve_mut<'a>(
+    doc: &'a mut Value,
+    tokens: &[String],
+    pointer: &str,
+) -> Result<&'a mut Value, Error> {
+    let mut current = doc;
+    for token in tokens {
+        current = match current {
+            Value::Object(object) => object.get_mut(token),
+            Value::Array(array) => {
+                let index = array_index(token, array.len(), pointer)?;
+                Some(&mut array[index])
+            }
+            _ => None,
+        }
+        .ok_or_else(|| Error::Process(format!("JSON Pointer '{}' does not exist", pointer)))?;
+    }
+    Ok(current)
+}
+
+fn add(doc: &mut Value, pointer: &str, value: Value) -> Result<(), Error> {
+    let tokens = parse_pointer(pointer)?;
+    let Some((last, parent)) = tokens.split_last() else {
+        *doc = value;
+        return Ok(());
+    };
+    match resolve_mut(doc, parent, pointer)? {
+        Value::Object(object) => {
+            object.insert(last.clone(), value);
+        }
+        Value::Array(array) if last == "-" => array.push(value),
+        Value::Array(array) => {
+            // Inserting right after the last element is allowed
+            let index = array_index(last, array.len() + 1, pointer)?;
+            array.insert(index, value);
+        }
+        _ => {
+            return Err(Error::Process(format!(
+                "JSON Pointer '{}' does not reference an object or array member",
+                pointer
+            )))
+        }
+    }
+    Ok(())
+}
+
+fn remove(doc: &mut Value, pointer: &str) -> Result<Value, Error> {
+    let tokens = parse_pointer(pointer)?;
+    let Some((last, parent)) = tokens.split_last() else {
+        return Err(Error::Process(
+            "Cannot remove the whole document".to_string(),
+        ));
+    };
+    let removed = match resolve_mut(doc, parent, pointer)? {
+        Value::Object(object) => object.remove(last),
+        Value::Array(array) => {
+            let index = array_index(last, array.len(), pointer)?;
+            Some(array.remove(index))
+        }
+        _ => None,
+    };
+    removed.ok_or_else(|| Error::Process(format!("JSON Pointer '{}' does not exist", pointer)))
+}
+
+fn apply_operation(doc: &mut Value, operation: &PatchOperation) -> Result<(), Error> {
+    match operation {
+        PatchOperation::Add { path, value } => add(doc, path, value.clone()),
+        PatchOperation::Remove { path } => remove(doc, path).map(|_| ()),
+        PatchOperation::Replace { path, value } => {
+            *resolve_mut(doc, &parse_pointer(path)?, path)? = value.clone();
+            Ok(())
+        }
+        PatchOperation::Move { from, path } => {
+            if path.starts_with(&format!("{}/", from)) {
+                return Err(Error::Process(format!(
+                    "Cannot move '{}' into one of its children '{}'",
+                    from, path
+                )));
+            }
+            let value = remove(doc, from)?;
+            add(doc, path, value)
+        }
+        PatchOperation::Copy { from, path } => {
+            let value = resolve(doc, &parse_pointer(from)?, from)?.clone();
+            add(doc, path, value)
+        }
+        PatchOperation::Test { path, value } => {
+            if resolve(doc, &parse_pointer(path)?, path)? == value {
+                Ok(())
+            } else {
+                Err(Error::Process(format!(
+                    "JSON Patch test failed at '{}'",
+                    path
+                )))
+            }
+        }
+    }
+}
+
+struct JsonMergeProcessorBuilder;
+impl ProcessorBuilder for JsonMergeProcessorBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Processor>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "JSON merge processor configuration is missing".to_string(),
+            ));
+        }
+        let config: JsonMergeProcessorConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(JsonMergeProcessor::new(config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_processor_builder("json_merge", Arc::new(JsonMergeProcessorBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{AsArray, Int64Array, StringArray};
+    use datafusion::arrow::datatypes::Int64Type;
+    use datafusion::arrow::record_batch::RecordBatch;
+    use serde_json::json;
+    use std::cell::RefCell;
+
+    fn build(config: Value) -> Result<Arc<dyn Processor>, Error> {
+        JsonMergeProcessorBuilder.build(
+            None,
+            &Some(config),
+            &Resource {
+                temporary: Default::default(),
+                input_names: RefCell::new(Default::default()),
+            },
+        )
+    }
+
+    async fn process_binary(processor: &Arc<dyn Processor>, messages: &[Value]) -> Vec<Value> {
+        let batch = MessageBatch::new_binary(
+            messages
+                .iter()
+                .map(|m| serde_json::to_vec(m).unwrap())
+                .collect(),
+        )
+        .unwrap();
+        let result = processor.process(batch).await.unwrap();
+        result[0]
+            .to_binary(DEFAULT_BINARY_VALUE_FIELD)
+            .unwrap()
+            .into_iter()
+            .map(|m| serde_json::from_slice(m).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_merge_patch() {
+        let processor = build(json!({
+            "mode": "merge",
+            "patch": {"a": "z", "c": {"f": null, "g": 1}, "tags": ["new"]},
+        }))
+        .unwrap();
+        let result = process_binary(
+            &processor,
+            &[json!({"a": "b", "c": {"d": "e", "f": "g"}, "tags": ["old"]})],
+        )
+        .await;
+        assert_eq!(
+            result,
+            vec![json!({"a": "z", "c": {"d": "e", "g": 1}, "tags": ["new"]})]
+        );
+
+        // Non-object patches replace the whole target
+        let mut target = json!({"a": 1});
+        merge(&mut target, &json!(["x"]));
+        assert_eq!(target, json!(["x"]));
+    }
+
+    #[tokio::test]
+    async fn test_json_patch() {
+        let processor = build(json!({
+            "mode": "patch",
+            "patch": [
+                {"op": "test", "path": "/kind", "value": "order"},
+                {"op": "add", "path": "/items/1", "value": "b"},
+                {"op": "add", "path": "/items/-", "value": "d"},
+                {"op": "remove", "path": "/internal"},
+                {"op": "replace", "path": "/a~1b", "value": 2},
+                {"op": "copy", "from": "/kind", "path": "/type"},
+                {"op": "move", "from": "/kind", "path": "/meta/kind"},
+            ],
+        }))
+        .unwrap();
+        let result = process_binary(
+            &processor,
+            &[json!({
+                "kind": "order",
+                "items": ["a", "c"],
+                "internal": true,
+                "a/b": 1,
+                "meta": {},
+            })],
+        )
+        .await;
+        assert_eq!(
+            result,
+            vec![json!({
+                "items": ["a", "b", "c", "d"],
+                "a/b": 2,
+                "type": "order",
+                "meta": {"kind": "order"},
+            })]
+        );
+
+        // A failed test leaves the message unchanged and fails the batch
+        let batch = MessageBatch::new_binary(vec![br#"{"kind": "refund"}"#.to_vec()]).unwrap();
+        assert!(processor.process(batch).await.is_err());
+
+        let mut doc = json!({"a": [1]});
+        assert!(apply_operation(
+            &mut doc,
+            &PatchOperation::Remove {
+                path: "/a/01".to_string()
+            }
+        )
+        .is_err());
+        assert!(apply_operation(
+            &mut doc,
+            &PatchOperation::Move {
+                from: "/a".to_string(),
+                path: "/a/0".to_string()
+            }
+        )
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_patch_field_and_arrow() {
+        let processor = build(json!({
+            "mode": "merge",
+            "patch": {"source": "sensor"},
+            "patch_field": "overrides",
+        }))
+        .unwrap();
+        let batch = RecordBatch::try_from_iter([
+            (
+                "id",
+                Arc::new(Int64Array::from(vec![1, 2])) as datafusion::arrow::array::ArrayRef,
+            ),
+            (
+                "overrides",
+                Arc::new(StringArray::from(vec![
+                    Some(r#"{"source": "manual"}"#),
+                    None,
+                ])),
+            ),
+        ])
+        .unwrap();
+        let mut msg = MessageBatch::new_arrow(batch);
+        msg.set_input_name(Some("readings".to_string()));
+
+        let result = processor.process(msg).await.unwrap();
+        let batch = &result[0];
+        assert_eq!(batch.get_input_name(), Some("readings".to_string()));
+        assert!(batch.column_by_name("overrides").is_none());
+        let ids = batch
+            .column_by_name("id")
+            .unwrap()
+            .as_primitive::<Int64Type>();
+        assert_eq!(ids.values(), &[1, 2]);
+        let sources = batch.column_by_name("source").unwrap().as_string::<i32>();
+        assert_eq!(sources.value(0), "manual");
+        assert_eq!(sources.value(1), "sensor");
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        assert!(build(json!({"mode": "merge"})).is_err());
+        assert!(build(json!({"mode": "patch", "patch": {"op": "add"}})).is_err());
+        assert!(build(json!({"mode": "upsert", "patch": {}})).is_err());
+    }
+}
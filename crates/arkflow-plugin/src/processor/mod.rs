@@ -19,11 +19,28 @@
 use arkflow_core::Error;
 
 pub mod batch;
+pub mod encrypt;
+pub mod group_split;
+pub mod hash;
+pub mod incremental_agg;
 pub mod json;
+pub mod json_merge;
+#[cfg(feature = "testing")]
+pub mod noop;
+pub mod persistent_dedup;
 pub mod protobuf;
 pub mod python;
+pub mod rename;
+pub mod schema_evolution;
+pub mod session_window;
+pub mod size_guard;
+pub mod sort;
 pub mod sql;
+pub mod template;
+pub mod timestamp;
+pub mod trace_sample;
 pub mod vrl;
+pub mod wasm;
 
 pub fn init() -> Result<(), Error> {
     batch::init()?;
@@ -32,5 +49,22 @@ pub fn init() -> Result<(), Error> {
     sql::init()?;
     vrl::init()?;
     python::init()?;
+    hash::init()?;
+    size_guard::init()?;
+    encrypt::init()?;
+    sort::init()?;
+    persistent_dedup::init()?;
+    timestamp::init()?;
+    json_merge::init()?;
+    trace_sample::init()?;
+    wasm::init()?;
+    schema_evolution::init()?;
+    group_split::init()?;
+    rename::init()?;
+    session_window::init()?;
+    incremental_agg::init()?;
+    template::init()?;
+    #[cfg(feature = "testing")]
+    noop::init()?;
     Ok(())
 }
@@ -0,0 +1,245 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Testing Processor Components
+//!
+//! Passthrough processors for measuring pipeline overhead and exercising error paths.
+//! Only available with the `testing` feature.
+
+use arkflow_core::processor::{register_processor_builder, Processor, ProcessorBuilder};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// Noop processor configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct NoopProcessorConfig {
+    /// Simulated processing time per message (ms)
+    sleep_ms: Option<u64>,
+    /// Probability in `[0, 1]` of returning an error instead of the message
+    fail_rate: Option<f64>,
+}
+
+/// Processor that returns messages unchanged
+struct NoopProcessor {
+    config: NoopProcessorConfig,
+}
+
+#[async_trait]
+impl Processor for NoopProcessor {
+    async fn process(&self, msg: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+        if let Some(sleep_ms) = self.config.sleep_ms {
+            tokio::time::sleep(Duration::from_millis(sleep_ms)).await;
+        }
+        if let Some(fail_rate) = self.config.fail_rate {
+            if rand::random::<f64>() < fail_rate {
+                return Err(Error::Process(
+                    "Simulated noop processor failure".to_string(),
+                ));
+            }
+        }
+        Ok(vec![msg])
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Counting processor configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CountingProcessorConfig {
+    /// Interval between throughput reports (ms)
+    #[serde(default = "default_report_interval_ms")]
+    report_interval_ms: u64,
+}
+
+/// Processor that counts messages and periodically logs the throughput
+struct CountingProcessor {
+    name: String,
+    report_interval: Duration,
+    messages: AtomicU64,
+    rows: AtomicU64,
+    last_report: Mutex<(Instant, u64, u64)>,
+}
+
+impl CountingProcessor {
+    fn new(name: Option<&String>, config: CountingProcessorConfig) -> Self {
+        Self {
+            name: name.cloned().unwrap_or_else(|| "counting".to_string()),
+            report_interval: Duration::from_millis(config.report_interval_ms),
+            messages: AtomicU64::new(0),
+            rows: AtomicU64::new(0),
+            last_report: Mutex::new((Instant::now(), 0, 0)),
+        }
+    }
+
+    fn report(&self, messages: u64, rows: u64) {
+        let Ok(mut last_report) = self.last_report.try_lock() else {
+            // Another worker is already reporting
+            return;
+        };
+        let (last_time, last_messages, last_rows) = *last_report;
+        let elapsed = last_time.elapsed();
+        if elapsed < self.report_interval {
+            return;
+        }
+
+        let secs = elapsed.as_secs_f64();
+        info!(
+            "{}: {} messages ({:.1} msg/s), {} rows ({:.1} rows/s)",
+            self.name,
+            messages,
+            (messages - last_messages) as f64 / secs,
+            rows,
+            (rows - last_rows) as f64 / secs,
+        );
+        *last_report = (Instant::now(), messages, rows);
+    }
+}
+
+#[async_trait]
+impl Processor for CountingProcessor {
+    async fn process(&self, msg: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+        let messages = self.messages.fetch_add(1, Ordering::Relaxed) + 1;
+        let rows = self.rows.fetch_add(msg.len() as u64, Ordering::Relaxed) + msg.len() as u64;
+        self.report(messages, rows);
+        Ok(vec![msg])
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        info!(
+            "{}: processed {} messages, {} rows in total",
+            self.name,
+            self.messages.load(Ordering::Relaxed),
+            self.rows.load(Ordering::Relaxed)
+        );
+        Ok(())
+    }
+}
+
+fn default_report_interval_ms() -> u64 {
+    5000
+}
+
+struct NoopProcessorBuilder;
+impl ProcessorBuilder for NoopProcessorBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Processor>, Error> {
+        let config: NoopProcessorConfig = match config {
+            Some(config) => serde_json::from_value(config.clone())?,
+            None => NoopProcessorConfig::default(),
+        };
+        if let Some(fail_rate) = config.fail_rate {
+            if !(0.0..=1.0).contains(&fail_rate) {
+                return Err(Error::Config(format!(
+                    "Noop processor fail_rate must be between 0 and 1, got {}",
+                    fail_rate
+                )));
+            }
+        }
+
+        Ok(Arc::new(NoopProcessor { config }))
+    }
+}
+
+struct CountingProcessorBuilder;
+impl ProcessorBuilder for CountingProcessorBuilder {
+    fn build(
+        &self,
+        name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Processor>, Error> {
+        let config: CountingProcessorConfig = match config {
+            Some(config) => serde_json::from_value(config.clone())?,
+            None => CountingProcessorConfig {
+                report_interval_ms: default_report_interval_ms(),
+            },
+        };
+
+        Ok(Arc::new(CountingProcessor::new(name, config)))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_processor_builder("noop", Arc::new(NoopProcessorBuilder))?;
+    register_processor_builder("counting", Arc::new(CountingProcessorBuilder))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::cell::RefCell;
+
+    fn resource() -> Resource {
+        Resource {
+            temporary: Default::default(),
+            input_names: RefCell::new(Default::default()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_noop_passthrough() {
+        let processor = NoopProcessorBuilder
+            .build(None, &None, &resource())
+            .unwrap();
+        let msg = MessageBatch::from_string("hello").unwrap();
+        let result = processor.process(msg.clone()).await.unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(*result[0], *msg);
+    }
+
+    #[tokio::test]
+    async fn test_noop_fail_rate() {
+        let processor = NoopProcessorBuilder
+            .build(None, &Some(json!({"fail_rate": 1.0})), &resource())
+            .unwrap();
+        let msg = MessageBatch::from_string("hello").unwrap();
+        assert!(matches!(
+            processor.process(msg).await,
+            Err(Error::Process(_))
+        ));
+
+        assert!(NoopProcessorBuilder
+            .build(None, &Some(json!({"fail_rate": 1.5})), &resource())
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_counting() {
+        let processor = CountingProcessor::new(
+            None,
+            CountingProcessorConfig {
+                report_interval_ms: 0,
+            },
+        );
+        for _ in 0..3 {
+            let msg = MessageBatch::new_binary(vec![b"a".to_vec(), b"b".to_vec()]).unwrap();
+            processor.process(msg).await.unwrap();
+        }
+        assert_eq!(processor.messages.load(Ordering::Relaxed), 3);
+        assert_eq!(processor.rows.load(Ordering::Relaxed), 6);
+    }
+}
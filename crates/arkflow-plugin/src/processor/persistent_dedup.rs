@@ -0,0 +1,501 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Persistent Deduplicate Processor Component
+//!
+//! Drop messages whose key fields were already seen, remembering the keys in a state store
+//! so that duplicates are detected across restarts. Keys are only stored once the message
+//! was written to the output, so that a message delivered again after a failed write is
+//! not mistaken for a duplicate. State store reads and writes run on the blocking thread pool.
+
+use crate::state::StateStoreConfig;
+use arkflow_core::input::Ack;
+use arkflow_core::processor::{
+    register_processor_builder, Processor, ProcessorBuilder, StateStore,
+};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use datafusion::arrow::array::{Array, BooleanArray};
+use datafusion::arrow::compute::filter_record_batch;
+use datafusion::arrow::util::display::{ArrayFormatter, FormatOptions};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::error;
+
+/// Prefix of the keys written to the state store
+const KEY_PREFIX: &[u8] = b"dedup/";
+
+/// How often expired keys are deleted from the state store, in milliseconds
+const SWEEP_INTERVAL_MS: u64 = 60_000;
+
+/// Persistent deduplicate processor configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PersistentDedupProcessorConfig {
+    /// Fields identifying a message
+    key_fields: Vec<String>,
+    /// How long a key is remembered
+    ttl_secs: u64,
+    /// Directory of the RocksDB database holding the seen keys
+    state_store_path: String,
+}
+
+/// Keys kept but not yet written, shared with the pending [`DedupCommit`]s
+#[derive(Default)]
+struct DedupState {
+    /// Keys of kept messages that were not written to the output yet
+    pending: HashSet<Vec<u8>>,
+    /// Time of the last deletion of expired keys
+    last_sweep_ms: u64,
+}
+
+struct PersistentDedupProcessor {
+    config: PersistentDedupProcessorConfig,
+    store: Arc<dyn StateStore>,
+    /// Serializes the check of the keys so that concurrent batches cannot both keep a key
+    state: Arc<Mutex<DedupState>>,
+}
+
+impl PersistentDedupProcessor {
+    fn new(config: PersistentDedupProcessorConfig) -> Result<Self, Error> {
+        let store = StateStoreConfig::Rocksdb {
+            path: config.state_store_path.clone(),
+        }
+        .build()?;
+        Self::with_state_store(config, store)
+    }
+
+    /// Create a new processor remembering the seen keys in `store`.
+    fn with_state_store(
+        config: PersistentDedupProcessorConfig,
+        store: Arc<dyn StateStore>,
+    ) -> Result<Self, Error> {
+        if config.key_fields.is_empty() {
+            return Err(Error::Config(
+                "Persistent deduplicate processor requires at least one key field".to_string(),
+            ));
+        }
+        Ok(Self {
+            config,
+            store,
+            state: Arc::new(Mutex::new(DedupState::default())),
+        })
+    }
+
+    /// State store key of each row: the SHA-256 of its key field values
+    fn row_keys(&self, batch: &MessageBatch) -> Result<Vec<Vec<u8>>, Error> {
+        let columns = self
+            .config
+            .key_fields
+            .iter()
+            .map(|field| {
+                batch.column_by_name(field).cloned().ok_or_else(|| {
+                    Error::Process(format!("Key field {} not found in the batch", field))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let options = FormatOptions::default();
+        let formatters = columns
+            .iter()
+            .map(|column| ArrayFormatter::try_new(column.as_ref(), &options))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Error::Process(format!("Failed to format column: {}", e)))?;
+
+        let mut keys = Vec::with_capacity(batch.num_rows());
+        for row in 0..batch.num_rows() {
+            let mut hasher = Sha256::new();
+            for (column, formatter) in columns.iter().zip(&formatters) {
+                // Length-prefix every value so that ("ab", "c") and ("a", "bc") differ
+                if column.is_null(row) {
+                    hasher.update(u64::MAX.to_be_bytes());
+                } else {
+                    let value = formatter.value(row).to_string();
+                    hasher.update((value.len() as u64).to_be_bytes());
+                    hasher.update(value.as_bytes());
+                }
+            }
+            let mut key = KEY_PREFIX.to_vec();
+            key.extend_from_slice(&hasher.finalize());
+            keys.push(key);
+        }
+        Ok(keys)
+    }
+}
+
+/// Run blocking state store I/O on the blocking thread pool
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, Error> + Send + 'static,
+) -> Result<T, Error> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| Error::Process(format!("Deduplicate task failed: {}", e)))?
+}
+
+/// Delete the keys that expired before `now`
+fn sweep_expired(store: &dyn StateStore, now: u64) -> Result<(), Error> {
+    let is_expired = |value: &[u8]| expiry(value).is_none_or(|expires_at| expires_at <= now);
+    for (key, value) in store.scan_prefix(KEY_PREFIX)? {
+        // Read the key again, it may have been stored since the scan
+        if is_expired(&value) && store.get(&key)?.is_none_or(|value| is_expired(&value)) {
+            store.delete(&key)?;
+        }
+    }
+    Ok(())
+}
+
+/// Check which `keys` were not seen yet, and mark them as pending.
+///
+/// Returns whether each row is kept, and the keys of the kept rows.
+fn check_keys(
+    store: &dyn StateStore,
+    state: &Mutex<DedupState>,
+    keys: Vec<Vec<u8>>,
+    now: u64,
+) -> Result<(Vec<bool>, Vec<Vec<u8>>), Error> {
+    let mut state = state
+        .lock()
+        .map_err(|_| Error::Unknown("Deduplicate lock poisoned".to_string()))?;
+    let mut keep = Vec::with_capacity(keys.len());
+    let mut kept_keys = Vec::new();
+    for key in keys {
+        // The value is the expiry time; an expired key counts as unseen
+        let seen = state.pending.contains(&key)
+            || store
+                .get(&key)?
+                .and_then(|value| expiry(&value))
+                .is_some_and(|expires_at| expires_at > now);
+        if !seen {
+            state.pending.insert(key.clone());
+            kept_keys.push(key);
+        }
+        keep.push(!seen);
+    }
+    Ok((keep, kept_keys))
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Expiry time stored as the value of a key
+fn expiry(value: &[u8]) -> Option<u64> {
+    value.try_into().ok().map(u64::from_be_bytes)
+}
+
+#[async_trait]
+impl Processor for PersistentDedupProcessor {
+    async fn process(&self, batch: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+        if batch.num_rows() == 0 {
+            return Ok(vec![]);
+        }
+
+        let keys = self.row_keys(&batch)?;
+        let now = now_ms();
+
+        // The sweep does not hold the lock, so that batches are not checked one at a time
+        // behind it
+        let sweep_due = {
+            let mut state = self
+                .state
+                .lock()
+                .map_err(|_| Error::Unknown("Deduplicate lock poisoned".to_string()))?;
+            let due = now.saturating_sub(state.last_sweep_ms) >= SWEEP_INTERVAL_MS;
+            if due {
+                state.last_sweep_ms = now;
+            }
+            due
+        };
+        if sweep_due {
+            let store = self.store.clone();
+            blocking(move || sweep_expired(store.as_ref(), now)).await?;
+        }
+
+        let store = self.store.clone();
+        let state = self.state.clone();
+        let (keep, kept_keys) =
+            blocking(move || check_keys(store.as_ref(), &state, keys, now)).await?;
+
+        if kept_keys.is_empty() {
+            return Ok(vec![]);
+        }
+        let commit = Arc::new(DedupCommit {
+            keys: kept_keys,
+            expires_at: now.saturating_add(self.config.ttl_secs.saturating_mul(1000)),
+            store: self.store.clone(),
+            state: self.state.clone(),
+            committed: AtomicBool::new(false),
+        });
+
+        let mut result = if keep.iter().all(|keep| *keep) {
+            batch
+        } else {
+            let filtered = filter_record_batch(&batch, &BooleanArray::from(keep))
+                .map_err(|e| Error::Process(format!("Failed to filter duplicates: {}", e)))?;
+            let mut result = MessageBatch::new_arrow(filtered);
+            result.set_input_name(batch.get_input_name());
+            result
+        };
+        result.on_ack(commit);
+        Ok(vec![result])
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Stores the keys of the kept messages once they were written to the output
+struct DedupCommit {
+    keys: Vec<Vec<u8>>,
+    expires_at: u64,
+    store: Arc<dyn StateStore>,
+    state: Arc<Mutex<DedupState>>,
+    committed: AtomicBool,
+}
+
+impl DedupCommit {
+    fn release(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            for key in &self.keys {
+                state.pending.remove(key);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Ack for DedupCommit {
+    async fn ack(&self) {
+        if self.committed.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let keys = self.keys.clone();
+        let store = self.store.clone();
+        let value = self.expires_at.to_be_bytes();
+        let stored = blocking(move || {
+            for key in &keys {
+                if let Err(e) = store.put(key, &value) {
+                    error!("Failed to store deduplicate key: {}", e);
+                }
+            }
+            Ok(())
+        })
+        .await;
+        if let Err(e) = stored {
+            error!("{}", e);
+        }
+        self.release();
+    }
+}
+
+impl Drop for DedupCommit {
+    /// Keys of messages that were never written are unseen again
+    fn drop(&mut self) {
+        if !*self.committed.get_mut() {
+            self.release();
+        }
+    }
+}
+
+struct PersistentDedupProcessorBuilder;
+impl ProcessorBuilder for PersistentDedupProcessorBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Processor>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Persistent deduplicate processor configuration is missing".to_string(),
+            ));
+        }
+        if !cfg!(feature = "rocksdb") {
+            return Err(Error::Config(
+                "Persistent deduplicate processor requires arkflow to be built with the rocksdb feature"
+                    .to_string(),
+            ));
+        }
+        let config: PersistentDedupProcessorConfig =
+            serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(PersistentDedupProcessor::new(config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_processor_builder(
+        "persistent_dedup",
+        Arc::new(PersistentDedupProcessorBuilder),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::memory::MemoryStateStore;
+    use datafusion::arrow::array::{Int64Array, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+
+    fn config(ttl_secs: u64) -> PersistentDedupProcessorConfig {
+        PersistentDedupProcessorConfig {
+            key_fields: vec!["id".to_string(), "source".to_string()],
+            ttl_secs,
+            state_store_path: String::new(),
+        }
+    }
+
+    fn batch(rows: &[(i64, &str)]) -> MessageBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("source", DataType::Utf8, false),
+        ]));
+        MessageBatch::new_arrow(
+            RecordBatch::try_new(
+                schema,
+                vec![
+                    Arc::new(Int64Array::from_iter_values(rows.iter().map(|r| r.0))),
+                    Arc::new(StringArray::from_iter_values(rows.iter().map(|r| r.1))),
+                ],
+            )
+            .unwrap(),
+        )
+    }
+
+    fn ids(result: &[MessageBatch]) -> Vec<i64> {
+        result
+            .iter()
+            .flat_map(|batch| {
+                let column = batch.column_by_name("id").unwrap();
+                let column = column.as_any().downcast_ref::<Int64Array>().unwrap();
+                column.values().to_vec()
+            })
+            .collect()
+    }
+
+    /// Acknowledge the results as the stream does once they were written
+    async fn write(mut result: Vec<MessageBatch>) -> Vec<i64> {
+        for batch in &mut result {
+            for ack in batch.take_acks() {
+                ack.ack().await;
+            }
+        }
+        ids(&result)
+    }
+
+    #[tokio::test]
+    async fn test_duplicates_dropped_across_restart() {
+        let store: Arc<dyn StateStore> = Arc::new(MemoryStateStore::default());
+
+        let processor =
+            PersistentDedupProcessor::with_state_store(config(3600), store.clone()).unwrap();
+        let result = processor
+            .process(batch(&[(1, "a"), (2, "a"), (1, "a"), (1, "b")]))
+            .await
+            .unwrap();
+        assert_eq!(write(result).await, vec![1, 2, 1]);
+
+        // A new processor on the same store remembers the keys
+        let processor = PersistentDedupProcessor::with_state_store(config(3600), store).unwrap();
+        let result = processor
+            .process(batch(&[(2, "a"), (3, "a")]))
+            .await
+            .unwrap();
+        assert_eq!(write(result).await, vec![3]);
+
+        let result = processor.process(batch(&[(3, "a")])).await.unwrap();
+        assert!(result.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_keys_stored_after_write() {
+        let store: Arc<dyn StateStore> = Arc::new(MemoryStateStore::default());
+        let processor =
+            PersistentDedupProcessor::with_state_store(config(3600), store.clone()).unwrap();
+
+        // A pending message is a duplicate, but its key is not stored yet
+        let pending = processor.process(batch(&[(1, "a")])).await.unwrap();
+        assert!(processor
+            .process(batch(&[(1, "a")]))
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(store.scan_prefix(KEY_PREFIX).unwrap().is_empty());
+
+        // The write failed, so the message delivered again is kept
+        drop(pending);
+        let result = processor.process(batch(&[(1, "a")])).await.unwrap();
+        assert_eq!(write(result).await, vec![1]);
+        assert_eq!(store.scan_prefix(KEY_PREFIX).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_expired_keys_are_unseen() {
+        let store: Arc<dyn StateStore> = Arc::new(MemoryStateStore::default());
+        let processor = PersistentDedupProcessor::with_state_store(config(0), store).unwrap();
+
+        write(processor.process(batch(&[(1, "a")])).await.unwrap()).await;
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        let result = processor.process(batch(&[(1, "a")])).await.unwrap();
+        assert_eq!(write(result).await, vec![1]);
+    }
+
+    #[tokio::test]
+    async fn test_expired_keys_are_deleted() {
+        let store: Arc<dyn StateStore> = Arc::new(MemoryStateStore::default());
+        let processor =
+            PersistentDedupProcessor::with_state_store(config(0), store.clone()).unwrap();
+
+        write(processor.process(batch(&[(1, "a")])).await.unwrap()).await;
+        assert_eq!(store.scan_prefix(KEY_PREFIX).unwrap().len(), 1);
+
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        processor.state.lock().unwrap().last_sweep_ms = 0;
+        processor.process(batch(&[(2, "a")])).await.unwrap();
+        assert!(store.scan_prefix(KEY_PREFIX).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_missing_key_field() {
+        let store: Arc<dyn StateStore> = Arc::new(MemoryStateStore::default());
+        let mut config = config(60);
+        config.key_fields = vec!["missing".to_string()];
+        let processor = PersistentDedupProcessor::with_state_store(config, store).unwrap();
+        assert!(matches!(
+            processor.process(batch(&[(1, "a")])).await,
+            Err(Error::Process(_))
+        ));
+    }
+
+    #[cfg(not(feature = "rocksdb"))]
+    #[test]
+    fn test_requires_rocksdb_feature() {
+        let resource = Resource {
+            temporary: Default::default(),
+            input_names: Default::default(),
+        };
+        let config = serde_json::to_value(config(60)).unwrap();
+        assert!(matches!(
+            PersistentDedupProcessorBuilder.build(None, &Some(config), &resource),
+            Err(Error::Config(_))
+        ));
+    }
+}
@@ -0,0 +1,303 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Rename Processor Component
+//!
+//! Rename the columns of Arrow batches, or the top-level keys of binary JSON messages, to
+//! standardize the field names of different sources. Arrow columns are renamed by replacing
+//! the schema only, without copying the column data.
+
+use arkflow_core::processor::Processor;
+use arkflow_core::{Error, MessageBatch, DEFAULT_BINARY_VALUE_FIELD};
+use arkflow_macros::arkflow_processor;
+use async_trait::async_trait;
+use datafusion::arrow::datatypes::SchemaBuilder;
+use datafusion::arrow::record_batch::RecordBatch;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Rename processor configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RenameProcessorConfig {
+    /// New name of every renamed field, by old name
+    mappings: HashMap<String, String>,
+    /// Drop the fields that are not in the mappings
+    #[serde(default)]
+    drop_unmapped: bool,
+    /// Whether old names must match the field names exactly, or regardless of case
+    #[serde(default = "default_case_sensitive")]
+    case_sensitive: bool,
+}
+
+fn default_case_sensitive() -> bool {
+    true
+}
+
+#[arkflow_processor(name = "rename")]
+#[derive(Deserialize)]
+#[serde(try_from = "RenameProcessorConfig")]
+struct RenameProcessor {
+    config: RenameProcessorConfig,
+    /// Mappings keyed by lowercase old name, when matching regardless of case
+    lowercase_mappings: HashMap<String, String>,
+}
+
+impl TryFrom<RenameProcessorConfig> for RenameProcessor {
+    type Error = Error;
+
+    fn try_from(config: RenameProcessorConfig) -> Result<Self, Error> {
+        let mut lowercase_mappings = HashMap::new();
+        if !config.case_sensitive {
+            for (old_name, new_name) in &config.mappings {
+                let key = old_name.to_lowercase();
+                if lowercase_mappings.insert(key, new_name.clone()).is_some() {
+                    return Err(Error::Config(format!(
+                        "Field {} is mapped more than once regardless of case",
+                        old_name
+                    )));
+                }
+            }
+        }
+        Ok(Self {
+            config,
+            lowercase_mappings,
+        })
+    }
+}
+
+impl RenameProcessor {
+    /// New name of the field `name`, `None` if the field is dropped
+    fn target_name<'a>(&'a self, name: &'a str) -> Option<&'a str> {
+        let mapped = if self.config.case_sensitive {
+            self.config.mappings.get(name)
+        } else {
+            self.lowercase_mappings.get(&name.to_lowercase())
+        };
+        match mapped {
+            Some(new_name) => Some(new_name),
+            None if self.config.drop_unmapped => None,
+            None => Some(name),
+        }
+    }
+
+    fn rename_arrow(&self, batch: &RecordBatch) -> Result<RecordBatch, Error> {
+        let schema = batch.schema();
+        let mut builder = SchemaBuilder::with_capacity(schema.fields().len());
+        let mut columns = Vec::with_capacity(schema.fields().len());
+        let mut names = HashSet::with_capacity(schema.fields().len());
+        for (field, column) in schema.fields().iter().zip(batch.columns()) {
+            let Some(name) = self.target_name(field.name()) else {
+                continue;
+            };
+            if !names.insert(name) {
+                return Err(Error::Process(format!(
+                    "Renaming produces duplicate column {}",
+                    name
+                )));
+            }
+            builder.push(field.as_ref().clone().with_name(name));
+            columns.push(column.clone());
+        }
+        let schema = builder.finish().with_metadata(schema.metadata().clone());
+
+        RecordBatch::try_new(Arc::new(schema), columns)
+            .map_err(|e| Error::Process(format!("Creating an Arrow record batch failed: {}", e)))
+    }
+
+    fn rename_json(&self, content: &[u8]) -> Result<Vec<u8>, Error> {
+        let doc: Value = serde_json::from_slice(content)
+            .map_err(|e| Error::Process(format!("Invalid JSON message: {}", e)))?;
+        let Value::Object(object) = doc else {
+            return Err(Error::Process(
+                "The JSON message is not an object".to_string(),
+            ));
+        };
+
+        let mut renamed = Map::with_capacity(object.len());
+        for (key, value) in object {
+            let Some(name) = self.target_name(&key) else {
+                continue;
+            };
+            if renamed.contains_key(name) {
+                return Err(Error::Process(format!(
+                    "Renaming produces duplicate field {}",
+                    name
+                )));
+            }
+            renamed.insert(name.to_string(), value);
+        }
+        Ok(serde_json::to_vec(&renamed)?)
+    }
+}
+
+#[async_trait]
+impl Processor for RenameProcessor {
+    async fn process(&self, msg: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+        if msg.is_empty() {
+            return Ok(vec![msg]);
+        }
+        let mut renamed = if msg.is_binary() {
+            let messages = msg
+                .to_binary(DEFAULT_BINARY_VALUE_FIELD)?
+                .into_iter()
+                .map(|content| self.rename_json(content))
+                .collect::<Result<Vec<_>, Error>>()?;
+            msg.with_binary_values(messages)?
+        } else {
+            MessageBatch::new_arrow(self.rename_arrow(&msg)?)
+        };
+        renamed.set_input_name(msg.get_input_name());
+        Ok(vec![renamed])
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arkflow_core::processor::ProcessorBuilder;
+    use arkflow_core::Resource;
+    use datafusion::arrow::array::{ArrayRef, BinaryArray, Int64Array, StringArray};
+    use serde_json::json;
+    use std::cell::RefCell;
+
+    fn build(config: serde_json::Value) -> Result<Arc<dyn Processor>, Error> {
+        RenameProcessorBuilder.build(
+            None,
+            &Some(config),
+            &Resource {
+                temporary: Default::default(),
+                input_names: RefCell::new(Default::default()),
+            },
+        )
+    }
+
+    fn batch() -> RecordBatch {
+        RecordBatch::try_from_iter([
+            ("UserId", Arc::new(Int64Array::from(vec![1, 2])) as ArrayRef),
+            (
+                "user_name",
+                Arc::new(StringArray::from(vec!["a", "b"])) as ArrayRef,
+            ),
+            ("ts", Arc::new(Int64Array::from(vec![10, 20])) as ArrayRef),
+        ])
+        .unwrap()
+    }
+
+    fn names(msg: &MessageBatch) -> Vec<String> {
+        msg.schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_rename_arrow() {
+        let processor = build(json!({
+            "mappings": {"UserId": "user_id", "user_name": "name"},
+        }))
+        .unwrap();
+        let batch = batch();
+        let result = processor
+            .process(MessageBatch::new_arrow(batch.clone()))
+            .await
+            .unwrap();
+        assert_eq!(names(&result[0]), vec!["user_id", "name", "ts"]);
+        // The column data is shared with the input
+        assert!(Arc::ptr_eq(result[0].column(0), batch.column(0)));
+
+        let processor = build(json!({
+            "mappings": {"userid": "user_id"},
+            "drop_unmapped": true,
+            "case_sensitive": false,
+        }))
+        .unwrap();
+        let result = processor
+            .process(MessageBatch::new_arrow(batch))
+            .await
+            .unwrap();
+        assert_eq!(names(&result[0]), vec!["user_id"]);
+        assert_eq!(result[0].num_rows(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_rename_json() {
+        let processor = build(json!({
+            "mappings": {"USERID": "user_id"},
+            "case_sensitive": false,
+        }))
+        .unwrap();
+        let msg = MessageBatch::new_binary(vec![br#"{"UserId":1,"name":"a"}"#.to_vec()]).unwrap();
+        let result = processor.process(msg).await.unwrap();
+        let doc: Value =
+            serde_json::from_slice(result[0].to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap()[0])
+                .unwrap();
+        assert_eq!(doc, json!({"user_id": 1, "name": "a"}));
+
+        let msg = MessageBatch::new_binary(vec![b"[1]".to_vec()]).unwrap();
+        assert!(matches!(
+            processor.process(msg).await,
+            Err(Error::Process(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_rename_json_keeps_metadata_columns() {
+        let processor = build(json!({"mappings": {"UserId": "user_id"}})).unwrap();
+        // Shaped like a message of the MQTT input, with the topic next to the payload
+        let msg = MessageBatch::new_arrow(
+            RecordBatch::try_from_iter([
+                (
+                    DEFAULT_BINARY_VALUE_FIELD,
+                    Arc::new(BinaryArray::from_vec(vec![br#"{"UserId":1}"#])) as ArrayRef,
+                ),
+                (
+                    "mqtt_topic",
+                    Arc::new(StringArray::from(vec!["sensors/a"])) as ArrayRef,
+                ),
+            ])
+            .unwrap(),
+        );
+        let result = processor.process(msg).await.unwrap();
+        assert_eq!(
+            names(&result[0]),
+            vec![DEFAULT_BINARY_VALUE_FIELD, "mqtt_topic"]
+        );
+        let doc: Value =
+            serde_json::from_slice(result[0].to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap()[0])
+                .unwrap();
+        assert_eq!(doc, json!({"user_id": 1}));
+    }
+
+    #[tokio::test]
+    async fn test_rename_duplicates() {
+        let processor = build(json!({"mappings": {"UserId": "ts"}})).unwrap();
+        assert!(matches!(
+            processor.process(MessageBatch::new_arrow(batch())).await,
+            Err(Error::Process(_))
+        ));
+
+        let result = build(json!({
+            "mappings": {"id": "a", "ID": "b"},
+            "case_sensitive": false,
+        }));
+        assert!(matches!(result, Err(Error::Config(_))));
+    }
+}
@@ -0,0 +1,569 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Schema Evolution Detector Processor Component
+//!
+//! Pass the messages through unchanged while tracking the fields observed in windows of
+//! `window_size` batches. When the fields of a window differ from those of the previous
+//! window, the changes are reported as a batch of [`SchemaChangeEvent`] rows on the side
+//! channel and to the alert output.
+
+use arkflow_core::output::{Output, OutputConfig};
+use arkflow_core::processor::{register_processor_builder, Processor, ProcessorBuilder};
+use arkflow_core::{Error, MessageBatch, Resource, DEFAULT_BINARY_VALUE_FIELD};
+use async_trait::async_trait;
+use datafusion::arrow::array::{Int64Array, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::OnceCell;
+use tracing::{error, warn};
+
+/// Schema evolution detector configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaEvolutionDetectorConfig {
+    /// Number of batches whose fields are compared with the previous window
+    #[serde(default = "default_window_size")]
+    pub window_size: usize,
+    /// Output receiving the schema change events
+    pub alert_output: Option<OutputConfig>,
+}
+
+fn default_window_size() -> usize {
+    1
+}
+
+/// Kind of change of a field between two windows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaChangeType {
+    FieldAdded,
+    FieldRemoved,
+    TypeChanged,
+}
+
+impl SchemaChangeType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SchemaChangeType::FieldAdded => "field_added",
+            SchemaChangeType::FieldRemoved => "field_removed",
+            SchemaChangeType::TypeChanged => "type_changed",
+        }
+    }
+}
+
+/// Change of a field, one row of the event batches
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaChangeEvent {
+    pub event_type: SchemaChangeType,
+    pub field_name: String,
+    /// Type of the field in the previous window, `None` for added fields
+    pub old_type: Option<DataType>,
+    /// Type of the field in the current window, `None` for removed fields
+    pub new_type: Option<DataType>,
+    pub timestamp_ms: i64,
+}
+
+impl SchemaChangeEvent {
+    /// Arrow batch with one row per event, with the columns `event_type`, `field_name`,
+    /// `old_type`, `new_type` and `timestamp_ms`
+    pub fn to_message_batch(events: &[SchemaChangeEvent]) -> Result<MessageBatch, Error> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("event_type", DataType::Utf8, false),
+            Field::new("field_name", DataType::Utf8, false),
+            Field::new("old_type", DataType::Utf8, true),
+            Field::new("new_type", DataType::Utf8, true),
+            Field::new("timestamp_ms", DataType::Int64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from_iter_values(
+                    events.iter().map(|e| e.event_type.as_str()),
+                )),
+                Arc::new(StringArray::from_iter_values(
+                    events.iter().map(|e| e.field_name.as_str()),
+                )),
+                Arc::new(StringArray::from_iter(
+                    events
+                        .iter()
+                        .map(|e| e.old_type.as_ref().map(|t| t.to_string())),
+                )),
+                Arc::new(StringArray::from_iter(
+                    events
+                        .iter()
+                        .map(|e| e.new_type.as_ref().map(|t| t.to_string())),
+                )),
+                Arc::new(Int64Array::from_iter_values(
+                    events.iter().map(|e| e.timestamp_ms),
+                )),
+            ],
+        )
+        .map_err(|e| Error::Process(format!("Failed to create schema change events: {}", e)))?;
+        Ok(MessageBatch::new_arrow(batch))
+    }
+}
+
+/// Fields observed in the current and previous windows
+#[derive(Default)]
+struct WindowState {
+    batches: usize,
+    current: BTreeMap<String, DataType>,
+    previous: Option<BTreeMap<String, DataType>>,
+}
+
+impl WindowState {
+    /// Record the fields of a batch, and compare the window with the previous one once it is
+    /// complete
+    fn observe(
+        &mut self,
+        fields: Vec<(String, DataType)>,
+        window_size: usize,
+    ) -> Vec<SchemaChangeEvent> {
+        self.current.extend(fields);
+        self.batches += 1;
+        if self.batches < window_size {
+            return vec![];
+        }
+
+        self.batches = 0;
+        let current = std::mem::take(&mut self.current);
+        let events = match &self.previous {
+            Some(previous) => diff(previous, &current, now_ms()),
+            None => vec![],
+        };
+        self.previous = Some(current);
+        events
+    }
+}
+
+/// Changes of the fields from `previous` to `current`
+fn diff(
+    previous: &BTreeMap<String, DataType>,
+    current: &BTreeMap<String, DataType>,
+    timestamp_ms: i64,
+) -> Vec<SchemaChangeEvent> {
+    let mut events = Vec::new();
+    for (name, old_type) in previous {
+        match current.get(name) {
+            None => events.push(SchemaChangeEvent {
+                event_type: SchemaChangeType::FieldRemoved,
+                field_name: name.clone(),
+                old_type: Some(old_type.clone()),
+                new_type: None,
+                timestamp_ms,
+            }),
+            Some(new_type) if new_type != old_type => events.push(SchemaChangeEvent {
+                event_type: SchemaChangeType::TypeChanged,
+                field_name: name.clone(),
+                old_type: Some(old_type.clone()),
+                new_type: Some(new_type.clone()),
+                timestamp_ms,
+            }),
+            Some(_) => {}
+        }
+    }
+    for (name, new_type) in current {
+        if !previous.contains_key(name) {
+            events.push(SchemaChangeEvent {
+                event_type: SchemaChangeType::FieldAdded,
+                field_name: name.clone(),
+                old_type: None,
+                new_type: Some(new_type.clone()),
+                timestamp_ms,
+            });
+        }
+    }
+    events
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+/// Fields of a batch. The schema of binary messages is inferred from their JSON content.
+fn observed_fields(msg: &MessageBatch) -> Result<Vec<(String, DataType)>, Error> {
+    let schema = if msg.is_binary() {
+        let mut content = Vec::new();
+        for payload in msg.to_binary(DEFAULT_BINARY_VALUE_FIELD)? {
+            content.extend_from_slice(payload);
+            content.push(b'\n');
+        }
+        let (schema, _) = arrow_json::reader::infer_json_schema(&mut Cursor::new(content), None)
+            .map_err(|e| Error::Process(format!("Schema inference error: {}", e)))?;
+        Arc::new(schema)
+    } else {
+        msg.schema()
+    };
+    Ok(schema
+        .fields()
+        .iter()
+        .map(|f| (f.name().clone(), f.data_type().clone()))
+        .collect())
+}
+
+/// Schema evolution detector processor
+pub struct SchemaEvolutionDetector {
+    window_size: usize,
+    state: Mutex<WindowState>,
+    side_channel: Option<flume::Sender<MessageBatch>>,
+    alert_output: Option<Arc<dyn Output>>,
+    alert_output_connected: OnceCell<()>,
+}
+
+impl SchemaEvolutionDetector {
+    pub fn new(config: SchemaEvolutionDetectorConfig, resource: &Resource) -> Result<Self, Error> {
+        if config.window_size == 0 {
+            return Err(Error::Config(
+                "Schema evolution detector window_size must be positive".to_string(),
+            ));
+        }
+        let alert_output = config
+            .alert_output
+            .map(|output| output.build(resource))
+            .transpose()?;
+        Ok(Self {
+            window_size: config.window_size,
+            state: Mutex::new(WindowState::default()),
+            side_channel: None,
+            alert_output,
+            alert_output_connected: OnceCell::new(),
+        })
+    }
+
+    /// Also send the schema change events to `sender`
+    pub fn with_side_channel(mut self, sender: flume::Sender<MessageBatch>) -> Self {
+        self.side_channel = Some(sender);
+        self
+    }
+
+    async fn emit(&self, events: MessageBatch) {
+        if let Some(sender) = &self.side_channel {
+            if let Err(e) = sender.try_send(events.clone()) {
+                warn!("Failed to send schema change events: {}", e);
+            }
+        }
+
+        if let Some(output) = &self.alert_output {
+            let connected = self
+                .alert_output_connected
+                .get_or_try_init(|| output.connect())
+                .await;
+            let result = match connected {
+                Ok(_) => output.write(events).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                error!("Failed to write schema change events: {}", e);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Processor for SchemaEvolutionDetector {
+    async fn process(&self, msg: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+        let fields = match observed_fields(&msg) {
+            Ok(fields) => fields,
+            Err(e) => {
+                warn!("Unable to determine the fields of the message: {}", e);
+                return Ok(vec![msg]);
+            }
+        };
+        let events = self.state.lock().unwrap().observe(fields, self.window_size);
+        if !events.is_empty() {
+            self.emit(SchemaChangeEvent::to_message_batch(&events)?)
+                .await;
+        }
+        Ok(vec![msg])
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        match &self.alert_output {
+            Some(output) if self.alert_output_connected.initialized() => output.close().await,
+            _ => Ok(()),
+        }
+    }
+}
+
+struct SchemaEvolutionDetectorBuilder;
+impl ProcessorBuilder for SchemaEvolutionDetectorBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<Value>,
+        resource: &Resource,
+    ) -> Result<Arc<dyn Processor>, Error> {
+        let config: SchemaEvolutionDetectorConfig = match config {
+            Some(config) => serde_json::from_value(config.clone())?,
+            None => SchemaEvolutionDetectorConfig {
+                window_size: default_window_size(),
+                alert_output: None,
+            },
+        };
+        Ok(Arc::new(SchemaEvolutionDetector::new(config, resource)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_processor_builder(
+        "schema_evolution_detector",
+        Arc::new(SchemaEvolutionDetectorBuilder),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Array, AsArray};
+    use std::cell::RefCell;
+
+    fn resource() -> Resource {
+        Resource {
+            temporary: Default::default(),
+            input_names: RefCell::new(Default::default()),
+        }
+    }
+
+    fn detector(window_size: usize) -> (SchemaEvolutionDetector, flume::Receiver<MessageBatch>) {
+        let (sender, receiver) = flume::unbounded();
+        let config = SchemaEvolutionDetectorConfig {
+            window_size,
+            alert_output: None,
+        };
+        let detector = SchemaEvolutionDetector::new(config, &resource())
+            .unwrap()
+            .with_side_channel(sender);
+        (detector, receiver)
+    }
+
+    fn batch(fields: Vec<(&str, DataType)>) -> MessageBatch {
+        let schema = Schema::new(
+            fields
+                .into_iter()
+                .map(|(name, data_type)| Field::new(name, data_type, true))
+                .collect::<Vec<_>>(),
+        );
+        MessageBatch::new_arrow(RecordBatch::new_empty(Arc::new(schema)))
+    }
+
+    /// (event_type, field_name, old_type, new_type) of the rows of an event batch
+    fn rows(events: &MessageBatch) -> Vec<(String, String, Option<String>, Option<String>)> {
+        let column = |name| events.column_by_name(name).unwrap().as_string::<i32>();
+        (0..events.num_rows())
+            .map(|i| {
+                let optional = |name| {
+                    let column = column(name);
+                    column.is_valid(i).then(|| column.value(i).to_string())
+                };
+                (
+                    column("event_type").value(i).to_string(),
+                    column("field_name").value(i).to_string(),
+                    optional("old_type"),
+                    optional("new_type"),
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_detect_changes_between_batches() {
+        let (detector, events) = detector(1);
+
+        let first = batch(vec![("id", DataType::Int64), ("name", DataType::Utf8)]);
+        let result = detector.process(first.clone()).await.unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].schema(), first.schema());
+        detector
+            .process(batch(vec![
+                ("id", DataType::Int64),
+                ("name", DataType::Utf8),
+            ]))
+            .await
+            .unwrap();
+        assert!(events.is_empty());
+
+        detector
+            .process(batch(vec![
+                ("id", DataType::Utf8),
+                ("email", DataType::Utf8),
+            ]))
+            .await
+            .unwrap();
+        let events = events.try_recv().unwrap();
+        assert_eq!(
+            rows(&events),
+            vec![
+                (
+                    "type_changed".to_string(),
+                    "id".to_string(),
+                    Some("Int64".to_string()),
+                    Some("Utf8".to_string())
+                ),
+                (
+                    "field_removed".to_string(),
+                    "name".to_string(),
+                    Some("Utf8".to_string()),
+                    None
+                ),
+                (
+                    "field_added".to_string(),
+                    "email".to_string(),
+                    None,
+                    Some("Utf8".to_string())
+                ),
+            ]
+        );
+        assert_eq!(
+            events.column_by_name("timestamp_ms").unwrap().null_count(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_window_merges_batch_fields() {
+        let (detector, events) = detector(2);
+
+        // Both windows observe id and name, in different batches
+        detector
+            .process(batch(vec![("id", DataType::Int64)]))
+            .await
+            .unwrap();
+        detector
+            .process(batch(vec![("name", DataType::Utf8)]))
+            .await
+            .unwrap();
+        detector
+            .process(batch(vec![
+                ("id", DataType::Int64),
+                ("name", DataType::Utf8),
+            ]))
+            .await
+            .unwrap();
+        detector
+            .process(batch(vec![("id", DataType::Int64)]))
+            .await
+            .unwrap();
+        assert!(events.is_empty());
+
+        detector
+            .process(batch(vec![("id", DataType::Int64)]))
+            .await
+            .unwrap();
+        detector
+            .process(batch(vec![("id", DataType::Int64)]))
+            .await
+            .unwrap();
+        assert_eq!(
+            rows(&events.try_recv().unwrap()),
+            vec![(
+                "field_removed".to_string(),
+                "name".to_string(),
+                Some("Utf8".to_string()),
+                None
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_binary_json_messages() {
+        let (detector, events) = detector(1);
+
+        detector
+            .process(MessageBatch::new_binary(vec![br#"{"a": 1}"#.to_vec()]).unwrap())
+            .await
+            .unwrap();
+        detector
+            .process(MessageBatch::new_binary(vec![br#"{"a": 1, "b": true}"#.to_vec()]).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            rows(&events.try_recv().unwrap()),
+            vec![(
+                "field_added".to_string(),
+                "b".to_string(),
+                None,
+                Some("Boolean".to_string())
+            )]
+        );
+    }
+
+    #[derive(Default)]
+    struct CaptureOutput {
+        written: Mutex<Vec<MessageBatch>>,
+    }
+
+    #[async_trait]
+    impl Output for CaptureOutput {
+        async fn connect(&self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        async fn write(&self, msg: MessageBatch) -> Result<(), Error> {
+            self.written.lock().unwrap().push(msg);
+            Ok(())
+        }
+
+        async fn close(&self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_alert_output() {
+        let (mut detector, _) = detector(1);
+        let output = Arc::new(CaptureOutput::default());
+        detector.alert_output = Some(output.clone());
+
+        detector
+            .process(batch(vec![("id", DataType::Int64)]))
+            .await
+            .unwrap();
+        detector
+            .process(batch(vec![
+                ("id", DataType::Int64),
+                ("name", DataType::Utf8),
+            ]))
+            .await
+            .unwrap();
+
+        let written = output.written.lock().unwrap();
+        assert_eq!(written.len(), 1);
+        assert_eq!(
+            rows(&written[0]),
+            vec![(
+                "field_added".to_string(),
+                "name".to_string(),
+                None,
+                Some("Utf8".to_string())
+            )]
+        );
+    }
+
+    #[test]
+    fn test_invalid_window_size() {
+        let config = SchemaEvolutionDetectorConfig {
+            window_size: 0,
+            alert_output: None,
+        };
+        assert!(SchemaEvolutionDetector::new(config, &resource()).is_err());
+    }
+}
@@ -0,0 +1,421 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Session Window Processor Component
+//!
+//! Accumulate the rows of every group until no row arrives for the group during the session
+//! gap, then emit the aggregates of the session. Closed sessions are detected by a background
+//! task and emitted with the result of the next processed batch.
+
+use crate::udf;
+use arkflow_core::processor::{register_processor_builder, Processor, ProcessorBuilder};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use datafusion::arrow;
+use datafusion::arrow::array::{Array, StringArray, UInt32Array};
+use datafusion::arrow::compute::{cast, take_record_batch};
+use datafusion::arrow::datatypes::DataType;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::prelude::SessionContext;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::error;
+
+/// Name of the table holding the rows of a session in the aggregate query
+const SESSION_TABLE_NAME: &str = "flow";
+/// Separates the values of the `group_by` columns in a group key
+const GROUP_KEY_SEPARATOR: char = '\u{1f}';
+
+/// Session window processor configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SessionWindowProcessorConfig {
+    /// Columns identifying the group of a row, all rows belong to the same group if empty
+    #[serde(default)]
+    group_by: Vec<String>,
+    /// Time without rows after which the session of a group is closed
+    session_gap_ms: u64,
+    /// Column holding the event time of the rows
+    timestamp_column: String,
+    /// Aggregates computed over the rows of a session
+    aggregate: AggregateConfig,
+}
+
+/// Aggregates computed over a set of rows
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AggregateConfig {
+    /// SQL aggregate expressions, e.g. `count(*) AS events` or `sum(amount) AS total`
+    expressions: Vec<String>,
+}
+
+/// Rows of an open session
+struct Session {
+    last_event_time: Instant,
+    rows: Vec<RecordBatch>,
+}
+
+struct SessionWindowProcessor {
+    config: SessionWindowProcessorConfig,
+    /// Open sessions by group key
+    sessions: Arc<Mutex<HashMap<String, Session>>>,
+    /// Closed sessions aggregated by the background task
+    closed: flume::Receiver<MessageBatch>,
+    closed_sender: flume::Sender<MessageBatch>,
+    /// Started with the first batch, so that the processor can be built outside a runtime
+    task: std::sync::Once,
+    cancellation_token: CancellationToken,
+}
+
+impl SessionWindowProcessor {
+    fn new(config: SessionWindowProcessorConfig) -> Result<Self, Error> {
+        if config.session_gap_ms == 0 {
+            return Err(Error::Config(
+                "Session window session_gap_ms must be positive".to_string(),
+            ));
+        }
+        if config.aggregate.expressions.is_empty() {
+            return Err(Error::Config(
+                "Session window requires at least one aggregate expression".to_string(),
+            ));
+        }
+        let (closed_sender, closed) = flume::unbounded();
+        Ok(Self {
+            config,
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            closed,
+            closed_sender,
+            task: std::sync::Once::new(),
+            cancellation_token: CancellationToken::new(),
+        })
+    }
+
+    /// Spawn the task closing the sessions of the groups without rows during the session gap
+    fn spawn_timeout_task(&self) {
+        let config = self.config.clone();
+        let sessions = self.sessions.clone();
+        let sender = self.closed_sender.clone();
+        let cancellation_token = self.cancellation_token.clone();
+        let gap = Duration::from_millis(config.session_gap_ms);
+        let check_interval = (gap / 4).clamp(Duration::from_millis(10), Duration::from_secs(1));
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = cancellation_token.cancelled() => break,
+                    _ = tokio::time::sleep(check_interval) => {}
+                }
+
+                let expired: Vec<Vec<RecordBatch>> = {
+                    let mut sessions = sessions.lock().unwrap();
+                    let keys: Vec<String> = sessions
+                        .iter()
+                        .filter(|(_, session)| session.last_event_time.elapsed() >= gap)
+                        .map(|(key, _)| key.clone())
+                        .collect();
+                    keys.iter()
+                        .filter_map(|key| sessions.remove(key))
+                        .map(|session| session.rows)
+                        .collect()
+                };
+                for rows in expired {
+                    match aggregate_session(&config, &rows).await {
+                        Ok(batch) => {
+                            if sender.send(MessageBatch::new_arrow(batch)).is_err() {
+                                return;
+                            }
+                        }
+                        Err(e) => error!("Failed to aggregate session: {}", e),
+                    }
+                }
+            }
+        });
+    }
+
+    /// Group key of every row of `batch`
+    fn group_keys(&self, batch: &RecordBatch) -> Result<Vec<String>, Error> {
+        let columns = self
+            .config
+            .group_by
+            .iter()
+            .map(|name| {
+                let column = batch
+                    .column_by_name(name)
+                    .ok_or_else(|| Error::Process(format!("Group column {} not found", name)))?;
+                let values = cast(column, &DataType::Utf8).map_err(|e| {
+                    Error::Process(format!("Failed to read group column {}: {}", name, e))
+                })?;
+                values
+                    .as_any()
+                    .downcast_ref::<StringArray>()
+                    .cloned()
+                    .ok_or_else(|| Error::Process(format!("Invalid group column {}", name)))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok((0..batch.num_rows())
+            .map(|row| {
+                let mut key = String::new();
+                for (i, column) in columns.iter().enumerate() {
+                    if i > 0 {
+                        key.push(GROUP_KEY_SEPARATOR);
+                    }
+                    if column.is_valid(row) {
+                        key.push_str(column.value(row));
+                    }
+                }
+                key
+            })
+            .collect())
+    }
+}
+
+/// Aggregate the rows of a session, one row per group with the `session_start` and
+/// `session_end` event times
+async fn aggregate_session(
+    config: &SessionWindowProcessorConfig,
+    rows: &[RecordBatch],
+) -> Result<RecordBatch, Error> {
+    let schema = rows
+        .first()
+        .map(|batch| batch.schema())
+        .ok_or_else(|| Error::Process("Empty session".to_string()))?;
+    let batch = arrow::compute::concat_batches(&schema, rows)
+        .map_err(|e| Error::Process(format!("Merge batches failed: {}", e)))?;
+
+    let mut ctx = SessionContext::new();
+    udf::init(&mut ctx)?;
+    ctx.register_batch(SESSION_TABLE_NAME, batch)
+        .map_err(|e| Error::Process(format!("Registration failed: {}", e)))?;
+
+    let group_by: Vec<String> = config
+        .group_by
+        .iter()
+        .map(|column| quote_identifier(column))
+        .collect();
+    let timestamp = quote_identifier(&config.timestamp_column);
+    let mut select = group_by.clone();
+    select.push(format!("min({}) AS session_start", timestamp));
+    select.push(format!("max({}) AS session_end", timestamp));
+    select.extend(config.aggregate.expressions.iter().cloned());
+    let mut query = format!("SELECT {} FROM {}", select.join(", "), SESSION_TABLE_NAME);
+    if !group_by.is_empty() {
+        query.push_str(&format!(" GROUP BY {}", group_by.join(", ")));
+    }
+
+    let batches = ctx
+        .sql(&query)
+        .await
+        .map_err(|e| Error::Process(format!("SQL query error: {}", e)))?
+        .collect()
+        .await
+        .map_err(|e| Error::Process(format!("Collection query results error: {}", e)))?;
+    let schema = batches
+        .first()
+        .map(|batch| batch.schema())
+        .ok_or_else(|| Error::Process("Empty aggregate result".to_string()))?;
+    arrow::compute::concat_batches(&schema, &batches)
+        .map_err(|e| Error::Process(format!("Merge batches failed: {}", e)))
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[async_trait]
+impl Processor for SessionWindowProcessor {
+    async fn process(&self, msg: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+        self.task.call_once(|| self.spawn_timeout_task());
+
+        let batch: RecordBatch = msg.into();
+        if batch
+            .column_by_name(&self.config.timestamp_column)
+            .is_none()
+        {
+            return Err(Error::Process(format!(
+                "Timestamp column {} not found",
+                self.config.timestamp_column
+            )));
+        }
+
+        let keys = self.group_keys(&batch)?;
+        let mut groups: HashMap<&str, Vec<u32>> = HashMap::new();
+        for (row, key) in keys.iter().enumerate() {
+            groups.entry(key).or_default().push(row as u32);
+        }
+        {
+            let now = Instant::now();
+            let mut sessions = self.sessions.lock().unwrap();
+            for (key, rows) in groups {
+                let rows = take_record_batch(&batch, &UInt32Array::from(rows))
+                    .map_err(|e| Error::Process(format!("Failed to group rows: {}", e)))?;
+                let session = sessions.entry(key.to_string()).or_insert_with(|| Session {
+                    last_event_time: now,
+                    rows: Vec::new(),
+                });
+                session.last_event_time = now;
+                session.rows.push(rows);
+            }
+        }
+
+        Ok(self.closed.try_iter().collect())
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        self.cancellation_token.cancel();
+        Ok(())
+    }
+}
+
+struct SessionWindowProcessorBuilder;
+impl ProcessorBuilder for SessionWindowProcessorBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Processor>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Session window processor configuration is missing".to_string(),
+            ));
+        }
+        let config: SessionWindowProcessorConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(SessionWindowProcessor::new(config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_processor_builder("session_window", Arc::new(SessionWindowProcessorBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{ArrayRef, Int64Array};
+
+    fn processor(group_by: Vec<&str>) -> SessionWindowProcessor {
+        SessionWindowProcessor::new(SessionWindowProcessorConfig {
+            group_by: group_by.into_iter().map(str::to_string).collect(),
+            session_gap_ms: 50,
+            timestamp_column: "ts".to_string(),
+            aggregate: AggregateConfig {
+                expressions: vec![
+                    "count(*) AS events".to_string(),
+                    "sum(amount) AS total".to_string(),
+                ],
+            },
+        })
+        .unwrap()
+    }
+
+    fn batch(users: Vec<&str>, ts: Vec<i64>, amounts: Vec<i64>) -> MessageBatch {
+        MessageBatch::new_arrow(
+            RecordBatch::try_from_iter([
+                ("user", Arc::new(StringArray::from(users)) as ArrayRef),
+                ("ts", Arc::new(Int64Array::from(ts))),
+                ("amount", Arc::new(Int64Array::from(amounts))),
+            ])
+            .unwrap(),
+        )
+    }
+
+    /// Rows of the closed sessions as (user, session_start, session_end, events, total)
+    fn sessions(batches: Vec<MessageBatch>) -> Vec<(String, i64, i64, i64, i64)> {
+        let mut rows = Vec::new();
+        for batch in batches {
+            let column = |name: &str| {
+                cast(batch.column_by_name(name).unwrap(), &DataType::Int64)
+                    .unwrap()
+                    .as_any()
+                    .downcast_ref::<Int64Array>()
+                    .unwrap()
+                    .clone()
+            };
+            let users = batch
+                .column_by_name("user")
+                .unwrap()
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap()
+                .clone();
+            let (start, end, events, total) = (
+                column("session_start"),
+                column("session_end"),
+                column("events"),
+                column("total"),
+            );
+            for i in 0..batch.num_rows() {
+                rows.push((
+                    users.value(i).to_string(),
+                    start.value(i),
+                    end.value(i),
+                    events.value(i),
+                    total.value(i),
+                ));
+            }
+        }
+        rows.sort();
+        rows
+    }
+
+    #[tokio::test]
+    async fn test_session_window() {
+        let processor = processor(vec!["user"]);
+        let result = processor
+            .process(batch(vec!["a", "b", "a"], vec![1, 2, 3], vec![10, 20, 30]))
+            .await
+            .unwrap();
+        assert!(result.is_empty());
+        let result = processor
+            .process(batch(vec!["a"], vec![4], vec![40]))
+            .await
+            .unwrap();
+        assert!(result.is_empty());
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let result = processor
+            .process(batch(vec!["c"], vec![5], vec![50]))
+            .await
+            .unwrap();
+        assert_eq!(
+            sessions(result),
+            vec![
+                ("a".to_string(), 1, 4, 3, 80),
+                ("b".to_string(), 2, 2, 1, 20),
+            ]
+        );
+        processor.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_session_window_errors() {
+        assert!(SessionWindowProcessor::new(SessionWindowProcessorConfig {
+            group_by: vec![],
+            session_gap_ms: 0,
+            timestamp_column: "ts".to_string(),
+            aggregate: AggregateConfig {
+                expressions: vec!["count(*)".to_string()],
+            },
+        })
+        .is_err());
+
+        let processor = processor(vec!["missing"]);
+        assert!(processor
+            .process(batch(vec!["a"], vec![1], vec![1]))
+            .await
+            .is_err());
+        processor.close().await.unwrap();
+    }
+}
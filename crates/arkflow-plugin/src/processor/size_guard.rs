@@ -0,0 +1,272 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Size Guard Processor Component
+//!
+//! Reject, truncate or drop messages that exceed a size limit before they are buffered
+
+use arkflow_core::processor::{register_processor_builder, Processor, ProcessorBuilder};
+use arkflow_core::{Error, MessageBatch, Resource, DEFAULT_BINARY_VALUE_FIELD};
+use async_trait::async_trait;
+use datafusion::arrow::array::{Array, ArrayRef, BinaryArray, BooleanArray};
+use datafusion::arrow::compute::filter_record_batch;
+use datafusion::arrow::record_batch::RecordBatch;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Size guard processor configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SizeGuardProcessorConfig {
+    /// Maximum size of a single message in bytes
+    max_bytes_per_message: usize,
+    /// Action for messages exceeding the limit
+    #[serde(default)]
+    on_exceed: OnExceed,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum OnExceed {
+    /// Fail the whole batch
+    #[default]
+    Error,
+    /// Cut binary messages to the limit; oversized Arrow rows are dropped
+    Truncate,
+    /// Remove oversized messages from the batch
+    Drop,
+}
+
+struct SizeGuardProcessor {
+    config: SizeGuardProcessorConfig,
+}
+
+impl SizeGuardProcessor {
+    /// Apply the limit to the binary payload of each message
+    fn guard_binary(
+        &self,
+        batch: &RecordBatch,
+        values: &BinaryArray,
+    ) -> Result<RecordBatch, Error> {
+        let max = self.config.max_bytes_per_message;
+        match self.config.on_exceed {
+            OnExceed::Error => {
+                if let Some(size) = values
+                    .iter()
+                    .flatten()
+                    .map(<[u8]>::len)
+                    .find(|&len| len > max)
+                {
+                    return Err(Error::Process(format!(
+                        "Message size {} bytes exceeds the limit of {} bytes",
+                        size, max
+                    )));
+                }
+                Ok(batch.clone())
+            }
+            OnExceed::Truncate => {
+                let truncated: BinaryArray = values
+                    .iter()
+                    .map(|value| value.map(|v| &v[..v.len().min(max)]))
+                    .collect();
+                let index = batch
+                    .schema()
+                    .index_of(DEFAULT_BINARY_VALUE_FIELD)
+                    .map_err(|e| Error::Process(e.to_string()))?;
+                let mut columns: Vec<ArrayRef> = batch.columns().to_vec();
+                columns[index] = Arc::new(truncated);
+                RecordBatch::try_new(batch.schema(), columns).map_err(|e| {
+                    Error::Process(format!("Creating an Arrow record batch failed: {}", e))
+                })
+            }
+            OnExceed::Drop => {
+                let keep: BooleanArray = values
+                    .iter()
+                    .map(|value| Some(value.is_none_or(|v| v.len() <= max)))
+                    .collect();
+                filter_record_batch(batch, &keep)
+                    .map_err(|e| Error::Process(format!("Failed to filter messages: {}", e)))
+            }
+        }
+    }
+
+    /// Apply the limit to the estimated size of each Arrow row
+    fn guard_arrow(&self, batch: &RecordBatch) -> Result<RecordBatch, Error> {
+        let max = self.config.max_bytes_per_message;
+        let row_size = batch.get_array_memory_size() / batch.num_rows();
+        if row_size <= max {
+            return Ok(batch.clone());
+        }
+        match self.config.on_exceed {
+            OnExceed::Error => Err(Error::Process(format!(
+                "Estimated row size {} bytes exceeds the limit of {} bytes",
+                row_size, max
+            ))),
+            // Arrow rows cannot be truncated, and all rows share the same estimate
+            OnExceed::Truncate | OnExceed::Drop => Ok(batch.slice(0, 0)),
+        }
+    }
+}
+
+#[async_trait]
+impl Processor for SizeGuardProcessor {
+    async fn process(&self, msg_batch: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+        if msg_batch.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let binary = msg_batch
+            .column_by_name(DEFAULT_BINARY_VALUE_FIELD)
+            .and_then(|column| column.as_any().downcast_ref::<BinaryArray>());
+        let batch = match binary {
+            Some(values) => self.guard_binary(&msg_batch, values)?,
+            None => self.guard_arrow(&msg_batch)?,
+        };
+        if batch.num_rows() == 0 {
+            return Ok(vec![]);
+        }
+
+        let mut result = MessageBatch::new_arrow(batch);
+        result.set_input_name(msg_batch.get_input_name());
+        Ok(vec![result])
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+struct SizeGuardProcessorBuilder;
+impl ProcessorBuilder for SizeGuardProcessorBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Processor>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Size guard processor configuration is missing".to_string(),
+            ));
+        }
+        let config: SizeGuardProcessorConfig = serde_json::from_value(config.clone().unwrap())?;
+
+        Ok(Arc::new(SizeGuardProcessor { config }))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_processor_builder("size_guard", Arc::new(SizeGuardProcessorBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Int64Array, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use serde_json::json;
+    use std::cell::RefCell;
+
+    fn build(config: serde_json::Value) -> Arc<dyn Processor> {
+        SizeGuardProcessorBuilder
+            .build(
+                None,
+                &Some(config),
+                &Resource {
+                    temporary: Default::default(),
+                    input_names: RefCell::new(Default::default()),
+                },
+            )
+            .unwrap()
+    }
+
+    fn messages() -> MessageBatch {
+        MessageBatch::new_binary(vec![b"short".to_vec(), b"much too long".to_vec()]).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_error() {
+        let processor = build(json!({"max_bytes_per_message": 8}));
+        let err = processor.process(messages()).await.unwrap_err();
+        assert!(err.to_string().contains("13 bytes"), "{}", err);
+
+        let msg = MessageBatch::new_binary(vec![b"short".to_vec()]).unwrap();
+        assert_eq!(processor.process(msg).await.unwrap()[0].len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_truncate() {
+        let processor = build(json!({"max_bytes_per_message": 8, "on_exceed": "truncate"}));
+        let result = processor.process(messages()).await.unwrap();
+        assert_eq!(
+            result[0].to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap(),
+            vec![b"short".as_slice(), b"much too".as_slice()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_drop() {
+        let processor = build(json!({"max_bytes_per_message": 8, "on_exceed": "drop"}));
+        let mut msg = messages();
+        msg.set_input_name(Some("input".to_string()));
+        let result = processor.process(msg).await.unwrap();
+        assert_eq!(
+            result[0].to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap(),
+            vec![b"short".as_slice()]
+        );
+        assert_eq!(result[0].get_input_name(), Some("input".to_string()));
+
+        let msg = MessageBatch::new_binary(vec![b"much too long".to_vec()]).unwrap();
+        assert!(processor.process(msg).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_arrow_rows() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec!["a", "b"])),
+            ],
+        )
+        .unwrap();
+        let row_size = batch.get_array_memory_size() / 2;
+
+        let processor = build(json!({"max_bytes_per_message": row_size}));
+        let result = processor
+            .process(MessageBatch::new_arrow(batch.clone()))
+            .await
+            .unwrap();
+        assert_eq!(result[0].len(), 2);
+
+        let processor = build(json!({"max_bytes_per_message": row_size - 1}));
+        assert!(processor
+            .process(MessageBatch::new_arrow(batch.clone()))
+            .await
+            .is_err());
+
+        let processor = build(json!({
+            "max_bytes_per_message": row_size - 1,
+            "on_exceed": "truncate",
+        }));
+        assert!(processor
+            .process(MessageBatch::new_arrow(batch))
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
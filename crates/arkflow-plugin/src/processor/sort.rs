@@ -0,0 +1,302 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Sort Processor Component
+//!
+//! Sort the rows of a message batch without going through SQL
+
+use arkflow_core::processor::Processor;
+use arkflow_core::{Error, MessageBatch, DEFAULT_BINARY_VALUE_FIELD};
+use arkflow_macros::arkflow_processor;
+use async_trait::async_trait;
+use datafusion::arrow::array::{Array, BinaryArray, UInt32Array};
+use datafusion::arrow::compute::{lexsort_to_indices, take_record_batch, SortColumn, SortOptions};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
+
+/// Sort processor configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SortProcessorConfig {
+    /// Keys to sort by, in order of precedence
+    sort_keys: Vec<SortKey>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SortKey {
+    /// Column name, or top-level JSON field for binary messages
+    column: String,
+    #[serde(default)]
+    descending: bool,
+    #[serde(default)]
+    nulls_first: bool,
+}
+
+impl SortKey {
+    fn options(&self) -> SortOptions {
+        SortOptions {
+            descending: self.descending,
+            nulls_first: self.nulls_first,
+        }
+    }
+}
+
+#[arkflow_processor(name = "sort")]
+#[derive(Deserialize)]
+#[serde(try_from = "SortProcessorConfig")]
+struct SortProcessor {
+    config: SortProcessorConfig,
+}
+
+impl TryFrom<SortProcessorConfig> for SortProcessor {
+    type Error = Error;
+
+    fn try_from(config: SortProcessorConfig) -> Result<Self, Error> {
+        if config.sort_keys.is_empty() {
+            return Err(Error::Config(
+                "Sort processor requires at least one sort key".to_string(),
+            ));
+        }
+        Ok(Self { config })
+    }
+}
+
+impl SortProcessor {
+    /// Sorted row order of an Arrow batch
+    fn sort_arrow(&self, msg: &MessageBatch) -> Result<UInt32Array, Error> {
+        let columns = self
+            .config
+            .sort_keys
+            .iter()
+            .map(|key| {
+                let values = msg.column_by_name(&key.column).cloned().ok_or_else(|| {
+                    Error::Process(format!("Column {} not found in message batch", key.column))
+                })?;
+                Ok(SortColumn {
+                    values,
+                    options: Some(key.options()),
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        lexsort_to_indices(&columns, None)
+            .map_err(|e| Error::Process(format!("Failed to sort message batch: {}", e)))
+    }
+
+    /// Sorted row order of binary messages, comparing top-level fields of their JSON payloads
+    fn sort_binary(&self, values: &BinaryArray) -> Result<UInt32Array, Error> {
+        let documents = values
+            .iter()
+            .map(|value| match value {
+                Some(value) => serde_json::from_slice::<Value>(value)
+                    .map_err(|e| Error::Process(format!("Failed to parse message as JSON: {}", e))),
+                None => Ok(Value::Null),
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let mut indices: Vec<u32> = (0..documents.len() as u32).collect();
+        indices.sort_by(|&a, &b| {
+            let (a, b) = (&documents[a as usize], &documents[b as usize]);
+            self.config
+                .sort_keys
+                .iter()
+                .map(|key| compare_fields(a.get(&key.column), b.get(&key.column), &key.options()))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+        Ok(UInt32Array::from(indices))
+    }
+}
+
+/// Compare two JSON values under the given sort options.
+///
+/// Missing fields sort like nulls. Values of different types are ordered by type, with
+/// booleans before numbers before strings before arrays and objects.
+fn compare_fields(a: Option<&Value>, b: Option<&Value>, options: &SortOptions) -> Ordering {
+    let a = a.filter(|v| !v.is_null());
+    let b = b.filter(|v| !v.is_null());
+    let (a, b) = match (a, b) {
+        (None, None) => return Ordering::Equal,
+        (None, Some(_)) if options.nulls_first => return Ordering::Less,
+        (None, Some(_)) => return Ordering::Greater,
+        (Some(_), None) if options.nulls_first => return Ordering::Greater,
+        (Some(_), None) => return Ordering::Less,
+        (Some(a), Some(b)) => (a, b),
+    };
+
+    let ordering = match (a, b) {
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        _ => type_rank(a).cmp(&type_rank(b)),
+    };
+    if options.descending {
+        ordering.reverse()
+    } else {
+        ordering
+    }
+}
+
+fn type_rank(value: &Value) -> u8 {
+    match value {
+        Value::Null => 0,
+        Value::Bool(_) => 1,
+        Value::Number(_) => 2,
+        Value::String(_) => 3,
+        Value::Array(_) => 4,
+        Value::Object(_) => 5,
+    }
+}
+
+#[async_trait]
+impl Processor for SortProcessor {
+    async fn process(&self, msg_batch: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+        if msg_batch.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let binary = msg_batch
+            .column_by_name(DEFAULT_BINARY_VALUE_FIELD)
+            .and_then(|column| column.as_any().downcast_ref::<BinaryArray>());
+        let indices = match binary {
+            Some(values) => self.sort_binary(values)?,
+            None => self.sort_arrow(&msg_batch)?,
+        };
+
+        let batch = take_record_batch(&msg_batch, &indices)
+            .map_err(|e| Error::Process(format!("Failed to sort message batch: {}", e)))?;
+        let mut result = MessageBatch::new_arrow(batch);
+        result.set_input_name(msg_batch.get_input_name());
+        Ok(vec![result])
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arkflow_core::processor::ProcessorBuilder;
+    use arkflow_core::Resource;
+    use datafusion::arrow::array::{Int64Array, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use serde_json::json;
+    use std::cell::RefCell;
+    use std::sync::Arc;
+
+    fn build(config: Value) -> Result<Arc<dyn Processor>, Error> {
+        SortProcessorBuilder.build(
+            None,
+            &Some(config),
+            &Resource {
+                temporary: Default::default(),
+                input_names: RefCell::new(Default::default()),
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn test_sort_arrow() {
+        let processor = build(json!({
+            "sort_keys": [
+                {"column": "device"},
+                {"column": "ts", "descending": true, "nulls_first": true},
+            ]
+        }))
+        .unwrap();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("device", DataType::Utf8, false),
+            Field::new("ts", DataType::Int64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["b", "a", "a", "b", "a"])),
+                Arc::new(Int64Array::from(vec![
+                    Some(1),
+                    Some(2),
+                    None,
+                    Some(3),
+                    Some(5),
+                ])),
+            ],
+        )
+        .unwrap();
+
+        let result = processor
+            .process(MessageBatch::new_arrow(batch))
+            .await
+            .unwrap();
+        let expected = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["a", "a", "a", "b", "b"])),
+                Arc::new(Int64Array::from(vec![
+                    None,
+                    Some(5),
+                    Some(2),
+                    Some(3),
+                    Some(1),
+                ])),
+            ],
+        )
+        .unwrap();
+        assert_eq!(*result[0], expected);
+    }
+
+    #[tokio::test]
+    async fn test_sort_binary() {
+        let processor = build(json!({"sort_keys": [{"column": "ts"}]})).unwrap();
+        let msg = MessageBatch::new_binary(vec![
+            br#"{"ts": 3, "v": "c"}"#.to_vec(),
+            br#"{"v": "missing"}"#.to_vec(),
+            br#"{"ts": 1.5, "v": "a"}"#.to_vec(),
+            br#"{"ts": 2, "v": "b"}"#.to_vec(),
+        ])
+        .unwrap();
+
+        let result = processor.process(msg).await.unwrap();
+        let values: Vec<Value> = result[0]
+            .to_binary(DEFAULT_BINARY_VALUE_FIELD)
+            .unwrap()
+            .into_iter()
+            .map(|v| serde_json::from_slice(v).unwrap())
+            .collect();
+        let order: Vec<&str> = values.iter().map(|v| v["v"].as_str().unwrap()).collect();
+        assert_eq!(order, vec!["a", "b", "c", "missing"]);
+    }
+
+    #[tokio::test]
+    async fn test_sort_errors() {
+        assert!(matches!(
+            build(json!({"sort_keys": []})),
+            Err(Error::Config(_))
+        ));
+
+        let processor = build(json!({"sort_keys": [{"column": "missing"}]})).unwrap();
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(Int64Array::from(vec![2, 1]))]).unwrap();
+        assert!(processor
+            .process(MessageBatch::new_arrow(batch))
+            .await
+            .is_err());
+    }
+}
@@ -16,15 +16,26 @@
 //!
 //! DataFusion is used to process data with SQL queries.
 
+use crate::state::StateStoreConfig;
 use crate::{expr, udf};
-use arkflow_core::processor::{register_processor_builder, Processor, ProcessorBuilder};
+use arkflow_core::pipeline::watermark::{timestamp_millis, watermark_of};
+use arkflow_core::processor::{
+    register_processor_builder, Processor, ProcessorBuilder, StateStore,
+};
 use arkflow_core::temporary::Temporary;
 use arkflow_core::{Error, MessageBatch, Resource};
 use async_trait::async_trait;
 use datafusion::arrow;
-use datafusion::arrow::datatypes::Schema;
+use datafusion::arrow::array::{BooleanArray, TimestampMillisecondArray};
+use datafusion::arrow::compute::filter_record_batch;
+use datafusion::arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use datafusion::arrow::ipc::reader::StreamReader;
+use datafusion::arrow::ipc::writer::StreamWriter;
 use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::catalog::{MemoryCatalogProviderList, TableProvider};
 use datafusion::common::DataFusionError;
+use datafusion::datasource::MemTable;
+use datafusion::execution::SessionStateBuilder;
 use datafusion::logical_expr::ColumnarValue;
 use datafusion::optimizer::OptimizerConfig;
 use datafusion::prelude::*;
@@ -32,20 +43,114 @@ use datafusion::scalar::ScalarValue;
 use datafusion::sql::parser::Statement;
 use expr::Expr;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync::Arc;
+use tokio::sync::{Mutex, OnceCell};
+use tracing::warn;
 
 const DEFAULT_TABLE_NAME: &str = "flow";
+const DEFAULT_STATE_TABLE_NAME: &str = "state";
+const DEFAULT_STATE_MAX_ROWS: usize = 10_000;
+const DEFAULT_ALLOWED_LATENESS_MS: u64 = 60_000;
 /// SQL processor configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct SqlProcessorConfig {
     /// SQL query statement
+    #[serde(default)]
     query: String,
 
+    /// Query with `?` placeholders, run once per row of the batch instead of `query`.
+    /// The batch itself is not registered as a table.
+    parameterized_query: Option<String>,
+
+    /// Columns of the batch bound to the placeholders of `parameterized_query`, in order
+    #[serde(default)]
+    params: Vec<String>,
+
     /// Table name (used in SQL queries)
     table_name: Option<String>,
 
     temporary_list: Option<Vec<TemporaryConfig>>,
+
+    /// Persisted state table carried across batches and restarts
+    state: Option<SqlStateConfig>,
+
+    /// Tables available to the query besides the current batch
+    #[serde(default)]
+    tables: Vec<SqlTableConfig>,
+
+    /// Tumbling event-time windows the query is run on, each one once the pipeline watermark
+    /// passes its end
+    window: Option<SqlWindowConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SqlWindowConfig {
+    /// Column holding the event time of the rows
+    timestamp_column: String,
+    /// Length of the windows
+    size_ms: u64,
+    /// How rows arriving for a window that was already emitted are handled
+    #[serde(default)]
+    late_data_policy: LateDataPolicy,
+    /// How long after closing a window its rows are kept to be updated by late rows, with the
+    /// `update_window` policy
+    #[serde(default = "default_allowed_lateness_ms")]
+    allowed_lateness_ms: u64,
+}
+
+fn default_allowed_lateness_ms() -> u64 {
+    DEFAULT_ALLOWED_LATENESS_MS
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum LateDataPolicy {
+    /// Drop the late rows with a warning
+    #[default]
+    Drop,
+    /// Run the query on the late rows of each window alone, flagged with a `_window_late` column
+    EmitSeparateBatch,
+    /// Run the query again on all the rows of the window, flagged with a `_window_corrected`
+    /// column
+    UpdateWindow,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SqlTableConfig {
+    /// Table name (used in SQL queries)
+    name: String,
+    source: TableSource,
+}
+
+/// Source of the rows of a table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum TableSource {
+    /// CSV file read once
+    StaticCsv { path: String },
+    /// Parquet file read once
+    StaticParquet { path: String },
+    /// Newline-delimited JSON file read once
+    StaticJson { path: String },
+    /// The batch being processed, in addition to `table_name`
+    CurrentBatch,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SqlStateConfig {
+    /// Table name of the retained rows (used in SQL queries)
+    table_name: Option<String>,
+    /// Maximum number of retained rows, the oldest rows are dropped first. All retained rows are
+    /// rewritten to the store after every batch, so this bounds the cost of each write.
+    #[serde(default = "default_state_max_rows")]
+    max_rows: usize,
+    /// State store the retained rows are persisted to
+    store: StateStoreConfig,
+}
+
+fn default_state_max_rows() -> usize {
+    DEFAULT_STATE_MAX_ROWS
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -65,12 +170,44 @@ struct BallistaConfig {
 struct SqlProcessor {
     config: SqlProcessorConfig,
     statement: Statement,
+    /// Context holding the functions, the base of the context of every query
+    ctx: SessionContext,
+    /// Static tables, loaded into memory on the first batch
+    static_tables: OnceCell<Vec<(String, Arc<dyn TableProvider>)>>,
     temporary: Option<HashMap<String, (Arc<dyn Temporary>, TemporaryConfig)>>,
+    state_store: Option<Arc<dyn StateStore>>,
+    /// Serializes the read-modify-write of the state table
+    state_lock: Mutex<()>,
+    /// Rows of the windows not closed yet
+    window_state: Mutex<WindowState>,
+}
+
+#[derive(Default)]
+struct WindowState {
+    /// Buffered rows
+    rows: Vec<RecordBatch>,
+    /// Rows of the closed windows, by window start, kept to be updated by late rows
+    closed: BTreeMap<i64, RecordBatch>,
+    /// Watermark the windows were last emitted at, windows ending before it are closed
+    watermark: Option<i64>,
 }
 
 impl SqlProcessor {
     /// Create a new SQL processor component.
     pub fn new(config: SqlProcessorConfig, resource: &Resource) -> Result<Self, Error> {
+        let state_store = match &config.state {
+            Some(state) => Some(state.store.build()?),
+            None => None,
+        };
+        Self::with_state_store(config, resource, state_store)
+    }
+
+    /// Create a new SQL processor component persisting its state to `state_store`.
+    fn with_state_store(
+        config: SqlProcessorConfig,
+        resource: &Resource,
+        state_store: Option<Arc<dyn StateStore>>,
+    ) -> Result<Self, Error> {
         let temporary = {
             if let Some(temporary_list) = config.temporary_list.as_ref() {
                 let mut temporary_map = HashMap::with_capacity(temporary_list.len());
@@ -90,25 +227,189 @@ impl SqlProcessor {
             }
         };
 
-        let ctx = SessionContext::new();
+        let query = match &config.parameterized_query {
+            Some(query) => {
+                if config.state.is_some() {
+                    return Err(Error::Config(
+                        "The state table cannot be used with a parameterized query".to_string(),
+                    ));
+                }
+                let (query, placeholders) = number_placeholders(query);
+                if placeholders != config.params.len() {
+                    return Err(Error::Config(format!(
+                        "Parameterized query has {} placeholders but {} params are configured",
+                        placeholders,
+                        config.params.len()
+                    )));
+                }
+                query
+            }
+            None => config.query.clone(),
+        };
+
+        if let Some(window) = &config.window {
+            if window.size_ms == 0 || i64::try_from(window.size_ms).is_err() {
+                return Err(Error::Config(format!(
+                    "Invalid window size: {}ms",
+                    window.size_ms
+                )));
+            }
+            if config.parameterized_query.is_some() {
+                return Err(Error::Config(
+                    "Windows cannot be used with a parameterized query".to_string(),
+                ));
+            }
+        }
+
+        let mut ctx = SessionContext::new();
+        udf::init(&mut ctx)?;
+        udf::table_udf::init(&ctx)?;
+        datafusion_functions_json::register_all(&mut ctx)
+            .map_err(|e| Error::Process(format!("Registration JSON function failed: {}", e)))?;
         let statement = ctx
             .state()
-            .sql_to_statement(
-                &config.query,
-                ctx.state().options().sql_parser.dialect.as_str(),
-            )
+            .sql_to_statement(&query, ctx.state().options().sql_parser.dialect.as_str())
             .map_err(|e| Error::Process(format!("SQL query error: {}", e)))?;
         Ok(Self {
             config,
             statement,
+            ctx,
+            static_tables: OnceCell::new(),
             temporary,
+            state_store,
+            state_lock: Mutex::new(()),
+            window_state: Mutex::new(WindowState::default()),
         })
     }
 
+    /// Buffer the rows of `batch` until the watermark closes their window. The rows of closed
+    /// windows are handled according to the late data policy.
+    async fn buffer_window_rows(
+        &self,
+        window: &SqlWindowConfig,
+        batch: RecordBatch,
+    ) -> Result<Vec<MessageBatch>, Error> {
+        let size = window.size_ms as i64;
+        let windows = split_windows(&batch, &window.timestamp_column, size)?;
+        let missing =
+            batch.num_rows() - windows.values().map(|rows| rows.num_rows()).sum::<usize>();
+        if missing > 0 {
+            warn!("Dropping {} rows without a timestamp", missing);
+        }
+
+        let mut state = self.window_state.lock().await;
+        let mut results = vec![];
+        for (start, rows) in windows {
+            let end = start + size;
+            let Some(watermark) = state.watermark.filter(|watermark| end <= *watermark) else {
+                state.rows.push(rows);
+                continue;
+            };
+            match window.late_data_policy {
+                LateDataPolicy::Drop => {
+                    warn!(
+                        "Dropping {} rows of the closed window [{}, {})",
+                        rows.num_rows(),
+                        start,
+                        end
+                    );
+                }
+                LateDataPolicy::EmitSeparateBatch => {
+                    let result = self.execute_window_query(&rows, start, end).await?;
+                    results.push(MessageBatch::new_arrow(with_flag_column(
+                        &result,
+                        "_window_late",
+                    )?));
+                }
+                LateDataPolicy::UpdateWindow => {
+                    if end + window.allowed_lateness_ms as i64 <= watermark {
+                        warn!(
+                            "Dropping {} rows of the window [{}, {}), closed for longer than the allowed lateness",
+                            rows.num_rows(),
+                            start,
+                            end
+                        );
+                        continue;
+                    }
+                    let rows = match state.closed.remove(&start) {
+                        Some(closed) => combine_batches(&[closed, rows])?,
+                        None => rows,
+                    };
+                    let result = self.execute_window_query(&rows, start, end).await?;
+                    state.closed.insert(start, rows);
+                    results.push(MessageBatch::new_arrow(with_flag_column(
+                        &result,
+                        "_window_corrected",
+                    )?));
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    /// Run the query on every window ending at or before `watermark`, oldest first. The rows
+    /// of the window are passed with its `window_start` and `window_end` timestamps.
+    async fn apply_window(
+        &self,
+        window: &SqlWindowConfig,
+        watermark: i64,
+    ) -> Result<Vec<MessageBatch>, Error> {
+        let mut state = self.window_state.lock().await;
+        if state
+            .watermark
+            .is_some_and(|previous| previous >= watermark)
+        {
+            return Ok(vec![]);
+        }
+        state.watermark = Some(watermark);
+
+        let size = window.size_ms as i64;
+        let mut results = vec![];
+        if !state.rows.is_empty() {
+            let rows = combine_batches(&state.rows)?;
+            let mut pending = vec![];
+            for (start, rows) in split_windows(&rows, &window.timestamp_column, size)? {
+                if start + size > watermark {
+                    pending.push(rows);
+                    continue;
+                }
+                let result = self
+                    .execute_window_query(&rows, start, start + size)
+                    .await?;
+                results.push(MessageBatch::new_arrow(result));
+                if window.late_data_policy == LateDataPolicy::UpdateWindow {
+                    state.closed.insert(start, rows);
+                }
+            }
+            state.rows = pending;
+        }
+
+        let lateness = window.allowed_lateness_ms as i64;
+        state
+            .closed
+            .retain(|start, _| start + size + lateness > watermark);
+        Ok(results)
+    }
+
+    /// Run the query on the rows of the window from `start` to `end`
+    async fn execute_window_query(
+        &self,
+        rows: &RecordBatch,
+        start: i64,
+        end: i64,
+    ) -> Result<RecordBatch, Error> {
+        let batch = with_window_columns(rows, start, end)?;
+        self.execute_query(MessageBatch::new_arrow(batch)).await
+    }
+
     /// Execute SQL query
     async fn execute_query(&self, batch: MessageBatch) -> Result<RecordBatch, Error> {
         // Create a session context
         let ctx = self.create_session_context().await?;
+        for (name, table) in self.static_tables().await? {
+            ctx.register_table(name.as_str(), Arc::clone(table))
+                .map_err(|e| Error::Process(format!("Registration failed: {}", e)))?;
+        }
 
         let table_name = self
             .config
@@ -116,8 +417,35 @@ impl SqlProcessor {
             .as_deref()
             .unwrap_or(DEFAULT_TABLE_NAME);
         self.get_temporary_message_batch(&ctx, &batch).await?;
-        ctx.register_batch(table_name, batch.into())
+        let batch: RecordBatch = batch.into();
+        if self.config.parameterized_query.is_some() {
+            return self.execute_parameterized_query(ctx, &batch).await;
+        }
+
+        // Hold the lock until the new state is stored so that concurrent batches are not lost.
+        let state = match (&self.config.state, &self.state_store) {
+            (Some(state_config), Some(store)) => {
+                let guard = self.state_lock.lock().await;
+                let previous = self.load_state(store.as_ref(), &batch)?;
+                let state_table_name = state_config
+                    .table_name
+                    .as_deref()
+                    .unwrap_or(DEFAULT_STATE_TABLE_NAME);
+                ctx.register_batch(state_table_name, previous.clone())
+                    .map_err(|e| Error::Process(format!("Registration failed: {}", e)))?;
+                Some((guard, store, state_config, previous))
+            }
+            _ => None,
+        };
+
+        ctx.register_batch(table_name, batch.clone())
             .map_err(|e| Error::Process(format!("Registration failed: {}", e)))?;
+        for table in &self.config.tables {
+            if let TableSource::CurrentBatch = table.source {
+                ctx.register_batch(&table.name, batch.clone())
+                    .map_err(|e| Error::Process(format!("Registration failed: {}", e)))?;
+            }
+        }
         // Execute the SQL query and collect the results.
         let df = self
             .execute_query_with_statement(ctx)
@@ -128,18 +456,144 @@ impl SqlProcessor {
             .await
             .map_err(|e| Error::Process(format!("Collection query results error: {}", e)))?;
 
-        if result_batches.is_empty() {
-            return Ok(RecordBatch::new_empty(Arc::new(Schema::empty())));
+        if let Some((_guard, store, state_config, previous)) = state {
+            self.store_state(store.as_ref(), state_config, &previous, &batch)?;
+        }
+
+        merge_results(result_batches)
+    }
+
+    /// Run the parameterized query once per row of `batch`, binding the `params` columns of
+    /// the row to its placeholders
+    async fn execute_parameterized_query(
+        &self,
+        ctx: SessionContext,
+        batch: &RecordBatch,
+    ) -> Result<RecordBatch, Error> {
+        let columns = self
+            .config
+            .params
+            .iter()
+            .map(|name| {
+                batch.column_by_name(name).ok_or_else(|| {
+                    Error::Process(format!("Parameter column {} not found in the batch", name))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let plan = ctx
+            .state()
+            .statement_to_plan(self.statement.clone())
+            .await
+            .map_err(|e| Error::Process(format!("SQL query error: {}", e)))?;
+        SQLOptions::new()
+            .with_allow_ddl(false)
+            .with_allow_dml(false)
+            .with_allow_statements(false)
+            .verify_plan(&plan)
+            .map_err(|e| Error::Process(format!("SQL query error: {}", e)))?;
+        let param_types = plan
+            .get_parameter_types()
+            .map_err(|e| Error::Process(format!("SQL query error: {}", e)))?;
+
+        let mut result_batches = Vec::new();
+        for row in 0..batch.num_rows() {
+            let mut values = Vec::with_capacity(columns.len());
+            for (i, column) in columns.iter().enumerate() {
+                let value = ScalarValue::try_from_array(column, row)
+                    .map_err(|e| Error::Process(format!("Failed to read parameter: {}", e)))?;
+                // Bind values with the type inferred for the placeholder when there is one
+                let value = match param_types.get(&format!("${}", i + 1)) {
+                    Some(Some(data_type)) if *data_type != value.data_type() => {
+                        value.cast_to(data_type).map_err(|e| {
+                            Error::Process(format!("Failed to convert parameter: {}", e))
+                        })?
+                    }
+                    _ => value,
+                };
+                values.push(value);
+            }
+
+            let plan = plan
+                .clone()
+                .with_param_values(values)
+                .map_err(|e| Error::Process(format!("Failed to bind parameters: {}", e)))?;
+            let batches = ctx
+                .execute_logical_plan(plan)
+                .await
+                .map_err(|e| Error::Process(format!("Execution query error: {}", e)))?
+                .collect()
+                .await
+                .map_err(|e| Error::Process(format!("Collection query results error: {}", e)))?;
+            result_batches.extend(batches);
         }
 
-        if result_batches.len() == 1 {
-            return Ok(result_batches[0].clone());
+        merge_results(result_batches)
+    }
+
+    fn state_key(&self) -> Vec<u8> {
+        let table_name = self
+            .config
+            .table_name
+            .as_deref()
+            .unwrap_or(DEFAULT_TABLE_NAME);
+        format!("sql/{}", table_name).into_bytes()
+    }
+
+    /// Load the retained rows, or an empty table with the schema of `batch` if there are none
+    fn load_state(
+        &self,
+        store: &dyn StateStore,
+        batch: &RecordBatch,
+    ) -> Result<RecordBatch, Error> {
+        let empty = || RecordBatch::new_empty(batch.schema());
+        let Some(data) = store.get(&self.state_key())? else {
+            return Ok(empty());
+        };
+
+        let reader = StreamReader::try_new(data.as_slice(), None)
+            .map_err(|e| Error::Process(format!("Failed to read SQL state: {}", e)))?;
+        let schema = reader.schema();
+        let batches = reader
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Error::Process(format!("Failed to read SQL state: {}", e)))?;
+        // Columns whose type changed, e.g. from `Null` before any value was seen, are widened
+        let Ok(schema) = widen_schema(&[RecordBatch::new_empty(schema), empty()]) else {
+            warn!("Input schema of the SQL processor changed, discarding the persisted state");
+            return Ok(empty());
+        };
+
+        let schema = Arc::new(schema);
+        let batches = batches
+            .into_iter()
+            .map(|batch| cast_batch_to_schema(batch, &schema))
+            .collect::<Result<Vec<_>, _>>()?;
+        arrow::compute::concat_batches(&schema, &batches)
+            .map_err(|e| Error::Process(format!("Batch merge failed: {}", e)))
+    }
+
+    /// Append `batch` to the retained rows and persist them
+    fn store_state(
+        &self,
+        store: &dyn StateStore,
+        config: &SqlStateConfig,
+        previous: &RecordBatch,
+        batch: &RecordBatch,
+    ) -> Result<(), Error> {
+        let mut retained = combine_batches(&[previous.clone(), batch.clone()])?;
+        if retained.num_rows() > config.max_rows {
+            retained = retained.slice(retained.num_rows() - config.max_rows, config.max_rows);
         }
 
-        Ok(
-            arrow::compute::concat_batches(&&result_batches[0].schema(), &result_batches)
-                .map_err(|e| Error::Process(format!("Batch merge failed: {}", e)))?,
-        )
+        let mut data = Vec::new();
+        let mut writer = StreamWriter::try_new(&mut data, &retained.schema())
+            .map_err(|e| Error::Process(format!("Failed to write SQL state: {}", e)))?;
+        writer
+            .write(&retained)
+            .and_then(|_| writer.finish())
+            .map_err(|e| Error::Process(format!("Failed to write SQL state: {}", e)))?;
+        drop(writer);
+        store.put(&self.state_key(), &data)
     }
 
     async fn get_temporary_message_batch(
@@ -197,24 +651,287 @@ impl SqlProcessor {
         ctx.execute_logical_plan(plan).await
     }
 
-    /// Create a new session context with UDFs and JSON functions registered
+    /// Create a session context sharing the functions of `self.ctx` with an empty catalog,
+    /// so that concurrent batches do not see each other's tables
     async fn create_session_context(&self) -> Result<SessionContext, Error> {
-        let mut ctx = SessionContext::new();
-        udf::init(&mut ctx)?;
-        datafusion_functions_json::register_all(&mut ctx)
-            .map_err(|e| Error::Process(format!("Registration JSON function failed: {}", e)))?;
-        Ok(ctx)
+        let state = SessionStateBuilder::new_from_existing(self.ctx.state())
+            .with_config(self.ctx.copied_config())
+            .with_catalog_list(Arc::new(MemoryCatalogProviderList::new()))
+            .build();
+        Ok(SessionContext::new_with_state(state))
+    }
+
+    /// Static tables, read into memory the first time they are needed
+    async fn static_tables(&self) -> Result<&Vec<(String, Arc<dyn TableProvider>)>, Error> {
+        self.static_tables
+            .get_or_try_init(|| async {
+                let mut tables = Vec::new();
+                for table in &self.config.tables {
+                    let df = match &table.source {
+                        TableSource::StaticCsv { path } => {
+                            self.ctx.read_csv(path, CsvReadOptions::new()).await
+                        }
+                        TableSource::StaticParquet { path } => {
+                            self.ctx
+                                .read_parquet(path, ParquetReadOptions::default())
+                                .await
+                        }
+                        TableSource::StaticJson { path } => {
+                            self.ctx.read_json(path, NdJsonReadOptions::default()).await
+                        }
+                        TableSource::CurrentBatch => continue,
+                    }
+                    .map_err(|e| {
+                        Error::Process(format!("Failed to read table {}: {}", table.name, e))
+                    })?;
+                    let schema = Arc::new(df.schema().as_arrow().clone());
+                    let batches = df.collect().await.map_err(|e| {
+                        Error::Process(format!("Failed to read table {}: {}", table.name, e))
+                    })?;
+                    let provider = MemTable::try_new(schema, vec![batches]).map_err(|e| {
+                        Error::Process(format!("Failed to load table {}: {}", table.name, e))
+                    })?;
+                    tables.push((
+                        table.name.clone(),
+                        Arc::new(provider) as Arc<dyn TableProvider>,
+                    ));
+                }
+                Ok(tables)
+            })
+            .await
+    }
+}
+
+/// Start of the window of `size` milliseconds holding `timestamp`
+fn window_start(timestamp: i64, size: i64) -> i64 {
+    timestamp - timestamp.rem_euclid(size)
+}
+
+/// Split the rows of `batch` by the start of their window of `size` milliseconds, leaving out
+/// the rows without a timestamp
+fn split_windows(
+    batch: &RecordBatch,
+    column: &str,
+    size: i64,
+) -> Result<BTreeMap<i64, RecordBatch>, Error> {
+    let starts: Vec<Option<i64>> = timestamp_millis(batch, column)?
+        .iter()
+        .map(|ts| ts.map(|ts| window_start(ts, size)))
+        .collect();
+    let mut windows = BTreeMap::new();
+    for start in starts.iter().flatten().copied().collect::<BTreeSet<i64>>() {
+        let mask: BooleanArray = starts.iter().map(|s| Some(*s == Some(start))).collect();
+        let rows = filter_record_batch(batch, &mask)
+            .map_err(|e| Error::Process(format!("Failed to filter window rows: {}", e)))?;
+        windows.insert(start, rows);
+    }
+    Ok(windows)
+}
+
+/// Append a boolean column `name`, true on every row, flagging a late window result
+fn with_flag_column(batch: &RecordBatch, name: &str) -> Result<RecordBatch, Error> {
+    let mut fields: Vec<Field> = batch
+        .schema()
+        .fields()
+        .iter()
+        .map(|field| field.as_ref().clone())
+        .collect();
+    fields.push(Field::new(name, DataType::Boolean, false));
+    let mut columns = batch.columns().to_vec();
+    columns.push(Arc::new(BooleanArray::from(vec![true; batch.num_rows()])));
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+        .map_err(|e| Error::Process(format!("Failed to add the {} column: {}", name, e)))
+}
+
+/// Append the `window_start` and `window_end` timestamp columns to the rows of a window
+fn with_window_columns(batch: &RecordBatch, start: i64, end: i64) -> Result<RecordBatch, Error> {
+    let rows = batch.num_rows();
+    let mut fields: Vec<Field> = batch
+        .schema()
+        .fields()
+        .iter()
+        .map(|field| field.as_ref().clone())
+        .collect();
+    let mut columns = batch.columns().to_vec();
+    for (name, value) in [("window_start", start), ("window_end", end)] {
+        fields.push(Field::new(
+            name,
+            DataType::Timestamp(TimeUnit::Millisecond, None),
+            false,
+        ));
+        columns.push(Arc::new(TimestampMillisecondArray::from(vec![value; rows])));
+    }
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+        .map_err(|e| Error::Process(format!("Failed to add window columns: {}", e)))
+}
+
+/// Concatenate the batches of a query result
+fn merge_results(result_batches: Vec<RecordBatch>) -> Result<RecordBatch, Error> {
+    if result_batches.is_empty() {
+        return Ok(RecordBatch::new_empty(Arc::new(Schema::empty())));
+    }
+
+    if result_batches.len() == 1 {
+        return Ok(result_batches[0].clone());
+    }
+
+    combine_batches(&result_batches)
+}
+
+/// Concatenate batches, widening the columns whose types differ between batches
+fn combine_batches(batches: &[RecordBatch]) -> Result<RecordBatch, Error> {
+    let schema = Arc::new(widen_schema(batches)?);
+    let batches = batches
+        .iter()
+        .map(|batch| cast_batch_to_schema(batch.clone(), &schema))
+        .collect::<Result<Vec<_>, _>>()?;
+    arrow::compute::concat_batches(&schema, &batches)
+        .map_err(|e| Error::Process(format!("Batch merge failed: {}", e)))
+}
+
+/// Least upper bound of the schemas of `batches`, which must have the same columns in the
+/// same order. A column is nullable if it is nullable in any of the batches.
+fn widen_schema(batches: &[RecordBatch]) -> Result<Schema, Error> {
+    let Some(first) = batches.first() else {
+        return Ok(Schema::empty());
+    };
+    let mut fields: Vec<Field> = first
+        .schema()
+        .fields()
+        .iter()
+        .map(|field| field.as_ref().clone())
+        .collect();
+
+    for batch in &batches[1..] {
+        let schema = batch.schema();
+        let same_columns = schema.fields().len() == fields.len()
+            && fields
+                .iter()
+                .zip(schema.fields())
+                .all(|(a, b)| a.name() == b.name());
+        if !same_columns {
+            return Err(Error::Process(
+                "Batch schemas are inconsistent: the columns differ".to_string(),
+            ));
+        }
+
+        for (field, other) in fields.iter_mut().zip(schema.fields()) {
+            let data_type = widen_type(field.data_type(), other.data_type()).ok_or_else(|| {
+                Error::Process(format!(
+                    "Batch schemas are inconsistent: column {} is {} and {}",
+                    field.name(),
+                    field.data_type(),
+                    other.data_type()
+                ))
+            })?;
+            let nullable = field.is_nullable()
+                || other.is_nullable()
+                || field.data_type() == &DataType::Null
+                || other.data_type() == &DataType::Null;
+            *field = field
+                .clone()
+                .with_data_type(data_type)
+                .with_nullable(nullable);
+        }
+    }
+    Ok(Schema::new(fields))
+}
+
+/// Type holding the values of both types, `None` if there is none without loss
+fn widen_type(a: &DataType, b: &DataType) -> Option<DataType> {
+    let wider = |a: &DataType, b: &DataType| {
+        if a.primitive_width() >= b.primitive_width() {
+            a.clone()
+        } else {
+            b.clone()
+        }
+    };
+
+    match (a, b) {
+        _ if a == b => Some(a.clone()),
+        (DataType::Null, other) | (other, DataType::Null) => Some(other.clone()),
+        _ if a.is_floating() && b.is_floating() => Some(wider(a, b)),
+        _ if (a.is_floating() && b.is_integer()) || (a.is_integer() && b.is_floating()) => {
+            Some(DataType::Float64)
+        }
+        _ if a.is_signed_integer() && b.is_signed_integer() => Some(wider(a, b)),
+        _ if a.is_unsigned_integer() && b.is_unsigned_integer() => Some(wider(a, b)),
+        _ if a.is_integer() && b.is_integer() => {
+            // A signed type twice as wide as the unsigned one holds both
+            let (signed, unsigned) = if a.is_signed_integer() {
+                (a, b)
+            } else {
+                (b, a)
+            };
+            match unsigned {
+                DataType::UInt8 => Some(wider(signed, &DataType::Int16)),
+                DataType::UInt16 => Some(wider(signed, &DataType::Int32)),
+                DataType::UInt32 => Some(DataType::Int64),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Cast the columns of `batch` to the types of `schema`
+fn cast_batch_to_schema(batch: RecordBatch, schema: &Arc<Schema>) -> Result<RecordBatch, Error> {
+    if batch.schema() == *schema {
+        return Ok(batch);
+    }
+    let columns = batch
+        .columns()
+        .iter()
+        .zip(schema.fields())
+        .map(|(column, field)| arrow::compute::cast(column, field.data_type()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| Error::Process(format!("Failed to widen column: {}", e)))?;
+    RecordBatch::try_new(schema.clone(), columns)
+        .map_err(|e| Error::Process(format!("Batch merge failed: {}", e)))
+}
+
+/// Replace the `?` placeholders of `query` outside of quotes with the `$1`, `$2`, ...
+/// placeholders of DataFusion, returning the query and the number of placeholders
+fn number_placeholders(query: &str) -> (String, usize) {
+    let mut result = String::with_capacity(query.len());
+    let mut count = 0;
+    let mut quote = None;
+    for c in query.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None if c == '?' => {
+                count += 1;
+                result.push_str(&format!("${}", count));
+                continue;
+            }
+            None => {}
+        }
+        result.push(c);
     }
+    (result, count)
 }
 
 #[async_trait]
 impl Processor for SqlProcessor {
     async fn process(&self, msg_batch: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+        if let Some(watermark) = watermark_of(&msg_batch) {
+            return match &self.config.window {
+                Some(window) => self.apply_window(window, watermark).await,
+                None => Ok(vec![]),
+            };
+        }
+
         // If the batch is empty, return an empty result.
         if msg_batch.is_empty() {
             return Ok(vec![]);
         }
 
+        if let Some(window) = &self.config.window {
+            return self.buffer_window_rows(window, msg_batch.into()).await;
+        }
+
         // Execute SQL query
         let result_batch = self.execute_query(msg_batch).await?;
         Ok(vec![MessageBatch::new_arrow(result_batch)])
@@ -223,6 +940,14 @@ impl Processor for SqlProcessor {
     async fn close(&self) -> Result<(), Error> {
         Ok(())
     }
+
+    fn state_store(&self) -> Option<Arc<dyn StateStore>> {
+        self.state_store.clone()
+    }
+
+    fn accepts_watermarks(&self) -> bool {
+        self.config.window.is_some()
+    }
 }
 
 struct SqlProcessorBuilder;
@@ -251,8 +976,12 @@ pub fn init() -> Result<(), Error> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use datafusion::arrow::array::{Int64Array, StringArray};
-    use datafusion::arrow::datatypes::{DataType, Field};
+    use crate::state::memory::MemoryStateStore;
+    use arkflow_core::pipeline::watermark::watermark_batch;
+    use datafusion::arrow::array::{
+        Array, ArrayRef, Float32Array, Int32Array, Int64Array, Int8Array, NullArray, StringArray,
+        UInt16Array,
+    };
     use std::cell::RefCell;
 
     #[tokio::test]
@@ -262,6 +991,11 @@ mod tests {
                 query: "SELECT * FROM flow".to_string(),
                 table_name: None,
                 temporary_list: None,
+                state: None,
+                window: None,
+                tables: vec![],
+                parameterized_query: None,
+                params: vec![],
             },
             &Resource {
                 temporary: Default::default(),
@@ -293,6 +1027,47 @@ mod tests {
         assert_eq!(result[0].len(), 3);
     }
 
+    #[tokio::test]
+    async fn test_sql_processor_table_function_join() {
+        let processor = SqlProcessor::new(
+            SqlProcessorConfig {
+                query:
+                    "SELECT id, value FROM flow CROSS JOIN generate_series(1, 2) ORDER BY id, value"
+                        .to_string(),
+                table_name: None,
+                temporary_list: None,
+                state: None,
+                window: None,
+                tables: vec![],
+                parameterized_query: None,
+                params: vec![],
+            },
+            &Resource {
+                temporary: Default::default(),
+                input_names: RefCell::new(Default::default()),
+            },
+        )
+        .unwrap();
+
+        let batch = RecordBatch::try_from_iter([(
+            "id",
+            Arc::new(Int64Array::from(vec![7, 8])) as ArrayRef,
+        )])
+        .unwrap();
+        let result = processor
+            .process(MessageBatch::new_arrow(batch))
+            .await
+            .unwrap();
+
+        let values = result[0]
+            .column_by_name("value")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(values.values(), &[1, 2, 1, 2]);
+    }
+
     #[tokio::test]
     async fn test_sql_processor_empty_batch() {
         let processor = SqlProcessor::new(
@@ -300,6 +1075,11 @@ mod tests {
                 query: "SELECT * FROM flow".to_string(),
                 table_name: None,
                 temporary_list: None,
+                state: None,
+                window: None,
+                tables: vec![],
+                parameterized_query: None,
+                params: vec![],
             },
             &Resource {
                 temporary: Default::default(),
@@ -325,6 +1105,11 @@ mod tests {
                 query: "INVALID SQL QUERY".to_string(),
                 table_name: None,
                 temporary_list: None,
+                state: None,
+                window: None,
+                tables: vec![],
+                parameterized_query: None,
+                params: vec![],
             },
             &Resource {
                 temporary: Default::default(),
@@ -342,6 +1127,11 @@ mod tests {
                 query: "SELECT * FROM custom_table".to_string(),
                 table_name: Some("custom_table".to_string()),
                 temporary_list: None,
+                state: None,
+                window: None,
+                tables: vec![],
+                parameterized_query: None,
+                params: vec![],
             },
             &Resource {
                 temporary: Default::default(),
@@ -366,4 +1156,427 @@ mod tests {
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].len(), 1);
     }
+
+    #[tokio::test]
+    async fn test_sql_processor_static_table_join() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("devices.csv");
+        std::fs::write(&path, "id,location\n1,kitchen\n2,garage\n").unwrap();
+
+        let processor = SqlProcessor::new(
+            SqlProcessorConfig {
+                query: "SELECT d.location, r.value FROM readings r JOIN devices d ON r.id = d.id ORDER BY r.value".to_string(),
+                table_name: None,
+                temporary_list: None,
+                state: None,
+                window: None,
+                tables: vec![
+                    SqlTableConfig {
+                        name: "devices".to_string(),
+                        source: TableSource::StaticCsv {
+                            path: path.to_str().unwrap().to_string(),
+                        },
+                    },
+                    SqlTableConfig {
+                        name: "readings".to_string(),
+                        source: TableSource::CurrentBatch,
+                    },
+                ],
+                parameterized_query: None,
+                params: vec![],
+            },
+            &Resource {
+                temporary: Default::default(),
+                input_names: RefCell::new(Default::default()),
+            },
+        )
+        .unwrap();
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("value", DataType::Int64, false),
+        ]));
+        let batch = |ids: Vec<i64>, values: Vec<i64>| {
+            MessageBatch::new_arrow(
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(Int64Array::from(ids)),
+                        Arc::new(Int64Array::from(values)),
+                    ],
+                )
+                .unwrap(),
+            )
+        };
+
+        let result = processor
+            .process(batch(vec![2, 1], vec![10, 20]))
+            .await
+            .unwrap();
+        let locations = result[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(
+            locations.iter().collect::<Vec<_>>(),
+            vec![Some("garage"), Some("kitchen")]
+        );
+
+        // The file is only read once
+        std::fs::remove_file(&path).unwrap();
+        let result = processor.process(batch(vec![1], vec![5])).await.unwrap();
+        assert_eq!(result[0].len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_sql_processor_parameterized_query() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("orders.csv");
+        std::fs::write(&path, "user_id,item\n1,book\n2,pen\n1,lamp\n").unwrap();
+
+        let processor = SqlProcessor::new(
+            SqlProcessorConfig {
+                query: String::new(),
+                table_name: None,
+                temporary_list: None,
+                state: None,
+                window: None,
+                tables: vec![SqlTableConfig {
+                    name: "orders".to_string(),
+                    source: TableSource::StaticCsv {
+                        path: path.to_str().unwrap().to_string(),
+                    },
+                }],
+                parameterized_query: Some(
+                    "SELECT item, '?' AS mark FROM orders WHERE user_id = ? ORDER BY item"
+                        .to_string(),
+                ),
+                params: vec!["user_id".to_string()],
+            },
+            &Resource {
+                temporary: Default::default(),
+                input_names: RefCell::new(Default::default()),
+            },
+        )
+        .unwrap();
+
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new(
+                "user_id",
+                DataType::Int32,
+                false,
+            )])),
+            vec![Arc::new(datafusion::arrow::array::Int32Array::from(vec![
+                1, 2,
+            ]))],
+        )
+        .unwrap();
+
+        let result = processor
+            .process(MessageBatch::new_arrow(batch))
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 1);
+        let items = result[0]
+            .column_by_name("item")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(
+            items.iter().collect::<Vec<_>>(),
+            vec![Some("book"), Some("lamp"), Some("pen")]
+        );
+        // The batch is not registered as a table
+        assert!(result[0].column_by_name("user_id").is_none());
+    }
+
+    #[test]
+    fn test_sql_processor_parameterized_query_params_mismatch() {
+        let result = SqlProcessor::new(
+            SqlProcessorConfig {
+                query: String::new(),
+                table_name: None,
+                temporary_list: None,
+                state: None,
+                window: None,
+                tables: vec![],
+                parameterized_query: Some("SELECT ? + ?".to_string()),
+                params: vec!["a".to_string()],
+            },
+            &Resource {
+                temporary: Default::default(),
+                input_names: RefCell::new(Default::default()),
+            },
+        );
+        assert!(matches!(result, Err(Error::Config(_))));
+    }
+
+    #[tokio::test]
+    async fn test_sql_processor_state_survives_restart() {
+        let store: Arc<dyn StateStore> = Arc::new(MemoryStateStore::default());
+        let new_processor = || {
+            SqlProcessor::with_state_store(
+                SqlProcessorConfig {
+                    query: "SELECT sum(value) AS total FROM (SELECT value FROM flow UNION ALL SELECT value FROM state)".to_string(),
+                    table_name: None,
+                    temporary_list: None,
+                    tables: vec![],
+                    window: None,
+                    parameterized_query: None,
+                    params: vec![],
+                    state: Some(SqlStateConfig {
+                        table_name: None,
+                        max_rows: 2,
+                        store: StateStoreConfig::Rocksdb {
+                            path: "unused".to_string(),
+                        },
+                    }),
+                },
+                &Resource {
+                    temporary: Default::default(),
+                    input_names: RefCell::new(Default::default()),
+                },
+                Some(store.clone()),
+            )
+            .unwrap()
+        };
+        let batch = |values: Vec<i64>| {
+            let schema = Arc::new(Schema::new(vec![Field::new(
+                "value",
+                DataType::Int64,
+                false,
+            )]));
+            MessageBatch::new_arrow(
+                RecordBatch::try_new(schema, vec![Arc::new(Int64Array::from(values))]).unwrap(),
+            )
+        };
+        let total = |result: Vec<MessageBatch>| {
+            result[0]
+                .column(0)
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .unwrap()
+                .value(0)
+        };
+
+        let processor = new_processor();
+        assert!(processor.state_store().is_some());
+        assert_eq!(
+            total(processor.process(batch(vec![1, 2, 3])).await.unwrap()),
+            6
+        );
+        processor.close().await.unwrap();
+
+        // Only the last two rows are retained
+        let processor = new_processor();
+        assert_eq!(total(processor.process(batch(vec![10])).await.unwrap()), 15);
+        assert_eq!(
+            total(processor.process(batch(vec![100])).await.unwrap()),
+            113
+        );
+    }
+
+    fn window_processor(late_data_policy: LateDataPolicy) -> SqlProcessor {
+        SqlProcessor::new(
+            SqlProcessorConfig {
+                query: "SELECT window_start, window_end, count(*) AS n FROM flow GROUP BY window_start, window_end".to_string(),
+                table_name: None,
+                temporary_list: None,
+                parameterized_query: None,
+                params: vec![],
+                state: None,
+                window: Some(SqlWindowConfig {
+                    timestamp_column: "ts".to_string(),
+                    size_ms: 1000,
+                    late_data_policy,
+                    allowed_lateness_ms: 1000,
+                }),
+                tables: vec![],
+            },
+            &Resource {
+                temporary: Default::default(),
+                input_names: RefCell::new(Default::default()),
+            },
+        )
+        .unwrap()
+    }
+
+    fn timestamps(values: Vec<i64>) -> MessageBatch {
+        MessageBatch::new_arrow(
+            RecordBatch::try_from_iter([("ts", Arc::new(Int64Array::from(values)) as ArrayRef)])
+                .unwrap(),
+        )
+    }
+
+    /// Window start, end and row count of a window result
+    fn window_counts(result: &MessageBatch) -> (i64, i64, i64) {
+        let column = |i: usize| {
+            arrow::compute::cast(result.column(i), &DataType::Int64)
+                .unwrap()
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .unwrap()
+                .value(0)
+        };
+        (column(0), column(1), column(2))
+    }
+
+    #[tokio::test]
+    async fn test_sql_processor_window() {
+        let processor = window_processor(LateDataPolicy::Drop);
+        assert!(processor.accepts_watermarks());
+        let watermark = |ms: i64| watermark_batch(ms).unwrap();
+
+        assert!(processor
+            .process(timestamps(vec![100, 2500, 900, 1500]))
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(processor.process(watermark(999)).await.unwrap().is_empty());
+        let result = processor.process(watermark(2000)).await.unwrap();
+        assert_eq!(
+            result.iter().map(window_counts).collect::<Vec<_>>(),
+            vec![(0, 1000, 2), (1000, 2000, 1)]
+        );
+
+        // Rows of closed windows are dropped
+        assert!(processor
+            .process(timestamps(vec![1999, 2001]))
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(processor.process(watermark(2000)).await.unwrap().is_empty());
+        let result = processor.process(watermark(3000)).await.unwrap();
+        assert_eq!(
+            result.iter().map(window_counts).collect::<Vec<_>>(),
+            vec![(2000, 3000, 2)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sql_processor_window_emit_late_rows() {
+        let processor = window_processor(LateDataPolicy::EmitSeparateBatch);
+        processor.process(timestamps(vec![100, 200])).await.unwrap();
+        processor
+            .process(watermark_batch(1000).unwrap())
+            .await
+            .unwrap();
+
+        let result = processor
+            .process(timestamps(vec![300, 1500]))
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(window_counts(&result[0]), (0, 1000, 1));
+        let late = result[0]
+            .column_by_name("_window_late")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<BooleanArray>()
+            .unwrap();
+        assert!(late.value(0));
+
+        let result = processor
+            .process(watermark_batch(2000).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            result.iter().map(window_counts).collect::<Vec<_>>(),
+            vec![(1000, 2000, 1)]
+        );
+        assert!(result[0].column_by_name("_window_late").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sql_processor_window_update_window() {
+        let processor = window_processor(LateDataPolicy::UpdateWindow);
+        processor.process(timestamps(vec![100, 200])).await.unwrap();
+        processor
+            .process(watermark_batch(1000).unwrap())
+            .await
+            .unwrap();
+
+        // The closed window is queried again with the late rows
+        let result = processor.process(timestamps(vec![300])).await.unwrap();
+        assert_eq!(
+            result.iter().map(window_counts).collect::<Vec<_>>(),
+            vec![(0, 1000, 3)]
+        );
+        assert!(result[0].column_by_name("_window_corrected").is_some());
+        let result = processor.process(timestamps(vec![400])).await.unwrap();
+        assert_eq!(window_counts(&result[0]), (0, 1000, 4));
+
+        // Past the allowed lateness, late rows are dropped
+        processor
+            .process(watermark_batch(2000).unwrap())
+            .await
+            .unwrap();
+        assert!(processor
+            .process(timestamps(vec![500]))
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_widen_schema() {
+        let batch = |fields: Vec<(&str, ArrayRef)>| RecordBatch::try_from_iter(fields).unwrap();
+        let batches = [
+            batch(vec![
+                ("a", Arc::new(NullArray::new(1)) as ArrayRef),
+                ("b", Arc::new(Int32Array::from(vec![1]))),
+                ("c", Arc::new(UInt16Array::from(vec![1]))),
+                ("d", Arc::new(Float32Array::from(vec![1.5]))),
+            ]),
+            batch(vec![
+                ("a", Arc::new(Int64Array::from(vec![2])) as ArrayRef),
+                ("b", Arc::new(Int64Array::from(vec![2]))),
+                ("c", Arc::new(Int8Array::from(vec![-2]))),
+                ("d", Arc::new(Int64Array::from(vec![2]))),
+            ]),
+        ];
+
+        let schema = widen_schema(&batches).unwrap();
+        let types: Vec<&DataType> = schema.fields().iter().map(|f| f.data_type()).collect();
+        assert_eq!(
+            types,
+            vec![
+                &DataType::Int64,
+                &DataType::Int64,
+                &DataType::Int32,
+                &DataType::Float64
+            ]
+        );
+        assert!(schema.field(0).is_nullable());
+
+        let combined = combine_batches(&batches).unwrap();
+        assert_eq!(combined.num_rows(), 2);
+        let a = combined
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert!(a.is_null(0));
+        assert_eq!(a.value(1), 2);
+    }
+
+    #[test]
+    fn test_widen_schema_incompatible() {
+        let batches = [
+            RecordBatch::try_from_iter([("a", Arc::new(Int64Array::from(vec![1])) as ArrayRef)])
+                .unwrap(),
+            RecordBatch::try_from_iter([("a", Arc::new(StringArray::from(vec!["x"])) as ArrayRef)])
+                .unwrap(),
+        ];
+        assert!(matches!(widen_schema(&batches), Err(Error::Process(_))));
+
+        let batches = [
+            batches[0].clone(),
+            RecordBatch::try_from_iter([("b", Arc::new(Int64Array::from(vec![1])) as ArrayRef)])
+                .unwrap(),
+        ];
+        assert!(matches!(combine_batches(&batches), Err(Error::Process(_))));
+    }
 }
@@ -0,0 +1,266 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Template Processor Component
+//!
+//! Render a Handlebars template with every JSON message as its context
+
+use arkflow_core::processor::{register_processor_builder, Processor, ProcessorBuilder};
+use arkflow_core::{Error, MessageBatch, Resource, DEFAULT_BINARY_VALUE_FIELD};
+use async_trait::async_trait;
+use base64::Engine;
+use handlebars::{
+    Context, Handlebars, Helper, HelperResult, Output, RenderContext, RenderErrorReason,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name the template is registered under
+const TEMPLATE_NAME: &str = "template";
+
+/// Template processor configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TemplateProcessorConfig {
+    /// Handlebars template
+    #[serde(default)]
+    template: String,
+    /// File to read the template from instead of `template`
+    template_file: Option<String>,
+}
+
+struct TemplateProcessor {
+    handlebars: Handlebars<'static>,
+}
+
+impl TemplateProcessor {
+    fn new(config: TemplateProcessorConfig) -> Result<Self, Error> {
+        let source = match (&config.template_file, config.template.is_empty()) {
+            (Some(_), false) => {
+                return Err(Error::Config(
+                    "Template processor accepts either template or template_file, not both"
+                        .to_string(),
+                ))
+            }
+            (Some(path), true) => std::fs::read_to_string(path).map_err(|e| {
+                Error::Config(format!("Failed to read template file {}: {}", path, e))
+            })?,
+            (None, false) => config.template,
+            (None, true) => {
+                return Err(Error::Config(
+                    "Template processor requires template or template_file".to_string(),
+                ))
+            }
+        };
+
+        let mut handlebars = Handlebars::new();
+        // Missing fields fail the message instead of rendering as empty strings
+        handlebars.set_strict_mode(true);
+        // The output is JSON, not HTML
+        handlebars.register_escape_fn(handlebars::no_escape);
+        handlebars.register_helper("json_encode", Box::new(json_encode_helper));
+        handlebars.register_helper("base64", Box::new(base64_helper));
+        handlebars.register_helper("now_ms", Box::new(now_ms_helper));
+        handlebars
+            .register_template_string(TEMPLATE_NAME, source)
+            .map_err(|e| Error::Config(format!("Invalid template: {}", e)))?;
+        Ok(Self { handlebars })
+    }
+
+    fn render(&self, content: &[u8]) -> Result<Vec<u8>, Error> {
+        let doc: Value = serde_json::from_slice(content)
+            .map_err(|e| Error::Process(format!("Invalid JSON message: {}", e)))?;
+        let rendered =
+            self.handlebars
+                .render(TEMPLATE_NAME, &doc)
+                .map_err(|e| match e.reason() {
+                    RenderErrorReason::MissingVariable(Some(field)) => {
+                        Error::Process(format!("Template references missing field {}", field))
+                    }
+                    _ => Error::Process(format!("Rendering the template failed: {}", e)),
+                })?;
+        Ok(rendered.into_bytes())
+    }
+}
+
+#[async_trait]
+impl Processor for TemplateProcessor {
+    async fn process(&self, msg: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+        if msg.is_empty() {
+            return Ok(vec![msg]);
+        }
+        let messages = msg
+            .to_binary(DEFAULT_BINARY_VALUE_FIELD)?
+            .into_iter()
+            .map(|content| self.render(content))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let mut rendered = MessageBatch::new_binary(messages)?;
+        rendered.set_input_name(msg.get_input_name());
+        Ok(vec![rendered])
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// `{{json_encode value}}`: the value encoded as JSON
+fn json_encode_helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let value = h
+        .param(0)
+        .ok_or(RenderErrorReason::ParamNotFoundForIndex("json_encode", 0))?;
+    out.write(&value.value().to_string())?;
+    Ok(())
+}
+
+/// `{{base64 value}}`: strings base64-encoded, other values encoded as JSON first
+fn base64_helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let value = h
+        .param(0)
+        .ok_or(RenderErrorReason::ParamNotFoundForIndex("base64", 0))?;
+    let encoded = match value.value() {
+        Value::String(s) => base64::engine::general_purpose::STANDARD.encode(s),
+        other => base64::engine::general_purpose::STANDARD.encode(other.to_string()),
+    };
+    out.write(&encoded)?;
+    Ok(())
+}
+
+/// `{{now_ms}}`: the current time in milliseconds since the Unix epoch
+fn now_ms_helper(
+    _: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    out.write(&now.to_string())?;
+    Ok(())
+}
+
+struct TemplateProcessorBuilder;
+
+impl ProcessorBuilder for TemplateProcessorBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Processor>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Template processor configuration is missing".to_string(),
+            ));
+        }
+        let config: TemplateProcessorConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(TemplateProcessor::new(config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_processor_builder("template", Arc::new(TemplateProcessorBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn processor(template: &str) -> TemplateProcessor {
+        TemplateProcessor::new(TemplateProcessorConfig {
+            template: template.to_string(),
+            template_file: None,
+        })
+        .unwrap()
+    }
+
+    async fn render(processor: &TemplateProcessor, doc: Value) -> Result<Vec<u8>, Error> {
+        let msg = MessageBatch::new_binary(vec![doc.to_string().into_bytes()])?;
+        let result = processor.process(msg).await?;
+        Ok(result[0].to_binary(DEFAULT_BINARY_VALUE_FIELD)?[0].to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_render() {
+        let processor = processor(
+            r#"{"user": {{json_encode name}}, "city": "{{address.city}}", "tags": {{json_encode tags}}, "token": "{{base64 name}}"}"#,
+        );
+        let output = render(
+            &processor,
+            json!({"name": "a\"b", "address": {"city": "Paris"}, "tags": ["x", "y"]}),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Value>(&output).unwrap(),
+            json!({"user": "a\"b", "city": "Paris", "tags": ["x", "y"], "token": "YSJi"})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_now_ms() {
+        let processor = processor(r#"{"ts": {{now_ms}}}"#);
+        let output = render(&processor, json!({})).await.unwrap();
+        let ts = serde_json::from_slice::<Value>(&output).unwrap()["ts"]
+            .as_u64()
+            .unwrap();
+        assert!(ts > 1_600_000_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_missing_field() {
+        let processor = processor(r#"{"id": {{order.id}}}"#);
+        match render(&processor, json!({"order": {}})).await {
+            Err(Error::Process(message)) => assert!(message.contains("order.id"), "{}", message),
+            other => panic!("Expected a process error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let config = |template: &str, template_file: Option<&str>| TemplateProcessorConfig {
+            template: template.to_string(),
+            template_file: template_file.map(str::to_string),
+        };
+        assert!(matches!(
+            TemplateProcessor::new(config("", None)),
+            Err(Error::Config(_))
+        ));
+        assert!(matches!(
+            TemplateProcessor::new(config("{{a}}", Some("template.hbs"))),
+            Err(Error::Config(_))
+        ));
+        assert!(matches!(
+            TemplateProcessor::new(config("{{#if}}", None)),
+            Err(Error::Config(_))
+        ));
+    }
+}
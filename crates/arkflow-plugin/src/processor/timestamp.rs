@@ -0,0 +1,461 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Timestamp Normalize Processor Component
+//!
+//! Convert a column holding Unix times or date strings to an Arrow timestamp column
+
+use arkflow_core::processor::{register_processor_builder, Processor, ProcessorBuilder};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use datafusion::arrow::array::timezone::Tz;
+use datafusion::arrow::array::{Array, ArrayRef, AsArray, PrimitiveArray};
+use datafusion::arrow::compute::{cast, cast_with_options, CastOptions};
+use datafusion::arrow::datatypes::{
+    DataType, Field, Int64Type, Schema, TimeUnit, TimestampMicrosecondType,
+    TimestampMillisecondType, TimestampNanosecondType, TimestampSecondType,
+};
+use datafusion::arrow::record_batch::RecordBatch;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Timestamp normalize processor configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TimestampNormalizeConfig {
+    /// Column to convert, replaced by the timestamp column
+    column: String,
+    /// Format of the values in the column
+    source_format: SourceFormat,
+    /// Arrow type of the converted column
+    #[serde(default)]
+    target_type: TargetType,
+    /// Time zone of the converted column, e.g. `+08:00` or `Asia/Shanghai`
+    target_timezone: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SourceFormat {
+    /// Seconds since the Unix epoch
+    UnixSeconds,
+    /// Milliseconds since the Unix epoch
+    UnixMillis,
+    /// RFC 3339 string such as `2024-01-01T08:00:00+08:00`
+    Rfc3339,
+    /// String in a strftime format such as `%d/%m/%Y %H:%M:%S`
+    Custom(String),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+enum TargetType {
+    #[serde(rename = "timestamp_seconds")]
+    Seconds,
+    #[default]
+    #[serde(rename = "timestamp_millis")]
+    Millis,
+    #[serde(rename = "timestamp_micros")]
+    Micros,
+    #[serde(rename = "timestamp_nanos")]
+    Nanos,
+}
+
+impl TargetType {
+    fn unit(&self) -> TimeUnit {
+        match self {
+            TargetType::Seconds => TimeUnit::Second,
+            TargetType::Millis => TimeUnit::Millisecond,
+            TargetType::Micros => TimeUnit::Microsecond,
+            TargetType::Nanos => TimeUnit::Nanosecond,
+        }
+    }
+
+    fn units_per_second(&self) -> i64 {
+        match self {
+            TargetType::Seconds => 1,
+            TargetType::Millis => 1_000,
+            TargetType::Micros => 1_000_000,
+            TargetType::Nanos => 1_000_000_000,
+        }
+    }
+}
+
+struct TimestampNormalizeProcessor {
+    config: TimestampNormalizeConfig,
+    /// Parsed `target_timezone`
+    timezone: Option<Tz>,
+}
+
+impl TimestampNormalizeProcessor {
+    fn new(config: TimestampNormalizeConfig) -> Result<Self, Error> {
+        let timezone = config
+            .target_timezone
+            .as_deref()
+            .map(|tz| {
+                Tz::from_str(tz)
+                    .map_err(|e| Error::Config(format!("Invalid target_timezone {}: {}", tz, e)))
+            })
+            .transpose()?;
+        Ok(Self { config, timezone })
+    }
+
+    /// Convert the column to the target unit
+    fn convert(&self, column: &ArrayRef) -> Result<Vec<Option<i64>>, Error> {
+        let target = self.config.target_type;
+        match &self.config.source_format {
+            SourceFormat::UnixSeconds => unix_to_target(column, 1, target),
+            SourceFormat::UnixMillis => unix_to_target(column, 1_000, target),
+            SourceFormat::Rfc3339 => parse_strings(column, |value| {
+                DateTime::parse_from_rfc3339(value)
+                    .map(|dt| dt.with_timezone(&Utc))
+                    .ok()
+            })?
+            .into_iter()
+            .map(|dt| dt.map(|dt| datetime_to_target(&dt, target)).transpose())
+            .collect(),
+            SourceFormat::Custom(format) => {
+                parse_strings(column, |value| self.parse_custom(value, format))?
+                    .into_iter()
+                    .map(|dt| dt.map(|dt| datetime_to_target(&dt, target)).transpose())
+                    .collect()
+            }
+        }
+    }
+
+    /// Parse a string in a custom format. Strings without an offset are read in the target
+    /// time zone, or in UTC without one.
+    fn parse_custom(&self, value: &str, format: &str) -> Option<DateTime<Utc>> {
+        if let Ok(dt) = DateTime::parse_from_str(value, format) {
+            return Some(dt.with_timezone(&Utc));
+        }
+        let naive = NaiveDateTime::parse_from_str(value, format)
+            .ok()
+            .or_else(|| {
+                NaiveDate::parse_from_str(value, format)
+                    .ok()
+                    .and_then(|date| date.and_hms_opt(0, 0, 0))
+            })?;
+        match &self.timezone {
+            Some(tz) => tz
+                .from_local_datetime(&naive)
+                .earliest()
+                .map(|dt| dt.with_timezone(&Utc)),
+            None => Some(naive.and_utc()),
+        }
+    }
+
+    fn timestamp_array(&self, values: Vec<Option<i64>>) -> ArrayRef {
+        let timezone = self.config.target_timezone.clone();
+        match self.config.target_type {
+            TargetType::Seconds => Arc::new(
+                PrimitiveArray::<TimestampSecondType>::from(values).with_timezone_opt(timezone),
+            ),
+            TargetType::Millis => Arc::new(
+                PrimitiveArray::<TimestampMillisecondType>::from(values)
+                    .with_timezone_opt(timezone),
+            ),
+            TargetType::Micros => Arc::new(
+                PrimitiveArray::<TimestampMicrosecondType>::from(values)
+                    .with_timezone_opt(timezone),
+            ),
+            TargetType::Nanos => Arc::new(
+                PrimitiveArray::<TimestampNanosecondType>::from(values).with_timezone_opt(timezone),
+            ),
+        }
+    }
+}
+
+/// Rescale Unix times given in `source_per_second` units per second to the target unit
+fn unix_to_target(
+    column: &ArrayRef,
+    source_per_second: i64,
+    target: TargetType,
+) -> Result<Vec<Option<i64>>, Error> {
+    // Numeric strings are accepted too, but values that are not numbers are an error
+    let options = CastOptions {
+        safe: false,
+        ..Default::default()
+    };
+    let values = cast_with_options(column, &DataType::Int64, &options)
+        .map_err(|e| Error::Process(format!("Invalid Unix timestamp: {}", e)))?;
+    let target_per_second = target.units_per_second();
+
+    values
+        .as_primitive::<Int64Type>()
+        .iter()
+        .map(|value| {
+            let Some(value) = value else {
+                return Ok(None);
+            };
+            let converted = if target_per_second >= source_per_second {
+                value.checked_mul(target_per_second / source_per_second)
+            } else {
+                Some(value.div_euclid(source_per_second / target_per_second))
+            };
+            converted
+                .map(Some)
+                .ok_or_else(|| Error::Process(format!("Unix timestamp {} is out of range", value)))
+        })
+        .collect()
+}
+
+/// Parse every string of the column, failing on the first value that cannot be parsed
+fn parse_strings(
+    column: &ArrayRef,
+    parse: impl Fn(&str) -> Option<DateTime<Utc>>,
+) -> Result<Vec<Option<DateTime<Utc>>>, Error> {
+    let strings = cast(column, &DataType::Utf8).map_err(|e| {
+        Error::Process(format!(
+            "Timestamp column must hold strings, found {}: {}",
+            column.data_type(),
+            e
+        ))
+    })?;
+    strings
+        .as_string::<i32>()
+        .iter()
+        .map(|value| match value {
+            Some(value) => parse(value)
+                .map(Some)
+                .ok_or_else(|| Error::Process(format!("Invalid timestamp: {}", value))),
+            None => Ok(None),
+        })
+        .collect()
+}
+
+fn datetime_to_target(dt: &DateTime<Utc>, target: TargetType) -> Result<i64, Error> {
+    match target {
+        TargetType::Seconds => Ok(dt.timestamp()),
+        TargetType::Millis => Ok(dt.timestamp_millis()),
+        TargetType::Micros => Ok(dt.timestamp_micros()),
+        TargetType::Nanos => dt
+            .timestamp_nanos_opt()
+            .ok_or_else(|| Error::Process(format!("Timestamp {} is out of range", dt))),
+    }
+}
+
+#[async_trait]
+impl Processor for TimestampNormalizeProcessor {
+    async fn process(&self, msg_batch: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+        if msg_batch.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let schema = msg_batch.schema();
+        let Ok(index) = schema.index_of(&self.config.column) else {
+            return Err(Error::Process(format!(
+                "Column {} not found in message batch",
+                self.config.column
+            )));
+        };
+        let values = self.convert(msg_batch.column(index))?;
+
+        let mut fields: Vec<Field> = schema.fields().iter().map(|f| f.as_ref().clone()).collect();
+        fields[index] = fields[index].clone().with_data_type(DataType::Timestamp(
+            self.config.target_type.unit(),
+            self.config.target_timezone.clone().map(Into::into),
+        ));
+        let mut columns = msg_batch.columns().to_vec();
+        columns[index] = self.timestamp_array(values);
+
+        let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+            .map_err(|e| Error::Process(format!("Failed to normalize timestamps: {}", e)))?;
+        let mut result = MessageBatch::new_arrow(batch);
+        result.set_input_name(msg_batch.get_input_name());
+        Ok(vec![result])
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+struct TimestampNormalizeProcessorBuilder;
+impl ProcessorBuilder for TimestampNormalizeProcessorBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Processor>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Timestamp normalize processor configuration is missing".to_string(),
+            ));
+        }
+        let config: TimestampNormalizeConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(TimestampNormalizeProcessor::new(config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_processor_builder(
+        "timestamp_normalize",
+        Arc::new(TimestampNormalizeProcessorBuilder),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Int64Array, StringArray};
+    use serde_json::{json, Value};
+    use std::cell::RefCell;
+
+    fn build(config: Value) -> Result<Arc<dyn Processor>, Error> {
+        TimestampNormalizeProcessorBuilder.build(
+            None,
+            &Some(config),
+            &Resource {
+                temporary: Default::default(),
+                input_names: RefCell::new(Default::default()),
+            },
+        )
+    }
+
+    async fn normalize(config: Value, column: ArrayRef) -> Result<ArrayRef, Error> {
+        let batch = RecordBatch::try_from_iter([
+            (
+                "id",
+                Arc::new(Int64Array::from(vec![1; column.len()])) as ArrayRef,
+            ),
+            ("ts", column),
+        ])
+        .unwrap();
+        let result = build(config)?
+            .process(MessageBatch::new_arrow(batch))
+            .await?;
+        assert_eq!(result[0].schema().field(0).name(), "id");
+        Ok(result[0].column_by_name("ts").unwrap().clone())
+    }
+
+    #[tokio::test]
+    async fn test_unix_timestamps() {
+        let column = normalize(
+            json!({
+                "column": "ts",
+                "source_format": "unix_seconds",
+                "target_type": "timestamp_millis",
+                "target_timezone": "+08:00",
+            }),
+            Arc::new(Int64Array::from(vec![Some(1_704_067_200), None])),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            column.data_type(),
+            &DataType::Timestamp(TimeUnit::Millisecond, Some("+08:00".into()))
+        );
+        let values = column.as_primitive::<TimestampMillisecondType>();
+        assert_eq!(values.value(0), 1_704_067_200_000);
+        assert!(values.is_null(1));
+
+        // Numeric strings are accepted and coarser units round down
+        let column = normalize(
+            json!({
+                "column": "ts",
+                "source_format": "unix_millis",
+                "target_type": "timestamp_seconds",
+            }),
+            Arc::new(StringArray::from(vec!["1704067200999", "-1"])),
+        )
+        .await
+        .unwrap();
+        let values = column.as_primitive::<TimestampSecondType>();
+        assert_eq!(values.values().to_vec(), vec![1_704_067_200, -1]);
+    }
+
+    #[tokio::test]
+    async fn test_rfc3339() {
+        let column = normalize(
+            json!({
+                "column": "ts",
+                "source_format": "rfc3339",
+                "target_type": "timestamp_micros",
+            }),
+            Arc::new(StringArray::from(vec![
+                "2024-01-01T08:00:00.000001+08:00",
+                "2024-01-01T00:00:00Z",
+            ])),
+        )
+        .await
+        .unwrap();
+        let values = column.as_primitive::<TimestampMicrosecondType>();
+        assert_eq!(
+            values.values().to_vec(),
+            vec![1_704_067_200_000_001, 1_704_067_200_000_000]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_custom_format() {
+        let column = normalize(
+            json!({
+                "column": "ts",
+                "source_format": {"custom": "%d/%m/%Y %H:%M:%S"},
+                "target_type": "timestamp_nanos",
+                "target_timezone": "Asia/Shanghai",
+            }),
+            Arc::new(StringArray::from(vec!["01/01/2024 08:00:00"])),
+        )
+        .await
+        .unwrap();
+        let values = column.as_primitive::<TimestampNanosecondType>();
+        assert_eq!(values.value(0), 1_704_067_200_000_000_000);
+
+        // Dates without a time are read as midnight
+        let column = normalize(
+            json!({"column": "ts", "source_format": {"custom": "%Y-%m-%d"}}),
+            Arc::new(StringArray::from(vec!["2024-01-01"])),
+        )
+        .await
+        .unwrap();
+        let values = column.as_primitive::<TimestampMillisecondType>();
+        assert_eq!(values.value(0), 1_704_067_200_000);
+    }
+
+    #[tokio::test]
+    async fn test_errors() {
+        assert!(matches!(
+            build(json!({
+                "column": "ts",
+                "source_format": "rfc3339",
+                "target_timezone": "Mars/Olympus",
+            })),
+            Err(Error::Config(_))
+        ));
+
+        let config = json!({"column": "ts", "source_format": "rfc3339"});
+        let invalid = normalize(
+            config.clone(),
+            Arc::new(StringArray::from(vec!["yesterday"])),
+        )
+        .await;
+        assert!(invalid.is_err());
+
+        let missing = build(json!({"column": "missing", "source_format": "rfc3339"}))
+            .unwrap()
+            .process(MessageBatch::new_binary(vec![b"x".to_vec()]).unwrap())
+            .await;
+        assert!(missing.is_err());
+
+        let not_a_number = normalize(
+            json!({"column": "ts", "source_format": "unix_seconds"}),
+            Arc::new(StringArray::from(vec!["2024-01-01"])),
+        )
+        .await;
+        assert!(not_a_number.is_err());
+    }
+}
@@ -0,0 +1,291 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Trace Sample Processor Component
+//!
+//! Keep the trace context of only part of the messages, so that high-throughput pipelines do
+//! not trace every message. Unsampled messages keep their context with the sampled flag
+//! cleared: the pipeline does not create spans for them and outputs do not propagate it.
+
+use arkflow_core::processor::{register_processor_builder, Processor, ProcessorBuilder};
+use arkflow_core::trace::SpanContext;
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Trace sample processor configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TraceSampleProcessorConfig {
+    sampler_type: SamplerType,
+    /// Fraction of the messages traced by the `probabilistic` sampler, from 0.0 to 1.0
+    sample_rate: Option<f64>,
+    /// Maximum number of messages traced per second by the `rate_limiting` sampler
+    max_traces_per_second: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SamplerType {
+    /// Trace each message with probability `sample_rate`
+    Probabilistic,
+    /// Trace at most `max_traces_per_second` messages per second
+    RateLimiting,
+    AlwaysOn,
+    AlwaysOff,
+}
+
+enum Sampler {
+    Probabilistic(f64),
+    RateLimiting(Mutex<TokenBucket>),
+    AlwaysOn,
+    AlwaysOff,
+}
+
+/// Token bucket refilled at `rate` tokens per second, holding at most `rate` tokens but
+/// at least one
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            tokens: rate.max(1.0),
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn try_acquire(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate.max(1.0));
+        self.last_refill = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+struct TraceSampleProcessor {
+    sampler: Sampler,
+}
+
+impl TraceSampleProcessor {
+    fn new(config: TraceSampleProcessorConfig) -> Result<Self, Error> {
+        let sampler = match config.sampler_type {
+            SamplerType::Probabilistic => {
+                let rate = config.sample_rate.ok_or_else(|| {
+                    Error::Config("The probabilistic sampler requires sample_rate".to_string())
+                })?;
+                if !(0.0..=1.0).contains(&rate) {
+                    return Err(Error::Config(format!(
+                        "sample_rate must be between 0.0 and 1.0, got {}",
+                        rate
+                    )));
+                }
+                Sampler::Probabilistic(rate)
+            }
+            SamplerType::RateLimiting => {
+                let rate = config.max_traces_per_second.ok_or_else(|| {
+                    Error::Config(
+                        "The rate limiting sampler requires max_traces_per_second".to_string(),
+                    )
+                })?;
+                if !rate.is_finite() || rate <= 0.0 {
+                    return Err(Error::Config(format!(
+                        "max_traces_per_second must be positive, got {}",
+                        rate
+                    )));
+                }
+                Sampler::RateLimiting(Mutex::new(TokenBucket::new(rate)))
+            }
+            SamplerType::AlwaysOn => Sampler::AlwaysOn,
+            SamplerType::AlwaysOff => Sampler::AlwaysOff,
+        };
+        Ok(Self { sampler })
+    }
+
+    fn should_sample(&self) -> bool {
+        match &self.sampler {
+            Sampler::Probabilistic(rate) => random_f64() < *rate,
+            Sampler::RateLimiting(bucket) => bucket.lock().unwrap().try_acquire(),
+            Sampler::AlwaysOn => true,
+            Sampler::AlwaysOff => false,
+        }
+    }
+}
+
+thread_local! {
+    /// State of the xorshift generator of the probabilistic sampler
+    static RNG_STATE: Cell<u64> = Cell::new(RandomState::new().build_hasher().finish() | 1);
+}
+
+/// Uniformly distributed value in `[0, 1)`
+fn random_f64() -> f64 {
+    RNG_STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    })
+}
+
+#[async_trait]
+impl Processor for TraceSampleProcessor {
+    async fn process(&self, mut msg_batch: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+        let context = msg_batch.extract_span_context();
+        if self.should_sample() {
+            let context = match context {
+                Some(context) => SpanContext {
+                    sampled: true,
+                    ..context
+                },
+                None => SpanContext::new_root(),
+            };
+            msg_batch.inject_span_context(&context);
+        } else if let Some(context) = context {
+            msg_batch.inject_span_context(&SpanContext {
+                sampled: false,
+                ..context
+            });
+        }
+        Ok(vec![msg_batch])
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+struct TraceSampleProcessorBuilder;
+impl ProcessorBuilder for TraceSampleProcessorBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<serde_json::Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Processor>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Trace sample processor configuration is missing".to_string(),
+            ));
+        }
+        let config: TraceSampleProcessorConfig = serde_json::from_value(config.clone().unwrap())?;
+
+        Ok(Arc::new(TraceSampleProcessor::new(config)?))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_processor_builder("trace_sample", Arc::new(TraceSampleProcessorBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::cell::RefCell;
+
+    fn build(config: serde_json::Value) -> Result<Arc<dyn Processor>, Error> {
+        TraceSampleProcessorBuilder.build(
+            None,
+            &Some(config),
+            &Resource {
+                temporary: Default::default(),
+                input_names: RefCell::new(Default::default()),
+            },
+        )
+    }
+
+    fn traced() -> MessageBatch {
+        let mut msg = MessageBatch::from_string("hello").unwrap();
+        msg.inject_span_context(
+            &SpanContext::from_traceparent(
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            )
+            .unwrap(),
+        );
+        msg
+    }
+
+    async fn sampled(processor: &Arc<dyn Processor>, msg: MessageBatch) -> Option<bool> {
+        let result = processor.process(msg).await.unwrap();
+        result[0]
+            .extract_span_context()
+            .map(|context| context.sampled)
+    }
+
+    #[tokio::test]
+    async fn test_always_on_and_off() {
+        let on = build(json!({"sampler_type": "always_on"})).unwrap();
+        assert_eq!(sampled(&on, traced()).await, Some(true));
+        // A trace is started for untraced messages
+        let untraced = MessageBatch::from_string("hello").unwrap();
+        assert_eq!(sampled(&on, untraced).await, Some(true));
+
+        let off = build(json!({"sampler_type": "always_off"})).unwrap();
+        assert_eq!(sampled(&off, traced()).await, Some(false));
+        let untraced = MessageBatch::from_string("hello").unwrap();
+        assert_eq!(sampled(&off, untraced).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_probabilistic() {
+        let processor =
+            build(json!({"sampler_type": "probabilistic", "sample_rate": 0.5})).unwrap();
+        let mut count = 0;
+        for _ in 0..1000 {
+            if sampled(&processor, traced()).await == Some(true) {
+                count += 1;
+            }
+        }
+        assert!((350..650).contains(&count), "{} sampled", count);
+
+        assert!(build(json!({"sampler_type": "probabilistic", "sample_rate": 1.5})).is_err());
+        assert!(build(json!({"sampler_type": "probabilistic"})).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiting() {
+        let processor = build(json!({
+            "sampler_type": "rate_limiting",
+            "max_traces_per_second": 3.0,
+        }))
+        .unwrap();
+        let mut count = 0;
+        for _ in 0..10 {
+            if sampled(&processor, traced()).await == Some(true) {
+                count += 1;
+            }
+        }
+        assert_eq!(count, 3);
+
+        assert!(
+            build(json!({"sampler_type": "rate_limiting", "max_traces_per_second": 0.0})).is_err()
+        );
+    }
+}
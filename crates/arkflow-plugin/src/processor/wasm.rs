@@ -0,0 +1,325 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Wasm Processor Component
+//!
+//! Run untrusted transformation logic compiled to WebAssembly. Each batch is passed as NDJSON to
+//! a fresh instance of the module, whose fuel and memory are limited so that a misbehaving
+//! module cannot stall or exhaust the host.
+//!
+//! The module must export its `memory`, an `alloc(len: i32) -> i32` function returning a buffer
+//! of `len` bytes for the input, and the transformation function
+//! `(ptr: i32, len: i32) -> (ptr: i32, len: i32)` returning the output NDJSON.
+
+use crate::component;
+use arkflow_core::processor::{register_processor_builder, Processor, ProcessorBuilder};
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use datafusion::arrow::json::LineDelimitedWriter;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use wasmtime::{Config, Engine, Instance, Module, Store, StoreLimits, StoreLimitsBuilder, Trap};
+
+/// Name of the function exported by the module to allocate the input buffer
+const ALLOC_FUNCTION: &str = "alloc";
+/// Name of the memory exported by the module
+const MEMORY_EXPORT: &str = "memory";
+
+/// Wasm processor configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct WasmProcessorConfig {
+    /// Path of the module, in binary or text format
+    module_path: String,
+    /// Name of the exported transformation function
+    function_name: String,
+    /// Maximum size of the linear memory of the module
+    memory_limit_bytes: u64,
+    /// Instructions the module may execute per batch
+    fuel_limit: u64,
+}
+
+struct WasmProcessor {
+    runner: Arc<WasmRunner>,
+}
+
+/// Compiled module and the limits of its instances
+struct WasmRunner {
+    config: WasmProcessorConfig,
+    engine: Engine,
+    module: Module,
+}
+
+impl WasmRunner {
+    fn new(config: WasmProcessorConfig) -> Result<Self, Error> {
+        let mut wasm_config = Config::new();
+        wasm_config.consume_fuel(true);
+        let engine = Engine::new(&wasm_config)
+            .map_err(|e| Error::Config(format!("Failed to create Wasm engine: {}", e)))?;
+        let module = Module::from_file(&engine, &config.module_path).map_err(|e| {
+            Error::Config(format!(
+                "Failed to load Wasm module {}: {}",
+                config.module_path, e
+            ))
+        })?;
+        Ok(Self {
+            config,
+            engine,
+            module,
+        })
+    }
+
+    /// Run the transformation function of a new instance on `input`
+    fn call(&self, input: &[u8]) -> Result<Vec<u8>, Error> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(usize::try_from(self.config.memory_limit_bytes).unwrap_or(usize::MAX))
+            .instances(1)
+            .build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits: &mut StoreLimits| limits);
+        store
+            .set_fuel(self.config.fuel_limit)
+            .map_err(|e| Error::Process(format!("Failed to set Wasm fuel: {}", e)))?;
+
+        let instance = Instance::new(&mut store, &self.module, &[])
+            .map_err(|e| wasm_error("Failed to instantiate Wasm module", e))?;
+        let memory = instance
+            .get_memory(&mut store, MEMORY_EXPORT)
+            .ok_or_else(|| Error::Process("Wasm module does not export its memory".to_string()))?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&mut store, ALLOC_FUNCTION)
+            .map_err(|e| wasm_error("Invalid Wasm alloc function", e))?;
+        let function = instance
+            .get_typed_func::<(i32, i32), (i32, i32)>(&mut store, &self.config.function_name)
+            .map_err(|e| wasm_error("Invalid Wasm transformation function", e))?;
+
+        let len = i32::try_from(input.len())
+            .map_err(|_| Error::Process("Batch is too large for Wasm memory".to_string()))?;
+        let ptr = alloc
+            .call(&mut store, len)
+            .map_err(|e| wasm_error("Wasm alloc function failed", e))?;
+        memory
+            .write(&mut store, ptr as u32 as usize, input)
+            .map_err(|e| Error::Process(format!("Failed to write Wasm memory: {}", e)))?;
+
+        let (ptr, len) = function
+            .call(&mut store, (ptr, len))
+            .map_err(|e| wasm_error("Wasm transformation function failed", e))?;
+        let mut output = vec![0; len as u32 as usize];
+        memory
+            .read(&store, ptr as u32 as usize, &mut output)
+            .map_err(|e| Error::Process(format!("Failed to read Wasm memory: {}", e)))?;
+        Ok(output)
+    }
+}
+
+/// Describe a Wasm error, calling out the exhaustion of the fuel
+fn wasm_error(context: &str, e: wasmtime::Error) -> Error {
+    match e.downcast_ref::<Trap>() {
+        Some(Trap::OutOfFuel) => Error::Process(format!(
+            "{}: fuel limit exhausted, the module ran too many instructions",
+            context
+        )),
+        _ => Error::Process(format!("{}: {:#}", context, e)),
+    }
+}
+
+#[async_trait]
+impl Processor for WasmProcessor {
+    async fn process(&self, msg: MessageBatch) -> Result<Vec<MessageBatch>, Error> {
+        if msg.is_empty() {
+            return Ok(vec![msg]);
+        }
+
+        let mut input = Vec::new();
+        let mut writer = LineDelimitedWriter::new(&mut input);
+        writer
+            .write(&msg)
+            .map_err(|e| Error::Process(format!("Arrow JSON Serialization error: {}", e)))?;
+        writer.finish().map_err(|e| {
+            Error::Process(format!("Arrow JSON Serialization Complete Error: {}", e))
+        })?;
+
+        let runner = self.runner.clone();
+        let output = tokio::task::spawn_blocking(move || runner.call(&input))
+            .await
+            .map_err(|e| Error::Process(format!("Failed to spawn blocking task: {}", e)))??;
+        if output.iter().all(u8::is_ascii_whitespace) {
+            return Ok(vec![]);
+        }
+
+        let mut result = MessageBatch::new_arrow(component::json::try_to_arrow(&output, None)?);
+        result.set_input_name(msg.get_input_name());
+        Ok(vec![result])
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+struct WasmProcessorBuilder;
+impl ProcessorBuilder for WasmProcessorBuilder {
+    fn build(
+        &self,
+        _name: Option<&String>,
+        config: &Option<Value>,
+        _resource: &Resource,
+    ) -> Result<Arc<dyn Processor>, Error> {
+        if config.is_none() {
+            return Err(Error::Config(
+                "Wasm processor configuration is missing".to_string(),
+            ));
+        }
+        let config: WasmProcessorConfig = serde_json::from_value(config.clone().unwrap())?;
+        Ok(Arc::new(WasmProcessor {
+            runner: Arc::new(WasmRunner::new(config)?),
+        }))
+    }
+}
+
+pub fn init() -> Result<(), Error> {
+    register_processor_builder("wasm", Arc::new(WasmProcessorBuilder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Int64Array, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use serde_json::json;
+    use std::cell::RefCell;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    /// Module exporting a bump allocator, an identity function, a function dropping every
+    /// message and a function looping forever
+    const MODULE: &str = r#"
+        (module
+          (memory (export "memory") 1)
+          (global $heap (mut i32) (i32.const 1024))
+          (func (export "alloc") (param $len i32) (result i32)
+            (local $ptr i32)
+            (local.set $ptr (global.get $heap))
+            (global.set $heap (i32.add (global.get $heap) (local.get $len)))
+            (local.get $ptr))
+          (func (export "identity") (param $ptr i32) (param $len i32) (result i32 i32)
+            (local.get $ptr)
+            (local.get $len))
+          (func (export "drop_all") (param $ptr i32) (param $len i32) (result i32 i32)
+            (local.get $ptr)
+            (i32.const 0))
+          (func (export "spin") (param $ptr i32) (param $len i32) (result i32 i32)
+            (loop $forever (br $forever))
+            unreachable))
+    "#;
+
+    fn module_file(module: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::with_suffix(".wat").unwrap();
+        file.write_all(module.as_bytes()).unwrap();
+        file
+    }
+
+    fn build(file: &NamedTempFile, function_name: &str) -> Result<Arc<dyn Processor>, Error> {
+        WasmProcessorBuilder.build(
+            None,
+            &Some(json!({
+                "module_path": file.path().to_str().unwrap(),
+                "function_name": function_name,
+                "memory_limit_bytes": 1 << 20,
+                "fuel_limit": 1_000_000,
+            })),
+            &Resource {
+                temporary: Default::default(),
+                input_names: RefCell::new(Default::default()),
+            },
+        )
+    }
+
+    fn batch() -> MessageBatch {
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("id", DataType::Int64, false),
+                Field::new("name", DataType::Utf8, false),
+            ])),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec!["a", "b"])),
+            ],
+        )
+        .unwrap();
+        MessageBatch::new_arrow(batch)
+    }
+
+    #[tokio::test]
+    async fn test_wasm_identity() {
+        let file = module_file(MODULE);
+        let processor = build(&file, "identity").unwrap();
+
+        let mut msg = batch();
+        msg.set_input_name(Some("events".to_string()));
+        let result = processor.process(msg).await.unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].num_rows(), 2);
+        assert_eq!(result[0].get_input_name(), Some("events".to_string()));
+
+        let ids = result[0]
+            .column_by_name("id")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(ids.values(), &[1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_wasm_empty_output() {
+        let file = module_file(MODULE);
+        let processor = build(&file, "drop_all").unwrap();
+        assert!(processor.process(batch()).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_wasm_fuel_limit() {
+        let file = module_file(MODULE);
+        let processor = build(&file, "spin").unwrap();
+        let err = processor.process(batch()).await.unwrap_err();
+        assert!(err.to_string().contains("fuel limit exhausted"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_wasm_memory_limit() {
+        // 32 pages of 64 KiB, over the 1 MiB limit
+        let file = module_file(&MODULE.replace(
+            "(memory (export \"memory\") 1)",
+            "(memory (export \"memory\") 32)",
+        ));
+        let processor = build(&file, "identity").unwrap();
+        let err = processor.process(batch()).await.unwrap_err();
+        assert!(err.to_string().contains("Failed to instantiate"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_wasm_missing_function() {
+        let file = module_file(MODULE);
+        let processor = build(&file, "missing").unwrap();
+        let err = processor.process(batch()).await.unwrap_err();
+        assert!(
+            err.to_string().contains("transformation function"),
+            "{}",
+            err
+        );
+    }
+}
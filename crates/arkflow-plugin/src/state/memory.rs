@@ -0,0 +1,74 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! In-memory state store
+//!
+//! Keep processor state in memory, it is lost when the process exits
+
+use arkflow_core::processor::{StateEntry, StateStore};
+use arkflow_core::Error;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// State store backed by an in-memory ordered map
+#[derive(Default)]
+pub struct MemoryStateStore(Mutex<BTreeMap<Vec<u8>, Vec<u8>>>);
+
+impl StateStore for MemoryStateStore {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.0.lock().unwrap().get(key).cloned())
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        self.0.lock().unwrap().insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn delete(&self, key: &[u8]) -> Result<(), Error> {
+        self.0.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    fn scan_prefix(&self, prefix: &[u8]) -> Result<Vec<StateEntry>, Error> {
+        Ok(self
+            .0
+            .lock()
+            .unwrap()
+            .range(prefix.to_vec()..)
+            .take_while(|(k, _)| k.starts_with(prefix))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_state_store() {
+        let store = MemoryStateStore::default();
+        store.put(b"a/1", b"one").unwrap();
+        store.put(b"a/2", b"two").unwrap();
+        store.put(b"b/1", b"three").unwrap();
+        store.delete(b"a/2").unwrap();
+
+        assert_eq!(store.get(b"a/1").unwrap(), Some(b"one".to_vec()));
+        assert_eq!(store.get(b"a/2").unwrap(), None);
+        assert_eq!(
+            store.scan_prefix(b"a/").unwrap(),
+            vec![(b"a/1".to_vec(), b"one".to_vec())]
+        );
+    }
+}
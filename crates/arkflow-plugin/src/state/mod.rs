@@ -0,0 +1,100 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! State store module
+//!
+//! Persistent key-value stores used by stateful processors.
+
+use arkflow_core::processor::StateStore;
+use arkflow_core::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+
+pub mod memory;
+#[cfg(feature = "rocksdb")]
+pub mod rocksdb;
+
+/// State store configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StateStoreConfig {
+    /// Local RocksDB database, requires the `rocksdb` feature
+    Rocksdb {
+        /// Directory of the database
+        path: String,
+    },
+}
+
+lazy_static::lazy_static! {
+    /// RocksDB stores currently open, by path. A database can only be opened once, so a
+    /// pipeline rebuilt while the previous one is still open shares its stores.
+    static ref ROCKSDB_STORES: Mutex<HashMap<String, Weak<dyn StateStore>>> =
+        Mutex::new(HashMap::new());
+}
+
+impl StateStoreConfig {
+    /// Open the configured state store, or get it if it is already open
+    pub fn build(&self) -> Result<Arc<dyn StateStore>, Error> {
+        match self {
+            StateStoreConfig::Rocksdb { path } => {
+                let mut stores = ROCKSDB_STORES.lock().unwrap();
+                if let Some(store) = stores.get(path).and_then(Weak::upgrade) {
+                    return Ok(store);
+                }
+                let store = open_rocksdb(path)?;
+                stores.retain(|_, store| store.strong_count() > 0);
+                stores.insert(path.clone(), Arc::downgrade(&store));
+                Ok(store)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rocksdb")]
+fn open_rocksdb(path: &str) -> Result<Arc<dyn StateStore>, Error> {
+    Ok(Arc::new(rocksdb::RocksDbStateStore::open(path)?))
+}
+
+#[cfg(not(feature = "rocksdb"))]
+fn open_rocksdb(_path: &str) -> Result<Arc<dyn StateStore>, Error> {
+    Err(Error::Config(
+        "The RocksDB state store requires arkflow to be built with the `rocksdb` feature"
+            .to_string(),
+    ))
+}
+
+#[cfg(all(test, feature = "rocksdb"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_shares_open_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = StateStoreConfig::Rocksdb {
+            path: dir.path().to_string_lossy().to_string(),
+        };
+
+        let store = config.build().unwrap();
+        let shared = config.build().unwrap();
+        assert!(Arc::ptr_eq(&store, &shared));
+        store.put(b"key", b"value").unwrap();
+
+        // Once closed, the database is opened again
+        drop(store);
+        drop(shared);
+        let reopened = config.build().unwrap();
+        assert_eq!(reopened.get(b"key").unwrap(), Some(b"value".to_vec()));
+    }
+}
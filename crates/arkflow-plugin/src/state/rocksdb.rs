@@ -0,0 +1,104 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! RocksDB state store
+//!
+//! Persist processor state in a local RocksDB database
+
+use arkflow_core::processor::{StateEntry, StateStore};
+use arkflow_core::Error;
+use rocksdb::{Direction, IteratorMode, Options, DB};
+
+/// State store backed by a RocksDB database
+pub struct RocksDbStateStore {
+    db: DB,
+}
+
+impl RocksDbStateStore {
+    /// Open the database at `path`, creating it if it does not exist
+    pub fn open(path: &str) -> Result<Self, Error> {
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        let db = DB::open(&options, path).map_err(|e| {
+            Error::Config(format!(
+                "Unable to open RocksDB state store {}: {}",
+                path, e
+            ))
+        })?;
+        Ok(Self { db })
+    }
+}
+
+impl StateStore for RocksDbStateStore {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        self.db
+            .get(key)
+            .map_err(|e| Error::Process(format!("RocksDB get error: {}", e)))
+    }
+
+    fn put(&self, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        self.db
+            .put(key, value)
+            .map_err(|e| Error::Process(format!("RocksDB put error: {}", e)))
+    }
+
+    fn delete(&self, key: &[u8]) -> Result<(), Error> {
+        self.db
+            .delete(key)
+            .map_err(|e| Error::Process(format!("RocksDB delete error: {}", e)))
+    }
+
+    fn scan_prefix(&self, prefix: &[u8]) -> Result<Vec<StateEntry>, Error> {
+        let mut result = Vec::new();
+        for item in self
+            .db
+            .iterator(IteratorMode::From(prefix, Direction::Forward))
+        {
+            let (key, value) =
+                item.map_err(|e| Error::Process(format!("RocksDB scan error: {}", e)))?;
+            if !key.starts_with(prefix) {
+                break;
+            }
+            result.push((key.to_vec(), value.to_vec()));
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rocksdb_state_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_string_lossy().to_string();
+        {
+            let store = RocksDbStateStore::open(&path).unwrap();
+            store.put(b"a/1", b"one").unwrap();
+            store.put(b"a/2", b"two").unwrap();
+            store.put(b"b/1", b"three").unwrap();
+            store.delete(b"a/2").unwrap();
+        }
+
+        // Reopen to make sure the state survives a restart
+        let store = RocksDbStateStore::open(&path).unwrap();
+        assert_eq!(store.get(b"a/1").unwrap(), Some(b"one".to_vec()));
+        assert_eq!(store.get(b"a/2").unwrap(), None);
+        assert_eq!(
+            store.scan_prefix(b"a/").unwrap(),
+            vec![(b"a/1".to_vec(), b"one".to_vec())]
+        );
+    }
+}
@@ -11,9 +11,27 @@
  *    See the License for the specific language governing permissions and
  *    limitations under the License.
  */
+
+//! Aggregate user-defined functions
+//!
+//! Aggregate UDFs registered with [`register`] are added to the SQL context of every component
+//! running queries. [`geometric_mean`] is an example of such a function:
+//!
+//! ```ignore
+//! use arkflow_plugin::udf::aggregate_udf;
+//!
+//! aggregate_udf::register(aggregate_udf::geometric_mean())?;
+//! // SELECT sensor, geometric_mean(value) FROM flow GROUP BY sensor
+//! ```
+
 use arkflow_core::Error;
+use datafusion::arrow::array::{ArrayRef, AsArray};
+use datafusion::arrow::compute::sum;
+use datafusion::arrow::datatypes::{DataType, Float64Type, UInt64Type};
+use datafusion::common::{exec_err, Result as DataFusionResult, ScalarValue};
 use datafusion::execution::FunctionRegistry;
-use datafusion::logical_expr::AggregateUDF;
+use datafusion::logical_expr::{create_udaf, AggregateUDF, Volatility};
+use datafusion::physical_plan::Accumulator;
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 use tracing::debug;
@@ -59,3 +77,155 @@ pub(crate) fn init<T: FunctionRegistry>(registry: &mut T) -> Result<(), Error> {
         })
         .map_err(|e| Error::Config(format!("Failed to register aggregate UDFs: {}", e)))
 }
+
+/// Create the `geometric_mean` aggregate UDF.
+///
+/// Returns the n-th root of the product of the n non-null values, computed as the exponential
+/// of the mean of their logarithms to avoid overflowing. Values must be positive.
+pub fn geometric_mean() -> AggregateUDF {
+    create_udaf(
+        "geometric_mean",
+        vec![DataType::Float64],
+        Arc::new(DataType::Float64),
+        Volatility::Immutable,
+        Arc::new(|_| Ok(Box::<GeometricMeanAccumulator>::default())),
+        Arc::new(vec![DataType::UInt64, DataType::Float64]),
+    )
+}
+
+/// Accumulator of `geometric_mean`: the number of values and the sum of their logarithms
+#[derive(Debug, Default)]
+struct GeometricMeanAccumulator {
+    count: u64,
+    log_sum: f64,
+}
+
+impl Accumulator for GeometricMeanAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> DataFusionResult<()> {
+        for value in values[0].as_primitive::<Float64Type>().iter().flatten() {
+            if value <= 0.0 {
+                return exec_err!(
+                    "geometric_mean is only defined for positive values, got {}",
+                    value
+                );
+            }
+            self.count += 1;
+            self.log_sum += value.ln();
+        }
+        Ok(())
+    }
+
+    fn evaluate(&mut self) -> DataFusionResult<ScalarValue> {
+        let mean = (self.count > 0).then(|| (self.log_sum / self.count as f64).exp());
+        Ok(ScalarValue::Float64(mean))
+    }
+
+    fn size(&self) -> usize {
+        size_of_val(self)
+    }
+
+    fn state(&mut self) -> DataFusionResult<Vec<ScalarValue>> {
+        Ok(vec![
+            ScalarValue::UInt64(Some(self.count)),
+            ScalarValue::Float64(Some(self.log_sum)),
+        ])
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> DataFusionResult<()> {
+        self.count += sum(states[0].as_primitive::<UInt64Type>()).unwrap_or_default();
+        self.log_sum += sum(states[1].as_primitive::<Float64Type>()).unwrap_or_default();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Float64Array, Int64Array, StringArray};
+    use datafusion::arrow::datatypes::{Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use datafusion::prelude::SessionContext;
+
+    fn context() -> SessionContext {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("sensor", DataType::Utf8, false),
+            Field::new("value", DataType::Int64, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["a", "a", "a", "b", "b"])),
+                Arc::new(Int64Array::from(vec![
+                    Some(2),
+                    Some(8),
+                    None,
+                    Some(-1),
+                    Some(4),
+                ])),
+            ],
+        )
+        .unwrap();
+        let ctx = SessionContext::new();
+        ctx.register_batch("flow", batch).unwrap();
+        ctx
+    }
+
+    #[tokio::test]
+    async fn test_geometric_mean() {
+        register(geometric_mean()).unwrap();
+        assert!(register(geometric_mean()).is_err());
+
+        let mut ctx = context();
+        init(&mut ctx).unwrap();
+        let batches = ctx
+            .sql("SELECT GEOMETRIC_MEAN(value) AS mean FROM flow WHERE sensor = 'a'")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let mean = batches[0].column(0).as_primitive::<Float64Type>();
+        assert!((mean.value(0) - 4.0).abs() < 1e-9);
+
+        let empty = ctx
+            .sql("SELECT geometric_mean(value) FROM flow WHERE sensor = 'c'")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        assert!(empty[0].column(0).is_null(0));
+
+        let err = ctx
+            .sql("SELECT geometric_mean(value) FROM flow WHERE sensor = 'b'")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("positive"), "{}", err);
+    }
+
+    #[test]
+    fn test_geometric_mean_merge() {
+        let mut left = GeometricMeanAccumulator::default();
+        left.update_batch(&[Arc::new(Float64Array::from(vec![1.0, 3.0]))])
+            .unwrap();
+        let mut right = GeometricMeanAccumulator::default();
+        right
+            .update_batch(&[Arc::new(Float64Array::from(vec![9.0]))])
+            .unwrap();
+
+        let state = right
+            .state()
+            .unwrap()
+            .into_iter()
+            .map(|value| value.to_array().unwrap())
+            .collect::<Vec<_>>();
+        left.merge_batch(&state).unwrap();
+        let ScalarValue::Float64(Some(mean)) = left.evaluate().unwrap() else {
+            panic!("expected a value");
+        };
+        assert!((mean - 3.0).abs() < 1e-9);
+    }
+}
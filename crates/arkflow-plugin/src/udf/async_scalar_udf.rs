@@ -0,0 +1,251 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Scalar UDFs backed by async functions
+//!
+//! DataFusion evaluates scalar UDFs synchronously, while some transformations need async work
+//! such as calling an HTTP API. An async UDF evaluates its function for every row of a batch on the
+//! current Tokio runtime and blocks the calling thread until all rows are done.
+//!
+//! Queries run on the Tokio worker threads, where `Handle::block_on` would panic. The blocking
+//! wait is wrapped in `tokio::task::block_in_place`, which moves the other tasks of the worker to
+//! another thread first. This requires the multi-threaded runtime, on a current-thread runtime the
+//! UDF returns an error instead. Each blocked batch occupies a worker thread, so slow functions
+//! reduce the parallelism of the whole stream.
+
+use crate::udf::scalar_udf;
+use arkflow_core::Error;
+use datafusion::arrow::array::new_empty_array;
+use datafusion::arrow::datatypes::DataType;
+use datafusion::common::{DataFusionError, Result, ScalarValue};
+use datafusion::logical_expr::{
+    ColumnarValue, ScalarFunctionArgs, ScalarUDF, ScalarUDFImpl, Signature, Volatility,
+};
+use futures::future::BoxFuture;
+use futures::{StreamExt, TryStreamExt};
+use std::any::Any;
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::sync::Arc;
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+/// Maximum number of rows of a batch evaluated concurrently
+const MAX_CONCURRENT_CALLS: usize = 16;
+
+type AsyncFn = dyn Fn(Vec<ScalarValue>) -> BoxFuture<'static, Result<ScalarValue>> + Send + Sync;
+
+/// Scalar UDF calling an async function with the argument values of each row
+pub struct AsyncScalarUdf {
+    name: String,
+    signature: Signature,
+    return_type: DataType,
+    func: Arc<AsyncFn>,
+}
+
+impl AsyncScalarUdf {
+    /// Create an async UDF accepting any arguments and returning values of `return_type`
+    pub fn new<F, Fut>(name: &str, return_type: DataType, func: F) -> Self
+    where
+        F: Fn(Vec<ScalarValue>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<ScalarValue>> + Send + 'static,
+    {
+        Self {
+            name: name.to_string(),
+            signature: Signature::variadic_any(Volatility::Volatile),
+            return_type,
+            func: Arc::new(move |args| Box::pin(func(args))),
+        }
+    }
+
+    /// Evaluate the function for every row, keeping the order of the rows
+    async fn evaluate(&self, rows: Vec<Vec<ScalarValue>>) -> Result<Vec<ScalarValue>> {
+        futures::stream::iter(rows)
+            .map(|row| (self.func)(row))
+            .buffered(MAX_CONCURRENT_CALLS)
+            .try_collect()
+            .await
+    }
+}
+
+impl Debug for AsyncScalarUdf {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AsyncScalarUdf")
+            .field("name", &self.name)
+            .field("return_type", &self.return_type)
+            .finish()
+    }
+}
+
+impl ScalarUDFImpl for AsyncScalarUdf {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(self.return_type.clone())
+    }
+
+    // The UDF API returns DataFusion errors
+    #[allow(clippy::result_large_err)]
+    fn invoke_with_args(&self, args: ScalarFunctionArgs) -> Result<ColumnarValue> {
+        if args.number_rows == 0 {
+            return Ok(ColumnarValue::Array(new_empty_array(&self.return_type)));
+        }
+
+        let rows = (0..args.number_rows)
+            .map(|row| {
+                args.args
+                    .iter()
+                    .map(|arg| match arg {
+                        ColumnarValue::Array(array) => ScalarValue::try_from_array(array, row),
+                        ColumnarValue::Scalar(scalar) => Ok(scalar.clone()),
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let handle = Handle::try_current().map_err(|e| {
+            DataFusionError::Execution(format!("{} requires a Tokio runtime: {}", self.name, e))
+        })?;
+        if handle.runtime_flavor() == RuntimeFlavor::CurrentThread {
+            return Err(DataFusionError::Execution(format!(
+                "{} requires the multi-threaded Tokio runtime",
+                self.name
+            )));
+        }
+        let values = tokio::task::block_in_place(|| handle.block_on(self.evaluate(rows)))?;
+
+        // Untyped nulls cannot be mixed with the values of the return type
+        let null = ScalarValue::try_from(&self.return_type)?;
+        let values = values
+            .into_iter()
+            .map(|value| if value.is_null() { null.clone() } else { value });
+        Ok(ColumnarValue::Array(ScalarValue::iter_to_array(values)?))
+    }
+}
+
+/// Register a scalar UDF backed by an async function.
+///
+/// The function is called with the argument values of each row and must return a value of
+/// `return_type` or null. See the module documentation for the threading implications.
+pub fn register_async<F, Fut>(name: &str, return_type: DataType, func: F) -> Result<(), Error>
+where
+    F: Fn(Vec<ScalarValue>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<ScalarValue>> + Send + 'static,
+{
+    scalar_udf::register(ScalarUDF::new_from_impl(AsyncScalarUdf::new(
+        name,
+        return_type,
+        func,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::Path;
+    use axum::routing::get;
+    use axum::Router;
+    use datafusion::arrow::array::StringArray;
+    use datafusion::prelude::SessionContext;
+
+    /// Start an HTTP API returning the name of a user
+    async fn start_user_api() -> String {
+        let app = Router::new().route(
+            "/users/:id",
+            get(|Path(id): Path<i64>| async move { format!("user-{}", id) }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        format!("http://{}", addr)
+    }
+
+    /// Look up the name of a user with one HTTP request per row
+    fn user_name_udf(base_url: String) -> AsyncScalarUdf {
+        let client = reqwest::Client::new();
+        AsyncScalarUdf::new("user_name", DataType::Utf8, move |args| {
+            let client = client.clone();
+            let base_url = base_url.clone();
+            async move {
+                let ScalarValue::Int64(Some(id)) = args[0] else {
+                    return Ok(ScalarValue::Utf8(None));
+                };
+                let name = client
+                    .get(format!("{}/users/{}", base_url, id))
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .map_err(|e| DataFusionError::External(Box::new(e)))?
+                    .text()
+                    .await
+                    .map_err(|e| DataFusionError::External(Box::new(e)))?;
+                Ok(ScalarValue::Utf8(Some(name)))
+            }
+        })
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_async_udf_calls_http_api() {
+        let ctx = SessionContext::new();
+        ctx.register_udf(ScalarUDF::new_from_impl(user_name_udf(
+            start_user_api().await,
+        )));
+
+        let batches = ctx
+            .sql("SELECT user_name(id) AS name FROM (VALUES (3), (NULL), (1)) AS t(id)")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let names = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(
+            names.iter().collect::<Vec<_>>(),
+            vec![Some("user-3"), None, Some("user-1")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_async_udf_requires_multi_thread_runtime() {
+        let ctx = SessionContext::new();
+        ctx.register_udf(ScalarUDF::new_from_impl(AsyncScalarUdf::new(
+            "constant",
+            DataType::Int64,
+            |_| async { Ok(ScalarValue::Int64(Some(1))) },
+        )));
+
+        let result = ctx
+            .sql("SELECT constant(id) FROM (VALUES (1)) AS t(id)")
+            .await
+            .unwrap()
+            .collect()
+            .await;
+        assert!(result.is_err());
+    }
+}
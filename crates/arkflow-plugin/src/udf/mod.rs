@@ -20,7 +20,9 @@ use arkflow_core::Error;
 use datafusion::execution::FunctionRegistry;
 
 pub mod aggregate_udf;
+pub mod async_scalar_udf;
 pub mod scalar_udf;
+pub mod table_udf;
 pub mod window_udf;
 
 /// Initializes and registers all user-defined functions (UDFs).
@@ -0,0 +1,152 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! User-defined table functions (UDTFs)
+//!
+//! A table function is called in the `FROM` clause of a query, e.g.
+//! `SELECT * FROM my_function(1, 'a')`, and returns a table built from its arguments.
+//! DataFusion registers `generate_series` and `range` itself.
+
+use arkflow_core::Error;
+use datafusion::catalog::{TableFunctionImpl, TableProvider};
+use datafusion::common::Result;
+use datafusion::logical_expr::Expr;
+use datafusion::prelude::SessionContext;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, RwLock};
+use tracing::debug;
+
+lazy_static::lazy_static! {
+    static ref UDTFS: RwLock<HashMap<String, Arc<dyn TableFunction>>> = RwLock::new(HashMap::new());
+}
+
+/// Table function, implemented by every DataFusion `TableFunctionImpl`
+pub trait TableFunction: Debug + Send + Sync {
+    /// Table of the function for the arguments of a call
+    #[allow(clippy::result_large_err)]
+    fn call(&self, args: &[Expr]) -> Result<Arc<dyn TableProvider>>;
+}
+
+impl<T: TableFunctionImpl> TableFunction for T {
+    fn call(&self, args: &[Expr]) -> Result<Arc<dyn TableProvider>> {
+        TableFunctionImpl::call(self, args)
+    }
+}
+
+/// Registers a [`TableFunction`] with DataFusion
+#[derive(Debug)]
+struct TableFunctionAdapter(Arc<dyn TableFunction>);
+
+impl TableFunctionImpl for TableFunctionAdapter {
+    fn call(&self, args: &[Expr]) -> Result<Arc<dyn TableProvider>> {
+        self.0.call(args)
+    }
+}
+
+/// Register a new table UDF.
+///
+/// This function adds a UDTF to the global registry. The UDTF will be available for use
+/// in SQL queries after the next call to `init`.
+///
+/// # Arguments
+///
+/// * `name` - The name the function is called by in SQL queries.
+/// * `func` - The table function.
+pub fn register_table_udf(name: &str, func: Arc<dyn TableFunction>) -> Result<(), Error> {
+    let mut udtfs = UDTFS
+        .write()
+        .map_err(|_| Error::Config("Failed to acquire write lock for table UDFS".to_string()))?;
+    if udtfs.contains_key(name) {
+        return Err(Error::Config(format!(
+            "Table UDF with name '{}' already registered",
+            name
+        )));
+    };
+    udtfs.insert(name.to_string(), func);
+    Ok(())
+}
+
+/// Register the table UDFs with `ctx`. Table functions are not part of DataFusion's
+/// `FunctionRegistry`, so they are registered with the session context directly.
+pub(crate) fn init(ctx: &SessionContext) -> Result<(), Error> {
+    let table_udfs = UDTFS
+        .read()
+        .expect("Failed to acquire read lock for table UDFS");
+    for (name, func) in table_udfs.iter() {
+        if ctx.table_function(name).is_ok() {
+            debug!("Overwrite existing table UDF: {}", name);
+        }
+        ctx.register_udtf(name, Arc::new(TableFunctionAdapter(Arc::clone(func))));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Array, ArrayRef, Int64Array};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use datafusion::common::{plan_err, ScalarValue};
+    use datafusion::datasource::MemTable;
+
+    /// `repeat(value, count)`: a table of `count` rows holding `value`
+    #[derive(Debug)]
+    struct Repeat;
+
+    impl TableFunctionImpl for Repeat {
+        fn call(&self, args: &[Expr]) -> Result<Arc<dyn TableProvider>> {
+            let literals: Vec<i64> = args
+                .iter()
+                .filter_map(|arg| match arg {
+                    Expr::Literal(ScalarValue::Int64(Some(n))) => Some(*n),
+                    _ => None,
+                })
+                .collect();
+            let [value, count] = literals[..] else {
+                return plan_err!("repeat requires two integer literals");
+            };
+            let values = Int64Array::from(vec![value; count.max(0) as usize]);
+            let batch = RecordBatch::try_from_iter([("value", Arc::new(values) as ArrayRef)])?;
+            Ok(Arc::new(MemTable::try_new(
+                batch.schema(),
+                vec![vec![batch]],
+            )?))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_table_udf() {
+        register_table_udf("test_repeat", Arc::new(Repeat)).unwrap();
+        assert!(register_table_udf("test_repeat", Arc::new(Repeat)).is_err());
+
+        let ctx = SessionContext::new();
+        init(&ctx).unwrap();
+        let batches = ctx
+            .sql("SELECT sum(value) AS total FROM test_repeat(7, 3)")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let total = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(total.value(0), 21);
+
+        assert!(ctx.sql("SELECT * FROM test_repeat('a', 3)").await.is_err());
+    }
+}
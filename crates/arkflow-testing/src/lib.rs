@@ -0,0 +1,151 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+//! Testing harness for ArkFlow pipelines
+//!
+//! Run processors end-to-end through a [`Stream`] without wiring up real inputs and outputs.
+//! Add this crate as a dev-dependency only.
+
+use arkflow_core::input::{Ack, Input, NoopAck};
+use arkflow_core::output::Output;
+use arkflow_core::pipeline::Pipeline;
+use arkflow_core::processor::Processor;
+use arkflow_core::stream::Stream;
+use arkflow_core::{Error, MessageBatch, Resource};
+use async_trait::async_trait;
+use flume::{Receiver, Sender};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+/// Input replaying a fixed list of batches, then returning [`Error::EOF`]
+pub struct TestInput {
+    batches: Mutex<VecDeque<MessageBatch>>,
+}
+
+impl TestInput {
+    pub fn from_batches(batches: Vec<MessageBatch>) -> Arc<dyn Input> {
+        Arc::new(Self {
+            batches: Mutex::new(batches.into()),
+        })
+    }
+}
+
+#[async_trait]
+impl Input for TestInput {
+    async fn connect(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn read(&self) -> Result<(MessageBatch, Arc<dyn Ack>), Error> {
+        match self.batches.lock().await.pop_front() {
+            Some(batch) => Ok((batch, Arc::new(NoopAck))),
+            None => Err(Error::EOF),
+        }
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Output sending every written batch to a channel
+pub struct CollectingOutput {
+    sender: Sender<MessageBatch>,
+}
+
+impl CollectingOutput {
+    /// Create the output and the receiving end of its channel
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new() -> (Arc<dyn Output>, Receiver<MessageBatch>) {
+        let (sender, receiver) = flume::unbounded();
+        (Arc::new(Self { sender }), receiver)
+    }
+}
+
+#[async_trait]
+impl Output for CollectingOutput {
+    async fn connect(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn write(&self, msg: MessageBatch) -> Result<(), Error> {
+        self.sender
+            .send(msg)
+            .map_err(|_| Error::Process("Collecting output receiver dropped".to_string()))
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Output forwarding every batch to an inner output and recording it
+struct TeeOutput {
+    inner: Arc<dyn Output>,
+    sender: Sender<MessageBatch>,
+}
+
+#[async_trait]
+impl Output for TeeOutput {
+    async fn connect(&self) -> Result<(), Error> {
+        self.inner.connect().await
+    }
+
+    async fn write(&self, msg: MessageBatch) -> Result<(), Error> {
+        self.inner.write(msg.clone()).await?;
+        // The receiver lives until the stream has finished
+        let _ = self.sender.send(msg);
+        Ok(())
+    }
+
+    async fn close(&self) -> Result<(), Error> {
+        self.inner.close().await
+    }
+}
+
+/// Runs processors as a single-worker stream
+pub struct TestPipeline;
+
+impl TestPipeline {
+    /// Run `input` through `processors` into `output` until the input is exhausted.
+    ///
+    /// Returns the batches written to `output`, in order.
+    pub async fn run(
+        input: Arc<dyn Input>,
+        processors: Vec<Arc<dyn Processor>>,
+        output: Arc<dyn Output>,
+    ) -> Result<Vec<MessageBatch>, Error> {
+        let (sender, receiver) = flume::unbounded();
+        let output = Arc::new(TeeOutput {
+            inner: output,
+            sender,
+        });
+        let resource = Resource::default();
+
+        let mut stream = Stream::new(
+            input,
+            Pipeline::new(processors),
+            output,
+            None,
+            None,
+            resource,
+            1,
+        );
+        stream.run(CancellationToken::new()).await?;
+
+        Ok(receiver.drain().collect())
+    }
+}
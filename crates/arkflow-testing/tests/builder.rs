@@ -0,0 +1,117 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use arkflow_core::input::InputConfig;
+use arkflow_core::output::OutputConfig;
+use arkflow_core::pipeline::PipelineConfig;
+use arkflow_core::stream::Stream;
+use arkflow_core::Resource;
+use arkflow_testing::CollectingOutput;
+use datafusion::arrow::array::{AsArray, StringArray};
+use datafusion::arrow::datatypes::Int64Type;
+use serde::Serialize;
+use std::sync::Once;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Serialize)]
+struct MemoryInputConfig {
+    messages: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct SqlProcessorConfig {
+    query: String,
+}
+
+fn init() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        arkflow_plugin::input::init().unwrap();
+        arkflow_plugin::processor::init().unwrap();
+        arkflow_plugin::output::init().unwrap();
+    });
+}
+
+fn resource() -> Resource {
+    Resource {
+        temporary: Default::default(),
+        input_names: Default::default(),
+    }
+}
+
+#[tokio::test]
+async fn test_build_and_run_pipeline() {
+    init();
+    let input = InputConfig::from_typed(
+        &MemoryInputConfig {
+            messages: vec![
+                r#"{"name": "a", "value": 1}"#.to_string(),
+                r#"{"name": "b", "value": 2}"#.to_string(),
+            ],
+        },
+        "memory",
+    )
+    .unwrap()
+    .build(&resource())
+    .unwrap();
+    let (pipeline, thread_num) = PipelineConfig::builder()
+        .with_processor(&(), "json_to_arrow")
+        .unwrap()
+        .with_processor(
+            &SqlProcessorConfig {
+                query: "SELECT name, value * 10 AS value FROM flow".to_string(),
+            },
+            "sql",
+        )
+        .unwrap()
+        .with_thread_num(1)
+        .build()
+        .unwrap();
+    assert_eq!(thread_num, 1);
+    let (output, receiver) = CollectingOutput::new();
+
+    let mut stream = Stream::new(input, pipeline, output, None, None, resource(), thread_num);
+    stream.run(CancellationToken::new()).await.unwrap();
+
+    let rows: Vec<(String, i64)> = receiver
+        .drain()
+        .flat_map(|batch| {
+            let names = batch
+                .column_by_name("name")
+                .unwrap()
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap()
+                .clone();
+            let values = batch
+                .column_by_name("value")
+                .unwrap()
+                .as_primitive::<Int64Type>()
+                .clone();
+            (0..batch.num_rows())
+                .map(|i| (names.value(i).to_string(), values.value(i)))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    assert_eq!(rows, vec![("a".to_string(), 10), ("b".to_string(), 20)]);
+}
+
+#[test]
+fn test_typed_output_config() {
+    init();
+    let output = OutputConfig::from_typed(&(), "drop").unwrap();
+    assert_eq!(output.output_type, "drop");
+    assert_eq!(output.config, Some(serde_json::json!({})));
+    assert!(output.build(&resource()).is_ok());
+}
@@ -0,0 +1,99 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use arkflow_core::output::OutputFormat;
+use arkflow_core::MessageBatch;
+use datafusion::arrow::array::{Int64Array, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use std::sync::Arc;
+
+#[test]
+fn test_encode_formats() {
+    let value = serde_json::json!({"a": 1});
+    assert_eq!(OutputFormat::Json.encode(&value).unwrap(), br#"{"a":1}"#);
+    assert_eq!(
+        OutputFormat::MessagePack.encode(&value).unwrap(),
+        [0x81, 0xa1, b'a', 0x01]
+    );
+    assert_eq!(
+        OutputFormat::Cbor.encode(&value).unwrap(),
+        [0xa1, 0x61, b'a', 0x01]
+    );
+    assert_eq!(
+        OutputFormat::Bson.encode(&value).unwrap(),
+        [0x0c, 0, 0, 0, 0x10, b'a', 0, 0x01, 0, 0, 0, 0]
+    );
+    // BSON documents cannot hold other values
+    assert!(OutputFormat::Bson
+        .encode(&serde_json::json!([1, 2]))
+        .is_err());
+}
+
+#[test]
+fn test_encode_message_pack_values() {
+    let value = serde_json::json!([null, true, -1, -200, 300, 1.5, "x"]);
+    let mut expected = vec![
+        0x97, 0xc0, 0xc3, 0xff, 0xd1, 0xff, 0x38, 0xcd, 0x01, 0x2c, 0xcb,
+    ];
+    expected.extend_from_slice(&1.5f64.to_be_bytes());
+    expected.extend_from_slice(&[0xa1, b'x']);
+    assert_eq!(OutputFormat::MessagePack.encode(&value).unwrap(), expected);
+}
+
+#[test]
+fn test_serialize_batches() {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+    let batch = MessageBatch::new_arrow(
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec!["a", "b"])),
+            ],
+        )
+        .unwrap(),
+    );
+    let payloads = batch.serialize_to(OutputFormat::Json).unwrap();
+    assert_eq!(
+        payloads,
+        vec![
+            br#"{"id":1,"name":"a"}"#.to_vec(),
+            br#"{"id":2,"name":"b"}"#.to_vec()
+        ]
+    );
+    let payloads = batch.serialize_to(OutputFormat::MessagePack).unwrap();
+    assert_eq!(payloads.len(), 2);
+    assert_eq!(payloads[0][0], 0x82);
+
+    // Binary messages are parsed as JSON and re-encoded
+    let binary = MessageBatch::new_binary(vec![br#"{"a":1}"#.to_vec()]).unwrap();
+    assert_eq!(
+        binary.serialize_to(OutputFormat::Cbor).unwrap(),
+        vec![vec![0xa1, 0x61, b'a', 0x01]]
+    );
+    let invalid = MessageBatch::new_binary(vec![b"not json".to_vec()]).unwrap();
+    assert!(invalid.serialize_to(OutputFormat::Cbor).is_err());
+}
+
+#[test]
+fn test_output_format_config() {
+    let format: OutputFormat = serde_json::from_value(serde_json::json!("msgpack")).unwrap();
+    assert_eq!(format, OutputFormat::MessagePack);
+    let format: OutputFormat = serde_json::from_value(serde_json::json!("message_pack")).unwrap();
+    assert_eq!(format.content_type(), "application/msgpack");
+}
@@ -0,0 +1,115 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use arkflow_core::processor::{Processor, ProcessorConfig};
+use arkflow_core::{MessageBatch, Resource};
+use arkflow_testing::{CollectingOutput, TestInput, TestPipeline};
+use datafusion::arrow::array::{Int64Array, StringArray};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::DataType;
+use std::sync::{Arc, Once};
+
+fn build_processor(config: serde_json::Value) -> Arc<dyn Processor> {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| arkflow_plugin::processor::init().unwrap());
+
+    let config: ProcessorConfig = serde_json::from_value(config).unwrap();
+    config.build(&Resource::default()).unwrap()
+}
+
+fn json_batches(messages: &[&str]) -> Vec<MessageBatch> {
+    messages
+        .iter()
+        .map(|message| MessageBatch::from_string(message).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_sql_processor() {
+    let input = TestInput::from_batches(json_batches(&[
+        r#"{"name": "a", "value": 1}"#,
+        r#"{"name": "b", "value": 2}"#,
+    ]));
+    let processors = vec![
+        build_processor(serde_json::json!({"type": "json_to_arrow"})),
+        build_processor(serde_json::json!({
+            "type": "sql",
+            "query": "SELECT name, value * 10 AS value FROM flow",
+        })),
+    ];
+    let (output, receiver) = CollectingOutput::new();
+
+    let batches = TestPipeline::run(input, processors, output).await.unwrap();
+
+    assert_eq!(batches.len(), 2);
+    let values: Vec<i64> = batches
+        .iter()
+        .map(|batch| {
+            let column = batch.column_by_name("value").unwrap();
+            column
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .unwrap()
+                .value(0)
+        })
+        .collect();
+    assert_eq!(values, vec![10, 20]);
+    // The collecting output saw the same batches
+    assert_eq!(receiver.drain().count(), 2);
+}
+
+#[tokio::test]
+async fn test_sql_processor_where_clause() {
+    let input = TestInput::from_batches(json_batches(&[
+        r#"{"level": "info", "message": "started"}"#,
+        r#"{"level": "error", "message": "failed"}"#,
+        r#"{"level": "debug", "message": "tick"}"#,
+    ]));
+    let processors = vec![
+        build_processor(serde_json::json!({"type": "json_to_arrow"})),
+        build_processor(serde_json::json!({
+            "type": "sql",
+            "query": "SELECT message FROM flow WHERE level = 'error'",
+        })),
+    ];
+    let (output, _receiver) = CollectingOutput::new();
+
+    let batches = TestPipeline::run(input, processors, output).await.unwrap();
+
+    // Batches without a match come out empty
+    let messages: Vec<String> = batches
+        .iter()
+        .filter(|batch| batch.num_rows() > 0)
+        .flat_map(|batch| {
+            // DataFusion may return string view columns
+            let column = cast(batch.column_by_name("message").unwrap(), &DataType::Utf8).unwrap();
+            let column = column.as_any().downcast_ref::<StringArray>().unwrap();
+            column
+                .iter()
+                .flatten()
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    assert_eq!(messages, vec!["failed".to_string()]);
+}
+
+#[tokio::test]
+async fn test_empty_input() {
+    let (output, _receiver) = CollectingOutput::new();
+    let batches = TestPipeline::run(TestInput::from_batches(vec![]), vec![], output)
+        .await
+        .unwrap();
+    assert!(batches.is_empty());
+}
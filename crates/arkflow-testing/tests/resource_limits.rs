@@ -0,0 +1,55 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use arkflow_core::pipeline::Pipeline;
+use arkflow_core::stream::{ResourceLimits, Stream};
+use arkflow_core::{MessageBatch, Resource, DEFAULT_BINARY_VALUE_FIELD};
+use arkflow_testing::{CollectingOutput, TestInput};
+use tokio_util::sync::CancellationToken;
+
+#[tokio::test]
+async fn test_stream_runs_with_resource_limits() {
+    let batches = (0..20)
+        .map(|i| MessageBatch::from_string(&format!("message {}", i)).unwrap())
+        .collect();
+    let (output, receiver) = CollectingOutput::new();
+    let mut stream = Stream::new(
+        TestInput::from_batches(batches),
+        Pipeline::new(vec![]),
+        output,
+        None,
+        None,
+        Resource {
+            temporary: Default::default(),
+            input_names: Default::default(),
+        },
+        4,
+    );
+    // Far above what the test uses, so applying it does not affect the test process
+    stream.set_resource_limits(ResourceLimits {
+        max_memory_bytes: Some(1 << 44),
+        max_cpu_percent: None,
+    });
+    stream.run(CancellationToken::new()).await.unwrap();
+
+    let written: Vec<String> = receiver
+        .drain()
+        .map(|msg| {
+            String::from_utf8(msg.to_binary(DEFAULT_BINARY_VALUE_FIELD).unwrap()[0].to_vec())
+                .unwrap()
+        })
+        .collect();
+    let expected: Vec<String> = (0..20).map(|i| format!("message {}", i)).collect();
+    assert_eq!(written, expected);
+}
@@ -0,0 +1,52 @@
+/*
+ *    Licensed under the Apache License, Version 2.0 (the "License");
+ *    you may not use this file except in compliance with the License.
+ *    You may obtain a copy of the License at
+ *
+ *        http://www.apache.org/licenses/LICENSE-2.0
+ *
+ *    Unless required by applicable law or agreed to in writing, software
+ *    distributed under the License is distributed on an "AS IS" BASIS,
+ *    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ *    See the License for the specific language governing permissions and
+ *    limitations under the License.
+ */
+
+use arkflow_core::pipeline::Pipeline;
+use arkflow_core::processor::{Processor, ProcessorConfig};
+use arkflow_core::trace::SpanContext;
+use arkflow_core::{MessageBatch, Resource};
+use std::sync::Arc;
+
+const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+fn build_processor(config: serde_json::Value) -> Arc<dyn Processor> {
+    arkflow_plugin::processor::init().ok();
+
+    let config: ProcessorConfig = serde_json::from_value(config).unwrap();
+    let resource = Resource {
+        temporary: Default::default(),
+        input_names: Default::default(),
+    };
+    config.build(&resource).unwrap()
+}
+
+#[tokio::test]
+async fn test_pipeline_keeps_sampling_decision() {
+    let pipeline = Pipeline::new(vec![
+        build_processor(serde_json::json!({
+            "type": "trace_sample",
+            "sampler_type": "always_off",
+        })),
+        build_processor(serde_json::json!({"type": "json_to_arrow"})),
+    ]);
+
+    let parent = SpanContext::from_traceparent(TRACEPARENT).unwrap();
+    let mut msg = MessageBatch::from_string(r#"{"value": 1}"#).unwrap();
+    msg.inject_span_context(&parent);
+    let results = pipeline.process(msg).await.unwrap();
+
+    let context = results[0].extract_span_context().unwrap();
+    assert_eq!(context.trace_id, parent.trace_id);
+    assert!(!context.sampled);
+}
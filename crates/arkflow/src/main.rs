@@ -25,5 +25,6 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     codec::init()?;
     let mut cli = Cli::default();
     cli.parse()?;
+    cli.init_logging()?;
     cli.run().await
 }